@@ -0,0 +1,352 @@
+use crate::parser::{canonicalize, get_at_mut, get_by_path, parse_steps};
+use crate::path::Expression;
+use anyhow::{Error, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Removes repeated elements from the array at a path, keeping the first of each.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the array, changed in place.
+/// * `path` - The path of the array in `get_by_path` syntax, e.g. `data.items`, or an
+///   empty string for the document itself.
+/// * `key` - An expression identifying an element (see `Expression`), e.g. `.id`; whole
+///   elements are compared if `None`. Elements for which the key is undefined are kept.
+///
+/// # Returns
+///
+/// * `Result<usize, Error>` - The number of elements removed, or an error if the key is
+///   not a valid expression or the path does not lead to an array.
+pub fn dedupe(json: &mut Value, path: &str, key: Option<&str>) -> Result<usize, Error> {
+    let mut interner = Interner::new(key)?;
+    let items = array_at_mut(json, path)?;
+    let before = items.len();
+    let mut seen = HashSet::new();
+    items.retain(|item| match interner.intern(item) {
+        Some(id) => seen.insert(id),
+        None => true,
+    });
+    Ok(before - items.len())
+}
+
+/// Keeps a range of the array at a path.
+///
+/// Indexes count from the end when negative and are clamped to the array, as in
+/// Python: `start = -3` with no `end` keeps the last three elements.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the array, changed in place.
+/// * `path` - The path of the array, or an empty string for the document itself.
+/// * `start` - The first index kept.
+/// * `end` - The index after the last one kept; the end of the array if `None`.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the path does not lead to an array.
+pub fn slice(json: &mut Value, path: &str, start: i64, end: Option<i64>) -> Result<(), Error> {
+    let items = array_at_mut(json, path)?;
+    let len = items.len() as i64;
+    let clamp = |index: i64| {
+        if index < 0 {
+            (len + index).max(0)
+        } else {
+            index.min(len)
+        }
+    };
+    let start = clamp(start) as usize;
+    let end = clamp(end.unwrap_or(len)) as usize;
+    if start >= end {
+        items.clear();
+    } else {
+        items.truncate(end);
+        items.drain(..start);
+    }
+    Ok(())
+}
+
+/// Splits the array at a path into arrays of `size` elements, e.g. to send records in
+/// batches; the last chunk may be shorter.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the array, changed in place.
+/// * `path` - The path of the array, or an empty string for the document itself.
+/// * `size` - The number of elements per chunk.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if `size` is zero or the path does not lead to an array.
+pub fn chunk(json: &mut Value, path: &str, size: usize) -> Result<(), Error> {
+    if size == 0 {
+        return Err(Error::msg("Chunk size must be positive"));
+    }
+    let items = array_at_mut(json, path)?;
+    let chunks: Vec<Value> = items
+        .chunks(size)
+        .map(|chunk| Value::Array(chunk.to_vec()))
+        .collect();
+    *items = chunks;
+    Ok(())
+}
+
+/// Appends the elements of other arrays to the array at a path.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the array, changed in place.
+/// * `path` - The path of the array, or an empty string for the document itself.
+/// * `others` - The arrays whose elements are appended, in order.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the path does not lead to an array or one of
+///   `others` is not an array.
+pub fn concat(json: &mut Value, path: &str, others: &[Value]) -> Result<(), Error> {
+    let items = array_at_mut(json, path)?;
+    for (index, other) in others.iter().enumerate() {
+        let other = other.as_array().ok_or_else(|| {
+            Error::msg(format!(
+                "Value {} to concatenate is not an array",
+                index + 1
+            ))
+        })?;
+        items.extend(other.iter().cloned());
+    }
+    Ok(())
+}
+
+/// Extends the array at a path with the elements of other arrays it does not hold yet,
+/// e.g. to merge ID lists exported from two systems. Each element is kept once, at its
+/// first occurrence, comparing elements as `dedupe` does.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the array, changed in place.
+/// * `path` - The path of the array, or an empty string for the document itself.
+/// * `others` - The arrays whose new elements are appended, in order.
+/// * `key` - An expression identifying an element, e.g. `.id`; whole elements are
+///   compared if `None`. Elements for which the key is undefined match nothing and are
+///   all kept.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the key is not a valid expression, the path does
+///   not lead to an array or one of `others` is not an array.
+pub fn union(
+    json: &mut Value,
+    path: &str,
+    others: &[Value],
+    key: Option<&str>,
+) -> Result<(), Error> {
+    let mut interner = Interner::new(key)?;
+    let others = other_arrays(others)?;
+    let items = array_at_mut(json, path)?;
+    let mut seen = HashSet::new();
+    let mut merged = Vec::with_capacity(items.len());
+    for item in items.drain(..).chain(others.into_iter().flatten().cloned()) {
+        if interner.intern(&item).is_none_or(|id| seen.insert(id)) {
+            merged.push(item);
+        }
+    }
+    *items = merged;
+    Ok(())
+}
+
+/// Keeps the elements of the array at a path that every other array also holds, e.g.
+/// the IDs known to both systems. Each element is kept once, at its first occurrence.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the array, changed in place.
+/// * `path` - The path of the array, or an empty string for the document itself.
+/// * `others` - The arrays an element must be found in.
+/// * `key` - An expression identifying an element, e.g. `.id`; whole elements are
+///   compared if `None`. Elements for which the key is undefined match nothing and are
+///   removed.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the key is not a valid expression, the path does
+///   not lead to an array or one of `others` is not an array.
+pub fn intersection(
+    json: &mut Value,
+    path: &str,
+    others: &[Value],
+    key: Option<&str>,
+) -> Result<(), Error> {
+    keep_by_membership(
+        json,
+        path,
+        others,
+        key,
+        |found, total| found == total,
+        false,
+    )
+}
+
+/// Keeps the elements of the array at a path that none of the other arrays holds, e.g.
+/// the IDs missing from the second system. Each element is kept once, at its first
+/// occurrence.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the array, changed in place.
+/// * `path` - The path of the array, or an empty string for the document itself.
+/// * `others` - The arrays whose elements are removed.
+/// * `key` - An expression identifying an element, e.g. `.id`; whole elements are
+///   compared if `None`. Elements for which the key is undefined match nothing and are
+///   all kept.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the key is not a valid expression, the path does
+///   not lead to an array or one of `others` is not an array.
+pub fn difference(
+    json: &mut Value,
+    path: &str,
+    others: &[Value],
+    key: Option<&str>,
+) -> Result<(), Error> {
+    keep_by_membership(json, path, others, key, |found, _| found == 0, true)
+}
+
+/// Combines the arrays at several paths element by element: `zip(&json, &["names",
+/// "ages"])` turns `["ann", "bob"]` and `[31, 25]` into `[["ann", 31], ["bob", 25]]`.
+/// The result is as long as the shortest array.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the arrays.
+/// * `paths` - The paths of the arrays, in `get_by_path` syntax.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The array of tuples, or an error if a path does not lead
+///   to an array.
+pub fn zip(json: &Value, paths: &[&str]) -> Result<Value, Error> {
+    let arrays = paths
+        .iter()
+        .map(|path| match value_at(json, path)? {
+            Value::Array(items) => Ok(items),
+            _ => Err(Error::msg(format!("No array at path: {}", path))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let len = arrays.iter().map(Vec::len).min().unwrap_or(0);
+    Ok(Value::Array(
+        (0..len)
+            .map(|index| Value::Array(arrays.iter().map(|items| items[index].clone()).collect()))
+            .collect(),
+    ))
+}
+
+/// Gives each distinct element identity a number, so that an element is canonicalized
+/// once and then compared with the others by number.
+struct Interner {
+    key: Option<Expression>,
+    ids: HashMap<String, usize>,
+}
+
+impl Interner {
+    /// Creates an interner identifying elements by a key expression, or as a whole.
+    fn new(key: Option<&str>) -> Result<Self, Error> {
+        Ok(Interner {
+            key: key.map(compile_key).transpose()?,
+            ids: HashMap::new(),
+        })
+    }
+
+    /// Returns the number of an element's identity, or `None` if its key is undefined.
+    fn intern(&mut self, item: &Value) -> Option<usize> {
+        let keyed;
+        let identity = match &self.key {
+            Some(key) => {
+                keyed = key.evaluate(item)?;
+                &keyed
+            }
+            None => item,
+        };
+        // Canonical text, so `1` and `1.0` or reordered objects are the same element.
+        let text = canonicalize(identity).unwrap_or_else(|_| identity.to_string());
+        let next = self.ids.len();
+        Some(*self.ids.entry(text).or_insert(next))
+    }
+}
+
+/// Keeps the first occurrence of each element of the array at a path for which `keep`
+/// accepts the number of `others` holding it out of their total; elements with an
+/// undefined key are kept if `keep_unkeyed` is set.
+fn keep_by_membership(
+    json: &mut Value,
+    path: &str,
+    others: &[Value],
+    key: Option<&str>,
+    keep: impl Fn(usize, usize) -> bool,
+    keep_unkeyed: bool,
+) -> Result<(), Error> {
+    let mut interner = Interner::new(key)?;
+    let members: Vec<HashSet<usize>> = other_arrays(others)?
+        .into_iter()
+        .map(|other| {
+            other
+                .iter()
+                .filter_map(|item| interner.intern(item))
+                .collect()
+        })
+        .collect();
+    let items = array_at_mut(json, path)?;
+    let mut seen = HashSet::new();
+    items.retain(|item| match interner.intern(item) {
+        Some(id) => {
+            let found = members.iter().filter(|set| set.contains(&id)).count();
+            keep(found, members.len()) && seen.insert(id)
+        }
+        None => keep_unkeyed,
+    });
+    Ok(())
+}
+
+/// Returns the elements of the arrays a set operation compares with.
+fn other_arrays(others: &[Value]) -> Result<Vec<&Vec<Value>>, Error> {
+    others
+        .iter()
+        .enumerate()
+        .map(|(index, other)| {
+            other.as_array().ok_or_else(|| {
+                Error::msg(format!("Value {} to compare is not an array", index + 1))
+            })
+        })
+        .collect()
+}
+
+/// Compiles a key expression, accepting a jq-style leading dot as in `.id`.
+pub(crate) fn compile_key(key: &str) -> Result<Expression, Error> {
+    match key.strip_prefix('.') {
+        Some(rest) => Expression::compile(&format!("@.{}", rest)),
+        None => Expression::compile(key),
+    }
+}
+
+/// Returns the array at a `get_by_path` path, or the document itself for an empty path.
+pub(crate) fn array_at_mut<'a>(
+    json: &'a mut Value,
+    path: &str,
+) -> Result<&'a mut Vec<Value>, Error> {
+    let steps = if path.is_empty() {
+        Vec::new()
+    } else {
+        parse_steps(path)?
+    };
+    get_at_mut(json, &steps)
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| Error::msg(format!("No array at path: {}", path)))
+}
+
+/// Returns the value at a path, or the document itself for an empty path.
+fn value_at(json: &Value, path: &str) -> Result<Value, Error> {
+    if path.is_empty() {
+        return Ok(json.clone());
+    }
+    get_by_path(json, path).ok_or_else(|| Error::msg(format!("No array at path: {}", path)))
+}
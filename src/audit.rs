@@ -0,0 +1,162 @@
+use crate::datetime::{format_date, now};
+use crate::diff::diff_json;
+use crate::parser::canonicalize;
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// One value changed by an audited mutation.
+///
+/// Values are recorded as hashes only, so the log can show that a secret changed
+/// without containing it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the mutation ran, in RFC 3339 (UTC).
+    pub timestamp: String,
+    /// The operation, e.g. `edit`, `patch`, `merge` or `rename-keys`.
+    pub operation: String,
+    /// The changed location in `get_by_path` syntax; empty for the whole document.
+    pub path: String,
+    /// `hash_value` of the previous value; `None` if the value was added.
+    pub old_hash: Option<String>,
+    /// `hash_value` of the new value; `None` if the value was removed.
+    pub new_hash: Option<String>,
+    /// Who made the change, if the sink knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+}
+
+/// Destination of audit entries, such as an `AuditLog` file or a `Vec<AuditEntry>`.
+pub trait AuditSink {
+    /// Records one entry.
+    fn record(&mut self, entry: AuditEntry) -> Result<(), Error>;
+}
+
+impl AuditSink for Vec<AuditEntry> {
+    fn record(&mut self, entry: AuditEntry) -> Result<(), Error> {
+        self.push(entry);
+        Ok(())
+    }
+}
+
+/// An append-only NDJSON audit file, one `AuditEntry` per line.
+#[derive(Debug)]
+pub struct AuditLog {
+    file: File,
+    actor: Option<String>,
+}
+
+impl AuditLog {
+    /// Opens an audit file for appending, creating it if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The NDJSON file.
+    /// * `actor` - Who makes the changes, e.g. a user name; set on entries without one.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<AuditLog, Error>` - The log, or an error if the file cannot be opened.
+    pub fn open(path: &Path, actor: Option<&str>) -> Result<AuditLog, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log: {}", path.display()))?;
+        Ok(AuditLog {
+            file,
+            actor: actor.map(str::to_string),
+        })
+    }
+}
+
+impl AuditSink for AuditLog {
+    fn record(&mut self, mut entry: AuditEntry) -> Result<(), Error> {
+        if entry.actor.is_none() {
+            entry.actor = self.actor.clone();
+        }
+        // One write per line, so concurrent writers do not interleave within an entry.
+        let line = format!("{}\n", serde_json::to_string(&entry)?);
+        self.file
+            .write_all(line.as_bytes())
+            .context("Failed to write to audit log")?;
+        self.file.flush().context("Failed to write to audit log")
+    }
+}
+
+/// Hashes a value as `sha256:` and the hex SHA-256 digest of its canonical form, so
+/// equal values hash equally whatever their key order or number formatting.
+pub fn hash_value(value: &Value) -> String {
+    let text = canonicalize(value).unwrap_or_else(|_| value.to_string());
+    let digest = Sha256::digest(text.as_bytes());
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256:{}", hex)
+}
+
+/// Records every difference between two versions of a document, all with the current
+/// time.
+///
+/// # Arguments
+///
+/// * `before` - The document before the mutation.
+/// * `after` - The document after it.
+/// * `operation` - The name of the mutation, e.g. `patch`.
+/// * `sink` - Where the entries go.
+///
+/// # Returns
+///
+/// * `Result<usize, Error>` - The number of entries recorded, or the sink's error.
+pub fn record_changes(
+    before: &Value,
+    after: &Value,
+    operation: &str,
+    sink: &mut dyn AuditSink,
+) -> Result<usize, Error> {
+    let timestamp = format_date(now(), None, 0);
+    let changes = diff_json(before, after);
+    for change in &changes {
+        sink.record(AuditEntry {
+            timestamp: timestamp.clone(),
+            operation: operation.to_string(),
+            path: change.path.clone(),
+            old_hash: change.old_value.as_ref().map(hash_value),
+            new_hash: change.new_value.as_ref().map(hash_value),
+            actor: None,
+        })?;
+    }
+    Ok(changes.len())
+}
+
+/// Runs a mutating call, such as `apply_patch` or `merge_json`, and records what it
+/// changed when a sink is given:
+/// `audited(&mut config, "patch", Some(&mut log), |json| apply_patch(json, &patch))`.
+///
+/// # Arguments
+///
+/// * `json` - The document to mutate.
+/// * `operation` - The name recorded in the entries.
+/// * `sink` - Where the entries go; with `None`, the call just runs.
+/// * `mutate` - The mutation.
+///
+/// # Returns
+///
+/// * `Result<T, Error>` - The result of `mutate`. Nothing is recorded if it fails,
+///   although the document may then be partly changed.
+pub fn audited<T>(
+    json: &mut Value,
+    operation: &str,
+    sink: Option<&mut dyn AuditSink>,
+    mutate: impl FnOnce(&mut Value) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let Some(sink) = sink else {
+        return mutate(json);
+    };
+    let before = json.clone();
+    let result = mutate(json)?;
+    record_changes(&before, json, operation, sink)?;
+    Ok(result)
+}
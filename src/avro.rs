@@ -0,0 +1,986 @@
+use crate::path_syntax::PathSyntax;
+use anyhow::{Error, Result};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Magic bytes at the start of an Avro Object Container File.
+const MAGIC: &[u8] = b"Obj\x01";
+
+/// Deepest nesting of records, arrays and maps `avro_to_json` accepts, so a malicious
+/// file cannot exhaust the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Options for `json_to_avro`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AvroOptions {
+    /// The Avro schema of the records, e.g. read from an `.avsc` file; inferred from
+    /// the data with `infer_avro_schema` if `None`.
+    pub schema: Option<Value>,
+}
+
+/// A parsed Avro schema; named types are referred to by their full name.
+#[derive(Debug, Clone)]
+enum Schema {
+    Null,
+    Boolean,
+    Int,
+    Long,
+    Float,
+    Double,
+    Bytes,
+    String,
+    Record { name: String, fields: Vec<Field> },
+    Enum { name: String, symbols: Vec<String> },
+    Array(Box<Schema>),
+    Map(Box<Schema>),
+    Union(Vec<Schema>),
+    Fixed { name: String, size: usize },
+    Named(String),
+}
+
+/// A field of a record schema.
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    schema: Schema,
+    default: Option<Value>,
+}
+
+/// A schema with the named types it defines.
+struct Schemas {
+    root: Schema,
+    names: BTreeMap<String, Schema>,
+}
+
+/// Infers an Avro schema for the records of a document: the elements of an array, or
+/// the document itself otherwise.
+///
+/// Objects become records named after their path (`Record`, `Record_address`, ...),
+/// unless a key is not a valid Avro name, in which case they become maps. Integers
+/// become `long`, other numbers `double`, and fields that are `null` or missing in some
+/// records a union with `null` and a `null` default.
+///
+/// # Arguments
+///
+/// * `json` - The records, e.g. an array of objects from an API.
+///
+/// # Returns
+///
+/// * `Value` - The schema as JSON, ready to save as an `.avsc` file.
+pub fn infer_avro_schema(json: &Value) -> Value {
+    match json {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| infer(item, "Record"))
+            .reduce(merge_schemas)
+            .unwrap_or_else(|| json!("null")),
+        _ => infer(json, "Record"),
+    }
+}
+
+/// Encodes JSON as an Avro Object Container File, the `.avro` files of Hadoop, Kafka
+/// Connect and most data lakes.
+///
+/// An array is written as one record per element, unless the schema is an array
+/// schema; any other document is a single record. The schema is stored in the file,
+/// and blocks are not compressed (the `null` codec). `bytes` and `fixed` values are
+/// strings of code points 0 to 255, as in the Avro JSON encoding.
+///
+/// # Arguments
+///
+/// * `json` - The records.
+/// * `options` - The schema, or `None` to infer one.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, Error>` - The file, or an error if the schema is invalid or a
+///   value does not match it.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn json_to_avro(json: &Value, options: &AvroOptions) -> Result<Vec<u8>, Error> {
+    let schema_json = options
+        .schema
+        .clone()
+        .unwrap_or_else(|| infer_avro_schema(json));
+    let schemas = Schemas::parse(&schema_json)?;
+    let records: Vec<(String, &Value)> = match json {
+        Value::Array(items) if !matches!(schemas.resolve(&schemas.root)?, Schema::Array(_)) => {
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| (PathSyntax::default().join_index("", index), item))
+                .collect()
+        }
+        _ => vec![(String::new(), json)],
+    };
+    let mut data = Vec::new();
+    for (path, record) in &records {
+        schemas.write(&schemas.root, record, path, &mut data)?;
+    }
+
+    let schema_text = schema_json.to_string();
+    let sync: [u8; 16] = Sha256::digest(schema_text.as_bytes())[..16]
+        .try_into()
+        .unwrap();
+    let mut out = MAGIC.to_vec();
+    write_long(&mut out, 2);
+    for (key, value) in [
+        ("avro.codec", "null"),
+        ("avro.schema", schema_text.as_str()),
+    ] {
+        write_bytes(&mut out, key.as_bytes());
+        write_bytes(&mut out, value.as_bytes());
+    }
+    write_long(&mut out, 0);
+    out.extend_from_slice(&sync);
+    if !records.is_empty() {
+        write_long(&mut out, records.len() as i64);
+        write_long(&mut out, data.len() as i64);
+        out.extend_from_slice(&data);
+        out.extend_from_slice(&sync);
+    }
+    Ok(out)
+}
+
+/// Decodes an Avro Object Container File with the schema it stores.
+///
+/// Records become objects, unions their value, enums their symbol, and `bytes` and
+/// `fixed` strings of code points 0 to 255. Files compressed with `deflate`, `snappy`
+/// or other codecs are not supported.
+///
+/// # Arguments
+///
+/// * `input` - The file.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The record of a file with one, or an array of the records
+///   otherwise; an error if the file is malformed or compressed.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(input), fields(bytes = input.len()))
+)]
+pub fn avro_to_json(input: &[u8]) -> Result<Value, Error> {
+    let mut decoder = Decoder { input, offset: 0 };
+    if decoder.take(MAGIC.len())? != MAGIC {
+        return Err(Error::msg("Not an Avro Object Container File"));
+    }
+    let mut metadata = BTreeMap::new();
+    loop {
+        let count = decoder.block_count()?;
+        if count == 0 {
+            break;
+        }
+        for _ in 0..count {
+            let key = decoder.string()?;
+            let value = decoder.bytes()?.to_vec();
+            metadata.insert(key, value);
+        }
+    }
+    match metadata.get("avro.codec").map(Vec::as_slice) {
+        None | Some(b"null") => {}
+        Some(codec) => {
+            return Err(Error::msg(format!(
+                "Unsupported Avro codec '{}'; only uncompressed files can be read",
+                String::from_utf8_lossy(codec)
+            )))
+        }
+    }
+    let schema_json: Value = metadata
+        .get("avro.schema")
+        .ok_or_else(|| Error::msg("The Avro file has no schema"))
+        .and_then(|text| {
+            serde_json::from_slice(text)
+                .map_err(|e| Error::msg(format!("Invalid Avro schema in the file: {}", e)))
+        })?;
+    let schemas = Schemas::parse(&schema_json)?;
+    let sync = decoder.take(16)?.to_vec();
+    let mut records = Vec::new();
+    while decoder.offset < input.len() {
+        let count = decoder.long()?;
+        let size = usize::try_from(decoder.long()?).map_err(|_| {
+            Error::msg(format!(
+                "Invalid Avro block size at byte {}",
+                decoder.offset
+            ))
+        })?;
+        let end = decoder.offset + size;
+        for _ in 0..count {
+            records.push(schemas.read(&schemas.root, &mut decoder, 0)?);
+        }
+        if decoder.offset != end || decoder.take(16)? != sync.as_slice() {
+            return Err(Error::msg(format!(
+                "Corrupt Avro block ending at byte {}",
+                decoder.offset
+            )));
+        }
+    }
+    Ok(if records.len() == 1 {
+        records.remove(0)
+    } else {
+        Value::Array(records)
+    })
+}
+
+/// Writes a value in the Avro JSON encoding of a schema, where non-null union values
+/// are wrapped in an object naming their branch: `{"string": "a"}` or
+/// `{"com.example.Address": {...}}`. As in `json_to_avro`, an array is taken as records
+/// of the schema unless it is an array schema.
+///
+/// # Arguments
+///
+/// * `json` - The value, or an array of records.
+/// * `schema` - Its Avro schema.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The Avro JSON, or an error if the schema is invalid or the
+///   value does not match it.
+pub fn json_to_avro_json(json: &Value, schema: &Value) -> Result<Value, Error> {
+    let schemas = Schemas::parse(schema)?;
+    schemas.each_record(json, |path, record| {
+        schemas.encode_json(&schemas.root, record, path)
+    })
+}
+
+/// Reads a value from the Avro JSON encoding of a schema, the reverse of
+/// `json_to_avro_json`.
+///
+/// # Arguments
+///
+/// * `json` - The Avro JSON.
+/// * `schema` - Its Avro schema.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The plain JSON value, or an error if the schema is invalid
+///   or the value does not match it.
+pub fn avro_json_to_json(json: &Value, schema: &Value) -> Result<Value, Error> {
+    let schemas = Schemas::parse(schema)?;
+    schemas.each_record(json, |path, record| {
+        schemas.decode_json(&schemas.root, record, path)
+    })
+}
+
+impl Schemas {
+    /// Parses a schema and collects its named types.
+    fn parse(schema: &Value) -> Result<Schemas, Error> {
+        let mut names = BTreeMap::new();
+        let root = parse_schema(schema, None, &mut names)?;
+        Ok(Schemas { root, names })
+    }
+
+    /// Applies a conversion to a document, or to each element of an array of records if
+    /// the schema is not an array schema.
+    fn each_record(
+        &self,
+        json: &Value,
+        convert: impl Fn(&str, &Value) -> Result<Value>,
+    ) -> Result<Value> {
+        match json {
+            Value::Array(items) if !matches!(self.resolve(&self.root)?, Schema::Array(_)) => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| convert(&PathSyntax::default().join_index("", index), item))
+                .collect(),
+            _ => convert("", json),
+        }
+    }
+
+    /// Follows a reference to a named type.
+    fn resolve<'a>(&'a self, schema: &'a Schema) -> Result<&'a Schema, Error> {
+        match schema {
+            Schema::Named(name) => self
+                .names
+                .get(name)
+                .or_else(|| {
+                    let short = name.rsplit('.').next().unwrap_or(name);
+                    self.names.get(short)
+                })
+                .ok_or_else(|| Error::msg(format!("Unknown Avro type '{}'", name))),
+            schema => Ok(schema),
+        }
+    }
+
+    /// Returns whether a value can be written with a schema, to pick a union branch.
+    fn matches(&self, schema: &Schema, value: &Value) -> bool {
+        let Ok(schema) = self.resolve(schema) else {
+            return false;
+        };
+        match (schema, value) {
+            (Schema::Null, Value::Null) | (Schema::Boolean, Value::Bool(_)) => true,
+            (Schema::Int, Value::Number(number)) => number
+                .as_i64()
+                .is_some_and(|int| i32::try_from(int).is_ok()),
+            (Schema::Long, Value::Number(number)) => number.is_i64(),
+            (Schema::Float | Schema::Double, Value::Number(_)) => true,
+            (Schema::Bytes | Schema::String, Value::String(_)) => true,
+            (Schema::Enum { symbols, .. }, Value::String(text)) => symbols.contains(text),
+            (Schema::Fixed { size, .. }, Value::String(text)) => text.chars().count() == *size,
+            (Schema::Record { fields, .. }, Value::Object(map)) => fields
+                .iter()
+                .all(|field| map.contains_key(&field.name) || field.default.is_some()),
+            (Schema::Map(_), Value::Object(_)) | (Schema::Array(_), Value::Array(_)) => true,
+            (Schema::Union(branches), value) => {
+                branches.iter().any(|branch| self.matches(branch, value))
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the index and schema of the first union branch matching a value.
+    fn branch<'a>(
+        &self,
+        branches: &'a [Schema],
+        value: &Value,
+        path: &str,
+    ) -> Result<(usize, &'a Schema), Error> {
+        branches
+            .iter()
+            .enumerate()
+            .find(|(_, branch)| self.matches(branch, value))
+            .ok_or_else(|| mismatch(path, "a branch of the union", value))
+    }
+
+    /// Writes a value in the binary encoding.
+    fn write(&self, schema: &Schema, value: &Value, path: &str, out: &mut Vec<u8>) -> Result<()> {
+        let syntax = PathSyntax::default();
+        match (self.resolve(schema)?, value) {
+            (Schema::Null, Value::Null) => {}
+            (Schema::Boolean, Value::Bool(flag)) => out.push(*flag as u8),
+            (Schema::Int, _) | (Schema::Long, _) if self.matches(schema, value) => {
+                write_long(out, value.as_i64().unwrap())
+            }
+            (Schema::Float, Value::Number(number)) => {
+                out.extend_from_slice(&(number.as_f64().unwrap_or_default() as f32).to_le_bytes())
+            }
+            (Schema::Double, Value::Number(number)) => {
+                out.extend_from_slice(&number.as_f64().unwrap_or_default().to_le_bytes())
+            }
+            (Schema::String, Value::String(text)) => write_bytes(out, text.as_bytes()),
+            (Schema::Bytes, Value::String(text)) => write_bytes(out, &latin1_bytes(text, path)?),
+            (fixed @ Schema::Fixed { .. }, Value::String(_)) if self.matches(fixed, value) => {
+                out.extend_from_slice(&latin1_bytes(value.as_str().unwrap(), path)?)
+            }
+            (Schema::Enum { symbols, .. }, Value::String(text)) if symbols.contains(text) => {
+                write_long(out, symbols.iter().position(|s| s == text).unwrap() as i64)
+            }
+            (Schema::Record { fields, .. }, Value::Object(map)) => {
+                for field in fields {
+                    let field_path = syntax.join_key(path, &field.name);
+                    let value =
+                        map.get(&field.name)
+                            .or(field.default.as_ref())
+                            .ok_or_else(|| {
+                                Error::msg(format!("Missing Avro field at {}", field_path))
+                            })?;
+                    self.write(&field.schema, value, &field_path, out)?;
+                }
+            }
+            (Schema::Array(items), Value::Array(values)) => {
+                if !values.is_empty() {
+                    write_long(out, values.len() as i64);
+                    for (index, item) in values.iter().enumerate() {
+                        self.write(items, item, &syntax.join_index(path, index), out)?;
+                    }
+                }
+                write_long(out, 0);
+            }
+            (Schema::Map(values), Value::Object(map)) => {
+                if !map.is_empty() {
+                    write_long(out, map.len() as i64);
+                    for (key, value) in map {
+                        write_bytes(out, key.as_bytes());
+                        self.write(values, value, &syntax.join_key(path, key), out)?;
+                    }
+                }
+                write_long(out, 0);
+            }
+            (Schema::Union(branches), value) => {
+                let (index, branch) = self.branch(branches, value, path)?;
+                write_long(out, index as i64);
+                self.write(branch, value, path, out)?;
+            }
+            (schema, value) => return Err(mismatch(path, &type_name(schema), value)),
+        }
+        Ok(())
+    }
+
+    /// Reads a value in the binary encoding.
+    fn read(&self, schema: &Schema, decoder: &mut Decoder, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(Error::msg(format!(
+                "Avro nesting deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+        Ok(match self.resolve(schema)? {
+            Schema::Null => Value::Null,
+            Schema::Boolean => Value::Bool(decoder.byte()? != 0),
+            Schema::Int | Schema::Long => Value::from(decoder.long()?),
+            Schema::Float => {
+                let float = f32::from_le_bytes(decoder.take(4)?.try_into().unwrap());
+                json!(float as f64)
+            }
+            Schema::Double => json!(f64::from_le_bytes(decoder.take(8)?.try_into().unwrap())),
+            Schema::String => Value::String(decoder.string()?),
+            Schema::Bytes => Value::String(latin1_string(decoder.bytes()?)),
+            Schema::Fixed { size, .. } => Value::String(latin1_string(decoder.take(*size)?)),
+            Schema::Enum { symbols, name } => {
+                let index = decoder.long()?;
+                let symbol = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| symbols.get(index))
+                    .ok_or_else(|| {
+                        Error::msg(format!("Invalid index {} of the Avro enum {}", index, name))
+                    })?;
+                Value::String(symbol.clone())
+            }
+            Schema::Record { fields, .. } => {
+                let mut map = Map::new();
+                for field in fields {
+                    let value = self.read(&field.schema, decoder, depth + 1)?;
+                    map.insert(field.name.clone(), value);
+                }
+                Value::Object(map)
+            }
+            Schema::Array(items) => {
+                let mut values = Vec::new();
+                loop {
+                    let count = decoder.block_count()?;
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        values.push(self.read(items, decoder, depth + 1)?);
+                    }
+                }
+                Value::Array(values)
+            }
+            Schema::Map(values) => {
+                let mut map = Map::new();
+                loop {
+                    let count = decoder.block_count()?;
+                    if count == 0 {
+                        break;
+                    }
+                    for _ in 0..count {
+                        let key = decoder.string()?;
+                        let value = self.read(values, decoder, depth + 1)?;
+                        map.insert(key, value);
+                    }
+                }
+                Value::Object(map)
+            }
+            Schema::Union(branches) => {
+                let index = decoder.long()?;
+                let branch = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| branches.get(index))
+                    .ok_or_else(|| Error::msg(format!("Invalid Avro union index {}", index)))?;
+                self.read(branch, decoder, depth + 1)?
+            }
+            Schema::Named(name) => return Err(Error::msg(format!("Unknown Avro type '{}'", name))),
+        })
+    }
+
+    /// Writes a value in the Avro JSON encoding.
+    fn encode_json(&self, schema: &Schema, value: &Value, path: &str) -> Result<Value> {
+        let syntax = PathSyntax::default();
+        let resolved = self.resolve(schema)?;
+        if !self.matches(resolved, value) {
+            return Err(mismatch(path, &type_name(resolved), value));
+        }
+        Ok(match (resolved, value) {
+            (Schema::Record { fields, .. }, Value::Object(map)) => {
+                let mut record = Map::new();
+                for field in fields {
+                    let field_path = syntax.join_key(path, &field.name);
+                    let value = map.get(&field.name).or(field.default.as_ref()).unwrap();
+                    let encoded = self.encode_json(&field.schema, value, &field_path)?;
+                    record.insert(field.name.clone(), encoded);
+                }
+                Value::Object(record)
+            }
+            (Schema::Array(items), Value::Array(values)) => values
+                .iter()
+                .enumerate()
+                .map(|(index, item)| self.encode_json(items, item, &syntax.join_index(path, index)))
+                .collect::<Result<_>>()?,
+            (Schema::Map(values), Value::Object(map)) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| {
+                        let encoded =
+                            self.encode_json(values, value, &syntax.join_key(path, key))?;
+                        Ok((key.clone(), encoded))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            (Schema::Union(branches), value) => {
+                let (_, branch) = self.branch(branches, value, path)?;
+                match self.resolve(branch)? {
+                    Schema::Null => Value::Null,
+                    resolved => {
+                        let encoded = self.encode_json(branch, value, path)?;
+                        json!({ type_name(resolved): encoded })
+                    }
+                }
+            }
+            (_, value) => value.clone(),
+        })
+    }
+
+    /// Reads a value in the Avro JSON encoding.
+    fn decode_json(&self, schema: &Schema, value: &Value, path: &str) -> Result<Value> {
+        let syntax = PathSyntax::default();
+        let resolved = self.resolve(schema)?;
+        match (resolved, value) {
+            (Schema::Union(_), Value::Null) if self.matches(resolved, value) => Ok(Value::Null),
+            (Schema::Union(branches), Value::Object(wrapper)) if wrapper.len() == 1 => {
+                let (label, inner) = wrapper.iter().next().unwrap();
+                let branch = branches
+                    .iter()
+                    .find(|branch| {
+                        self.resolve(branch)
+                            .is_ok_and(|branch| &type_name(branch) == label)
+                    })
+                    .ok_or_else(|| mismatch(path, "a branch of the union", value))?;
+                self.decode_json(branch, inner, path)
+            }
+            (Schema::Union(_), value) => Err(mismatch(path, "a branch of the union", value)),
+            (Schema::Record { fields, .. }, Value::Object(map)) => {
+                let mut record = Map::new();
+                for field in fields {
+                    let field_path = syntax.join_key(path, &field.name);
+                    // A default stands for the plain value, not its Avro JSON.
+                    let decoded = match (map.get(&field.name), &field.default) {
+                        (Some(value), _) => self.decode_json(&field.schema, value, &field_path)?,
+                        (None, Some(default)) => default.clone(),
+                        (None, None) => {
+                            return Err(Error::msg(format!("Missing Avro field at {}", field_path)))
+                        }
+                    };
+                    record.insert(field.name.clone(), decoded);
+                }
+                Ok(Value::Object(record))
+            }
+            (Schema::Array(items), Value::Array(values)) => values
+                .iter()
+                .enumerate()
+                .map(|(index, item)| self.decode_json(items, item, &syntax.join_index(path, index)))
+                .collect(),
+            (Schema::Map(values), Value::Object(map)) => map
+                .iter()
+                .map(|(key, value)| {
+                    let decoded = self.decode_json(values, value, &syntax.join_key(path, key))?;
+                    Ok((key.clone(), decoded))
+                })
+                .collect::<Result<Map<_, _>>>()
+                .map(Value::Object),
+            (resolved, value) if self.matches(resolved, value) => Ok(value.clone()),
+            (resolved, value) => Err(mismatch(path, &type_name(resolved), value)),
+        }
+    }
+}
+
+/// Parses a schema given as JSON, registering the named types it defines.
+fn parse_schema(
+    json: &Value,
+    namespace: Option<&str>,
+    names: &mut BTreeMap<String, Schema>,
+) -> Result<Schema, Error> {
+    let invalid = |reason: String| Error::msg(format!("Invalid Avro schema: {}", reason));
+    let full_name = |name: &str, namespace: Option<&str>| match namespace {
+        Some(namespace) if !name.contains('.') && !namespace.is_empty() => {
+            format!("{}.{}", namespace, name)
+        }
+        _ => name.to_string(),
+    };
+    match json {
+        Value::String(name) => Ok(match name.as_str() {
+            "null" => Schema::Null,
+            "boolean" => Schema::Boolean,
+            "int" => Schema::Int,
+            "long" => Schema::Long,
+            "float" => Schema::Float,
+            "double" => Schema::Double,
+            "bytes" => Schema::Bytes,
+            "string" => Schema::String,
+            name => Schema::Named(full_name(name, namespace)),
+        }),
+        Value::Array(branches) => branches
+            .iter()
+            .map(|branch| parse_schema(branch, namespace, names))
+            .collect::<Result<_>>()
+            .map(Schema::Union),
+        Value::Object(map) => {
+            let kind = map
+                .get("type")
+                .ok_or_else(|| invalid(format!("missing \"type\" in {}", json)))?;
+            let Some(kind) = kind.as_str() else {
+                return parse_schema(kind, namespace, names);
+            };
+            let name = || {
+                map.get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid(format!("a {} needs a \"name\"", kind)))
+            };
+            let namespace = map.get("namespace").and_then(Value::as_str).or(namespace);
+            let schema = match kind {
+                "record" | "error" => {
+                    let name = full_name(name()?, namespace);
+                    let inner_namespace = name.rsplit_once('.').map(|(namespace, _)| namespace);
+                    // Registered before the fields, which may refer to the record.
+                    names.insert(name.clone(), Schema::Named(name.clone()));
+                    let fields = map
+                        .get("fields")
+                        .and_then(Value::as_array)
+                        .ok_or_else(|| invalid(format!("the record {} needs \"fields\"", name)))?
+                        .iter()
+                        .map(|field| {
+                            let field_name =
+                                field.get("name").and_then(Value::as_str).ok_or_else(|| {
+                                    invalid(format!("a field of {} needs a \"name\"", name))
+                                })?;
+                            let field_type = field.get("type").ok_or_else(|| {
+                                invalid(format!(
+                                    "the field {}.{} needs a \"type\"",
+                                    name, field_name
+                                ))
+                            })?;
+                            Ok(Field {
+                                name: field_name.to_string(),
+                                schema: parse_schema(field_type, inner_namespace, names)?,
+                                default: field.get("default").cloned(),
+                            })
+                        })
+                        .collect::<Result<_>>()?;
+                    Schema::Record { name, fields }
+                }
+                "enum" => Schema::Enum {
+                    name: full_name(name()?, namespace),
+                    symbols: map
+                        .get("symbols")
+                        .and_then(Value::as_array)
+                        .and_then(|symbols| {
+                            symbols
+                                .iter()
+                                .map(|symbol| symbol.as_str().map(str::to_string))
+                                .collect()
+                        })
+                        .ok_or_else(|| invalid("an enum needs string \"symbols\"".to_string()))?,
+                },
+                "fixed" => Schema::Fixed {
+                    name: full_name(name()?, namespace),
+                    size: map
+                        .get("size")
+                        .and_then(Value::as_u64)
+                        .ok_or_else(|| invalid("a fixed needs a \"size\"".to_string()))?
+                        as usize,
+                },
+                "array" => Schema::Array(Box::new(parse_schema(
+                    map.get("items")
+                        .ok_or_else(|| invalid("an array needs \"items\"".to_string()))?,
+                    namespace,
+                    names,
+                )?)),
+                "map" => Schema::Map(Box::new(parse_schema(
+                    map.get("values")
+                        .ok_or_else(|| invalid("a map needs \"values\"".to_string()))?,
+                    namespace,
+                    names,
+                )?)),
+                // Primitive types with attributes, such as logical types, keep their
+                // underlying representation.
+                primitive => parse_schema(&Value::from(primitive), namespace, names)?,
+            };
+            if let Schema::Record { name, .. }
+            | Schema::Enum { name, .. }
+            | Schema::Fixed { name, .. } = &schema
+            {
+                let short = name.rsplit('.').next().unwrap_or(name).to_string();
+                names.entry(short).or_insert_with(|| schema.clone());
+                names.insert(name.clone(), schema.clone());
+            }
+            Ok(schema)
+        }
+        _ => Err(invalid(format!("unexpected {}", json))),
+    }
+}
+
+/// Infers the schema of a value; nested records are named after their path.
+fn infer(value: &Value, name: &str) -> Value {
+    match value {
+        Value::Null => json!("null"),
+        Value::Bool(_) => json!("boolean"),
+        Value::Number(number) if number.is_i64() => json!("long"),
+        Value::Number(_) => json!("double"),
+        Value::String(_) => json!("string"),
+        Value::Array(items) => json!({
+            "type": "array",
+            "items": items
+                .iter()
+                .map(|item| infer(item, &format!("{}_item", name)))
+                .reduce(merge_schemas)
+                .unwrap_or_else(|| json!("null")),
+        }),
+        Value::Object(map) if map.keys().all(|key| is_avro_name(key)) => json!({
+            "type": "record",
+            "name": name,
+            "fields": map
+                .iter()
+                .map(|(key, value)| field(key, infer(value, &format!("{}_{}", name, key))))
+                .collect::<Vec<_>>(),
+        }),
+        Value::Object(map) => json!({
+            "type": "map",
+            "values": map
+                .values()
+                .map(|value| infer(value, &format!("{}_value", name)))
+                .reduce(merge_schemas)
+                .unwrap_or_else(|| json!("null")),
+        }),
+    }
+}
+
+/// Builds a record field, giving nullable fields a `null` default.
+fn field(name: &str, schema: Value) -> Value {
+    let nullable = schema == "null"
+        || schema
+            .as_array()
+            .is_some_and(|branches| branches.contains(&json!("null")));
+    if nullable {
+        json!({ "name": name, "type": schema, "default": null })
+    } else {
+        json!({ "name": name, "type": schema })
+    }
+}
+
+/// Combines two inferred schemas into one that accepts the values of both.
+fn merge_schemas(a: Value, b: Value) -> Value {
+    let branches = |schema: Value| match schema {
+        Value::Array(branches) => branches,
+        schema => vec![schema],
+    };
+    let mut merged: Vec<Value> = branches(a);
+    for branch in branches(b) {
+        let kind = schema_kind(&branch);
+        match merged
+            .iter()
+            .position(|existing| schema_kind(existing) == kind)
+        {
+            Some(index) => {
+                let existing = merged.remove(index);
+                merged.insert(index, merge_same_kind(existing, branch));
+            }
+            None => merged.push(branch),
+        }
+    }
+    // A union with `null` lists it first, so that `null` can be the default.
+    merged.sort_by_key(|branch| branch != "null");
+    if merged.len() == 1 {
+        merged.remove(0)
+    } else {
+        Value::Array(merged)
+    }
+}
+
+/// Returns the kind of an inferred schema, treating `long` and `double` as one.
+fn schema_kind(schema: &Value) -> &str {
+    match schema.as_str() {
+        Some("long") | Some("double") => "number",
+        Some(primitive) => primitive,
+        None => schema["type"].as_str().unwrap_or_default(),
+    }
+}
+
+/// Merges two inferred schemas of the same kind.
+fn merge_same_kind(a: Value, b: Value) -> Value {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (Value::String(_), Value::String(_)) => json!("double"),
+        (Value::Object(mut a), Value::Object(b)) if a["type"] == "array" => {
+            a["items"] = merge_schemas(a["items"].take(), b["items"].clone());
+            Value::Object(a)
+        }
+        (Value::Object(mut a), Value::Object(b)) if a["type"] == "map" => {
+            a["values"] = merge_schemas(a["values"].take(), b["values"].clone());
+            Value::Object(a)
+        }
+        (Value::Object(mut a), Value::Object(b)) => {
+            let fields = |record: &Map<String, Value>| -> Vec<(String, Value)> {
+                record["fields"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|field| {
+                        (
+                            field["name"].as_str().unwrap().to_string(),
+                            field["type"].clone(),
+                        )
+                    })
+                    .collect()
+            };
+            let (a_fields, b_fields) = (fields(&a), fields(&b));
+            let mut merged: Vec<(String, Value)> = a_fields
+                .iter()
+                .map(|(name, schema)| {
+                    let schema = match b_fields.iter().find(|(other, _)| other == name) {
+                        Some((_, other)) => merge_schemas(schema.clone(), other.clone()),
+                        None => merge_schemas(schema.clone(), json!("null")),
+                    };
+                    (name.clone(), schema)
+                })
+                .collect();
+            for (name, schema) in b_fields {
+                if !a_fields.iter().any(|(other, _)| *other == name) {
+                    merged.push((name, merge_schemas(schema, json!("null"))));
+                }
+            }
+            a.insert(
+                "fields".to_string(),
+                merged
+                    .into_iter()
+                    .map(|(name, schema)| field(&name, schema))
+                    .collect(),
+            );
+            Value::Object(a)
+        }
+        (a, _) => a,
+    }
+}
+
+/// Returns whether a key can be a record field name: `[A-Za-z_][A-Za-z0-9_]*`.
+fn is_avro_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns the name of a schema as used for union branches in the Avro JSON encoding.
+fn type_name(schema: &Schema) -> String {
+    match schema {
+        Schema::Null => "null",
+        Schema::Boolean => "boolean",
+        Schema::Int => "int",
+        Schema::Long => "long",
+        Schema::Float => "float",
+        Schema::Double => "double",
+        Schema::Bytes => "bytes",
+        Schema::String => "string",
+        Schema::Array(_) => "array",
+        Schema::Map(_) => "map",
+        Schema::Union(_) => "union",
+        Schema::Record { name, .. }
+        | Schema::Enum { name, .. }
+        | Schema::Fixed { name, .. }
+        | Schema::Named(name) => name,
+    }
+    .to_string()
+}
+
+/// Describes a value that does not match its schema.
+fn mismatch(path: &str, expected: &str, value: &Value) -> Error {
+    let path = if path.is_empty() { "the root" } else { path };
+    Error::msg(format!(
+        "Value at {} does not match the Avro schema: expected {}, found {}",
+        path, expected, value
+    ))
+}
+
+/// Converts a string of code points 0 to 255 to the bytes they stand for.
+fn latin1_bytes(text: &str, path: &str) -> Result<Vec<u8>, Error> {
+    text.chars()
+        .map(u8::try_from)
+        .collect::<Result<_, _>>()
+        .map_err(|_| {
+            Error::msg(format!(
+                "Avro bytes at {} must be a string of code points 0 to 255",
+                path
+            ))
+        })
+}
+
+/// Converts bytes to a string of code points 0 to 255.
+fn latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
+
+/// Appends a zigzag varint, the encoding of `int` and `long`.
+fn write_long(out: &mut Vec<u8>, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Appends a length-prefixed byte string.
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(out, bytes.len() as i64);
+    out.extend_from_slice(bytes);
+}
+
+/// Reads Avro binary data from a byte slice.
+struct Decoder<'a> {
+    input: &'a [u8],
+    offset: usize,
+}
+
+impl Decoder<'_> {
+    fn long(&mut self) -> Result<i64, Error> {
+        let mut value = 0u64;
+        for shift in (0..70).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte < 0x80 {
+                return Ok(((value >> 1) as i64) ^ -((value & 1) as i64));
+            }
+        }
+        Err(Error::msg(format!(
+            "Invalid Avro varint at byte {}",
+            self.offset
+        )))
+    }
+
+    /// Reads the item count of an array or map block, skipping the byte size that
+    /// follows a negative count.
+    fn block_count(&mut self) -> Result<usize, Error> {
+        let count = self.long()?;
+        if count < 0 {
+            self.long()?;
+        }
+        usize::try_from(count.unsigned_abs())
+            .map_err(|_| Error::msg(format!("Invalid Avro block count at byte {}", self.offset)))
+    }
+
+    fn bytes(&mut self) -> Result<&[u8], Error> {
+        let len = usize::try_from(self.long()?)
+            .map_err(|_| Error::msg(format!("Invalid Avro length at byte {}", self.offset)))?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let offset = self.offset;
+        String::from_utf8(self.bytes()?.to_vec())
+            .map_err(|_| Error::msg(format!("Invalid UTF-8 in Avro string at byte {}", offset)))
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], Error> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.input.len())
+            .ok_or_else(|| Error::msg(format!("Truncated Avro input at byte {}", self.offset)))?;
+        let bytes = &self.input[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+}
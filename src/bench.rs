@@ -0,0 +1,267 @@
+use crate::parser::{parse_json, parse_json5, ParseErrorDetails, ParserError};
+use anyhow::{Context, Error, Result};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+/// Standard JSON benchmark corpora, as used by serde_json and simdjson, with their
+/// download locations.
+pub const CORPORA: [(&str, &str); 3] = [
+    (
+        "canada",
+        "https://raw.githubusercontent.com/serde-rs/json-benchmark/master/data/canada.json",
+    ),
+    (
+        "citm_catalog",
+        "https://raw.githubusercontent.com/serde-rs/json-benchmark/master/data/citm_catalog.json",
+    ),
+    (
+        "twitter",
+        "https://raw.githubusercontent.com/serde-rs/json-benchmark/master/data/twitter.json",
+    ),
+];
+
+/// A parser measured by `run_bench`.
+pub type ParseFn = fn(&str) -> Result<Value, ParserError>;
+
+/// Parsers measured by `run_bench`: `serde_json`, which reads the CLI's input, and the
+/// pest grammars of `parse_json` and `parse_json5`.
+pub const PARSERS: [(&str, ParseFn); 3] = [
+    ("serde_json", |text| {
+        serde_json::from_str(text).map_err(|e| {
+            ParserError::JsonParseError(Box::new(ParseErrorDetails::from_serde(&e, text)))
+        })
+    }),
+    ("parse_json", parse_json),
+    ("json5", parse_json5),
+];
+
+/// Returns the corpus cache directory: `$JSON_PARSER_CACHE`, else
+/// `$XDG_CACHE_HOME/json-parser/corpora`, else `~/.cache/json-parser/corpora`.
+pub fn default_cache_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("JSON_PARSER_CACHE") {
+        return PathBuf::from(dir);
+    }
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .unwrap_or_else(|| PathBuf::from(".cache"));
+    cache.join("json-parser").join("corpora")
+}
+
+/// Downloads the `CORPORA` that are not cached yet, using the system `curl`.
+///
+/// Each file is checked to be valid JSON before it is moved into the cache, so an
+/// interrupted or failed download is fetched again next time.
+///
+/// # Arguments
+///
+/// * `cache_dir` - The cache directory, created if needed.
+///
+/// # Returns
+///
+/// * `Result<Vec<(String, PathBuf)>, Error>` - Every corpus with its cached file, or an
+///   error if a download fails.
+pub fn fetch_corpora(cache_dir: &Path) -> Result<Vec<(String, PathBuf)>, Error> {
+    fs::create_dir_all(cache_dir)
+        .with_context(|| format!("Failed to create cache {}", cache_dir.display()))?;
+    let mut corpora = Vec::new();
+    for (name, url) in CORPORA {
+        let path = cache_dir.join(format!("{}.json", name));
+        if !path.exists() {
+            let partial = path.with_extension("partial");
+            let status = Command::new("curl")
+                .args([
+                    "--fail",
+                    "--silent",
+                    "--show-error",
+                    "--location",
+                    "--output",
+                ])
+                .arg(&partial)
+                .arg(url)
+                .status()
+                .context("Failed to run curl; it is needed to download corpora")?;
+            if !status.success() {
+                let _ = fs::remove_file(&partial);
+                return Err(Error::msg(format!(
+                    "Failed to download {} from {}",
+                    name, url
+                )));
+            }
+            let text = fs::read_to_string(&partial)?;
+            if let Err(e) = serde_json::from_str::<Value>(&text) {
+                let _ = fs::remove_file(&partial);
+                return Err(Error::msg(format!(
+                    "Downloaded {} is not valid JSON: {}",
+                    name, e
+                )));
+            }
+            fs::rename(&partial, &path)?;
+        }
+        corpora.push((name.to_string(), path));
+    }
+    Ok(corpora)
+}
+
+/// Throughput of one parser on one corpus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub corpus: String,
+    /// The name of the parser in `PARSERS`.
+    pub parser: String,
+    /// Size of the corpus file.
+    pub bytes: u64,
+    pub iterations: u32,
+    /// Total time of the measured iterations.
+    pub seconds: f64,
+}
+
+impl BenchResult {
+    /// Returns the throughput in megabytes (10^6 bytes) per second.
+    pub fn mb_per_s(&self) -> f64 {
+        self.bytes as f64 * f64::from(self.iterations) / self.seconds.max(f64::EPSILON) / 1e6
+    }
+
+    /// Returns the name of this result in reports, e.g. `canada/serde_json`.
+    pub fn name(&self) -> String {
+        format!("{}/{}", self.corpus, self.parser)
+    }
+}
+
+/// Measures how fast each of the `PARSERS` parses each corpus, after one warm-up run.
+///
+/// # Arguments
+///
+/// * `corpora` - Corpus names with their files, e.g. from `fetch_corpora`.
+/// * `iterations` - Measured parses per corpus.
+///
+/// # Returns
+///
+/// * `Result<Vec<BenchResult>, Error>` - One result per corpus and parser, or an error
+///   if a file cannot be read or parsed.
+pub fn run_bench(
+    corpora: &[(String, PathBuf)],
+    iterations: u32,
+) -> Result<Vec<BenchResult>, Error> {
+    let iterations = iterations.max(1);
+    let mut results = Vec::new();
+    for (name, path) in corpora {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read corpus {}", path.display()))?;
+        for (parser, parse) in PARSERS {
+            parse(&text).with_context(|| format!("{} failed to parse corpus {}", parser, name))?;
+            let start = Instant::now();
+            for _ in 0..iterations {
+                parse(&text)?;
+            }
+            results.push(BenchResult {
+                corpus: name.clone(),
+                parser: parser.to_string(),
+                bytes: text.len() as u64,
+                iterations,
+                seconds: start.elapsed().as_secs_f64(),
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// Builds the machine-readable report of a benchmark run, which `compare_bench` also
+/// reads as a baseline: `{"benchmarks": {"canada/serde_json": {"bytes": ...,
+/// "iterations": ..., "seconds": ..., "mb_per_s": ...}}}`.
+pub fn bench_report(results: &[BenchResult]) -> Value {
+    let benchmarks: Map<String, Value> = results
+        .iter()
+        .map(|result| {
+            let entry = json!({
+                "bytes": result.bytes,
+                "iterations": result.iterations,
+                "seconds": result.seconds,
+                "mb_per_s": result.mb_per_s(),
+            });
+            (result.name(), entry)
+        })
+        .collect();
+    json!({ "benchmarks": benchmarks })
+}
+
+/// Compares a benchmark report with a baseline report.
+///
+/// Each benchmark gets the baseline and current throughput, the change in percent, and
+/// a `status`: `regression` or `improvement` when the change exceeds the tolerance,
+/// otherwise `ok`; `new` and `missing` mark benchmarks in only one of the reports.
+///
+/// # Arguments
+///
+/// * `current` - The report of this run, from `bench_report`.
+/// * `baseline` - A saved report.
+/// * `tolerance_percent` - The change in throughput, in percent, treated as noise.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The comparison, with the number of `regressions` at the
+///   top level, or an error if a report is malformed.
+pub fn compare_bench(
+    current: &Value,
+    baseline: &Value,
+    tolerance_percent: f64,
+) -> Result<Value, Error> {
+    let throughputs = |report: &Value, which: &str| -> Result<Map<String, Value>> {
+        let benchmarks = report
+            .get("benchmarks")
+            .and_then(Value::as_object)
+            .ok_or_else(|| Error::msg(format!("The {} report has no benchmarks", which)))?;
+        benchmarks
+            .iter()
+            .map(|(name, entry)| {
+                let mb_per_s = entry
+                    .get("mb_per_s")
+                    .and_then(Value::as_f64)
+                    .ok_or_else(|| {
+                        Error::msg(format!("The {} report has no mb_per_s for {}", which, name))
+                    })?;
+                Ok((name.clone(), Value::from(mb_per_s)))
+            })
+            .collect()
+    };
+    let current = throughputs(current, "current")?;
+    let baseline = throughputs(baseline, "baseline")?;
+    let mut benchmarks = Map::new();
+    let mut regressions = 0;
+    for name in current.keys().chain(baseline.keys()) {
+        if benchmarks.contains_key(name) {
+            continue;
+        }
+        let (now, then) = (current.get(name), baseline.get(name));
+        let entry = match (now.and_then(Value::as_f64), then.and_then(Value::as_f64)) {
+            (Some(now), Some(then)) => {
+                let change = (now - then) / then.max(f64::EPSILON) * 100.0;
+                let status = if change < -tolerance_percent {
+                    regressions += 1;
+                    "regression"
+                } else if change > tolerance_percent {
+                    "improvement"
+                } else {
+                    "ok"
+                };
+                json!({
+                    "baseline_mb_per_s": then,
+                    "current_mb_per_s": now,
+                    "change_percent": (change * 10.0).round() / 10.0,
+                    "status": status,
+                })
+            }
+            (Some(now), None) => json!({ "current_mb_per_s": now, "status": "new" }),
+            _ => json!({ "baseline_mb_per_s": then, "status": "missing" }),
+        };
+        benchmarks.insert(name.clone(), entry);
+    }
+    Ok(json!({
+        "tolerance_percent": tolerance_percent,
+        "regressions": regressions,
+        "benchmarks": benchmarks,
+    }))
+}
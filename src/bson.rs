@@ -0,0 +1,656 @@
+use crate::cbor::{base64_decode, base64_encode, BASE64};
+use crate::datetime::{format_date, parse_date};
+use anyhow::{Error, Result};
+use serde_json::{json, Map, Number, Value};
+
+/// Deepest nesting of documents and arrays `bson_to_json` accepts, so a malicious
+/// dump cannot exhaust the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Largest coefficient of a Decimal128, 10^34 - 1.
+const MAX_DECIMAL_COEFFICIENT: u128 = 10u128.pow(34) - 1;
+
+/// Options for `bson_to_json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BsonOptions {
+    /// Writes canonical Extended JSON, which keeps every numeric type:
+    /// `{"$numberInt": "1"}`, `{"$numberLong": "1"}`, `{"$numberDouble": "1.5"}` and
+    /// dates as `{"$date": {"$numberLong": "<milliseconds>"}}`. Otherwise relaxed
+    /// Extended JSON is written, with plain numbers and RFC 3339 dates.
+    pub canonical: bool,
+}
+
+/// Encodes JSON as BSON, the format of MongoDB and its `mongodump` files.
+///
+/// An object becomes one document and an array of objects a dump, its documents one
+/// after another. Integers are written as 32-bit integers when they fit and as 64-bit
+/// integers otherwise, other numbers as doubles. MongoDB Extended JSON v2 values, such
+/// as `{"$oid": "..."}`, `{"$date": "..."}` and `{"$binary": {"base64": "...",
+/// "subType": "00"}}`, are written as their BSON types.
+///
+/// # Arguments
+///
+/// * `json` - The document, or the array of documents.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, Error>` - The BSON bytes, or an error if `json` is not a document
+///   or an array of them, a key contains a NUL character, or an Extended JSON value is
+///   malformed.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn json_to_bson(json: &Value) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    match json {
+        Value::Object(map) => encode_document(map.iter(), &mut out)?,
+        Value::Array(documents) => {
+            for (index, document) in documents.iter().enumerate() {
+                let map = document.as_object().ok_or_else(|| {
+                    Error::msg(format!(
+                        "Element {} is not an object and cannot be a BSON document",
+                        index
+                    ))
+                })?;
+                encode_document(map.iter(), &mut out)?;
+            }
+        }
+        _ => {
+            return Err(Error::msg(
+                "Only an object or an array of objects can be converted to BSON",
+            ))
+        }
+    }
+    Ok(out)
+}
+
+/// Decodes BSON into MongoDB Extended JSON v2.
+///
+/// ObjectIds become `{"$oid": "<hex>"}`, dates `{"$date": "<RFC 3339>"}` (or
+/// `{"$date": {"$numberLong": "..."}}` before 1970 and after 9999), binary data
+/// `{"$binary": {"base64": "...", "subType": "<hex>"}}`, and the other types their
+/// Extended JSON forms, such as `$timestamp`, `$regularExpression` and
+/// `$numberDecimal`. `json_to_bson` writes them back as the same types.
+///
+/// # Arguments
+///
+/// * `input` - The BSON bytes: one document, or a `mongodump` file of several.
+/// * `options` - Relaxed or canonical Extended JSON.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The document, or an array of the documents of a dump
+///   with several, or an error if the input is truncated, malformed or nested too
+///   deeply.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(bytes = input.len()))
+)]
+pub fn bson_to_json(input: &[u8], options: &BsonOptions) -> Result<Value, Error> {
+    let mut decoder = Decoder {
+        input,
+        offset: 0,
+        options,
+    };
+    let mut documents = Vec::new();
+    while decoder.offset < input.len() {
+        documents.push(Value::Object(decoder.document(0)?.into_iter().collect()));
+    }
+    Ok(if documents.len() == 1 {
+        documents.remove(0)
+    } else {
+        Value::Array(documents)
+    })
+}
+
+/// Writes a document of the given elements, recursively.
+fn encode_document<'a>(
+    elements: impl Iterator<Item = (&'a String, &'a Value)>,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    for (key, value) in elements {
+        let kind = out.len();
+        out.push(0);
+        write_cstring(out, key)?;
+        out[kind] = encode_value(value, out)?;
+    }
+    out.push(0);
+    patch_length(out, start)
+}
+
+/// Writes the value of an element and returns its BSON type.
+fn encode_value(value: &Value, out: &mut Vec<u8>) -> Result<u8, Error> {
+    let kind = match value {
+        Value::Null => 0x0a,
+        Value::Bool(flag) => {
+            out.push(u8::from(*flag));
+            0x08
+        }
+        Value::Number(number) => match number.as_i64() {
+            Some(n) => match i32::try_from(n) {
+                Ok(n) => {
+                    out.extend_from_slice(&n.to_le_bytes());
+                    0x10
+                }
+                Err(_) => {
+                    out.extend_from_slice(&n.to_le_bytes());
+                    0x12
+                }
+            },
+            None => {
+                let float = number.as_f64().unwrap_or(f64::NAN);
+                out.extend_from_slice(&float.to_le_bytes());
+                0x01
+            }
+        },
+        Value::String(text) => {
+            write_string(out, text);
+            0x02
+        }
+        Value::Array(items) => {
+            let keys: Vec<String> = (0..items.len()).map(|index| index.to_string()).collect();
+            encode_document(keys.iter().zip(items), out)?;
+            0x04
+        }
+        Value::Object(map) => match encode_extended(map, out)? {
+            Some(kind) => kind,
+            None => {
+                encode_document(map.iter(), out)?;
+                0x03
+            }
+        },
+    };
+    Ok(kind)
+}
+
+/// Writes an Extended JSON value such as `{"$oid": "..."}` as its BSON type, returning
+/// the type, or `None` if the object is a plain document.
+fn encode_extended(map: &Map<String, Value>, out: &mut Vec<u8>) -> Result<Option<u8>, Error> {
+    let with_scope = map.len() == 2 && map.contains_key("$code") && map.contains_key("$scope");
+    let Some((key, value)) = map.iter().next().filter(|_| map.len() == 1 || with_scope) else {
+        return Ok(None);
+    };
+    let invalid = || Error::msg(format!("Invalid Extended JSON {}: {}", key, value));
+    let text = || value.as_str().ok_or_else(invalid);
+    let field = |name: &str| value.get(name).ok_or_else(invalid);
+    let kind = match key.as_str() {
+        "$oid" => {
+            let id = text().ok().and_then(object_id).ok_or_else(invalid)?;
+            out.extend_from_slice(&id);
+            0x07
+        }
+        "$date" => {
+            let millis = match value {
+                Value::String(date) => {
+                    parse_date(date, None).map(|seconds| (seconds * 1000.0).round() as i64)
+                }
+                Value::Object(_) => number_long(value),
+                _ => value.as_i64(),
+            };
+            out.extend_from_slice(&millis.ok_or_else(invalid)?.to_le_bytes());
+            0x09
+        }
+        "$binary" => {
+            let bytes = field("base64")?
+                .as_str()
+                .and_then(|text| base64_decode(text, BASE64))
+                .ok_or_else(invalid)?;
+            let subtype = field("subType")?
+                .as_str()
+                .and_then(|text| u8::from_str_radix(text, 16).ok())
+                .ok_or_else(invalid)?;
+            out.extend_from_slice(&length(bytes.len())?.to_le_bytes());
+            out.push(subtype);
+            out.extend_from_slice(&bytes);
+            0x05
+        }
+        "$numberInt" => {
+            let n = text()?.parse::<i32>().map_err(|_| invalid())?;
+            out.extend_from_slice(&n.to_le_bytes());
+            0x10
+        }
+        "$numberLong" => {
+            let n = text()?.parse::<i64>().map_err(|_| invalid())?;
+            out.extend_from_slice(&n.to_le_bytes());
+            0x12
+        }
+        "$numberDouble" => {
+            let float = match text()? {
+                "Infinity" => f64::INFINITY,
+                "-Infinity" => f64::NEG_INFINITY,
+                "NaN" => f64::NAN,
+                number => number.parse::<f64>().map_err(|_| invalid())?,
+            };
+            out.extend_from_slice(&float.to_le_bytes());
+            0x01
+        }
+        "$numberDecimal" => {
+            let bits = parse_decimal128(text()?).ok_or_else(invalid)?;
+            out.extend_from_slice(&bits.to_le_bytes());
+            0x13
+        }
+        "$timestamp" => {
+            let part = |name: &str| {
+                field(name)?
+                    .as_u64()
+                    .and_then(|n| u32::try_from(n).ok())
+                    .ok_or_else(invalid)
+            };
+            out.extend_from_slice(&part("i")?.to_le_bytes());
+            out.extend_from_slice(&part("t")?.to_le_bytes());
+            0x11
+        }
+        "$regularExpression" => {
+            let pattern = field("pattern")?.as_str().ok_or_else(invalid)?;
+            let flags = field("options")?.as_str().ok_or_else(invalid)?;
+            write_cstring(out, pattern)?;
+            write_cstring(out, flags)?;
+            0x0b
+        }
+        "$code" if with_scope => {
+            let scope = map["$scope"].as_object().ok_or_else(invalid)?;
+            let start = out.len();
+            out.extend_from_slice(&[0; 4]);
+            write_string(out, text()?);
+            encode_document(scope.iter(), out)?;
+            patch_length(out, start)?;
+            0x0f
+        }
+        "$code" => {
+            write_string(out, text()?);
+            0x0d
+        }
+        "$symbol" => {
+            write_string(out, text()?);
+            0x0e
+        }
+        "$dbPointer" => {
+            let namespace = field("$ref")?.as_str().ok_or_else(invalid)?;
+            let id = field("$id")?
+                .get("$oid")
+                .and_then(Value::as_str)
+                .and_then(object_id)
+                .ok_or_else(invalid)?;
+            write_string(out, namespace);
+            out.extend_from_slice(&id);
+            0x0c
+        }
+        "$undefined" => 0x06,
+        "$minKey" => 0xff,
+        "$maxKey" => 0x7f,
+        _ => return Ok(None),
+    };
+    Ok(Some(kind))
+}
+
+/// Reads the milliseconds of `{"$numberLong": "..."}`.
+fn number_long(value: &Value) -> Option<i64> {
+    value.get("$numberLong")?.as_str()?.parse().ok()
+}
+
+/// Reads the 24 hex digits of an ObjectId.
+fn object_id(hex: &str) -> Option<[u8; 12]> {
+    if hex.len() != 24 || !hex.is_ascii() {
+        return None;
+    }
+    let mut id = [0; 12];
+    for (index, byte) in id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(id)
+}
+
+/// Writes a string as its length, including the terminating NUL, its UTF-8 bytes and NUL.
+fn write_string(out: &mut Vec<u8>, text: &str) {
+    out.extend_from_slice(&(text.len() as i32 + 1).to_le_bytes());
+    out.extend_from_slice(text.as_bytes());
+    out.push(0);
+}
+
+/// Writes a NUL-terminated key or regular expression part.
+fn write_cstring(out: &mut Vec<u8>, text: &str) -> Result<(), Error> {
+    if text.contains('\0') {
+        return Err(Error::msg(format!(
+            "BSON keys and patterns cannot contain NUL: {:?}",
+            text
+        )));
+    }
+    out.extend_from_slice(text.as_bytes());
+    out.push(0);
+    Ok(())
+}
+
+/// Fills in the length of the item written from `start`.
+fn patch_length(out: &mut [u8], start: usize) -> Result<(), Error> {
+    let len = length(out.len() - start)?;
+    out[start..start + 4].copy_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn length(len: usize) -> Result<i32, Error> {
+    i32::try_from(len).map_err(|_| Error::msg("BSON documents are limited to 2 GiB"))
+}
+
+/// Reads BSON documents from a byte slice.
+struct Decoder<'a> {
+    input: &'a [u8],
+    offset: usize,
+    options: &'a BsonOptions,
+}
+
+impl Decoder<'_> {
+    /// Reads a document as its elements in order.
+    fn document(&mut self, depth: usize) -> Result<Vec<(String, Value)>, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::msg(format!(
+                "BSON nesting deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+        let start = self.offset;
+        let end = usize::try_from(self.int32()?)
+            .ok()
+            .filter(|len| *len >= 5)
+            .and_then(|len| start.checked_add(len))
+            .filter(|end| *end <= self.input.len())
+            .ok_or_else(|| Error::msg(format!("Invalid BSON document length at byte {}", start)))?;
+        let mut elements = Vec::new();
+        while self.offset < end - 1 {
+            let kind = self.byte()?;
+            let key = self.cstring()?;
+            let value = self.value(kind, depth)?;
+            elements.push((key, value));
+        }
+        if self.offset != end - 1 || self.byte()? != 0 {
+            return Err(Error::msg(format!(
+                "BSON document at byte {} does not end where its length says",
+                start
+            )));
+        }
+        Ok(elements)
+    }
+
+    /// Reads the value of an element of the given type.
+    fn value(&mut self, kind: u8, depth: usize) -> Result<Value, Error> {
+        let start = self.offset;
+        let value = match kind {
+            0x01 => {
+                let float = f64::from_le_bytes(self.take(8)?.try_into().unwrap());
+                match Number::from_f64(float) {
+                    Some(number) if !self.options.canonical => Value::Number(number),
+                    _ => json!({ "$numberDouble": format_double(float) }),
+                }
+            }
+            0x02 => Value::String(self.string()?),
+            0x03 => Value::Object(self.document(depth + 1)?.into_iter().collect()),
+            0x04 => Value::Array(
+                self.document(depth + 1)?
+                    .into_iter()
+                    .map(|(_, value)| value)
+                    .collect(),
+            ),
+            0x05 => {
+                let len = usize::try_from(self.int32()?).map_err(|_| self.truncated())?;
+                let subtype = self.byte()?;
+                let bytes = self.take(len)?;
+                json!({ "$binary": {
+                    "base64": base64_encode(bytes, BASE64, true),
+                    "subType": format!("{:02x}", subtype),
+                } })
+            }
+            0x06 => json!({ "$undefined": true }),
+            0x07 => json!({ "$oid": self.object_id()? }),
+            0x08 => match self.byte()? {
+                0 => Value::Bool(false),
+                1 => Value::Bool(true),
+                _ => {
+                    return Err(Error::msg(format!(
+                        "Invalid BSON boolean at byte {}",
+                        start
+                    )))
+                }
+            },
+            0x09 => {
+                let millis = self.int64()?;
+                let year_10000 = 253_402_300_800_000;
+                if self.options.canonical || !(0..year_10000).contains(&millis) {
+                    json!({ "$date": { "$numberLong": millis.to_string() } })
+                } else {
+                    let mut text = format_date((millis / 1000) as f64, None, 0);
+                    if millis % 1000 != 0 {
+                        text.insert_str(text.len() - 1, &format!(".{:03}", millis % 1000));
+                    }
+                    json!({ "$date": text })
+                }
+            }
+            0x0a => Value::Null,
+            0x0b => {
+                let pattern = self.cstring()?;
+                let flags = self.cstring()?;
+                json!({ "$regularExpression": { "pattern": pattern, "options": flags } })
+            }
+            0x0c => {
+                let namespace = self.string()?;
+                let id = self.object_id()?;
+                json!({ "$dbPointer": { "$ref": namespace, "$id": { "$oid": id } } })
+            }
+            0x0d => json!({ "$code": self.string()? }),
+            0x0e => json!({ "$symbol": self.string()? }),
+            0x0f => {
+                self.int32()?;
+                let code = self.string()?;
+                let scope: Map<String, Value> = self.document(depth + 1)?.into_iter().collect();
+                json!({ "$code": code, "$scope": scope })
+            }
+            0x10 => {
+                let n = self.int32()?;
+                if self.options.canonical {
+                    json!({ "$numberInt": n.to_string() })
+                } else {
+                    Value::from(n)
+                }
+            }
+            0x11 => {
+                let increment = self.uint32()?;
+                let timestamp = self.uint32()?;
+                json!({ "$timestamp": { "t": timestamp, "i": increment } })
+            }
+            0x12 => {
+                let n = self.int64()?;
+                if self.options.canonical {
+                    json!({ "$numberLong": n.to_string() })
+                } else {
+                    Value::from(n)
+                }
+            }
+            0x13 => {
+                let bits = u128::from_le_bytes(self.take(16)?.try_into().unwrap());
+                json!({ "$numberDecimal": decimal128_to_string(bits) })
+            }
+            0x7f => json!({ "$maxKey": 1 }),
+            0xff => json!({ "$minKey": 1 }),
+            _ => {
+                return Err(Error::msg(format!(
+                    "Unknown BSON type 0x{:02x} at byte {}",
+                    kind,
+                    start - 1
+                )))
+            }
+        };
+        Ok(value)
+    }
+
+    /// Reads a length-prefixed, NUL-terminated UTF-8 string.
+    fn string(&mut self) -> Result<String, Error> {
+        let start = self.offset;
+        let len = usize::try_from(self.int32()?)
+            .ok()
+            .filter(|len| *len >= 1)
+            .ok_or_else(|| Error::msg(format!("Invalid BSON string at byte {}", start)))?;
+        let bytes = self.take(len)?;
+        let (text, nul) = bytes.split_at(len - 1);
+        if nul != [0] {
+            return Err(Error::msg(format!("Invalid BSON string at byte {}", start)));
+        }
+        String::from_utf8(text.to_vec())
+            .map_err(|_| Error::msg(format!("Invalid UTF-8 in BSON string at byte {}", start)))
+    }
+
+    /// Reads a NUL-terminated UTF-8 string.
+    fn cstring(&mut self) -> Result<String, Error> {
+        let start = self.offset;
+        let len = self.input[start..]
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(|| self.truncated())?;
+        let text = std::str::from_utf8(&self.input[start..start + len])
+            .map_err(|_| Error::msg(format!("Invalid UTF-8 in BSON key at byte {}", start)))?;
+        self.offset += len + 1;
+        Ok(text.to_string())
+    }
+
+    /// Reads an ObjectId as 24 hex digits.
+    fn object_id(&mut self) -> Result<String, Error> {
+        Ok(self
+            .take(12)?
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    fn int32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn uint32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn int64(&mut self) -> Result<i64, Error> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], Error> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.input.len())
+            .ok_or_else(|| self.truncated())?;
+        let bytes = &self.input[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn truncated(&self) -> Error {
+        Error::msg(format!("Truncated BSON input at byte {}", self.offset))
+    }
+}
+
+/// Formats a double for `$numberDouble`, keeping a decimal point on whole numbers.
+fn format_double(float: f64) -> String {
+    match float {
+        f64::INFINITY => "Infinity".to_string(),
+        f64::NEG_INFINITY => "-Infinity".to_string(),
+        _ if float.is_nan() => "NaN".to_string(),
+        _ => format!("{:?}", float),
+    }
+}
+
+/// Formats an IEEE 754 Decimal128 in its binary integer encoding, as the BSON
+/// specification describes: plain notation for small exponents, scientific otherwise.
+fn decimal128_to_string(bits: u128) -> String {
+    let sign = if bits >> 127 == 1 { "-" } else { "" };
+    match bits >> 122 & 0x1f {
+        0x1f => return "NaN".to_string(),
+        0x1e => return format!("{}Infinity", sign),
+        _ => {}
+    }
+    let (biased, coefficient) = if bits >> 125 & 0b11 == 0b11 {
+        (bits >> 111 & 0x3fff, 0)
+    } else {
+        (bits >> 113 & 0x3fff, bits & ((1 << 113) - 1))
+    };
+    let coefficient = if coefficient > MAX_DECIMAL_COEFFICIENT {
+        0
+    } else {
+        coefficient
+    };
+    let exponent = biased as i64 - 6176;
+    let digits = coefficient.to_string();
+    let adjusted = exponent + digits.len() as i64 - 1;
+    let text = if exponent > 0 || adjusted < -6 {
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        let exponent_sign = if adjusted < 0 { '-' } else { '+' };
+        format!(
+            "{}{}{}E{}{}",
+            first,
+            point,
+            rest,
+            exponent_sign,
+            adjusted.abs()
+        )
+    } else if exponent == 0 {
+        digits
+    } else {
+        let point = digits.len() as i64 + exponent;
+        if point > 0 {
+            let (whole, fraction) = digits.split_at(point as usize);
+            format!("{}.{}", whole, fraction)
+        } else {
+            format!("0.{}{}", "0".repeat(-point as usize), digits)
+        }
+    };
+    format!("{}{}", sign, text)
+}
+
+/// Parses a decimal number, `NaN` or `Infinity` as a Decimal128, without rounding.
+fn parse_decimal128(text: &str) -> Option<u128> {
+    let (negative, rest) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let sign = u128::from(negative) << 127;
+    match rest.to_ascii_lowercase().as_str() {
+        "nan" => return Some(0x1f << 122),
+        "inf" | "infinity" => return Some(sign | 0x1e << 122),
+        _ => {}
+    }
+    let (mantissa, exponent) = match rest.find(['e', 'E']) {
+        Some(index) => (&rest[..index], rest[index + 1..].parse::<i64>().ok()?),
+        None => (rest, 0),
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let digits = format!("{}{}", whole, fraction);
+    let digits = digits.trim_start_matches('0');
+    let mut exponent = exponent.checked_sub(fraction.len() as i64)?;
+    let coefficient = if digits.len() > 34 {
+        let significant = digits.trim_end_matches('0');
+        if significant.len() > 34 {
+            return None;
+        }
+        exponent += (digits.len() - significant.len()) as i64;
+        significant.parse::<u128>().ok()?
+    } else {
+        digits.parse::<u128>().unwrap_or(0)
+    };
+    if coefficient == 0 {
+        exponent = exponent.clamp(-6176, 6111);
+    }
+    if !(-6176..=6111).contains(&exponent) {
+        return None;
+    }
+    Some(sign | ((exponent + 6176) as u128) << 113 | coefficient)
+}
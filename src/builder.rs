@@ -0,0 +1,201 @@
+use serde_json::{Map, Value};
+
+/// Entry point for building a `Value` step by step, for documents whose shape is only
+/// known at run time, e.g. fields added in a loop or only when an option is set:
+///
+/// ```text
+/// JsonBuilder::object()
+///     .key("name").value("ann")
+///     .array("tags", |tags| tags.extend(["a", "b"]))
+///     .object("address", |address| address.field("city", "Kyiv"))
+///     .build()
+/// ```
+///
+/// Every builder is consumed by each call, and `key` returns a `KeyBuilder` that only
+/// accepts a value, so a key without a value or a misplaced `build` does not compile.
+pub struct JsonBuilder;
+
+impl JsonBuilder {
+    /// Starts building an object.
+    pub fn object() -> ObjectBuilder {
+        ObjectBuilder::default()
+    }
+
+    /// Starts building an array.
+    pub fn array() -> ArrayBuilder {
+        ArrayBuilder::default()
+    }
+}
+
+/// Builds an object member by member. A key added twice keeps the last value.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectBuilder {
+    map: Map<String, Value>,
+}
+
+impl ObjectBuilder {
+    /// Starts a member whose value is given next with `value`, `array` or `object`.
+    pub fn key(self, key: impl Into<String>) -> KeyBuilder {
+        KeyBuilder {
+            object: self,
+            key: key.into(),
+        }
+    }
+
+    /// Adds a member.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The member name.
+    /// * `value` - Anything convertible to a `Value`: numbers, strings, booleans,
+    ///   options (`None` is `null`), vectors, or a `Value`.
+    pub fn field(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.map.insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds a member if the value is `Some`, and nothing otherwise.
+    pub fn field_opt(self, key: impl Into<String>, value: Option<impl Into<Value>>) -> Self {
+        match value {
+            Some(value) => self.field(key, value),
+            None => self,
+        }
+    }
+
+    /// Adds a member holding an array built by a closure.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The member name.
+    /// * `build` - Fills the array, e.g. `|tags| tags.value("a").value("b")`.
+    pub fn array(
+        self,
+        key: impl Into<String>,
+        build: impl FnOnce(ArrayBuilder) -> ArrayBuilder,
+    ) -> Self {
+        self.key(key).array(build)
+    }
+
+    /// Adds a member holding an object built by a closure.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The member name.
+    /// * `build` - Fills the object, e.g. `|address| address.field("city", "Kyiv")`.
+    pub fn object(
+        self,
+        key: impl Into<String>,
+        build: impl FnOnce(ObjectBuilder) -> ObjectBuilder,
+    ) -> Self {
+        self.key(key).object(build)
+    }
+
+    /// Adds the members of an iterator, e.g. a `HashMap` or the pairs of a loop.
+    pub fn extend<K: Into<String>, V: Into<Value>>(
+        mut self,
+        members: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.map.extend(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        );
+        self
+    }
+
+    /// Applies a closure to the builder if a condition holds, for members that depend
+    /// on run-time options.
+    pub fn when(self, condition: bool, build: impl FnOnce(Self) -> Self) -> Self {
+        if condition {
+            build(self)
+        } else {
+            self
+        }
+    }
+
+    /// Returns the object.
+    pub fn build(self) -> Value {
+        Value::Object(self.map)
+    }
+}
+
+/// A member of an `ObjectBuilder` waiting for its value.
+#[derive(Debug, Clone)]
+#[must_use = "a key needs a value"]
+pub struct KeyBuilder {
+    object: ObjectBuilder,
+    key: String,
+}
+
+impl KeyBuilder {
+    /// Gives the member its value and returns to the object.
+    pub fn value(self, value: impl Into<Value>) -> ObjectBuilder {
+        self.object.field(self.key, value)
+    }
+
+    /// Gives the member an array built by a closure and returns to the object.
+    pub fn array(self, build: impl FnOnce(ArrayBuilder) -> ArrayBuilder) -> ObjectBuilder {
+        self.value(build(ArrayBuilder::default()).build())
+    }
+
+    /// Gives the member an object built by a closure and returns to the object.
+    pub fn object(self, build: impl FnOnce(ObjectBuilder) -> ObjectBuilder) -> ObjectBuilder {
+        self.value(build(ObjectBuilder::default()).build())
+    }
+}
+
+/// Builds an array element by element.
+#[derive(Debug, Clone, Default)]
+pub struct ArrayBuilder {
+    items: Vec<Value>,
+}
+
+impl ArrayBuilder {
+    /// Appends an element.
+    pub fn value(mut self, value: impl Into<Value>) -> Self {
+        self.items.push(value.into());
+        self
+    }
+
+    /// Appends an array built by a closure.
+    pub fn array(self, build: impl FnOnce(ArrayBuilder) -> ArrayBuilder) -> Self {
+        self.value(build(ArrayBuilder::default()).build())
+    }
+
+    /// Appends an object built by a closure.
+    pub fn object(self, build: impl FnOnce(ObjectBuilder) -> ObjectBuilder) -> Self {
+        self.value(build(ObjectBuilder::default()).build())
+    }
+
+    /// Appends the elements of an iterator.
+    pub fn extend<V: Into<Value>>(mut self, items: impl IntoIterator<Item = V>) -> Self {
+        self.items.extend(items.into_iter().map(Into::into));
+        self
+    }
+
+    /// Applies a closure to the builder if a condition holds.
+    pub fn when(self, condition: bool, build: impl FnOnce(Self) -> Self) -> Self {
+        if condition {
+            build(self)
+        } else {
+            self
+        }
+    }
+
+    /// Returns the array.
+    pub fn build(self) -> Value {
+        Value::Array(self.items)
+    }
+}
+
+impl From<ObjectBuilder> for Value {
+    fn from(builder: ObjectBuilder) -> Value {
+        builder.build()
+    }
+}
+
+impl From<ArrayBuilder> for Value {
+    fn from(builder: ArrayBuilder) -> Value {
+        builder.build()
+    }
+}
@@ -0,0 +1,192 @@
+use crate::parser::ParserError;
+use std::cell::RefCell;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Most `with_timeout` workers running at once, counting those still finishing a call
+/// that timed out, so adversarial inputs cannot pile up CPU-bound threads.
+pub const MAX_TIMEOUT_WORKERS: usize = 32;
+
+/// Number of running `with_timeout` workers, and the signal that one finished.
+static WORKERS: Mutex<usize> = Mutex::new(0);
+static WORKER_FINISHED: Condvar = Condvar::new();
+
+/// A flag another thread can set to abort long-running library calls, such as
+/// `sort_ndjson` on a multi-gigabyte file or `migrate_records` on a large batch.
+///
+/// Clones share the flag: keep one to call `cancel`, e.g. from a UI or signal handler,
+/// and pass another to the worker thread, which runs the calls inside `run`.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+/// The error of a call stopped by its `CancellationToken`; `error.is::<Cancelled>()`
+/// tells cancellations apart from failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Operation cancelled")]
+pub struct Cancelled;
+
+thread_local! {
+    static TOKENS: RefCell<Vec<CancellationToken>> = const { RefCell::new(Vec::new()) };
+    /// The token of the `with_timeout` call this worker thread runs, whose result is
+    /// dropped once it is cancelled.
+    static ABANDONED: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+/// A slot of `MAX_TIMEOUT_WORKERS`, released when its worker ends, even by a panic.
+struct WorkerSlot;
+
+impl WorkerSlot {
+    /// Waits until `deadline` for a free slot.
+    fn acquire(deadline: Instant) -> Option<WorkerSlot> {
+        let mut running = WORKERS.lock().unwrap_or_else(|e| e.into_inner());
+        while *running >= MAX_TIMEOUT_WORKERS {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return None;
+            }
+            running = WORKER_FINISHED
+                .wait_timeout(running, left)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        *running += 1;
+        Some(WorkerSlot)
+    }
+}
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        *WORKERS.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        WORKER_FINISHED.notify_one();
+    }
+}
+
+/// Removes a token from this thread when its run ends, even by a panic.
+struct TokenGuard;
+
+impl Drop for TokenGuard {
+    fn drop(&mut self) {
+        TOKENS.with(|tokens| tokens.borrow_mut().pop());
+    }
+}
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    /// Asks the calls running under this token to stop, at their next record.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether `cancel` was called on this token or one of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Runs library calls that stop once this token is cancelled, e.g.
+    /// `token.run(|| sort_ndjson(input, output, &options))`.
+    ///
+    /// Streaming, batch and analysis calls on this thread check the token for every
+    /// record. Calls that produce a report, such as `route_ndjson`, `dedup_ndjson` and
+    /// `migrate_records`, then return the partial report with `cancelled` set; the
+    /// others fail with `Cancelled`. Runs may be nested; any cancelled token stops them.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The calls to run.
+    ///
+    /// # Returns
+    ///
+    /// * `T` - The result of `operation`.
+    pub fn run<T>(&self, operation: impl FnOnce() -> T) -> T {
+        TOKENS.with(|tokens| tokens.borrow_mut().push(self.clone()));
+        let _guard = TokenGuard;
+        operation()
+    }
+}
+
+/// Runs library calls on a worker thread and stops waiting for them after `timeout`,
+/// protecting services from adversarial inputs that make parsing, validation or queries
+/// take far longer than their size suggests, e.g.
+/// `with_timeout(Duration::from_secs(2), move || parse_json(&text))`.
+///
+/// On timeout the worker runs under a cancelled `CancellationToken`, so streaming, batch
+/// and analysis calls stop at their next record, parsing at its next object or array,
+/// and validation and JSONPath queries at their next node; their result is dropped.
+/// Only the grammar matching of a single parse cannot be interrupted, so at most
+/// `MAX_TIMEOUT_WORKERS` workers run at once: a call waits for a free worker within
+/// its timeout. Tokens the caller runs under also apply to the worker, and a panic of
+/// `operation` is resumed in the caller.
+///
+/// # Arguments
+///
+/// * `timeout` - The wall-clock time to wait.
+/// * `operation` - The calls to run; it owns its input, as it may outlive this call.
+///
+/// # Returns
+///
+/// * `Result<T, ParserError>` - The result of `operation`, or `ParserError::Timeout` if it
+///   did not finish in time.
+pub fn with_timeout<T, F>(timeout: Duration, operation: F) -> Result<T, ParserError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let deadline = Instant::now() + timeout;
+    let Some(slot) = WorkerSlot::acquire(deadline) else {
+        return Err(ParserError::Timeout(timeout));
+    };
+    let token = CancellationToken::new();
+    let mut tokens = TOKENS.with(|tokens| tokens.borrow().clone());
+    tokens.push(token.clone());
+    let abandoned = token.clone();
+    let (sender, receiver) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        let _slot = slot;
+        TOKENS.with(|current| *current.borrow_mut() = tokens);
+        ABANDONED.with(|current| *current.borrow_mut() = Some(abandoned));
+        // The caller may have stopped waiting, so a failed send is expected.
+        let _ = sender.send(operation());
+    });
+    let left = deadline.saturating_duration_since(Instant::now());
+    match receiver.recv_timeout(left) {
+        Ok(result) => Ok(result),
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            token.cancel();
+            Err(ParserError::Timeout(timeout))
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => match worker.join() {
+            Err(payload) => panic::resume_unwind(payload),
+            Ok(()) => unreachable!("the with_timeout worker ended without a result"),
+        },
+    }
+}
+
+/// Returns whether this thread runs a `with_timeout` call that timed out, whose result
+/// is dropped, so that calls without a way to report cancellation can stop early.
+pub(crate) fn is_abandoned() -> bool {
+    ABANDONED.with(|token| token.borrow().as_ref().is_some_and(CancellationToken::is_cancelled))
+}
+
+/// Returns whether a token the current call runs under was cancelled.
+pub(crate) fn is_cancelled() -> bool {
+    TOKENS.with(|tokens| tokens.borrow().iter().any(CancellationToken::is_cancelled))
+}
+
+/// Fails with `Cancelled` if a token the current call runs under was cancelled.
+pub(crate) fn check() -> Result<(), Cancelled> {
+    if is_cancelled() {
+        Err(Cancelled)
+    } else {
+        Ok(())
+    }
+}
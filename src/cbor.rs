@@ -0,0 +1,588 @@
+use crate::datetime::{format_date, parse_date};
+use anyhow::{Error, Result};
+use serde_json::{json, Map, Number, Value};
+
+/// Deepest nesting of arrays, maps and tags `cbor_to_json` accepts, so a malicious
+/// payload cannot exhaust the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Options for `cbor_to_json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CborOptions {
+    /// Keeps what JSON has no type for as `{"type": ..., "value": ...}` objects, which
+    /// `json_to_cbor` writes back as such: `datetime` (tags 0 and 1, as RFC 3339),
+    /// `bignum` (tags 2 and 3, as decimal digits) and `bytes` (byte strings, as
+    /// unpadded base64url). Otherwise these become plain strings, and bignums that fit
+    /// in 64 bits become numbers.
+    pub annotate_types: bool,
+}
+
+/// Encodes JSON as CBOR (RFC 8949).
+///
+/// Integers use the shortest encoding, floats are written as single precision when
+/// that is exact and as double precision otherwise, and objects become maps with text
+/// keys. Objects annotated as by `cbor_to_json` with `annotate_types` are written as
+/// tagged datetimes (tag 0), bignums (tags 2 and 3) or byte strings.
+///
+/// # Arguments
+///
+/// * `json` - The value to encode.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, Error>` - The CBOR bytes, or an error if an annotated value is
+///   malformed, e.g. a datetime that is not RFC 3339.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn json_to_cbor(json: &Value) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    encode(json, &mut out)?;
+    Ok(out)
+}
+
+/// Decodes CBOR (RFC 8949) into JSON, following its section 6.1 on converting to JSON.
+///
+/// Datetimes (tags 0 and 1) become RFC 3339 strings, bignums (tags 2 and 3) numbers
+/// when they fit in 64 bits, byte strings unpadded base64url strings, `undefined`,
+/// NaN and infinities `null`, and map keys that are not text their JSON text. Other
+/// tags are dropped, keeping the tagged value. See `CborOptions` to keep the types.
+///
+/// # Arguments
+///
+/// * `input` - The CBOR bytes, holding one data item.
+/// * `options` - Type annotation.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The JSON value, or an error if the input is truncated,
+///   malformed, nested too deeply, or followed by other bytes.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(bytes = input.len()))
+)]
+pub fn cbor_to_json(input: &[u8], options: &CborOptions) -> Result<Value, Error> {
+    let mut decoder = Decoder {
+        input,
+        offset: 0,
+        options,
+    };
+    let value = decoder.item(0)?;
+    if decoder.offset != input.len() {
+        return Err(Error::msg(format!(
+            "Unexpected data after the CBOR item at byte {}",
+            decoder.offset
+        )));
+    }
+    Ok(value)
+}
+
+/// Writes a value, recursively.
+fn encode(json: &Value, out: &mut Vec<u8>) -> Result<(), Error> {
+    match json {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(number) => {
+            if let Some(n) = number.as_u64() {
+                write_head(out, 0, n);
+            } else if let Some(n) = number.as_i64() {
+                write_head(out, 1, !(n as u64));
+            } else {
+                let float = number.as_f64().unwrap_or(f64::NAN);
+                if f64::from(float as f32) == float {
+                    out.push(0xfa);
+                    out.extend_from_slice(&(float as f32).to_be_bytes());
+                } else {
+                    out.push(0xfb);
+                    out.extend_from_slice(&float.to_be_bytes());
+                }
+            }
+        }
+        Value::String(text) => {
+            write_head(out, 3, text.len() as u64);
+            out.extend_from_slice(text.as_bytes());
+        }
+        Value::Array(items) => {
+            write_head(out, 4, items.len() as u64);
+            for item in items {
+                encode(item, out)?;
+            }
+        }
+        Value::Object(map) => {
+            if let Some((kind, text)) = annotation(map) {
+                return encode_annotated(kind, text, out);
+            }
+            write_head(out, 5, map.len() as u64);
+            for (key, value) in map {
+                write_head(out, 3, key.len() as u64);
+                out.extend_from_slice(key.as_bytes());
+                encode(value, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the type and text of an object annotated like `{"type": "bignum", "value": "..."}`.
+fn annotation(map: &Map<String, Value>) -> Option<(&str, &str)> {
+    let kind = map.get("type")?.as_str()?;
+    let text = map.get("value")?.as_str()?;
+    (map.len() == 2 && matches!(kind, "datetime" | "bignum" | "bytes")).then_some((kind, text))
+}
+
+/// Writes an annotated datetime, bignum or byte string.
+fn encode_annotated(kind: &str, text: &str, out: &mut Vec<u8>) -> Result<(), Error> {
+    match kind {
+        "datetime" => {
+            if parse_date(text, None).is_none() {
+                return Err(Error::msg(format!("Invalid CBOR datetime: {}", text)));
+            }
+            write_head(out, 6, 0);
+            write_head(out, 3, text.len() as u64);
+            out.extend_from_slice(text.as_bytes());
+        }
+        "bignum" => {
+            let (negative, digits) = match text.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, text),
+            };
+            let mut magnitude = decimal_to_bytes(digits)
+                .filter(|magnitude| !negative || magnitude.iter().any(|byte| *byte != 0))
+                .ok_or_else(|| Error::msg(format!("Invalid CBOR bignum: {}", text)))?;
+            if negative {
+                decrement(&mut magnitude);
+            }
+            write_head(out, 6, if negative { 3 } else { 2 });
+            write_head(out, 2, magnitude.len() as u64);
+            out.extend_from_slice(&magnitude);
+        }
+        _ => {
+            let bytes = base64_decode(text, BASE64URL)
+                .ok_or_else(|| Error::msg(format!("Invalid base64url in CBOR bytes: {}", text)))?;
+            write_head(out, 2, bytes.len() as u64);
+            out.extend_from_slice(&bytes);
+        }
+    }
+    Ok(())
+}
+
+/// Writes the initial byte of a data item with its argument in the shortest form.
+fn write_head(out: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    if argument < 24 {
+        out.push(major | argument as u8);
+    } else if argument <= u64::from(u8::MAX) {
+        out.extend_from_slice(&[major | 24, argument as u8]);
+    } else if argument <= u64::from(u16::MAX) {
+        out.push(major | 25);
+        out.extend_from_slice(&(argument as u16).to_be_bytes());
+    } else if argument <= u64::from(u32::MAX) {
+        out.push(major | 26);
+        out.extend_from_slice(&(argument as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&argument.to_be_bytes());
+    }
+}
+
+/// Reads CBOR data items from a byte slice.
+struct Decoder<'a> {
+    input: &'a [u8],
+    offset: usize,
+    options: &'a CborOptions,
+}
+
+/// The argument of a data item head: a value, or an indefinite length.
+enum Argument {
+    Value(u64),
+    Indefinite,
+}
+
+impl Decoder<'_> {
+    /// Reads one data item.
+    fn item(&mut self, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::msg(format!(
+                "CBOR nesting deeper than {} levels",
+                MAX_DEPTH
+            )));
+        }
+        let start = self.offset;
+        let initial = self.byte()?;
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return self.simple(info, start);
+        }
+        let argument = self.argument(info, start)?;
+        match (major, argument) {
+            (0, Argument::Value(n)) => Ok(Value::from(n)),
+            (1, Argument::Value(n)) => Ok(match i64::try_from(n) {
+                Ok(n) => Value::from(-1 - n),
+                Err(_) => Value::from(-1.0 - n as f64),
+            }),
+            (2, argument) => {
+                let bytes = self.string(2, argument)?;
+                let text = base64_encode(&bytes, BASE64URL, false);
+                Ok(self.annotate("bytes", text))
+            }
+            (3, argument) => {
+                let bytes = self.string(3, argument)?;
+                String::from_utf8(bytes).map(Value::String).map_err(|_| {
+                    Error::msg(format!("Invalid UTF-8 in CBOR text at byte {}", start))
+                })
+            }
+            (4, argument) => {
+                let mut items = Vec::new();
+                match argument {
+                    Argument::Value(n) => {
+                        for _ in 0..n {
+                            items.push(self.item(depth + 1)?);
+                        }
+                    }
+                    Argument::Indefinite => {
+                        while !self.at_break()? {
+                            items.push(self.item(depth + 1)?);
+                        }
+                    }
+                }
+                Ok(Value::Array(items))
+            }
+            (5, argument) => {
+                let mut map = Map::new();
+                let mut remaining = match argument {
+                    Argument::Value(n) => Some(n),
+                    Argument::Indefinite => None,
+                };
+                loop {
+                    match &mut remaining {
+                        Some(0) => break,
+                        Some(n) => *n -= 1,
+                        None if self.at_break()? => break,
+                        None => {}
+                    }
+                    let key = match self.item(depth + 1)? {
+                        Value::String(key) => key,
+                        other => other.to_string(),
+                    };
+                    let value = self.item(depth + 1)?;
+                    map.insert(key, value);
+                }
+                Ok(Value::Object(map))
+            }
+            (6, Argument::Value(tag @ (2 | 3))) => {
+                let content = self.offset;
+                let initial = self.byte()?;
+                if initial >> 5 != 2 {
+                    return Err(Error::msg(format!(
+                        "Invalid content of CBOR tag {} at byte {}",
+                        tag, start
+                    )));
+                }
+                let argument = self.argument(initial & 0x1f, content)?;
+                let magnitude = self.string(2, argument)?;
+                Ok(self.bignum(tag == 3, magnitude))
+            }
+            (6, Argument::Value(tag)) => {
+                let value = self.item(depth + 1)?;
+                self.tagged(tag, value, start)
+            }
+            _ => Err(Error::msg(format!("Invalid CBOR item at byte {}", start))),
+        }
+    }
+
+    /// Converts the value of a tag.
+    fn tagged(&self, tag: u64, value: Value, start: usize) -> Result<Value, Error> {
+        let invalid = || {
+            Error::msg(format!(
+                "Invalid content of CBOR tag {} at byte {}",
+                tag, start
+            ))
+        };
+        match tag {
+            0 => {
+                let text = value.as_str().ok_or_else(invalid)?;
+                Ok(self.annotate("datetime", text.to_string()))
+            }
+            1 => {
+                let seconds = value.as_f64().ok_or_else(invalid)?;
+                let mut text = format_date(seconds, None, 0);
+                let fraction = seconds - seconds.floor();
+                if fraction > 0.0 {
+                    let digits = format!("{:.6}", fraction);
+                    text.insert_str(text.len() - 1, digits[1..].trim_end_matches('0'));
+                }
+                Ok(self.annotate("datetime", text))
+            }
+            _ => Ok(value),
+        }
+    }
+
+    /// Converts the magnitude of a bignum; a negative bignum n is stored as -1 - n.
+    fn bignum(&self, negative: bool, mut magnitude: Vec<u8>) -> Value {
+        if negative {
+            increment(&mut magnitude);
+        }
+        let digits = bytes_to_decimal(&magnitude);
+        let text = if negative {
+            format!("-{}", digits)
+        } else {
+            digits
+        };
+        if self.options.annotate_types {
+            return json!({ "type": "bignum", "value": text });
+        }
+        text.parse::<u64>()
+            .map(Value::from)
+            .or_else(|_| text.parse::<i64>().map(Value::from))
+            .unwrap_or(Value::String(text))
+    }
+
+    /// Wraps a converted value as `{"type": kind, "value": text}` if types are kept.
+    fn annotate(&self, kind: &str, text: String) -> Value {
+        if self.options.annotate_types {
+            json!({ "type": kind, "value": text })
+        } else {
+            Value::String(text)
+        }
+    }
+
+    /// Reads a simple value or float.
+    fn simple(&mut self, info: u8, start: usize) -> Result<Value, Error> {
+        let float = match info {
+            20 => return Ok(Value::Bool(false)),
+            21 => return Ok(Value::Bool(true)),
+            22 | 23 => return Ok(Value::Null),
+            24 => {
+                self.byte()?;
+                return Ok(Value::Null);
+            }
+            25 => {
+                let bytes = self.take(2)?;
+                half_to_f64(u16::from_be_bytes([bytes[0], bytes[1]]))
+            }
+            26 => {
+                let bytes = self.take(4)?;
+                f64::from(f32::from_be_bytes(bytes.try_into().unwrap()))
+            }
+            27 => f64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            0..=19 => return Ok(Value::Null),
+            _ => {
+                return Err(Error::msg(format!(
+                    "Invalid CBOR simple value at byte {}",
+                    start
+                )))
+            }
+        };
+        Ok(Number::from_f64(float).map_or(Value::Null, Value::Number))
+    }
+
+    /// Reads the argument following the initial byte.
+    fn argument(&mut self, info: u8, start: usize) -> Result<Argument, Error> {
+        let value = match info {
+            0..=23 => u64::from(info),
+            24 => u64::from(self.byte()?),
+            25 => u64::from(u16::from_be_bytes(self.take(2)?.try_into().unwrap())),
+            26 => u64::from(u32::from_be_bytes(self.take(4)?.try_into().unwrap())),
+            27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+            31 => return Ok(Argument::Indefinite),
+            _ => return Err(Error::msg(format!("Invalid CBOR item at byte {}", start))),
+        };
+        Ok(Argument::Value(value))
+    }
+
+    /// Reads a byte or text string, joining the chunks of an indefinite-length one.
+    fn string(&mut self, major: u8, argument: Argument) -> Result<Vec<u8>, Error> {
+        match argument {
+            Argument::Value(len) => {
+                let len = usize::try_from(len).map_err(|_| self.truncated())?;
+                Ok(self.take(len)?.to_vec())
+            }
+            Argument::Indefinite => {
+                let mut bytes = Vec::new();
+                while !self.at_break()? {
+                    let start = self.offset;
+                    let initial = self.byte()?;
+                    if initial >> 5 != major {
+                        return Err(Error::msg(format!(
+                            "Invalid chunk of an indefinite-length CBOR string at byte {}",
+                            start
+                        )));
+                    }
+                    match self.argument(initial & 0x1f, start)? {
+                        Argument::Value(len) => {
+                            let len = usize::try_from(len).map_err(|_| self.truncated())?;
+                            bytes.extend_from_slice(self.take(len)?);
+                        }
+                        Argument::Indefinite => {
+                            return Err(Error::msg(format!(
+                                "Nested indefinite-length CBOR string at byte {}",
+                                start
+                            )))
+                        }
+                    }
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Consumes the break code ending an indefinite-length item, if it is next.
+    fn at_break(&mut self) -> Result<bool, Error> {
+        match self.input.get(self.offset) {
+            Some(0xff) => {
+                self.offset += 1;
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+            None => Err(self.truncated()),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], Error> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.input.len())
+            .ok_or_else(|| self.truncated())?;
+        let bytes = &self.input[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn truncated(&self) -> Error {
+        Error::msg(format!("Truncated CBOR input at byte {}", self.offset))
+    }
+}
+
+/// Converts an IEEE 754 half-precision float.
+fn half_to_f64(half: u16) -> f64 {
+    let exponent = i32::from((half >> 10) & 0x1f);
+    let mantissa = f64::from(half & 0x3ff);
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi(exponent - 25),
+    };
+    if half & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Converts a big-endian unsigned integer to decimal digits.
+fn bytes_to_decimal(bytes: &[u8]) -> String {
+    let mut number: Vec<u8> = bytes
+        .iter()
+        .copied()
+        .skip_while(|byte| *byte == 0)
+        .collect();
+    let mut digits = Vec::new();
+    while !number.is_empty() {
+        let mut remainder = 0u32;
+        for byte in &mut number {
+            let value = (remainder << 8) | u32::from(*byte);
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+        let zeros = number.iter().take_while(|byte| **byte == 0).count();
+        number.drain(..zeros);
+    }
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+/// Converts decimal digits to a big-endian unsigned integer.
+fn decimal_to_bytes(digits: &str) -> Option<Vec<u8>> {
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    for digit in digits.bytes() {
+        let mut carry = u32::from(digit - b'0');
+        for byte in bytes.iter_mut().rev() {
+            let value = u32::from(*byte) * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry > 0 {
+            bytes.insert(0, carry as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Adds one to a big-endian unsigned integer.
+fn increment(bytes: &mut Vec<u8>) {
+    for byte in bytes.iter_mut().rev() {
+        let (value, overflow) = byte.overflowing_add(1);
+        *byte = value;
+        if !overflow {
+            return;
+        }
+    }
+    bytes.insert(0, 1);
+}
+
+/// Subtracts one from a positive big-endian unsigned integer.
+fn decrement(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut().rev() {
+        let (value, underflow) = byte.overflowing_sub(1);
+        *byte = value;
+        if !underflow {
+            return;
+        }
+    }
+}
+
+/// The standard base64 alphabet (RFC 4648 section 4).
+pub(crate) const BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The URL- and filename-safe base64 alphabet (RFC 4648 section 5).
+pub(crate) const BASE64URL: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encodes bytes as base64 in `alphabet`, padded with `=` if `padded`. RFC 8949
+/// recommends unpadded base64url for byte strings in JSON.
+pub(crate) fn base64_encode(bytes: &[u8], alphabet: &[u8; 64], padded: bool) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | u32::from(*byte) << (16 - 8 * index)
+        });
+        for index in 0..=chunk.len() {
+            text.push(alphabet[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+        }
+        if padded {
+            text.extend(std::iter::repeat_n('=', 3 - chunk.len()));
+        }
+    }
+    text
+}
+
+/// Decodes base64 in `alphabet`, with or without padding.
+pub(crate) fn base64_decode(text: &str, alphabet: &[u8; 64]) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut group = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = alphabet.iter().position(|symbol| *symbol == c)?;
+        group = group << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,202 @@
+use crate::parser::{format_json, FormatOptions, ParseErrorDetails};
+use serde::{Deserialize, Serialize};
+use std::io;
+use thiserror::Error;
+
+pub use serde_json::{json, Map, Value};
+
+/// The result of the `compat` functions, as `serde_json::Result`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// What kind of problem an `Error` is, as `serde_json::error::Category`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Reading or writing failed.
+    Io,
+    /// The input is not valid JSON.
+    Syntax,
+    /// The input is valid JSON but does not match the type, or the value cannot be
+    /// serialized.
+    Data,
+    /// The input ended in the middle of a value.
+    Eof,
+}
+
+/// The error of the `compat` functions, with the accessors of `serde_json::Error`.
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct Error {
+    category: Category,
+    message: String,
+    details: Option<Box<ParseErrorDetails>>,
+    #[source]
+    io: Option<io::Error>,
+}
+
+impl Error {
+    /// Returns the 1-based line of a syntax error, or 0.
+    pub fn line(&self) -> usize {
+        self.details.as_ref().map_or(0, |details| details.line)
+    }
+
+    /// Returns the 1-based column of a syntax error, or 0.
+    pub fn column(&self) -> usize {
+        self.details.as_ref().map_or(0, |details| details.column)
+    }
+
+    /// Returns what kind of problem the error is.
+    pub fn classify(&self) -> Category {
+        self.category
+    }
+
+    /// Returns `true` if reading or writing failed.
+    pub fn is_io(&self) -> bool {
+        self.category == Category::Io
+    }
+
+    /// Returns `true` if the input is not valid JSON.
+    pub fn is_syntax(&self) -> bool {
+        self.category == Category::Syntax
+    }
+
+    /// Returns `true` if the value does not match the type.
+    pub fn is_data(&self) -> bool {
+        self.category == Category::Data
+    }
+
+    /// Returns `true` if the input ended in the middle of a value.
+    pub fn is_eof(&self) -> bool {
+        self.category == Category::Eof
+    }
+
+    /// Returns the details of a syntax error, as in `ParserError::JsonParseError`: the
+    /// byte offset, the input line and the message.
+    pub fn details(&self) -> Option<&ParseErrorDetails> {
+        self.details.as_deref()
+    }
+
+    /// Wraps an error of reading `input` as JSON.
+    fn syntax(error: serde_json::Error, input: &str) -> Self {
+        let details = ParseErrorDetails::from_serde(&error, input);
+        Error {
+            category: if error.is_eof() {
+                Category::Eof
+            } else {
+                Category::Syntax
+            },
+            message: format!("JSON parse error{}", details),
+            details: Some(Box::new(details)),
+            io: None,
+        }
+    }
+
+    /// Creates an error of a value that does not match its type.
+    fn data(message: String) -> Self {
+        Error {
+            category: Category::Data,
+            message,
+            details: None,
+            io: None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error {
+            category: Category::Io,
+            message: error.to_string(),
+            details: None,
+            io: Some(error),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::data(error.to_string())
+    }
+}
+
+/// Deserializes a value from JSON text, as `serde_json::from_str`, with syntax errors
+/// described as by `parse_format`.
+///
+/// # Arguments
+///
+/// * `s` - The JSON text.
+///
+/// # Returns
+///
+/// * `Result<T>` - The value, or a syntax error with its line and column, or a data
+///   error if the JSON does not match `T`. Fields borrowed from the input, such as
+///   `&str`, are not supported; use owned types.
+pub fn from_str<'a, T: Deserialize<'a>>(s: &'a str) -> Result<T> {
+    let value: Value = serde_json::from_str(s).map_err(|error| Error::syntax(error, s))?;
+    Ok(T::deserialize(value)?)
+}
+
+/// Serializes a value as compact JSON, as `serde_json::to_string`. The value goes
+/// through a `Value`, so object keys are written in sorted order rather than in field
+/// order.
+///
+/// # Arguments
+///
+/// * `value` - The value to serialize.
+///
+/// # Returns
+///
+/// * `Result<String>` - The JSON text, or a data error if the value cannot be
+///   represented, e.g. a map with non-string keys.
+pub fn to_string<T: ?Sized + Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_value(value)?.to_string())
+}
+
+/// Serializes a value as JSON indented by two spaces, as `serde_json::to_string_pretty`,
+/// with `format_json`.
+///
+/// # Arguments
+///
+/// * `value` - The value to serialize.
+///
+/// # Returns
+///
+/// * `Result<String>` - The JSON text, or a data error if the value cannot be
+///   represented.
+pub fn to_string_pretty<T: ?Sized + Serialize>(value: &T) -> Result<String> {
+    let options = FormatOptions {
+        trailing_newline: false,
+        ..FormatOptions::default()
+    };
+    to_string_with(value, &options)
+}
+
+/// Serializes a value as JSON in the style of `options`, e.g. with tabs or compact
+/// arrays, where `serde_json` would need a custom formatter.
+///
+/// # Arguments
+///
+/// * `value` - The value to serialize.
+/// * `options` - The indentation and layout.
+///
+/// # Returns
+///
+/// * `Result<String>` - The JSON text, or a data error if the value cannot be
+///   represented.
+pub fn to_string_with<T: ?Sized + Serialize>(value: &T, options: &FormatOptions) -> Result<String> {
+    Ok(format_json(&serde_json::to_value(value)?, options))
+}
+
+/// Serializes a value as compact JSON into a writer, as `serde_json::to_writer`.
+///
+/// # Arguments
+///
+/// * `writer` - Where to write, e.g. a file or `Vec<u8>`; wrap files in a `BufWriter`.
+/// * `value` - The value to serialize.
+///
+/// # Returns
+///
+/// * `Result<()>` - Nothing, or an I/O error or a data error.
+pub fn to_writer<W: io::Write, T: ?Sized + Serialize>(mut writer: W, value: &T) -> Result<()> {
+    writer.write_all(to_string(value)?.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use crate::path::{Expression, FunctionLibrary, JsonPath};
+use crate::redact::RedactionRules;
+use crate::sandbox::{self, Sandbox};
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Config file read by the CLI when `--config` is not given.
+pub const DEFAULT_CONFIG_FILE: &str = ".json-parser.json";
+
+/// Settings shared by a team through a config file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Config {
+    /// Named queries, run with `query --saved <name>`.
+    #[serde(default)]
+    pub queries: BTreeMap<String, SavedQuery>,
+    /// Expression files defining functions the saved queries can call (see
+    /// `FunctionLibrary`), relative to the config file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<String>,
+    /// Named redaction profiles in the `RedactionRules::from_json` format, so each
+    /// audience of a document gets its own masked view with `redact --profile <name>`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub redaction: BTreeMap<String, Value>,
+    /// The access tokens of `serve`, by the SHA-256 hex digest of the token (see
+    /// `token_digest`), each bound to the redaction profile applied to its responses.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tokens: BTreeMap<String, String>,
+    /// Limits for the untrusted expressions this config's users run: saved queries,
+    /// `route` rules, `compute` migrations and `eval` programs each run in this sandbox.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<Sandbox>,
+}
+
+impl Config {
+    /// Parses a config, checking that every saved query compiles with its defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The config as JSON, e.g.
+    ///   `{ "queries": { "active_users": { "query": "$.users[?(@.age >= ${min_age})]", "params": { "min_age": 18 } } } }`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Config, Error>` - The config, or an error if it is malformed or a
+    ///   function file cannot be read.
+    pub fn from_json(config: &Value) -> Result<Config, Error> {
+        Config::from_json_in(config, Path::new(""))
+    }
+
+    /// Parses a config whose function files are relative to `dir`.
+    fn from_json_in(config: &Value, dir: &Path) -> Result<Config, Error> {
+        let mut config: Config = serde_json::from_value(config.clone())
+            .map_err(|e| Error::msg(format!("Invalid config: {}", e)))?;
+        let mut library = FunctionLibrary::default();
+        for file in &config.functions {
+            let path = dir.join(file);
+            let text = fs::read_to_string(&path).map_err(|e| {
+                Error::msg(format!(
+                    "Failed to read functions {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            library
+                .define(&text)
+                .map_err(|e| Error::msg(format!("Functions {}: {}", path.display(), e)))?;
+        }
+        for saved in config.queries.values_mut() {
+            saved.functions = library.clone();
+            saved.sandbox = config.sandbox;
+        }
+        for (name, rules) in &config.redaction {
+            RedactionRules::from_json(rules)
+                .map_err(|e| Error::msg(format!("Redaction profile '{}': {}", name, e)))?;
+        }
+        for (digest, profile) in &config.tokens {
+            if !config.redaction.contains_key(profile) {
+                return Err(Error::msg(format!(
+                    "Token {} is bound to unknown redaction profile '{}'",
+                    digest, profile
+                )));
+            }
+        }
+        for (name, saved) in &config.queries {
+            let names = saved.parameter_names();
+            if names.iter().all(|param| saved.params.contains_key(param)) {
+                saved
+                    .compile(&BTreeMap::new())
+                    .map_err(|e| Error::msg(format!("Saved query '{}': {}", name, e)))?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Reads a config file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The config file path.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Config, Error>` - The config, or an error if it cannot be read or is malformed.
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Error::msg(format!("Failed to read config {}: {}", path.display(), e)))?;
+        let json: Value = serde_json::from_str(&text)
+            .map_err(|e| Error::msg(format!("Failed to parse config {}: {}", path.display(), e)))?;
+        Config::from_json_in(&json, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Looks up a redaction profile by name.
+    pub fn redaction_profile(&self, name: &str) -> Result<RedactionRules, Error> {
+        let rules = self.redaction.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.redaction.keys().map(String::as_str).collect();
+            Error::msg(format!(
+                "Unknown redaction profile '{}' (available: {})",
+                name,
+                known.join(", ")
+            ))
+        })?;
+        RedactionRules::from_json(rules)
+    }
+
+    /// Looks up a saved query by name.
+    pub fn saved_query(&self, name: &str) -> Result<&SavedQuery, Error> {
+        self.queries.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.queries.keys().map(String::as_str).collect();
+            Error::msg(format!(
+                "Unknown saved query '{}' (available: {})",
+                name,
+                known.join(", ")
+            ))
+        })
+    }
+}
+
+/// A named JSONPath query with an optional transform, shared through the config file.
+///
+/// `${name}` placeholders in `query` and `transform` are replaced by parameter values
+/// written as literals, so a parameter can never change the structure of the query.
+/// Use them where a literal is allowed: in filters (`?(@.role == ${role})`) and
+/// brackets (`$.users[${index}]`).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SavedQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The JSONPath query.
+    pub query: String,
+    /// Expression applied to each result (see `Expression`), e.g. `first + ' ' + last`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+    /// Default parameter values; parameters without a default must be given.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, Value>,
+    /// Functions from the config's `functions` files.
+    #[serde(skip)]
+    pub functions: FunctionLibrary,
+    /// The config's `sandbox`, applied by `run` to the query and to each transform.
+    #[serde(skip)]
+    pub sandbox: Option<Sandbox>,
+}
+
+impl SavedQuery {
+    /// Substitutes parameters and compiles the query and transform.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Parameter values, overriding the defaults.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(JsonPath, Option<Expression>), Error>` - The compiled query and transform, or
+    ///   an error if a parameter is missing, unknown, not a scalar, or the result does not compile.
+    pub fn compile(
+        &self,
+        params: &BTreeMap<String, Value>,
+    ) -> Result<(JsonPath, Option<Expression>), Error> {
+        let names = self.parameter_names();
+        if let Some(unknown) = params.keys().find(|name| !names.contains(name)) {
+            return Err(Error::msg(format!("Unknown parameter '{}'", unknown)));
+        }
+        let mut values = self.params.clone();
+        values.extend(params.clone());
+        let query =
+            JsonPath::compile_with_functions(&substitute(&self.query, &values)?, &self.functions)?;
+        let transform = self
+            .transform
+            .as_deref()
+            .map(|text| {
+                Expression::compile_with_functions(&substitute(text, &values)?, &self.functions)
+            })
+            .transpose()?;
+        Ok((query, transform))
+    }
+
+    /// Runs the query against a document.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The document.
+    /// * `params` - Parameter values, overriding the defaults.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<(String, Value)>, Error>` - Each result with its normalized path; the
+    ///   transform result replaces the value, or `null` if it is undefined. An error if
+    ///   the query or a transform exceeds a limit of `sandbox`.
+    pub fn run(
+        &self,
+        json: &Value,
+        params: &BTreeMap<String, Value>,
+    ) -> Result<Vec<(String, Value)>, Error> {
+        let (query, transform) = self.compile(params)?;
+        let selected = sandbox::run_in(self.sandbox.as_ref(), || query.select_with_paths(json))?;
+        selected
+            .into_iter()
+            .map(|(path, value)| match &transform {
+                Some(expression) => {
+                    let result = sandbox::run_in(self.sandbox.as_ref(), || expression.evaluate(value))?;
+                    Ok((path, result.unwrap_or(Value::Null)))
+                }
+                None => Ok((path, value.clone())),
+            })
+            .collect()
+    }
+
+    /// Returns the placeholder names used by the query and transform.
+    fn parameter_names(&self) -> Vec<String> {
+        let mut names = placeholders(&self.query);
+        if let Some(transform) = &self.transform {
+            names.extend(placeholders(transform));
+        }
+        names
+    }
+}
+
+/// Returns the names of the `${name}` placeholders in a text, in order of appearance.
+fn placeholders(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim().to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &rest[start + end + 1..];
+    }
+    names
+}
+
+/// Replaces each `${name}` placeholder with its value as a literal.
+fn substitute(text: &str, values: &BTreeMap<String, Value>) -> Result<String, Error> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| Error::msg("Unclosed ${ placeholder"))?;
+        let name = rest[start + 2..end].trim();
+        let value = values
+            .get(name)
+            .ok_or_else(|| Error::msg(format!("Missing parameter '{}'", name)))?;
+        if value.is_array() || value.is_object() {
+            return Err(Error::msg(format!(
+                "Parameter '{}' must be a string, number, boolean or null",
+                name
+            )));
+        }
+        result.push_str(&rest[..start]);
+        result.push_str(&value.to_string());
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
@@ -0,0 +1,582 @@
+use crate::parser::{
+    format_json, parse_jsonc, pointer_index, pointer_tokens, FormatOptions, ParserError,
+};
+use anyhow::{Error, Result};
+use serde_json::Value;
+use std::fmt;
+use std::ops::Range;
+
+/// A value in the syntax tree, with the byte span it covers in the source text.
+#[derive(Debug, Clone)]
+enum Node {
+    Object {
+        span: Range<usize>,
+        members: Vec<Member>,
+    },
+    Array {
+        span: Range<usize>,
+        items: Vec<Node>,
+    },
+    Scalar {
+        span: Range<usize>,
+    },
+}
+
+/// An object member; its span runs from the start of the key to the end of the value.
+#[derive(Debug, Clone)]
+struct Member {
+    key: String,
+    key_start: usize,
+    value: Node,
+}
+
+impl Node {
+    fn span(&self) -> Range<usize> {
+        match self {
+            Node::Object { span, .. } | Node::Array { span, .. } | Node::Scalar { span } => {
+                span.clone()
+            }
+        }
+    }
+
+    /// Returns the spans of the members or elements of a container.
+    fn item_spans(&self) -> Vec<Range<usize>> {
+        match self {
+            Node::Object { members, .. } => members
+                .iter()
+                .map(|member| member.key_start..member.value.span().end)
+                .collect(),
+            Node::Array { items, .. } => items.iter().map(Node::span).collect(),
+            Node::Scalar { .. } => Vec::new(),
+        }
+    }
+}
+
+/// A lossless concrete syntax tree for JSON with comments.
+///
+/// The original text is kept byte for byte, including whitespace, comments and
+/// trailing commas. Edits by JSON Pointer only rewrite the spans they touch, and new
+/// values are indented like their neighbors, so commented config files survive a
+/// programmatic change with a minimal diff.
+#[derive(Debug, Clone)]
+pub struct JsonCst {
+    text: String,
+    root: Node,
+}
+
+impl JsonCst {
+    /// Parses JSON or JSONC text into a syntax tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The document; `//` and `/* */` comments and trailing commas are allowed.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<JsonCst, ParserError>` - The tree, or a `JsonParseError` with the position of the syntax error.
+    pub fn parse(text: &str) -> Result<JsonCst, ParserError> {
+        parse_jsonc(text)?;
+        let mut scanner = Scanner { text, pos: 0 };
+        let root = scanner.node();
+        Ok(JsonCst {
+            text: text.to_string(),
+            root,
+        })
+    }
+
+    /// Returns the document as a `serde_json::Value`.
+    pub fn value(&self) -> Value {
+        parse_jsonc(&self.text).unwrap_or(Value::Null)
+    }
+
+    /// Returns the value at a JSON Pointer, or `None` if it does not exist.
+    pub fn get(&self, pointer: &str) -> Option<Value> {
+        let node = self.find(&pointer_tokens(pointer).ok()?)?;
+        parse_jsonc(&self.text[node.span()]).ok()
+    }
+
+    /// Returns the byte range of the value at a JSON Pointer in the text, or `None` if it
+    /// does not exist.
+    pub fn span(&self, pointer: &str) -> Option<Range<usize>> {
+        Some(self.find(&pointer_tokens(pointer).ok()?)?.span())
+    }
+
+    /// Sets the value at a JSON Pointer.
+    ///
+    /// An existing value is replaced in place. A missing object member is added
+    /// after the last member, and `-` appends to an array.
+    ///
+    /// # Arguments
+    ///
+    /// * `pointer` - The JSON Pointer, e.g. `/editor/tabSize`; the empty string replaces the whole document.
+    /// * `value` - The new value.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - An error if the pointer is invalid or its parent does not exist.
+    pub fn set(&mut self, pointer: &str, value: &Value) -> Result<(), Error> {
+        let tokens = pointer_tokens(pointer)?;
+        if let Some(node) = self.find(&tokens) {
+            let span = node.span();
+            let multiline = match tokens.split_last() {
+                Some((_, parent)) => self.is_multiline(self.find(parent).unwrap()),
+                None => self.text.contains('\n'),
+            };
+            let indent = line_indent(&self.text, span.start).to_string();
+            let rendered = self.render(value, &indent, multiline);
+            return self.apply(vec![(span, rendered)]);
+        }
+        self.insert(pointer, value)
+    }
+
+    /// Inserts a value at a JSON Pointer.
+    ///
+    /// In an array the value is inserted before the element at the index (`-` or the
+    /// length appends); in an object a new member is added after the last one.
+    ///
+    /// # Arguments
+    ///
+    /// * `pointer` - The JSON Pointer of the new value.
+    /// * `value` - The value to insert.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - An error if the pointer is invalid, its parent does not
+    ///   exist, or the object member already exists.
+    pub fn insert(&mut self, pointer: &str, value: &Value) -> Result<(), Error> {
+        let tokens = pointer_tokens(pointer)?;
+        let (last, parent) = tokens
+            .split_last()
+            .ok_or_else(|| Error::msg("Cannot insert at the document root"))?;
+        let container = self
+            .find(parent)
+            .ok_or_else(|| Error::msg(format!("Parent of {} does not exist", pointer)))?;
+        let (index, key) = match container {
+            Node::Object { members, .. } => {
+                if members.iter().any(|member| &member.key == last) {
+                    return Err(Error::msg(format!("Member {} already exists", pointer)));
+                }
+                (members.len(), Some(last.as_str()))
+            }
+            Node::Array { items, .. } => {
+                let index = if last == "-" {
+                    items.len()
+                } else {
+                    pointer_index(last)
+                        .filter(|index| *index <= items.len())
+                        .ok_or_else(|| Error::msg(format!("Invalid array index in {}", pointer)))?
+                };
+                (index, None)
+            }
+            Node::Scalar { .. } => {
+                return Err(Error::msg(format!(
+                    "Parent of {} is not a container",
+                    pointer
+                )))
+            }
+        };
+        let edits = self.insert_edits(container, index, key, value);
+        self.apply(edits)
+    }
+
+    /// Removes the value at a JSON Pointer, together with its separating comma and,
+    /// when it sits on its own line, the line and the comment lines right above it.
+    ///
+    /// # Arguments
+    ///
+    /// * `pointer` - The JSON Pointer of the value to remove.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Value, Error>` - The removed value, or an error if it does not exist.
+    pub fn remove(&mut self, pointer: &str) -> Result<Value, Error> {
+        let tokens = pointer_tokens(pointer)?;
+        let (last, parent) = tokens
+            .split_last()
+            .ok_or_else(|| Error::msg("Cannot remove the document root"))?;
+        let removed = self
+            .get(pointer)
+            .ok_or_else(|| Error::msg(format!("Path not found: {}", pointer)))?;
+        let container = self.find(parent).unwrap();
+        let index = match container {
+            Node::Object { members, .. } => members
+                .iter()
+                .rposition(|member| &member.key == last)
+                .unwrap(),
+            _ => pointer_index(last).unwrap(),
+        };
+        let edits = self.remove_edits(container, index);
+        self.apply(edits)?;
+        Ok(removed)
+    }
+
+    /// Resolves pointer tokens to a node.
+    fn find(&self, tokens: &[String]) -> Option<&Node> {
+        tokens
+            .iter()
+            .try_fold(&self.root, |node, token| match node {
+                Node::Object { members, .. } => members
+                    .iter()
+                    .rev()
+                    .find(|member| &member.key == token)
+                    .map(|member| &member.value),
+                Node::Array { items, .. } => items.get(pointer_index(token)?),
+                Node::Scalar { .. } => None,
+            })
+    }
+
+    /// Applies replacements to the text and rebuilds the tree. Insertions at the same
+    /// offset keep the order in which they were listed.
+    fn apply(&mut self, edits: Vec<(Range<usize>, String)>) -> Result<(), Error> {
+        let mut text = self.text.clone();
+        let mut edits: Vec<_> = edits.into_iter().enumerate().collect();
+        edits.sort_by_key(|(order, (span, _))| std::cmp::Reverse((span.start, *order)));
+        for (_, (span, replacement)) in edits {
+            text.replace_range(span, &replacement);
+        }
+        *self = JsonCst::parse(&text)
+            .map_err(|e| Error::msg(format!("Edit produced invalid JSON: {}", e)))?;
+        Ok(())
+    }
+
+    /// Returns the line break used by the document.
+    fn newline(&self) -> &'static str {
+        if self.text.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+
+    fn is_multiline(&self, node: &Node) -> bool {
+        self.text[node.span()].contains('\n')
+    }
+
+    /// Returns the indentation unit of the document: the smallest indentation of any
+    /// line, or two spaces.
+    fn indent_unit(&self) -> String {
+        self.text
+            .lines()
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .filter(|indent| !indent.is_empty() && indent.len() < 9)
+            .min_by_key(|indent| indent.len())
+            .unwrap_or("  ")
+            .to_string()
+    }
+
+    /// Serializes a value, pretty-printed in the document's style when the surrounding
+    /// container spans several lines and compact otherwise.
+    fn render(&self, value: &Value, indent: &str, multiline: bool) -> String {
+        if !multiline {
+            return value.to_string();
+        }
+        let unit = self.indent_unit();
+        let options = FormatOptions {
+            indent: unit.len(),
+            use_tabs: unit.starts_with('\t'),
+            trailing_newline: false,
+            ..FormatOptions::default()
+        };
+        format_json(value, &options).replace('\n', &format!("{}{}", self.newline(), indent))
+    }
+
+    /// Computes the edits that insert a member or element before position `index`.
+    fn insert_edits(
+        &self,
+        container: &Node,
+        index: usize,
+        key: Option<&str>,
+        value: &Value,
+    ) -> Vec<(Range<usize>, String)> {
+        let text = &self.text;
+        let span = container.span();
+        let items = container.item_spans();
+        let multiline = self.is_multiline(container) || (items.is_empty() && text.contains('\n'));
+        let indent = match items.first() {
+            Some(first) if multiline => line_indent(text, first.start).to_string(),
+            _ => format!("{}{}", line_indent(text, span.start), self.indent_unit()),
+        };
+        let rendered = self.render(value, &indent, multiline);
+        let newline = self.newline();
+        let item = match key {
+            Some(key) => format!("{}: {}", Value::from(key), rendered),
+            None => rendered,
+        };
+
+        if items.is_empty() {
+            let inner = span.start + 1..span.end - 1;
+            if !multiline {
+                return vec![(inner, item)];
+            }
+            let closing = line_indent(text, span.start);
+            if text[inner.clone()].trim().is_empty() {
+                return vec![(
+                    inner,
+                    format!("{}{}{}{}{}", newline, indent, item, newline, closing),
+                )];
+            }
+            return vec![(
+                inner.start..inner.start,
+                format!("{}{}{}", newline, indent, item),
+            )];
+        }
+        if index < items.len() {
+            let start = items[index].start;
+            let separator = if multiline && starts_line(text, start) {
+                format!(",{}{}", newline, line_indent(text, start))
+            } else {
+                ", ".to_string()
+            };
+            return vec![(start..start, format!("{}{}", item, separator))];
+        }
+
+        let last = items[items.len() - 1].clone();
+        if !multiline {
+            return vec![(last.end..last.end, format!(", {}", item))];
+        }
+        let after = skip_spaces(text, last.end);
+        let has_comma = text[after..].starts_with(',');
+        let rest = if has_comma {
+            skip_spaces(text, after + 1)
+        } else {
+            after
+        };
+        match line_comment_end(text, rest) {
+            // Keep a comment at the end of the last line with the member it describes.
+            Some(line_end) => {
+                let mut edits = Vec::new();
+                if !has_comma {
+                    edits.push((last.end..last.end, ",".to_string()));
+                }
+                let trailing = if has_comma { "," } else { "" };
+                edits.push((
+                    line_end..line_end,
+                    format!("{}{}{}{}", newline, indent, item, trailing),
+                ));
+                edits
+            }
+            None => vec![(
+                last.end..last.end,
+                format!(",{}{}{}", newline, indent, item),
+            )],
+        }
+    }
+
+    /// Computes the edits that remove the member or element at position `index`.
+    fn remove_edits(&self, container: &Node, index: usize) -> Vec<(Range<usize>, String)> {
+        let text = &self.text;
+        let items = container.item_spans();
+        let item = items[index].clone();
+        let span = container.span();
+        // Removing the only entry collapses the container to `{}` or `[]` unless
+        // comments live inside it.
+        if items.len() == 1
+            && text[span.start + 1..item.start].trim().is_empty()
+            && text[item.end..span.end - 1]
+                .trim()
+                .trim_start_matches(',')
+                .is_empty()
+        {
+            return vec![(span.start + 1..span.end - 1, String::new())];
+        }
+        let after = skip_spaces(text, item.end);
+        let has_comma = text[after..].starts_with(',');
+        let rest = if has_comma {
+            skip_spaces(text, after + 1)
+        } else {
+            after
+        };
+
+        if let (true, Some(line_end)) =
+            (starts_line(text, item.start), line_comment_end(text, rest))
+        {
+            let mut start = line_start(text, item.start);
+            // Comment lines directly above the removed line describe it.
+            while start > 0 {
+                let previous = line_start(text, start - 1);
+                if !text[previous..start].trim_start().starts_with("//") {
+                    break;
+                }
+                start = previous;
+            }
+            let end = (line_end + 1).min(text.len());
+            let mut edits = vec![(start..end, String::new())];
+            if !has_comma && index > 0 {
+                if let Some(comma) = comma_after(text, items[index - 1].end) {
+                    edits.push((comma..comma + 1, String::new()));
+                }
+            }
+            return edits;
+        }
+        if index + 1 < items.len() {
+            vec![(item.start..items[index + 1].start, String::new())]
+        } else if index > 0 {
+            vec![(items[index - 1].end..item.end, String::new())]
+        } else if has_comma {
+            vec![(item.start..after + 1, String::new())]
+        } else {
+            vec![(item, String::new())]
+        }
+    }
+}
+
+impl fmt::Display for JsonCst {
+    /// Writes the document text, including every edit made so far.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Builds the tree from text already checked by `parse_jsonc`.
+struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> u8 {
+        self.text.as_bytes().get(self.pos).copied().unwrap_or(0)
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) {
+        loop {
+            let rest = &self.text[self.pos..];
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with("/*") {
+                self.pos += rest.find("*/").map_or(rest.len(), |end| end + 2);
+            } else if matches!(self.peek(), b' ' | b'\t' | b'\r' | b'\n') {
+                self.pos += 1;
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Returns the end of the string starting at the current position.
+    fn string_end(&self) -> usize {
+        let bytes = self.text.as_bytes();
+        let mut i = self.pos + 1;
+        while i < bytes.len() && bytes[i] != b'"' {
+            i += if bytes[i] == b'\\' { 2 } else { 1 };
+        }
+        (i + 1).min(bytes.len())
+    }
+
+    fn node(&mut self) -> Node {
+        self.skip_trivia();
+        let start = self.pos;
+        match self.peek() {
+            b'{' => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                while let Some(key_start) = self.next_item(b'}') {
+                    let key_end = self.string_end();
+                    let key =
+                        serde_json::from_str(&self.text[key_start..key_end]).unwrap_or_default();
+                    self.pos = key_end;
+                    self.skip_trivia();
+                    self.pos += 1;
+                    let value = self.node();
+                    members.push(Member {
+                        key,
+                        key_start,
+                        value,
+                    });
+                }
+                Node::Object {
+                    span: start..self.pos,
+                    members,
+                }
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                while self.next_item(b']').is_some() {
+                    items.push(self.node());
+                }
+                Node::Array {
+                    span: start..self.pos,
+                    items,
+                }
+            }
+            b'"' => {
+                self.pos = self.string_end();
+                Node::Scalar {
+                    span: start..self.pos,
+                }
+            }
+            _ => {
+                let length = self.text[start..]
+                    .find(|c: char| c.is_whitespace() || ",]}/".contains(c))
+                    .unwrap_or(self.text.len() - start);
+                self.pos += length;
+                Node::Scalar {
+                    span: start..self.pos,
+                }
+            }
+        }
+    }
+
+    /// Moves to the start of the next member or element, or past the closing bracket.
+    fn next_item(&mut self, close: u8) -> Option<usize> {
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                b',' => self.pos += 1,
+                c if c == close || c == 0 => {
+                    self.pos += usize::from(c == close);
+                    return None;
+                }
+                _ => return Some(self.pos),
+            }
+        }
+    }
+}
+
+/// Returns the offset of the start of the line containing `pos`.
+fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Returns the leading whitespace of the line containing `pos`.
+fn line_indent(text: &str, pos: usize) -> &str {
+    let start = line_start(text, pos);
+    let line = &text[start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Returns true if only whitespace precedes `pos` on its line.
+fn starts_line(text: &str, pos: usize) -> bool {
+    text[line_start(text, pos)..pos].trim().is_empty()
+}
+
+/// Skips spaces and tabs.
+fn skip_spaces(text: &str, pos: usize) -> usize {
+    pos + text[pos..].len() - text[pos..].trim_start_matches([' ', '\t']).len()
+}
+
+/// If only an optional comment follows `pos` on its line, returns the offset of the
+/// line break (or the end of the text).
+fn line_comment_end(text: &str, pos: usize) -> Option<usize> {
+    let rest = &text[pos..];
+    let line_end = pos + rest.find('\n').unwrap_or(rest.len());
+    let remainder = text[pos..line_end].trim();
+    let is_comment = remainder.is_empty()
+        || remainder.starts_with("//")
+        || (remainder.starts_with("/*") && remainder.find("*/") == Some(remainder.len() - 2));
+    let line_end = if text[..line_end].ends_with('\r') {
+        line_end - 1
+    } else {
+        line_end
+    };
+    is_comment.then_some(line_end)
+}
+
+/// Returns the offset of the comma following `pos`, skipping whitespace and comments.
+fn comma_after(text: &str, pos: usize) -> Option<usize> {
+    let mut scanner = Scanner { text, pos };
+    scanner.skip_trivia();
+    (scanner.peek() == b',').then_some(scanner.pos)
+}
@@ -0,0 +1,473 @@
+use crate::datetime::{format_date, parse_date};
+use crate::flatten::{flatten, unflatten};
+use crate::parser::Step;
+use crate::path_syntax::PathSyntax;
+use crate::validator::validate_document;
+use crate::warnings;
+use anyhow::{Error, Result};
+use serde_json::{json, Map, Number, Value};
+use std::collections::BTreeMap;
+
+/// Options for `json_to_csv` and `csv_to_json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Field delimiter, e.g. `,`, `;` or a tab.
+    pub delimiter: char,
+    /// Separator of the column names of nested fields, as in `flatten`: `address.city`.
+    pub separator: String,
+    /// Reads numbers, `true` and `false`, `{}` and `[]` as JSON values and empty fields
+    /// as `null` in `csv_to_json`; every field is a string otherwise.
+    pub infer_types: bool,
+    /// Decimal separator of numbers read by `csv_to_json`, e.g. `,` for `3,14` in
+    /// European exports.
+    pub decimal_separator: char,
+    /// Thousands separator of numbers read by `csv_to_json`, e.g. `.` for `1.234,5` or
+    /// a space; digits must then be grouped by three.
+    pub thousands_separator: Option<char>,
+    /// Date formats of columns, in the `strftime` style of `parse_date`, e.g.
+    /// `"%d.%m.%Y"` for the column `born`. `csv_to_json` writes matching dates as
+    /// `YYYY-MM-DD`, or as RFC 3339 if the format has a time, and keeps other fields
+    /// as strings with a warning.
+    pub date_formats: BTreeMap<String, String>,
+}
+
+impl Default for CsvOptions {
+    /// Comma-delimited, `.`-separated nested columns, with type inference of numbers
+    /// such as `1234.5`.
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            separator: ".".to_string(),
+            infer_types: true,
+            decimal_separator: '.',
+            thousands_separator: None,
+            date_formats: BTreeMap::new(),
+        }
+    }
+}
+
+/// Converts an array of objects to CSV, one row per object.
+///
+/// Nested objects and arrays are flattened into columns such as `address.city` and
+/// `tags[0]`. The header lists every column in order of first appearance, so objects
+/// may have different fields; missing fields and `null` are written as empty fields.
+/// Fields containing the delimiter, quotes or line breaks are quoted.
+///
+/// # Arguments
+///
+/// * `json` - The array of objects, e.g. records from an API.
+/// * `options` - The delimiter and the separator of nested column names.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The CSV text with a header row, or an error if `json` is
+///   not an array of objects.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn json_to_csv(json: &Value, options: &CsvOptions) -> Result<String, Error> {
+    let items = json
+        .as_array()
+        .ok_or_else(|| Error::msg("Only an array of objects can be converted to CSV"))?;
+    let mut header: Vec<String> = Vec::new();
+    let mut rows = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        if !item.is_object() {
+            return Err(Error::msg(format!(
+                "Element {} is not an object and cannot be a CSV row",
+                index
+            )));
+        }
+        let Value::Object(row) = flatten(item, &options.separator) else {
+            unreachable!("flatten returns an object");
+        };
+        for column in row.keys() {
+            if !header.contains(column) {
+                header.push(column.clone());
+            }
+        }
+        rows.push(row);
+    }
+    if header.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut csv = String::new();
+    write_record(
+        &mut csv,
+        header.iter().map(String::as_str),
+        options.delimiter,
+    );
+    for row in &rows {
+        let fields: Vec<String> = header
+            .iter()
+            .map(|column| match row.get(column) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(text)) => text.clone(),
+                Some(other) => other.to_string(),
+            })
+            .collect();
+        write_record(
+            &mut csv,
+            fields.iter().map(String::as_str),
+            options.delimiter,
+        );
+    }
+    Ok(csv)
+}
+
+/// Converts CSV with a header row to an array of objects, one per row.
+///
+/// Column names are split on `options.separator` to rebuild nested values, the reverse
+/// of `json_to_csv`: a column `address.city` becomes `{"address": {"city": ...}}`.
+///
+/// # Arguments
+///
+/// * `input` - The CSV text. Fields may be quoted with `"`, doubling quotes inside;
+///   quoted fields may span lines.
+/// * `options` - The delimiter, the separator of nested column names, whether to
+///   infer types, and the number and date formats.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The array, or an error if the CSV is malformed, a row has
+///   the wrong number of fields, the header repeats a column, column names conflict
+///   (`a` and `a.b`), or the decimal separator is the delimiter.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = input.len())))]
+pub fn csv_to_json(input: &str, options: &CsvOptions) -> Result<Value, Error> {
+    check_separators(options)?;
+    let mut records = read_records(input, options.delimiter)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(Value::Array(Vec::new()));
+    };
+    check_header(&header)?;
+    let types = vec![Vec::new(); header.len()];
+    records
+        .enumerate()
+        .map(|(line, record)| read_row(&header, record, line, &types, options))
+        .collect::<Result<_>>()
+        .map(Value::Array)
+}
+
+/// Outcome of `csv_to_json_validated`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CsvImport {
+    /// The rows that satisfy the schema.
+    pub rows: Vec<Value>,
+    /// The other rows, as `{"row": 3, "error": "...", "text": "..."}` with the row
+    /// number counting the header as row 1, the violations or parse error, and the
+    /// row as CSV.
+    pub quarantined: Vec<Value>,
+}
+
+/// Converts CSV with a header row to an array of objects, validating each row against
+/// a JSON Schema in the same pass.
+///
+/// Fields are read as the type the schema declares for their column, e.g. `"007"` as a
+/// string and `"7"` as a number, so that type inference does not fail valid rows;
+/// columns the schema does not describe are read as by `csv_to_json`. Rows that
+/// violate the schema, have the wrong number of fields, or have conflicting columns
+/// are quarantined instead of failing the import.
+///
+/// # Arguments
+///
+/// * `input` - The CSV text.
+/// * `schema` - The JSON Schema of one row; `properties` and `items` describe nested
+///   columns such as `address.city` and `tags[0]`.
+/// * `options` - The delimiter, the separator of nested column names, whether to
+///   infer types, and the number and date formats.
+///
+/// # Returns
+///
+/// * `Result<CsvImport, Error>` - The valid rows and the quarantined ones, or an error
+///   if the CSV is malformed, e.g. has an unterminated quoted field, the header repeats
+///   a column, or the decimal separator is the delimiter.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip_all, fields(bytes = input.len()))
+)]
+pub fn csv_to_json_validated(
+    input: &str,
+    schema: &Value,
+    options: &CsvOptions,
+) -> Result<CsvImport, Error> {
+    check_separators(options)?;
+    let mut records = read_records(input, options.delimiter)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(CsvImport::default());
+    };
+    check_header(&header)?;
+    let types: Vec<Vec<String>> = header
+        .iter()
+        .map(|column| column_types(schema, column, &options.separator))
+        .collect();
+    let mut import = CsvImport::default();
+    for (line, record) in records.enumerate() {
+        let mut text = String::new();
+        write_record(
+            &mut text,
+            record.iter().map(String::as_str),
+            options.delimiter,
+        );
+        let error = match read_row(&header, record, line, &types, options) {
+            Ok(row) => {
+                let report = validate_document(&row, schema);
+                if report.is_valid() {
+                    import.rows.push(row);
+                    continue;
+                }
+                report.to_string()
+            }
+            Err(e) => e.to_string(),
+        };
+        import.quarantined.push(json!({
+            "row": line + 2,
+            "error": error,
+            "text": text.trim_end_matches('\n'),
+        }));
+    }
+    Ok(import)
+}
+
+/// Rejects a decimal separator that is also the delimiter or the thousands separator,
+/// as numbers could then not be told apart from the fields or groups around them.
+fn check_separators(options: &CsvOptions) -> Result<(), Error> {
+    if options.decimal_separator == options.delimiter {
+        return Err(Error::msg(format!(
+            "The decimal separator '{}' is also the CSV delimiter",
+            options.decimal_separator
+        )));
+    }
+    if options.thousands_separator == Some(options.decimal_separator) {
+        return Err(Error::msg(format!(
+            "The decimal separator '{}' is also the thousands separator",
+            options.decimal_separator
+        )));
+    }
+    Ok(())
+}
+
+/// Rejects a header that names a column twice, whose fields would overwrite each other.
+fn check_header(header: &[String]) -> Result<(), Error> {
+    let mut seen = std::collections::BTreeSet::new();
+    match header.iter().find(|column| !seen.insert(column.as_str())) {
+        Some(column) => Err(Error::msg(format!(
+            "The CSV header repeats the column '{}'",
+            column
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Builds the object of one record, reading each field as one of the `types` of its
+/// column, or as by `options` if there are none. `line` is the index of the record.
+fn read_row(
+    header: &[String],
+    record: Vec<String>,
+    line: usize,
+    types: &[Vec<String>],
+    options: &CsvOptions,
+) -> Result<Value, Error> {
+    if record.len() != header.len() {
+        return Err(Error::msg(format!(
+            "CSV row {} has {} fields, expected {}",
+            line + 2,
+            record.len(),
+            header.len()
+        )));
+    }
+    let row: Map<String, Value> = header
+        .iter()
+        .zip(types)
+        .zip(record)
+        .map(|((column, types), field)| {
+            let value = match options.date_formats.get(column) {
+                Some(format) => read_date(field, format, line, column),
+                None => coerce(field, types, options),
+            };
+            (column.clone(), value)
+        })
+        .collect();
+    unflatten(&Value::Object(row), &options.separator)
+        .map_err(|e| Error::msg(format!("CSV row {}: {:#}", line + 2, e)))
+}
+
+/// Returns the types a JSON Schema declares for a column, e.g. `["integer", "null"]`,
+/// following `properties` for keys and `items` for indexes; empty if none are declared.
+fn column_types(schema: &Value, column: &str, separator: &str) -> Vec<String> {
+    let Ok(steps) = PathSyntax::delimited(separator).parse(column) else {
+        return Vec::new();
+    };
+    let mut node = schema;
+    for step in &steps {
+        let child = match step {
+            Step::Key(key) => node.get("properties").and_then(|p| p.get(key)),
+            Step::Index(_) => node.get("items"),
+        };
+        match child {
+            Some(child) => node = child,
+            None => return Vec::new(),
+        }
+    }
+    match node.get("type") {
+        Some(Value::String(kind)) => vec![kind.clone()],
+        Some(Value::Array(kinds)) => kinds
+            .iter()
+            .filter_map(|kind| kind.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Reads a field as the first of `types` it can be; if none fits, or no types are
+/// given, it is read as by `options`, leaving a mismatch to validation.
+fn coerce(field: String, types: &[String], options: &CsvOptions) -> Value {
+    if field.is_empty() && types.iter().any(|kind| kind == "null") {
+        return Value::Null;
+    }
+    for kind in types {
+        let number = || normalize_number(&field, options);
+        match kind.as_str() {
+            "string" => return Value::String(field),
+            "integer" => {
+                if let Some(n) = number().and_then(|text| text.parse::<i64>().ok()) {
+                    return Value::from(n);
+                }
+            }
+            "number" => {
+                if let Some(n) = number().and_then(|text| text.parse::<Number>().ok()) {
+                    return Value::Number(n);
+                }
+            }
+            "boolean" if field == "true" || field == "false" => {
+                return Value::Bool(field == "true")
+            }
+            _ => {}
+        }
+    }
+    if options.infer_types {
+        infer_type(field, options)
+    } else {
+        Value::String(field)
+    }
+}
+
+/// Splits CSV text into records of fields, skipping blank lines and a leading UTF-8
+/// byte order mark, as written by Excel.
+///
+/// Fields may be quoted with `"`, doubling quotes inside; quoted fields may span lines.
+pub(crate) fn read_records(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, Error> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if c == delimiter && !quoted => record.push(std::mem::take(&mut field)),
+            '\n' | '\r' if !quoted => {
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::msg("Unterminated quoted field in CSV"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|record| !(record.len() == 1 && record[0].is_empty()));
+    Ok(records)
+}
+
+/// Appends one CSV line, quoting the fields that need it.
+fn write_record<'a>(csv: &mut String, fields: impl Iterator<Item = &'a str>, delimiter: char) {
+    for (index, field) in fields.enumerate() {
+        if index > 0 {
+            csv.push(delimiter);
+        }
+        if field.contains([delimiter, '"', '\n', '\r']) {
+            csv.push('"');
+            csv.push_str(&field.replace('"', "\"\""));
+            csv.push('"');
+        } else {
+            csv.push_str(field);
+        }
+    }
+    csv.push('\n');
+}
+
+/// Reads a field as the JSON value it looks like. Numbers with leading zeros, such as
+/// zip codes, stay strings.
+fn infer_type(field: String, options: &CsvOptions) -> Value {
+    match field.as_str() {
+        "" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "{}" => Value::Object(Map::new()),
+        "[]" => Value::Array(Vec::new()),
+        text => {
+            match normalize_number(text, options).and_then(|text| text.parse::<Number>().ok()) {
+                Some(number) => Value::Number(number),
+                None => Value::String(field),
+            }
+        }
+    }
+}
+
+/// Rewrites a number in the separators of `options` with a `.` decimal point and no
+/// grouping, or returns `None` if its digits are not grouped by three.
+fn normalize_number(text: &str, options: &CsvOptions) -> Option<String> {
+    let (integer, fraction) = match text.split_once(options.decimal_separator) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (text, None),
+    };
+    let integer = match options.thousands_separator {
+        Some(thousands) if integer.contains(thousands) => {
+            let unsigned = integer.trim_start_matches(['-', '+']);
+            let groups: Vec<&str> = unsigned.split(thousands).collect();
+            let grouped = (1..=3).contains(&groups[0].len())
+                && groups[1..].iter().all(|group| group.len() == 3);
+            if !grouped {
+                return None;
+            }
+            integer.replace(thousands, "")
+        }
+        _ => integer.to_string(),
+    };
+    Some(match fraction {
+        Some(fraction) => format!("{}.{}", integer, fraction),
+        None => integer,
+    })
+}
+
+/// Reads a field of a date column as `YYYY-MM-DD`, or RFC 3339 if `format` has a time;
+/// empty fields become `null`, and fields not matching `format` stay strings.
+fn read_date(field: String, format: &str, line: usize, column: &str) -> Value {
+    if field.is_empty() {
+        return Value::Null;
+    }
+    let Some(seconds) = parse_date(&field, Some(format)) else {
+        warnings::warn(
+            &format!("[{}].{}", line, column),
+            format!("'{}' does not match the date format {}", field, format),
+        );
+        return Value::String(field);
+    };
+    let has_time = ["%H", "%M", "%S", "%s"]
+        .iter()
+        .any(|specifier| format.contains(specifier));
+    let output = if has_time { None } else { Some("%Y-%m-%d") };
+    Value::String(format_date(seconds, output, 0))
+}
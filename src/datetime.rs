@@ -0,0 +1,277 @@
+use crate::presets::{civil_from_days, days_from_civil};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Abbreviated month names used by `%b`.
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}
+
+/// Parses a date into seconds since the Unix epoch.
+///
+/// Without a format, RFC 3339 timestamps (`2024-03-01T12:00:00.5+02:00`, with `T`,
+/// `t` or a space) and plain dates (`2024-03-01`, midnight UTC) are accepted. A format
+/// uses `strftime`-style specifiers: `%Y %m %d %H %M %S %b %z %s %%`, e.g.
+/// `%d/%b/%Y:%H:%M:%S %z` for web server logs; missing fields default to the start of
+/// the day in UTC.
+///
+/// # Arguments
+///
+/// * `text` - The date text.
+/// * `format` - Optional format.
+///
+/// # Returns
+///
+/// * `Option<f64>` - The timestamp, or `None` if the text does not match.
+pub fn parse_date(text: &str, format: Option<&str>) -> Option<f64> {
+    match format {
+        Some(format) => parse_with_format(text, format),
+        None if text.len() == 10 => parse_with_format(text, "%Y-%m-%d"),
+        None => parse_rfc3339(text),
+    }
+}
+
+/// Formats seconds since the Unix epoch.
+///
+/// # Arguments
+///
+/// * `seconds` - The timestamp; fractions of a second are dropped.
+/// * `format` - Optional `strftime`-style format (`%Y %m %d %H %M %S %b %z %:z %s %%`);
+///   RFC 3339 (`2024-03-01T12:00:00Z`) by default.
+/// * `offset` - UTC offset in seconds the date is shown in.
+///
+/// # Returns
+///
+/// * `String` - The formatted date.
+pub fn format_date(seconds: f64, format: Option<&str>, offset: i64) -> String {
+    let seconds = seconds.floor() as i64;
+    let local = seconds + offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+    let time = local.rem_euclid(86_400);
+    let zone = |colon: bool| {
+        if offset == 0 && colon {
+            return "Z".to_string();
+        }
+        let sign = if offset < 0 { '-' } else { '+' };
+        let minutes = offset.abs() / 60;
+        let separator = if colon { ":" } else { "" };
+        format!(
+            "{}{:02}{}{:02}",
+            sign,
+            minutes / 60,
+            separator,
+            minutes % 60
+        )
+    };
+    let Some(format) = format else {
+        return format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+            year,
+            month,
+            day,
+            time / 3600,
+            time % 3600 / 60,
+            time % 60,
+            zone(true)
+        );
+    };
+    let mut result = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => result.push_str(&format!("{:04}", year)),
+            Some('m') => result.push_str(&format!("{:02}", month)),
+            Some('d') => result.push_str(&format!("{:02}", day)),
+            Some('H') => result.push_str(&format!("{:02}", time / 3600)),
+            Some('M') => result.push_str(&format!("{:02}", time % 3600 / 60)),
+            Some('S') => result.push_str(&format!("{:02}", time % 60)),
+            Some('b') => result.push_str(MONTHS[(month - 1) as usize]),
+            Some('s') => result.push_str(&seconds.to_string()),
+            Some('z') => result.push_str(&zone(false)),
+            Some(':') if chars.clone().next() == Some('z') => {
+                chars.next();
+                result.push_str(&zone(true));
+            }
+            Some(other) => {
+                result.push('%');
+                result.push(other);
+            }
+            None => result.push('%'),
+        }
+    }
+    result
+}
+
+/// Parses a UTC offset: `Z`, `UTC`, `GMT`, `+02:00`, `+0200` or `+02`.
+///
+/// # Returns
+///
+/// * `Option<i64>` - The offset in seconds, or `None` for other text. Named time
+///   zones such as `Europe/Kyiv` are not supported.
+pub fn parse_offset(text: &str) -> Option<i64> {
+    if matches!(text, "Z" | "z" | "UTC" | "GMT") {
+        return Some(0);
+    }
+    let sign = match text.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = text[1..].replace(':', "");
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i64>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i64>().ok()?,
+            digits[2..].parse::<i64>().ok()?,
+        ),
+        _ => return None,
+    };
+    (hours < 24 && minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Returns the number of days in a month of the proleptic Gregorian calendar.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns true if the fields name an existing date and time of day; a second of 60
+/// is accepted for leap seconds.
+fn is_valid_date_time(year: i64, month: i64, day: i64, hour: i64, minute: i64, second: i64) -> bool {
+    (1..=12).contains(&month)
+        && (1..=days_in_month(year, month)).contains(&day)
+        && (0..24).contains(&hour)
+        && (0..60).contains(&minute)
+        && (0..=60).contains(&second)
+}
+
+/// Parses a fixed-width field of ASCII digits.
+fn parse_digits(digits: &str) -> Option<i64> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Parses an RFC 3339 timestamp.
+fn parse_rfc3339(text: &str) -> Option<f64> {
+    let field = |start: usize, end: usize| parse_digits(text.get(start..end)?);
+    let (year, month, day) = (field(0, 4)?, field(5, 7)?, field(8, 10)?);
+    let (hour, minute, second) = (field(11, 13)?, field(14, 16)?, field(17, 19)?);
+    let bytes = text.as_bytes();
+    if bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+        || !is_valid_date_time(year, month, day, hour, minute, second)
+    {
+        return None;
+    }
+    let rest = &text[19..];
+    let digits = rest.strip_prefix('.').map_or(0, |fraction| {
+        fraction.bytes().take_while(u8::is_ascii_digit).count() + 1
+    });
+    let fraction = if digits > 1 {
+        rest[..digits].parse::<f64>().ok()?
+    } else {
+        0.0
+    };
+    let offset = match &rest[digits..] {
+        "Z" | "z" => 0,
+        zone if zone.len() == 6 && zone.as_bytes()[3] == b':' => parse_offset(zone)?,
+        _ => return None,
+    };
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    Some(seconds as f64 + fraction)
+}
+
+/// Parses a date with a `strftime`-style format.
+fn parse_with_format(text: &str, format: &str) -> Option<f64> {
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second, mut offset) = (0, 0, 0, 0);
+    let mut epoch = None;
+    let mut rest = text;
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            rest = rest.strip_prefix(c)?;
+            continue;
+        }
+        let specifier = chars.next()?;
+        if specifier == '%' {
+            rest = rest.strip_prefix('%')?;
+            continue;
+        }
+        if specifier == 'b' {
+            let index = MONTHS
+                .iter()
+                .position(|name| rest.get(..3).is_some_and(|m| m.eq_ignore_ascii_case(name)))?;
+            month = index as i64 + 1;
+            rest = &rest[3..];
+            continue;
+        }
+        if specifier == 'z' {
+            let length = if rest.starts_with(['Z', 'z']) {
+                1
+            } else if rest.get(3..4) == Some(":") {
+                6
+            } else {
+                5
+            };
+            offset = parse_offset(rest.get(..length)?)?;
+            rest = &rest[length..];
+            continue;
+        }
+        let width = match specifier {
+            'Y' => 4,
+            'm' | 'd' | 'H' | 'M' | 'S' => 2,
+            's' => rest
+                .bytes()
+                .enumerate()
+                .take_while(|(i, b)| b.is_ascii_digit() || (*i == 0 && *b == b'-'))
+                .count(),
+            _ => return None,
+        };
+        let digits = rest.get(..width)?;
+        let value = if specifier == 's' {
+            digits.parse().ok()?
+        } else {
+            parse_digits(digits)?
+        };
+        rest = &rest[width..];
+        match specifier {
+            'Y' => year = value,
+            'm' => month = value,
+            'd' => day = value,
+            'H' => hour = value,
+            'M' => minute = value,
+            'S' => second = value,
+            _ => epoch = Some(value),
+        }
+    }
+    let valid = rest.is_empty() && is_valid_date_time(year, month, day, hour, minute, second);
+    if let Some(epoch) = epoch {
+        return rest.is_empty().then_some(epoch as f64);
+    }
+    let days = days_from_civil(year, month, day);
+    valid.then(|| (days * 86_400 + hour * 3600 + minute * 60 + second - offset) as f64)
+}
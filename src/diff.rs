@@ -0,0 +1,580 @@
+use crate::parser::Step;
+use crate::patch::generate_patch;
+use crate::path_syntax::PathSyntax;
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// ANSI escape sequences used by the pretty renderer.
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Kind of change detected between two JSON documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A single difference between two JSON documents.
+///
+/// `path` uses the crate's dot/bracket notation (e.g. `data.items[0].name`) unless
+/// `diff_json_with` was given another `PathSyntax`, while `pointer` holds the same
+/// location as an RFC 6901 JSON Pointer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffEntry {
+    pub path: String,
+    pub pointer: String,
+    pub kind: DiffKind,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+}
+
+/// Output format of the `diff` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    Pretty,
+    Patch,
+    Json,
+    Paths,
+}
+
+impl FromStr for DiffFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(DiffFormat::Pretty),
+            "patch" => Ok(DiffFormat::Patch),
+            "json" => Ok(DiffFormat::Json),
+            "paths" => Ok(DiffFormat::Paths),
+            _ => Err(Error::msg(format!("Unsupported diff format: {}", s))),
+        }
+    }
+}
+
+/// Options controlling how a diff is rendered.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Number of unchanged lines shown around each change.
+    pub context: usize,
+    /// Whether ANSI colors are emitted.
+    pub color: bool,
+    /// Unchanged objects and arrays spanning more lines than this are collapsed.
+    pub collapse_threshold: usize,
+    /// Total terminal width used by the side-by-side renderer.
+    pub width: usize,
+    /// Syntax of the paths in the json and paths formats.
+    pub syntax: PathSyntax,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            context: 3,
+            color: true,
+            collapse_threshold: 8,
+            width: 120,
+            syntax: PathSyntax::default(),
+        }
+    }
+}
+
+/// Computes the structural differences between two JSON documents.
+///
+/// # Arguments
+///
+/// * `old` - The original JSON document.
+/// * `new` - The updated JSON document.
+///
+/// # Returns
+///
+/// * `Vec<DiffEntry>` - Every added, removed, or changed path, in document order.
+pub fn diff_json(old: &Value, new: &Value) -> Vec<DiffEntry> {
+    diff_json_with(old, new, &PathSyntax::default())
+}
+
+/// Computes the structural differences between two JSON documents, writing each `path`
+/// in the given syntax.
+///
+/// # Arguments
+///
+/// * `old` - The original JSON document.
+/// * `new` - The updated JSON document.
+/// * `syntax` - The syntax of the reported paths, e.g. `PathSyntax::delimited("/")`.
+///
+/// # Returns
+///
+/// * `Vec<DiffEntry>` - Every added, removed, or changed path, in document order.
+pub fn diff_json_with(old: &Value, new: &Value, syntax: &PathSyntax) -> Vec<DiffEntry> {
+    let mut entries = Vec::new();
+    diff_recursive(old, new, &mut Vec::new(), syntax, &mut entries);
+    entries
+}
+
+/// Recursive helper for `diff_json_with`, comparing objects by key and arrays by index.
+fn diff_recursive(
+    old: &Value,
+    new: &Value,
+    path: &mut Vec<Step>,
+    syntax: &PathSyntax,
+    entries: &mut Vec<DiffEntry>,
+) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                path.push(Step::Key(key.clone()));
+                match new_map.get(key) {
+                    Some(new_value) => diff_recursive(old_value, new_value, path, syntax, entries),
+                    None => entries.push(make_entry(
+                        path,
+                        syntax,
+                        DiffKind::Removed,
+                        Some(old_value),
+                        None,
+                    )),
+                }
+                path.pop();
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    path.push(Step::Key(key.clone()));
+                    entries.push(make_entry(
+                        path,
+                        syntax,
+                        DiffKind::Added,
+                        None,
+                        Some(new_value),
+                    ));
+                    path.pop();
+                }
+            }
+        }
+        (Value::Array(old_arr), Value::Array(new_arr)) => {
+            for index in 0..old_arr.len().max(new_arr.len()) {
+                path.push(Step::Index(index));
+                match (old_arr.get(index), new_arr.get(index)) {
+                    (Some(o), Some(n)) => diff_recursive(o, n, path, syntax, entries),
+                    (Some(o), None) => {
+                        entries.push(make_entry(path, syntax, DiffKind::Removed, Some(o), None))
+                    }
+                    (None, Some(n)) => {
+                        entries.push(make_entry(path, syntax, DiffKind::Added, None, Some(n)))
+                    }
+                    (None, None) => {}
+                }
+                path.pop();
+            }
+        }
+        _ => {
+            if old != new {
+                entries.push(make_entry(
+                    path,
+                    syntax,
+                    DiffKind::Changed,
+                    Some(old),
+                    Some(new),
+                ));
+            }
+        }
+    }
+}
+
+/// Builds a `DiffEntry` for the given location.
+fn make_entry(
+    path: &[Step],
+    syntax: &PathSyntax,
+    kind: DiffKind,
+    old_value: Option<&Value>,
+    new_value: Option<&Value>,
+) -> DiffEntry {
+    DiffEntry {
+        path: syntax.format(path),
+        pointer: PathSyntax::pointer().format(path),
+        kind,
+        old_value: old_value.cloned(),
+        new_value: new_value.cloned(),
+    }
+}
+
+/// Renders the differences between two JSON documents in the requested format.
+///
+/// # Arguments
+///
+/// * `old` - The original JSON document.
+/// * `new` - The updated JSON document.
+/// * `format` - The output format (`pretty`, `patch`, `json`, or `paths`).
+/// * `options` - Rendering options for the pretty and paths formats.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The rendered diff, or an error if serialization fails.
+pub fn render_diff(
+    old: &Value,
+    new: &Value,
+    format: DiffFormat,
+    options: &RenderOptions,
+) -> Result<String, Error> {
+    match format {
+        DiffFormat::Pretty => Ok(render_pretty(old, new, options)),
+        DiffFormat::Patch => serde_json::to_string_pretty(&generate_patch(old, new))
+            .map_err(|e| Error::msg(e.to_string())),
+        DiffFormat::Json => {
+            let report: Vec<Value> = diff_json_with(old, new, &options.syntax)
+                .into_iter()
+                .map(|entry| {
+                    json!({
+                        "path": entry.path,
+                        "kind": format!("{:?}", entry.kind).to_lowercase(),
+                        "old": entry.old_value,
+                        "new": entry.new_value,
+                    })
+                })
+                .collect();
+            serde_json::to_string_pretty(&Value::Array(report))
+                .map_err(|e| Error::msg(e.to_string()))
+        }
+        DiffFormat::Paths => Ok(render_paths(old, new, options)),
+    }
+}
+
+/// Renders one line per changed path: `+ path: new`, `- path: old`, or `~ path: old -> new`.
+fn render_paths(old: &Value, new: &Value, options: &RenderOptions) -> String {
+    let compact = |value: &Option<Value>| value.as_ref().map(Value::to_string).unwrap_or_default();
+    let mut output = String::new();
+    for entry in diff_json_with(old, new, &options.syntax) {
+        let (color, line) = match entry.kind {
+            DiffKind::Added => (
+                GREEN,
+                format!("+ {}: {}", entry.path, compact(&entry.new_value)),
+            ),
+            DiffKind::Removed => (
+                RED,
+                format!("- {}: {}", entry.path, compact(&entry.old_value)),
+            ),
+            DiffKind::Changed => (
+                CYAN,
+                format!(
+                    "~ {}: {} -> {}",
+                    entry.path,
+                    compact(&entry.old_value),
+                    compact(&entry.new_value)
+                ),
+            ),
+        };
+        if options.color {
+            output.push_str(&format!("{}{}{}\n", color, line, RESET));
+        } else {
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// Tag of a rendered line in the pretty format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineTag {
+    Same,
+    Added,
+    Removed,
+}
+
+/// A single line of the pretty diff before context filtering.
+struct Line {
+    tag: LineTag,
+    text: String,
+}
+
+/// Renders a unified-style diff of the pretty-printed documents.
+fn render_pretty(old: &Value, new: &Value, options: &RenderOptions) -> String {
+    let mut lines = Vec::new();
+    pretty_lines(old, new, 0, "", false, options, &mut lines);
+    let visible = match visible_lines(&lines, options.context) {
+        Some(visible) => visible,
+        None => return String::new(),
+    };
+
+    let mut output = String::new();
+    let mut hidden = 0;
+    for (line, &show) in lines.iter().zip(&visible) {
+        if !show {
+            hidden += 1;
+            continue;
+        }
+        if hidden > 0 {
+            push_marker(&mut output, hidden, options.color);
+            hidden = 0;
+        }
+        let (sign, color) = match line.tag {
+            LineTag::Same => (' ', ""),
+            LineTag::Added => ('+', GREEN),
+            LineTag::Removed => ('-', RED),
+        };
+        if options.color && !color.is_empty() {
+            output.push_str(&format!("{}{} {}{}\n", color, sign, line.text, RESET));
+        } else {
+            output.push_str(&format!("{} {}\n", sign, line.text));
+        }
+    }
+    if hidden > 0 {
+        push_marker(&mut output, hidden, options.color);
+    }
+    output
+}
+
+/// Renders the old and new documents in two aligned columns.
+///
+/// Unchanged lines appear on both sides, while removed and added lines of the
+/// same location share a row so that paths stay aligned between the columns.
+///
+/// # Arguments
+///
+/// * `old` - The original JSON document.
+/// * `new` - The updated JSON document.
+/// * `options` - Rendering options; `width` sets the total terminal width.
+///
+/// # Returns
+///
+/// * `String` - The rendered two-column diff, empty if the documents are equal.
+pub fn render_side_by_side(old: &Value, new: &Value, options: &RenderOptions) -> String {
+    let mut lines = Vec::new();
+    pretty_lines(old, new, 0, "", false, options, &mut lines);
+    let visible = match visible_lines(&lines, options.context) {
+        Some(visible) => visible,
+        None => return String::new(),
+    };
+    let column = options.width.saturating_sub(3) / 2;
+
+    let mut output = String::new();
+    let mut hidden = 0;
+    let mut index = 0;
+    while index < lines.len() {
+        if lines[index].tag == LineTag::Same {
+            if visible[index] {
+                if hidden > 0 {
+                    push_marker(&mut output, hidden, options.color);
+                    hidden = 0;
+                }
+                let text = fit_column(&lines[index].text, column);
+                output.push_str(&format!("{:<width$}   {}\n", text, text, width = column));
+            } else {
+                hidden += 1;
+            }
+            index += 1;
+            continue;
+        }
+
+        if hidden > 0 {
+            push_marker(&mut output, hidden, options.color);
+            hidden = 0;
+        }
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        while index < lines.len() && lines[index].tag != LineTag::Same {
+            match lines[index].tag {
+                LineTag::Removed => removed.push(fit_column(&lines[index].text, column)),
+                _ => added.push(fit_column(&lines[index].text, column)),
+            }
+            index += 1;
+        }
+        for row in 0..removed.len().max(added.len()) {
+            let left = removed.get(row).map(String::as_str).unwrap_or("");
+            let right = added.get(row).map(String::as_str).unwrap_or("");
+            let marker = match (removed.get(row), added.get(row)) {
+                (Some(_), Some(_)) => '|',
+                (Some(_), None) => '<',
+                _ => '>',
+            };
+            let left = format!("{:<width$}", left, width = column);
+            if options.color {
+                output.push_str(&format!(
+                    "{}{}{} {} {}{}{}\n",
+                    RED, left, RESET, marker, GREEN, right, RESET
+                ));
+            } else {
+                output.push_str(&format!("{} {} {}\n", left, marker, right));
+            }
+        }
+    }
+    if hidden > 0 {
+        push_marker(&mut output, hidden, options.color);
+    }
+    output
+}
+
+/// Truncates a line to the given column width, marking the cut with an ellipsis.
+fn fit_column(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let mut fitted: String = text.chars().take(width.saturating_sub(1)).collect();
+        fitted.push('…');
+        fitted
+    }
+}
+
+/// Determines which lines are within `context` lines of a change.
+/// Returns `None` when there are no changes at all.
+fn visible_lines(lines: &[Line], context: usize) -> Option<Vec<bool>> {
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.tag != LineTag::Same)
+        .map(|(index, _)| index)
+        .collect();
+    if changed.is_empty() {
+        return None;
+    }
+    Some(
+        (0..lines.len())
+            .map(|index| changed.iter().any(|&c| index.abs_diff(c) <= context))
+            .collect(),
+    )
+}
+
+/// Appends a marker for a run of hidden unchanged lines.
+fn push_marker(output: &mut String, hidden: usize, color: bool) {
+    let marker = format!("@@ {} unchanged lines @@", hidden);
+    if color {
+        output.push_str(&format!("{}{}{}\n", CYAN, marker, RESET));
+    } else {
+        output.push_str(&format!("{}\n", marker));
+    }
+}
+
+/// Recursively produces the tagged lines of the merged old/new documents.
+fn pretty_lines(
+    old: &Value,
+    new: &Value,
+    indent: usize,
+    prefix: &str,
+    comma: bool,
+    options: &RenderOptions,
+    lines: &mut Vec<Line>,
+) {
+    let pad = " ".repeat(indent);
+    let trailer = if comma { "," } else { "" };
+    match (old, new) {
+        _ if old == new => push_unchanged(old, indent, prefix, comma, options, lines),
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            lines.push(Line {
+                tag: LineTag::Same,
+                text: format!("{}{}{{", pad, prefix),
+            });
+            let keys: Vec<&String> = old_map
+                .keys()
+                .chain(new_map.keys().filter(|key| !old_map.contains_key(*key)))
+                .collect();
+            for (position, key) in keys.iter().enumerate() {
+                let key_prefix = format!("{}: ", Value::String((*key).clone()));
+                let last = position + 1 == keys.len();
+                match (old_map.get(*key), new_map.get(*key)) {
+                    (Some(o), Some(n)) => {
+                        pretty_lines(o, n, indent + 2, &key_prefix, !last, options, lines)
+                    }
+                    (Some(o), None) => {
+                        push_value(o, LineTag::Removed, indent + 2, &key_prefix, !last, lines)
+                    }
+                    (None, Some(n)) => {
+                        push_value(n, LineTag::Added, indent + 2, &key_prefix, !last, lines)
+                    }
+                    (None, None) => {}
+                }
+            }
+            lines.push(Line {
+                tag: LineTag::Same,
+                text: format!("{}}}{}", pad, trailer),
+            });
+        }
+        (Value::Array(old_arr), Value::Array(new_arr)) => {
+            lines.push(Line {
+                tag: LineTag::Same,
+                text: format!("{}{}[", pad, prefix),
+            });
+            let len = old_arr.len().max(new_arr.len());
+            for index in 0..len {
+                let last = index + 1 == len;
+                match (old_arr.get(index), new_arr.get(index)) {
+                    (Some(o), Some(n)) => pretty_lines(o, n, indent + 2, "", !last, options, lines),
+                    (Some(o), None) => {
+                        push_value(o, LineTag::Removed, indent + 2, "", !last, lines)
+                    }
+                    (None, Some(n)) => push_value(n, LineTag::Added, indent + 2, "", !last, lines),
+                    (None, None) => {}
+                }
+            }
+            lines.push(Line {
+                tag: LineTag::Same,
+                text: format!("{}]{}", pad, trailer),
+            });
+        }
+        _ => {
+            push_value(old, LineTag::Removed, indent, prefix, comma, lines);
+            push_value(new, LineTag::Added, indent, prefix, comma, lines);
+        }
+    }
+}
+
+/// Pushes an unchanged value, collapsing it when it exceeds the collapse threshold.
+fn push_unchanged(
+    value: &Value,
+    indent: usize,
+    prefix: &str,
+    comma: bool,
+    options: &RenderOptions,
+    lines: &mut Vec<Line>,
+) {
+    let span = pretty(value).lines().count();
+    if span > options.collapse_threshold && (value.is_object() || value.is_array()) {
+        let (open, close) = if value.is_object() {
+            ("{", "}")
+        } else {
+            ("[", "]")
+        };
+        lines.push(Line {
+            tag: LineTag::Same,
+            text: format!(
+                "{}{}{} … {} unchanged lines … {}{}",
+                " ".repeat(indent),
+                prefix,
+                open,
+                span,
+                close,
+                if comma { "," } else { "" }
+            ),
+        });
+    } else {
+        push_value(value, LineTag::Same, indent, prefix, comma, lines);
+    }
+}
+
+/// Pushes the pretty-printed lines of a value with the given tag.
+fn push_value(
+    value: &Value,
+    tag: LineTag,
+    indent: usize,
+    prefix: &str,
+    comma: bool,
+    lines: &mut Vec<Line>,
+) {
+    let pad = " ".repeat(indent);
+    let rendered = pretty(value);
+    let count = rendered.lines().count();
+    for (index, text) in rendered.lines().enumerate() {
+        let lead = if index == 0 { prefix } else { "" };
+        let trailer = if comma && index + 1 == count { "," } else { "" };
+        lines.push(Line {
+            tag,
+            text: format!("{}{}{}{}", pad, lead, text, trailer),
+        });
+    }
+}
+
+/// Pretty-prints a value with two-space indentation.
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
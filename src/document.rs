@@ -0,0 +1,297 @@
+use crate::cst::JsonCst;
+use crate::parser::{
+    convert_to_format, delete_by_path_with, format_json, get_by_path_with, parse_format, search,
+    set_by_path_with, FormatOptions, SearchOptions,
+};
+use crate::path::JsonPath;
+use crate::path_syntax::PathSyntax;
+use crate::validator::{validate_document, ValidationReport};
+use anyhow::{Context, Error, Result};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// Options of a `JsonDocument`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentOptions {
+    /// Syntax of the paths the document reads and reports.
+    pub syntax: PathSyntax,
+    /// Style of `to_json` for documents without source text, or edited beyond it.
+    pub format: FormatOptions,
+}
+
+/// A parsed document with its source text, where it came from, and the options its
+/// methods share: one entry point to query, edit, validate and serialize it.
+///
+/// Documents read from JSON or JSONC keep their text, so `span` locates values in it
+/// and edits keep comments and formatting, as with `JsonCst`. Paths are written in
+/// `options.syntax` throughout, so a path from `search` can be passed to `set`.
+#[derive(Debug, Clone)]
+pub struct JsonDocument {
+    value: Value,
+    cst: Option<JsonCst>,
+    source: Option<String>,
+    options: DocumentOptions,
+}
+
+impl JsonDocument {
+    /// Parses JSON text into a document.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The JSON text.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<JsonDocument, Error>` - The document, or the parse error.
+    pub fn parse(text: &str) -> Result<JsonDocument, Error> {
+        JsonDocument::parse_format(text, "json")
+    }
+
+    /// Parses text of any format `parse_format` reads into a document.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to parse.
+    /// * `format` - "json", "jsonc", "json5", "yaml", "xml", "toml", "csv", "querystring",
+    ///   "dotenv" or "properties". Only JSON and JSONC keep the text for `span` and
+    ///   formatting-preserving edits.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<JsonDocument, Error>` - The document, or the parse error.
+    pub fn parse_format(text: &str, format: &str) -> Result<JsonDocument, Error> {
+        let value = parse_format(text, format)?;
+        let cst = match format {
+            "json" | "jsonc" => Some(JsonCst::parse(text)?),
+            _ => None,
+        };
+        Ok(JsonDocument {
+            value,
+            cst,
+            source: None,
+            options: DocumentOptions::default(),
+        })
+    }
+
+    /// Reads a file into a document whose source is the file path, choosing the format
+    /// by extension (`.jsonc`, `.json5`, `.yaml` / `.yml`, `.toml`, `.xml`, `.csv`,
+    /// `.properties`, and JSON otherwise).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to read.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<JsonDocument, Error>` - The document, or an error if the file cannot be
+    ///   read or parsed.
+    pub fn open(path: impl AsRef<Path>) -> Result<JsonDocument, Error> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let format = match extension.as_str() {
+            "jsonc" | "json5" | "yaml" | "toml" | "xml" | "csv" | "properties" => {
+                extension.as_str()
+            }
+            "yml" => "yaml",
+            _ => "json",
+        };
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file at path: {}", path.display()))?;
+        let document = JsonDocument::parse_format(&text, format)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(document.with_source(path.display().to_string()))
+    }
+
+    /// Wraps a value in a document without source text.
+    pub fn from_value(value: Value) -> JsonDocument {
+        JsonDocument {
+            value,
+            cst: None,
+            source: None,
+            options: DocumentOptions::default(),
+        }
+    }
+
+    /// Records where the document came from, e.g. a file path or URL.
+    pub fn with_source(mut self, source: impl Into<String>) -> JsonDocument {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Replaces the options of the document.
+    pub fn with_options(mut self, options: DocumentOptions) -> JsonDocument {
+        self.options = options;
+        self
+    }
+
+    /// Returns the parsed value.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Returns the parsed value, consuming the document.
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+
+    /// Returns where the document came from, if recorded.
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// Returns the options of the document.
+    pub fn options(&self) -> &DocumentOptions {
+        &self.options
+    }
+
+    /// Returns the byte range of the value at a path in the source text, or `None` if it
+    /// does not exist or the document has no source text.
+    pub fn span(&self, path: &str) -> Option<Range<usize>> {
+        let pointer = self
+            .options
+            .syntax
+            .convert(path, &PathSyntax::pointer())
+            .ok()?;
+        self.cst.as_ref()?.span(&pointer)
+    }
+
+    /// Returns the value at a path, or `None` if it does not exist.
+    pub fn get(&self, path: &str) -> Option<Value> {
+        get_by_path_with(&self.value, path, &self.options.syntax)
+    }
+
+    /// Runs a JSONPath query, e.g. `$.items[?(@.price > 10)].name`.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The JSONPath expression.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<Value>, Error>` - The selected values in document order, or an error
+    ///   if the query is invalid.
+    pub fn query(&self, query: &str) -> Result<Vec<Value>, Error> {
+        Ok(JsonPath::compile(query)?
+            .select(&self.value)
+            .into_iter()
+            .cloned()
+            .collect())
+    }
+
+    /// Searches the document like `search`, reporting paths in the document's syntax.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The text, regular expression or JSON value to find.
+    /// * `options` - How the pattern is matched; its `syntax` is ignored.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<(String, Value)>, Error>` - Each match with its path, or an error for
+    ///   an invalid regular expression.
+    pub fn search(
+        &self,
+        pattern: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<(String, Value)>, Error> {
+        let options = SearchOptions {
+            syntax: self.options.syntax.clone(),
+            ..options.clone()
+        };
+        search(&self.value, pattern, &options)
+    }
+
+    /// Sets the value at a path.
+    ///
+    /// With source text, an existing value is replaced in place and a new member or `-`
+    /// element is added after its neighbors, keeping comments and formatting. Creating
+    /// missing parents, as `set_by_path` does, drops the source text, and `to_json` then
+    /// writes the document in `options.format`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the target value.
+    /// * `value` - The value to store.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - An error if the path is invalid or crosses a scalar.
+    pub fn set(&mut self, path: &str, value: Value) -> Result<(), Error> {
+        if let Some(cst) = &mut self.cst {
+            let pointer = self.options.syntax.convert(path, &PathSyntax::pointer())?;
+            if cst.set(&pointer, &value).is_ok() {
+                self.value = cst.value();
+                return Ok(());
+            }
+        }
+        set_by_path_with(&mut self.value, path, value, &self.options.syntax)?;
+        self.cst = None;
+        Ok(())
+    }
+
+    /// Deletes the value at a path, with the comment lines directly above it in source
+    /// text.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the value to delete.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Value, Error>` - The deleted value, or an error if the path does not exist.
+    pub fn delete(&mut self, path: &str) -> Result<Value, Error> {
+        if let Some(cst) = &mut self.cst {
+            let pointer = self.options.syntax.convert(path, &PathSyntax::pointer())?;
+            let removed = cst.remove(&pointer)?;
+            self.value = cst.value();
+            return Ok(removed);
+        }
+        delete_by_path_with(&mut self.value, path, &self.options.syntax)
+    }
+
+    /// Validates the document against a JSON Schema or key template, like
+    /// `validate_document`.
+    pub fn validate(&self, schema: &Value) -> ValidationReport {
+        validate_document(&self.value, schema)
+    }
+
+    /// Returns the document as JSON: the source text with its edits if it has one, and
+    /// the value formatted with `options.format` otherwise.
+    pub fn to_json(&self) -> String {
+        match &self.cst {
+            Some(cst) => cst.to_string(),
+            None => format_json(&self.value, &self.options.format),
+        }
+    }
+
+    /// Writes the document in a format of `convert_to_format`, or as JSON with "json".
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - "json", "yaml", "xml", "toml", "csv", "markdown", "html",
+    ///   "querystring", "dotenv", "properties" or "sql".
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, Error>` - The text, or an error if the document cannot be
+    ///   written in the format.
+    pub fn serialize(&self, format: &str) -> Result<String, Error> {
+        match format {
+            "json" => Ok(self.to_json()),
+            _ => convert_to_format(&self.value, format),
+        }
+    }
+}
+
+impl fmt::Display for JsonDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_json())
+    }
+}
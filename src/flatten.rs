@@ -0,0 +1,121 @@
+use crate::parser::{insert_at, ParserError};
+use crate::path_syntax::PathSyntax;
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+
+/// Flattens a document into a single-level object with compound keys.
+///
+/// Object keys are joined with the separator and array indexes written in brackets:
+/// `{"a": {"b": [{"c": 1}]}}` becomes `{"a.b[0].c": 1}` with `.`. Keys that are empty
+/// or contain the separator, brackets, quotes or backslashes are quoted in brackets
+/// (`a["x.y"]`), and empty objects and arrays are kept as values, so `unflatten`
+/// restores the document exactly. A scalar document becomes `{"": value}`.
+///
+/// # Arguments
+///
+/// * `json` - The document to flatten.
+/// * `separator` - Text placed between object keys, e.g. `.` or `/`.
+///
+/// # Returns
+///
+/// * `Value` - The flat object.
+pub fn flatten(json: &Value, separator: &str) -> Value {
+    flatten_with(json, &PathSyntax::delimited(separator))
+}
+
+/// Flattens a document into a single-level object whose keys are paths of the given
+/// syntax, e.g. `{"/a/b/0/c": 1}` with JSON Pointers.
+///
+/// # Arguments
+///
+/// * `json` - The document to flatten.
+/// * `syntax` - The syntax of the keys.
+///
+/// # Returns
+///
+/// * `Value` - The flat object.
+pub fn flatten_with(json: &Value, syntax: &PathSyntax) -> Value {
+    let mut flat = Map::new();
+    if json.as_object().is_none_or(|map| !map.is_empty()) {
+        flatten_into(json, String::new(), syntax, &mut flat);
+    }
+    Value::Object(flat)
+}
+
+/// Rebuilds a nested document from the compound keys written by `flatten`.
+///
+/// # Arguments
+///
+/// * `json` - A flat object, e.g. `{"a.b[0].c": 1}`.
+/// * `separator` - The separator the keys were written with.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The nested document, or an error if `json` is not an object,
+///   a key is malformed, two keys conflict (`a` is a number and `a.b` is set), or an
+///   index is more than `MAX_ARRAY_PADDING` past the indexes before it.
+pub fn unflatten(json: &Value, separator: &str) -> Result<Value, Error> {
+    unflatten_with(json, &PathSyntax::delimited(separator))
+}
+
+/// Rebuilds a nested document from the keys written by `flatten_with`.
+///
+/// # Arguments
+///
+/// * `json` - A flat object, e.g. `{"/a/b/0/c": 1}`.
+/// * `syntax` - The syntax the keys were written in.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The nested document, or an error if `json` is not an object,
+///   a key is malformed, two keys conflict, or an index is out of range.
+pub fn unflatten_with(json: &Value, syntax: &PathSyntax) -> Result<Value, Error> {
+    let flat = json
+        .as_object()
+        .ok_or_else(|| Error::msg("Only a flat JSON object can be unflattened"))?;
+    if flat.is_empty() {
+        return Ok(Value::Object(Map::new()));
+    }
+    let mut entries = flat
+        .iter()
+        .map(|(key, value)| {
+            let steps = syntax
+                .parse(key)
+                .map_err(|e| Error::msg(format!("Malformed flattened key: {}", e)))?;
+            Ok((steps, key, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Indexes in numeric order, so arrays grow one element at a time rather than jumping
+    // from a[1] to a[10] as the keys sort.
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut nested = Value::Null;
+    for (steps, key, value) in entries {
+        insert_at(&mut nested, &steps, value.clone()).map_err(|e| {
+            if e.is::<ParserError>() {
+                e.context(format!("Invalid key '{}'", key))
+            } else {
+                Error::msg(format!("Conflicting key '{}': {}", key, e))
+            }
+        })?;
+    }
+    Ok(nested)
+}
+
+/// Recursive helper function for `flatten_with`, adding the leaves under `prefix`.
+fn flatten_into(json: &Value, prefix: String, syntax: &PathSyntax, flat: &mut Map<String, Value>) {
+    match json {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                flatten_into(value, syntax.join_key(&prefix, key), syntax, flat);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (index, item) in items.iter().enumerate() {
+                flatten_into(item, syntax.join_index(&prefix, index), syntax, flat);
+            }
+        }
+        leaf => {
+            flat.insert(prefix, leaf.clone());
+        }
+    }
+}
@@ -0,0 +1,1057 @@
+use crate::sandbox;
+use crate::stats::compare_values;
+use anyhow::{Error, Result};
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::Parser;
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+
+/// JqParser struct, generated from the grammar defined in `jq.pest`.
+///
+/// Covers a practical subset of jq: paths (`.a.b`, `.[0]`, `.[1:3]`, `.[]`, `..`),
+/// pipes, commas, object and array construction, arithmetic, comparisons, `and`,
+/// `or`, `//`, `if`/`then`/`else`, `try`/`catch`, `?` and the built-in functions in
+/// `FUNCTIONS`.
+#[derive(Parser)]
+#[grammar = "jq.pest"]
+struct JqParser;
+
+/// Built-in functions with their number of arguments.
+///
+/// Arguments are filters run against the function's input, as in jq: `select(.price > 10)`
+/// tests `.price` of each value it is given.
+const FUNCTIONS: [(&str, usize); 39] = [
+    // Generic: empty, not, length (characters, items, members or absolute value), type,
+    // tostring, tonumber and tojson.
+    ("empty", 0),
+    ("not", 0),
+    ("length", 0),
+    ("type", 0),
+    ("tostring", 0),
+    ("tonumber", 0),
+    ("tojson", 0),
+    // Arrays: keys also lists object keys; add sums numbers or joins strings, arrays
+    // and objects; any and all test the items for truth; range(n) outputs 0 to n - 1.
+    ("keys", 0),
+    ("add", 0),
+    ("any", 0),
+    ("all", 0),
+    ("sort", 0),
+    ("unique", 0),
+    ("reverse", 0),
+    ("min", 0),
+    ("max", 0),
+    ("first", 0),
+    ("last", 0),
+    ("flatten", 0),
+    ("range", 1),
+    // Objects: {"key", "value"} entries in both directions.
+    ("to_entries", 0),
+    ("from_entries", 0),
+    ("with_entries", 1),
+    ("has", 1),
+    // Strings and numbers.
+    ("ascii_downcase", 0),
+    ("ascii_upcase", 0),
+    ("floor", 0),
+    ("split", 1),
+    ("join", 1),
+    ("test", 1),
+    ("startswith", 1),
+    ("endswith", 1),
+    // Filtering and mapping.
+    ("select", 1),
+    ("map", 1),
+    // Ordering and grouping by the outputs of a filter.
+    ("sort_by", 1),
+    ("group_by", 1),
+    ("unique_by", 1),
+    ("min_by", 1),
+    ("max_by", 1),
+];
+
+/// A compiled jq-style program, reusable across inputs.
+///
+/// Each filter turns one input into zero or more outputs: `.items[]` outputs every
+/// item, `select(...)` outputs its input or nothing, and `a | b` runs `b` on every
+/// output of `a`.
+#[derive(Debug, Clone)]
+pub struct JqFilter {
+    filter: Filter,
+}
+
+/// A node of a compiled program.
+#[derive(Debug, Clone)]
+enum Filter {
+    Identity,
+    Recurse,
+    Literal(Value),
+    /// `.name` or `.[key]` of each output of the first filter.
+    Index(Box<Filter>, Box<Filter>),
+    Slice(Box<Filter>, Option<Box<Filter>>, Option<Box<Filter>>),
+    Iterate(Box<Filter>),
+    /// `f?`: the outputs of `f`, or nothing if it fails.
+    Optional(Box<Filter>),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    Alternative(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Binary(Box<Filter>, BinaryOp, Box<Filter>),
+    Array(Option<Box<Filter>>),
+    Object(Vec<(Filter, Filter)>),
+    /// `try f catch g`: `g` runs on the error message when `f` fails.
+    Try(Box<Filter>, Option<Box<Filter>>),
+    /// Conditions with their branches, and the `else` branch.
+    If(Vec<(Filter, Filter)>, Option<Box<Filter>>),
+    /// A built-in function call, checked against `FUNCTIONS` when compiled.
+    Call(String, Vec<Filter>),
+}
+
+/// Arithmetic and comparison operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl JqFilter {
+    /// Compiles a program.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program, e.g. `.items[] | select(.price > 10) | {name, price}`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<JqFilter, Error>` - The compiled program, or an error if it is invalid or
+    ///   calls an unknown function.
+    pub fn compile(program: &str) -> Result<JqFilter, Error> {
+        let pair = JqParser::parse(Rule::program, program)
+            .map_err(|e| Error::msg(format!("Invalid program: {}", e)))?
+            .next()
+            .and_then(|program| program.into_inner().next())
+            .ok_or_else(|| Error::msg("Empty program"))?;
+        Ok(JqFilter {
+            filter: build(pair)?,
+        })
+    }
+
+    /// Runs the program against an input.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The input value.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<Value>, Error>` - The outputs in order, or the first error, such as
+    ///   indexing a number or iterating over a string.
+    pub fn run(&self, input: &Value) -> Result<Vec<Value>, Error> {
+        self.filter.run(input)
+    }
+}
+
+/// Compiles and runs a jq-style program against a JSON value.
+///
+/// # Arguments
+///
+/// * `input` - The input value.
+/// * `program` - The program, e.g. `.items[] | select(.price > 10) | {name, price}`.
+///
+/// # Returns
+///
+/// * `Result<Vec<Value>, Error>` - The outputs in order, or an error if the program is
+///   invalid or fails.
+pub fn eval_expr(input: &Value, program: &str) -> Result<Vec<Value>, Error> {
+    JqFilter::compile(program)?.run(input)
+}
+
+/// Builds a filter from a parsed grammar rule.
+fn build(pair: Pair<Rule>) -> Result<Filter, Error> {
+    let rule = pair.as_rule();
+    match rule {
+        Rule::pipe | Rule::comma | Rule::alternative | Rule::or_expr | Rule::and_expr => {
+            let mut parts = pair
+                .into_inner()
+                .filter(|part| !matches!(part.as_rule(), Rule::or_op | Rule::and_op))
+                .map(build);
+            let first = parts
+                .next()
+                .ok_or_else(|| Error::msg("Missing expression"))??;
+            parts.try_fold(first, |left, right| {
+                let (left, right) = (Box::new(left), Box::new(right?));
+                Ok(match rule {
+                    Rule::pipe => Filter::Pipe(left, right),
+                    Rule::comma => Filter::Comma(left, right),
+                    Rule::alternative => Filter::Alternative(left, right),
+                    Rule::or_expr => Filter::Or(left, right),
+                    _ => Filter::And(left, right),
+                })
+            })
+        }
+        Rule::comparison | Rule::sum | Rule::product => {
+            let mut inner = pair.into_inner();
+            let mut left = build(inner.next().ok_or_else(|| Error::msg("Missing operand"))?)?;
+            while let (Some(op), Some(right)) = (inner.next(), inner.next()) {
+                let op = match op.as_str() {
+                    "+" => BinaryOp::Add,
+                    "-" => BinaryOp::Sub,
+                    "*" => BinaryOp::Mul,
+                    "/" => BinaryOp::Div,
+                    "%" => BinaryOp::Rem,
+                    "==" => BinaryOp::Eq,
+                    "!=" => BinaryOp::Ne,
+                    "<" => BinaryOp::Lt,
+                    "<=" => BinaryOp::Le,
+                    ">" => BinaryOp::Gt,
+                    _ => BinaryOp::Ge,
+                };
+                left = Filter::Binary(Box::new(left), op, Box::new(build(right)?));
+            }
+            Ok(left)
+        }
+        Rule::postfix => {
+            let mut inner = pair.into_inner();
+            let mut filter = build(inner.next().ok_or_else(|| Error::msg("Missing term"))?)?;
+            for suffix in inner {
+                filter = match suffix.as_rule() {
+                    Rule::optional => Filter::Optional(Box::new(filter)),
+                    _ => build_suffix(filter, suffix)?,
+                };
+            }
+            Ok(filter)
+        }
+        Rule::field => build_suffix(Filter::Identity, pair),
+        Rule::negate => {
+            let inner = pair
+                .into_inner()
+                .next()
+                .ok_or_else(|| Error::msg("Missing operand"))?;
+            Ok(Filter::Binary(
+                Box::new(Filter::Literal(Value::from(0))),
+                BinaryOp::Sub,
+                Box::new(build(inner)?),
+            ))
+        }
+        Rule::identity => Ok(Filter::Identity),
+        Rule::recurse => Ok(Filter::Recurse),
+        Rule::number => serde_json::from_str(pair.as_str())
+            .map(Filter::Literal)
+            .map_err(|e| Error::msg(format!("Invalid number {}: {}", pair.as_str(), e))),
+        Rule::string => Ok(Filter::Literal(Value::String(decode_string(&pair)?))),
+        Rule::true_lit => Ok(Filter::Literal(Value::Bool(true))),
+        Rule::false_lit => Ok(Filter::Literal(Value::Bool(false))),
+        Rule::null_lit => Ok(Filter::Literal(Value::Null)),
+        Rule::array => pair
+            .into_inner()
+            .next()
+            .map(build)
+            .transpose()
+            .map(|inner| Filter::Array(inner.map(Box::new))),
+        Rule::object => pair
+            .into_inner()
+            .map(build_entry)
+            .collect::<Result<Vec<_>, Error>>()
+            .map(Filter::Object),
+        Rule::if_expr => {
+            let mut parts = pair
+                .into_inner()
+                .filter(|part| part.as_rule() == Rule::pipe)
+                .map(build)
+                .collect::<Result<Vec<_>, Error>>()?;
+            let otherwise = (parts.len() % 2 == 1).then(|| Box::new(parts.remove(parts.len() - 1)));
+            let mut branches = Vec::new();
+            let mut parts = parts.into_iter();
+            while let (Some(condition), Some(body)) = (parts.next(), parts.next()) {
+                branches.push((condition, body));
+            }
+            Ok(Filter::If(branches, otherwise))
+        }
+        Rule::try_expr => {
+            let mut parts = pair
+                .into_inner()
+                .filter(|part| part.as_rule() == Rule::postfix)
+                .map(build);
+            let body = parts
+                .next()
+                .ok_or_else(|| Error::msg("Missing try body"))??;
+            let handler = parts.next().transpose()?.map(Box::new);
+            Ok(Filter::Try(Box::new(body), handler))
+        }
+        Rule::call => {
+            let mut inner = pair.into_inner();
+            let name = inner
+                .next()
+                .ok_or_else(|| Error::msg("Missing function name"))?
+                .as_str()
+                .to_string();
+            let args = inner.map(build).collect::<Result<Vec<_>, Error>>()?;
+            if !FUNCTIONS.contains(&(name.as_str(), args.len())) {
+                return Err(Error::msg(format!(
+                    "Unknown function: {}/{}",
+                    name,
+                    args.len()
+                )));
+            }
+            Ok(Filter::Call(name, args))
+        }
+        Rule::entry_value => build(
+            pair.into_inner()
+                .next()
+                .ok_or_else(|| Error::msg("Missing object value"))?,
+        ),
+        _ => Err(Error::msg(format!(
+            "Unexpected token in program: {}",
+            pair.as_str()
+        ))),
+    }
+}
+
+/// Applies a `.name`, `[...]` or `[start:end]` suffix to a filter.
+fn build_suffix(filter: Filter, suffix: Pair<Rule>) -> Result<Filter, Error> {
+    let filter = Box::new(filter);
+    if suffix.as_rule() == Rule::field {
+        let key = suffix
+            .into_inner()
+            .next()
+            .ok_or_else(|| Error::msg("Missing field name"))?;
+        return Ok(Filter::Index(
+            filter,
+            Box::new(Filter::Literal(key_name(&key)?)),
+        ));
+    }
+    let Some(inner) = suffix.into_inner().next() else {
+        return Ok(Filter::Iterate(filter));
+    };
+    if inner.as_rule() != Rule::slice {
+        return Ok(Filter::Index(filter, Box::new(build(inner)?)));
+    }
+    let (mut start, mut end) = (None, None);
+    for bound in inner.into_inner() {
+        let is_start = bound.as_rule() == Rule::slice_start;
+        let bound = bound
+            .into_inner()
+            .next()
+            .map(build)
+            .transpose()?
+            .map(Box::new);
+        if is_start {
+            start = bound;
+        } else {
+            end = bound;
+        }
+    }
+    Ok(Filter::Slice(filter, start, end))
+}
+
+/// Builds an object entry; `{name}` is short for `{name: .name}`.
+fn build_entry(entry: Pair<Rule>) -> Result<(Filter, Filter), Error> {
+    let mut inner = entry.into_inner();
+    let key = inner
+        .next()
+        .ok_or_else(|| Error::msg("Missing object key"))?;
+    let value = inner.next().map(build).transpose()?;
+    if key.as_rule() == Rule::pipe {
+        let value = value.ok_or_else(|| Error::msg("A computed object key needs a value"))?;
+        return Ok((build(key)?, value));
+    }
+    let key = Filter::Literal(key_name(&key)?);
+    let value =
+        value.unwrap_or_else(|| Filter::Index(Box::new(Filter::Identity), Box::new(key.clone())));
+    Ok((key, value))
+}
+
+/// Returns a bare or quoted key as a string value.
+fn key_name(key: &Pair<Rule>) -> Result<Value, Error> {
+    match key.as_rule() {
+        Rule::string => decode_string(key).map(Value::String),
+        _ => Ok(Value::String(key.as_str().to_string())),
+    }
+}
+
+/// Decodes a double-quoted string literal with JSON escapes.
+fn decode_string(pair: &Pair<Rule>) -> Result<String, Error> {
+    serde_json::from_str(pair.as_str())
+        .map_err(|e| Error::msg(format!("Invalid string {}: {}", pair.as_str(), e)))
+}
+
+impl Filter {
+    /// Runs the filter against an input, returning its outputs.
+    fn run(&self, input: &Value) -> Result<Vec<Value>, Error> {
+        sandbox::charge(1)?;
+        match self {
+            Filter::Identity => Ok(vec![input.clone()]),
+            Filter::Recurse => {
+                let mut values = Vec::new();
+                descendants(input, &mut values);
+                sandbox::charge(values.len() as u64)?;
+                Ok(values)
+            }
+            Filter::Literal(value) => Ok(vec![value.clone()]),
+            Filter::Index(..) | Filter::Slice(..) | Filter::Iterate(_) => {
+                self.run_step(input, false)
+            }
+            Filter::Optional(inner) => match inner.as_ref() {
+                Filter::Index(..) | Filter::Slice(..) | Filter::Iterate(_) => {
+                    inner.run_step(input, true)
+                }
+                _ => Ok(inner.run(input).unwrap_or_default()),
+            },
+            Filter::Pipe(left, right) => {
+                let mut values = Vec::new();
+                for value in left.run(input)? {
+                    values.extend(right.run(&value)?);
+                }
+                Ok(values)
+            }
+            Filter::Comma(left, right) => {
+                let mut values = left.run(input)?;
+                values.extend(right.run(input)?);
+                Ok(values)
+            }
+            Filter::Alternative(left, right) => {
+                let values: Vec<Value> = left
+                    .run(input)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(truthy)
+                    .collect();
+                if values.is_empty() {
+                    right.run(input)
+                } else {
+                    Ok(values)
+                }
+            }
+            Filter::Or(left, right) | Filter::And(left, right) => {
+                let is_or = matches!(self, Filter::Or(..));
+                let mut values = Vec::new();
+                for value in left.run(input)? {
+                    if truthy(&value) == is_or {
+                        values.push(Value::Bool(is_or));
+                        continue;
+                    }
+                    for value in right.run(input)? {
+                        values.push(Value::Bool(truthy(&value)));
+                    }
+                }
+                Ok(values)
+            }
+            Filter::Binary(left, op, right) => {
+                let lefts = left.run(input)?;
+                let mut values = Vec::new();
+                for right in right.run(input)? {
+                    for left in &lefts {
+                        let value = op.apply(left, &right)?;
+                        sandbox::allocate(&value)?;
+                        values.push(value);
+                    }
+                }
+                Ok(values)
+            }
+            Filter::Array(inner) => {
+                let items = match inner {
+                    Some(inner) => inner.run(input)?,
+                    None => Vec::new(),
+                };
+                let array = Value::Array(items);
+                sandbox::allocate(&array)?;
+                Ok(vec![array])
+            }
+            Filter::Object(entries) => {
+                let mut objects = vec![Map::new()];
+                for (key, value) in entries {
+                    let (keys, values) = (key.run(input)?, value.run(input)?);
+                    let mut next = Vec::new();
+                    for object in &objects {
+                        for key in &keys {
+                            let Value::String(key) = key else {
+                                return Err(Error::msg(format!(
+                                    "Object keys must be strings, got {}",
+                                    type_name(key)
+                                )));
+                            };
+                            for value in &values {
+                                sandbox::charge(1)?;
+                                let mut object = object.clone();
+                                object.insert(key.clone(), value.clone());
+                                next.push(object);
+                            }
+                        }
+                    }
+                    objects = next;
+                }
+                let objects: Vec<Value> = objects.into_iter().map(Value::Object).collect();
+                for object in &objects {
+                    sandbox::allocate(object)?;
+                }
+                Ok(objects)
+            }
+            Filter::Try(body, handler) => match (body.run(input), handler) {
+                (Ok(values), _) => Ok(values),
+                (Err(e), Some(handler)) => handler.run(&Value::String(e.to_string())),
+                (Err(_), None) => Ok(Vec::new()),
+            },
+            Filter::If(branches, otherwise) => run_if(branches, otherwise.as_deref(), input),
+            Filter::Call(name, args) => call(name, args, input),
+        }
+    }
+
+    /// Runs an index, slice or iteration. When `lenient`, as for `.items[].tags[]?`,
+    /// values it cannot be applied to are skipped instead of failing the program.
+    fn run_step(&self, input: &Value, lenient: bool) -> Result<Vec<Value>, Error> {
+        let (Filter::Index(target, _) | Filter::Slice(target, ..) | Filter::Iterate(target)) = self
+        else {
+            return self.run(input);
+        };
+        let step = |value: &Value| match self {
+            Filter::Index(_, key) => key
+                .run(input)?
+                .iter()
+                .map(|key| index(value, key))
+                .collect::<Result<Vec<_>, Error>>(),
+            Filter::Slice(_, start, end) => {
+                let bounds = |bound: &Option<Box<Filter>>| match bound {
+                    Some(bound) => Ok(bound.run(input)?.into_iter().map(Some).collect()),
+                    None => Ok::<_, Error>(vec![None]),
+                };
+                let mut values = Vec::new();
+                for start in &bounds(start)? {
+                    for end in &bounds(end)? {
+                        values.push(slice(value, start.as_ref(), end.as_ref())?);
+                    }
+                }
+                Ok(values)
+            }
+            _ => iterate(value),
+        };
+        let targets = match target.run(input) {
+            Err(_) if lenient => Vec::new(),
+            targets => targets?,
+        };
+        let mut values = Vec::new();
+        for value in &targets {
+            match step(value) {
+                Ok(outputs) => {
+                    sandbox::charge(outputs.len() as u64)?;
+                    values.extend(outputs);
+                }
+                Err(_) if lenient => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Runs the first branch whose condition holds, for each output of the condition.
+fn run_if(
+    branches: &[(Filter, Filter)],
+    otherwise: Option<&Filter>,
+    input: &Value,
+) -> Result<Vec<Value>, Error> {
+    let Some(((condition, body), rest)) = branches.split_first() else {
+        return otherwise.map_or_else(|| Ok(vec![input.clone()]), |filter| filter.run(input));
+    };
+    let mut values = Vec::new();
+    for value in condition.run(input)? {
+        if truthy(&value) {
+            values.extend(body.run(input)?);
+        } else {
+            values.extend(run_if(rest, otherwise, input)?);
+        }
+    }
+    Ok(values)
+}
+
+impl BinaryOp {
+    fn apply(self, left: &Value, right: &Value) -> Result<Value, Error> {
+        let order = compare(left, right);
+        let result = match self {
+            BinaryOp::Eq => order == Ordering::Equal,
+            BinaryOp::Ne => order != Ordering::Equal,
+            BinaryOp::Lt => order == Ordering::Less,
+            BinaryOp::Le => order != Ordering::Greater,
+            BinaryOp::Gt => order == Ordering::Greater,
+            BinaryOp::Ge => order != Ordering::Less,
+            _ => return arithmetic(left, self, right),
+        };
+        Ok(Value::Bool(result))
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+        }
+    }
+}
+
+/// Applies `+ - * / %`: numbers, joined strings, arrays and objects, array difference,
+/// and string splitting with `/`. `null` is the identity of `+`.
+fn arithmetic(left: &Value, op: BinaryOp, right: &Value) -> Result<Value, Error> {
+    match (op, left, right) {
+        (BinaryOp::Add, Value::Null, other) | (BinaryOp::Add, other, Value::Null) => {
+            Ok(other.clone())
+        }
+        (_, Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (a.as_f64().unwrap_or(0.0), b.as_f64().unwrap_or(0.0));
+            let result = match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div if b != 0.0 => a / b,
+                BinaryOp::Rem if b as i64 != 0 => (a as i64 % b as i64) as f64,
+                _ => {
+                    return Err(Error::msg(format!(
+                        "{} and {} cannot be divided because the divisor is zero",
+                        left, right
+                    )))
+                }
+            };
+            Ok(number(result))
+        }
+        (BinaryOp::Add, Value::String(a), Value::String(b)) => {
+            Ok(Value::String(format!("{}{}", a, b)))
+        }
+        (BinaryOp::Add, Value::Array(a), Value::Array(b)) => {
+            Ok(Value::Array(a.iter().chain(b).cloned().collect()))
+        }
+        (BinaryOp::Add, Value::Object(a), Value::Object(b)) => {
+            let mut merged = a.clone();
+            merged.extend(b.clone());
+            Ok(Value::Object(merged))
+        }
+        (BinaryOp::Sub, Value::Array(a), Value::Array(b)) => Ok(Value::Array(
+            a.iter().filter(|item| !b.contains(item)).cloned().collect(),
+        )),
+        (BinaryOp::Div, Value::String(a), Value::String(b)) => Ok(split(a, b)),
+        _ => Err(Error::msg(format!(
+            "{} and {} cannot be combined with '{}'",
+            type_name(left),
+            type_name(right),
+            op.symbol()
+        ))),
+    }
+}
+
+/// Runs a built-in function.
+fn call(name: &str, args: &[Filter], input: &Value) -> Result<Vec<Value>, Error> {
+    let Some(arg) = args.first() else {
+        return Ok(match name {
+            "empty" => Vec::new(),
+            _ => vec![builtin(name, input)?],
+        });
+    };
+    let mut values = Vec::new();
+    match name {
+        "select" => {
+            for value in arg.run(input)? {
+                if truthy(&value) {
+                    values.push(input.clone());
+                }
+            }
+        }
+        "map" => {
+            let mut items = Vec::new();
+            for item in iterate(input)? {
+                items.extend(arg.run(&item)?);
+            }
+            values.push(Value::Array(items));
+        }
+        "with_entries" => {
+            let mut entries = Vec::new();
+            for entry in iterate(&builtin("to_entries", input)?)? {
+                entries.extend(arg.run(&entry)?);
+            }
+            values.push(builtin("from_entries", &Value::Array(entries))?);
+        }
+        "range" => {
+            for end in arg.run(input)? {
+                let end = end
+                    .as_f64()
+                    .ok_or_else(|| Error::msg("range() needs a number"))?;
+                sandbox::charge(end.max(0.0).ceil() as u64)?;
+                values.extend((0..end.ceil() as i64).map(Value::from));
+            }
+        }
+        "sort_by" | "group_by" | "unique_by" | "min_by" | "max_by" => {
+            values.push(by_key(name, input, arg)?);
+        }
+        "join" => {
+            for separator in arg.run(input)? {
+                let separator = expect_string(name, &separator)?;
+                let mut parts = Vec::new();
+                for item in iterate(input)? {
+                    parts.push(match item {
+                        Value::Null => String::new(),
+                        Value::String(text) => text,
+                        Value::Array(_) | Value::Object(_) => {
+                            return Err(Error::msg(format!("Cannot join {}", type_name(&item))))
+                        }
+                        other => other.to_string(),
+                    });
+                }
+                let joined = Value::String(parts.join(separator));
+                sandbox::allocate(&joined)?;
+                values.push(joined);
+            }
+        }
+        _ => {
+            for value in arg.run(input)? {
+                values.push(call_with_value(name, input, &value)?);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Runs a built-in function of the input and one argument value.
+fn call_with_value(name: &str, input: &Value, arg: &Value) -> Result<Value, Error> {
+    match name {
+        "has" => match (input, arg) {
+            (Value::Object(map), Value::String(key)) => Ok(Value::Bool(map.contains_key(key))),
+            (Value::Array(items), Value::Number(n)) => Ok(Value::Bool(
+                n.as_f64()
+                    .is_some_and(|i| i >= 0.0 && i < items.len() as f64),
+            )),
+            _ => Err(Error::msg(format!(
+                "Cannot check whether {} has a {} key",
+                type_name(input),
+                type_name(arg)
+            ))),
+        },
+        _ => {
+            let (text, arg) = (expect_string(name, input)?, expect_string(name, arg)?);
+            Ok(match name {
+                "split" => split(text, arg),
+                "test" => {
+                    let pattern = Regex::new(arg)
+                        .map_err(|e| Error::msg(format!("Invalid pattern in test(): {}", e)))?;
+                    Value::Bool(pattern.is_match(text))
+                }
+                "startswith" => Value::Bool(text.starts_with(arg)),
+                _ => Value::Bool(text.ends_with(arg)),
+            })
+        }
+    }
+}
+
+/// Runs a built-in function without arguments.
+fn builtin(name: &str, input: &Value) -> Result<Value, Error> {
+    let unsupported = || {
+        Error::msg(format!(
+            "{}() is not defined for {}",
+            name,
+            type_name(input)
+        ))
+    };
+    Ok(match (name, input) {
+        ("not", _) => Value::Bool(!truthy(input)),
+        ("type", _) => Value::from(type_name(input)),
+        ("tojson", _) => Value::String(input.to_string()),
+        ("tostring", Value::String(_)) => input.clone(),
+        ("tostring", _) => Value::String(input.to_string()),
+        ("tonumber", Value::Number(_)) => input.clone(),
+        ("tonumber", Value::String(text)) => text
+            .trim()
+            .parse::<f64>()
+            .map(number)
+            .map_err(|_| Error::msg(format!("Cannot parse '{}' as a number", text)))?,
+        ("length", Value::Null) => Value::from(0),
+        ("length", Value::String(text)) => Value::from(text.chars().count()),
+        ("length", Value::Array(items)) => Value::from(items.len()),
+        ("length", Value::Object(map)) => Value::from(map.len()),
+        ("length", Value::Number(n)) => number(n.as_f64().unwrap_or(0.0).abs()),
+        ("keys", Value::Object(map)) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            Value::from(keys.into_iter().cloned().collect::<Vec<_>>())
+        }
+        ("keys", Value::Array(items)) => Value::from((0..items.len()).collect::<Vec<_>>()),
+        ("add", Value::Null) => Value::Null,
+        ("add", _) => iterate(input)?.iter().try_fold(Value::Null, |sum, item| {
+            arithmetic(&sum, BinaryOp::Add, item)
+        })?,
+        ("any", _) => Value::Bool(iterate(input)?.iter().any(truthy)),
+        ("all", _) => Value::Bool(iterate(input)?.iter().all(truthy)),
+        ("first", _) => index(input, &json!(0))?,
+        ("last", _) => index(input, &json!(-1))?,
+        ("reverse", Value::Null) => json!([]),
+        ("reverse", Value::String(text)) => Value::String(text.chars().rev().collect()),
+        ("reverse", Value::Array(items)) => Value::Array(items.iter().rev().cloned().collect()),
+        ("sort" | "unique" | "min" | "max", Value::Array(items)) => {
+            let mut items = items.clone();
+            items.sort_by(compare);
+            match name {
+                "sort" => Value::Array(items),
+                "unique" => {
+                    items.dedup_by(|a, b| compare(a, b) == Ordering::Equal);
+                    Value::Array(items)
+                }
+                "min" => items.into_iter().next().unwrap_or(Value::Null),
+                _ => items.pop().unwrap_or(Value::Null),
+            }
+        }
+        ("flatten", Value::Array(items)) => {
+            let mut flat = Vec::new();
+            flatten(items, &mut flat);
+            Value::Array(flat)
+        }
+        ("to_entries", Value::Object(map)) => Value::Array(
+            map.iter()
+                .map(|(key, value)| json!({ "key": key, "value": value }))
+                .collect(),
+        ),
+        ("to_entries", Value::Array(items)) => Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(key, value)| json!({ "key": key, "value": value }))
+                .collect(),
+        ),
+        ("from_entries", Value::Array(entries)) => {
+            let mut map = Map::new();
+            for entry in entries {
+                let field = |names: &[&str]| names.iter().find_map(|name| entry.get(name));
+                let key = match field(&["key", "k", "name"]) {
+                    Some(Value::String(key)) => key.clone(),
+                    Some(key @ (Value::Number(_) | Value::Bool(_))) => key.to_string(),
+                    _ => return Err(Error::msg(format!("Entry {} has no string key", entry))),
+                };
+                map.insert(key, field(&["value", "v"]).cloned().unwrap_or(Value::Null));
+            }
+            Value::Object(map)
+        }
+        ("ascii_downcase", Value::String(text)) => Value::String(text.to_ascii_lowercase()),
+        ("ascii_upcase", Value::String(text)) => Value::String(text.to_ascii_uppercase()),
+        ("floor", Value::Number(n)) => number(n.as_f64().unwrap_or(0.0).floor()),
+        _ => return Err(unsupported()),
+    })
+}
+
+/// Runs `sort_by`, `group_by`, `unique_by`, `min_by` or `max_by`, keying each item by
+/// the array of the filter's outputs.
+fn by_key(name: &str, input: &Value, key: &Filter) -> Result<Value, Error> {
+    let Value::Array(items) = input else {
+        return Err(Error::msg(format!(
+            "{}() is not defined for {}",
+            name,
+            type_name(input)
+        )));
+    };
+    let mut keyed = items
+        .iter()
+        .map(|item| Ok((Value::Array(key.run(item)?), item.clone())))
+        .collect::<Result<Vec<_>, Error>>()?;
+    keyed.sort_by(|a, b| compare(&a.0, &b.0));
+    Ok(match name {
+        "sort_by" => Value::Array(keyed.into_iter().map(|(_, item)| item).collect()),
+        "min_by" => keyed
+            .into_iter()
+            .next()
+            .map_or(Value::Null, |(_, item)| item),
+        "max_by" => keyed.pop().map_or(Value::Null, |(_, item)| item),
+        _ => {
+            let mut groups: Vec<(Value, Vec<Value>)> = Vec::new();
+            for (key, item) in keyed {
+                match groups.last_mut() {
+                    Some((last, group)) if compare(last, &key) == Ordering::Equal => {
+                        group.push(item)
+                    }
+                    _ => groups.push((key, vec![item])),
+                }
+            }
+            Value::Array(
+                groups
+                    .into_iter()
+                    .map(|(_, mut group)| match name {
+                        "group_by" => Value::Array(group),
+                        _ => group.swap_remove(0),
+                    })
+                    .collect(),
+            )
+        }
+    })
+}
+
+/// Returns `value[key]`: an object member, an array item (negative indexes count from
+/// the end), or `null` when it is missing or `value` is `null`.
+fn index(value: &Value, key: &Value) -> Result<Value, Error> {
+    match (value, key) {
+        (Value::Object(map), Value::String(name)) => {
+            Ok(map.get(name).cloned().unwrap_or(Value::Null))
+        }
+        (Value::Array(items), Value::Number(n)) => {
+            let i = n.as_f64().unwrap_or(0.0).floor() as i64;
+            let i = if i < 0 { items.len() as i64 + i } else { i };
+            Ok(usize::try_from(i)
+                .ok()
+                .and_then(|i| items.get(i))
+                .cloned()
+                .unwrap_or(Value::Null))
+        }
+        (Value::Null, Value::String(_) | Value::Number(_)) => Ok(Value::Null),
+        _ => Err(Error::msg(format!(
+            "Cannot index {} with {}",
+            type_name(value),
+            key
+        ))),
+    }
+}
+
+/// Returns `value[start:end]` of an array or string; bounds default to the whole value.
+fn slice(value: &Value, start: Option<&Value>, end: Option<&Value>) -> Result<Value, Error> {
+    let bound = |bound: Option<&Value>, length: usize, default: usize| match bound {
+        None | Some(Value::Null) => Ok(default),
+        Some(Value::Number(n)) => {
+            let (i, length) = (n.as_f64().unwrap_or(0.0).floor() as i64, length as i64);
+            Ok((if i < 0 { length + i } else { i }).clamp(0, length) as usize)
+        }
+        Some(other) => Err(Error::msg(format!(
+            "Cannot slice with {}",
+            type_name(other)
+        ))),
+    };
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Array(items) => {
+            let start = bound(start, items.len(), 0)?;
+            let end = bound(end, items.len(), items.len())?.max(start);
+            Ok(Value::Array(items[start..end].to_vec()))
+        }
+        Value::String(text) => {
+            let chars: Vec<char> = text.chars().collect();
+            let start = bound(start, chars.len(), 0)?;
+            let end = bound(end, chars.len(), chars.len())?.max(start);
+            Ok(Value::String(chars[start..end].iter().collect()))
+        }
+        _ => Err(Error::msg(format!("Cannot slice {}", type_name(value)))),
+    }
+}
+
+/// Returns the items of an array or the member values of an object.
+fn iterate(value: &Value) -> Result<Vec<Value>, Error> {
+    match value {
+        Value::Array(items) => Ok(items.clone()),
+        Value::Object(map) => Ok(map.values().cloned().collect()),
+        _ => Err(Error::msg(format!(
+            "Cannot iterate over {}",
+            type_name(value)
+        ))),
+    }
+}
+
+/// Collects a value and everything nested in it, parents first.
+fn descendants(value: &Value, values: &mut Vec<Value>) {
+    values.push(value.clone());
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| descendants(item, values)),
+        Value::Object(map) => map.values().for_each(|item| descendants(item, values)),
+        _ => {}
+    }
+}
+
+/// Appends the items of nested arrays.
+fn flatten(items: &[Value], flat: &mut Vec<Value>) {
+    for item in items {
+        match item {
+            Value::Array(inner) => flatten(inner, flat),
+            other => flat.push(other.clone()),
+        }
+    }
+}
+
+/// Splits a string into an array of parts.
+fn split(text: &str, separator: &str) -> Value {
+    Value::from(text.split(separator).map(Value::from).collect::<Vec<_>>())
+}
+
+/// Returns the string in a value, or an error naming the function.
+fn expect_string<'a>(name: &str, value: &'a Value) -> Result<&'a str, Error> {
+    value.as_str().ok_or_else(|| {
+        Error::msg(format!(
+            "{}() needs strings, got {}",
+            name,
+            type_name(value)
+        ))
+    })
+}
+
+/// Everything except `null` and `false` is true.
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+/// Orders values as jq does: by type, then numbers by value, strings by text, and
+/// arrays and objects item by item.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    let items = |a: &Vec<Value>, b: &Vec<Value>| {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| compare(x, y))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    };
+    match (a, b) {
+        (Value::Array(x), Value::Array(y)) => items(x, y),
+        (Value::Object(x), Value::Object(y)) => {
+            let keys = |map: &Map<String, Value>| {
+                let mut keys: Vec<Value> = map.keys().cloned().map(Value::String).collect();
+                keys.sort_by(compare);
+                keys
+            };
+            let (x_keys, y_keys) = (keys(x), keys(y));
+            items(&x_keys, &y_keys).then_with(|| {
+                let values = |map: &Map<String, Value>, keys: &[Value]| -> Vec<Value> {
+                    keys.iter()
+                        .filter_map(|key| map.get(key.as_str()?).cloned())
+                        .collect()
+                };
+                items(&values(x, &x_keys), &values(y, &y_keys))
+            })
+        }
+        _ => compare_values(a, b),
+    }
+}
+
+/// Returns a number, as an integer when it is whole.
+fn number(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < 9e15 {
+        Value::from(value as i64)
+    } else {
+        serde_json::Number::from_f64(value).map_or(Value::Null, Value::Number)
+    }
+}
+
+/// Returns the jq type name of a value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
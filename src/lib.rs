@@ -1,6 +1,150 @@
+pub mod array_ops;
+pub mod audit;
+pub mod avro;
+pub mod bench;
+pub mod bson;
+pub mod builder;
+pub mod cancel;
+pub mod cbor;
+pub mod compat;
+pub mod config;
+pub mod cst;
+pub mod csv;
+pub mod datetime;
+pub mod diff;
+pub mod document;
+pub mod flatten;
+pub mod jq;
+pub mod merge;
+pub mod migrate;
+pub mod ndjson;
+#[cfg(feature = "parquet")]
+pub mod parquet;
 pub mod parser;
+pub mod patch;
+pub mod path;
+pub mod path_syntax;
+pub mod presets;
+pub mod properties;
+pub mod querystring;
+pub mod random;
+pub mod recovery;
+pub mod redact;
+pub mod regression;
+pub mod sandbox;
+pub mod schema;
+pub mod serve;
+pub mod sql;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
+pub mod table;
+pub mod toml;
+pub mod transform;
+pub mod type_schema;
+pub mod validator;
+pub mod warnings;
+pub mod xml;
+pub mod xsd;
+pub mod yaml;
 
 pub use parser::{
-    convert_to_format, edit_json, handle_large_json, parse_json, parse_partial_json,
-    validate_json_schema,ParserError
+    canonicalize, convert_from_bytes, convert_from_format, convert_to_bytes, convert_to_format,
+    delete_by_path, delete_by_path_with, edit_json, for_each_record, format_json, get_by_path_with,
+    handle_large_json, parse_format, parse_json, parse_json5, parse_jsonc, parse_partial_json,
+    search, search_by_key, select_paths, set_by_path, set_by_path_with, strip_json_comments,
+    validate_json_schema, Diagnostic, Diagnostics, FormatOptions, JsonValues, ParseErrorDetails,
+    ParserError, SearchOptions, MAX_ARRAY_PADDING,
 };
+
+pub use audit::{audited, hash_value, record_changes, AuditEntry, AuditLog, AuditSink};
+pub use avro::{
+    avro_json_to_json, avro_to_json, infer_avro_schema, json_to_avro, json_to_avro_json,
+    AvroOptions,
+};
+pub use bench::{bench_report, compare_bench, fetch_corpora, run_bench, BenchResult};
+pub use bson::{bson_to_json, json_to_bson, BsonOptions};
+pub use builder::{ArrayBuilder, JsonBuilder, KeyBuilder, ObjectBuilder};
+pub use cancel::{with_timeout, CancellationToken, Cancelled, MAX_TIMEOUT_WORKERS};
+pub use cbor::{cbor_to_json, json_to_cbor, CborOptions};
+pub use config::{Config, SavedQuery, DEFAULT_CONFIG_FILE};
+pub use cst::JsonCst;
+pub use csv::{csv_to_json, csv_to_json_validated, json_to_csv, CsvImport, CsvOptions};
+pub use document::{DocumentOptions, JsonDocument};
+pub use flatten::{flatten, flatten_with, unflatten, unflatten_with};
+pub use jq::{eval_expr, JqFilter};
+#[cfg(feature = "parquet")]
+pub use parquet::{json_to_parquet, ParquetOptions};
+pub use path_syntax::{BracketStyle, PathSyntax};
+pub use properties::{
+    dotenv_to_json, json_to_dotenv, json_to_properties, properties_to_json, PropertiesOptions,
+};
+pub use querystring::{json_to_querystring, querystring_to_json, ArrayFormat, QueryStringOptions};
+pub use random::SeededRng;
+pub use recovery::{parse_json_with_recovery, ParseDiagnostic};
+pub use redact::{redact, RedactionRules, Replacement};
+pub use regression::{replay_corpus, replay_input, save_repro, ReplayFailure};
+pub use sandbox::Sandbox;
+pub use serve::{token_digest, DocumentServer};
+pub use sql::{json_to_sql, SqlOptions};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{json_to_sqlite, sqlite_to_json, SqliteOptions};
+pub use table::{json_to_html, json_to_markdown, NestedCells, TableOptions};
+pub use type_schema::{schema_for, validate_as};
+
+pub use diff::{
+    diff_json, diff_json_with, render_diff, render_side_by_side, DiffEntry, DiffFormat, DiffKind,
+    RenderOptions,
+};
+
+pub use schema::{
+    example_from_schema, merge_schemas, merge_schemas_with, simplify_schema, ConstraintPolicy,
+    SchemaExamples,
+};
+
+pub use patch::{apply_patch, generate_patch};
+
+pub use path::{
+    explain, format_query_results, query_jsonpath, Expression, FunctionLibrary, JsonPath,
+    QueryFormat, Variables,
+};
+
+pub use presets::{
+    cargo_report, json_feed_to_xml, npm_audit, tfstate_report, validate_json_feed,
+    xml_to_json_feed, FeedFormat,
+};
+
+pub use merge::{merge_json, ArrayMerge, MergeStrategy, NullMerge};
+
+pub use migrate::{migrate_records, Migration, MigrationFailure, MigrationOp, MigrationReport};
+
+pub use ndjson::{
+    aggregate_windows, build_index, dedup_ndjson, index_path, lookup_sorted, route_ndjson,
+    sample_ndjson, sort_ndjson, DedupOptions, DedupReport, NdjsonTail, RouteConfig, RouteReport,
+    RouteRule, SampleOptions, SortOptions, WindowOptions,
+};
+
+pub use stats::{
+    compare_values, data_dictionary, BloomFilter, CountMinSketch, DataDictionary, DriftOptions,
+    HeavyHitters, HyperLogLog, IncrementalStats, StreamSketches, TDigest,
+};
+
+pub use toml::{json_to_toml, parse_toml, TomlOptions};
+
+pub use transform::{
+    fill_defaults, omit, project, prune, sort_array_by, sort_keys, transform_keys, KeyCase,
+    PruneOptions, SortOrder,
+};
+
+pub use validator::{
+    is_json_schema, validate_document, validate_document_at, validate_schema, validate_schema_at,
+    SchemaViolation, ValidationReport,
+};
+
+pub use warnings::{Warning, Warnings};
+
+pub use xml::{json_to_xml, xml_to_json, XmlNodeKind, XmlOptions, TEXT_KEY, XSI_NAMESPACE};
+
+pub use xsd::{validate_against_xsd, XsdViolation};
+
+pub use yaml::{json_to_yaml, parse_yaml_documents, YamlOptions, YamlQuoting};
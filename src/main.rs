@@ -1,20 +1,130 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use anyhow::{Context, Result};
+use json_parser_with_pest::bench::{bench_report, compare_bench, default_cache_dir, fetch_corpora, run_bench};
+use json_parser_with_pest::csv::{csv_to_json, csv_to_json_validated, json_to_csv, CsvOptions};
+use json_parser_with_pest::diff::{render_diff, render_side_by_side, DiffFormat, RenderOptions};
+use json_parser_with_pest::merge::{merge_json, MergeStrategy};
+use json_parser_with_pest::migrate::{migrate_records, Migration};
+use json_parser_with_pest::patch::apply_patch;
+use json_parser_with_pest::path::{
+    explain, format_query_results, query_jsonpath, FunctionLibrary, JsonPath, QueryFormat,
+    Variables,
+};
+use json_parser_with_pest::presets::{
+    cargo_report, json_feed_to_xml, npm_audit, tfstate_report, validate_json_feed,
+    xml_to_json_feed, FeedFormat,
+};
+use json_parser_with_pest::schema::{
+    example_from_schema, merge_schemas_with, simplify_schema, ConstraintPolicy,
+};
+use json_parser_with_pest::ndjson::{
+    aggregate_windows, build_index, dedup_ndjson, lookup_sorted, route_ndjson, sample_ndjson,
+    sort_ndjson, DedupOptions, NdjsonTail, RouteConfig, SampleOptions, SortOptions, WindowOptions,
+};
+use json_parser_with_pest::random::SeededRng;
+use json_parser_with_pest::redact::{redact, RedactionRules, Replacement};
+use json_parser_with_pest::serve::DocumentServer;
+use json_parser_with_pest::regression::{save_repro, DEFAULT_CORPUS_DIR};
+use json_parser_with_pest::sandbox::run_in;
+use json_parser_with_pest::toml::{json_to_toml, parse_toml, TomlOptions};
+use json_parser_with_pest::transform::{
+    fill_defaults, omit, project, prune, sort_array_by, transform_keys, KeyCase, PruneOptions,
+    SortOrder,
+};
+use json_parser_with_pest::validator::validate_document_at;
+use json_parser_with_pest::xml::{json_to_xml, XmlOptions};
+use json_parser_with_pest::xsd::validate_against_xsd;
+use json_parser_with_pest::yaml::{json_to_yaml, parse_yaml_documents, YamlOptions};
+use json_parser_with_pest::stats::{
+    BloomFilter, DataDictionary, DriftOptions, IncrementalStats, StreamSketches,
+};
 use json_parser_with_pest::parser::{
-    display_structure, get_by_path, minify_json, search_by_value,
+    canonicalize, convert_from_bytes, convert_to_bytes, display_structure, for_each_record,
+    format_json, get_by_path, minify_json, parse_format, search, FormatOptions, SearchOptions,
 };
+use json_parser_with_pest::avro::{avro_json_to_json, infer_avro_schema, json_to_avro, json_to_avro_json, AvroOptions};
+use json_parser_with_pest::bson::{bson_to_json, BsonOptions};
+use json_parser_with_pest::properties::{dotenv_to_json, json_to_dotenv, json_to_properties, properties_to_json, PropertiesOptions};
+use json_parser_with_pest::querystring::{json_to_querystring, querystring_to_json, QueryStringOptions};
+use json_parser_with_pest::sql::{json_to_sql, SqlOptions};
+#[cfg(feature = "sqlite")]
+use json_parser_with_pest::sqlite::{json_to_sqlite, sqlite_to_json, SqliteOptions};
+use json_parser_with_pest::table::{json_to_html, json_to_markdown, NestedCells, TableOptions};
+use json_parser_with_pest::array_ops::{chunk, concat, dedupe, difference, intersection, slice, union, zip};
+use json_parser_with_pest::audit::{record_changes, AuditLog};
+use json_parser_with_pest::config::{Config, DEFAULT_CONFIG_FILE};
 use json_parser_with_pest::{
-    convert_to_format, edit_json, handle_large_json, parse_partial_json, validate_json_schema,
+    flatten_with, parse_json, parse_json5, parse_json_with_recovery, parse_jsonc,
+    strip_json_comments, unflatten_with, with_timeout, JqFilter, JsonCst,
+    ParseErrorDetails, ParserError, PathSyntax,
 };
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Set by the global `--json5` flag: input files are read as JSON5.
+static JSON5_INPUT: AtomicBool = AtomicBool::new(false);
+
+/// Set by the global `--timeout` flag: milliseconds parsing an input file may take, or 0.
+static PARSE_TIMEOUT_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Set by the global `--save-repro` flag: the regression corpus input files are copied to.
+static SAVE_REPRO_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Inputs larger than this get a warning when a query has to load them whole.
+const LARGE_INPUT_BYTES: u64 = 256 * 1024 * 1024;
 
 /// Reads and parses a JSON file into a `serde_json::Value` structure.
 fn read_and_parse_json(file_path: &str) -> Result<Value> {
     let json_str = fs::read_to_string(file_path)
         .with_context(|| format!("Failed to read JSON file at path: {}", file_path))?;
-    serde_json::from_str(&json_str).with_context(|| "Failed to parse JSON".to_string())
+    save_repro_input(file_path, &json_str)?;
+    match PARSE_TIMEOUT_MS.load(Ordering::Relaxed) {
+        0 => parse_input(file_path, &json_str),
+        ms => {
+            let path = file_path.to_string();
+            with_timeout(Duration::from_millis(ms), move || parse_input(&path, &json_str))
+                .with_context(|| format!("Failed to parse {}", file_path))?
+        }
+    }
+}
+
+/// Copies an input file into the regression corpus with `--save-repro`, before it is
+/// parsed, so that inputs which crash the parser are kept too.
+fn save_repro_input(file_path: &str, text: &str) -> Result<()> {
+    if let Some(dir) = SAVE_REPRO_DIR.get() {
+        let case = save_repro(dir, text.as_bytes(), file_path)?;
+        eprintln!("Saved {} as regression case {}", file_path, case.display());
+    }
+    Ok(())
+}
+
+/// Parses the text of an input file as JSON, or as JSON5 with `--json5`, printing an
+/// excerpt around a syntax error.
+fn parse_input(file_path: &str, json_str: &str) -> Result<Value> {
+    if JSON5_INPUT.load(Ordering::Relaxed) {
+        return parse_json5(json_str).or_else(|e| {
+            if let ParserError::JsonParseError(details) = &e {
+                eprintln!("{}:\n{}", file_path, details.excerpt());
+            }
+            Err(e).with_context(|| format!("Failed to parse JSON5 in {}", file_path))
+        });
+    }
+    serde_json::from_str(json_str).or_else(|e| {
+        // The grammar reports the expected tokens; serde_json only knows the position.
+        let details = match parse_json(json_str) {
+            Err(ParserError::JsonParseError(details)) => *details,
+            _ => ParseErrorDetails::from_serde(&e, json_str),
+        };
+        eprintln!("{}:\n{}", file_path, details.excerpt());
+        Err(e).with_context(|| format!("Failed to parse JSON in {}{}", file_path, details))
+    })
 }
 
 /// Writes the given content to the output file `output.txt`.
@@ -22,6 +132,66 @@ fn write_to_file(content: &str) -> Result<()> {
     fs::write("output.txt", content).with_context(|| "Failed to write to output.txt")
 }
 
+/// Appends the differences between two versions of a document to the `--audit-log`
+/// file, if one is given, with the user from `USER` or `USERNAME` as the actor.
+fn record_audit(log_path: Option<&String>, operation: &str, before: &Value, after: &Value) -> Result<()> {
+    if let Some(log_path) = log_path {
+        let actor = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok();
+        let mut log = AuditLog::open(Path::new(log_path), actor.as_deref())?;
+        record_changes(before, after, operation, &mut log)?;
+    }
+    Ok(())
+}
+
+/// Loads the config file given with `--config`, or `.json-parser.json` in the
+/// current directory if it exists.
+fn load_config(path: Option<&String>) -> Result<Config> {
+    match path {
+        Some(path) => Config::load(Path::new(path)),
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+            Config::load(Path::new(DEFAULT_CONFIG_FILE))
+        }
+        None => Ok(Config::default()),
+    }
+}
+
+/// Reads a `--delimiter` argument: one character, or `tab` / `\t` for a tab.
+fn parse_delimiter(text: &str) -> Result<char> {
+    if text == "tab" || text == "\\t" {
+        return Ok('\t');
+    }
+    parse_character(text, "delimiter")
+}
+
+/// Reads a one-character argument, or `space` for a space.
+fn parse_character(text: &str, name: &str) -> Result<char> {
+    if text == "space" {
+        return Ok(' ');
+    }
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(character), None) => Ok(character),
+        _ => Err(anyhow::Error::msg(format!("The {} must be one character, got '{}'", name, text))),
+    }
+}
+
+/// Reads the global `--path-syntax` argument, with `--separator` as the delimiter if given.
+fn path_syntax(args: &ArgMatches) -> Result<PathSyntax> {
+    let mut syntax: PathSyntax = args.get_one::<String>("path-syntax").unwrap().parse()?;
+    if let Some(separator) = args.try_get_one::<String>("separator").ok().flatten() {
+        syntax.delimiter = separator.clone();
+    }
+    Ok(syntax)
+}
+
+/// Reads the `convert` arguments `--array-format` and `--typed`.
+fn querystring_options(args: &ArgMatches) -> Result<QueryStringOptions> {
+    Ok(QueryStringOptions {
+        arrays: args.get_one::<String>("array-format").unwrap().parse()?,
+        typed: args.get_flag("typed"),
+    })
+}
+
 /// CLI-supported main function.
 fn main() -> Result<()> {
     // Initialize the logger for displaying information and error messages.
@@ -32,6 +202,48 @@ fn main() -> Result<()> {
         .version("1.0")
         .author("Your Name <your.email@example.com>")
         .about("A tool for parsing and manipulating JSON files")
+        .arg(
+            Arg::new("json5")
+                .long("json5")
+                .global(true)
+                .action(ArgAction::SetTrue)
+                .help("Reads JSON input files as JSON5 (comments, unquoted keys, trailing commas)"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .global(true)
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Fails parsing an input file that takes longer than this many milliseconds"),
+        )
+        .arg(
+            Arg::new("save-repro")
+                .long("save-repro")
+                .global(true)
+                .num_args(0..=1)
+                .default_missing_value(DEFAULT_CORPUS_DIR)
+                .help("Copies each JSON input file into this regression corpus (default: tests/regressions), which cargo test replays"),
+        )
+        .arg(
+            Arg::new("path-syntax")
+                .long("path-syntax")
+                .global(true)
+                .default_value("dot")
+                .value_parser(["dot", "slash", "brackets", "segments", "pointer"])
+                .help("Syntax of the paths written by flatten, search and diff: a.b[0], a/b[0], [\"a\"][\"b\"][0], a.b.0 or /a/b/0"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .global(true)
+                .help("Config file with saved queries (default: .json-parser.json if present)"),
+        )
+        .arg(
+            Arg::new("audit-log")
+                .long("audit-log")
+                .global(true)
+                .help("Appends the paths and value hashes changed by edit, patch, merge and the transforms to this NDJSON file"),
+        )
         .subcommand(
             Command::new("help")
                 .about("Displays help information for available commands"),
@@ -47,12 +259,1286 @@ fn main() -> Result<()> {
                 .about("Minifies a JSON file by removing whitespace")
                 .arg(Arg::new("input").required(true).help("Input JSON file path")),
         )
+        .subcommand(
+            Command::new("canonicalize")
+                .about("Writes a JSON file in RFC 8785 canonical form")
+                .arg(Arg::new("input").required(true).help("Input JSON file path")),
+        )
+        .subcommand(
+            Command::new("flatten")
+                .about("Flattens a JSON file into one object with compound keys such as a.b[0].c")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(
+                    Arg::new("separator")
+                        .long("separator")
+                        .help("Text placed between object keys instead of the --path-syntax one"),
+                ),
+        )
+        .subcommand(
+            Command::new("unflatten")
+                .about("Rebuilds nested JSON from an object with compound keys")
+                .arg(Arg::new("input").required(true).help("Flat JSON file path"))
+                .arg(
+                    Arg::new("separator")
+                        .long("separator")
+                        .help("Text the keys were joined with instead of the --path-syntax one"),
+                ),
+        )
+        .subcommand(
+            Command::new("rename-keys")
+                .about("Converts every object key to a naming convention, e.g. userId to user_id")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(
+                    Arg::new("case")
+                        .long("case")
+                        .required(true)
+                        .value_parser(["camel", "snake", "kebab", "pascal"])
+                        .help("Target naming convention"),
+                ),
+        )
+        .subcommand(
+            Command::new("array")
+                .about("Dedupes, slices, chunks, concatenates, zips, or takes the union, intersection or difference of the array at a path")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("dedupe")
+                        .about("Removes repeated elements, keeping the first")
+                        .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .default_value("")
+                                .help("Path of the array, e.g. data.items; the root by default"),
+                        )
+                        .arg(Arg::new("by").long("by").help("Expression identifying an element, e.g. .id")),
+                )
+                .subcommand(
+                    Command::new("slice")
+                        .about("Keeps a range of elements; negative indexes count from the end")
+                        .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .default_value("")
+                                .help("Path of the array, e.g. data.items; the root by default"),
+                        )
+                        .arg(
+                            Arg::new("start")
+                                .long("start")
+                                .default_value("0")
+                                .allow_hyphen_values(true)
+                                .value_parser(clap::value_parser!(i64))
+                                .help("First index kept"),
+                        )
+                        .arg(
+                            Arg::new("end")
+                                .long("end")
+                                .allow_hyphen_values(true)
+                                .value_parser(clap::value_parser!(i64))
+                                .help("Index after the last one kept"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("chunk")
+                        .about("Splits the array into arrays of --size elements")
+                        .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .default_value("")
+                                .help("Path of the array, e.g. data.items; the root by default"),
+                        )
+                        .arg(
+                            Arg::new("size")
+                                .long("size")
+                                .required(true)
+                                .value_parser(clap::value_parser!(usize))
+                                .help("Elements per chunk"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("concat")
+                        .about("Appends the arrays at the same path in other files")
+                        .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                        .arg(Arg::new("others").required(true).num_args(1..).help("JSON files whose arrays are appended"))
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .default_value("")
+                                .help("Path of the array, e.g. data.items; the root by default"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("union")
+                        .about("Appends the elements of the arrays in other files that the array does not hold, keeping each once")
+                        .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                        .arg(Arg::new("others").required(true).num_args(1..).help("JSON files whose arrays are merged"))
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .default_value("")
+                                .help("Path of the array in every file, e.g. data.items; the root by default"),
+                        )
+                        .arg(Arg::new("by").long("by").help("Expression identifying an element, e.g. .id")),
+                )
+                .subcommand(
+                    Command::new("intersection")
+                        .about("Keeps the elements that the arrays in all other files hold too")
+                        .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                        .arg(Arg::new("others").required(true).num_args(1..).help("JSON files whose arrays an element must be in"))
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .default_value("")
+                                .help("Path of the array in every file, e.g. data.items; the root by default"),
+                        )
+                        .arg(Arg::new("by").long("by").help("Expression identifying an element, e.g. .id")),
+                )
+                .subcommand(
+                    Command::new("difference")
+                        .about("Keeps the elements that the arrays in the other files do not hold")
+                        .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                        .arg(Arg::new("others").required(true).num_args(1..).help("JSON files whose arrays' elements are removed"))
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .default_value("")
+                                .help("Path of the array in every file, e.g. data.items; the root by default"),
+                        )
+                        .arg(Arg::new("by").long("by").help("Expression identifying an element, e.g. .id")),
+                )
+                .subcommand(
+                    Command::new("zip")
+                        .about("Combines the arrays at several paths element by element")
+                        .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .required(true)
+                                .action(ArgAction::Append)
+                                .help("Path of an array; give two or more"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("project")
+                .about("Keeps or removes the values at some paths, e.g. to strip timestamps from fixtures")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(
+                    Arg::new("keep")
+                        .long("keep")
+                        .action(ArgAction::Append)
+                        .help("Path of values to keep, e.g. items[*].name; everything else is removed"),
+                )
+                .arg(
+                    Arg::new("drop")
+                        .long("drop")
+                        .action(ArgAction::Append)
+                        .help("Path of values to remove, e.g. ..updated_at; applied after --keep"),
+                ),
+        )
+        .subcommand(
+            Command::new("prune")
+                .about("Removes nulls, empty strings and empty objects and arrays")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(Arg::new("nulls").long("nulls").action(ArgAction::SetTrue).help("Removes nulls"))
+                .arg(
+                    Arg::new("empty-strings")
+                        .long("empty-strings")
+                        .action(ArgAction::SetTrue)
+                        .help("Removes empty strings"),
+                )
+                .arg(
+                    Arg::new("empty-objects")
+                        .long("empty-objects")
+                        .action(ArgAction::SetTrue)
+                        .help("Removes empty objects"),
+                )
+                .arg(
+                    Arg::new("empty-arrays")
+                        .long("empty-arrays")
+                        .action(ArgAction::SetTrue)
+                        .help("Removes empty arrays; without any of these flags, all four kinds are removed"),
+                ),
+        )
+        .subcommand(
+            Command::new("fill-defaults")
+                .about("Inserts the members of a defaults file that a JSON file is missing")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(Arg::new("defaults").required(true).help("Defaults JSON file path")),
+        )
+        .subcommand(
+            Command::new("redact")
+                .about("Masks passwords, tokens, card numbers and emails before a payload is shared")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(
+                    Arg::new("rules")
+                        .long("rules")
+                        .help("JSON file with the key fragments and value patterns to redact"),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .long("profile")
+                        .conflicts_with("rules")
+                        .help("Redaction profile from the config file, e.g. support"),
+                )
+                .arg(
+                    Arg::new("hash")
+                        .long("hash")
+                        .action(ArgAction::SetTrue)
+                        .help("Replaces values with a stable hash instead of ***"),
+                )
+                .arg(
+                    Arg::new("hash-key-file")
+                        .long("hash-key-file")
+                        .help("File holding the secret key of --hash (default: $JSON_PARSER_HASH_KEY, or a key per run)"),
+                ),
+        )
+        .subcommand(
+            Command::new("strip-comments")
+                .about("Converts JSONC (comments and trailing commas) to strict JSON")
+                .arg(Arg::new("input").required(true).help("Input JSONC file path, e.g. tsconfig.json"))
+                .arg(
+                    Arg::new("in-place")
+                        .long("in-place")
+                        .action(ArgAction::SetTrue)
+                        .help("Rewrites the input file instead of writing output.txt"),
+                ),
+        )
+        .subcommand(
+            Command::new("edit")
+                .about("Sets, inserts or removes a value while keeping comments and formatting")
+                .arg(Arg::new("input").required(true).help("Input JSON or JSONC file path"))
+                .arg(
+                    Arg::new("key")
+                        .required(true)
+                        .help("Top-level key, or a JSON Pointer such as /compilerOptions/strict"),
+                )
+                .arg(
+                    Arg::new("value")
+                        .required_unless_present("remove")
+                        .help("New value as JSON; other text is used as a string"),
+                )
+                .arg(
+                    Arg::new("insert")
+                        .long("insert")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("remove")
+                        .help("Inserts before an array index (or appends with -) instead of replacing"),
+                )
+                .arg(
+                    Arg::new("remove")
+                        .long("remove")
+                        .action(ArgAction::SetTrue)
+                        .help("Removes the key instead of setting it"),
+                )
+                .arg(
+                    Arg::new("in-place")
+                        .long("in-place")
+                        .action(ArgAction::SetTrue)
+                        .help("Rewrites the input file instead of writing output.txt"),
+                ),
+        )
+        .subcommand(
+            Command::new("format")
+                .about("Pretty-prints a JSON file with a configurable style")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(
+                    Arg::new("indent")
+                        .long("indent")
+                        .default_value("2")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of spaces per nesting level"),
+                )
+                .arg(
+                    Arg::new("tabs")
+                        .long("tabs")
+                        .action(ArgAction::SetTrue)
+                        .help("Indents with tabs instead of spaces"),
+                )
+                .arg(
+                    Arg::new("sort-keys")
+                        .long("sort-keys")
+                        .action(ArgAction::SetTrue)
+                        .help("Writes object keys in sorted order"),
+                )
+                .arg(
+                    Arg::new("compact-arrays")
+                        .long("compact-arrays")
+                        .action(ArgAction::SetTrue)
+                        .help("Keeps arrays of scalars on one line"),
+                )
+                .arg(
+                    Arg::new("no-trailing-newline")
+                        .long("no-trailing-newline")
+                        .action(ArgAction::SetTrue)
+                        .help("Omits the newline at the end of the output"),
+                )
+                .arg(
+                    Arg::new("in-place")
+                        .long("in-place")
+                        .action(ArgAction::SetTrue)
+                        .help("Overwrites the input file instead of writing output.txt"),
+                ),
+        )
         .subcommand(
             Command::new("structure")
                 .about("Displays the structure of a JSON file")
                 .arg(Arg::new("input").required(true).help("Input JSON file path")),
         )
+        .subcommand(
+            Command::new("diff")
+                .about("Shows the differences between two JSON files")
+                .arg(Arg::new("old").required(true).help("Original JSON file path"))
+                .arg(Arg::new("new").required(true).help("Updated JSON file path"))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .default_value("pretty")
+                        .value_parser(["pretty", "patch", "json", "paths"])
+                        .help("Output format"),
+                )
+                .arg(
+                    Arg::new("context")
+                        .long("context")
+                        .default_value("3")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of unchanged lines shown around each change"),
+                )
+                .arg(
+                    Arg::new("no-color")
+                        .long("no-color")
+                        .action(ArgAction::SetTrue)
+                        .help("Disables colored output"),
+                )
+                .arg(
+                    Arg::new("side-by-side")
+                        .long("side-by-side")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("format")
+                        .help("Shows both documents in two aligned columns"),
+                )
+                .arg(
+                    Arg::new("width")
+                        .long("width")
+                        .default_value("120")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Terminal width used by the side-by-side view"),
+                ),
+        )
+        .subcommand(
+            Command::new("merge-schemas")
+                .about("Merges several JSON Schemas into one")
+                .arg(
+                    Arg::new("schemas")
+                        .required(true)
+                        .num_args(1..)
+                        .help("Schema JSON file paths"),
+                )
+                .arg(
+                    Arg::new("policy")
+                        .long("policy")
+                        .default_value("loosest")
+                        .value_parser(["loosest", "strictest"])
+                        .help("How conflicting constraints are combined"),
+                ),
+        )
+        .subcommand(
+            Command::new("simplify-schema")
+                .about("Simplifies a JSON Schema without changing what it accepts")
+                .arg(Arg::new("input").required(true).help("Schema JSON file path")),
+        )
+        .subcommand(
+            Command::new("example")
+                .about("Generates a deterministic example document from a JSON Schema")
+                .arg(Arg::new("schema").required(true).help("Schema JSON file path"))
+                .arg(
+                    Arg::new("maximal")
+                        .long("maximal")
+                        .action(ArgAction::SetTrue)
+                        .help("Includes all optional properties"),
+                ),
+        )
+        .subcommand(
+            Command::new("dictionary")
+                .about("Builds a data dictionary report for a JSON array or NDJSON dataset")
+                .arg(Arg::new("input").required(true).help("Input dataset file path")),
+        )
+        .subcommand(
+            Command::new("sketch")
+                .about("Estimates distinct counts and most frequent values per path over NDJSON")
+                .arg(Arg::new("input").required(true).help("Input dataset file path"))
+                .arg(
+                    Arg::new("path")
+                        .long("path")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .help("Path to analyze (repeatable)"),
+                )
+                .arg(
+                    Arg::new("top")
+                        .long("top")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of most frequent values reported per path"),
+                ),
+        )
+        .subcommand(
+            Command::new("route")
+                .about("Routes NDJSON records to several outputs by rule in one pass")
+                .arg(Arg::new("input").required(true).help("Input NDJSON file path"))
+                .arg(
+                    Arg::new("routes")
+                        .value_name("CONFIG")
+                        .required(true)
+                        .help("Routing config file path"),
+                ),
+        )
+        .subcommand(
+            Command::new("dedup")
+                .about("Copies NDJSON records not seen before, remembering them across runs")
+                .arg(Arg::new("input").required(true).help("Input NDJSON file path"))
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .default_value("output.txt")
+                        .help("Output NDJSON file path"),
+                )
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .help("Key path identifying a record, e.g. id; whole records are compared by default"),
+                )
+                .arg(
+                    Arg::new("state")
+                        .long("state")
+                        .help("Bloom filter file remembering the records of earlier runs, e.g. seen.bloom"),
+                )
+                .arg(
+                    Arg::new("capacity")
+                        .long("capacity")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1000000")
+                        .help("Number of distinct records a new state file is sized for"),
+                )
+                .arg(
+                    Arg::new("false-positive-rate")
+                        .long("false-positive-rate")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.001")
+                        .help("Chance that a new record is skipped as a duplicate"),
+                ),
+        )
+        .subcommand(
+            Command::new("sample")
+                .about("Copies a uniform random sample of NDJSON records, reproducible with --seed")
+                .arg(Arg::new("input").required(true).help("Input NDJSON file path"))
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .default_value("output.txt")
+                        .help("Output NDJSON file path"),
+                )
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .required(true)
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of records to keep"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Seed of the sample, so a run can be repeated exactly (default: a random seed, which is printed)"),
+                ),
+        )
+        .subcommand(
+            Command::new("convert-state")
+                .about("Converts a dedup state file between its binary and JSON forms")
+                .arg(Arg::new("input").required(true).help("State file to read, e.g. seen.bloom"))
+                .arg(
+                    Arg::new("output")
+                        .required(true)
+                        .help("State file to write; JSON if it ends in .json, e.g. seen.json"),
+                ),
+        )
+        .subcommand(
+            Command::new("sort")
+                .about("Sorts an NDJSON file by a key path using an external merge sort, or an array in a JSON file")
+                .arg(Arg::new("input").required(true).help("Input NDJSON file path"))
+                .arg(
+                    Arg::new("by")
+                        .long("by")
+                        .required(true)
+                        .help("Key path to sort by; with --array, an expression such as .age"),
+                )
+                .arg(
+                    Arg::new("array")
+                        .long("array")
+                        .num_args(0..=1)
+                        .default_missing_value("")
+                        .help("Sorts the array at this path of a JSON file instead, the root if no path is given"),
+                )
+                .arg(
+                    Arg::new("desc")
+                        .long("desc")
+                        .action(ArgAction::SetTrue)
+                        .help("Sorts in descending order"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .default_value("output.txt")
+                        .help("Output NDJSON file path"),
+                )
+                .arg(
+                    Arg::new("chunk-size")
+                        .long("chunk-size")
+                        .default_value("100000")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Maximum number of records sorted in memory at once"),
+                ),
+        )
+        .subcommand(
+            Command::new("window")
+                .about("Aggregates a timestamped NDJSON stream into tumbling or sliding windows")
+                .arg(Arg::new("input").required(true).help("Input NDJSON file path"))
+                .arg(
+                    Arg::new("timestamp")
+                        .long("timestamp")
+                        .required(true)
+                        .help("Key path of the event time (epoch seconds or RFC 3339)"),
+                )
+                .arg(
+                    Arg::new("size")
+                        .long("size")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Window length in seconds"),
+                )
+                .arg(
+                    Arg::new("slide")
+                        .long("slide")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Seconds between window starts for sliding windows"),
+                )
+                .arg(Arg::new("group-by").long("group-by").help("Key path to group each window by"))
+                .arg(Arg::new("value").long("value").help("Key path of the numeric value to sum"))
+                .arg(
+                    Arg::new("percentile")
+                        .long("percentile")
+                        .action(ArgAction::Append)
+                        .value_parser(clap::value_parser!(f64))
+                        .default_values(["50", "90", "99"])
+                        .help("Percentile of the value to report, e.g. 95 (repeatable)"),
+                )
+                .arg(
+                    Arg::new("compression")
+                        .long("compression")
+                        .value_parser(clap::value_parser!(f64))
+                        .help("Estimates percentiles with a t-digest of this compression (e.g. 100) instead of keeping every value"),
+                )
+                .arg(
+                    Arg::new("precision")
+                        .long("precision")
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Decimal places the reported figures are rounded to"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .default_value("output.txt")
+                        .help("Output NDJSON file path"),
+                )
+                .arg(
+                    Arg::new("state")
+                        .long("state")
+                        .help("JSON checkpoint of the windows still open, continued by the next run"),
+                )
+                .arg(
+                    Arg::new("flush")
+                        .long("flush")
+                        .action(ArgAction::SetTrue)
+                        .help("Writes the windows still open at the end despite --state"),
+                ),
+        )
+        .subcommand(
+            Command::new("index")
+                .about("Builds a sidecar index for an NDJSON file sorted by a key")
+                .arg(Arg::new("input").required(true).help("Sorted NDJSON file path"))
+                .arg(Arg::new("key").long("key").required(true).help("Key path the file is sorted by"))
+                .arg(
+                    Arg::new("every")
+                        .long("every")
+                        .default_value("1000")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of records between index entries"),
+                ),
+        )
+        .subcommand(
+            Command::new("lookup")
+                .about("Finds records by key in a sorted NDJSON file using binary search")
+                .arg(Arg::new("input").required(true).help("Sorted NDJSON file path"))
+                .arg(Arg::new("key").long("key").required(true).help("Key path the file is sorted by"))
+                .arg(Arg::new("value").long("value").required(true).help("Key value to look up")),
+        )
+        .subcommand(
+            Command::new("query")
+                .about("Runs a JSONPath query against a JSON file")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(
+                    Arg::new("path")
+                        .required_unless_present("saved")
+                        .help("JSONPath expression, e.g. $.items[?(@.price > 10)].name"),
+                )
+                .arg(
+                    Arg::new("saved")
+                        .long("saved")
+                        .conflicts_with("path")
+                        .help("Runs a named query from the config file instead"),
+                )
+                .arg(
+                    Arg::new("param")
+                        .long("param")
+                        .action(ArgAction::Append)
+                        .requires("saved")
+                        .help("Saved query parameter as name=value (value as JSON or a string)"),
+                )
+                .arg(
+                    Arg::new("arg-file")
+                        .long("arg-file")
+                        .action(ArgAction::Append)
+                        .help("Binds a JSON file to a $name variable as name=path, e.g. users=users.json"),
+                )
+                .arg(
+                    Arg::new("functions")
+                        .long("functions")
+                        .action(ArgAction::Append)
+                        .conflicts_with("saved")
+                        .help("Expression file defining functions the query can call (saved queries use the config's functions)"),
+                )
+                .arg(
+                    Arg::new("output-format")
+                        .long("output-format")
+                        .value_parser(["json", "raw", "ndjson", "tsv", "paths"])
+                        .help("Prints the results to stdout in this format instead of writing output.txt"),
+                ),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Shows the syntax tree, touched paths and evaluation plan of a query or expression")
+                .arg(
+                    Arg::new("expression")
+                        .required(true)
+                        .help("JSONPath query (starting with $) or expression, e.g. price * qty > 100"),
+                )
+                .arg(
+                    Arg::new("input")
+                        .long("input")
+                        .help("Sample JSON file; reports how many values each step matches"),
+                ),
+        )
+        .subcommand(
+            Command::new("search")
+                .about("Finds values or keys matching a pattern and prints their paths")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(Arg::new("pattern").required(true).help("Text to find; an exact string value by default"))
+                .arg(
+                    Arg::new("regex")
+                        .long("regex")
+                        .action(ArgAction::SetTrue)
+                        .help("Treats the pattern as a regular expression"),
+                )
+                .arg(
+                    Arg::new("substring")
+                        .long("substring")
+                        .action(ArgAction::SetTrue)
+                        .help("Matches text containing the pattern"),
+                )
+                .arg(
+                    Arg::new("ignore-case")
+                        .long("ignore-case")
+                        .action(ArgAction::SetTrue)
+                        .help("Ignores case when matching text"),
+                )
+                .arg(
+                    Arg::new("typed")
+                        .long("typed")
+                        .action(ArgAction::SetTrue)
+                        .help("Reads the pattern as JSON to match numbers, booleans and null, e.g. 42"),
+                )
+                .arg(
+                    Arg::new("keys")
+                        .long("keys")
+                        .action(ArgAction::SetTrue)
+                        .help("Matches object keys instead of values"),
+                )
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Stops after this many matches"),
+                ),
+        )
+        .subcommand(
+            Command::new("eval")
+                .about("Runs a jq-style program against a JSON file and prints each result")
+                .arg(
+                    Arg::new("program")
+                        .required(true)
+                        .help("Program, e.g. '.items[] | select(.price > 10) | {name, price}'"),
+                )
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(
+                    Arg::new("raw-output")
+                        .long("raw-output")
+                        .action(ArgAction::SetTrue)
+                        .help("Prints string results without quotes"),
+                )
+                .arg(
+                    Arg::new("compact")
+                        .long("compact")
+                        .action(ArgAction::SetTrue)
+                        .help("Prints each result on one line"),
+                )
+                .arg(
+                    Arg::new("sandbox")
+                        .long("sandbox")
+                        .action(ArgAction::SetTrue)
+                        .help("Bounds steps, memory and time, for untrusted programs"),
+                )
+                .arg(
+                    Arg::new("max-steps")
+                        .long("max-steps")
+                        .requires("sandbox")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Sandbox step limit (default 1000000)"),
+                )
+                .arg(
+                    Arg::new("max-memory")
+                        .long("max-memory")
+                        .requires("sandbox")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Sandbox limit in bytes of strings and arrays built (default 64 MiB)"),
+                )
+                .arg(
+                    Arg::new("timeout-ms")
+                        .long("timeout-ms")
+                        .requires("sandbox")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Sandbox time limit in milliseconds (default 1000)"),
+                ),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Follows a growing NDJSON file and periodically emits incremental statistics")
+                .arg(Arg::new("input").required(true).help("NDJSON file path"))
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(u64))
+                        .help("Seconds between metrics snapshots"),
+                )
+                .arg(
+                    Arg::new("window")
+                        .long("window")
+                        .default_value("1000")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of values in the drift baseline and recent window"),
+                )
+                .arg(
+                    Arg::new("from-start")
+                        .long("from-start")
+                        .action(ArgAction::SetTrue)
+                        .help("Processes existing records before following appended ones"),
+                ),
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Applies a declarative migration spec to every record of a dataset")
+                .arg(Arg::new("input").required(true).help("Input JSON or NDJSON file path"))
+                .arg(Arg::new("spec").required(true).help("Migration spec file path"))
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .default_value("output.txt")
+                        .help("Output NDJSON file path"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Reports what would fail without writing the migrated records"),
+                )
+                .arg(
+                    Arg::new("reverse")
+                        .long("reverse")
+                        .help("Writes the inverse migration spec to this path and verifies it"),
+                )
+                .arg(
+                    Arg::new("sample")
+                        .long("sample")
+                        .default_value("100")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of records used to verify the inverse migration"),
+                ),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Converts a JSON file to YAML, XML, TOML, CSV, Markdown, HTML, a query string, .env, properties, SQL, CBOR, BSON, Parquet or Avro, or most of these formats to JSON")
+                .arg(Arg::new("input").required(true).help("Input file path"))
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .default_value("json")
+                        .value_parser(["json", "jsonc", "json5", "yaml", "xml", "toml", "csv", "querystring", "dotenv", "properties", "cbor", "bson", "avro", "avro-json"])
+                        .help("Input format"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .default_value("yaml")
+                        .value_parser(["yaml", "xml", "toml", "csv", "markdown", "html", "querystring", "dotenv", "properties", "sql", "cbor", "bson", "parquet", "avro", "avro-json", "json"])
+                        .help("Target format; CBOR, BSON, Parquet and Avro are written to output.cbor, output.bson, output.parquet and output.avro"),
+                )
+                .arg(
+                    Arg::new("avro-schema")
+                        .long("avro-schema")
+                        .help("Avro schema (.avsc) of --to avro and avro-json, inferred from the data if omitted; required by --from avro-json"),
+                )
+                .arg(
+                    Arg::new("array-format")
+                        .long("array-format")
+                        .default_value("brackets")
+                        .value_parser(["brackets", "indices", "repeat"])
+                        .help("How --to querystring writes arrays: tags[]=a, tags[0]=a or tags=a"),
+                )
+                .arg(
+                    Arg::new("typed")
+                        .long("typed")
+                        .action(ArgAction::SetTrue)
+                        .help("Reads --from querystring, dotenv and properties values that look like numbers, booleans or null as such"),
+                )
+                .arg(
+                    Arg::new("canonical")
+                        .long("canonical")
+                        .action(ArgAction::SetTrue)
+                        .help("Reads BSON as canonical Extended JSON, keeping int32, int64 and double types"),
+                )
+                .arg(
+                    Arg::new("inline")
+                        .long("inline")
+                        .action(ArgAction::SetTrue)
+                        .help("Writes nested objects and arrays as JSON in one Markdown or HTML cell instead of flattening them into columns"),
+                )
+                .arg(
+                    Arg::new("table")
+                        .long("table")
+                        .help("Table of --to sql, optionally with a schema as in analytics.users; the input file name by default"),
+                )
+                .arg(
+                    Arg::new("rows-per-insert")
+                        .long("rows-per-insert")
+                        .default_value("1")
+                        .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+                        .help("Rows per INSERT statement of --to sql"),
+                )
+                .arg(
+                    Arg::new("create-table")
+                        .long("create-table")
+                        .action(ArgAction::SetTrue)
+                        .help("Starts --to sql with a CREATE TABLE IF NOT EXISTS statement with column types inferred from the data"),
+                )
+                .arg(
+                    Arg::new("root")
+                        .long("root")
+                        .default_value("root")
+                        .help("XML root element name, optionally prefixed"),
+                )
+                .arg(
+                    Arg::new("namespace")
+                        .long("namespace")
+                        .action(ArgAction::Append)
+                        .help("XML namespace declaration as prefix=uri (repeatable)"),
+                )
+                .arg(
+                    Arg::new("default-namespace")
+                        .long("default-namespace")
+                        .help("XML default namespace URI"),
+                )
+                .arg(
+                    Arg::new("xsi-nil")
+                        .long("xsi-nil")
+                        .action(ArgAction::SetTrue)
+                        .help("Emits nulls as xsi:nil=\"true\" elements"),
+                )
+                .arg(
+                    Arg::new("declaration")
+                        .long("declaration")
+                        .action(ArgAction::SetTrue)
+                        .help("Starts the XML with an <?xml version=\"1.0\" encoding=\"UTF-8\"?> declaration"),
+                )
+                .arg(
+                    Arg::new("item-name")
+                        .long("item-name")
+                        .help("Wraps XML arrays in an element named after their key, with one element of this name per item"),
+                )
+                .arg(
+                    Arg::new("attributes")
+                        .long("attributes")
+                        .action(ArgAction::SetTrue)
+                        .help("Writes scalar fields of objects as XML attributes"),
+                )
+                .arg(
+                    Arg::new("mapping")
+                        .long("mapping")
+                        .help("XML mapping config with per-path attribute/element/cdata kinds and element order"),
+                )
+                .arg(
+                    Arg::new("xsd")
+                        .long("xsd")
+                        .help("XSD file the generated XML is validated against"),
+                )
+                .arg(
+                    Arg::new("anchors")
+                        .long("anchors")
+                        .action(ArgAction::SetTrue)
+                        .help("Factors repeated YAML subtrees into anchors and aliases"),
+                )
+                .arg(
+                    Arg::new("indent")
+                        .long("indent")
+                        .default_value("2")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("YAML spaces per nesting level"),
+                )
+                .arg(
+                    Arg::new("indent-sequences")
+                        .long("indent-sequences")
+                        .action(ArgAction::SetTrue)
+                        .help("Indents YAML sequences nested under a mapping key"),
+                )
+                .arg(
+                    Arg::new("flow-max-items")
+                        .long("flow-max-items")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Writes YAML collections of at most this many scalars in flow style"),
+                )
+                .arg(
+                    Arg::new("quote")
+                        .long("quote")
+                        .default_value("minimal")
+                        .value_parser(["minimal", "single", "double"])
+                        .help("YAML string quoting policy"),
+                )
+                .arg(
+                    Arg::new("width")
+                        .long("width")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Preferred maximum YAML line width (0 disables wrapping)"),
+                )
+                .arg(
+                    Arg::new("document-markers")
+                        .long("document-markers")
+                        .action(ArgAction::SetTrue)
+                        .help("Wraps the YAML document in --- and ... markers"),
+                )
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .default_value(",")
+                        .help("CSV field delimiter: one character, or tab"),
+                ),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Deep-merges overlay JSON files on top of a base file")
+                .arg(Arg::new("base").required(true).help("Base JSON file path"))
+                .arg(
+                    Arg::new("overlays")
+                        .required(true)
+                        .num_args(1..)
+                        .help("Overlay JSON file paths, applied in order"),
+                )
+                .arg(
+                    Arg::new("arrays")
+                        .long("arrays")
+                        .default_value("replace")
+                        .help("Array handling: replace, concat, or union:<key>"),
+                )
+                .arg(
+                    Arg::new("nulls")
+                        .long("nulls")
+                        .default_value("overwrite")
+                        .value_parser(["overwrite", "delete", "ignore"])
+                        .help("Null handling in overlays"),
+                ),
+        )
+        .subcommand(
+            Command::new("patch")
+                .about("Applies a JSON Patch (RFC 6902) to a JSON file")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(Arg::new("patch").required(true).help("JSON Patch file path")),
+        )
+        .subcommand(
+            Command::new("from-yaml")
+                .about("Converts a YAML file, possibly with several documents, to JSON")
+                .arg(Arg::new("input").required(true).help("Input YAML file path"))
+                .arg(
+                    Arg::new("ndjson")
+                        .long("ndjson")
+                        .action(ArgAction::SetTrue)
+                        .help("Writes one document per line instead of a JSON array"),
+                ),
+        )
+        .subcommand(
+            Command::new("from-toml")
+                .about("Converts a TOML file to JSON")
+                .arg(Arg::new("input").required(true).help("Input TOML file path"))
+                .arg(
+                    Arg::new("annotate-types")
+                        .long("annotate-types")
+                        .action(ArgAction::SetTrue)
+                        .help("Wraps datetimes as {\"type\": ..., \"value\": ...} objects"),
+                ),
+        )
+        .subcommand(
+            Command::new("from-csv")
+                .about("Converts a CSV file with a header row to a JSON array of objects")
+                .arg(Arg::new("input").required(true).help("Input CSV file path"))
+                .arg(
+                    Arg::new("delimiter")
+                        .long("delimiter")
+                        .default_value(",")
+                        .help("CSV field delimiter: one character, or tab"),
+                )
+                .arg(
+                    Arg::new("separator")
+                        .long("separator")
+                        .default_value(".")
+                        .help("Separator of nested column names, e.g. address.city"),
+                )
+                .arg(
+                    Arg::new("strings")
+                        .long("strings")
+                        .action(ArgAction::SetTrue)
+                        .help("Keeps every field a string instead of reading numbers, booleans and nulls"),
+                )
+                .arg(
+                    Arg::new("decimal")
+                        .long("decimal")
+                        .default_value(".")
+                        .help("Decimal separator of numbers, e.g. , for 3,14"),
+                )
+                .arg(
+                    Arg::new("thousands")
+                        .long("thousands")
+                        .help("Thousands separator of numbers, e.g. . for 1.234,5, or space"),
+                )
+                .arg(
+                    Arg::new("date-format")
+                        .long("date-format")
+                        .action(ArgAction::Append)
+                        .help("Date format of a column as column=format, e.g. born=%d.%m.%Y (repeatable)"),
+                )
+                .arg(
+                    Arg::new("schema")
+                        .long("schema")
+                        .help("JSON Schema of a row; fields are read as its types and invalid rows are quarantined"),
+                )
+                .arg(
+                    Arg::new("quarantine")
+                        .long("quarantine")
+                        .requires("schema")
+                        .help("NDJSON file for rows failing the schema, with their row number and error"),
+                ),
+        )
+        .subcommand(
+            Command::new("export-sqlite")
+                .about("Writes a JSON array of objects into a new SQLite database, inferring column types")
+                .arg(Arg::new("input").required(true).help("Input JSON file path"))
+                .arg(
+                    Arg::new("db")
+                        .long("db")
+                        .required(true)
+                        .help("SQLite database to create; an existing file is not overwritten"),
+                )
+                .arg(
+                    Arg::new("table")
+                        .long("table")
+                        .help("Table to create; the input file name by default"),
+                ),
+        )
+        .subcommand(
+            Command::new("import-sqlite")
+                .about("Reads a table of an SQLite database as a JSON array of objects")
+                .arg(Arg::new("db").required(true).help("SQLite database path"))
+                .arg(
+                    Arg::new("table")
+                        .long("table")
+                        .help("Table to read; may be omitted if the database has only one"),
+                ),
+        )
+        .subcommand(
+            Command::new("cargo")
+                .about("Extracts dependencies, versions and features of a Rust project as JSON")
+                .arg(
+                    Arg::new("manifest")
+                        .long("manifest")
+                        .default_value("Cargo.toml")
+                        .help("Path to Cargo.toml"),
+                )
+                .arg(
+                    Arg::new("lock")
+                        .long("lock")
+                        .help("Path to Cargo.lock (defaults to the one next to the manifest, if any)"),
+                )
+                .arg(
+                    Arg::new("query")
+                        .long("query")
+                        .help("JSONPath query applied to the report, e.g. $.dependencies[?(@.optional == true)].name"),
+                ),
+        )
+        .subcommand(
+            Command::new("npm-audit")
+                .about("Reports duplicate dependency versions and scripts of an npm project")
+                .arg(
+                    Arg::new("package")
+                        .long("package")
+                        .default_value("package.json")
+                        .help("Path to package.json"),
+                )
+                .arg(
+                    Arg::new("lock")
+                        .long("lock")
+                        .help("Path to package-lock.json (defaults to the one next to package.json, if any)"),
+                )
+                .arg(
+                    Arg::new("top")
+                        .long("top")
+                        .default_value("10")
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of most frequently installed packages to report"),
+                ),
+        )
+        .subcommand(
+            Command::new("tfstate")
+                .about("Extracts resources, outputs and providers from a Terraform state file")
+                .arg(
+                    Arg::new("input")
+                        .default_value("terraform.tfstate")
+                        .help("Path to the terraform.tfstate file"),
+                )
+                .arg(
+                    Arg::new("show-sensitive")
+                        .long("show-sensitive")
+                        .action(ArgAction::SetTrue)
+                        .help("Keeps sensitive outputs and attributes instead of masking them"),
+                )
+                .arg(
+                    Arg::new("query")
+                        .long("query")
+                        .help("JSONPath query applied to the report, e.g. $.resources[?(@.type == 'aws_instance')].address"),
+                ),
+        )
+        .subcommand(
+            Command::new("feed")
+                .about("Validates a JSON Feed 1.1 and converts it to or from RSS/Atom")
+                .arg(Arg::new("input").required(true).help("Path to a JSON Feed, RSS or Atom file"))
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .value_parser(["rss", "atom"])
+                        .help("Converts a valid JSON Feed to RSS 2.0 or Atom 1.0"),
+                ),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Measures parser throughput on standard corpora")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("fetch")
+                        .about("Downloads the canada, citm_catalog and twitter corpora into the cache")
+                        .arg(
+                            Arg::new("cache")
+                                .long("cache")
+                                .help("Corpus cache directory (default: $JSON_PARSER_CACHE or ~/.cache/json-parser/corpora)"),
+                        )
+                )
+                .subcommand(
+                    Command::new("run")
+                        .about("Parses each cached corpus with each parser and writes a JSON throughput report")
+                        .arg(
+                            Arg::new("cache")
+                                .long("cache")
+                                .help("Corpus cache directory (default: $JSON_PARSER_CACHE or ~/.cache/json-parser/corpora)"),
+                        )
+                        .arg(
+                            Arg::new("iterations")
+                                .long("iterations")
+                                .default_value("10")
+                                .value_parser(clap::value_parser!(u32))
+                                .help("Measured parses per corpus"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .long("output")
+                                .help("Saves the report to this file, e.g. baseline.json, instead of output.txt"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("compare")
+                        .about("Runs the benchmark and compares its throughput with a saved report")
+                        .arg(Arg::new("baseline").required(true).help("Report saved by bench run"))
+                        .arg(
+                            Arg::new("cache")
+                                .long("cache")
+                                .help("Corpus cache directory (default: $JSON_PARSER_CACHE or ~/.cache/json-parser/corpora)"),
+                        )
+                        .arg(
+                            Arg::new("iterations")
+                                .long("iterations")
+                                .default_value("10")
+                                .value_parser(clap::value_parser!(u32))
+                                .help("Measured parses per corpus"),
+                        )
+                        .arg(
+                            Arg::new("tolerance")
+                                .long("tolerance")
+                                .default_value("5")
+                                .value_parser(clap::value_parser!(f64))
+                                .help("Change in throughput, in percent, treated as noise"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Reports every syntax error in a JSON file in one pass")
+                .arg(Arg::new("input").required(true).help("Path to the JSON file")),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serves the JSON documents of a directory over HTTP, redacted with the profile of each access token")
+                .arg(Arg::new("dir").required(true).help("Directory of .json documents"))
+                .arg(
+                    Arg::new("bind")
+                        .long("bind")
+                        .default_value("127.0.0.1:8080")
+                        .help("Address to listen on"),
+                ),
+        )
         .get_matches();
+    JSON5_INPUT.store(matches.get_flag("json5"), Ordering::Relaxed);
+    if let Some(&timeout) = matches.get_one::<u64>("timeout") {
+        PARSE_TIMEOUT_MS.store(timeout, Ordering::Relaxed);
+    }
+    if let Some(dir) = matches.get_one::<String>("save-repro") {
+        SAVE_REPRO_DIR.get_or_init(|| PathBuf::from(dir));
+    }
 
     match matches.subcommand() {
         Some(("help", _)) => {
@@ -60,7 +1546,52 @@ fn main() -> Result<()> {
                 "Available commands:\n\
                  validate: Validates a JSON file against a schema\n\
                  minify: Minifies a JSON file by removing whitespace\n\
-                 structure: Displays the structure of a JSON file"
+                 format: Pretty-prints a JSON file with configurable indentation and key order\n\
+                 canonicalize: Writes a JSON file in RFC 8785 canonical form for signing\n\
+                 strip-comments: Removes comments and trailing commas from a JSONC file\n\
+                 flatten / unflatten: Converts between nested JSON and one object with keys such as a.b[0].c\n\
+                 rename-keys: Converts every key to camelCase, snake_case, kebab-case or PascalCase\n\
+                 array: Dedupes, slices, chunks, concatenates, zips or compares arrays (array dedupe|slice|chunk|concat|zip|union|intersection|difference)\n\
+                 project: Keeps (--keep) or removes (--drop) the values at some paths\n\
+                 prune: Removes nulls and empty strings, objects and arrays\n\
+                 fill-defaults: Inserts missing members from a defaults file\n\
+                 redact: Masks secrets, card numbers and emails\n\
+                 edit: Sets, inserts or removes a value, keeping comments and formatting\n\
+                 structure: Displays the structure of a JSON file\n\
+                 diff: Shows the differences between two JSON files\n\
+                 merge-schemas: Merges several JSON Schemas into one\n\
+                 simplify-schema: Simplifies a JSON Schema\n\
+                 example: Generates an example document from a JSON Schema\n\
+                 dictionary: Builds a data dictionary report for a dataset\n\
+                 sketch: Estimates distinct counts and top values per path\n\
+                 sort: Sorts an NDJSON file by a key path\n\
+                 route: Routes NDJSON records to several output files by rule\n\
+                 dedup: Skips NDJSON records seen in this or earlier runs\n\
+                 sample: Copies a random sample of NDJSON records, repeatable with --seed\n\
+                 convert-state: Exports or imports a dedup state file as JSON\n\
+                 window: Aggregates timestamped NDJSON into per-window counts, sums, stddev and percentiles\n\
+                 index: Builds a sidecar index for a sorted NDJSON file\n\
+                 lookup: Finds records by key in a sorted NDJSON file\n\
+                 query: Runs a JSONPath query against a JSON file\n\
+                 explain: Shows how a query or expression is parsed and evaluated, and where it stops matching\n\
+                 search: Finds values or keys by exact text, substring, regex or typed value\n\
+                 eval: Runs a jq-style program such as '.items[] | select(.price > 10) | {{name, price}}'\n\
+                 watch: Follows a growing NDJSON file and emits incremental statistics\n\
+                 migrate: Applies a declarative migration spec to a dataset\n\
+                 patch: Applies a JSON Patch (RFC 6902) to a JSON file\n\
+                 convert: Converts JSON to YAML, XML, TOML, CSV, Markdown, HTML, query strings, .env, properties, SQL, CBOR, BSON, Parquet or Avro (--to), or JSONC, JSON5, YAML, XML, TOML, CSV, query strings, .env, properties, CBOR, BSON or Avro to JSON (--from)\n\
+                 merge: Deep-merges overlay JSON files on top of a base file\n\
+                 from-yaml: Converts a multi-document YAML file to a JSON array or NDJSON\n\
+                 from-toml: Converts a TOML file to JSON, keeping datetimes as RFC 3339 strings\n\
+                 from-csv: Converts a CSV file to a JSON array of objects, nesting columns such as address.city\n\
+                 export-sqlite / import-sqlite: Writes a JSON array into an SQLite table or reads a table as a JSON array\n\
+                 cargo: Extracts dependency trees, versions and features from Cargo.toml/Cargo.lock\n\
+                 npm-audit: Reports duplicate dependency versions and scripts from package.json and its lockfile\n\
+                 tfstate: Extracts resources, outputs and providers from a Terraform state, masking sensitive values\n\
+                 feed: Validates a JSON Feed and converts it to or from RSS/Atom XML\n\
+                 bench: Downloads standard corpora (bench fetch), measures throughput (bench run) and compares it with a baseline (bench compare)\n\
+                 lint: Reports every syntax error in a JSON file instead of stopping at the first\n\
+                 serve: Serves a directory of JSON documents over HTTP, masked per access token by the config's redaction profiles"
             );
         }
         Some(("validate", args)) => {
@@ -68,11 +1599,16 @@ fn main() -> Result<()> {
             let schema_path = args.get_one::<String>("schema").unwrap();
             let json = read_and_parse_json(input_path)?;
             let schema = read_and_parse_json(schema_path)?;
-            let validate_result = match validate_json_schema(&json, &schema) {
-                Ok(_) => "JSON is valid against the schema.".to_string(),
-                Err(e) => format!("Validation error: {}", e),
-            };
-            write_to_file(&validate_result)?;
+            let base_dir = Path::new(schema_path).parent().unwrap_or(Path::new("."));
+            let report = validate_document_at(&json, &schema, base_dir);
+            if report.is_valid() {
+                write_to_file("JSON is valid against the schema.")?;
+            } else {
+                println!("{}", report);
+                write_to_file(&report.to_string())?;
+                eprintln!("Validation failed with {} violation(s).", report.violations.len());
+                std::process::exit(1);
+            }
         }
         Some(("minify", args)) => {
             let input_path = args.get_one::<String>("input").unwrap();
@@ -80,11 +1616,993 @@ fn main() -> Result<()> {
             let minified = minify_json(&json);
             write_to_file(&minified)?;
         }
+        Some(("canonicalize", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let json = read_and_parse_json(input_path)?;
+            write_to_file(&canonicalize(&json)?)?;
+        }
+        Some(("flatten", args)) => {
+            let json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let flat = flatten_with(&json, &path_syntax(args)?);
+            write_to_file(&serde_json::to_string_pretty(&flat)?)?;
+        }
+        Some(("unflatten", args)) => {
+            let json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let nested = unflatten_with(&json, &path_syntax(args)?)?;
+            write_to_file(&serde_json::to_string_pretty(&nested)?)?;
+        }
+        Some(("rename-keys", args)) => {
+            let mut json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let before = json.clone();
+            let case: KeyCase = args.get_one::<String>("case").unwrap().parse()?;
+            transform_keys(&mut json, case)?;
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+            record_audit(args.get_one::<String>("audit-log"), "rename-keys", &before, &json)?;
+        }
+        Some(("array", group)) => {
+            let (operation, args) = group.subcommand().unwrap();
+            let mut json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let before = json.clone();
+            match operation {
+                "dedupe" => {
+                    let path = args.get_one::<String>("path").unwrap();
+                    let by = args.get_one::<String>("by").map(String::as_str);
+                    let removed = dedupe(&mut json, path, by)?;
+                    println!("Removed {} duplicates", removed);
+                }
+                "slice" => {
+                    let start = *args.get_one::<i64>("start").unwrap();
+                    let end = args.get_one::<i64>("end").copied();
+                    slice(&mut json, args.get_one::<String>("path").unwrap(), start, end)?;
+                }
+                "chunk" => {
+                    let size = *args.get_one::<usize>("size").unwrap();
+                    chunk(&mut json, args.get_one::<String>("path").unwrap(), size)?;
+                }
+                "concat" | "union" | "intersection" | "difference" => {
+                    let path = args.get_one::<String>("path").unwrap();
+                    let others = args
+                        .get_many::<String>("others")
+                        .unwrap()
+                        .map(|other_path| {
+                            let other = read_and_parse_json(other_path)?;
+                            let array = if path.is_empty() { Some(other) } else { get_by_path(&other, path) };
+                            array.with_context(|| format!("No array at path {} in {}", path, other_path))
+                        })
+                        .collect::<Result<Vec<Value>>>()?;
+                    let by = args.try_get_one::<String>("by").ok().flatten().map(String::as_str);
+                    match operation {
+                        "concat" => concat(&mut json, path, &others)?,
+                        "union" => union(&mut json, path, &others, by)?,
+                        "intersection" => intersection(&mut json, path, &others, by)?,
+                        _ => difference(&mut json, path, &others, by)?,
+                    }
+                    if operation != "concat" {
+                        let count = get_by_path(&json, path).as_ref().unwrap_or(&json).as_array().map_or(0, Vec::len);
+                        println!("{} elements", count);
+                    }
+                }
+                _ => {
+                    let paths: Vec<&str> = args.get_many::<String>("path").unwrap().map(String::as_str).collect();
+                    json = zip(&json, &paths)?;
+                }
+            }
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+            if operation != "zip" {
+                record_audit(args.get_one::<String>("audit-log"), &format!("array {}", operation), &before, &json)?;
+            }
+        }
+        Some(("project", args)) => {
+            let mut json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let before = json.clone();
+            let keep: Vec<&str> = args
+                .get_many::<String>("keep")
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect();
+            if !keep.is_empty() {
+                json = project(&json, &keep);
+            }
+            let drop: Vec<&str> = args
+                .get_many::<String>("drop")
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect();
+            json = omit(&json, &drop);
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+            record_audit(args.get_one::<String>("audit-log"), "project", &before, &json)?;
+        }
+        Some(("prune", args)) => {
+            let mut json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let before = json.clone();
+            let kinds = ["nulls", "empty-strings", "empty-objects", "empty-arrays"];
+            let options = if kinds.iter().any(|kind| args.get_flag(kind)) {
+                PruneOptions {
+                    nulls: args.get_flag("nulls"),
+                    empty_strings: args.get_flag("empty-strings"),
+                    empty_objects: args.get_flag("empty-objects"),
+                    empty_arrays: args.get_flag("empty-arrays"),
+                }
+            } else {
+                PruneOptions::default()
+            };
+            let count = prune(&mut json, options);
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+            record_audit(args.get_one::<String>("audit-log"), "prune", &before, &json)?;
+            println!("Removed {} values", count);
+        }
+        Some(("fill-defaults", args)) => {
+            let mut json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let defaults = read_and_parse_json(args.get_one::<String>("defaults").unwrap())?;
+            let before = json.clone();
+            let count = fill_defaults(&mut json, &defaults);
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+            record_audit(args.get_one::<String>("audit-log"), "fill-defaults", &before, &json)?;
+            println!("Inserted {} defaults", count);
+        }
+        Some(("redact", args)) => {
+            let mut json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let mut rules = match (args.get_one::<String>("rules"), args.get_one::<String>("profile")) {
+                (Some(path), _) => RedactionRules::from_json(&read_and_parse_json(path)?)?,
+                (None, Some(profile)) => load_config(args.get_one::<String>("config"))?.redaction_profile(profile)?,
+                (None, None) => RedactionRules::default(),
+            };
+            if args.get_flag("hash") {
+                rules.replacement = Replacement::Hash;
+            }
+            if let Some(path) = args.get_one::<String>("hash-key-file") {
+                let key = fs::read(path)
+                    .with_context(|| format!("Failed to read hash key at path: {}", path))?;
+                rules.hash_key = Some(key);
+            }
+            let before = json.clone();
+            let count = redact(&mut json, &rules);
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+            record_audit(args.get_one::<String>("audit-log"), "redact", &before, &json)?;
+            println!("Redacted {} values", count);
+        }
+        Some(("strip-comments", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let text = fs::read_to_string(input_path)
+                .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+            // Check the whole document first so that invalid JSONC is not written out.
+            if let Err(ParserError::JsonParseError(details)) = parse_jsonc(&text) {
+                eprintln!("{}:\n{}", input_path, details.excerpt());
+                return Err(ParserError::JsonParseError(details))
+                    .with_context(|| format!("Failed to parse JSONC in {}", input_path));
+            }
+            let stripped = strip_json_comments(&text);
+            if args.get_flag("in-place") {
+                fs::write(input_path, stripped)
+                    .with_context(|| format!("Failed to write file at path: {}", input_path))?;
+            } else {
+                write_to_file(&stripped)?;
+            }
+        }
+        Some(("edit", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let key = args.get_one::<String>("key").unwrap();
+            let text = fs::read_to_string(input_path)
+                .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+            let mut cst = JsonCst::parse(&text)
+                .with_context(|| format!("Failed to parse JSON in {}", input_path))?;
+            let before = cst.value();
+            let pointer = if key.starts_with('/') {
+                key.clone()
+            } else {
+                format!("/{}", key.replace('~', "~0").replace('/', "~1"))
+            };
+            if args.get_flag("remove") {
+                cst.remove(&pointer)?;
+            } else {
+                let raw = args.get_one::<String>("value").unwrap();
+                let value = serde_json::from_str(raw).unwrap_or_else(|_| Value::from(raw.as_str()));
+                if args.get_flag("insert") {
+                    cst.insert(&pointer, &value)?;
+                } else {
+                    cst.set(&pointer, &value)?;
+                }
+            }
+            if args.get_flag("in-place") {
+                fs::write(input_path, cst.to_string())
+                    .with_context(|| format!("Failed to write file at path: {}", input_path))?;
+            } else {
+                write_to_file(&cst.to_string())?;
+            }
+            record_audit(args.get_one::<String>("audit-log"), "edit", &before, &cst.value())?;
+        }
+        Some(("format", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let json = read_and_parse_json(input_path)?;
+            let options = FormatOptions {
+                indent: *args.get_one::<usize>("indent").unwrap(),
+                use_tabs: args.get_flag("tabs"),
+                sort_keys: args.get_flag("sort-keys"),
+                trailing_newline: !args.get_flag("no-trailing-newline"),
+                compact_arrays: args.get_flag("compact-arrays"),
+            };
+            let formatted = format_json(&json, &options);
+            if args.get_flag("in-place") {
+                fs::write(input_path, formatted)
+                    .with_context(|| format!("Failed to write file at path: {}", input_path))?;
+            } else {
+                write_to_file(&formatted)?;
+            }
+        }
         Some(("structure", args)) => {
             let input_path = args.get_one::<String>("input").unwrap();
             let json = read_and_parse_json(input_path)?;
             display_structure(&json);
         }
+        Some(("diff", args)) => {
+            let old = read_and_parse_json(args.get_one::<String>("old").unwrap())?;
+            let new = read_and_parse_json(args.get_one::<String>("new").unwrap())?;
+            let options = RenderOptions {
+                context: *args.get_one::<usize>("context").unwrap(),
+                color: !args.get_flag("no-color"),
+                width: *args.get_one::<usize>("width").unwrap(),
+                syntax: path_syntax(args)?,
+                ..RenderOptions::default()
+            };
+            let rendered = if args.get_flag("side-by-side") {
+                render_side_by_side(&old, &new, &options)
+            } else {
+                let format: DiffFormat = args.get_one::<String>("format").unwrap().parse()?;
+                render_diff(&old, &new, format, &options)?
+            };
+            println!("{}", rendered.trim_end());
+        }
+        Some(("merge-schemas", args)) => {
+            let schemas = args
+                .get_many::<String>("schemas")
+                .unwrap()
+                .map(|path| read_and_parse_json(path))
+                .collect::<Result<Vec<Value>>>()?;
+            let policy: ConstraintPolicy = args.get_one::<String>("policy").unwrap().parse()?;
+            let merged = merge_schemas_with(&schemas, policy);
+            write_to_file(&serde_json::to_string_pretty(&merged)?)?;
+        }
+        Some(("simplify-schema", args)) => {
+            let schema = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let simplified = simplify_schema(&schema);
+            write_to_file(&serde_json::to_string_pretty(&simplified)?)?;
+        }
+        Some(("example", args)) => {
+            let schema = read_and_parse_json(args.get_one::<String>("schema").unwrap())?;
+            let examples = example_from_schema(&schema);
+            let example = if args.get_flag("maximal") {
+                examples.maximal
+            } else {
+                examples.minimal
+            };
+            write_to_file(&serde_json::to_string_pretty(&example)?)?;
+        }
+        Some(("dictionary", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let mut dictionary = DataDictionary::new();
+            for_each_record(Path::new(input_path), |record| dictionary.observe(&record))?;
+            write_to_file(&serde_json::to_string_pretty(&dictionary.to_json())?)?;
+        }
+        Some(("sketch", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let paths: Vec<String> = args.get_many::<String>("path").unwrap().cloned().collect();
+            let mut sketches = StreamSketches::new(&paths, *args.get_one::<usize>("top").unwrap());
+            for_each_record(Path::new(input_path), |record| sketches.observe(&record))?;
+            write_to_file(&serde_json::to_string_pretty(&sketches.to_json())?)?;
+        }
+        Some(("route", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let mut config = RouteConfig::from_json(&read_and_parse_json(args.get_one::<String>("routes").unwrap())?)?;
+            config.sandbox = load_config(args.get_one::<String>("config"))?.sandbox;
+            let report = route_ndjson(Path::new(input_path), &config)?;
+            println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+        }
+        Some(("dedup", args)) => {
+            let options = DedupOptions {
+                key: args.get_one::<String>("key").cloned(),
+                state: args.get_one::<String>("state").map(PathBuf::from),
+                capacity: *args.get_one::<usize>("capacity").unwrap(),
+                false_positive_rate: *args.get_one::<f64>("false-positive-rate").unwrap(),
+            };
+            let report = dedup_ndjson(
+                Path::new(args.get_one::<String>("input").unwrap()),
+                Path::new(args.get_one::<String>("output").unwrap()),
+                &options,
+            )?;
+            println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+        }
+        Some(("sample", args)) => {
+            let seed = match args.get_one::<u64>("seed") {
+                Some(seed) => *seed,
+                None => {
+                    let seed = SeededRng::entropy_seed();
+                    eprintln!("Sampling with --seed {}", seed);
+                    seed
+                }
+            };
+            let options = SampleOptions {
+                size: *args.get_one::<usize>("size").unwrap(),
+                seed,
+            };
+            let output_path = args.get_one::<String>("output").unwrap();
+            let total = sample_ndjson(
+                Path::new(args.get_one::<String>("input").unwrap()),
+                Path::new(output_path),
+                &options,
+            )?;
+            println!("Sampled {} of {} records into {}", total.min(options.size), total, output_path);
+        }
+        Some(("convert-state", args)) => {
+            let state = BloomFilter::load(Path::new(args.get_one::<String>("input").unwrap()))?;
+            state.save(Path::new(args.get_one::<String>("output").unwrap()))?;
+        }
+        Some(("sort", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let output_path = args.get_one::<String>("output").unwrap();
+            if let Some(array_path) = args.get_one::<String>("array") {
+                let mut json = read_and_parse_json(input_path)?;
+                let before = json.clone();
+                let order = if args.get_flag("desc") { SortOrder::Descending } else { SortOrder::Ascending };
+                sort_array_by(&mut json, array_path, args.get_one::<String>("by").unwrap(), order)?;
+                fs::write(output_path, serde_json::to_string_pretty(&json)?)
+                    .with_context(|| format!("Failed to write to {}", output_path))?;
+                record_audit(args.get_one::<String>("audit-log"), "sort", &before, &json)?;
+                println!("Sorted the array into {}", output_path);
+            } else {
+                let mut options = SortOptions::new(args.get_one::<String>("by").unwrap());
+                options.descending = args.get_flag("desc");
+                options.chunk_records = *args.get_one::<usize>("chunk-size").unwrap();
+                let count = sort_ndjson(Path::new(input_path), Path::new(output_path), &options)?;
+                println!("Sorted {} records into {}", count, output_path);
+            }
+        }
+        Some(("window", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let output_path = args.get_one::<String>("output").unwrap();
+            let mut options = WindowOptions::new(
+                args.get_one::<String>("timestamp").unwrap(),
+                *args.get_one::<u64>("size").unwrap(),
+            );
+            options.slide = args.get_one::<u64>("slide").copied();
+            options.group_by = args.get_one::<String>("group-by").cloned();
+            options.value = args.get_one::<String>("value").cloned();
+            options.percentiles = args.get_many::<f64>("percentile").into_iter().flatten().copied().collect();
+            options.compression = args.get_one::<f64>("compression").copied();
+            options.precision = args.get_one::<u32>("precision").copied();
+            options.state = args.get_one::<String>("state").map(PathBuf::from);
+            options.flush = args.get_flag("flush");
+            let count = aggregate_windows(Path::new(input_path), Path::new(output_path), &options)?;
+            println!("Wrote {} windows to {}", count, output_path);
+        }
+        Some(("index", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let key = args.get_one::<String>("key").unwrap();
+            let entries = build_index(Path::new(input_path), key, *args.get_one::<usize>("every").unwrap())?;
+            println!("Indexed {} with {} entries", input_path, entries);
+        }
+        Some(("lookup", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let key = args.get_one::<String>("key").unwrap();
+            let raw_value = args.get_one::<String>("value").unwrap();
+            // Values that are not valid JSON (e.g. bare words) are looked up as strings.
+            let value = serde_json::from_str(raw_value).unwrap_or_else(|_| Value::String(raw_value.clone()));
+            for record in lookup_sorted(Path::new(input_path), key, &value)? {
+                println!("{}", record);
+            }
+        }
+        Some(("query", args)) => {
+            let input = args.get_one::<String>("input").unwrap();
+            let mut variables = Variables::new();
+            for binding in args.get_many::<String>("arg-file").into_iter().flatten() {
+                let (name, path) = binding.split_once('=').with_context(|| {
+                    format!("Variable file '{}' is not in name=path form", binding)
+                })?;
+                variables.insert(name.to_string(), read_and_parse_json(path)?);
+            }
+            let mut sandbox = None;
+            let (query, transform) = match args.get_one::<String>("saved") {
+                Some(name) => {
+                    let mut params = BTreeMap::new();
+                    for param in args.get_many::<String>("param").into_iter().flatten() {
+                        let (key, raw) = param.split_once('=').with_context(|| {
+                            format!("Parameter '{}' is not in name=value form", param)
+                        })?;
+                        let value =
+                            serde_json::from_str(raw).unwrap_or_else(|_| Value::from(raw));
+                        params.insert(key.to_string(), value);
+                    }
+                    let config = load_config(args.get_one::<String>("config"))?;
+                    sandbox = config.sandbox;
+                    config.saved_query(name)?.compile(&params)?
+                }
+                None => {
+                    let mut library = FunctionLibrary::default();
+                    for file in args.get_many::<String>("functions").into_iter().flatten() {
+                        let text = fs::read_to_string(file)
+                            .with_context(|| format!("Failed to read functions {}", file))?;
+                        library.define(&text)?;
+                    }
+                    let path = args.get_one::<String>("path").unwrap();
+                    (JsonPath::compile_with_functions(path, &library)?, None)
+                }
+            };
+            // Stream the input when the query allows it, so large files are not loaded whole.
+            let mut reasons = query.streaming_blockers();
+            if JSON5_INPUT.load(Ordering::Relaxed) {
+                reasons.push("JSON5 input is parsed in memory".to_string());
+            }
+            let mut results: Vec<(String, Value)> = Vec::new();
+            if reasons.is_empty() {
+                let file = fs::File::open(input)
+                    .with_context(|| format!("Failed to read JSON file at path: {}", input))?;
+                run_in(sandbox.as_ref(), || {
+                    query.select_streaming(BufReader::new(file), &variables, |path, value| {
+                        results.push((path, value))
+                    })
+                })?
+                .with_context(|| format!("Failed to query {}", input))?;
+            } else {
+                if fs::metadata(input).is_ok_and(|metadata| metadata.len() > LARGE_INPUT_BYTES) {
+                    eprintln!(
+                        "Warning: loading all of {} into memory because the query cannot stream: {}",
+                        input,
+                        reasons.join("; ")
+                    );
+                }
+                let json = read_and_parse_json(input)?;
+                results = run_in(sandbox.as_ref(), || query.select_with_variables(&json, &variables))??
+                    .into_iter()
+                    .map(|(path, value)| (path, value.clone()))
+                    .collect();
+            }
+            if let Some(expression) = &transform {
+                for (_, value) in &mut results {
+                    *value = run_in(sandbox.as_ref(), || {
+                        expression.evaluate_with_variables(value, &variables)
+                    })?
+                    .unwrap_or(Value::Null);
+                }
+            }
+            match args.get_one::<String>("output-format") {
+                Some(format) => {
+                    let results: Vec<(String, &Value)> =
+                        results.iter().map(|(path, value)| (path.clone(), value)).collect();
+                    let format: QueryFormat = format.parse()?;
+                    println!("{}", format_query_results(&results, format).trim_end());
+                }
+                None => {
+                    let values: Vec<&Value> = results.iter().map(|(_, value)| value).collect();
+                    write_to_file(&serde_json::to_string_pretty(&values)?)?;
+                }
+            }
+        }
+        Some(("explain", args)) => {
+            let document = args
+                .get_one::<String>("input")
+                .map(|path| read_and_parse_json(path))
+                .transpose()?;
+            let report = explain(args.get_one::<String>("expression").unwrap(), document.as_ref())?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Some(("search", args)) => {
+            let json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let options = SearchOptions {
+                regex: args.get_flag("regex"),
+                substring: args.get_flag("substring"),
+                ignore_case: args.get_flag("ignore-case"),
+                typed: args.get_flag("typed"),
+                keys: args.get_flag("keys"),
+                limit: args.get_one::<usize>("limit").copied(),
+                syntax: path_syntax(args)?,
+            };
+            for (path, value) in search(&json, args.get_one::<String>("pattern").unwrap(), &options)? {
+                println!("{}\t{}", path, value);
+            }
+        }
+        Some(("eval", args)) => {
+            let json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let program = JqFilter::compile(args.get_one::<String>("program").unwrap())?;
+            let configured = load_config(args.get_one::<String>("config"))?.sandbox;
+            let results = if args.get_flag("sandbox") || configured.is_some() {
+                let mut sandbox = configured.unwrap_or_default();
+                if let Some(steps) = args.get_one::<u64>("max-steps") {
+                    sandbox.max_steps = *steps;
+                }
+                if let Some(bytes) = args.get_one::<usize>("max-memory") {
+                    sandbox.max_memory = *bytes;
+                }
+                if let Some(millis) = args.get_one::<u64>("timeout-ms") {
+                    sandbox.timeout = Duration::from_millis(*millis);
+                }
+                sandbox.run(|| program.run(&json))??
+            } else {
+                program.run(&json)?
+            };
+            for result in results {
+                match result {
+                    Value::String(text) if args.get_flag("raw-output") => println!("{}", text),
+                    result if args.get_flag("compact") => {
+                        println!("{}", serde_json::to_string(&result)?)
+                    }
+                    result => println!("{}", serde_json::to_string_pretty(&result)?),
+                }
+            }
+        }
+        Some(("watch", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let interval = Duration::from_secs(*args.get_one::<u64>("interval").unwrap());
+            let mut tail = NdjsonTail::new(Path::new(input_path), args.get_flag("from-start"))?;
+            let mut stats = IncrementalStats::new(DriftOptions {
+                window: *args.get_one::<usize>("window").unwrap(),
+                ..DriftOptions::default()
+            });
+            let mut last_snapshot = Instant::now();
+            let mut reported = u64::MAX;
+            loop {
+                for record in tail.poll()? {
+                    stats.observe(&record);
+                }
+                if last_snapshot.elapsed() >= interval && stats.records() != reported {
+                    println!("{}", stats.snapshot());
+                    reported = stats.records();
+                    last_snapshot = Instant::now();
+                }
+                thread::sleep(Duration::from_millis(500));
+            }
+        }
+        Some(("migrate", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let spec_path = args.get_one::<String>("spec").unwrap();
+            let mut migration = Migration::from_json(&read_and_parse_json(spec_path)?)?;
+            migration.sandbox = load_config(args.get_one::<String>("config"))?.sandbox;
+            migration.load_tables(Path::new(spec_path).parent().unwrap_or(Path::new(".")))?;
+            let mut records = Vec::new();
+            for_each_record(Path::new(input_path), |record| records.push(record))?;
+            if let Some(reverse_path) = args.get_one::<String>("reverse") {
+                let inverse = migration.inverse()?;
+                let sample = &records[..records.len().min(*args.get_one::<usize>("sample").unwrap())];
+                let mismatches = migration.verify_round_trip(&inverse, sample);
+                fs::write(reverse_path, serde_json::to_string_pretty(&inverse)?)
+                    .with_context(|| format!("Failed to write to file: {}", reverse_path))?;
+                println!(
+                    "Inverse migration written to {}; {} of {} sample records do not round-trip {:?}",
+                    reverse_path,
+                    mismatches.len(),
+                    sample.len(),
+                    mismatches
+                );
+            }
+            let report = migrate_records(&mut records, &migration);
+            if !args.get_flag("dry-run") {
+                let output_path = args.get_one::<String>("output").unwrap();
+                let lines: Vec<String> = records.iter().map(|record| record.to_string()).collect();
+                fs::write(output_path, lines.join("\n") + "\n")
+                    .with_context(|| format!("Failed to write to file: {}", output_path))?;
+            }
+            println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+        }
+        Some(("convert", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let csv_options = CsvOptions {
+                delimiter: parse_delimiter(args.get_one::<String>("delimiter").unwrap())?,
+                ..CsvOptions::default()
+            };
+            let json = match args.get_one::<String>("from").unwrap().as_str() {
+                "json" => read_and_parse_json(input_path)?,
+                "csv" => {
+                    let text = fs::read_to_string(input_path)
+                        .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+                    csv_to_json(&text, &csv_options)
+                        .with_context(|| format!("Failed to convert {} from csv", input_path))?
+                }
+                "cbor" => {
+                    let bytes = fs::read(input_path)
+                        .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+                    convert_from_bytes(&bytes, "cbor")
+                        .with_context(|| format!("Failed to convert {} from cbor", input_path))?
+                }
+                "bson" => {
+                    let bytes = fs::read(input_path)
+                        .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+                    let options = BsonOptions {
+                        canonical: args.get_flag("canonical"),
+                    };
+                    bson_to_json(&bytes, &options)
+                        .with_context(|| format!("Failed to convert {} from bson", input_path))?
+                }
+                "querystring" => {
+                    let text = fs::read_to_string(input_path)
+                        .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+                    querystring_to_json(&text, &querystring_options(args)?)
+                        .with_context(|| format!("Failed to convert {} from querystring", input_path))?
+                }
+                from @ ("dotenv" | "properties") => {
+                    let text = fs::read_to_string(input_path)
+                        .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+                    let options = PropertiesOptions {
+                        typed: args.get_flag("typed"),
+                        ..if from == "dotenv" { PropertiesOptions::dotenv() } else { PropertiesOptions::java() }
+                    };
+                    if from == "dotenv" {
+                        dotenv_to_json(&text, &options)
+                    } else {
+                        properties_to_json(&text, &options)
+                    }
+                    .with_context(|| format!("Failed to convert {} from {}", input_path, from))?
+                }
+                "avro" => {
+                    let bytes = fs::read(input_path)
+                        .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+                    convert_from_bytes(&bytes, "avro")
+                        .with_context(|| format!("Failed to convert {} from avro", input_path))?
+                }
+                "avro-json" => {
+                    let schema_path = args
+                        .get_one::<String>("avro-schema")
+                        .context("--from avro-json needs --avro-schema")?;
+                    avro_json_to_json(&read_and_parse_json(input_path)?, &read_and_parse_json(schema_path)?)
+                        .with_context(|| format!("Failed to convert {} from avro-json", input_path))?
+                }
+                format => {
+                    let text = fs::read_to_string(input_path)
+                        .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+                    parse_format(&text, format)
+                        .with_context(|| format!("Failed to convert {} from {}", input_path, format))?
+                }
+            };
+            let to = args.get_one::<String>("to").unwrap();
+            let avro_schema = match args.get_one::<String>("avro-schema") {
+                Some(schema_path) => Some(read_and_parse_json(schema_path)?),
+                None => None,
+            };
+            if to == "avro" {
+                let options = AvroOptions { schema: avro_schema };
+                fs::write("output.avro", json_to_avro(&json, &options)?)
+                    .context("Failed to write to output.avro")?;
+                return Ok(());
+            }
+            if to == "cbor" || to == "bson" || to == "parquet" {
+                let output_path = format!("output.{}", to);
+                fs::write(&output_path, convert_to_bytes(&json, to)?)
+                    .with_context(|| format!("Failed to write to {}", output_path))?;
+                return Ok(());
+            }
+            let converted = if to == "toml" {
+                json_to_toml(&json)?
+            } else if to == "csv" {
+                json_to_csv(&json, &csv_options)?
+            } else if to == "json" {
+                serde_json::to_string_pretty(&json)?
+            } else if to == "querystring" {
+                json_to_querystring(&json, &querystring_options(args)?)?
+            } else if to == "sql" {
+                let table = match args.get_one::<String>("table") {
+                    Some(table) => table.clone(),
+                    None => Path::new(input_path).file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                };
+                let options = SqlOptions {
+                    table,
+                    rows_per_insert: *args.get_one::<usize>("rows-per-insert").unwrap(),
+                    create_table: args.get_flag("create-table"),
+                };
+                json_to_sql(&json, &options)?
+            } else if to == "dotenv" {
+                json_to_dotenv(&json, &PropertiesOptions::dotenv())?
+            } else if to == "properties" {
+                json_to_properties(&json, &PropertiesOptions::java())?
+            } else if to == "avro-json" {
+                let schema = avro_schema.unwrap_or_else(|| infer_avro_schema(&json));
+                serde_json::to_string_pretty(&json_to_avro_json(&json, &schema)?)?
+            } else if to == "markdown" || to == "html" {
+                let options = TableOptions {
+                    nested: if args.get_flag("inline") { NestedCells::Inline } else { NestedCells::Flatten },
+                    syntax: path_syntax(args)?,
+                };
+                if to == "markdown" {
+                    json_to_markdown(&json, &options)?
+                } else {
+                    json_to_html(&json, &options)?
+                }
+            } else if to == "xml" {
+                let mut options = XmlOptions {
+                    root: args.get_one::<String>("root").unwrap().clone(),
+                    default_namespace: args.get_one::<String>("default-namespace").cloned(),
+                    nil_nulls: args.get_flag("xsi-nil"),
+                    declaration: args.get_flag("declaration"),
+                    item_name: args.get_one::<String>("item-name").cloned(),
+                    scalar_attributes: args.get_flag("attributes"),
+                    ..XmlOptions::default()
+                };
+                for declaration in args.get_many::<String>("namespace").unwrap_or_default() {
+                    let (prefix, uri) = declaration
+                        .split_once('=')
+                        .with_context(|| format!("Invalid namespace declaration: {}", declaration))?;
+                    options.namespaces.insert(prefix.to_string(), uri.to_string());
+                }
+                if let Some(mapping_path) = args.get_one::<String>("mapping") {
+                    options.apply_mapping(&read_and_parse_json(mapping_path)?)?;
+                }
+                if let Some(xsd_path) = args.get_one::<String>("xsd") {
+                    let xsd = fs::read_to_string(xsd_path)
+                        .with_context(|| format!("Failed to read XSD file at path: {}", xsd_path))?;
+                    for violation in validate_against_xsd(&json, &options, &xsd)? {
+                        println!("{}: {}", violation.path, violation.message);
+                    }
+                }
+                json_to_xml(&json, &options)?
+            } else {
+                let options = YamlOptions {
+                    anchors: args.get_flag("anchors"),
+                    indent: *args.get_one::<usize>("indent").unwrap(),
+                    indent_sequences: args.get_flag("indent-sequences"),
+                    flow_max_items: *args.get_one::<usize>("flow-max-items").unwrap(),
+                    quoting: args.get_one::<String>("quote").unwrap().parse()?,
+                    width: *args.get_one::<usize>("width").unwrap(),
+                    document_markers: args.get_flag("document-markers"),
+                    ..YamlOptions::default()
+                };
+                json_to_yaml(&json, &options)?
+            };
+            write_to_file(&converted)?;
+        }
+        Some(("merge", args)) => {
+            let mut merged = read_and_parse_json(args.get_one::<String>("base").unwrap())?;
+            let before = merged.clone();
+            let strategy = MergeStrategy {
+                arrays: args.get_one::<String>("arrays").unwrap().parse()?,
+                nulls: args.get_one::<String>("nulls").unwrap().parse()?,
+            };
+            for overlay_path in args.get_many::<String>("overlays").unwrap() {
+                merge_json(&mut merged, &read_and_parse_json(overlay_path)?, strategy.clone());
+            }
+            write_to_file(&serde_json::to_string_pretty(&merged)?)?;
+            record_audit(args.get_one::<String>("audit-log"), "merge", &before, &merged)?;
+        }
+        Some(("patch", args)) => {
+            let mut json = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let patch = read_and_parse_json(args.get_one::<String>("patch").unwrap())?;
+            let before = json.clone();
+            apply_patch(&mut json, &patch)?;
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+            record_audit(args.get_one::<String>("audit-log"), "patch", &before, &json)?;
+        }
+        Some(("from-yaml", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let yaml = fs::read_to_string(input_path)
+                .with_context(|| format!("Failed to read YAML file at path: {}", input_path))?;
+            let documents = parse_yaml_documents(&yaml)?;
+            let output = if args.get_flag("ndjson") {
+                documents
+                    .iter()
+                    .map(serde_json::to_string)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join("\n")
+            } else if documents.len() == 1 {
+                serde_json::to_string_pretty(&documents[0])?
+            } else {
+                serde_json::to_string_pretty(&documents)?
+            };
+            write_to_file(&output)?;
+            println!("Converted {} YAML document(s).", documents.len());
+        }
+        Some(("from-toml", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let toml = fs::read_to_string(input_path)
+                .with_context(|| format!("Failed to read TOML file at path: {}", input_path))?;
+            let options = TomlOptions {
+                annotate_types: args.get_flag("annotate-types"),
+            };
+            let json = parse_toml(&toml, &options)?;
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+        }
+        Some(("from-csv", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let csv = fs::read_to_string(input_path)
+                .with_context(|| format!("Failed to read CSV file at path: {}", input_path))?;
+            let mut options = CsvOptions {
+                delimiter: parse_delimiter(args.get_one::<String>("delimiter").unwrap())?,
+                separator: args.get_one::<String>("separator").unwrap().clone(),
+                infer_types: !args.get_flag("strings"),
+                decimal_separator: parse_character(args.get_one::<String>("decimal").unwrap(), "decimal separator")?,
+                thousands_separator: args
+                    .get_one::<String>("thousands")
+                    .map(|text| parse_character(text, "thousands separator"))
+                    .transpose()?,
+                ..CsvOptions::default()
+            };
+            for column_format in args.get_many::<String>("date-format").unwrap_or_default() {
+                let (column, format) = column_format.split_once('=').with_context(|| {
+                    format!("Date format '{}' is not in column=format form", column_format)
+                })?;
+                options.date_formats.insert(column.to_string(), format.to_string());
+            }
+            let Some(schema_path) = args.get_one::<String>("schema") else {
+                let json = csv_to_json(&csv, &options)?;
+                write_to_file(&serde_json::to_string_pretty(&json)?)?;
+                return Ok(());
+            };
+            let schema = read_and_parse_json(schema_path)?;
+            let import = csv_to_json_validated(&csv, &schema, &options)?;
+            write_to_file(&serde_json::to_string_pretty(&import.rows)?)?;
+            if let Some(quarantine_path) = args.get_one::<String>("quarantine") {
+                let lines: String = import.quarantined.iter().map(|row| format!("{}\n", row)).collect();
+                fs::write(quarantine_path, lines)
+                    .with_context(|| format!("Failed to write to {}", quarantine_path))?;
+            }
+            if !import.quarantined.is_empty() {
+                eprintln!(
+                    "{} of {} rows failed the schema",
+                    import.quarantined.len(),
+                    import.rows.len() + import.quarantined.len()
+                );
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        Some(("export-sqlite", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let db_path = args.get_one::<String>("db").unwrap();
+            let json = read_and_parse_json(input_path)?;
+            let table = match args.get_one::<String>("table") {
+                Some(table) => table.clone(),
+                None => Path::new(input_path).file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            };
+            let database = json_to_sqlite(&json, &SqliteOptions { table: table.clone() })?;
+            use std::io::Write;
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(db_path)
+                .with_context(|| format!("Failed to create SQLite database at path: {}", db_path))?;
+            file.write_all(&database)
+                .with_context(|| format!("Failed to write to {}", db_path))?;
+            let rows = json.as_array().map_or(1, Vec::len);
+            println!("Wrote {} row(s) to table {} of {}.", rows, table, db_path);
+        }
+        #[cfg(feature = "sqlite")]
+        Some(("import-sqlite", args)) => {
+            let db_path = args.get_one::<String>("db").unwrap();
+            let database = fs::read(db_path)
+                .with_context(|| format!("Failed to read SQLite database at path: {}", db_path))?;
+            let json = sqlite_to_json(&database, args.get_one::<String>("table").map(String::as_str))?;
+            write_to_file(&serde_json::to_string_pretty(&json)?)?;
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Some(("export-sqlite" | "import-sqlite", _)) => {
+            return Err(anyhow::Error::msg("SQLite export and import need the crate's `sqlite` feature"));
+        }
+        Some(("cargo", args)) => {
+            let manifest_path = Path::new(args.get_one::<String>("manifest").unwrap());
+            let read_toml = |path: &Path| -> Result<Value> {
+                let text = fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read TOML file at path: {}", path.display()))?;
+                parse_toml(&text, &TomlOptions::default())
+            };
+            let manifest = read_toml(manifest_path)?;
+            let lock_path = match args.get_one::<String>("lock") {
+                Some(path) => Some(Path::new(path).to_path_buf()),
+                None => Some(manifest_path.with_file_name("Cargo.lock")).filter(|path| path.exists()),
+            };
+            let lock = lock_path.as_deref().map(read_toml).transpose()?;
+            let report = cargo_report(&manifest, lock.as_ref())?;
+            let output = match args.get_one::<String>("query") {
+                Some(path) => serde_json::to_value(query_jsonpath(&report, path)?)?,
+                None => report,
+            };
+            write_to_file(&serde_json::to_string_pretty(&output)?)?;
+        }
+        Some(("npm-audit", args)) => {
+            let package_path = Path::new(args.get_one::<String>("package").unwrap());
+            let package = read_and_parse_json(&package_path.to_string_lossy())?;
+            let lock_path = match args.get_one::<String>("lock") {
+                Some(path) => Some(Path::new(path).to_path_buf()),
+                None => ["package-lock.json", "npm-shrinkwrap.json"]
+                    .iter()
+                    .map(|name| package_path.with_file_name(name))
+                    .find(|path| path.exists()),
+            };
+            let lock = lock_path
+                .map(|path| read_and_parse_json(&path.to_string_lossy()))
+                .transpose()?;
+            let report = npm_audit(&package, lock.as_ref(), *args.get_one::<usize>("top").unwrap())?;
+            write_to_file(&serde_json::to_string_pretty(&report)?)?;
+        }
+        Some(("tfstate", args)) => {
+            let state = read_and_parse_json(args.get_one::<String>("input").unwrap())?;
+            let report = tfstate_report(&state, !args.get_flag("show-sensitive"))?;
+            let output = match args.get_one::<String>("query") {
+                Some(path) => serde_json::to_value(query_jsonpath(&report, path)?)?,
+                None => report,
+            };
+            write_to_file(&serde_json::to_string_pretty(&output)?)?;
+        }
+        Some(("feed", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let text = fs::read_to_string(input_path)
+                .with_context(|| format!("Failed to read feed at path: {}", input_path))?;
+            if text.trim_start().starts_with('<') {
+                let feed = xml_to_json_feed(&text)?;
+                write_to_file(&serde_json::to_string_pretty(&feed)?)?;
+            } else {
+                let feed: Value = serde_json::from_str(&text)
+                    .with_context(|| format!("Failed to parse JSON in file: {}", input_path))?;
+                let report = validate_json_feed(&feed);
+                if !report.is_valid() {
+                    println!("{}", report);
+                    write_to_file(&report.to_string())?;
+                    eprintln!("Validation failed with {} violation(s).", report.violations.len());
+                    std::process::exit(1);
+                }
+                match args.get_one::<String>("to") {
+                    Some(format) => write_to_file(&json_feed_to_xml(&feed, format.parse::<FeedFormat>()?)?)?,
+                    None => write_to_file("JSON Feed is valid.")?,
+                }
+            }
+        }
+        Some(("bench", group)) => {
+            let (operation, args) = group.subcommand().unwrap();
+            let cache = args
+                .get_one::<String>("cache")
+                .map(PathBuf::from)
+                .unwrap_or_else(default_cache_dir);
+            let corpora = fetch_corpora(&cache)?;
+            if operation == "fetch" {
+                for (name, path) in &corpora {
+                    println!("{}: {}", name, path.display());
+                }
+                return Ok(());
+            }
+            let iterations = *args.get_one::<u32>("iterations").unwrap();
+            let results = run_bench(&corpora, iterations)?;
+            let report = bench_report(&results);
+            if operation == "run" {
+                let text = serde_json::to_string_pretty(&report)?;
+                match args.get_one::<String>("output") {
+                    Some(path) => fs::write(path, text).with_context(|| format!("Failed to write {}", path))?,
+                    None => write_to_file(&text)?,
+                }
+                for result in &results {
+                    println!("{}: {:.1} MB/s", result.name(), result.mb_per_s());
+                }
+                return Ok(());
+            }
+            let baseline = read_and_parse_json(args.get_one::<String>("baseline").unwrap())?;
+            let tolerance = *args.get_one::<f64>("tolerance").unwrap();
+            let comparison = compare_bench(&report, &baseline, tolerance)?;
+            println!("{}", serde_json::to_string_pretty(&comparison)?);
+            let regressions = comparison["regressions"].as_u64().unwrap_or(0);
+            if regressions > 0 {
+                eprintln!("Throughput regressed in {} benchmark(s).", regressions);
+                std::process::exit(1);
+            }
+        }
+        Some(("lint", args)) => {
+            let input_path = args.get_one::<String>("input").unwrap();
+            let text = fs::read_to_string(input_path)
+                .with_context(|| format!("Failed to read file at path: {}", input_path))?;
+            save_repro_input(input_path, &text)?;
+            let (_, diagnostics) = parse_json_with_recovery(&text);
+            if diagnostics.is_empty() {
+                println!("{}: no syntax errors", input_path);
+            } else {
+                for diagnostic in &diagnostics {
+                    eprintln!("{}:\n{}", input_path, diagnostic.excerpt());
+                }
+                eprintln!("Found {} syntax error(s).", diagnostics.len());
+                std::process::exit(1);
+            }
+        }
+        Some(("serve", args)) => {
+            let dir = args.get_one::<String>("dir").unwrap();
+            let bind = args.get_one::<String>("bind").unwrap();
+            let config = load_config(args.get_one::<String>("config"))?;
+            let server = DocumentServer::new(Path::new(dir), &config)?;
+            let listener = std::net::TcpListener::bind(bind)
+                .with_context(|| format!("Failed to listen on {}", bind))?;
+            println!("Serving {} on http://{}", dir, bind);
+            server.serve(listener)?;
+        }
         _ => {
             println!("Invalid command. Use `help` for the list of available commands.");
         }
@@ -0,0 +1,154 @@
+use anyhow::{Error, Result};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// How arrays are combined when both documents contain one at the same path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrayMerge {
+    /// The overlay array replaces the base array.
+    Replace,
+    /// Overlay elements are appended to the base array.
+    Concat,
+    /// Objects sharing the same value of the named key are deep-merged; other
+    /// overlay elements are appended unless an equal element already exists.
+    UnionByKey(String),
+}
+
+impl FromStr for ArrayMerge {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replace" => Ok(ArrayMerge::Replace),
+            "concat" => Ok(ArrayMerge::Concat),
+            _ => match s.strip_prefix("union:") {
+                Some(key) if !key.is_empty() => Ok(ArrayMerge::UnionByKey(key.to_string())),
+                _ => Err(Error::msg(format!(
+                    "Unsupported array merge strategy: {}",
+                    s
+                ))),
+            },
+        }
+    }
+}
+
+/// How `null` values in the overlay are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullMerge {
+    /// `null` overwrites the base value.
+    Overwrite,
+    /// `null` deletes the key from the base object, and `null` members of objects the
+    /// overlay adds are dropped (JSON Merge Patch semantics, RFC 7396).
+    Delete,
+    /// `null` is skipped and the base value is kept.
+    Ignore,
+}
+
+impl FromStr for NullMerge {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overwrite" => Ok(NullMerge::Overwrite),
+            "delete" => Ok(NullMerge::Delete),
+            "ignore" => Ok(NullMerge::Ignore),
+            _ => Err(Error::msg(format!(
+                "Unsupported null merge strategy: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Strategy used by `merge_json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeStrategy {
+    pub arrays: ArrayMerge,
+    pub nulls: NullMerge,
+}
+
+impl Default for MergeStrategy {
+    fn default() -> Self {
+        MergeStrategy {
+            arrays: ArrayMerge::Replace,
+            nulls: NullMerge::Overwrite,
+        }
+    }
+}
+
+/// Deep-merges `overlay` into `base`.
+///
+/// Objects are merged key by key; for every other combination of types the
+/// overlay value wins, subject to the array and null handling of `strategy`.
+///
+/// # Arguments
+///
+/// * `base` - The document to merge into, e.g. a base config.
+/// * `overlay` - The document layered on top, e.g. an environment config.
+/// * `strategy` - The array and null handling.
+pub fn merge_json(base: &mut Value, overlay: &Value, strategy: MergeStrategy) {
+    merge_recursive(base, overlay, &strategy);
+}
+
+/// Recursive helper for `merge_json`.
+fn merge_recursive(base: &mut Value, overlay: &Value, strategy: &MergeStrategy) {
+    match (base, overlay) {
+        (Value::Object(base_map), Value::Object(overlay_map)) => {
+            for (key, overlay_value) in overlay_map {
+                if overlay_value.is_null() {
+                    match strategy.nulls {
+                        NullMerge::Overwrite => {
+                            base_map.insert(key.clone(), Value::Null);
+                        }
+                        NullMerge::Delete => {
+                            base_map.remove(key);
+                        }
+                        NullMerge::Ignore => {}
+                    }
+                    continue;
+                }
+                match base_map.get_mut(key) {
+                    Some(base_value) => merge_recursive(base_value, overlay_value, strategy),
+                    None => {
+                        base_map.insert(key.clone(), inserted(overlay_value, strategy));
+                    }
+                }
+            }
+        }
+        (Value::Array(base_items), Value::Array(overlay_items)) => match &strategy.arrays {
+            ArrayMerge::Replace => *base_items = overlay_items.clone(),
+            ArrayMerge::Concat => base_items.extend(overlay_items.iter().cloned()),
+            ArrayMerge::UnionByKey(key) => {
+                for item in overlay_items {
+                    let matching = item.get(key).and_then(|id| {
+                        base_items
+                            .iter()
+                            .position(|existing| existing.get(key) == Some(id))
+                    });
+                    match matching {
+                        Some(index) => merge_recursive(&mut base_items[index], item, strategy),
+                        None if base_items.contains(item) => {}
+                        None => base_items.push(item.clone()),
+                    }
+                }
+            }
+        },
+        (_, Value::Null) if strategy.nulls == NullMerge::Ignore => {}
+        (base, overlay) => *base = inserted(overlay, strategy),
+    }
+}
+
+/// Returns an overlay value as it is inserted into the base: with `NullMerge::Delete`,
+/// without the `null` members of its objects, as if merged into an empty object.
+/// Arrays are inserted as they are.
+fn inserted(overlay: &Value, strategy: &MergeStrategy) -> Value {
+    match overlay {
+        Value::Object(map) if strategy.nulls == NullMerge::Delete => Value::Object(
+            map.iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), inserted(value, strategy)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
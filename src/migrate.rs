@@ -0,0 +1,519 @@
+use crate::cancel;
+use crate::csv::read_records;
+use crate::parser::{get_at_mut, insert_at, parse_steps, remove_at, Step};
+use crate::path::Expression;
+use crate::sandbox::{self, Sandbox};
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// A single declarative migration operation.
+///
+/// Paths use the `get_by_path` syntax (`user.addresses[0].city`).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum MigrationOp {
+    /// Renames the last key of `path` to `to`, keeping it under the same parent.
+    Rename { path: String, to: String },
+    /// Converts the value at `path` to `type` (`string`, `integer`, `number`, or `boolean`).
+    ///
+    /// The optional `from` names the original type so the cast can be reversed.
+    Cast {
+        path: String,
+        #[serde(rename = "type")]
+        target: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        from: Option<String>,
+    },
+    /// Moves the value at `from` to `to`, creating intermediate objects as needed.
+    Move { from: String, to: String },
+    /// Sets `path` to `value` when it is missing or null.
+    Default { path: String, value: Value },
+    /// Sets `path` to the result of `expr`, an expression over the record such as
+    /// `first + " " + last` or `end - start` (see `Expression`). Records for which
+    /// the expression has no value, e.g. because a field is missing, are unchanged.
+    Compute { path: String, expr: String },
+    /// Looks up the value at `path` in `table` and writes the matching entry to `to`.
+    ///
+    /// `table` is either an object mapping keys to entries or an array of row objects
+    /// whose `key` column (default `"key"`) holds the lookup key; a string names a JSON
+    /// or CSV file loaded by `Migration::load_tables`. Rows are written without their
+    /// key column, or only their `field` column if given. `match` is `exact` (the
+    /// default), `prefix` (longest string prefix), or `cidr` (longest IP network such
+    /// as `10.0.0.0/8` containing the value). Records without a match are unchanged.
+    Enrich {
+        path: String,
+        to: String,
+        table: Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        field: Option<String>,
+        #[serde(rename = "match", default, skip_serializing_if = "Option::is_none")]
+        matching: Option<String>,
+    },
+}
+
+/// A declarative migration: an ordered list of operations applied to every record.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Migration {
+    pub operations: Vec<MigrationOp>,
+    /// Limits for evaluating each `compute` expression, e.g. the config file's `sandbox`;
+    /// a record exceeding them fails that operation.
+    #[serde(skip)]
+    pub sandbox: Option<Sandbox>,
+}
+
+/// A record that could not be migrated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationFailure {
+    /// Zero-based position of the record in the dataset.
+    pub record: usize,
+    /// Zero-based position of the failing operation in the spec.
+    pub operation: usize,
+    pub message: String,
+}
+
+/// Outcome of migrating a dataset.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationReport {
+    pub total: usize,
+    pub migrated: usize,
+    pub failures: Vec<MigrationFailure>,
+    /// Whether a `CancellationToken` stopped the run; records after the last migrated
+    /// or failed one are unchanged.
+    pub cancelled: bool,
+}
+
+impl MigrationReport {
+    /// Converts the report to JSON, with `"cancelled": true` if the run was cancelled.
+    pub fn to_json(&self) -> Value {
+        let failures: Vec<Value> = self
+            .failures
+            .iter()
+            .map(|f| json!({ "record": f.record, "operation": f.operation, "error": f.message }))
+            .collect();
+        let mut report = json!({
+            "total": self.total,
+            "migrated": self.migrated,
+            "failed": self.failures.len(),
+            "failures": failures,
+        });
+        if self.cancelled {
+            report["cancelled"] = Value::Bool(true);
+        }
+        report
+    }
+}
+
+impl Migration {
+    /// Parses a migration spec, either `{ "operations": [...] }` or a bare array of operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `spec` - The migration spec as JSON.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Migration, Error>` - The parsed migration, or an error if the spec is malformed.
+    pub fn from_json(spec: &Value) -> Result<Migration, Error> {
+        let result = if spec.is_array() {
+            serde_json::from_value(json!({ "operations": spec }))
+        } else {
+            serde_json::from_value(spec.clone())
+        };
+        result.map_err(|e| Error::msg(format!("Invalid migration spec: {}", e)))
+    }
+
+    /// Loads the lookup tables of `enrich` operations that name a file.
+    ///
+    /// Files ending in `.csv` are read as a header row followed by data rows, giving
+    /// one object of string columns per row; other files are read as JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_dir` - Directory that relative table paths are resolved against,
+    ///   usually the directory of the migration spec.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - An error if a table cannot be read or parsed.
+    pub fn load_tables(&mut self, base_dir: &Path) -> Result<(), Error> {
+        for operation in &mut self.operations {
+            if let MigrationOp::Enrich { table, .. } = operation {
+                let Value::String(file) = table else {
+                    continue;
+                };
+                let path = base_dir.join(file.as_str());
+                let text = fs::read_to_string(&path).with_context(|| {
+                    format!("Failed to read lookup table at path: {}", path.display())
+                })?;
+                *table = if path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+                {
+                    Value::Array(parse_csv(&text)?)
+                } else {
+                    serde_json::from_str(&text).with_context(|| {
+                        format!("Failed to parse lookup table at path: {}", path.display())
+                    })?
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies every operation to a record.
+    ///
+    /// The record is only modified if all operations succeed; otherwise it is
+    /// left untouched and the index of the failing operation is returned with the error.
+    pub fn apply(&self, record: &mut Value) -> Result<(), (usize, Error)> {
+        let mut migrated = record.clone();
+        for (index, operation) in self.operations.iter().enumerate() {
+            apply_operation(operation, &mut migrated, self.sandbox.as_ref())
+                .map_err(|e| (index, e))?;
+        }
+        *record = migrated;
+        Ok(())
+    }
+
+    /// Generates the inverse migration, which undoes this one when applied afterwards.
+    ///
+    /// Renames and moves are always reversible; casts are reversible when they declare
+    /// their original type with `from`. Defaults cannot be reversed, since the migrated
+    /// record no longer shows whether the value was originally missing.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Migration, Error>` - The inverse migration, or an error naming the first
+    ///   operation that cannot be reversed.
+    pub fn inverse(&self) -> Result<Migration, Error> {
+        let mut operations = Vec::with_capacity(self.operations.len());
+        for (index, operation) in self.operations.iter().enumerate().rev() {
+            let not_reversible = |reason: &str| {
+                Error::msg(format!("Operation {} is not reversible: {}", index, reason))
+            };
+            operations.push(match operation {
+                MigrationOp::Rename { path, to } => {
+                    let (parent, name) = match path.rsplit_once('.') {
+                        Some((parent, name)) => (format!("{}.", parent), name),
+                        None => (String::new(), path.as_str()),
+                    };
+                    if name.contains('[') {
+                        return Err(not_reversible("renamed path ends with an array index"));
+                    }
+                    MigrationOp::Rename {
+                        path: format!("{}{}", parent, to),
+                        to: name.to_string(),
+                    }
+                }
+                MigrationOp::Cast { path, target, from } => MigrationOp::Cast {
+                    path: path.clone(),
+                    target: from
+                        .clone()
+                        .ok_or_else(|| not_reversible("cast does not declare its 'from' type"))?,
+                    from: Some(target.clone()),
+                },
+                MigrationOp::Move { from, to } => MigrationOp::Move {
+                    from: to.clone(),
+                    to: from.clone(),
+                },
+                MigrationOp::Default { .. } => {
+                    return Err(not_reversible(
+                        "defaults cannot be told apart from original values",
+                    ))
+                }
+                MigrationOp::Compute { .. } => {
+                    return Err(not_reversible(
+                        "computed values cannot be told apart from original values",
+                    ))
+                }
+                MigrationOp::Enrich { .. } => {
+                    return Err(not_reversible(
+                        "enriched values cannot be told apart from original values",
+                    ))
+                }
+            });
+        }
+        Ok(Migration {
+            operations,
+            sandbox: self.sandbox,
+        })
+    }
+
+    /// Checks that applying this migration and then `inverse` restores every sample record.
+    ///
+    /// Records that fail the forward migration are skipped, since they are reported
+    /// by `migrate_records` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `inverse` - The inverse migration, usually produced by `Migration::inverse`.
+    /// * `sample` - The original records to check.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<usize>` - Positions of sample records that do not round-trip.
+    pub fn verify_round_trip(&self, inverse: &Migration, sample: &[Value]) -> Vec<usize> {
+        sample
+            .iter()
+            .enumerate()
+            .filter(|(_, original)| {
+                let mut record = (*original).clone();
+                if self.apply(&mut record).is_err() {
+                    return false;
+                }
+                inverse.apply(&mut record).is_err() || record != **original
+            })
+            .map(|(position, _)| position)
+            .collect()
+    }
+}
+
+/// Migrates every record in place, collecting per-record failures.
+///
+/// # Arguments
+///
+/// * `records` - The records to migrate; failing records are left unchanged.
+/// * `migration` - The migration to apply.
+///
+/// # Returns
+///
+/// * `MigrationReport` - Counts of migrated records and the failures.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(records = records.len())))]
+pub fn migrate_records(records: &mut [Value], migration: &Migration) -> MigrationReport {
+    let mut report = MigrationReport {
+        total: records.len(),
+        ..MigrationReport::default()
+    };
+    for (position, record) in records.iter_mut().enumerate() {
+        if cancel::is_cancelled() {
+            report.cancelled = true;
+            break;
+        }
+        match migration.apply(record) {
+            Ok(()) => report.migrated += 1,
+            Err((operation, error)) => report.failures.push(MigrationFailure {
+                record: position,
+                operation,
+                message: error.to_string(),
+            }),
+        }
+    }
+    report
+}
+
+/// Applies a single operation to a record.
+fn apply_operation(
+    operation: &MigrationOp,
+    record: &mut Value,
+    sandbox: Option<&Sandbox>,
+) -> Result<(), Error> {
+    match operation {
+        MigrationOp::Rename { path, to } => {
+            let mut steps = parse_steps(path)?;
+            if let Some(value) = remove_at(record, &steps) {
+                match steps.last_mut() {
+                    Some(Step::Key(key)) => *key = to.clone(),
+                    _ => {
+                        return Err(Error::msg(format!(
+                            "Cannot rename array element '{}'",
+                            path
+                        )))
+                    }
+                }
+                insert_at(record, &steps, value)?;
+            }
+            Ok(())
+        }
+        MigrationOp::Cast { path, target, .. } => {
+            let steps = parse_steps(path)?;
+            if let Some(value) = get_at_mut(record, &steps) {
+                *value = cast_value(value, target)
+                    .map_err(|e| Error::msg(format!("Cannot cast '{}': {}", path, e)))?;
+            }
+            Ok(())
+        }
+        MigrationOp::Move { from, to } => {
+            let from_steps = parse_steps(from)?;
+            let to_steps = parse_steps(to)?;
+            if get_at_mut(record, &to_steps).is_some() {
+                return Err(Error::msg(format!("Destination '{}' already exists", to)));
+            }
+            if let Some(value) = remove_at(record, &from_steps) {
+                insert_at(record, &to_steps, value)?;
+            }
+            Ok(())
+        }
+        MigrationOp::Default { path, value } => {
+            let steps = parse_steps(path)?;
+            match get_at_mut(record, &steps) {
+                Some(existing) if !existing.is_null() => Ok(()),
+                Some(existing) => {
+                    *existing = value.clone();
+                    Ok(())
+                }
+                None => insert_at(record, &steps, value.clone()),
+            }
+        }
+        MigrationOp::Compute { path, expr } => {
+            let expression = Expression::compile(expr)?;
+            match sandbox::run_in(sandbox, || expression.evaluate(record))? {
+                Some(value) => insert_at(record, &parse_steps(path)?, value),
+                None => Ok(()),
+            }
+        }
+        MigrationOp::Enrich {
+            path,
+            to,
+            table,
+            key,
+            field,
+            matching,
+        } => {
+            let steps = parse_steps(path)?;
+            let lookup = match get_at_mut(record, &steps) {
+                Some(Value::String(s)) => s.clone(),
+                Some(value @ (Value::Number(_) | Value::Bool(_))) => value.to_string(),
+                _ => return Ok(()),
+            };
+            let key = key.as_deref().unwrap_or("key");
+            let entries: Vec<(&str, Value)> = match table {
+                Value::Object(map) => map.iter().map(|(k, v)| (k.as_str(), v.clone())).collect(),
+                Value::Array(rows) => rows
+                    .iter()
+                    .filter_map(|row| {
+                        let row_key = row.get(key)?.as_str()?;
+                        let mut entry = row.as_object()?.clone();
+                        entry.remove(key);
+                        Some((row_key, Value::Object(entry)))
+                    })
+                    .collect(),
+                Value::String(file) => {
+                    return Err(Error::msg(format!("Lookup table '{}' is not loaded", file)))
+                }
+                _ => return Err(Error::msg("Lookup table must be an object or an array")),
+            };
+            let found = match matching.as_deref().unwrap_or("exact") {
+                "exact" => entries.into_iter().find(|(k, _)| *k == lookup),
+                "prefix" => entries
+                    .into_iter()
+                    .filter(|(k, _)| lookup.starts_with(k))
+                    .max_by_key(|(k, _)| k.len()),
+                "cidr" => {
+                    let address: Option<IpAddr> = lookup.parse().ok();
+                    entries
+                        .into_iter()
+                        .filter_map(|(k, v)| Some((network_match(address?, k)?, v)))
+                        .max_by_key(|(length, _)| *length)
+                        .map(|(_, v)| ("", v))
+                }
+                other => return Err(Error::msg(format!("Unsupported match mode: {}", other))),
+            };
+            let Some((_, mut entry)) = found else {
+                return Ok(());
+            };
+            if let Some(field) = field {
+                entry = entry.get(field).cloned().unwrap_or(Value::Null);
+            }
+            insert_at(record, &parse_steps(to)?, entry)
+        }
+    }
+}
+
+/// Returns the prefix length of `network` (`10.0.0.0/8`, or a single address) if it
+/// contains `address`.
+fn network_match(address: IpAddr, network: &str) -> Option<u32> {
+    let (base, length) = match network.split_once('/') {
+        Some((base, length)) => (base, Some(length.parse::<u32>().ok()?)),
+        None => (network, None),
+    };
+    let (address, base, bits) = match (address, base.parse::<IpAddr>().ok()?) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => (u32::from(a) as u128, u32::from(b) as u128, 32),
+        (IpAddr::V6(a), IpAddr::V6(b)) => (u128::from(a), u128::from(b), 128),
+        _ => return None,
+    };
+    let length = length.unwrap_or(bits);
+    if length > bits {
+        return None;
+    }
+    let shift = bits - length;
+    let same = shift >= 128 || (address >> shift) == (base >> shift);
+    same.then_some(length)
+}
+
+/// Parses CSV text with a header row into one object of string columns per row.
+fn parse_csv(text: &str) -> Result<Vec<Value>, Error> {
+    let mut records = read_records(text, ',')?.into_iter();
+    let header = records
+        .next()
+        .ok_or_else(|| Error::msg("CSV lookup table has no header row"))?;
+    records
+        .enumerate()
+        .map(|(line, row)| {
+            if row.len() != header.len() {
+                return Err(Error::msg(format!(
+                    "CSV row {} has {} fields, expected {}",
+                    line + 2,
+                    row.len(),
+                    header.len()
+                )));
+            }
+            let row = header
+                .iter()
+                .cloned()
+                .zip(row.into_iter().map(Value::String));
+            Ok(Value::Object(row.collect()))
+        })
+        .collect()
+}
+
+/// Converts a value to the named JSON type.
+fn cast_value(value: &Value, target: &str) -> Result<Value, Error> {
+    let fail = || Error::msg(format!("{} is not convertible to {}", value, target));
+    match target {
+        "string" => Ok(match value {
+            Value::String(s) => Value::String(s.clone()),
+            other => Value::String(other.to_string()),
+        }),
+        "integer" => match value {
+            Value::Number(n) if n.is_i64() || n.is_u64() => Ok(value.clone()),
+            Value::Number(n) => match n.as_f64() {
+                Some(f) if f.fract() == 0.0 => Ok(json!(f as i64)),
+                _ => Err(fail()),
+            },
+            Value::String(s) => s.trim().parse::<i64>().map(Value::from).map_err(|_| fail()),
+            Value::Bool(b) => Ok(json!(*b as i64)),
+            _ => Err(fail()),
+        },
+        "number" => match value {
+            Value::Number(_) => Ok(value.clone()),
+            Value::String(s) => s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|f| f.is_finite())
+                .map(Value::from)
+                .ok_or_else(fail),
+            _ => Err(fail()),
+        },
+        "boolean" => match value {
+            Value::Bool(_) => Ok(value.clone()),
+            Value::String(s) => match s.trim().to_lowercase().as_str() {
+                "true" | "1" | "yes" => Ok(Value::Bool(true)),
+                "false" | "0" | "no" => Ok(Value::Bool(false)),
+                _ => Err(fail()),
+            },
+            Value::Number(n) => match n.as_f64() {
+                Some(0.0) => Ok(Value::Bool(false)),
+                Some(1.0) => Ok(Value::Bool(true)),
+                _ => Err(fail()),
+            },
+            _ => Err(fail()),
+        },
+        other => Err(Error::msg(format!("Unsupported cast type: {}", other))),
+    }
+}
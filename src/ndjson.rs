@@ -0,0 +1,1371 @@
+use crate::cancel;
+use crate::datetime::{format_date, parse_date};
+use crate::parser::{canonicalize, get_by_path};
+use crate::path::Expression;
+use crate::random::SeededRng;
+use crate::sandbox::{self, Sandbox};
+use crate::stats::{compare_values, write_replacing, BloomFilter, RunningMoments, TDigest};
+use crate::warnings;
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Options for sorting an NDJSON file.
+#[derive(Debug, Clone)]
+pub struct SortOptions {
+    /// Key path (in `get_by_path` syntax) the records are ordered by.
+    pub key: String,
+    /// Sorts in descending order when set.
+    pub descending: bool,
+    /// Maximum number of records held in memory per sorted run.
+    pub chunk_records: usize,
+    /// Directory for temporary run files; the system temp directory if `None`.
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl SortOptions {
+    /// Creates ascending sort options for the given key path.
+    pub fn new(key: &str) -> Self {
+        SortOptions {
+            key: key.to_string(),
+            descending: false,
+            chunk_records: 100_000,
+            temp_dir: None,
+        }
+    }
+}
+
+/// A record line together with its extracted sort key.
+struct KeyedLine {
+    key: Value,
+    line: String,
+}
+
+/// Head of a sorted run during the k-way merge.
+struct RunHead {
+    record: KeyedLine,
+    run: usize,
+    descending: bool,
+}
+
+impl RunHead {
+    fn order(&self, other: &Self) -> Ordering {
+        let by_key = compare_values(&self.record.key, &other.record.key);
+        let by_key = if self.descending {
+            by_key.reverse()
+        } else {
+            by_key
+        };
+        // Earlier runs hold earlier input lines, so ties keep input order.
+        by_key.then(self.run.cmp(&other.run))
+    }
+}
+
+impl PartialEq for RunHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.order(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RunHead {}
+
+impl PartialOrd for RunHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RunHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` is a max-heap, so the order is reversed to pop the smallest head.
+        other.order(self)
+    }
+}
+
+/// Sorts an NDJSON file by a key path using an external merge sort.
+///
+/// The input is read in chunks of `chunk_records` lines; each chunk is sorted
+/// in memory and spilled to a temporary run file, and the runs are then merged
+/// into the output. Memory use is therefore bounded by the chunk size rather
+/// than the file size. The sort is stable, records missing the key sort as
+/// `null`, and each record line is written back unchanged.
+///
+/// # Arguments
+///
+/// * `input` - The NDJSON file to sort.
+/// * `output` - The file the sorted records are written to.
+/// * `options` - The key path, order, and chunking options.
+///
+/// # Returns
+///
+/// * `Result<usize, Error>` - The number of records written, or an error if reading, parsing, or writing fails.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(options)))]
+pub fn sort_ndjson(input: &Path, output: &Path, options: &SortOptions) -> Result<usize, Error> {
+    let reader = BufReader::new(
+        File::open(input).with_context(|| format!("Failed to open {}", input.display()))?,
+    );
+    let chunk_size = options.chunk_records.max(1);
+    let mut runs = Vec::new();
+    let mut chunk = Vec::with_capacity(chunk_size);
+    let mut total = 0;
+
+    for (line_number, line) in reader.lines().enumerate() {
+        cancel::check()?;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid JSON on line {}", line_number + 1))?;
+        let key = get_by_path(&record, &options.key).unwrap_or(Value::Null);
+        chunk.push(KeyedLine { key, line });
+        total += 1;
+        if chunk.len() == chunk_size {
+            runs.push(spill_run(&mut chunk, options)?);
+        }
+    }
+
+    let mut writer = BufWriter::new(
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?,
+    );
+    if runs.is_empty() {
+        sort_chunk(&mut chunk, options.descending);
+        for record in &chunk {
+            writeln!(writer, "{}", record.line)?;
+        }
+    } else {
+        if !chunk.is_empty() {
+            runs.push(spill_run(&mut chunk, options)?);
+        }
+        merge_runs(runs, &mut writer, options)?;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Sorts a chunk in place, keeping equal keys in input order.
+fn sort_chunk(chunk: &mut [KeyedLine], descending: bool) {
+    chunk.sort_by(|a, b| {
+        let order = compare_values(&a.key, &b.key);
+        if descending {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+}
+
+/// Sorts a chunk and writes it to a temporary run file, leaving the chunk empty.
+fn spill_run(chunk: &mut Vec<KeyedLine>, options: &SortOptions) -> Result<File, Error> {
+    sort_chunk(chunk, options.descending);
+    let file = match &options.temp_dir {
+        Some(dir) => tempfile::tempfile_in(dir)?,
+        None => tempfile::tempfile()?,
+    };
+    let mut writer = BufWriter::new(file);
+    for record in chunk.drain(..) {
+        writeln!(writer, "{}", record.line)?;
+    }
+    let mut file = writer.into_inner().map_err(|e| Error::msg(e.to_string()))?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+/// Performs a k-way merge of sorted run files into the writer.
+fn merge_runs<W: Write>(
+    runs: Vec<File>,
+    writer: &mut W,
+    options: &SortOptions,
+) -> Result<(), Error> {
+    let mut readers: Vec<_> = runs
+        .into_iter()
+        .map(|file| BufReader::new(file).lines())
+        .collect();
+    let mut heap = BinaryHeap::new();
+
+    for (run, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = next_record(reader, &options.key)? {
+            heap.push(RunHead {
+                record,
+                run,
+                descending: options.descending,
+            });
+        }
+    }
+    while let Some(head) = heap.pop() {
+        cancel::check()?;
+        writeln!(writer, "{}", head.record.line)?;
+        if let Some(record) = next_record(&mut readers[head.run], &options.key)? {
+            heap.push(RunHead {
+                record,
+                run: head.run,
+                descending: options.descending,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Reads the next record of a run file and extracts its key.
+fn next_record<B: BufRead>(
+    lines: &mut std::io::Lines<B>,
+    key: &str,
+) -> Result<Option<KeyedLine>, Error> {
+    match lines.next() {
+        Some(line) => {
+            let line = line?;
+            let record: Value = serde_json::from_str(&line)?;
+            let key = get_by_path(&record, key).unwrap_or(Value::Null);
+            Ok(Some(KeyedLine { key, line }))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Returns the path of the sidecar index for an NDJSON file (`<file>.idx`).
+pub fn index_path(input: &Path) -> PathBuf {
+    let mut path = input.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// Builds a sparse sidecar index for an NDJSON file sorted by `key`.
+///
+/// Every `every`-th record contributes its key and byte offset to the index,
+/// which is written next to the input as `<file>.idx`. The index also records the
+/// length and modification time of the input, so that it is ignored once the input
+/// changes.
+///
+/// # Arguments
+///
+/// * `input` - The sorted NDJSON file.
+/// * `key` - The key path the file is sorted by.
+/// * `every` - Distance, in records, between index entries.
+///
+/// # Returns
+///
+/// * `Result<usize, Error>` - The number of index entries written, or an error if reading or writing fails.
+#[cfg_attr(feature = "tracing", tracing::instrument)]
+pub fn build_index(input: &Path, key: &str, every: usize) -> Result<usize, Error> {
+    let file = File::open(input).with_context(|| format!("Failed to open {}", input.display()))?;
+    let source = file_stamp(&file.metadata()?);
+    let mut reader = BufReader::new(file);
+    let every = every.max(1);
+    let mut entries = Vec::new();
+    let mut offset = 0u64;
+    let mut record_number = 0usize;
+    let mut line = String::new();
+
+    loop {
+        cancel::check()?;
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+        if !line.trim().is_empty() {
+            if record_number.is_multiple_of(every) {
+                let record: Value = serde_json::from_str(&line)
+                    .with_context(|| format!("Invalid JSON at byte offset {}", offset))?;
+                let value = get_by_path(&record, key).unwrap_or(Value::Null);
+                entries.push(serde_json::json!({ "key": value, "offset": offset }));
+            }
+            record_number += 1;
+        }
+        offset += read as u64;
+    }
+
+    let count = entries.len();
+    let index = serde_json::json!({
+        "key": key,
+        "every": every,
+        "source": source,
+        "entries": entries,
+    });
+    std::fs::write(index_path(input), serde_json::to_string(&index)?)?;
+    Ok(count)
+}
+
+/// Finds all records whose `key` equals `target` in an NDJSON file sorted
+/// ascending by that key, without scanning the whole file.
+///
+/// If a sidecar index built for the same key and the current contents of the
+/// file exists, it is binary-searched to find the starting offset; otherwise the
+/// file itself is bisected by byte offset. Either way only O(log n) positions are read before the matching
+/// records.
+///
+/// # Arguments
+///
+/// * `input` - The sorted NDJSON file.
+/// * `key` - The key path the file is sorted by.
+/// * `target` - The key value to look up.
+///
+/// # Returns
+///
+/// * `Result<Vec<Value>, Error>` - The matching records in file order, or an error if reading or parsing fails.
+#[cfg_attr(feature = "tracing", tracing::instrument)]
+pub fn lookup_sorted(input: &Path, key: &str, target: &Value) -> Result<Vec<Value>, Error> {
+    let mut file =
+        File::open(input).with_context(|| format!("Failed to open {}", input.display()))?;
+    let start = match read_index(input, key)? {
+        Some(entries) => {
+            let position =
+                entries.partition_point(|(k, _)| compare_values(k, target) == Ordering::Less);
+            if position == 0 {
+                0
+            } else {
+                entries[position - 1].1
+            }
+        }
+        None => bisect(&mut file, key, target)?,
+    };
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut matches = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = serde_json::from_str(&line)?;
+        let value = get_by_path(&record, key).unwrap_or(Value::Null);
+        match compare_values(&value, target) {
+            Ordering::Less => continue,
+            Ordering::Equal => matches.push(record),
+            Ordering::Greater => break,
+        }
+    }
+    Ok(matches)
+}
+
+/// Loads the sidecar index entries if an index for `key` exists and was built for the
+/// file as it is now.
+fn read_index(input: &Path, key: &str) -> Result<Option<Vec<(Value, u64)>>, Error> {
+    let path = index_path(input);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let index: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    if index["key"] != key {
+        return Ok(None);
+    }
+    if index["source"] != file_stamp(&std::fs::metadata(input)?) {
+        warnings::warn(
+            "",
+            format!(
+                "Ignoring stale index {}; rebuild it with `index`",
+                path.display()
+            ),
+        );
+        return Ok(None);
+    }
+    let entries = index["entries"]
+        .as_array()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| Some((entry["key"].clone(), entry["offset"].as_u64()?)))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Some(entries))
+}
+
+/// Returns the length and modification time of a file, as stored in its index.
+fn file_stamp(metadata: &std::fs::Metadata) -> Value {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_nanos() as u64);
+    json!({ "length": metadata.len(), "modified_ns": modified })
+}
+
+/// Bisects the file by byte offset for the first line whose key is not less than `target`.
+fn bisect(file: &mut File, key: &str, target: &Value) -> Result<u64, Error> {
+    let length = file.metadata()?.len();
+    let (mut low, mut high) = (0u64, length);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let start = next_line_start(file, middle)?;
+        let reached = match read_key_at(file, start, key)? {
+            Some(value) => compare_values(&value, target) != Ordering::Less,
+            None => true,
+        };
+        if reached {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    next_line_start(file, low)
+}
+
+/// Returns the offset of the first line starting at or after `offset`.
+fn next_line_start(file: &mut File, offset: u64) -> Result<u64, Error> {
+    if offset == 0 {
+        return Ok(0);
+    }
+    file.seek(SeekFrom::Start(offset - 1))?;
+    let mut skipped = Vec::new();
+    let read = BufReader::new(&mut *file).read_until(b'\n', &mut skipped)?;
+    Ok(offset - 1 + read as u64)
+}
+
+/// Reads the key of the record starting at `offset`, or `None` at end of file.
+fn read_key_at(file: &mut File, offset: u64, key: &str) -> Result<Option<Value>, Error> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut line = String::new();
+    if BufReader::new(&mut *file).read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let record: Value = serde_json::from_str(&line)?;
+    Ok(Some(get_by_path(&record, key).unwrap_or(Value::Null)))
+}
+
+/// Follows a growing NDJSON file, like `tail -f`, yielding appended records.
+///
+/// Incomplete trailing lines are buffered until their newline arrives, and the
+/// file is re-read from the beginning if it is truncated (e.g. on log rotation).
+#[derive(Debug, Clone)]
+pub struct NdjsonTail {
+    path: PathBuf,
+    offset: u64,
+    partial: Vec<u8>,
+}
+
+impl NdjsonTail {
+    /// Starts following a file, either from its beginning or from its current end.
+    pub fn new(path: &Path, from_start: bool) -> Result<Self, Error> {
+        let offset = if from_start {
+            0
+        } else {
+            std::fs::metadata(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?
+                .len()
+        };
+        Ok(NdjsonTail {
+            path: path.to_path_buf(),
+            offset,
+            partial: Vec::new(),
+        })
+    }
+
+    /// Returns the records completed since the previous poll.
+    /// Lines that are not valid JSON are logged and skipped.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn poll(&mut self) -> Result<Vec<Value>, Error> {
+        let length = std::fs::metadata(&self.path)?.len();
+        if length < self.offset {
+            warnings::warn(
+                "",
+                format!(
+                    "{} was truncated, reading from the start",
+                    self.path.display()
+                ),
+            );
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if length == self.offset {
+            return Ok(Vec::new());
+        }
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let read = file.read_to_end(&mut self.partial)?;
+        self.offset += read as u64;
+
+        let complete = match self.partial.iter().rposition(|&b| b == b'\n') {
+            Some(position) => position + 1,
+            None => return Ok(Vec::new()),
+        };
+        let lines: Vec<u8> = self.partial.drain(..complete).collect();
+        let mut records = Vec::new();
+        for line in String::from_utf8_lossy(&lines).lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(record) => records.push(record),
+                Err(e) => warnings::warn("", format!("Skipping invalid NDJSON line: {}", e)),
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// A routing rule: records for which `when` is true are written to `output`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RouteRule {
+    /// Expression evaluated against each record, e.g. `level == 'error'` (see `Expression`).
+    pub when: String,
+    /// Name of the output the matching records are written to.
+    pub output: String,
+}
+
+/// Configuration of `route_ndjson`.
+///
+/// Every rule is checked for every record, so one record can go to several outputs.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RouteConfig {
+    /// Output name to NDJSON file path.
+    pub outputs: BTreeMap<String, String>,
+    pub rules: Vec<RouteRule>,
+    /// Output for records that match no rule; they are dropped if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// Output for lines that are not valid JSON, written as
+    /// `{"line": 3, "error": "...", "text": "..."}`; they are skipped if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantine: Option<String>,
+    /// Limits for evaluating the rules against one record, e.g. the config file's
+    /// `sandbox`; records exceeding them are handled like invalid lines.
+    #[serde(skip)]
+    pub sandbox: Option<Sandbox>,
+}
+
+impl RouteConfig {
+    /// Parses a routing config, checking that rules only name declared outputs.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The config as JSON, e.g.
+    ///   `{ "outputs": { "errors": "errors.ndjson" }, "rules": [{ "when": "level == 'error'", "output": "errors" }] }`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RouteConfig, Error>` - The config, or an error if it is malformed.
+    pub fn from_json(config: &Value) -> Result<RouteConfig, Error> {
+        let config: RouteConfig = serde_json::from_value(config.clone())
+            .map_err(|e| Error::msg(format!("Invalid routing config: {}", e)))?;
+        let names = config.rules.iter().map(|rule| &rule.output);
+        for name in names.chain(&config.default).chain(&config.quarantine) {
+            if !config.outputs.contains_key(name) {
+                return Err(Error::msg(format!(
+                    "Unknown output '{}' in routing config",
+                    name
+                )));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Outcome of routing a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteReport {
+    pub total: usize,
+    /// Records written to each output.
+    pub routed: BTreeMap<String, usize>,
+    /// Records that matched no rule and had no default output.
+    pub dropped: usize,
+    /// Lines that were not valid JSON.
+    pub invalid: usize,
+    /// Whether a `CancellationToken` stopped the run; the counts cover the records read
+    /// until then, and the outputs hold their routed records.
+    pub cancelled: bool,
+}
+
+impl RouteReport {
+    /// Converts the report to JSON, with `"cancelled": true` if the run was cancelled.
+    pub fn to_json(&self) -> Value {
+        let mut report = json!({
+            "total": self.total,
+            "routed": self.routed,
+            "dropped": self.dropped,
+            "invalid": self.invalid,
+        });
+        if self.cancelled {
+            report["cancelled"] = Value::Bool(true);
+        }
+        report
+    }
+}
+
+/// Routes the records of an NDJSON file to several NDJSON outputs in one pass.
+///
+/// Each record is written, unchanged, to the output of every rule it matches, or to
+/// the default output if it matches none. Invalid lines go to the quarantine output.
+///
+/// # Arguments
+///
+/// * `input` - The NDJSON file to route.
+/// * `config` - The outputs and rules.
+///
+/// # Returns
+///
+/// * `Result<RouteReport, Error>` - Per-output counts, or an error if an expression
+///   is invalid or a file cannot be read or written.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(config)))]
+pub fn route_ndjson(input: &Path, config: &RouteConfig) -> Result<RouteReport, Error> {
+    let rules = config
+        .rules
+        .iter()
+        .map(|rule| Ok((Expression::compile(&rule.when)?, rule.output.as_str())))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut writers = BTreeMap::new();
+    for (name, path) in &config.outputs {
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path))?;
+        writers.insert(name.as_str(), BufWriter::new(file));
+    }
+
+    let mut report = RouteReport::default();
+    let reader = BufReader::new(
+        File::open(input).with_context(|| format!("Failed to open {}", input.display()))?,
+    );
+    for (number, line) in reader.lines().enumerate() {
+        if cancel::is_cancelled() {
+            report.cancelled = true;
+            break;
+        }
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        report.total += 1;
+        let mut targets: Vec<&str> = Vec::new();
+        let mut text = line.clone();
+        let matched = serde_json::from_str::<Value>(&line)
+            .map_err(Error::from)
+            .and_then(|record| {
+                sandbox::run_in(config.sandbox.as_ref(), || {
+                    rules
+                        .iter()
+                        .filter(|(expression, _)| {
+                            !matches!(
+                                expression.evaluate(&record),
+                                None | Some(Value::Null | Value::Bool(false))
+                            )
+                        })
+                        .map(|(_, output)| *output)
+                        .collect::<Vec<&str>>()
+                })
+            });
+        match matched {
+            Ok(outputs) => {
+                for output in outputs {
+                    if !targets.contains(&output) {
+                        targets.push(output);
+                    }
+                }
+                if targets.is_empty() {
+                    match &config.default {
+                        Some(output) => targets.push(output),
+                        None => report.dropped += 1,
+                    }
+                }
+            }
+            Err(e) => {
+                report.invalid += 1;
+                if let Some(output) = &config.quarantine {
+                    targets.push(output);
+                    text = json!({ "line": number + 1, "error": e.to_string(), "text": line })
+                        .to_string();
+                }
+            }
+        }
+        for output in targets {
+            writeln!(writers.get_mut(output).unwrap(), "{}", text)?;
+            *report.routed.entry(output.to_string()).or_insert(0) += 1;
+        }
+    }
+    for writer in writers.values_mut() {
+        writer.flush()?;
+    }
+    Ok(report)
+}
+
+/// Options for `dedup_ndjson`.
+#[derive(Debug, Clone)]
+pub struct DedupOptions {
+    /// Path of the field identifying a record (see `get_by_path`), e.g. `id`; whole
+    /// records are compared if unset.
+    pub key: Option<String>,
+    /// Bloom filter file remembering the records of earlier runs. It is created if
+    /// missing and saved after the run, so rerunning on the same input writes nothing.
+    /// A path ending in `.json` keeps the state as JSON (see `BloomFilter::to_json`).
+    pub state: Option<PathBuf>,
+    /// Number of distinct records a new filter is sized for.
+    pub capacity: usize,
+    /// Chance that a new record is taken for a duplicate and skipped, at full capacity.
+    pub false_positive_rate: f64,
+}
+
+impl Default for DedupOptions {
+    fn default() -> Self {
+        DedupOptions {
+            key: None,
+            state: None,
+            capacity: 1_000_000,
+            false_positive_rate: 0.001,
+        }
+    }
+}
+
+/// Outcome of deduplicating a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DedupReport {
+    pub total: usize,
+    /// Records written to the output.
+    pub written: usize,
+    /// Records skipped because they were seen before, in this run or an earlier one.
+    pub duplicates: usize,
+    /// Records without the key field; they are always written.
+    pub missing_key: usize,
+    /// Lines that were not valid JSON; they are skipped.
+    pub invalid: usize,
+    /// Whether a `CancellationToken` stopped the run; the output and the saved state
+    /// then cover the records read until then, so rerunning on the same input resumes.
+    pub cancelled: bool,
+}
+
+impl DedupReport {
+    /// Converts the report to JSON, with `"cancelled": true` if the run was cancelled.
+    pub fn to_json(&self) -> Value {
+        let mut report = json!({
+            "total": self.total,
+            "written": self.written,
+            "duplicates": self.duplicates,
+            "missing_key": self.missing_key,
+            "invalid": self.invalid,
+        });
+        if self.cancelled {
+            report["cancelled"] = Value::Bool(true);
+        }
+        report
+    }
+}
+
+/// Copies the records of an NDJSON file that have not been seen before, for
+/// idempotent incremental ingestion.
+///
+/// Records are identified by their key field or, without a key, by their whole
+/// structure (key order and number formatting do not matter). Seen records are
+/// remembered in a `BloomFilter`, which is kept in `options.state` across runs.
+///
+/// # Arguments
+///
+/// * `input` - The NDJSON file to read.
+/// * `output` - The NDJSON file receiving the new records.
+/// * `options` - The key and the state file.
+///
+/// # Returns
+///
+/// * `Result<DedupReport, Error>` - The counts, or an error if a file cannot be read or
+///   written. The state is only saved if the run succeeds.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(options)))]
+pub fn dedup_ndjson(
+    input: &Path,
+    output: &Path,
+    options: &DedupOptions,
+) -> Result<DedupReport, Error> {
+    let mut seen = match &options.state {
+        Some(state) if state.exists() => BloomFilter::load(state)?,
+        _ => BloomFilter::new(options.capacity, options.false_positive_rate),
+    };
+    let reader = BufReader::new(
+        File::open(input).with_context(|| format!("Failed to open {}", input.display()))?,
+    );
+    let mut writer = BufWriter::new(
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?,
+    );
+    let mut report = DedupReport::default();
+    for line in reader.lines() {
+        if cancel::is_cancelled() {
+            report.cancelled = true;
+            break;
+        }
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        report.total += 1;
+        let record: Value = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(e) => {
+                warnings::warn("", format!("Skipping invalid NDJSON line: {}", e));
+                report.invalid += 1;
+                continue;
+            }
+        };
+        let identity = match &options.key {
+            Some(key) => get_by_path(&record, key),
+            None => Some(record),
+        };
+        match identity {
+            Some(identity) => {
+                if seen.insert(canonicalize(&identity)?.as_bytes()) {
+                    report.duplicates += 1;
+                    continue;
+                }
+            }
+            None => report.missing_key += 1,
+        }
+        writeln!(writer, "{}", line)?;
+        report.written += 1;
+    }
+    writer.flush()?;
+    if seen.len() > seen.capacity() {
+        warnings::warn(
+            "",
+            format!(
+                "Deduplication state holds {} records but was sized for {}; more new records will be skipped as duplicates",
+                seen.len(),
+                seen.capacity()
+            ),
+        );
+    }
+    if let Some(state) = &options.state {
+        seen.save(state)?;
+    }
+    Ok(report)
+}
+
+/// Options for `sample_ndjson`.
+#[derive(Debug, Clone)]
+pub struct SampleOptions {
+    /// Number of records to keep.
+    pub size: usize,
+    /// Seed of the random choice; the same seed and input give the same sample.
+    pub seed: u64,
+}
+
+/// Copies a uniform random sample of the records of an NDJSON file, e.g. to build a
+/// test fixture from a production export.
+///
+/// The sample is drawn in one pass with reservoir sampling, so only `options.size`
+/// lines are held in memory, and written in input order. Lines are copied as they are;
+/// blank lines are skipped.
+///
+/// # Arguments
+///
+/// * `input` - The NDJSON file to read.
+/// * `output` - The NDJSON file receiving the sample.
+/// * `options` - The sample size and the seed.
+///
+/// # Returns
+///
+/// * `Result<usize, Error>` - The number of records read, or an error if a file cannot
+///   be read or written. All of them are written if there are at most `options.size`.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(options)))]
+pub fn sample_ndjson(input: &Path, output: &Path, options: &SampleOptions) -> Result<usize, Error> {
+    let reader = BufReader::new(
+        File::open(input).with_context(|| format!("Failed to open {}", input.display()))?,
+    );
+    let mut rng = SeededRng::new(options.seed);
+    let mut reservoir: Vec<(usize, String)> = Vec::with_capacity(options.size.min(100_000));
+    let mut total = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if reservoir.len() < options.size {
+            reservoir.push((total, line));
+        } else {
+            let slot = rng.below(total as u64 + 1) as usize;
+            if slot < options.size {
+                reservoir[slot] = (total, line);
+            }
+        }
+        total += 1;
+    }
+    reservoir.sort_unstable_by_key(|(position, _)| *position);
+    let mut writer = BufWriter::new(
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?,
+    );
+    for (_, line) in &reservoir {
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Options for `aggregate_windows`.
+#[derive(Debug, Clone)]
+pub struct WindowOptions {
+    /// Key path of the event time: epoch seconds or an RFC 3339 string.
+    pub timestamp: String,
+    /// Window length in seconds.
+    pub size: u64,
+    /// Seconds between window starts; `None` for tumbling windows (`size`).
+    pub slide: Option<u64>,
+    /// Key path whose value splits each window into groups.
+    pub group_by: Option<String>,
+    /// Key path of the numeric value summed and used for percentiles.
+    pub value: Option<String>,
+    /// Percentiles of `value` reported per window, e.g. `[50.0, 90.0, 99.0]`.
+    pub percentiles: Vec<f64>,
+    /// Estimates percentiles with a `TDigest` of this compression instead of keeping
+    /// every value of a window in memory.
+    pub compression: Option<f64>,
+    /// Decimal places the reported figures are rounded to.
+    pub precision: Option<u32>,
+    /// JSON checkpoint of the windows still open at the end of the input. If set, they
+    /// are read back before the input and saved after it instead of being written, so
+    /// the next input continues them, on this machine or another one.
+    pub state: Option<PathBuf>,
+    /// Writes the windows still open at the end even if `state` is set, e.g. after
+    /// the last input of a stream.
+    pub flush: bool,
+}
+
+impl WindowOptions {
+    /// Creates tumbling window options counting records per window.
+    pub fn new(timestamp: &str, size: u64) -> Self {
+        WindowOptions {
+            timestamp: timestamp.to_string(),
+            size,
+            slide: None,
+            group_by: None,
+            value: None,
+            percentiles: Vec::new(),
+            compression: None,
+            precision: None,
+            state: None,
+            flush: false,
+        }
+    }
+
+    /// Returns the options that must not change between checkpointed runs.
+    fn checkpoint_key(&self) -> Value {
+        json!({
+            "timestamp": self.timestamp,
+            "size": self.size,
+            "slide": self.slide,
+            "group_by": self.group_by,
+            "value": self.value,
+            "compression": self.compression,
+        })
+    }
+}
+
+/// Count and numeric summary of the records in one window or group.
+#[derive(Debug, Clone, Default)]
+struct WindowStats {
+    count: u64,
+    sum: f64,
+    moments: RunningMoments,
+    min: Option<f64>,
+    max: Option<f64>,
+    /// Every value, for exact percentiles when no digest is used.
+    values: Vec<f64>,
+    digest: Option<TDigest>,
+}
+
+impl WindowStats {
+    fn new(options: &WindowOptions) -> Self {
+        WindowStats {
+            digest: options.compression.map(TDigest::new),
+            ..WindowStats::default()
+        }
+    }
+
+    fn observe(&mut self, value: Option<f64>) {
+        self.count += 1;
+        let Some(value) = value else { return };
+        self.sum += value;
+        self.moments.push(value);
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+        match &mut self.digest {
+            Some(digest) => digest.insert(value),
+            None => self.values.push(value),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "count": self.count,
+            "sum": self.sum,
+            "moments": self.moments.to_json(),
+            "min": self.min,
+            "max": self.max,
+            "values": self.values,
+            "digest": self.digest.as_ref().map(TDigest::to_json),
+        })
+    }
+
+    fn from_json(json: &Value) -> Result<Self, Error> {
+        let invalid = || Error::msg("Invalid window state");
+        Ok(WindowStats {
+            count: json["count"].as_u64().ok_or_else(invalid)?,
+            sum: json["sum"].as_f64().ok_or_else(invalid)?,
+            moments: RunningMoments::from_json(&json["moments"]).ok_or_else(invalid)?,
+            min: json["min"].as_f64(),
+            max: json["max"].as_f64(),
+            values: serde_json::from_value(json["values"].clone()).map_err(|_| invalid())?,
+            digest: match &json["digest"] {
+                Value::Null => None,
+                digest => Some(TDigest::from_json(digest)?),
+            },
+        })
+    }
+
+    /// Summarizes the window. Exact percentiles use the nearest-rank method.
+    fn summarize(&mut self, options: &WindowOptions) -> Value {
+        let mut summary = json!({ "count": self.count });
+        if options.value.is_none() {
+            return summary;
+        }
+        let round = |value: Option<f64>| {
+            value.map(|value| match options.precision {
+                Some(digits) => {
+                    let scale = 10f64.powi(digits as i32);
+                    (value * scale).round() / scale
+                }
+                None => value,
+            })
+        };
+        let observed = self.min.is_some();
+        summary["sum"] = json!(round(Some(self.sum)));
+        summary["min"] = json!(round(self.min));
+        summary["max"] = json!(round(self.max));
+        summary["mean"] = json!(round(observed.then(|| self.moments.mean())));
+        summary["stddev"] = json!(round(observed.then(|| self.moments.stddev())));
+        self.values.sort_by(|a, b| a.total_cmp(b));
+        for percentile in &options.percentiles {
+            let value = match &self.digest {
+                Some(digest) => digest.quantile(percentile / 100.0),
+                None => {
+                    let rank = (percentile / 100.0 * self.values.len() as f64).ceil() as usize;
+                    self.values.get(rank.saturating_sub(1)).copied()
+                }
+            };
+            summary[format!("p{}", percentile)] = json!(round(value));
+        }
+        summary
+    }
+}
+
+/// A window that may still receive records.
+#[derive(Debug, Clone)]
+struct OpenWindow {
+    total: WindowStats,
+    groups: BTreeMap<String, WindowStats>,
+}
+
+/// Windows still open after an input, with what is needed to continue them.
+struct WindowCheckpoint {
+    windows: BTreeMap<i64, OpenWindow>,
+    watermark: i64,
+    rfc3339: Option<bool>,
+}
+
+impl WindowCheckpoint {
+    fn to_json(&self, options: &WindowOptions) -> Value {
+        let windows: Vec<Value> = self
+            .windows
+            .iter()
+            .map(|(start, window)| {
+                let groups: serde_json::Map<String, Value> = window
+                    .groups
+                    .iter()
+                    .map(|(key, stats)| (key.clone(), stats.to_json()))
+                    .collect();
+                json!({ "start": start, "total": window.total.to_json(), "groups": groups })
+            })
+            .collect();
+        json!({
+            "options": options.checkpoint_key(),
+            "watermark": (self.watermark != i64::MIN).then_some(self.watermark),
+            "rfc3339": self.rfc3339,
+            "windows": windows,
+        })
+    }
+
+    fn from_json(json: &Value, options: &WindowOptions) -> Result<Self, Error> {
+        if json["options"] != options.checkpoint_key() {
+            return Err(Error::msg(format!(
+                "Window state was saved with different options: {}",
+                json["options"]
+            )));
+        }
+        let invalid = || Error::msg("Invalid window state");
+        let mut windows = BTreeMap::new();
+        for window in json["windows"].as_array().ok_or_else(invalid)? {
+            let groups = window["groups"]
+                .as_object()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|(key, stats)| Ok((key.clone(), WindowStats::from_json(stats)?)))
+                .collect::<Result<_, Error>>()?;
+            windows.insert(
+                window["start"].as_i64().ok_or_else(invalid)?,
+                OpenWindow {
+                    total: WindowStats::from_json(&window["total"])?,
+                    groups,
+                },
+            );
+        }
+        Ok(WindowCheckpoint {
+            windows,
+            watermark: json["watermark"].as_i64().unwrap_or(i64::MIN),
+            rfc3339: json["rfc3339"].as_bool(),
+        })
+    }
+}
+
+/// Aggregates a timestamped NDJSON stream into tumbling or sliding windows.
+///
+/// Windows are aligned to multiples of the slide since the epoch, and each one is
+/// written as a summary record once a later record passes its end, e.g.
+/// `{"start": "2024-01-01T00:00:00Z", "end": "2024-01-01T00:01:00Z", "count": 42, "sum": 8.1, "min": 0.01, "max": 1.2, "mean": 0.19, "stddev": 0.2, "p95": 0.9, "groups": {...}}`.
+/// `start` and `end` are RFC 3339 strings if the timestamps are strings, otherwise
+/// epoch seconds. Records without a valid timestamp, and late records whose windows
+/// were already written, are skipped with a warning.
+///
+/// With `options.state`, a long stream can be processed one input at a time: the
+/// windows still open at the end are checkpointed as JSON and continued by the next
+/// run, which may be on another machine.
+///
+/// # Arguments
+///
+/// * `input` - The NDJSON file, roughly ordered by time.
+/// * `output` - The NDJSON file the window summaries are written to.
+/// * `options` - The timestamp path, window size and slide, grouping, and metrics.
+///
+/// # Returns
+///
+/// * `Result<usize, Error>` - The number of windows written, or an error if the
+///   options are invalid or a file cannot be read or written.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(options)))]
+pub fn aggregate_windows(
+    input: &Path,
+    output: &Path,
+    options: &WindowOptions,
+) -> Result<usize, Error> {
+    let size = options.size as i64;
+    let slide = options.slide.unwrap_or(options.size) as i64;
+    if size <= 0 || slide <= 0 {
+        return Err(Error::msg("Window size and slide must be positive"));
+    }
+    if size > MAX_TIMESTAMP - MIN_TIMESTAMP || slide > MAX_TIMESTAMP - MIN_TIMESTAMP {
+        return Err(Error::msg(
+            "Window size and slide must not exceed the years 0000 to 9999",
+        ));
+    }
+    if let Some(p) = options
+        .percentiles
+        .iter()
+        .find(|p| !(0.0..=100.0).contains(*p))
+    {
+        return Err(Error::msg(format!(
+            "Percentile {} is not between 0 and 100",
+            p
+        )));
+    }
+
+    let reader = BufReader::new(
+        File::open(input).with_context(|| format!("Failed to open {}", input.display()))?,
+    );
+    let mut writer = BufWriter::new(
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?,
+    );
+    let checkpoint = match &options.state {
+        Some(state) if state.exists() => {
+            let text = std::fs::read_to_string(state)
+                .with_context(|| format!("Failed to read {}", state.display()))?;
+            let json: Value = serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse {}", state.display()))?;
+            WindowCheckpoint::from_json(&json, options)?
+        }
+        _ => WindowCheckpoint {
+            windows: BTreeMap::new(),
+            watermark: i64::MIN,
+            rfc3339: None,
+        },
+    };
+    let mut windows = checkpoint.windows;
+    let mut rfc3339 = checkpoint.rfc3339;
+    // Latest second seen; windows ending at or before it are closed.
+    let mut watermark = checkpoint.watermark;
+    let mut written = 0;
+    for line in reader.lines() {
+        cancel::check()?;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(e) => {
+                warnings::warn("", format!("Skipping invalid NDJSON line: {}", e));
+                continue;
+            }
+        };
+        let timestamp = get_by_path(&record, &options.timestamp);
+        let Some(time) = timestamp.as_ref().and_then(parse_timestamp) else {
+            warnings::warn(
+                "",
+                format!(
+                    "Skipping record without a valid '{}' timestamp",
+                    options.timestamp
+                ),
+            );
+            continue;
+        };
+        let second = time.floor();
+        if !(MIN_TIMESTAMP as f64..=MAX_TIMESTAMP as f64).contains(&second) {
+            warnings::warn(
+                "",
+                format!(
+                    "Skipping record with out-of-range '{}' timestamp {}",
+                    options.timestamp, time
+                ),
+            );
+            continue;
+        }
+        let second = second as i64;
+        rfc3339.get_or_insert(matches!(timestamp, Some(Value::String(_))));
+
+        // Close every window that ends at or before this record.
+        watermark = watermark.max(second);
+        while let Some(entry) = windows.first_entry() {
+            if entry.key().saturating_add(size) > watermark {
+                break;
+            }
+            let (start, window) = entry.remove_entry();
+            write_window(
+                &mut writer,
+                start,
+                window,
+                size,
+                rfc3339 == Some(true),
+                options,
+            )?;
+            written += 1;
+        }
+
+        let group = options
+            .group_by
+            .as_ref()
+            .map(|path| match get_by_path(&record, path) {
+                Some(Value::String(s)) => s.clone(),
+                Some(value) => value.to_string(),
+                None => "null".to_string(),
+            });
+        let value = options
+            .value
+            .as_ref()
+            .and_then(|path| get_by_path(&record, path))
+            .and_then(|value| value.as_f64());
+        let mut start = second.div_euclid(slide) * slide;
+        let mut late = false;
+        while start > second - size {
+            if start + size <= watermark {
+                late = true;
+            } else {
+                let window = windows.entry(start).or_insert_with(|| OpenWindow {
+                    total: WindowStats::new(options),
+                    groups: BTreeMap::new(),
+                });
+                window.total.observe(value);
+                if let Some(group) = &group {
+                    window
+                        .groups
+                        .entry(group.clone())
+                        .or_insert_with(|| WindowStats::new(options))
+                        .observe(value);
+                }
+            }
+            start -= slide;
+        }
+        if late {
+            warnings::warn(
+                "",
+                format!(
+                    "Skipping late record at {} for windows already written",
+                    second
+                ),
+            );
+        }
+    }
+    if options.state.is_none() || options.flush {
+        for (start, window) in std::mem::take(&mut windows) {
+            write_window(
+                &mut writer,
+                start,
+                window,
+                size,
+                rfc3339 == Some(true),
+                options,
+            )?;
+            written += 1;
+        }
+    }
+    writer.flush()?;
+    if let Some(state) = &options.state {
+        let checkpoint = WindowCheckpoint {
+            windows,
+            watermark,
+            rfc3339,
+        };
+        write_replacing(state, checkpoint.to_json(options).to_string().as_bytes())
+            .with_context(|| format!("Failed to write {}", state.display()))?;
+    }
+    Ok(written)
+}
+
+/// Earliest timestamp `aggregate_windows` accepts, 0000-01-01T00:00:00Z in epoch seconds.
+const MIN_TIMESTAMP: i64 = -62_167_219_200;
+
+/// Latest timestamp `aggregate_windows` accepts, 9999-12-31T23:59:59Z in epoch seconds.
+/// Together with the bound on the window size, this keeps window arithmetic far from
+/// the limits of `i64`.
+const MAX_TIMESTAMP: i64 = 253_402_300_799;
+
+/// Writes the summary record of a closed window.
+fn write_window<W: Write>(
+    writer: &mut W,
+    start: i64,
+    mut window: OpenWindow,
+    size: i64,
+    rfc3339: bool,
+    options: &WindowOptions,
+) -> Result<(), Error> {
+    let bound = |seconds: i64| {
+        if rfc3339 {
+            json!(format_timestamp(seconds))
+        } else {
+            json!(seconds)
+        }
+    };
+    let mut summary = window.total.summarize(options);
+    summary["start"] = bound(start);
+    summary["end"] = bound(start.saturating_add(size));
+    if options.group_by.is_some() {
+        let groups: serde_json::Map<String, Value> = window
+            .groups
+            .iter_mut()
+            .map(|(key, stats)| (key.clone(), stats.summarize(options)))
+            .collect();
+        summary["groups"] = Value::Object(groups);
+    }
+    writeln!(writer, "{}", summary)?;
+    Ok(())
+}
+
+/// Reads a timestamp as epoch seconds: a number, or an RFC 3339 string such as
+/// `2024-01-01T12:00:00.5+02:00`.
+fn parse_timestamp(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(text) => parse_date(text, None),
+        _ => None,
+    }
+}
+
+/// Formats epoch seconds as an RFC 3339 UTC timestamp.
+fn format_timestamp(seconds: i64) -> String {
+    format_date(seconds as f64, None, 0)
+}
@@ -0,0 +1,440 @@
+use crate::flatten::flatten;
+use crate::table::NestedCells;
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+
+/// File magic at the start and end of every Parquet file.
+const MAGIC: &[u8] = b"PAR1";
+
+/// Physical types, encodings and other enum values of the Parquet format.
+const TYPE_BOOLEAN: i32 = 0;
+const TYPE_INT64: i32 = 2;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+const REPETITION_OPTIONAL: i32 = 1;
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_JSON: i32 = 19;
+const LOGICAL_STRING: i16 = 1;
+const LOGICAL_JSON: i16 = 12;
+
+/// Field types of the Thrift compact protocol.
+const THRIFT_I32: u8 = 5;
+const THRIFT_I64: u8 = 6;
+const THRIFT_BINARY: u8 = 8;
+const THRIFT_LIST: u8 = 9;
+const THRIFT_STRUCT: u8 = 12;
+
+/// Options for `json_to_parquet`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParquetOptions {
+    /// Whether nested objects and arrays become columns such as `address.city` or
+    /// JSON text in the column of their top-level key.
+    pub nested: NestedCells,
+    /// Maximum number of rows per row group.
+    pub row_group_size: usize,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        ParquetOptions {
+            nested: NestedCells::Flatten,
+            row_group_size: 100_000,
+        }
+    }
+}
+
+/// The type of a column, inferred from its non-null values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnType {
+    Boolean,
+    Int64,
+    Double,
+    /// UTF-8 text; values of other types are written as compact JSON.
+    String,
+    /// Objects and arrays written as JSON text.
+    Json,
+}
+
+impl ColumnType {
+    /// Returns the type that holds values of both types.
+    fn widen(self, other: ColumnType) -> ColumnType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Int64, ColumnType::Double) | (ColumnType::Double, ColumnType::Int64) => {
+                ColumnType::Double
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    fn of(value: &Value) -> Option<ColumnType> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some(ColumnType::Boolean),
+            Value::Number(number) if number.is_i64() => Some(ColumnType::Int64),
+            Value::Number(_) => Some(ColumnType::Double),
+            Value::String(_) => Some(ColumnType::String),
+            Value::Array(_) | Value::Object(_) => Some(ColumnType::Json),
+        }
+    }
+
+    fn physical(self) -> i32 {
+        match self {
+            ColumnType::Boolean => TYPE_BOOLEAN,
+            ColumnType::Int64 => TYPE_INT64,
+            ColumnType::Double => TYPE_DOUBLE,
+            ColumnType::String | ColumnType::Json => TYPE_BYTE_ARRAY,
+        }
+    }
+}
+
+/// Converts an array of records to a Parquet file with one optional column per field,
+/// so columnar exports need no separate tooling.
+///
+/// Columns appear in order of first appearance, as in `json_to_csv`. Their types are
+/// inferred from the data: booleans, 64-bit integers, doubles (for any other numbers,
+/// or integers mixed with them), UTF-8 strings, or JSON text for objects and arrays;
+/// columns with mixed types become strings, with non-strings written as JSON. Missing
+/// fields and `null` are nulls. Pages are PLAIN-encoded and uncompressed.
+///
+/// # Arguments
+///
+/// * `json` - The array of objects, e.g. records from an API.
+/// * `options` - How nested values are written, and the size of the row groups.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, Error>` - The Parquet file, or an error if `json` is not an array
+///   of objects.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn json_to_parquet(json: &Value, options: &ParquetOptions) -> Result<Vec<u8>, Error> {
+    let items = json
+        .as_array()
+        .ok_or_else(|| Error::msg("Only an array of objects can be converted to Parquet"))?;
+    let mut columns: Vec<(String, Option<ColumnType>)> = Vec::new();
+    let mut records: Vec<Map<String, Value>> = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        let record = match (item, options.nested) {
+            (Value::Object(_), NestedCells::Flatten) => match flatten(item, ".") {
+                Value::Object(record) => record,
+                _ => unreachable!("flatten returns an object"),
+            },
+            (Value::Object(record), NestedCells::Inline) => record.clone(),
+            _ => {
+                return Err(Error::msg(format!(
+                    "Element {} is not an object and cannot be a Parquet row",
+                    index
+                )))
+            }
+        };
+        for (name, value) in &record {
+            let kind = ColumnType::of(value);
+            match columns.iter_mut().find(|(column, _)| column == name) {
+                Some((_, existing)) => {
+                    *existing = match (*existing, kind) {
+                        (Some(a), Some(b)) => Some(a.widen(b)),
+                        (a, b) => a.or(b),
+                    }
+                }
+                None => columns.push((name.clone(), kind)),
+            }
+        }
+        records.push(record);
+    }
+    let columns: Vec<(String, ColumnType)> = columns
+        .into_iter()
+        .map(|(name, kind)| (name, kind.unwrap_or(ColumnType::String)))
+        .collect();
+
+    let mut file = MAGIC.to_vec();
+    let mut row_groups = Vec::new();
+    for chunk in records.chunks(options.row_group_size.max(1)) {
+        let mut chunks = Vec::with_capacity(columns.len());
+        for (name, kind) in &columns {
+            let values: Vec<Option<&Value>> = chunk
+                .iter()
+                .map(|record| record.get(name).filter(|value| !value.is_null()))
+                .collect();
+            let page = encode_page(&values, *kind);
+            let offset = file.len() as i64;
+            file.extend_from_slice(&page);
+            chunks.push(ColumnChunk {
+                name: name.clone(),
+                kind: *kind,
+                offset,
+                size: page.len() as i64,
+                num_values: values.len() as i64,
+            });
+        }
+        row_groups.push((chunks, chunk.len() as i64));
+    }
+    let footer_start = file.len();
+    file.extend_from_slice(&file_metadata(&columns, &row_groups, records.len() as i64));
+    let footer_length = (file.len() - footer_start) as u32;
+    file.extend_from_slice(&footer_length.to_le_bytes());
+    file.extend_from_slice(MAGIC);
+    Ok(file)
+}
+
+/// A column chunk written to the file, described again in the footer.
+struct ColumnChunk {
+    name: String,
+    kind: ColumnType,
+    offset: i64,
+    size: i64,
+    num_values: i64,
+}
+
+/// Encodes the values of a column chunk as one data page with its header.
+fn encode_page(values: &[Option<&Value>], kind: ColumnType) -> Vec<u8> {
+    let mut levels = Vec::new();
+    let mut run: Option<(bool, usize)> = None;
+    for defined in values.iter().map(Option::is_some) {
+        run = match run {
+            Some((level, count)) if level == defined => Some((level, count + 1)),
+            Some((level, count)) => {
+                push_rle_run(&mut levels, level, count);
+                Some((defined, 1))
+            }
+            None => Some((defined, 1)),
+        };
+    }
+    if let Some((level, count)) = run {
+        push_rle_run(&mut levels, level, count);
+    }
+    let mut data = (levels.len() as u32).to_le_bytes().to_vec();
+    data.extend_from_slice(&levels);
+    let present = values.iter().flatten();
+    match kind {
+        ColumnType::Boolean => {
+            let bits: Vec<bool> = present.map(|value| value.as_bool() == Some(true)).collect();
+            for byte in bits.chunks(8) {
+                data.push(
+                    byte.iter()
+                        .enumerate()
+                        .fold(0, |acc, (bit, &set)| acc | ((set as u8) << bit)),
+                );
+            }
+        }
+        ColumnType::Int64 => {
+            for value in present {
+                data.extend_from_slice(&value.as_i64().unwrap_or_default().to_le_bytes());
+            }
+        }
+        ColumnType::Double => {
+            for value in present {
+                data.extend_from_slice(&value.as_f64().unwrap_or_default().to_le_bytes());
+            }
+        }
+        ColumnType::String | ColumnType::Json => {
+            for value in present {
+                let text = match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                data.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+
+    let mut header = Thrift::new();
+    header.i32(1, PAGE_DATA);
+    header.i32(2, data.len() as i32);
+    header.i32(3, data.len() as i32);
+    header.structure(5, |page| {
+        page.i32(1, values.len() as i32);
+        page.i32(2, ENCODING_PLAIN);
+        page.i32(3, ENCODING_RLE);
+        page.i32(4, ENCODING_RLE);
+    });
+    let mut page = header.finish();
+    page.extend_from_slice(&data);
+    page
+}
+
+/// Appends a run of equal definition levels (bit width 1) in the RLE hybrid encoding.
+fn push_rle_run(out: &mut Vec<u8>, defined: bool, count: usize) {
+    push_varint(out, (count as u64) << 1);
+    out.push(defined as u8);
+}
+
+/// Encodes the file footer: the schema and the location of every column chunk.
+fn file_metadata(
+    columns: &[(String, ColumnType)],
+    row_groups: &[(Vec<ColumnChunk>, i64)],
+    num_rows: i64,
+) -> Vec<u8> {
+    let mut meta = Thrift::new();
+    meta.i32(1, 1);
+    meta.list(2, columns.len() + 1, THRIFT_STRUCT, |schema| {
+        schema.element(|root| {
+            root.binary(4, b"schema");
+            root.i32(5, columns.len() as i32);
+        });
+        for (name, kind) in columns {
+            schema.element(|column| {
+                column.i32(1, kind.physical());
+                column.i32(3, REPETITION_OPTIONAL);
+                column.binary(4, name.as_bytes());
+                let (converted, logical) = match kind {
+                    ColumnType::String => (CONVERTED_UTF8, LOGICAL_STRING),
+                    ColumnType::Json => (CONVERTED_JSON, LOGICAL_JSON),
+                    _ => return,
+                };
+                column.i32(6, converted);
+                column.structure(10, |union| union.structure(logical, |_| {}));
+            });
+        }
+    });
+    meta.i64(3, num_rows);
+    meta.list(4, row_groups.len(), THRIFT_STRUCT, |groups| {
+        for (chunks, rows) in row_groups {
+            groups.element(|group| {
+                group.list(1, chunks.len(), THRIFT_STRUCT, |list| {
+                    for chunk in chunks {
+                        list.element(|column| {
+                            column.i64(2, chunk.offset);
+                            column.structure(3, |meta| {
+                                meta.i32(1, chunk.kind.physical());
+                                meta.list_i32(2, &[ENCODING_PLAIN, ENCODING_RLE]);
+                                meta.list(3, 1, THRIFT_BINARY, |path| {
+                                    path.push_binary(chunk.name.as_bytes())
+                                });
+                                meta.i32(4, CODEC_UNCOMPRESSED);
+                                meta.i64(5, chunk.num_values);
+                                meta.i64(6, chunk.size);
+                                meta.i64(7, chunk.size);
+                                meta.i64(9, chunk.offset);
+                            });
+                        });
+                    }
+                });
+                group.i64(2, chunks.iter().map(|chunk| chunk.size).sum());
+                group.i64(3, *rows);
+            });
+        }
+    });
+    meta.binary(
+        6,
+        format!(
+            "json_parser_with_pest version {}",
+            env!("CARGO_PKG_VERSION")
+        )
+        .as_bytes(),
+    );
+    meta.finish()
+}
+
+/// Writes structs in the Thrift compact protocol, which Parquet uses for its metadata.
+struct Thrift {
+    out: Vec<u8>,
+    /// The last field id of each open struct, as field ids are written as deltas.
+    last_ids: Vec<i16>,
+}
+
+impl Thrift {
+    /// Starts the outermost struct.
+    fn new() -> Thrift {
+        Thrift {
+            out: Vec::new(),
+            last_ids: vec![0],
+        }
+    }
+
+    /// Writes a field header.
+    fn field(&mut self, id: i16, kind: u8) {
+        let last = self.last_ids.last_mut().expect("a struct is open");
+        let delta = id - *last;
+        *last = id;
+        if (1..=15).contains(&delta) {
+            self.out.push(((delta as u8) << 4) | kind);
+        } else {
+            self.out.push(kind);
+            push_varint(&mut self.out, zigzag(id as i64));
+        }
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, THRIFT_I32);
+        push_varint(&mut self.out, zigzag(value as i64));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, THRIFT_I64);
+        push_varint(&mut self.out, zigzag(value));
+    }
+
+    fn binary(&mut self, id: i16, bytes: &[u8]) {
+        self.field(id, THRIFT_BINARY);
+        self.push_binary(bytes);
+    }
+
+    /// Writes a binary value without a field header, as a list element.
+    fn push_binary(&mut self, bytes: &[u8]) {
+        push_varint(&mut self.out, bytes.len() as u64);
+        self.out.extend_from_slice(bytes);
+    }
+
+    /// Writes a struct field whose fields `body` writes.
+    fn structure(&mut self, id: i16, body: impl FnOnce(&mut Thrift)) {
+        self.field(id, THRIFT_STRUCT);
+        self.element(body);
+    }
+
+    /// Writes a struct without a field header, as a list element.
+    fn element(&mut self, body: impl FnOnce(&mut Thrift)) {
+        self.last_ids.push(0);
+        body(self);
+        self.out.push(0);
+        self.last_ids.pop();
+    }
+
+    /// Writes a list field of `len` elements of type `kind` that `body` writes.
+    fn list(&mut self, id: i16, len: usize, kind: u8, body: impl FnOnce(&mut Thrift)) {
+        self.field(id, THRIFT_LIST);
+        self.list_header(len, kind);
+        body(self);
+    }
+
+    fn list_i32(&mut self, id: i16, values: &[i32]) {
+        self.field(id, THRIFT_LIST);
+        self.list_header(values.len(), THRIFT_I32);
+        for value in values {
+            push_varint(&mut self.out, zigzag(*value as i64));
+        }
+    }
+
+    fn list_header(&mut self, len: usize, kind: u8) {
+        if len < 15 {
+            self.out.push(((len as u8) << 4) | kind);
+        } else {
+            self.out.push(0xF0 | kind);
+            push_varint(&mut self.out, len as u64);
+        }
+    }
+
+    /// Ends the outermost struct and returns its bytes.
+    fn finish(mut self) -> Vec<u8> {
+        self.out.push(0);
+        self.out
+    }
+}
+
+/// Maps signed integers to unsigned ones with small magnitudes staying small.
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Appends an unsigned LEB128 varint.
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
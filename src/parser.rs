@@ -1,13 +1,35 @@
 use anyhow::{Error, Result};
-use log::{error, info};
+use log::{debug, error};
 use pest::Parser;
 use pest_derive::Parser;
-use serde_json::{Map, Value};
+use regex::RegexBuilder;
+use serde_json::de::IoRead;
+use serde_json::{Map, StreamDeserializer, Value};
 use std::fs;
+use std::io::BufReader;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 use thiserror::Error;
 
+use crate::avro::{avro_to_json, json_to_avro, AvroOptions};
+use crate::bson::{bson_to_json, json_to_bson, BsonOptions};
+use crate::cancel;
+use crate::cbor::{cbor_to_json, json_to_cbor, CborOptions};
+use crate::csv::{csv_to_json, json_to_csv, CsvOptions};
+#[cfg(feature = "parquet")]
+use crate::parquet::{json_to_parquet, ParquetOptions};
+use crate::path_syntax::{BracketStyle, PathSyntax};
+use crate::properties::{
+    dotenv_to_json, json_to_dotenv, json_to_properties, properties_to_json, PropertiesOptions,
+};
+use crate::querystring::{json_to_querystring, querystring_to_json, QueryStringOptions};
+use crate::sql::{json_to_sql, SqlOptions};
+use crate::table::{json_to_html, json_to_markdown, TableOptions};
+use crate::toml::{json_to_toml, parse_toml, TomlOptions};
+use crate::warnings;
+use crate::xml::{json_to_xml, xml_to_json, XmlOptions};
+use crate::yaml::{json_to_yaml, parse_yaml_documents, YamlOptions};
 
 /// JSONParser struct, generated from the grammar defined in `json.pest`.
 /// This struct is used to parse JSON based on the defined rules in the `json.pest` grammar file.
@@ -106,7 +128,15 @@ use thiserror::Error;
 /// - Example:
 ///   - Input: `[ { "key1": "value1" }, { "key2": "value2" } ]`
 ///   - Match: Valid JSON key-value array.
-
+///
+/// ## json5
+/// Entry point for JSON5 documents, used by `parse_json5`. Adds comments, identifier
+/// keys, single-quoted strings, trailing commas, and hex, signed, `Infinity` and `NaN`
+/// numbers on top of JSON.
+///
+/// - Example:
+///   - Input: `{ port: 0x1F90, // dev server\n hosts: ['a', 'b',], }`
+///   - Match: Valid JSON5 document.
 
 #[derive(Parser)]
 #[grammar = "json.pest"]
@@ -117,10 +147,296 @@ pub struct JSONParser;
 pub enum ParserError {
     #[error("File read error: {0}")]
     FileReadError(#[from] std::io::Error),
-    #[error("JSON parse error")]
-    JsonParseError,
+    #[error("JSON parse error{0}")]
+    JsonParseError(Box<ParseErrorDetails>),
     #[error("Schema validation failed")]
     SchemaValidationError,
+    /// The call was stopped by its `CancellationToken`.
+    #[error("Operation cancelled")]
+    Cancelled,
+    /// The call did not finish within the timeout of `with_timeout`.
+    #[error("Operation timed out after {0:?}")]
+    Timeout(Duration),
+    /// A path set an array index more than `MAX_ARRAY_PADDING` past the end of the array.
+    #[error("Index {index} is more than {max} past the end of an array of {len} elements", max = MAX_ARRAY_PADDING)]
+    IndexOutOfRange { index: usize, len: usize },
+}
+
+/// The most `null` elements that setting an index past the end of an array pads it with,
+/// so that a path such as `a[4000000000]` fails instead of allocating gigabytes.
+pub const MAX_ARRAY_PADDING: usize = 1000;
+
+/// Where and why JSON parsing failed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseErrorDetails {
+    /// 1-based line of the error; 0 if unknown.
+    pub line: usize,
+    /// 1-based column of the error, counted in characters; 0 if unknown.
+    pub column: usize,
+    /// Byte offset of the error in the input, if known.
+    pub offset: Option<usize>,
+    /// The input line containing the error; empty if the input is not available.
+    pub snippet: String,
+    /// Tokens that would have been accepted at the error position.
+    pub expected: Vec<String>,
+    /// A description of the problem when it is not a missing token, e.g. an invalid escape.
+    pub message: Option<String>,
+    /// Context collected while the error propagated, innermost first.
+    pub diagnostics: Diagnostics,
+}
+
+/// A message about a position in the input, such as the object an error occurred in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based line the message refers to.
+    pub line: usize,
+    /// 1-based column the message refers to.
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (line {}, column {})",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+/// Diagnostics collected while parsing instead of being printed, so that embedding
+/// applications decide what to show. They are also logged at debug level.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    entries: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Adds a message about a position in the input.
+    pub fn push(&mut self, position: pest::Position, message: &str) {
+        let (line, column) = position.line_col();
+        debug!("{} at line {}, column {}", message, line, column);
+        self.entries.push(Diagnostic {
+            line,
+            column,
+            message: message.to_string(),
+        });
+    }
+
+    /// Returns the collected diagnostics in the order they were added.
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.entries.iter()
+    }
+
+    /// Returns the number of collected diagnostics.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if nothing was collected.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl ParseErrorDetails {
+    /// Builds the details of a failed `JSONParser` parse.
+    fn from_pest(error: &pest::error::Error<Rule>) -> Self {
+        let (line, column) = match error.line_col {
+            pest::error::LineColLocation::Pos(position) => position,
+            pest::error::LineColLocation::Span(start, _) => start,
+        };
+        let offset = match error.location {
+            pest::error::InputLocation::Pos(offset) => offset,
+            pest::error::InputLocation::Span((offset, _)) => offset,
+        };
+        let (expected, message) = match &error.variant {
+            pest::error::ErrorVariant::ParsingError { positives, .. } => (
+                positives.iter().map(|rule| rule_name(*rule)).collect(),
+                None,
+            ),
+            pest::error::ErrorVariant::CustomError { message } => {
+                (Vec::new(), Some(message.clone()))
+            }
+        };
+        ParseErrorDetails {
+            line,
+            column,
+            offset: Some(offset),
+            snippet: error.line().trim_end_matches(['\r', '\n']).to_string(),
+            expected,
+            message,
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    /// Builds the details of a problem found at a position of successfully parsed input.
+    pub(crate) fn at(position: pest::Position, message: &str) -> Self {
+        let (line, column) = position.line_col();
+        ParseErrorDetails {
+            line,
+            column,
+            offset: Some(position.pos()),
+            snippet: position
+                .line_of()
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+            expected: Vec::new(),
+            message: Some(message.to_string()),
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    /// Builds the details of a `serde_json` error. `input` is the parsed text, used for
+    /// the snippet and offset; pass `""` when it is not available, e.g. for streams.
+    pub fn from_serde(error: &serde_json::Error, input: &str) -> Self {
+        let snippet = input
+            .lines()
+            .nth(error.line().saturating_sub(1))
+            .unwrap_or_default();
+        let offset = (!input.is_empty()).then(|| {
+            let line_start: usize = input
+                .split_inclusive('\n')
+                .take(error.line().saturating_sub(1))
+                .map(str::len)
+                .sum();
+            let column: usize = snippet
+                .chars()
+                .take(error.column().saturating_sub(1))
+                .map(char::len_utf8)
+                .sum();
+            line_start + column
+        });
+        let message = error.to_string();
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) => message.to_string(),
+            None => message,
+        };
+        ParseErrorDetails {
+            line: error.line(),
+            column: error.column(),
+            offset,
+            snippet: snippet.trim_end_matches('\r').to_string(),
+            expected: Vec::new(),
+            message: Some(message),
+            diagnostics: Diagnostics::default(),
+        }
+    }
+
+    /// Builds the details of a problem without a known position.
+    fn message(message: &str) -> Self {
+        ParseErrorDetails {
+            message: Some(message.to_string()),
+            ..ParseErrorDetails::default()
+        }
+    }
+
+    /// Renders the offending line with a caret under the error column, followed by
+    /// the collected diagnostics, e.g.
+    ///
+    /// ```text
+    /// 2 |   "tags": [1, 2,]
+    ///   |                 ^ expected object, array, string, ...
+    /// note: in array (line 2, column 11)
+    /// note: in object (line 1, column 1)
+    /// ```
+    pub fn excerpt(&self) -> String {
+        let mut excerpt = self.caret_excerpt();
+        for diagnostic in self.diagnostics.iter() {
+            excerpt.push_str(&format!("\nnote: {}", diagnostic));
+        }
+        excerpt
+    }
+
+    /// Renders the offending line with a caret under the error column.
+    fn caret_excerpt(&self) -> String {
+        let reason = match (&self.message, self.expected.is_empty()) {
+            (Some(message), _) => message.clone(),
+            (None, false) => format!("expected {}", self.expected.join(", ")),
+            (None, true) => "unexpected input".to_string(),
+        };
+        if self.line == 0 {
+            return reason;
+        }
+        let gutter = " ".repeat(self.line.to_string().len());
+        // Tabs are kept so the caret lines up with the snippet in a terminal.
+        let padding: String = self
+            .snippet
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!(
+            "{} | {}\n{} | {}^ {}",
+            self.line, self.snippet, gutter, padding, reason
+        )
+    }
+}
+
+impl std::fmt::Display for ParseErrorDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.line > 0 {
+            write!(f, " at line {}, column {}", self.line, self.column)?;
+        }
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        } else if !self.expected.is_empty() {
+            write!(f, ": expected {}", self.expected.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a readable name for a grammar rule in error messages.
+fn rule_name(rule: Rule) -> String {
+    match rule {
+        Rule::EOI => "end of input".to_string(),
+        Rule::pair | Rule::json5_pair => "key-value pair".to_string(),
+        rule => format!("{:?}", rule)
+            .trim_start_matches("json5_")
+            .replace('_', " "),
+    }
+}
+
+/// Wraps `ParseErrorDetails` in a `ParserError::JsonParseError`.
+fn parse_error(details: ParseErrorDetails) -> ParserError {
+    debug!("JSON parse error{}", details);
+    ParserError::JsonParseError(Box::new(details))
+}
+
+/// Returns the offsets of the objects and arrays still open at `offset`, outermost first.
+fn enclosing_containers(input: &str, offset: usize) -> Vec<usize> {
+    let mut open = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for (position, c) in input[..offset.min(input.len())].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' | '[' if !in_string => open.push(position),
+            '}' | ']' if !in_string => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    open
+}
+
+/// Records the object or array that a parse error occurred in.
+fn with_context(error: ParserError, pair: &pest::iterators::Pair<Rule>) -> ParserError {
+    match error {
+        ParserError::JsonParseError(mut details) => {
+            let context = format!("in {}", rule_name(pair.as_rule()));
+            details
+                .diagnostics
+                .push(pair.as_span().start_pos(), &context);
+            ParserError::JsonParseError(details)
+        }
+        other => other,
+    }
 }
 
 /// Parses a JSON string using the `JSONParser` and converts it to a `serde_json::Value`.
@@ -133,10 +449,22 @@ pub enum ParserError {
 /// # Returns
 ///
 /// * `Result<Value, ParserError>` - The parsed JSON as a `serde_json::Value` if successful, or an error on failure.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = json_str.len())))]
 pub fn parse_json(json_str: &str) -> Result<Value, ParserError> {
     let pairs = JSONParser::parse(Rule::json, json_str).map_err(|e| {
-        println!("Parsing error in JSON input: {:?}", e);
-        ParserError::JsonParseError
+        let mut details = ParseErrorDetails::from_pest(&e);
+        let offset = details.offset.unwrap_or_default();
+        for start in enclosing_containers(json_str, offset).into_iter().rev() {
+            let context = if json_str[start..].starts_with('{') {
+                "in object"
+            } else {
+                "in array"
+            };
+            if let Some(position) = pest::Position::new(json_str, start) {
+                details.diagnostics.push(position, context);
+            }
+        }
+        parse_error(details)
     })?;
     parse_value(pairs)
 }
@@ -151,11 +479,22 @@ pub fn parse_json(json_str: &str) -> Result<Value, ParserError> {
 ///
 /// * `Result<Value, ParserError>` - A `serde_json::Value` representing the parsed JSON structure, or an error if parsing fails.
 fn parse_value(mut pairs: pest::iterators::Pairs<Rule>) -> Result<Value, ParserError> {
-    let pair = pairs.next().ok_or_else(|| {
-        println!("No pairs found in input.");
-        ParserError::JsonParseError
-    })?;
+    let pair = pairs
+        .next()
+        .ok_or_else(|| parse_error(ParseErrorDetails::message("No value found in input")))?;
+    parse_pair(pair)
+}
 
+/// Converts a single parsed value, such as an array element, to a `serde_json::Value`.
+///
+/// # Arguments
+///
+/// * `pair` - The `pest::iterators::Pair` of the value.
+///
+/// # Returns
+///
+/// * `Result<Value, ParserError>` - The value, or an error for rules that are not JSON values.
+fn parse_pair(pair: pest::iterators::Pair<Rule>) -> Result<Value, ParserError> {
     match pair.as_rule() {
         Rule::json => parse_value(pair.into_inner()),
         Rule::object => parse_object(pair),
@@ -164,10 +503,10 @@ fn parse_value(mut pairs: pest::iterators::Pairs<Rule>) -> Result<Value, ParserE
         Rule::number => Ok(Value::Number(parse_number(pair)?)),
         Rule::boolean => Ok(Value::Bool(pair.as_str() == "true")),
         Rule::null => Ok(Value::Null),
-        _ => {
-            println!("Unexpected pair encountered: {:?}", pair.as_rule());
-            Err(ParserError::JsonParseError)
-        }
+        rule => Err(parse_error(ParseErrorDetails::at(
+            pair.as_span().start_pos(),
+            &format!("unexpected {}", rule_name(rule)),
+        ))),
     }
 }
 
@@ -182,18 +521,38 @@ fn parse_value(mut pairs: pest::iterators::Pairs<Rule>) -> Result<Value, ParserE
 ///
 /// * `Result<Value, ParserError>` - Returns a `serde_json::Value::Object` on success, or an error if parsing fails.
 fn parse_object(pair: pest::iterators::Pair<Rule>) -> Result<Value, ParserError> {
+    cancel::check().map_err(|_| ParserError::Cancelled)?;
     let mut map = Map::new();
-    for inner_pair in pair.into_inner() {
+    for inner_pair in pair.clone().into_inner() {
         if inner_pair.as_rule() == Rule::pair {
+            let position = inner_pair.as_span().start_pos();
             let mut inner_rules = inner_pair.into_inner();
-            let key = parse_string(inner_rules.next().ok_or(ParserError::JsonParseError)?)?;
-            let value = parse_value(inner_rules)?;
+            let key = parse_string(inner_rules.next().ok_or_else(|| {
+                parse_error(ParseErrorDetails::message("Key-value pair without a key"))
+            })?)
+            .map_err(|e| with_context(e, &pair))?;
+            let value = parse_value(inner_rules).map_err(|e| with_context(e, &pair))?;
+            if map.contains_key(&key) {
+                warn_duplicate_key(&key, position);
+            }
             map.insert(key, value);
         }
     }
     Ok(Value::Object(map))
 }
 
+/// Warns about a duplicate object key, of which the last value is kept.
+fn warn_duplicate_key(key: &str, position: pest::Position) {
+    let (line, column) = position.line_col();
+    warnings::warn(
+        "",
+        format!(
+            "Duplicate key '{}' at line {}, column {}; the last value is kept",
+            key, line, column
+        ),
+    );
+}
+
 /// Parses a JSON array and returns it as a `serde_json::Value`.
 /// Processes each array element and collects them into a `Vec<Value>`.
 ///
@@ -205,9 +564,10 @@ fn parse_object(pair: pest::iterators::Pair<Rule>) -> Result<Value, ParserError>
 ///
 /// * `Result<Value, ParserError>` - Returns a `serde_json::Value::Array` on success, or an error if parsing fails.
 fn parse_array(pair: pest::iterators::Pair<Rule>) -> Result<Value, ParserError> {
+    cancel::check().map_err(|_| ParserError::Cancelled)?;
     let mut array = Vec::new();
-    for inner_pair in pair.into_inner() {
-        let value = parse_value(inner_pair.into_inner())?;
+    for inner_pair in pair.clone().into_inner() {
+        let value = parse_pair(inner_pair).map_err(|e| with_context(e, &pair))?;
         array.push(value);
     }
     Ok(Value::Array(array))
@@ -215,6 +575,9 @@ fn parse_array(pair: pest::iterators::Pair<Rule>) -> Result<Value, ParserError>
 
 /// Parses a JSON string, handling escape sequences and Unicode characters.
 ///
+/// The grammar has already checked the syntax, so the escapes are resolved by
+/// `serde_json`, which also joins surrogate pairs and rejects lone surrogates.
+///
 /// # Arguments
 ///
 /// * `pair` - The `pest::iterators::Pair` containing the JSON string.
@@ -223,38 +586,12 @@ fn parse_array(pair: pest::iterators::Pair<Rule>) -> Result<Value, ParserError>
 ///
 /// * `Result<String, ParserError>` - The parsed string or an error if parsing fails.
 fn parse_string(pair: pest::iterators::Pair<Rule>) -> Result<String, ParserError> {
-    let mut result = String::new();
-    for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::character => result.push_str(inner_pair.as_str()),
-            Rule::escape_sequence => {
-                let escaped = match inner_pair.as_str() {
-                    "\\\"" => "\"",
-                    "\\\\" => "\\",
-                    "\\/" => "/",
-                    "\\b" => "\u{0008}",
-                    "\\f" => "\u{000C}",
-                    "\\n" => "\n",
-                    "\\r" => "\r",
-                    "\\t" => "\t",
-                    escape if escape.starts_with("\\u") => {
-                        let hex = &escape[2..];
-                        let code_point = u32::from_str_radix(hex, 16)
-                            .map_err(|_| ParserError::JsonParseError)?;
-                        let unicode_char = std::char::from_u32(code_point)
-                            .ok_or(ParserError::JsonParseError)?
-                            .to_string();
-                        result.push_str(&unicode_char);
-                        continue;
-                    }
-                    _ => return Err(ParserError::JsonParseError),
-                };
-                result.push_str(escaped);
-            }
-            _ => {}
-        }
-    }
-    Ok(result)
+    serde_json::from_str(pair.as_str()).map_err(|e| {
+        parse_error(ParseErrorDetails::at(
+            pair.as_span().start_pos(),
+            &format!("invalid string {}: {}", pair.as_str(), e),
+        ))
+    })
 }
 
 /// Parses a JSON number and converts it to a `serde_json::Number`.
@@ -268,7 +605,310 @@ fn parse_string(pair: pest::iterators::Pair<Rule>) -> Result<String, ParserError
 /// * `Result<serde_json::Number, ParserError>` - The parsed number or an error if parsing fails.
 fn parse_number(pair: pest::iterators::Pair<Rule>) -> Result<serde_json::Number, ParserError> {
     let number_str = pair.as_str();
-    serde_json::Number::from_str(number_str).map_err(|_| ParserError::JsonParseError)
+    serde_json::Number::from_str(number_str).map_err(|_| {
+        parse_error(ParseErrorDetails::at(
+            pair.as_span().start_pos(),
+            &format!("invalid number {}", number_str),
+        ))
+    })
+}
+
+/// Parses JSON with comments (JSONC), as used by VS Code settings and `tsconfig.json`.
+///
+/// `//` and `/* */` comments and trailing commas in objects and arrays are accepted;
+/// everything else must be strict JSON. Error positions refer to the original text.
+///
+/// # Arguments
+///
+/// * `json_str` - The JSONC text.
+///
+/// # Returns
+///
+/// * `Result<Value, ParserError>` - The parsed document, or a `JsonParseError` with the position of the syntax error.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = json_str.len())))]
+pub fn parse_jsonc(json_str: &str) -> Result<Value, ParserError> {
+    serde_json::from_str(&blank_comments(json_str))
+        .map_err(|e| parse_error(ParseErrorDetails::from_serde(&e, json_str)))
+}
+
+/// Converts JSONC to strict JSON by removing comments and trailing commas.
+///
+/// The layout is otherwise kept: lines that only held a comment are dropped and
+/// whitespace left at the end of a line is trimmed.
+///
+/// # Arguments
+///
+/// * `json_str` - The JSONC text.
+///
+/// # Returns
+///
+/// * `String` - The text without comments and trailing commas.
+pub fn strip_json_comments(json_str: &str) -> String {
+    let stripped = remove_comments(json_str, false);
+    let mut result = String::with_capacity(stripped.len());
+    for (original, line) in json_str.lines().zip(stripped.lines()) {
+        let line = line.trim_end();
+        if line.is_empty() && !original.trim().is_empty() {
+            continue;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    if !json_str.ends_with('\n') {
+        result.pop();
+    }
+    result
+}
+
+/// Replaces comments and trailing commas with spaces of the same byte length, so
+/// offsets in the result match the original text.
+fn blank_comments(json_str: &str) -> String {
+    remove_comments(json_str, true)
+}
+
+/// Removes comments and trailing commas, keeping every line break so that lines
+/// still match the original. With `blank`, removed text is replaced by spaces of
+/// the same byte length; otherwise it is dropped, along with the spaces that
+/// followed a trailing comma on its line.
+fn remove_comments(json_str: &str, blank: bool) -> String {
+    let bytes = json_str.as_bytes();
+    let mut result = String::with_capacity(json_str.len());
+    let mut chars = json_str.char_indices().peekable();
+    let mut in_string = false;
+    let mut after_comma = false;
+    let remove = |result: &mut String, c: char| {
+        if c == '\n' || c == '\r' {
+            result.push(c);
+        } else if blank {
+            result.push_str(&" ".repeat(c.len_utf8()));
+        }
+    };
+    while let Some((i, c)) = chars.next() {
+        if in_string {
+            result.push(c);
+            match c {
+                '\\' => result.extend(chars.next().map(|(_, escaped)| escaped)),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, bytes.get(i + 1)) {
+            ('/', Some(b'/')) => {
+                remove(&mut result, c);
+                while let Some((_, c)) = chars.next_if(|&(_, c)| c != '\n') {
+                    remove(&mut result, c);
+                }
+            }
+            ('/', Some(b'*')) => {
+                remove(&mut result, c);
+                remove(&mut result, chars.next().map_or('*', |(_, c)| c));
+                while let Some((j, c)) = chars.next() {
+                    remove(&mut result, c);
+                    if c == '*' && bytes.get(j + 1) == Some(&b'/') {
+                        chars.next();
+                        remove(&mut result, '/');
+                        break;
+                    }
+                }
+            }
+            (',', _) if matches!(next_significant(bytes, i + 1), Some(b'}' | b']')) => {
+                remove(&mut result, c);
+                after_comma = true;
+            }
+            (' ' | '\t', _) if after_comma && !blank => {}
+            _ => {
+                after_comma = false;
+                in_string = c == '"';
+                result.push(c);
+            }
+        }
+    }
+    result
+}
+
+/// Returns the next byte after `start` that is not whitespace or part of a comment.
+fn next_significant(bytes: &[u8], mut start: usize) -> Option<u8> {
+    loop {
+        match (bytes.get(start)?, bytes.get(start + 1)) {
+            (b' ' | b'\t' | b'\r' | b'\n', _) => start += 1,
+            (b'/', Some(b'/')) => {
+                start += bytes[start..].iter().position(|&b| b == b'\n')?;
+            }
+            (b'/', Some(b'*')) => {
+                let end = bytes[start + 2..].windows(2).position(|w| w == b"*/")?;
+                start += end + 4;
+            }
+            (&b, _) => return Some(b),
+        }
+    }
+}
+
+/// Parses a JSON5 document and converts it to a `serde_json::Value`.
+///
+/// Accepts everything `parse_json` does for standard JSON, plus `//` and `/* */`
+/// comments, unquoted identifier keys, single-quoted strings, trailing commas,
+/// hex numbers, leading `+` signs and leading or trailing decimal points.
+/// `Infinity` and `NaN` have no JSON representation and become `null`.
+///
+/// # Arguments
+///
+/// * `json_str` - The JSON5 text, e.g. the contents of a frontend config file.
+///
+/// # Returns
+///
+/// * `Result<Value, ParserError>` - The parsed document, or a `JsonParseError` with the position of the first syntax error.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = json_str.len())))]
+pub fn parse_json5(json_str: &str) -> Result<Value, ParserError> {
+    let document = JSONParser::parse(Rule::json5, json_str)
+        .map_err(|e| parse_error(ParseErrorDetails::from_pest(&e)))?
+        .next()
+        .ok_or_else(|| parse_error(ParseErrorDetails::message("No value found in input")))?;
+    let value = document
+        .into_inner()
+        .find(|pair| pair.as_rule() != Rule::EOI)
+        .ok_or_else(|| parse_error(ParseErrorDetails::message("No value found in input")))?;
+    parse_json5_value(value)
+}
+
+/// Converts a JSON5 value pair into a `serde_json::Value`.
+fn parse_json5_value(pair: pest::iterators::Pair<Rule>) -> Result<Value, ParserError> {
+    match pair.as_rule() {
+        Rule::json5_object => {
+            let mut map = Map::new();
+            for member in pair.clone().into_inner() {
+                let position = member.as_span().start_pos();
+                let mut inner = member.into_inner();
+                let (key, value) = (inner.next().unwrap(), inner.next().unwrap());
+                let key = match key.as_rule() {
+                    Rule::json5_identifier => key.as_str().to_string(),
+                    _ => parse_json5_string(key),
+                };
+                let value = parse_json5_value(value).map_err(|e| with_context(e, &pair))?;
+                if map.contains_key(&key) {
+                    warn_duplicate_key(&key, position);
+                }
+                map.insert(key, value);
+            }
+            Ok(Value::Object(map))
+        }
+        Rule::json5_array => pair
+            .clone()
+            .into_inner()
+            .map(|item| parse_json5_value(item).map_err(|e| with_context(e, &pair)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Rule::json5_string => Ok(Value::String(parse_json5_string(pair))),
+        Rule::json5_number => parse_json5_number(pair),
+        Rule::json5_boolean => Ok(Value::Bool(pair.as_str() == "true")),
+        Rule::json5_null => Ok(Value::Null),
+        rule => Err(parse_error(ParseErrorDetails::at(
+            pair.as_span().start_pos(),
+            &format!("unexpected {}", rule_name(rule)),
+        ))),
+    }
+}
+
+/// Resolves the escape sequences of a single- or double-quoted JSON5 string.
+/// Unknown escapes stand for the escaped character, and an escaped line break is removed.
+fn parse_json5_string(pair: pest::iterators::Pair<Rule>) -> String {
+    let text = pair.as_str();
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text[1..text.len() - 1].chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let Some(escaped) = chars.next() else { break };
+        let hex = |chars: &mut std::iter::Peekable<std::str::Chars>, length: usize| {
+            let digits: String = chars.by_ref().take(length).collect();
+            u32::from_str_radix(&digits, 16).ok()
+        };
+        match escaped {
+            'b' => result.push('\u{0008}'),
+            'f' => result.push('\u{000C}'),
+            'n' => result.push('\n'),
+            'r' => result.push('\r'),
+            't' => result.push('\t'),
+            'v' => result.push('\u{000B}'),
+            '0' => result.push('\0'),
+            'x' => result.extend(hex(&mut chars, 2).and_then(char::from_u32)),
+            'u' => {
+                let Some(high) = hex(&mut chars, 4) else {
+                    continue;
+                };
+                let code = if (0xD800..0xDC00).contains(&high) && chars.peek() == Some(&'\\') {
+                    chars.next();
+                    chars.next();
+                    let low = hex(&mut chars, 4).unwrap_or(0);
+                    0x10000 + ((high - 0xD800) << 10) + low.saturating_sub(0xDC00)
+                } else {
+                    high
+                };
+                result.extend(char::from_u32(code));
+            }
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+            }
+            '\n' | '\u{2028}' | '\u{2029}' => {}
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+/// Converts a JSON5 number, including hex and signed forms, to a JSON value.
+fn parse_json5_number(pair: pest::iterators::Pair<Rule>) -> Result<Value, ParserError> {
+    let text = pair.as_str();
+    let (negative, digits) = match text.as_bytes()[0] {
+        b'-' => (true, &text[1..]),
+        b'+' => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let invalid = || {
+        parse_error(ParseErrorDetails::at(
+            pair.as_span().start_pos(),
+            &format!("invalid number {}", text),
+        ))
+    };
+    if digits == "Infinity" || digits == "NaN" {
+        let (line, column) = pair.as_span().start_pos().line_col();
+        warnings::warn(
+            "",
+            format!(
+                "{} at line {}, column {} has no JSON representation and became null",
+                text, line, column
+            ),
+        );
+        return Ok(Value::Null);
+    }
+    if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        let magnitude = i128::from_str_radix(hex, 16).map_err(|_| invalid())?;
+        let value = if negative { -magnitude } else { magnitude };
+        return match (i64::try_from(value), u64::try_from(value)) {
+            (Ok(value), _) => Ok(Value::from(value)),
+            (_, Ok(value)) => Ok(Value::from(value)),
+            _ => Err(invalid()),
+        };
+    }
+    let sign = if negative { "-" } else { "" };
+    if !digits.contains(['.', 'e', 'E']) {
+        return serde_json::Number::from_str(&format!("{}{}", sign, digits))
+            .map(Value::Number)
+            .map_err(|_| invalid());
+    }
+    format!("{}{}", sign, digits)
+        .parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+        .map(Value::Number)
+        .ok_or_else(invalid)
 }
 
 /// Validates a JSON object against a schema.
@@ -316,6 +956,8 @@ pub fn parse_partial_json(json: &Value, key: &str) -> Option<Value> {
 
 /// Edits a JSON file by updating a specific key with a new value.
 ///
+/// Only top-level keys are supported; use `set_by_path` for nested values.
+///
 /// # Arguments
 ///
 /// * `json` - A mutable reference to the JSON object to edit.
@@ -334,81 +976,181 @@ pub fn edit_json(json: &mut Value, key: &str, new_value: Value) -> Result<(), Er
     }
 }
 
-/// Converts JSON to YAML or XML format based on the specified format.
+/// Converts JSON to YAML, XML, TOML, CSV, or a Markdown or HTML table based on the
+/// specified format.
 ///
 /// # Arguments
 ///
 /// * `json` - The JSON object to convert.
-/// * `format` - The target format ("yaml" or "xml").
+/// * `format` - The target format ("yaml", "xml", "toml", "csv", "markdown", "html",
+///   "querystring", "dotenv", "properties" or "sql").
+///   CSV and the tables need an array of objects; nested fields become columns such as
+///   `address.city`. `.env` and properties files need an object; nested fields become keys
+///   such as `ADDRESS__CITY` and `address.city`. SQL needs an array of flat objects and
+///   inserts into a table named `data`.
 ///
 /// # Returns
 ///
 /// * `Result<String, Error>` - The converted JSON in the specified format, or an error if the format is unsupported.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(json)))]
 pub fn convert_to_format(json: &Value, format: &str) -> Result<String, Error> {
     match format {
-        "yaml" => serde_yaml::to_string(json).map_err(|e| Error::msg(e.to_string())),
-        "xml" => convert_json_to_xml(json),
+        "yaml" => json_to_yaml(json, &YamlOptions::default()),
+        "xml" => json_to_xml(json, &XmlOptions::default()),
+        "toml" => json_to_toml(json),
+        "csv" => json_to_csv(json, &CsvOptions::default()),
+        "markdown" => json_to_markdown(json, &TableOptions::default()),
+        "html" => json_to_html(json, &TableOptions::default()),
+        "querystring" => json_to_querystring(json, &QueryStringOptions::default()),
+        "dotenv" => json_to_dotenv(json, &PropertiesOptions::dotenv()),
+        "properties" => json_to_properties(json, &PropertiesOptions::java()),
+        "sql" => json_to_sql(json, &SqlOptions::default()),
+        "cbor" => Err(Error::msg("CBOR is a binary format; use convert_to_bytes")),
+        "bson" => Err(Error::msg("BSON is a binary format; use convert_to_bytes")),
+        "avro" => Err(Error::msg("Avro is a binary format; use convert_to_bytes")),
+        "parquet" => Err(Error::msg(
+            "Parquet is a binary format; use convert_to_bytes",
+        )),
         _ => Err(Error::msg("Unsupported format")),
     }
 }
 
-/// Converts JSON to XML format.
+/// Converts JSON to a binary format, or to a text format as UTF-8 bytes.
 ///
 /// # Arguments
 ///
-/// * `json` - The JSON object to convert.
+/// * `json` - The JSON value to convert.
+/// * `format` - "cbor", "bson", "avro" (with an inferred schema), "parquet" (with the `parquet` feature, for an array of
+///   records), or one of the formats of `convert_to_format`.
 ///
 /// # Returns
 ///
-/// * `Result<String, Error>` - The converted JSON in XML format, or an error if conversion fails.
-fn convert_json_to_xml(json: &Value) -> Result<String, Error> {
-    let mut writer = Vec::new();
-    write_xml(json, &mut writer, "root")?;
-    String::from_utf8(writer).map_err(|e| Error::msg(e.to_string()))
+/// * `Result<Vec<u8>, Error>` - The encoded value, or an error if it cannot be written in
+///   the format or the format is unsupported.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(json)))]
+pub fn convert_to_bytes(json: &Value, format: &str) -> Result<Vec<u8>, Error> {
+    match format {
+        "cbor" => json_to_cbor(json),
+        "bson" => json_to_bson(json),
+        "avro" => json_to_avro(json, &AvroOptions::default()),
+        #[cfg(feature = "parquet")]
+        "parquet" => json_to_parquet(json, &ParquetOptions::default()),
+        #[cfg(not(feature = "parquet"))]
+        "parquet" => Err(Error::msg(
+            "Parquet output needs the crate's `parquet` feature",
+        )),
+        _ => convert_to_format(json, format).map(String::into_bytes),
+    }
 }
 
-/// Writes XML data recursively from JSON, preserving the structure.
+/// Converts YAML, XML, TOML, CSV, query string, `.env` or properties text to JSON, the
+/// reverse of `convert_to_format`.
 ///
 /// # Arguments
 ///
-/// * `json` - The JSON object to write as XML.
-/// * `writer` - The writer to output the XML data.
-/// * `tag_name` - The XML tag name.
+/// * `input` - The text to convert.
+/// * `format` - The source format ("yaml", "xml", "toml", "csv", "querystring", "dotenv"
+///   or "properties"). A YAML stream with several documents becomes an array of them, and
+///   CSV an array of one object per row.
 ///
 /// # Returns
 ///
-/// * `Result<(), Error>` - Returns Ok if writing succeeds, or an error if it fails.
-fn write_xml<W: std::io::Write>(json: &Value, writer: &mut W, tag_name: &str) -> Result<(), Error> {
-    match json {
-        Value::Object(map) => {
-            writeln!(writer, "<{}>", tag_name)?;
-            for (key, value) in map {
-                write_xml(value, writer, key)?;
-            }
-            writeln!(writer, "</{}>", tag_name)?;
-        }
-        Value::Array(arr) => {
-            for value in arr {
-                write_xml(value, writer, tag_name)?;
-            }
-        }
-        Value::String(s) => {
-            writeln!(writer, "<{0}>{1}</{0}>", tag_name, s)?;
-        }
-        Value::Number(num) => {
-            writeln!(writer, "<{0}>{1}</{0}>", tag_name, num)?;
-        }
-        Value::Bool(b) => {
-            writeln!(writer, "<{0}>{1}</{0}>", tag_name, b)?;
+/// * `Result<Value, Error>` - The JSON value, or an error if the text is invalid or the format is unsupported.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(input), fields(bytes = input.len())))]
+pub fn convert_from_format(input: &str, format: &str) -> Result<Value, Error> {
+    match format {
+        "yaml" => {
+            let mut documents = parse_yaml_documents(input)?;
+            Ok(if documents.len() == 1 {
+                documents.remove(0)
+            } else {
+                Value::Array(documents)
+            })
         }
-        Value::Null => {
-            writeln!(writer, "<{} />", tag_name)?;
+        "xml" => xml_to_json(input),
+        "toml" => parse_toml(input, &TomlOptions::default()),
+        "csv" => csv_to_json(input, &CsvOptions::default()),
+        "querystring" => querystring_to_json(input, &QueryStringOptions::default()),
+        "dotenv" => dotenv_to_json(input, &PropertiesOptions::dotenv()),
+        "properties" => properties_to_json(input, &PropertiesOptions::java()),
+        "cbor" => Err(Error::msg(
+            "CBOR is a binary format; use convert_from_bytes",
+        )),
+        "bson" => Err(Error::msg(
+            "BSON is a binary format; use convert_from_bytes",
+        )),
+        "avro" => Err(Error::msg(
+            "Avro is a binary format; use convert_from_bytes",
+        )),
+        _ => Err(Error::msg("Unsupported format")),
+    }
+}
+
+/// Reads text in any supported text format into a `Value`, so that callers handle JSON
+/// and the formats of `convert_from_format` alike, e.g. a config file that may be
+/// `config.json`, `config.yaml` or `config.toml`.
+///
+/// # Arguments
+///
+/// * `input` - The text to read.
+/// * `format` - "json", "jsonc" (comments and trailing commas), "json5", or one of the
+///   formats of `convert_from_format`: "yaml", "xml", "toml", "csv", "querystring",
+///   "dotenv" or "properties".
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The value, or an error if the text is invalid or the
+///   format is unsupported; JSON syntax errors are `ParserError::JsonParseError` with
+///   their line and column.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(input), fields(bytes = input.len())))]
+pub fn parse_format(input: &str, format: &str) -> Result<Value, Error> {
+    match format {
+        "json" => serde_json::from_str(input)
+            .map_err(|e| parse_error(ParseErrorDetails::from_serde(&e, input)).into()),
+        "jsonc" => Ok(parse_jsonc(input)?),
+        "json5" => Ok(parse_json5(input)?),
+        _ => convert_from_format(input, format),
+    }
+}
+
+/// Converts a binary format, or a text format in UTF-8, to JSON, the reverse of
+/// `convert_to_bytes`.
+///
+/// # Arguments
+///
+/// * `input` - The bytes to convert, e.g. a CBOR payload from an IoT device or a
+///   `mongodump` BSON file.
+/// * `format` - "cbor", "bson", "avro", or one of the formats of `convert_from_format`.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The JSON value, or an error if the input is invalid or the
+///   format is unsupported.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(input), fields(bytes = input.len()))
+)]
+pub fn convert_from_bytes(input: &[u8], format: &str) -> Result<Value, Error> {
+    match format {
+        "cbor" => cbor_to_json(input, &CborOptions::default()),
+        "bson" => bson_to_json(input, &BsonOptions::default()),
+        "avro" => avro_to_json(input),
+        _ => {
+            let text = std::str::from_utf8(input)
+                .map_err(|e| Error::msg(format!("Input is not valid UTF-8: {}", e)))?;
+            convert_from_format(text, format)
         }
     }
-    Ok(())
 }
 
-/// Processes large JSON files by parsing them in chunks.
+/// Streams the top-level values of a large JSON file without loading it whole.
+///
+/// The file may hold one value or several concatenated / newline-delimited values;
+/// each is yielded as soon as it is parsed. Iteration stops after the first parse
+/// error, which is yielded as `ParserError::JsonParseError` with its line and column, or
+/// after `ParserError::Cancelled` if a `CancellationToken` the iteration runs under is
+/// cancelled. Use the iterator adapters to stop early, e.g. `take_while` or
+/// `try_for_each` with `ControlFlow`.
 ///
 /// # Arguments
 ///
@@ -416,15 +1158,81 @@ fn write_xml<W: std::io::Write>(json: &Value, writer: &mut W, tag_name: &str) ->
 ///
 /// # Returns
 ///
-/// * `Result<(), ParserError>` - Returns Ok if successful, or an error if parsing fails.
-pub fn handle_large_json(file_path: &Path) -> Result<(), ParserError> {
+/// * `Result<JsonValues, ParserError>` - An iterator over the parsed values, or an error if the file cannot be opened.
+#[cfg_attr(feature = "tracing", tracing::instrument)]
+pub fn handle_large_json(file_path: &Path) -> Result<JsonValues, ParserError> {
     let file = fs::File::open(file_path)?;
-    let stream = serde_json::Deserializer::from_reader(file).into_iter::<Value>();
+    Ok(JsonValues {
+        stream: serde_json::Deserializer::from_reader(BufReader::new(file)).into_iter(),
+        failed: false,
+    })
+}
+
+/// Iterator over the top-level values of a JSON file, returned by `handle_large_json`.
+pub struct JsonValues {
+    stream: StreamDeserializer<'static, IoRead<BufReader<fs::File>>, Value>,
+    failed: bool,
+}
+
+impl Iterator for JsonValues {
+    type Item = Result<Value, ParserError>;
 
-    for value in stream {
-        match value {
-            Ok(json_value) => info!("Parsed chunk: {:?}", json_value),
-            Err(e) => error!("Error parsing chunk: {:?}", e),
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "json_value", level = "trace", skip_all)
+    )]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if cancel::is_cancelled() {
+            self.failed = true;
+            return Some(Err(ParserError::Cancelled));
+        }
+        let result = self.stream.next()?;
+        Some(result.map_err(|e| {
+            error!("Error parsing chunk: {:?}", e);
+            self.failed = true;
+            parse_error(ParseErrorDetails::from_serde(&e, ""))
+        }))
+    }
+}
+
+/// Streams the records of a dataset file to a callback.
+/// The file may contain newline-delimited JSON, concatenated JSON values, or a
+/// single top-level array whose elements are treated as records.
+///
+/// # Arguments
+///
+/// * `file_path` - The path to the dataset file.
+/// * `callback` - Called once for every record.
+///
+/// # Returns
+///
+/// * `Result<(), ParserError>` - Returns Ok if every record was read, or an error if reading or parsing fails
+///   or the call is cancelled (see `CancellationToken`).
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(callback)))]
+pub fn for_each_record<F: FnMut(Value)>(
+    file_path: &Path,
+    mut callback: F,
+) -> Result<(), ParserError> {
+    let file = fs::File::open(file_path)?;
+    let reader = std::io::BufReader::new(file);
+    for value in serde_json::Deserializer::from_reader(reader).into_iter::<Value>() {
+        match value.map_err(|e| {
+            error!("Error parsing record: {:?}", e);
+            parse_error(ParseErrorDetails::from_serde(&e, ""))
+        })? {
+            Value::Array(items) => {
+                for item in items {
+                    cancel::check().map_err(|_| ParserError::Cancelled)?;
+                    callback(item);
+                }
+            }
+            record => {
+                cancel::check().map_err(|_| ParserError::Cancelled)?;
+                callback(record);
+            }
         }
     }
     Ok(())
@@ -432,6 +1240,9 @@ pub fn handle_large_json(file_path: &Path) -> Result<(), ParserError> {
 
 /// Searches for JSON keys by a specific value, returning paths where the value is found.
 ///
+/// Only string values equal to `target_value` match; see `search` for other kinds of
+/// matches.
+///
 /// # Arguments
 ///
 /// * `json` - The JSON object to search.
@@ -441,44 +1252,190 @@ pub fn handle_large_json(file_path: &Path) -> Result<(), ParserError> {
 ///
 /// * `Vec<String>` - A list of paths where the target value is found.
 pub fn search_by_value(json: &Value, target_value: &str) -> Vec<String> {
+    search(json, target_value, &SearchOptions::default())
+        .map(|matches| matches.into_iter().map(|(path, _)| path).collect())
+        .unwrap_or_default()
+}
+
+/// Options for `search`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Treats the pattern as a regular expression matched anywhere in the text.
+    pub regex: bool,
+    /// Matches text containing the pattern instead of text equal to it.
+    pub substring: bool,
+    /// Ignores case when matching text.
+    pub ignore_case: bool,
+    /// Reads the pattern as JSON, so `42`, `true` and `null` match numbers, booleans and
+    /// nulls equal to it (`1` also matches `1.0`) rather than strings.
+    pub typed: bool,
+    /// Matches object keys instead of values, reporting each matching member.
+    pub keys: bool,
+    /// Stops after this many matches.
+    pub limit: Option<usize>,
+    /// Syntax of the reported paths.
+    pub syntax: PathSyntax,
+}
+
+/// Searches a document for values or keys matching a pattern.
+///
+/// Without options only string values equal to the pattern match. The document
+/// itself is never reported, only values nested in it.
+///
+/// # Arguments
+///
+/// * `json` - The document to search.
+/// * `pattern` - The text, regular expression or (with `typed`) JSON value to find.
+/// * `options` - How the pattern is matched.
+///
+/// # Returns
+///
+/// * `Result<Vec<(String, Value)>, Error>` - Each match with its path (as accepted by
+///   `get_by_path_with` and `options.syntax`) in document order, or an error for an
+///   invalid regular expression.
+pub fn search(
+    json: &Value,
+    pattern: &str,
+    options: &SearchOptions,
+) -> Result<Vec<(String, Value)>, Error> {
+    let typed = match serde_json::from_str::<Value>(pattern) {
+        Ok(value) if options.typed && !options.keys && !value.is_string() => Some(value),
+        _ => None,
+    };
+    let regex = options
+        .regex
+        .then(|| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(options.ignore_case)
+                .build()
+        })
+        .transpose()
+        .map_err(|e| Error::msg(format!("Invalid search pattern: {}", e)))?;
+    let needle = if options.ignore_case {
+        pattern.to_lowercase()
+    } else {
+        pattern.to_string()
+    };
+    let matches_text = |text: &str| {
+        if let Some(regex) = &regex {
+            return regex.is_match(text);
+        }
+        let text = if options.ignore_case {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        };
+        if options.substring {
+            text.contains(&needle)
+        } else {
+            text == needle
+        }
+    };
+    let matches = |key: Option<&str>, value: &Value| match (&typed, value) {
+        _ if options.keys => key.is_some_and(matches_text),
+        (Some(Value::Number(expected)), Value::Number(actual)) => {
+            expected.as_f64() == actual.as_f64()
+        }
+        (Some(expected), value) => expected == value,
+        (None, Value::String(text)) => matches_text(text),
+        (None, _) => false,
+    };
     let mut results = Vec::new();
-    search_recursive(json, target_value, &mut results, "".to_string());
-    results
+    search_recursive(
+        json,
+        "",
+        &options.syntax,
+        &matches,
+        options.limit,
+        &mut results,
+    );
+    Ok(results)
 }
 
-/// Recursive helper function for `search_by_value`, traversing JSON structure.
+/// Finds every path where a key appears, at any depth.
+///
+/// The key is a glob, where `*` matches any run of characters and `?` a single
+/// character, or a regular expression between slashes: `/^time(out|_ms)$/`.
 ///
 /// # Arguments
 ///
-/// * `json` - The JSON object to search.
-/// * `target_value` - The target value to search for.
-/// * `results` - A mutable vector to store the found paths.
-/// * `path` - The current JSON path.
-fn search_recursive(json: &Value, target_value: &str, results: &mut Vec<String>, path: String) {
-    match json {
-        Value::Object(map) => {
-            for (key, value) in map {
-                let new_path = format!("{}.{}", path, key)
-                    .trim_start_matches('.')
-                    .to_string();
-                if value.is_string() && value.as_str().unwrap() == target_value {
-                    results.push(new_path.clone());
-                }
-                search_recursive(value, target_value, results, new_path);
-            }
+/// * `json` - The document to search.
+/// * `key` - The key, glob or `/regex/`, e.g. `timeout` or `*_timeout`.
+///
+/// # Returns
+///
+/// * `Vec<String>` - The paths of the matching members in document order; an invalid
+///   regular expression matches nothing.
+pub fn search_by_key(json: &Value, key: &str) -> Vec<String> {
+    let pattern = match key.strip_prefix('/').and_then(|key| key.strip_suffix('/')) {
+        Some(regex) => regex.to_string(),
+        None => {
+            let glob: String = key
+                .chars()
+                .map(|c| match c {
+                    '*' => ".*".to_string(),
+                    '?' => ".".to_string(),
+                    c => regex::escape(&c.to_string()),
+                })
+                .collect();
+            format!("^{}$", glob)
         }
-        Value::Array(arr) => {
-            for (index, item) in arr.iter().enumerate() {
-                let new_path = format!("{}[{}]", path, index);
-                search_recursive(item, target_value, results, new_path);
-            }
+    };
+    let options = SearchOptions {
+        regex: true,
+        keys: true,
+        ..SearchOptions::default()
+    };
+    search(json, &pattern, &options)
+        .map(|matches| matches.into_iter().map(|(path, _)| path).collect())
+        .unwrap_or_default()
+}
+
+/// Recursive helper function for `search`, traversing JSON structure.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value whose children are searched.
+/// * `path` - The path of `json`.
+/// * `syntax` - The syntax the paths are written in.
+/// * `matches` - Tests a child, given its key in an object, and its value.
+/// * `limit` - Maximum number of results.
+/// * `results` - A mutable vector to store the matches.
+fn search_recursive(
+    json: &Value,
+    path: &str,
+    syntax: &PathSyntax,
+    matches: &dyn Fn(Option<&str>, &Value) -> bool,
+    limit: Option<usize>,
+    results: &mut Vec<(String, Value)>,
+) {
+    let children: Vec<(String, Option<&str>, &Value)> = match json {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| (syntax.join_key(path, key), Some(key.as_str()), value))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| (syntax.join_index(path, index), None, item))
+            .collect(),
+        _ => return,
+    };
+    for (child_path, key, value) in children {
+        if limit.is_some_and(|limit| results.len() >= limit) {
+            return;
         }
-        _ => {}
+        if matches(key, value) {
+            results.push((child_path.clone(), value.clone()));
+        }
+        search_recursive(value, &child_path, syntax, matches, limit, results);
     }
 }
 
 /// Retrieves a JSON value by a given path (e.g., "data.items[0].name").
 ///
+/// Keys containing dots or brackets are quoted: `data["weird.key"].value`.
+///
 /// # Arguments
 ///
 /// * `json` - The JSON object to search.
@@ -489,22 +1446,582 @@ fn search_recursive(json: &Value, target_value: &str, results: &mut Vec<String>,
 /// * `Option<Value>` - The found value or `None` if the path does not exist.
 pub fn get_by_path(json: &Value, json_path: &str) -> Option<Value> {
     let mut current = json;
-    let parts = json_path.split('.');
-
-    for part in parts {
-        if part.contains('[') && part.contains(']') {
-            let name = &part[..part.find('[').unwrap()];
-            let index: usize = part[part.find('[').unwrap() + 1..part.find(']').unwrap()]
-                .parse()
-                .ok()?;
-            current = current.get(name)?.get(index)?;
-        } else {
-            current = current.get(part)?;
+    for step in parse_steps(json_path).ok()? {
+        current = match step {
+            Step::Key(key) => current.get(key)?,
+            Step::Index(index) => current.get(index)?,
+        };
+    }
+    Some(current.clone())
+}
+
+/// Retrieves a JSON value by a path of the given syntax, e.g. `data/items[0]/name` or
+/// the JSON Pointer `/data/items/0/name`; the empty path is the document itself.
+///
+/// # Arguments
+///
+/// * `json` - The JSON object to search.
+/// * `json_path` - The path to the target value.
+/// * `syntax` - The syntax `json_path` is written in.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The found value or `None` if the path is invalid or does not exist.
+pub fn get_by_path_with(json: &Value, json_path: &str, syntax: &PathSyntax) -> Option<Value> {
+    if syntax.pointer {
+        return get_by_pointer(json, json_path);
+    }
+    let mut current = json;
+    for step in syntax.parse(json_path).ok()? {
+        current = match step {
+            Step::Key(key) => current.get(key)?,
+            Step::Index(index) => current.get(index)?,
+        };
+    }
+    Some(current.clone())
+}
+
+/// Selects every value matching a path with wildcards, along with its concrete path.
+///
+/// Paths use the `get_by_path` syntax plus `*` / `[*]` for every member or element and
+/// `..name` for the member `name` at any depth, e.g. `data.items[*].name` or `..id`.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to search.
+/// * `json_path` - The path to select.
+///
+/// # Returns
+///
+/// * `Vec<(String, Value)>` - Each match in document order with a path that
+///   `get_by_path` accepts, or nothing if the path is invalid.
+pub fn select_paths(json: &Value, json_path: &str) -> Vec<(String, Value)> {
+    let Ok(selectors) = parse_selectors(json_path, &PathSyntax::default()) else {
+        return Vec::new();
+    };
+    let mut nodes = vec![(String::new(), json)];
+    for selector in &selectors {
+        let mut next = Vec::new();
+        for (path, node) in nodes {
+            match selector {
+                PathSelector::Step(Step::Key(key)) => {
+                    if let Some(child) = node.get(key) {
+                        next.push((join_path_key(&path, key), child));
+                    }
+                }
+                PathSelector::Step(Step::Index(index)) => {
+                    if let Some(child) = node.as_array().and_then(|items| items.get(*index)) {
+                        next.push((format!("{}[{}]", path, index), child));
+                    }
+                }
+                PathSelector::Wildcard => push_children(&path, node, &mut next),
+                PathSelector::Descendant(key) => {
+                    let mut pending = vec![(path, node)];
+                    while let Some((path, node)) = pending.pop() {
+                        if let Some(child) = node.as_object().and_then(|map| map.get(key)) {
+                            next.push((join_path_key(&path, key), child));
+                        }
+                        let mut children = Vec::new();
+                        push_children(&path, node, &mut children);
+                        pending.extend(children.into_iter().rev());
+                    }
+                }
+            }
+        }
+        nodes = next;
+    }
+    nodes
+        .into_iter()
+        .map(|(path, value)| (path, value.clone()))
+        .collect()
+}
+
+/// Appends the members or elements of a value with their paths.
+fn push_children<'a>(path: &str, node: &'a Value, out: &mut Vec<(String, &'a Value)>) {
+    match node {
+        Value::Object(map) => out.extend(
+            map.iter()
+                .map(|(key, child)| (join_path_key(path, key), child)),
+        ),
+        Value::Array(items) => out.extend(
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, child)| (format!("{}[{}]", path, index), child)),
+        ),
+        _ => {}
+    }
+}
+
+/// Sets the value at a `get_by_path`-style path (e.g., "config.servers[0].port").
+///
+/// Missing intermediate containers are created: an object before a key and an array
+/// before an index. Arrays are padded with `null` up to a new index, by at most
+/// `MAX_ARRAY_PADDING` elements.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to modify.
+/// * `json_path` - The path to the target value.
+/// * `new_value` - The value to store.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the path is invalid or crosses a scalar, or
+///   `ParserError::IndexOutOfRange` if an index is too far past the end of its array.
+pub fn set_by_path(json: &mut Value, json_path: &str, new_value: Value) -> Result<(), Error> {
+    insert_at(json, &parse_steps(json_path)?, new_value)
+}
+
+/// Sets the value at a path of the given syntax, like `set_by_path`.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to modify.
+/// * `json_path` - The path to the target value.
+/// * `new_value` - The value to store.
+/// * `syntax` - The syntax `json_path` is written in.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the path is invalid or crosses a scalar.
+pub fn set_by_path_with(
+    json: &mut Value,
+    json_path: &str,
+    new_value: Value,
+    syntax: &PathSyntax,
+) -> Result<(), Error> {
+    if syntax.pointer {
+        return set_by_pointer(json, json_path, new_value);
+    }
+    insert_at(json, &syntax.parse(json_path)?, new_value)
+}
+
+/// Deletes the value at a `get_by_path`-style path; array elements after it shift down.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to modify.
+/// * `json_path` - The path of the value to delete.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The deleted value, or an error if the path does not exist.
+pub fn delete_by_path(json: &mut Value, json_path: &str) -> Result<Value, Error> {
+    remove_at(json, &parse_steps(json_path)?)
+        .ok_or_else(|| Error::msg(format!("Path '{}' does not exist", json_path)))
+}
+
+/// Deletes the value at a path of the given syntax, like `delete_by_path`.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to modify.
+/// * `json_path` - The path of the value to delete.
+/// * `syntax` - The syntax `json_path` is written in.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The deleted value, or an error if the path does not exist.
+pub fn delete_by_path_with(
+    json: &mut Value,
+    json_path: &str,
+    syntax: &PathSyntax,
+) -> Result<Value, Error> {
+    if syntax.pointer {
+        return remove_by_pointer(json, json_path);
+    }
+    remove_at(json, &syntax.parse(json_path)?)
+        .ok_or_else(|| Error::msg(format!("Path '{}' does not exist", json_path)))
+}
+
+/// A single step of a parsed path.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// A selector of a `select_paths` path: a concrete step, or one matching many values.
+#[derive(Debug, Clone, PartialEq)]
+enum PathSelector {
+    Step(Step),
+    /// `*` or `[*]`: every member or element.
+    Wildcard,
+    /// `..name`: the member `name` of the value and all of its descendants.
+    Descendant(String),
+}
+
+/// Parses a `get_by_path`-style path into steps.
+///
+/// Keys are separated by dots and indexes written in brackets (`a.b[0].c`). Keys
+/// containing `.`, `[`, `]`, quotes or backslashes, or equal to `*`, are written quoted
+/// in brackets, with backslash escapes: `data["weird.key"].value` or `['it\'s']`.
+pub(crate) fn parse_steps(path: &str) -> Result<Vec<Step>, Error> {
+    parse_steps_with(path, &PathSyntax::default())
+}
+
+/// Parses a path of the given (non-pointer) syntax into steps.
+pub(crate) fn parse_steps_with(path: &str, syntax: &PathSyntax) -> Result<Vec<Step>, Error> {
+    parse_selectors(path, syntax)?
+        .into_iter()
+        .map(|selector| match selector {
+            PathSelector::Step(step) => Ok(step),
+            _ => Err(Error::msg(format!(
+                "Invalid path '{}': wildcards are only supported by select_paths",
+                path
+            ))),
+        })
+        .collect()
+}
+
+/// Parses a path that may contain `*`, `[*]` and `..name` selectors, where the
+/// delimiter of the syntax takes the place of `.`.
+fn parse_selectors(path: &str, syntax: &PathSyntax) -> Result<Vec<PathSelector>, Error> {
+    let invalid = |reason: &str| Error::msg(format!("Invalid path '{}': {}", path, reason));
+    let delimiter = syntax.delimiter.as_str();
+    let at_delimiter = |rest: &str| !delimiter.is_empty() && rest.starts_with(delimiter);
+    let mut selectors = Vec::new();
+    let mut chars = path.chars();
+    let mut expect_key = true;
+    let mut descendant = false;
+    while let Some(c) = chars.clone().next() {
+        if at_delimiter(chars.as_str()) {
+            chars = chars.as_str()[delimiter.len()..].chars();
+            descendant = at_delimiter(chars.as_str());
+            if descendant {
+                chars = chars.as_str()[delimiter.len()..].chars();
+            } else if expect_key {
+                return Err(invalid("empty key"));
+            }
+            expect_key = true;
+            if chars.as_str().is_empty() {
+                return Err(invalid(&format!("trailing '{}'", delimiter)));
+            }
+            continue;
+        }
+        match c {
+            '[' => {
+                chars.next();
+                match chars.clone().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        chars.next();
+                        let mut key = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('\\') => match chars.next() {
+                                    Some('n') => key.push('\n'),
+                                    Some('t') => key.push('\t'),
+                                    Some('r') => key.push('\r'),
+                                    Some('b') => key.push('\u{8}'),
+                                    Some('f') => key.push('\u{c}'),
+                                    Some('u') => {
+                                        let hex: String = chars.by_ref().take(4).collect();
+                                        let escaped = u32::from_str_radix(&hex, 16)
+                                            .ok()
+                                            .and_then(char::from_u32)
+                                            .ok_or_else(|| invalid("invalid unicode escape"))?;
+                                        key.push(escaped);
+                                    }
+                                    Some(escaped) => key.push(escaped),
+                                    None => return Err(invalid("dangling escape")),
+                                },
+                                Some(c) if c == quote => break,
+                                Some(c) => key.push(c),
+                                None => return Err(invalid("unclosed quote")),
+                            }
+                        }
+                        selectors.push(if descendant {
+                            PathSelector::Descendant(key)
+                        } else {
+                            PathSelector::Step(Step::Key(key))
+                        });
+                    }
+                    _ if descendant => return Err(invalid("only a name can follow '..'")),
+                    Some('*') => {
+                        chars.next();
+                        selectors.push(PathSelector::Wildcard);
+                    }
+                    _ => {
+                        let rest = chars.as_str();
+                        let digits = &rest[..rest.find(']').unwrap_or(rest.len())];
+                        chars = rest[digits.len()..].chars();
+                        let index = digits
+                            .trim()
+                            .parse()
+                            .map_err(|_| invalid(&format!("invalid index '{}'", digits)))?;
+                        selectors.push(PathSelector::Step(Step::Index(index)));
+                    }
+                }
+                if chars.next() != Some(']') {
+                    return Err(invalid("unclosed bracket"));
+                }
+                expect_key = false;
+                descendant = false;
+            }
+            _ if expect_key => {
+                let rest = chars.as_str();
+                let end = (0..rest.len())
+                    .find(|&at| {
+                        rest.is_char_boundary(at)
+                            && (rest[at..].starts_with('[') || at_delimiter(&rest[at..]))
+                    })
+                    .unwrap_or(rest.len());
+                let key = &rest[..end];
+                chars = rest[end..].chars();
+                let segment_index = (syntax.brackets == BracketStyle::None)
+                    .then(|| pointer_index(key))
+                    .flatten();
+                selectors.push(match (descendant, key, segment_index) {
+                    (_, "", _) => return Err(invalid("empty key")),
+                    (true, "*", _) => return Err(invalid("only a name can follow '..'")),
+                    (true, _, _) => PathSelector::Descendant(key.to_string()),
+                    (false, "*", _) => PathSelector::Wildcard,
+                    (false, _, Some(index)) => PathSelector::Step(Step::Index(index)),
+                    (false, _, None) => PathSelector::Step(Step::Key(key.to_string())),
+                });
+                expect_key = false;
+                descendant = false;
+            }
+            _ => return Err(invalid(&format!("unexpected '{}'", c))),
         }
     }
+    if selectors.is_empty() {
+        return Err(Error::msg("Empty path"));
+    }
+    Ok(selectors)
+}
+
+/// Appends an object key to a `get_by_path`-style path, quoting it in brackets when it
+/// contains characters that would otherwise split it.
+pub(crate) fn join_path_key(path: &str, key: &str) -> String {
+    PathSyntax::default().join_key(path, key)
+}
+
+/// Returns a mutable reference to the value at the given steps, if it exists.
+pub(crate) fn get_at_mut<'a>(value: &'a mut Value, steps: &[Step]) -> Option<&'a mut Value> {
+    steps.iter().try_fold(value, |current, step| match step {
+        Step::Key(key) => current.get_mut(key.as_str()),
+        Step::Index(index) => current.get_mut(*index),
+    })
+}
+
+/// Removes and returns the value at the given steps, if it exists.
+pub(crate) fn remove_at(value: &mut Value, steps: &[Step]) -> Option<Value> {
+    let (last, parent_steps) = steps.split_last()?;
+    let parent = get_at_mut(value, parent_steps)?;
+    match (last, parent) {
+        (Step::Key(key), Value::Object(map)) => map.remove(key),
+        (Step::Index(index), Value::Array(items)) if *index < items.len() => {
+            Some(items.remove(*index))
+        }
+        _ => None,
+    }
+}
+
+/// Inserts a value at the given steps, creating intermediate objects for missing keys
+/// and arrays for missing indexes, padded with at most `MAX_ARRAY_PADDING` nulls.
+pub(crate) fn insert_at(value: &mut Value, steps: &[Step], new_value: Value) -> Result<(), Error> {
+    let mut current = value;
+    for step in steps {
+        current = match step {
+            Step::Key(key) => {
+                if current.is_null() {
+                    *current = Value::Object(Map::new());
+                }
+                current
+                    .as_object_mut()
+                    .ok_or_else(|| Error::msg(format!("Cannot set key '{}' in a non-object", key)))?
+                    .entry(key.clone())
+                    .or_insert(Value::Null)
+            }
+            Step::Index(index) => {
+                if current.is_null() {
+                    *current = Value::Array(Vec::new());
+                }
+                let items = current.as_array_mut().ok_or_else(|| {
+                    Error::msg(format!("Cannot set index {} in a non-array", index))
+                })?;
+                if *index > items.len() + MAX_ARRAY_PADDING {
+                    return Err(ParserError::IndexOutOfRange {
+                        index: *index,
+                        len: items.len(),
+                    }
+                    .into());
+                }
+                if *index >= items.len() {
+                    items.resize(index + 1, Value::Null);
+                }
+                &mut items[*index]
+            }
+        };
+    }
+    *current = new_value;
+    Ok(())
+}
+
+/// Splits a JSON Pointer (RFC 6901) into unescaped reference tokens.
+pub(crate) fn pointer_tokens(pointer: &str) -> Result<Vec<String>, Error> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rest = pointer
+        .strip_prefix('/')
+        .ok_or_else(|| Error::msg(format!("JSON Pointer must start with '/': {}", pointer)))?;
+    rest.split('/')
+        .map(|token| {
+            let mut unescaped = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    unescaped.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => unescaped.push('~'),
+                    Some('1') => unescaped.push('/'),
+                    _ => {
+                        return Err(Error::msg(format!(
+                            "Invalid escape sequence in JSON Pointer: {}",
+                            pointer
+                        )))
+                    }
+                }
+            }
+            Ok(unescaped)
+        })
+        .collect()
+}
+
+/// Parses an array index token, rejecting leading zeros and signs as RFC 6901 requires.
+pub(crate) fn pointer_index(token: &str) -> Option<usize> {
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    if valid {
+        token.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Returns a mutable reference to the value referenced by the given tokens.
+pub(crate) fn pointer_target_mut<'a>(
+    json: &'a mut Value,
+    tokens: &[String],
+) -> Option<&'a mut Value> {
+    tokens
+        .iter()
+        .try_fold(json, |current, token| match current {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(items) => items.get_mut(pointer_index(token)?),
+            _ => None,
+        })
+}
+
+/// Retrieves a JSON value by a JSON Pointer (RFC 6901), e.g. "/data/items/0/name".
+///
+/// Unlike `get_by_path`, keys containing dots or slashes can be addressed
+/// using the `~0` (for `~`) and `~1` (for `/`) escapes.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to search.
+/// * `pointer` - The JSON Pointer; the empty string refers to the whole document.
+///
+/// # Returns
+///
+/// * `Option<Value>` - The found value or `None` if the pointer is invalid or does not resolve.
+pub fn get_by_pointer(json: &Value, pointer: &str) -> Option<Value> {
+    let tokens = pointer_tokens(pointer).ok()?;
+    let mut current = json;
+    for token in &tokens {
+        current = match current {
+            Value::Object(map) => map.get(token)?,
+            Value::Array(items) => items.get(pointer_index(token)?)?,
+            _ => return None,
+        };
+    }
     Some(current.clone())
 }
 
+/// Sets the value referenced by a JSON Pointer (RFC 6901).
+///
+/// Object members are inserted or replaced. Array elements are replaced, and the
+/// `-` token (or an index equal to the length) appends. The parent must already exist.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to modify.
+/// * `pointer` - The JSON Pointer; the empty string replaces the whole document.
+/// * `new_value` - The value to store.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the pointer is invalid or its parent does not exist.
+pub fn set_by_pointer(json: &mut Value, pointer: &str, new_value: Value) -> Result<(), Error> {
+    let tokens = pointer_tokens(pointer)?;
+    let Some((last, parent_tokens)) = tokens.split_last() else {
+        *json = new_value;
+        return Ok(());
+    };
+    let parent = pointer_target_mut(json, parent_tokens)
+        .ok_or_else(|| Error::msg(format!("Parent of '{}' does not exist", pointer)))?;
+    match parent {
+        Value::Object(map) => {
+            map.insert(last.clone(), new_value);
+        }
+        Value::Array(items) => {
+            let index = if last == "-" {
+                items.len()
+            } else {
+                pointer_index(last)
+                    .ok_or_else(|| Error::msg(format!("Invalid array index '{}'", last)))?
+            };
+            if index < items.len() {
+                items[index] = new_value;
+            } else if index == items.len() {
+                items.push(new_value);
+            } else {
+                return Err(Error::msg(format!("Array index {} out of bounds", index)));
+            }
+        }
+        _ => {
+            return Err(Error::msg(format!(
+                "Parent of '{}' is not an object or array",
+                pointer
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Removes the value referenced by a JSON Pointer (RFC 6901).
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to modify.
+/// * `pointer` - The JSON Pointer of the value to remove; it cannot be the empty string.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The removed value, or an error if the pointer does not resolve.
+pub fn remove_by_pointer(json: &mut Value, pointer: &str) -> Result<Value, Error> {
+    let tokens = pointer_tokens(pointer)?;
+    let (last, parent_tokens) = tokens
+        .split_last()
+        .ok_or_else(|| Error::msg("Cannot remove the document root"))?;
+    let missing = || Error::msg(format!("Path '{}' does not exist", pointer));
+    match pointer_target_mut(json, parent_tokens).ok_or_else(missing)? {
+        Value::Object(map) => map.remove(last).ok_or_else(missing),
+        Value::Array(items) => match pointer_index(last) {
+            Some(index) if index < items.len() => Ok(items.remove(index)),
+            _ => Err(missing()),
+        },
+        _ => Err(missing()),
+    }
+}
+
 /// Minifies JSON by removing whitespace.
 ///
 /// # Arguments
@@ -514,10 +2031,248 @@ pub fn get_by_path(json: &Value, json_path: &str) -> Option<Value> {
 /// # Returns
 ///
 /// * `String` - The minified JSON string.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
 pub fn minify_json(json: &Value) -> String {
     json.to_string()
 }
 
+/// Options for `format_json`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Number of spaces per nesting level; ignored when `use_tabs` is set.
+    pub indent: usize,
+    /// Indents with one tab per nesting level instead of spaces.
+    pub use_tabs: bool,
+    /// Writes object keys in sorted order.
+    pub sort_keys: bool,
+    /// Ends the output with a newline.
+    pub trailing_newline: bool,
+    /// Keeps arrays that contain only scalars on one line (`[1, 2, 3]`).
+    pub compact_arrays: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: 2,
+            use_tabs: false,
+            sort_keys: false,
+            trailing_newline: true,
+            compact_arrays: false,
+        }
+    }
+}
+
+/// Pretty-prints JSON with a configurable style.
+///
+/// Empty objects and arrays are written as `{}` and `[]`.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to format.
+/// * `options` - Indentation, key order, trailing newline, and array layout.
+///
+/// # Returns
+///
+/// * `String` - The formatted JSON text.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn format_json(json: &Value, options: &FormatOptions) -> String {
+    let mut out = String::new();
+    format_value(json, options, 0, &mut out);
+    if options.trailing_newline {
+        out.push('\n');
+    }
+    out
+}
+
+/// Helper function for `format_json` that writes a value at the given nesting level.
+fn format_value(json: &Value, options: &FormatOptions, level: usize, out: &mut String) {
+    let indent = |out: &mut String, level: usize| {
+        if options.use_tabs {
+            out.push_str(&"\t".repeat(level));
+        } else {
+            out.push_str(&" ".repeat(level * options.indent));
+        }
+    };
+    match json {
+        Value::Object(map) if !map.is_empty() => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            if options.sort_keys {
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+            }
+            out.push_str("{\n");
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push_str(",\n");
+                }
+                indent(out, level + 1);
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push_str(": ");
+                format_value(value, options, level + 1, out);
+            }
+            out.push('\n');
+            indent(out, level);
+            out.push('}');
+        }
+        Value::Array(items)
+            if options.compact_arrays
+                && items
+                    .iter()
+                    .all(|item| !item.is_object() && !item.is_array()) =>
+        {
+            let items: Vec<String> = items.iter().map(Value::to_string).collect();
+            out.push_str(&format!("[{}]", items.join(", ")));
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(",\n");
+                }
+                indent(out, level + 1);
+                format_value(item, options, level + 1, out);
+            }
+            out.push('\n');
+            indent(out, level);
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Serializes JSON in the canonical form of RFC 8785 (JSON Canonicalization Scheme).
+///
+/// Object keys are sorted by their UTF-16 code units, no whitespace is emitted,
+/// strings use the shortest escapes, and numbers are written as IEEE 754 doubles
+/// in the ECMAScript `Number.prototype.toString` format, so that equal documents
+/// produce identical bytes, e.g. for signing.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to canonicalize.
+///
+/// # Returns
+///
+/// * `Result<String>` - The canonical JSON text, or an error for an integer that an
+///   IEEE 754 double cannot hold exactly.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn canonicalize(json: &Value) -> Result<String> {
+    let mut out = String::new();
+    canonicalize_value(json, &mut out)?;
+    Ok(out)
+}
+
+/// Helper function for `canonicalize` that writes one value.
+fn canonicalize_value(json: &Value, out: &mut String) -> Result<()> {
+    match json {
+        Value::Null | Value::Bool(_) => out.push_str(&json.to_string()),
+        Value::Number(number) => out.push_str(&canonical_number(number)?),
+        Value::String(s) => canonical_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                canonicalize_value(item, out)?;
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16()));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                canonical_string(key, out);
+                out.push(':');
+                canonicalize_value(value, out)?;
+            }
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+/// Writes a string with the escapes RFC 8785 prescribes: `\"`, `\\`, the short
+/// forms `\b \f \n \r \t`, and `\u00xx` for other control characters.
+fn canonical_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{0008}' => out.push_str("\\b"),
+            '\u{000C}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Formats a number as an ECMAScript double: integers up to 21 digits in plain
+/// notation, small and large magnitudes in exponent notation (`1e+21`, `1e-7`).
+fn canonical_number(number: &serde_json::Number) -> Result<String> {
+    let value = number
+        .as_f64()
+        .ok_or_else(|| Error::msg(format!("Number {} is not a double", number)))?;
+    let exact = match (number.as_i64(), number.as_u64()) {
+        (Some(i), _) => value as i128 == i as i128,
+        (_, Some(u)) => value as u128 == u as u128,
+        _ => true,
+    };
+    if !exact {
+        return Err(Error::msg(format!(
+            "Number {} cannot be represented exactly as an IEEE 754 double",
+            number
+        )));
+    }
+    if value == 0.0 {
+        return Ok("0".to_string());
+    }
+    // `{:e}` yields the shortest digits that round-trip, e.g. `-1.25e-7`.
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let exponent: i32 = exponent.parse().unwrap();
+    let length = digits.len() as i32;
+    let point = exponent + 1;
+    let body = if length <= point && point <= 21 {
+        format!("{}{}", digits, "0".repeat((point - length) as usize))
+    } else if 0 < point && point <= 21 {
+        format!(
+            "{}.{}",
+            &digits[..point as usize],
+            &digits[point as usize..]
+        )
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        match digits.len() {
+            1 => format!("{}e{}{}", digits, sign, exponent.abs()),
+            _ => format!(
+                "{}.{}e{}{}",
+                &digits[..1],
+                &digits[1..],
+                sign,
+                exponent.abs()
+            ),
+        }
+    };
+    Ok(if value < 0.0 {
+        format!("-{}", body)
+    } else {
+        body
+    })
+}
+
 /// Displays the structure of JSON, printing each key and nested value with indentation.
 ///
 /// # Arguments
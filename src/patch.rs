@@ -0,0 +1,156 @@
+use crate::diff::{diff_json, DiffKind};
+use crate::parser::{pointer_index, pointer_target_mut, pointer_tokens, remove_by_pointer};
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+
+/// Applies a JSON Patch (RFC 6902) to a document.
+///
+/// Supports the `add`, `remove`, `replace`, `move`, `copy`, and `test` operations.
+/// The patch is applied atomically: if any operation fails, the document is left unchanged.
+///
+/// # Arguments
+///
+/// * `json` - The JSON document to modify.
+/// * `patch` - The patch, an array of operation objects.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error naming the failing operation if the patch cannot be applied.
+pub fn apply_patch(json: &mut Value, patch: &Value) -> Result<(), Error> {
+    let operations = patch
+        .as_array()
+        .ok_or_else(|| Error::msg("JSON Patch must be an array of operations"))?;
+    let mut patched = json.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(&mut patched, operation)
+            .map_err(|e| Error::msg(format!("Patch operation {} failed: {}", index, e)))?;
+    }
+    *json = patched;
+    Ok(())
+}
+
+/// Generates a JSON Patch (RFC 6902) that transforms `old` into `new`.
+///
+/// Removals are emitted last and in reverse document order, so that removing
+/// trailing array elements does not shift the indices of later operations.
+///
+/// # Arguments
+///
+/// * `old` - The original JSON document.
+/// * `new` - The updated JSON document.
+///
+/// # Returns
+///
+/// * `Value` - An array of `add`, `remove`, and `replace` operations.
+pub fn generate_patch(old: &Value, new: &Value) -> Value {
+    let mut operations = Vec::new();
+    let mut removals = Vec::new();
+    for entry in diff_json(old, new) {
+        match entry.kind {
+            DiffKind::Added => operations
+                .push(json!({ "op": "add", "path": entry.pointer, "value": entry.new_value })),
+            DiffKind::Changed => operations
+                .push(json!({ "op": "replace", "path": entry.pointer, "value": entry.new_value })),
+            DiffKind::Removed => removals.push(json!({ "op": "remove", "path": entry.pointer })),
+        }
+    }
+    operations.extend(removals.into_iter().rev());
+    Value::Array(operations)
+}
+
+/// Applies a single patch operation.
+fn apply_operation(json: &mut Value, operation: &Value) -> Result<(), Error> {
+    let op = operation
+        .get("op")
+        .and_then(Value::as_str)
+        .ok_or_else(|| Error::msg("missing 'op'"))?;
+    let path = string_member(operation, "path")?;
+    match op {
+        "add" => add_value(json, path, member(operation, "value")?.clone()),
+        "remove" => remove_by_pointer(json, path).map(|_| ()),
+        "replace" => {
+            let tokens = pointer_tokens(path)?;
+            let target = pointer_target_mut(json, &tokens)
+                .ok_or_else(|| Error::msg(format!("path '{}' does not exist", path)))?;
+            *target = member(operation, "value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = string_member(operation, "from")?;
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(Error::msg(format!(
+                    "cannot move '{}' into its own child",
+                    from
+                )));
+            }
+            let value = remove_by_pointer(json, from)?;
+            add_value(json, path, value)
+        }
+        "copy" => {
+            let from = string_member(operation, "from")?;
+            let tokens = pointer_tokens(from)?;
+            let value = pointer_target_mut(json, &tokens)
+                .ok_or_else(|| Error::msg(format!("path '{}' does not exist", from)))?
+                .clone();
+            add_value(json, path, value)
+        }
+        "test" => {
+            let tokens = pointer_tokens(path)?;
+            let expected = member(operation, "value")?;
+            match pointer_target_mut(json, &tokens) {
+                Some(actual) if *actual == *expected => Ok(()),
+                Some(actual) => Err(Error::msg(format!(
+                    "test failed at '{}': expected {}, found {}",
+                    path, expected, actual
+                ))),
+                None => Err(Error::msg(format!("path '{}' does not exist", path))),
+            }
+        }
+        other => Err(Error::msg(format!("unknown operation '{}'", other))),
+    }
+}
+
+/// Returns a required member of an operation object.
+fn member<'a>(operation: &'a Value, name: &str) -> Result<&'a Value, Error> {
+    operation
+        .get(name)
+        .ok_or_else(|| Error::msg(format!("missing '{}'", name)))
+}
+
+/// Returns a required string member of an operation object.
+fn string_member<'a>(operation: &'a Value, name: &str) -> Result<&'a str, Error> {
+    member(operation, name)?
+        .as_str()
+        .ok_or_else(|| Error::msg(format!("'{}' must be a string", name)))
+}
+
+/// Implements `add`: inserts into arrays (shifting later elements) and sets object members.
+fn add_value(json: &mut Value, path: &str, value: Value) -> Result<(), Error> {
+    let tokens = pointer_tokens(path)?;
+    let Some((last, parent_tokens)) = tokens.split_last() else {
+        *json = value;
+        return Ok(());
+    };
+    match pointer_target_mut(json, parent_tokens) {
+        Some(Value::Object(map)) => {
+            map.insert(last.clone(), value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => {
+            let index = if last == "-" {
+                items.len()
+            } else {
+                pointer_index(last)
+                    .filter(|index| *index <= items.len())
+                    .ok_or_else(|| Error::msg(format!("invalid array index '{}'", last)))?
+            };
+            items.insert(index, value);
+            Ok(())
+        }
+        Some(_) => Err(Error::msg(format!(
+            "parent of '{}' is not a container",
+            path
+        ))),
+        None => Err(Error::msg(format!("parent of '{}' does not exist", path))),
+    }
+}
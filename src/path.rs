@@ -0,0 +1,2253 @@
+use crate::cancel;
+use crate::datetime;
+use crate::sandbox;
+use anyhow::{Error, Result};
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::Parser;
+use regex::Regex;
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// JsonPathParser struct, generated from the grammar defined in `jsonpath.pest`.
+///
+/// Supports the root `$`, dot and bracket child access (`.name`, `['name']`),
+/// wildcards (`*`), recursive descent (`..`), indexes (`[0]`, `[-1]`), unions
+/// (`[0,2]`), slices (`[0:3]`, `[::2]`), and filters (`[?(@.price > 10)]`).
+#[derive(Parser)]
+#[grammar = "jsonpath.pest"]
+struct JsonPathParser;
+
+/// A compiled JSONPath segment.
+#[derive(Debug, Clone)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+/// A selector applied to a node within a segment.
+#[derive(Debug, Clone)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Expr),
+}
+
+/// A filter expression.
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    Not(Box<Expr>),
+    Test(Operand),
+    Compare(Operand, CompareOp, Operand),
+}
+
+/// An operand of a filter comparison.
+#[derive(Debug, Clone)]
+enum Operand {
+    Literal(Value),
+    Path {
+        relative: bool,
+        segments: Vec<Segment>,
+    },
+    /// A path into the document bound to `$name`.
+    Variable {
+        name: String,
+        segments: Vec<Segment>,
+    },
+    Group(Box<Expr>),
+    Arithmetic(Box<Operand>, ArithmeticOp, Box<Operand>),
+    /// `a // b`: `b` when `a` is undefined, null or false.
+    Default(Box<Operand>, Box<Operand>),
+    /// `a?`: null when `a` is undefined.
+    Attempt(Box<Operand>),
+    /// A built-in function call, checked against `FUNCTIONS` when compiled.
+    Call(String, Vec<Operand>),
+    /// A call to a user-defined function from a `FunctionLibrary`.
+    Apply(Arc<Function>, Vec<Operand>),
+}
+
+/// Built-in functions with their minimum and maximum number of arguments.
+///
+/// Dates are passed around as seconds since the Unix epoch; date arguments also
+/// accept RFC 3339 strings and plain `YYYY-MM-DD` dates (see `datetime::parse_date`).
+/// Aggregates (see `AGGREGATES`) take any number of arguments.
+const FUNCTIONS: [(&str, usize, usize); 23] = [
+    // now(): the current time.
+    ("now", 0, 0),
+    // parse_date(text, format?): a date string as epoch seconds.
+    ("parse_date", 1, 2),
+    // date_diff(end, start, unit?): `end - start` in seconds, minutes, hours, days or weeks.
+    ("date_diff", 2, 3),
+    // format_date(date, format?, offset?): a date as text, RFC 3339 in UTC by default.
+    ("format_date", 1, 3),
+    // to_timezone(date, offset): a date as RFC 3339 text at a UTC offset such as `+02:00`.
+    ("to_timezone", 2, 2),
+    // split(text, separator): the parts of a string as an array.
+    ("split", 2, 2),
+    // join(array, separator): array items as one string; `null` items are empty.
+    ("join", 2, 2),
+    // lower(text) and upper(text): the string in lower or upper case.
+    ("lower", 1, 1),
+    ("upper", 1, 1),
+    // trim(text): the string without leading and trailing whitespace.
+    ("trim", 1, 1),
+    // replace(text, from, to): every occurrence of `from` replaced by `to`.
+    ("replace", 3, 3),
+    // matches(text, pattern): whether a regular expression matches part of the string.
+    ("matches", 2, 2),
+    // substring(text, start, length?): characters from `start` (negative counts from the end).
+    ("substring", 2, 3),
+    // len(value): characters in a string, items in an array or members in an object.
+    ("len", 1, 1),
+    // round(number, digits?): rounded half away from zero to `digits` decimal places.
+    ("round", 1, 2),
+    // floor(number), ceil(number) and abs(number).
+    ("floor", 1, 1),
+    ("ceil", 1, 1),
+    ("abs", 1, 1),
+    // Aggregates over numbers, ignoring nulls: min, max, sum, avg; count counts non-null values.
+    ("min", 1, usize::MAX),
+    ("max", 1, usize::MAX),
+    ("sum", 1, usize::MAX),
+    ("avg", 1, usize::MAX),
+    ("count", 1, usize::MAX),
+];
+
+/// Functions whose arguments are gathered into one list: a path contributes every
+/// node it selects and an array contributes its items, so `sum(@.items[*].price)`,
+/// `max(@.scores)` and `min(@.a, @.b)` all work.
+const AGGREGATES: [&str; 5] = ["min", "max", "sum", "avg", "count"];
+
+/// Arithmetic operators allowed in filters and expressions.
+#[derive(Debug, Clone, Copy)]
+enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// Comparison operators allowed in filters.
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// The position of a selected value within its parent.
+#[derive(Debug, Clone, Copy)]
+enum Step<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Secondary documents bound to `$name` variables in queries and expressions.
+pub type Variables = BTreeMap<String, Value>;
+
+/// Evaluation scope used when no variables are bound.
+static NO_VARIABLES: Variables = BTreeMap::new();
+
+/// The documents `$` and `$name` refer to during evaluation.
+#[derive(Debug, Clone, Copy)]
+struct Scope<'a> {
+    root: &'a Value,
+    variables: &'a Variables,
+}
+
+impl<'a> Scope<'a> {
+    fn new(root: &'a Value) -> Self {
+        Scope {
+            root,
+            variables: &NO_VARIABLES,
+        }
+    }
+}
+
+/// A compiled JSONPath query that can be evaluated against many documents.
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    /// Variable the query starts from instead of the document root.
+    variable: Option<String>,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    /// Compiles a JSONPath expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The JSONPath expression, e.g. `$.store.book[?(@.price > 10)].title`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<JsonPath, Error>` - The compiled query, or an error describing the syntax problem.
+    pub fn compile(path: &str) -> Result<JsonPath, Error> {
+        JsonPath::compile_with_functions(path, &FunctionLibrary::default())
+    }
+
+    /// Compiles a JSONPath expression whose filters may call the functions of `library`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The JSONPath expression, e.g. `$.users[?(is_adult(@))]`.
+    /// * `library` - User-defined functions, see `FunctionLibrary`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<JsonPath, Error>` - The compiled query, or an error describing the syntax
+    ///   problem or an unknown function.
+    pub fn compile_with_functions(
+        path: &str,
+        library: &FunctionLibrary,
+    ) -> Result<JsonPath, Error> {
+        let query = JsonPathParser::parse(Rule::query, path)
+            .map_err(|e| Error::msg(format!("Invalid JSONPath expression: {}", e)))?
+            .next()
+            .ok_or_else(|| Error::msg("Empty JSONPath expression"))?;
+        let mut variable = None;
+        let mut segments = Vec::new();
+        for pair in query.into_inner() {
+            match pair.as_rule() {
+                Rule::variable => variable = Some(pair.as_str()[1..].to_string()),
+                Rule::EOI => {}
+                _ => segments.push(build_segment(pair)?),
+            }
+        }
+        link_segments(&mut segments, library, None)?;
+        Ok(JsonPath { variable, segments })
+    }
+
+    /// Returns references to all values selected by the query, in document order.
+    /// Variables are unbound, so a query starting at `$name` selects nothing.
+    pub fn select<'a>(&self, json: &'a Value) -> Vec<&'a Value> {
+        self.locate(json, &NO_VARIABLES)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Returns the selected values with their normalized paths, such as
+    /// `$['store']['book'][0]`, in document order.
+    pub fn select_with_paths<'a>(&self, json: &'a Value) -> Vec<(String, &'a Value)> {
+        self.locate(json, &NO_VARIABLES)
+    }
+
+    /// Returns the selected values with their normalized paths, resolving `$name` to
+    /// the documents in `variables`, e.g. `$.orders[?(@.user == $admins[0].id)]`.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The document `$` refers to.
+    /// * `variables` - The documents bound to variable names.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<(String, &Value)>, Error>` - The results, or an error naming a variable
+    ///   the query uses but `variables` does not bind.
+    pub fn select_with_variables<'a>(
+        &self,
+        json: &'a Value,
+        variables: &'a Variables,
+    ) -> Result<Vec<(String, &'a Value)>, Error> {
+        let mut names: Vec<&str> = self.variable.iter().map(String::as_str).collect();
+        collect_segment_variables(&self.segments, &mut names);
+        if let Some(name) = names.iter().find(|name| !variables.contains_key(**name)) {
+            return Err(Error::msg(format!("Unbound variable ${}", name)));
+        }
+        Ok(self.locate(json, variables))
+    }
+
+    fn locate<'a>(&self, json: &'a Value, variables: &'a Variables) -> Vec<(String, &'a Value)> {
+        let scope = Scope {
+            root: json,
+            variables,
+        };
+        let (prefix, start) = match &self.variable {
+            Some(name) => match variables.get(name) {
+                Some(start) => (format!("${}", name), start),
+                None => return Vec::new(),
+            },
+            None => ("$".to_string(), json),
+        };
+        locate(&self.segments, start, scope)
+            .into_iter()
+            .map(|(steps, value)| {
+                (
+                    format!("{}{}", prefix, &normalized_path(&steps)[1..]),
+                    value,
+                )
+            })
+            .collect()
+    }
+
+    /// Returns why the query cannot be evaluated in a single forward pass without
+    /// loading the whole document; empty if it can (see `select_streaming`).
+    ///
+    /// A query streams while each segment selects one member by name; the first other
+    /// segment must select children one at a time (a wildcard, a filter, a non-negative
+    /// index or slice), and everything after it runs on one child at a time. Recursive
+    /// descent, unions and negative indexes or slices there, filters that refer to `$`,
+    /// and queries starting at a variable need the full document.
+    pub fn streaming_blockers(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if let Some(name) = &self.variable {
+            reasons.push(format!("query starts at the variable ${}", name));
+        }
+        let mut streaming = true;
+        for segment in &self.segments {
+            if streaming {
+                let blockers = segment_streaming_reasons(segment);
+                streaming = blockers.is_empty()
+                    && streamed_selector(segment)
+                        .is_some_and(|selector| matches!(selector, Selector::Name(_)));
+                reasons.extend(
+                    blockers
+                        .into_iter()
+                        .map(|reason| format!("{} ({})", reason, render_segment(segment))),
+                );
+            }
+            let (Segment::Child(selectors) | Segment::Descendant(selectors)) = segment;
+            if selectors
+                .iter()
+                .any(|selector| matches!(selector, Selector::Filter(expr) if refers_to_root(expr)))
+            {
+                reasons.push(format!(
+                    "filter refers to the document root ($) ({})",
+                    render_segment(segment)
+                ));
+            }
+        }
+        reasons
+    }
+
+    /// Evaluates the query while reading a JSON document, calling `callback` with each
+    /// result and its normalized path in document order.
+    ///
+    /// Only the values the query is working on are kept in memory: for
+    /// `$.orders[?(@.total > 100)].id` one order is read at a time. Queries with
+    /// `streaming_blockers` read the whole document first. Wrap files in a `BufReader`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The JSON text.
+    /// * `variables` - Documents bound to `$name` variables.
+    /// * `callback` - Called with the path and value of every result.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - An error if the text is not a single JSON value, or if a
+    ///   filter uses a variable missing from `variables`.
+    pub fn select_streaming<R: Read, F: FnMut(String, Value)>(
+        &self,
+        reader: R,
+        variables: &Variables,
+        mut callback: F,
+    ) -> Result<(), Error> {
+        let invalid = |e: serde_json::Error| Error::msg(format!("Failed to parse JSON: {}", e));
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        if self.streaming_blockers().is_empty() {
+            let mut names = Vec::new();
+            collect_segment_variables(&self.segments, &mut names);
+            if let Some(name) = names.iter().find(|name| !variables.contains_key(**name)) {
+                return Err(Error::msg(format!("Unbound variable: ${}", name)));
+            }
+            let seed = StreamSeed {
+                segments: &self.segments,
+                variables,
+                path: "$".to_string(),
+                callback: &mut callback,
+            };
+            seed.deserialize(&mut deserializer).map_err(invalid)?;
+        } else {
+            let json = Value::deserialize(&mut deserializer).map_err(invalid)?;
+            for (path, value) in self.select_with_variables(&json, variables)? {
+                callback(path, value.clone());
+            }
+        }
+        deserializer.end().map_err(invalid)
+    }
+}
+
+/// Evaluates the segments of a streamable query while deserializing a value.
+struct StreamSeed<'q, 'c> {
+    segments: &'q [Segment],
+    variables: &'q Variables,
+    /// Normalized path of the value being deserialized.
+    path: String,
+    callback: &'c mut dyn FnMut(String, Value),
+}
+
+impl StreamSeed<'_, '_> {
+    /// Selects a child that has been read into memory, evaluating the remaining
+    /// segments on it.
+    fn select_child(&mut self, selector: &Selector, step: Step, child: Value) {
+        let scope = Scope {
+            root: &child,
+            variables: self.variables,
+        };
+        let selected = match (selector, step) {
+            (Selector::Wildcard, _) => true,
+            (Selector::Name(name), Step::Key(key)) => name == key,
+            (Selector::Index(index), Step::Index(i)) => usize::try_from(*index) == Ok(i),
+            (Selector::Slice(start, end, step), Step::Index(i)) => {
+                let (i, start, step) = (i as i64, start.unwrap_or(0), step.unwrap_or(1));
+                step > 0 && i >= start && end.is_none_or(|end| i < end) && (i - start) % step == 0
+            }
+            (Selector::Filter(expr), _) => eval_filter(expr, &child, scope),
+            _ => false,
+        };
+        if !selected {
+            return;
+        }
+        let mut path = self.path.clone();
+        push_step(&mut path, step);
+        let rest = &self.segments[1..];
+        if rest.is_empty() {
+            (self.callback)(path, child);
+            return;
+        }
+        for (steps, value) in locate(rest, &child, scope) {
+            (self.callback)(
+                format!("{}{}", path, &normalized_path(&steps)[1..]),
+                value.clone(),
+            );
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for StreamSeed<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        match self.segments.first().and_then(streamed_selector) {
+            Some(_) => deserializer.deserialize_any(self),
+            None => {
+                // Nothing left to stream: evaluate the remaining segments in memory.
+                let value = Value::deserialize(deserializer)?;
+                let scope = Scope {
+                    root: &value,
+                    variables: self.variables,
+                };
+                for (steps, selected) in locate(self.segments, &value, scope) {
+                    (self.callback)(
+                        format!("{}{}", self.path, &normalized_path(&steps)[1..]),
+                        selected.clone(),
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for StreamSeed<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    // Scalars have no children to select.
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let segments = self.segments;
+        let Some(selector) = segments.first().and_then(streamed_selector) else {
+            return Ok(());
+        };
+        if matches!(selector, Selector::Name(_)) {
+            while seq.next_element::<IgnoredAny>()?.is_some() {}
+            return Ok(());
+        }
+        let mut index = 0;
+        while let Some(item) = seq.next_element::<Value>()? {
+            self.select_child(selector, Step::Index(index), item);
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let segments = self.segments;
+        let Some(selector) = segments.first().and_then(streamed_selector) else {
+            return Ok(());
+        };
+        while let Some(key) = map.next_key::<String>()? {
+            match selector {
+                Selector::Name(name) if *name == key => {
+                    let mut path = self.path.clone();
+                    push_step(&mut path, Step::Key(&key));
+                    map.next_value_seed(StreamSeed {
+                        segments: &segments[1..],
+                        variables: self.variables,
+                        path,
+                        callback: &mut *self.callback,
+                    })?;
+                }
+                Selector::Name(_) | Selector::Index(_) | Selector::Slice(..) => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+                _ => {
+                    let value = map.next_value::<Value>()?;
+                    self.select_child(selector, Step::Key(&key), value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the selector of a segment that can select children one at a time while
+/// streaming, or `None` if the segment needs all of its input.
+fn streamed_selector(segment: &Segment) -> Option<&Selector> {
+    match segment {
+        Segment::Child(selectors) if selectors.len() == 1 => {
+            Some(&selectors[0]).filter(|_| segment_streaming_reasons(segment).is_empty())
+        }
+        _ => None,
+    }
+}
+
+/// A compiled value expression, evaluated against one JSON value at a time.
+///
+/// Expressions use the filter syntax without the surrounding `?()`: paths relative
+/// to the value (`@.user.first`, or just `user.first`), absolute paths (`$.total`),
+/// literals, arithmetic (`+ - * / %`, where `+` concatenates when either side is a
+/// string), comparisons, `&&`, `||`, `!`, and the functions in `FUNCTIONS`: dates
+/// (`parse_date`, `now`, `date_diff`, `format_date`, `to_timezone`) and strings
+/// (`split`, `join`, `lower`, `upper`, `trim`, `replace`, `matches`, `substring`, `len`),
+/// math (`round`, `floor`, `ceil`, `abs`) and aggregates over every value a path
+/// selects (`min`, `max`, `sum`, `avg`, `count`).
+///
+/// Missing fields and invalid operations make an expression undefined (`None`).
+/// `a // b` falls back to `b` when `a` is undefined, null or false, and `a?` turns an
+/// undefined `a` into null, so records without a field still get a value:
+/// `@.user.nickname // @.user.name // 'anonymous'`.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    expr: Expr,
+}
+
+impl Expression {
+    /// Compiles an expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The expression, e.g. `first + " " + last` or `end - start`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Expression, Error>` - The compiled expression, or an error describing the syntax problem.
+    pub fn compile(text: &str) -> Result<Expression, Error> {
+        Expression::compile_with_functions(text, &FunctionLibrary::default())
+    }
+
+    /// Compiles an expression that may call the functions of `library`.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The expression, e.g. `normalize(name)`.
+    /// * `library` - User-defined functions, see `FunctionLibrary`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Expression, Error>` - The compiled expression, or an error describing the
+    ///   syntax problem or an unknown function.
+    pub fn compile_with_functions(
+        text: &str,
+        library: &FunctionLibrary,
+    ) -> Result<Expression, Error> {
+        let pair = JsonPathParser::parse(Rule::expression, text)
+            .map_err(|e| Error::msg(format!("Invalid expression: {}", e)))?
+            .next()
+            .and_then(|expression| expression.into_inner().next())
+            .ok_or_else(|| Error::msg("Empty expression"))?;
+        let mut expr = build_expr(pair)?;
+        link_expr(&mut expr, library, None)?;
+        Ok(Expression { expr })
+    }
+
+    /// Evaluates the expression with `@` and `$` bound to `json`.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Value>` - The result, or `None` if a path selects nothing (or several
+    ///   values) or the arithmetic is undefined, such as a division by zero.
+    pub fn evaluate(&self, json: &Value) -> Option<Value> {
+        self.evaluate_with_variables(json, &NO_VARIABLES)
+    }
+
+    /// Evaluates the expression like `evaluate`, resolving `$name` to the documents in
+    /// `variables`; paths into unbound variables select nothing.
+    pub fn evaluate_with_variables(&self, json: &Value, variables: &Variables) -> Option<Value> {
+        let scope = Scope {
+            root: json,
+            variables,
+        };
+        resolve_expr(&self.expr, json, scope)
+    }
+}
+
+/// User-defined functions that queries and expressions can call like the built-ins,
+/// so shared logic lives in one expression file instead of being copied between queries:
+///
+/// ```text
+/// # Functions may call built-ins and the functions defined before them.
+/// def normalize(text): lower(trim($text));
+/// def full_name(user): normalize($user.first + ' ' + $user.last);
+/// ```
+///
+/// Parameters are used like variables, as `$name`, and are the only variables a
+/// function body can use; fields are read from the node the function is called on.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionLibrary {
+    functions: BTreeMap<String, Arc<Function>>,
+}
+
+/// A function defined in an expression file.
+#[derive(Debug)]
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Expr,
+    /// The definition as written, used to compare libraries.
+    source: String,
+}
+
+impl PartialEq for FunctionLibrary {
+    fn eq(&self, other: &Self) -> bool {
+        self.functions.len() == other.functions.len()
+            && self
+                .functions
+                .iter()
+                .zip(&other.functions)
+                .all(|((name, a), (other_name, b))| name == other_name && a.source == b.source)
+    }
+}
+
+impl FunctionLibrary {
+    /// Parses the function definitions of an expression file into a new library.
+    ///
+    /// # Arguments
+    ///
+    /// * `definitions` - The definitions, e.g. `def normalize(text): lower(trim($text));`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<FunctionLibrary, Error>` - The library, or an error if a definition is invalid.
+    pub fn parse(definitions: &str) -> Result<FunctionLibrary, Error> {
+        let mut library = FunctionLibrary::default();
+        library.define(definitions)?;
+        Ok(library)
+    }
+
+    /// Adds the function definitions of an expression file; they may call the functions
+    /// already in the library. Nothing is added if a definition is invalid.
+    ///
+    /// # Arguments
+    ///
+    /// * `definitions` - The definitions, e.g. `def normalize(text): lower(trim($text));`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - An error if a definition does not compile, redefines a
+    ///   function, or calls a function that is not defined before it.
+    pub fn define(&mut self, definitions: &str) -> Result<(), Error> {
+        let pairs = JsonPathParser::parse(Rule::definitions, definitions)
+            .map_err(|e| Error::msg(format!("Invalid function definitions: {}", e)))?
+            .next()
+            .ok_or_else(|| Error::msg("Empty function definitions"))?;
+        let mut library = self.clone();
+        for definition in pairs.into_inner() {
+            if definition.as_rule() == Rule::EOI {
+                continue;
+            }
+            let source = definition.as_str().to_string();
+            let mut inner = definition.into_inner();
+            let name = inner
+                .next()
+                .ok_or_else(|| Error::msg("Missing function name"))?
+                .as_str()
+                .to_string();
+            if FUNCTIONS.iter().any(|(builtin, ..)| *builtin == name) {
+                return Err(Error::msg(format!(
+                    "Cannot redefine built-in function: {}",
+                    name
+                )));
+            }
+            if library.functions.contains_key(&name) {
+                return Err(Error::msg(format!("Function already defined: {}", name)));
+            }
+            let mut params: Vec<String> = Vec::new();
+            let mut body = None;
+            for pair in inner {
+                match pair.as_rule() {
+                    Rule::param => {
+                        let param = pair.as_str().trim_start_matches('$').to_string();
+                        if params.contains(&param) {
+                            return Err(Error::msg(format!(
+                                "Duplicate parameter ${} in function {}",
+                                param, name
+                            )));
+                        }
+                        params.push(param);
+                    }
+                    _ => body = Some(build_expr(pair)?),
+                }
+            }
+            let mut body = body.ok_or_else(|| Error::msg("Missing function body"))?;
+            link_expr(&mut body, &library, Some(&params))
+                .map_err(|e| Error::msg(format!("In function {}: {}", name, e)))?;
+            let function = Function {
+                name: name.clone(),
+                params,
+                body,
+                source,
+            };
+            library.functions.insert(name, Arc::new(function));
+        }
+        *self = library;
+        Ok(())
+    }
+
+    /// Returns the names of the defined functions, in alphabetical order.
+    pub fn names(&self) -> Vec<&str> {
+        self.functions.keys().map(String::as_str).collect()
+    }
+}
+
+/// Runs a JSONPath query against a JSON document.
+///
+/// # Arguments
+///
+/// * `json` - The JSON document to query.
+/// * `path` - The JSONPath expression.
+///
+/// # Returns
+///
+/// * `Result<Vec<Value>, Error>` - The selected values in document order, or an error if the expression is invalid.
+pub fn query_jsonpath(json: &Value, path: &str) -> Result<Vec<Value>, Error> {
+    let compiled = JsonPath::compile(path)?;
+    Ok(compiled.select(json).into_iter().cloned().collect())
+}
+
+/// Output format of the `query` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryFormat {
+    /// A pretty-printed JSON array of the results.
+    Json,
+    /// One result per line; strings are written without quotes.
+    Raw,
+    /// One compact JSON result per line.
+    Ndjson,
+    /// Tab-separated rows: object results share a header row of their keys, array
+    /// results become one row each, and scalars one cell each.
+    Tsv,
+    /// `path<TAB>value` lines with the normalized path and compact JSON value.
+    Paths,
+}
+
+impl FromStr for QueryFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(QueryFormat::Json),
+            "raw" => Ok(QueryFormat::Raw),
+            "ndjson" => Ok(QueryFormat::Ndjson),
+            "tsv" => Ok(QueryFormat::Tsv),
+            "paths" => Ok(QueryFormat::Paths),
+            _ => Err(Error::msg(format!(
+                "Unsupported query output format: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Formats query results for shell tooling.
+///
+/// # Arguments
+///
+/// * `results` - The selected values with their paths, as returned by `JsonPath::select_with_paths`.
+/// * `format` - The output format.
+///
+/// # Returns
+///
+/// * `String` - The formatted results; every format but `json` writes one line per result.
+pub fn format_query_results(results: &[(String, &Value)], format: QueryFormat) -> String {
+    let mut lines: Vec<String> = match format {
+        QueryFormat::Json => {
+            let values: Vec<&Value> = results.iter().map(|(_, value)| *value).collect();
+            return serde_json::to_string_pretty(&values).unwrap_or_default();
+        }
+        QueryFormat::Raw => results
+            .iter()
+            .map(|(_, value)| match value {
+                Value::String(text) => text.clone(),
+                value => value.to_string(),
+            })
+            .collect(),
+        QueryFormat::Ndjson => results.iter().map(|(_, value)| value.to_string()).collect(),
+        QueryFormat::Paths => results
+            .iter()
+            .map(|(path, value)| format!("{}\t{}", path, value))
+            .collect(),
+        QueryFormat::Tsv => {
+            if !results.is_empty() && results.iter().all(|(_, value)| value.is_object()) {
+                let mut columns: Vec<&String> = Vec::new();
+                for (_, value) in results {
+                    for key in value.as_object().into_iter().flat_map(|map| map.keys()) {
+                        if !columns.contains(&key) {
+                            columns.push(key);
+                        }
+                    }
+                }
+                let header = columns
+                    .iter()
+                    .map(|key| tsv_cell(&Value::from(key.as_str())));
+                let mut lines = vec![header.collect::<Vec<_>>().join("\t")];
+                lines.extend(results.iter().map(|(_, value)| {
+                    columns
+                        .iter()
+                        .map(|key| value.get(key.as_str()).map(tsv_cell).unwrap_or_default())
+                        .collect::<Vec<_>>()
+                        .join("\t")
+                }));
+                lines
+            } else {
+                results
+                    .iter()
+                    .map(|(_, value)| match value {
+                        Value::Array(items) => {
+                            items.iter().map(tsv_cell).collect::<Vec<_>>().join("\t")
+                        }
+                        value => tsv_cell(value),
+                    })
+                    .collect()
+            }
+        }
+    };
+    if !lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines.join("\n")
+}
+
+/// Renders a TSV cell: strings unquoted with tabs, line breaks and backslashes
+/// escaped, `null` as an empty cell, and other values as compact JSON.
+fn tsv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+        value => value.to_string(),
+    }
+}
+
+/// Explains a JSONPath query or a value expression, to debug why it matches nothing.
+///
+/// The report holds the parsed syntax tree (`ast`), the document paths the
+/// expression reads (`paths`, with paths inside filters resolved against the nodes
+/// being filtered), and an evaluation plan (`plan`): one step per query segment and
+/// whether it can be evaluated while streaming or needs the full document, with the
+/// reasons (see `JsonPath::streaming_blockers`); expressions are evaluated one record
+/// at a time.
+///
+/// # Arguments
+///
+/// * `text` - A JSONPath query starting with `$`, or an expression such as `price * qty > 100`.
+/// * `document` - Optional sample document; each step then reports how many values it
+///   matched, and expression paths report the value they resolve to.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The report, or an error describing the syntax problem.
+pub fn explain(text: &str, document: Option<&Value>) -> Result<Value, Error> {
+    let mut paths = Vec::new();
+    if text.trim_start().starts_with('$') {
+        let query = JsonPath::compile(text)?;
+        let render = |segments: &[Segment]| match &query.variable {
+            Some(name) => render_variable(name, segments),
+            None => render_path(false, segments),
+        };
+        // A sample document cannot stand in for a variable, so nothing is counted.
+        let document = document.filter(|_| query.variable.is_none());
+        let reasons = query.streaming_blockers();
+        let mut steps = Vec::new();
+        let mut empty_at = None;
+        for (i, segment) in query.segments.iter().enumerate() {
+            let base = render(&query.segments[..i]);
+            collect_segment_paths(segment, &base, &mut paths);
+            let mut step = json!({
+                "step": i + 1,
+                "segment": render_segment(segment),
+                "action": describe_segment(segment),
+            });
+            if let Some(document) = document {
+                let matched = evaluate(&query.segments[..=i], document, Scope::new(document)).len();
+                step["matched"] = json!(matched);
+                if matched == 0 && empty_at.is_none() {
+                    empty_at = Some(i + 1);
+                }
+            }
+            steps.push(step);
+        }
+        push_unique(&mut paths, render(&query.segments));
+        let mut plan = json!({
+            "mode": if reasons.is_empty() { "streaming" } else { "full-document" },
+            "reasons": reasons,
+            "steps": steps,
+        });
+        if let Some(document) = document {
+            plan["matched"] = json!(query.select(document).len());
+            if let Some(step) = empty_at {
+                plan["empty_at"] = json!(step);
+            }
+        }
+        Ok(json!({
+            "kind": "query",
+            "ast": query.segments.iter().map(segment_json).collect::<Vec<_>>(),
+            "paths": paths,
+            "plan": plan,
+        }))
+    } else {
+        let expression = Expression::compile(text)?;
+        collect_expr_paths(&expression.expr, "$", &mut paths);
+        let mut plan = json!({
+            "mode": "streaming",
+            "reasons": ["expressions are evaluated against one record at a time"],
+        });
+        if let Some(document) = document {
+            let mut operands = Vec::new();
+            collect_path_operands(&expression.expr, &mut operands);
+            let resolved: Vec<Value> = operands
+                .into_iter()
+                .map(|(relative, segments)| {
+                    let value = resolve_operand(
+                        &Operand::Path {
+                            relative,
+                            segments: segments.to_vec(),
+                        },
+                        document,
+                        Scope::new(document),
+                    );
+                    json!({
+                        "path": render_path(relative, segments),
+                        "found": value.is_some(),
+                        "value": value,
+                    })
+                })
+                .collect();
+            plan["operands"] = json!(resolved);
+            plan["result"] = json!(expression.evaluate(document));
+        }
+        Ok(json!({
+            "kind": "expression",
+            "ast": expr_json(&expression.expr),
+            "paths": paths,
+            "plan": plan,
+        }))
+    }
+}
+
+/// Converts a `segment` pair into a `Segment`.
+fn build_segment(pair: Pair<Rule>) -> Result<Segment, Error> {
+    let rule = pair.as_rule();
+    let selectors = pair
+        .into_inner()
+        .map(build_selector)
+        .collect::<Result<Vec<_>, Error>>()?;
+    match rule {
+        Rule::descendant => Ok(Segment::Descendant(selectors)),
+        _ => Ok(Segment::Child(selectors)),
+    }
+}
+
+/// Converts a selector pair into a `Selector`.
+fn build_selector(pair: Pair<Rule>) -> Result<Selector, Error> {
+    match pair.as_rule() {
+        Rule::wildcard => Ok(Selector::Wildcard),
+        Rule::name => Ok(Selector::Name(pair.as_str().to_string())),
+        Rule::quoted => Ok(Selector::Name(unquote(pair)?)),
+        Rule::index => Ok(Selector::Index(parse_int(pair.as_str())?)),
+        Rule::slice => {
+            let (mut start, mut end, mut step) = (None, None, None);
+            for bound in pair.into_inner() {
+                let value = Some(parse_int(bound.as_str())?);
+                match bound.as_rule() {
+                    Rule::slice_start => start = value,
+                    Rule::slice_end => end = value,
+                    _ => step = value,
+                }
+            }
+            Ok(Selector::Slice(start, end, step))
+        }
+        Rule::filter => {
+            let expr = pair
+                .into_inner()
+                .next()
+                .ok_or_else(|| Error::msg("Empty filter expression"))?;
+            Ok(Selector::Filter(build_expr(expr)?))
+        }
+        rule => Err(Error::msg(format!(
+            "Unexpected JSONPath element: {:?}",
+            rule
+        ))),
+    }
+}
+
+/// Converts an expression pair into an `Expr`.
+fn build_expr(pair: Pair<Rule>) -> Result<Expr, Error> {
+    match pair.as_rule() {
+        Rule::or_expr | Rule::and_expr => {
+            let is_or = pair.as_rule() == Rule::or_expr;
+            let mut items = pair
+                .into_inner()
+                .map(build_expr)
+                .collect::<Result<Vec<_>, Error>>()?;
+            if items.len() == 1 {
+                Ok(items.remove(0))
+            } else if is_or {
+                Ok(Expr::Or(items))
+            } else {
+                Ok(Expr::And(items))
+            }
+        }
+        Rule::unary => {
+            let mut negations = 0;
+            let mut inner = None;
+            for part in pair.into_inner() {
+                match part.as_rule() {
+                    Rule::not_op => negations += 1,
+                    _ => inner = Some(build_expr(part)?),
+                }
+            }
+            let mut expr = inner.ok_or_else(|| Error::msg("Missing filter operand"))?;
+            for _ in 0..negations {
+                expr = Expr::Not(Box::new(expr));
+            }
+            Ok(expr)
+        }
+        Rule::comparison => {
+            let mut parts = pair.into_inner();
+            let left = build_operand(
+                parts
+                    .next()
+                    .ok_or_else(|| Error::msg("Missing filter operand"))?,
+            )?;
+            match (parts.next(), parts.next()) {
+                (Some(op), Some(right)) => {
+                    let op = match op.as_str() {
+                        "==" => CompareOp::Eq,
+                        "!=" => CompareOp::Ne,
+                        "<" => CompareOp::Lt,
+                        "<=" => CompareOp::Le,
+                        ">" => CompareOp::Gt,
+                        _ => CompareOp::Ge,
+                    };
+                    Ok(Expr::Compare(left, op, build_operand(right)?))
+                }
+                _ => Ok(Expr::Test(left)),
+            }
+        }
+        rule => Err(Error::msg(format!("Unexpected filter element: {:?}", rule))),
+    }
+}
+
+/// Converts an operand pair into an `Operand`.
+fn build_operand(pair: Pair<Rule>) -> Result<Operand, Error> {
+    match pair.as_rule() {
+        Rule::rel_path | Rule::abs_path => Ok(Operand::Path {
+            relative: pair.as_rule() == Rule::rel_path,
+            segments: pair
+                .into_inner()
+                .map(build_segment)
+                .collect::<Result<Vec<_>, Error>>()?,
+        }),
+        Rule::or_expr => Ok(Operand::Group(Box::new(build_expr(pair)?))),
+        Rule::coalesce => {
+            let mut parts = pair.into_inner().map(build_operand);
+            let first = parts
+                .next()
+                .ok_or_else(|| Error::msg("Missing default operand"))??;
+            parts.try_fold(first, |operand, fallback| {
+                Ok(Operand::Default(Box::new(operand), Box::new(fallback?)))
+            })
+        }
+        Rule::attempt => {
+            let inner = pair
+                .into_inner()
+                .next()
+                .ok_or_else(|| Error::msg("Missing operand before '?'"))?;
+            Ok(Operand::Attempt(Box::new(build_operand(inner)?)))
+        }
+        Rule::sum | Rule::product => {
+            let mut parts = pair.into_inner();
+            let first = parts
+                .next()
+                .ok_or_else(|| Error::msg("Missing arithmetic operand"))?;
+            let mut operand = build_operand(first)?;
+            while let (Some(op), Some(right)) = (parts.next(), parts.next()) {
+                let op = match op.as_str() {
+                    "+" => ArithmeticOp::Add,
+                    "-" => ArithmeticOp::Sub,
+                    "*" => ArithmeticOp::Mul,
+                    "/" => ArithmeticOp::Div,
+                    _ => ArithmeticOp::Rem,
+                };
+                operand =
+                    Operand::Arithmetic(Box::new(operand), op, Box::new(build_operand(right)?));
+            }
+            Ok(operand)
+        }
+        Rule::var_path => {
+            let mut inner = pair.into_inner();
+            let name = inner
+                .next()
+                .ok_or_else(|| Error::msg("Missing variable name"))?
+                .as_str()[1..]
+                .to_string();
+            Ok(Operand::Variable {
+                name,
+                segments: inner
+                    .map(build_segment)
+                    .collect::<Result<Vec<_>, Error>>()?,
+            })
+        }
+        Rule::call => {
+            let mut inner = pair.into_inner();
+            let name = inner
+                .next()
+                .ok_or_else(|| Error::msg("Missing function name"))?
+                .as_str()
+                .to_string();
+            let args = inner
+                .map(build_operand)
+                .collect::<Result<Vec<_>, Error>>()?;
+            // Other names are looked up in the function library by `link_operand`.
+            if let Some((_, min, max)) = FUNCTIONS.iter().find(|(function, ..)| *function == name) {
+                if !(*min..=*max).contains(&args.len()) {
+                    let expected = if *max == usize::MAX {
+                        format!("at least {}", min)
+                    } else {
+                        format!("{} to {}", min, max)
+                    };
+                    return Err(Error::msg(format!(
+                        "{}() takes {} arguments, got {}",
+                        name,
+                        expected,
+                        args.len()
+                    )));
+                }
+            }
+            // Report an invalid literal pattern when compiling rather than matching nothing.
+            if name == "matches" {
+                if let Some(Operand::Group(pattern)) = args.get(1) {
+                    if let Expr::Test(Operand::Literal(Value::String(pattern))) = pattern.as_ref() {
+                        Regex::new(pattern).map_err(|e| {
+                            Error::msg(format!("Invalid pattern in matches(): {}", e))
+                        })?;
+                    }
+                }
+            }
+            Ok(Operand::Call(name, args))
+        }
+        Rule::field => Ok(Operand::Path {
+            relative: true,
+            segments: pair
+                .into_inner()
+                .map(|ident| Segment::Child(vec![Selector::Name(ident.as_str().to_string())]))
+                .collect(),
+        }),
+        Rule::number => Ok(Operand::Literal(serde_json::from_str(pair.as_str())?)),
+        Rule::quoted => Ok(Operand::Literal(Value::String(unquote(pair)?))),
+        Rule::true_lit => Ok(Operand::Literal(Value::Bool(true))),
+        Rule::false_lit => Ok(Operand::Literal(Value::Bool(false))),
+        Rule::null_lit => Ok(Operand::Literal(Value::Null)),
+        rule => Err(Error::msg(format!("Unexpected filter operand: {:?}", rule))),
+    }
+}
+
+/// Resolves the calls in some segments' filters to functions of `library`; see `link_operand`.
+fn link_segments(
+    segments: &mut [Segment],
+    library: &FunctionLibrary,
+    params: Option<&[String]>,
+) -> Result<(), Error> {
+    for segment in segments {
+        let (Segment::Child(selectors) | Segment::Descendant(selectors)) = segment;
+        for selector in selectors {
+            if let Selector::Filter(expr) = selector {
+                link_expr(expr, library, params)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves the calls in an expression to functions of `library`; see `link_operand`.
+fn link_expr(
+    expr: &mut Expr,
+    library: &FunctionLibrary,
+    params: Option<&[String]>,
+) -> Result<(), Error> {
+    match expr {
+        Expr::Or(items) | Expr::And(items) => items
+            .iter_mut()
+            .try_for_each(|item| link_expr(item, library, params)),
+        Expr::Not(inner) => link_expr(inner, library, params),
+        Expr::Test(operand) => link_operand(operand, library, params),
+        Expr::Compare(left, _, right) => {
+            link_operand(left, library, params)?;
+            link_operand(right, library, params)
+        }
+    }
+}
+
+/// Replaces calls to names that are not built-ins with calls to the functions of
+/// `library`. Within a function body, `params` are the only variables that can be used.
+fn link_operand(
+    operand: &mut Operand,
+    library: &FunctionLibrary,
+    params: Option<&[String]>,
+) -> Result<(), Error> {
+    match operand {
+        Operand::Call(name, args) => {
+            for arg in args.iter_mut() {
+                link_operand(arg, library, params)?;
+            }
+            if FUNCTIONS.iter().any(|(builtin, ..)| builtin == name) {
+                return Ok(());
+            }
+            let function = library
+                .functions
+                .get(name.as_str())
+                .cloned()
+                .ok_or_else(|| Error::msg(format!("Unknown function: {}", name)))?;
+            if args.len() != function.params.len() {
+                return Err(Error::msg(format!(
+                    "{}() takes {} arguments, got {}",
+                    name,
+                    function.params.len(),
+                    args.len()
+                )));
+            }
+            let args = std::mem::take(args);
+            *operand = Operand::Apply(function, args);
+            Ok(())
+        }
+        Operand::Apply(_, args) => args
+            .iter_mut()
+            .try_for_each(|arg| link_operand(arg, library, params)),
+        Operand::Variable { name, segments } => {
+            if params.is_some_and(|params| !params.contains(name)) {
+                return Err(Error::msg(format!("Unknown variable: ${}", name)));
+            }
+            link_segments(segments, library, params)
+        }
+        Operand::Path { segments, .. } => link_segments(segments, library, params),
+        Operand::Group(inner) => link_expr(inner, library, params),
+        Operand::Arithmetic(left, _, right) | Operand::Default(left, right) => {
+            link_operand(left, library, params)?;
+            link_operand(right, library, params)
+        }
+        Operand::Attempt(inner) => link_operand(inner, library, params),
+        Operand::Literal(_) => Ok(()),
+    }
+}
+
+/// Parses a signed integer from an index or slice bound.
+fn parse_int(text: &str) -> Result<i64, Error> {
+    text.parse()
+        .map_err(|_| Error::msg(format!("Invalid integer in JSONPath: {}", text)))
+}
+
+/// Extracts and unescapes the contents of a `quoted` pair.
+fn unquote(pair: Pair<Rule>) -> Result<String, Error> {
+    let raw = pair.into_inner().next().map(|p| p.as_str()).unwrap_or("");
+    let mut result = String::new();
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('b') => result.push('\u{0008}'),
+            Some('f') => result.push('\u{000C}'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let code = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| Error::msg(format!("Invalid unicode escape: \\u{}", hex)))?;
+                result.push(code);
+            }
+            Some(other) => result.push(other),
+            None => return Err(Error::msg("Dangling escape in quoted name")),
+        }
+    }
+    Ok(result)
+}
+
+/// Applies compiled segments starting from `start`, resolving `$` and `$name` inside
+/// filters through `scope`.
+fn evaluate<'a>(segments: &[Segment], start: &'a Value, scope: Scope<'a>) -> Vec<&'a Value> {
+    locate(segments, start, scope)
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect()
+}
+
+/// Applies compiled segments like `evaluate`, keeping the steps from `start` to
+/// each selected value.
+fn locate<'a>(
+    segments: &[Segment],
+    start: &'a Value,
+    scope: Scope<'a>,
+) -> Vec<(Vec<Step<'a>>, &'a Value)> {
+    let mut nodes = vec![(Vec::new(), start)];
+    for segment in segments {
+        let mut next = Vec::new();
+        for (path, node) in &nodes {
+            let (selectors, bases) = match segment {
+                Segment::Child(selectors) => (selectors, vec![(path.clone(), *node)]),
+                Segment::Descendant(selectors) => (selectors, descendants(path.clone(), node)),
+            };
+            for (base_path, base) in bases {
+                for selector in selectors {
+                    let mut selected = Vec::new();
+                    apply_selector(selector, base, scope, &mut selected);
+                    next.extend(selected.into_iter().map(|(step, value)| {
+                        let mut path = base_path.clone();
+                        path.push(step);
+                        (path, value)
+                    }));
+                }
+            }
+        }
+        nodes = next;
+    }
+    nodes
+}
+
+/// Returns a node and all of its descendants in document order, with their paths.
+fn descendants<'a>(path: Vec<Step<'a>>, node: &'a Value) -> Vec<(Vec<Step<'a>>, &'a Value)> {
+    let mut result = Vec::new();
+    for (step, child) in children(node) {
+        let mut child_path = path.clone();
+        child_path.push(step);
+        result.extend(descendants(child_path, child));
+    }
+    result.insert(0, (path, node));
+    result
+}
+
+/// Returns the direct children of a node: object member values or array elements.
+fn children(node: &Value) -> Vec<(Step<'_>, &Value)> {
+    match node {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| (Step::Key(key), value))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (Step::Index(i), item))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Renders steps as a normalized path such as `$['store']['book'][0]`.
+fn normalized_path(steps: &[Step]) -> String {
+    let mut text = String::from("$");
+    for step in steps {
+        push_step(&mut text, *step);
+    }
+    text
+}
+
+/// Appends a step to a normalized path.
+fn push_step(path: &mut String, step: Step) {
+    let selector = match step {
+        Step::Key(key) => render_selector(&Selector::Name(key.to_string())),
+        Step::Index(index) => index.to_string(),
+    };
+    path.push_str(&format!("[{}]", selector));
+}
+
+/// Applies a single selector to a node, appending the selected children to `out`.
+fn apply_selector<'a>(
+    selector: &Selector,
+    node: &'a Value,
+    scope: Scope<'a>,
+    out: &mut Vec<(Step<'a>, &'a Value)>,
+) {
+    if cancel::is_abandoned() {
+        return;
+    }
+    match selector {
+        Selector::Name(name) => {
+            if let Some((key, value)) = node.as_object().and_then(|map| map.get_key_value(name)) {
+                out.push((Step::Key(key), value));
+            }
+        }
+        Selector::Wildcard => out.extend(children(node)),
+        Selector::Index(index) => {
+            if let Some(items) = node.as_array() {
+                let len = items.len() as i64;
+                let position = if *index < 0 { len + index } else { *index };
+                if (0..len).contains(&position) {
+                    let position = position as usize;
+                    out.push((Step::Index(position), &items[position]));
+                }
+            }
+        }
+        Selector::Slice(start, end, step) => {
+            if let Some(items) = node.as_array() {
+                for position in slice_indices(items.len(), *start, *end, *step) {
+                    out.push((Step::Index(position), &items[position]));
+                }
+            }
+        }
+        Selector::Filter(expr) => {
+            for (step, child) in children(node) {
+                if eval_filter(expr, child, scope) {
+                    out.push((step, child));
+                }
+            }
+        }
+    }
+}
+
+/// Computes the indices selected by a slice, following Python slice semantics.
+fn slice_indices(
+    len: usize,
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+) -> Vec<usize> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    let mut indices = Vec::new();
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+        let mut i = lower;
+        while i < upper {
+            indices.push(i as usize);
+            match i.checked_add(step) {
+                Some(next) => i = next,
+                None => break,
+            }
+        }
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).clamp(-1, len - 1);
+        let mut i = upper;
+        while lower < i {
+            indices.push(i as usize);
+            match i.checked_add(step) {
+                Some(next) => i = next,
+                None => break,
+            }
+        }
+    }
+    indices
+}
+
+/// Evaluates a filter expression with `current` bound to `@`.
+fn eval_filter(expr: &Expr, current: &Value, scope: Scope) -> bool {
+    match expr {
+        Expr::Or(items) => items.iter().any(|item| eval_filter(item, current, scope)),
+        Expr::And(items) => items.iter().all(|item| eval_filter(item, current, scope)),
+        Expr::Not(inner) => !eval_filter(inner, current, scope),
+        Expr::Test(operand) => match operand {
+            Operand::Path { relative, segments } => {
+                let start = if *relative { current } else { scope.root };
+                !evaluate(segments, start, scope).is_empty()
+            }
+            Operand::Variable { name, segments } => scope
+                .variables
+                .get(name)
+                .is_some_and(|start| !evaluate(segments, start, scope).is_empty()),
+            Operand::Group(inner) => eval_filter(inner, current, scope),
+            Operand::Literal(value) => !matches!(value, Value::Null | Value::Bool(false)),
+            Operand::Arithmetic(..)
+            | Operand::Call(..)
+            | Operand::Apply(..)
+            | Operand::Default(..)
+            | Operand::Attempt(_) => !matches!(
+                resolve_operand(operand, current, scope),
+                None | Some(Value::Null | Value::Bool(false))
+            ),
+        },
+        Expr::Compare(left, op, right) => {
+            let left = resolve_operand(left, current, scope);
+            let right = resolve_operand(right, current, scope);
+            match op {
+                CompareOp::Eq => values_equal(&left, &right),
+                CompareOp::Ne => !values_equal(&left, &right),
+                CompareOp::Lt => value_less(&left, &right),
+                CompareOp::Le => value_less(&left, &right) || values_equal(&left, &right),
+                CompareOp::Gt => value_less(&right, &left),
+                CompareOp::Ge => value_less(&right, &left) || values_equal(&left, &right),
+            }
+        }
+    }
+}
+
+/// Resolves an operand to a single value. Paths selecting zero or several
+/// nodes resolve to `None`.
+fn resolve_operand(operand: &Operand, current: &Value, scope: Scope) -> Option<Value> {
+    // A sandbox that runs out stops the evaluation; `Sandbox::run` reports why.
+    sandbox::charge(1).ok()?;
+    match operand {
+        Operand::Literal(value) => Some(value.clone()),
+        Operand::Group(inner) => resolve_expr(inner, current, scope),
+        Operand::Path { relative, segments } => {
+            let start = if *relative { current } else { scope.root };
+            let nodes = evaluate(segments, start, scope);
+            sandbox::charge(nodes.len() as u64).ok()?;
+            match nodes.as_slice() {
+                [node] => Some((*node).clone()),
+                _ => None,
+            }
+        }
+        Operand::Variable { name, segments } => {
+            let nodes = evaluate(segments, scope.variables.get(name)?, scope);
+            match nodes.as_slice() {
+                [node] => Some((*node).clone()),
+                _ => None,
+            }
+        }
+        Operand::Default(operand, fallback) => match resolve_operand(operand, current, scope) {
+            None | Some(Value::Null | Value::Bool(false)) => {
+                resolve_operand(fallback, current, scope)
+            }
+            value => value,
+        },
+        Operand::Attempt(operand) => {
+            Some(resolve_operand(operand, current, scope).unwrap_or(Value::Null))
+        }
+        Operand::Arithmetic(left, op, right) => arithmetic(
+            resolve_operand(left, current, scope)?,
+            *op,
+            resolve_operand(right, current, scope)?,
+        )
+        .filter(|value| sandbox::allocate(value).is_ok()),
+        Operand::Call(name, args) if AGGREGATES.contains(&name.as_str()) => {
+            let values: Vec<Value> = args
+                .iter()
+                .flat_map(|arg| resolve_all(arg, current, scope))
+                .flat_map(|value| match value {
+                    Value::Array(items) => items,
+                    other => vec![other],
+                })
+                .collect();
+            aggregate(name, &values)
+        }
+        Operand::Call(name, args) => {
+            let args: Vec<Option<Value>> = args
+                .iter()
+                .map(|arg| resolve_operand(arg, current, scope))
+                .collect();
+            call_function(name, &args).filter(|value| sandbox::allocate(value).is_ok())
+        }
+        Operand::Apply(function, args) => {
+            // The body sees only its parameters, bound to the argument values.
+            let variables: Variables = function
+                .params
+                .iter()
+                .zip(args)
+                .filter_map(|(param, arg)| {
+                    Some((param.clone(), resolve_operand(arg, current, scope)?))
+                })
+                .collect();
+            let scope = Scope {
+                root: scope.root,
+                variables: &variables,
+            };
+            resolve_expr(&function.body, current, scope)
+        }
+    }
+}
+
+/// Resolves an expression to a value: an operand's value, or the boolean result of a
+/// logical expression or comparison.
+fn resolve_expr(expr: &Expr, current: &Value, scope: Scope) -> Option<Value> {
+    match expr {
+        Expr::Test(operand) => resolve_operand(operand, current, scope),
+        expr => Some(Value::Bool(eval_filter(expr, current, scope))),
+    }
+}
+
+/// Resolves an operand to every value it selects; operands other than paths give
+/// their single value, if any.
+fn resolve_all(operand: &Operand, current: &Value, scope: Scope) -> Vec<Value> {
+    let nodes = match operand {
+        Operand::Path { relative, segments } => evaluate(
+            segments,
+            if *relative { current } else { scope.root },
+            scope,
+        ),
+        Operand::Variable { name, segments } => match scope.variables.get(name) {
+            Some(document) => evaluate(segments, document, scope),
+            None => Vec::new(),
+        },
+        Operand::Group(inner) => match inner.as_ref() {
+            Expr::Test(operand) => return resolve_all(operand, current, scope),
+            _ => {
+                return resolve_operand(operand, current, scope)
+                    .into_iter()
+                    .collect()
+            }
+        },
+        _ => {
+            return resolve_operand(operand, current, scope)
+                .into_iter()
+                .collect()
+        }
+    };
+    nodes.into_iter().cloned().collect()
+}
+
+/// Evaluates an aggregate over its gathered values; see `AGGREGATES`. Returns `None`
+/// if a value is neither a number nor `null`, or for `min`, `max` and `avg` of nothing.
+fn aggregate(name: &str, values: &[Value]) -> Option<Value> {
+    let values: Vec<&Value> = values.iter().filter(|value| !value.is_null()).collect();
+    if name == "count" {
+        return Some(Value::from(values.len()));
+    }
+    let numbers = values
+        .iter()
+        .map(|value| value.as_f64())
+        .collect::<Option<Vec<f64>>>()?;
+    let total: f64 = numbers.iter().sum();
+    match name {
+        "sum" => number(total),
+        "avg" if !numbers.is_empty() => number(total / numbers.len() as f64),
+        "min" => number(numbers.into_iter().reduce(f64::min)?),
+        "max" => number(numbers.into_iter().reduce(f64::max)?),
+        _ => None,
+    }
+}
+
+/// Returns a number, as an integer when it is whole.
+fn number(value: f64) -> Option<Value> {
+    if value.fract() == 0.0 && value.abs() < 9e15 {
+        Some(Value::from(value as i64))
+    } else {
+        serde_json::Number::from_f64(value).map(Value::Number)
+    }
+}
+
+/// Evaluates a built-in function; see `FUNCTIONS`. Returns `None` if an argument is
+/// missing or invalid.
+fn call_function(name: &str, args: &[Option<Value>]) -> Option<Value> {
+    let arg = |i: usize| args.get(i).and_then(Option::as_ref);
+    let text = |i: usize| arg(i).and_then(Value::as_str);
+    let date = |i: usize| match arg(i)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => datetime::parse_date(s, None),
+        _ => None,
+    };
+    let float = |i: usize| arg(i).and_then(Value::as_f64);
+    match name {
+        "now" => number(datetime::now().floor()),
+        "parse_date" => number(datetime::parse_date(text(0)?, text(1))?),
+        "date_diff" => {
+            let unit = match text(2).unwrap_or("seconds") {
+                "seconds" => 1.0,
+                "minutes" => 60.0,
+                "hours" => 3600.0,
+                "days" => 86_400.0,
+                "weeks" => 604_800.0,
+                _ => return None,
+            };
+            number((date(0)? - date(1)?) / unit)
+        }
+        "format_date" => {
+            let offset = match text(2) {
+                Some(zone) => datetime::parse_offset(zone)?,
+                None => 0,
+            };
+            Some(Value::from(datetime::format_date(
+                date(0)?,
+                text(1),
+                offset,
+            )))
+        }
+        "to_timezone" => {
+            let offset = datetime::parse_offset(text(1)?)?;
+            Some(Value::from(datetime::format_date(date(0)?, None, offset)))
+        }
+        "split" => Some(Value::from(
+            text(0)?
+                .split(text(1)?)
+                .map(Value::from)
+                .collect::<Vec<_>>(),
+        )),
+        "join" => {
+            let parts: Vec<String> = arg(0)?
+                .as_array()?
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => s.clone(),
+                    Value::Null => String::new(),
+                    other => other.to_string(),
+                })
+                .collect();
+            Some(Value::from(parts.join(text(1)?)))
+        }
+        "lower" => Some(Value::from(text(0)?.to_lowercase())),
+        "upper" => Some(Value::from(text(0)?.to_uppercase())),
+        "trim" => Some(Value::from(text(0)?.trim())),
+        "replace" => Some(Value::from(text(0)?.replace(text(1)?, text(2)?))),
+        "matches" => {
+            let pattern = Regex::new(text(1)?).ok()?;
+            Some(Value::Bool(pattern.is_match(text(0)?)))
+        }
+        "substring" => {
+            let chars: Vec<char> = text(0)?.chars().collect();
+            let count = chars.len() as i64;
+            let start = arg(1)?.as_i64()?;
+            let start = if start < 0 { count + start } else { start }.clamp(0, count);
+            let end = match arg(2) {
+                Some(length) => (start + length.as_i64()?.max(0)).min(count),
+                None => count,
+            };
+            Some(Value::from(
+                chars[start as usize..end as usize]
+                    .iter()
+                    .collect::<String>(),
+            ))
+        }
+        "round" => {
+            let scale = 10f64.powi(arg(1).map_or(Some(0), Value::as_i64)?.clamp(-15, 15) as i32);
+            number((float(0)? * scale).round() / scale)
+        }
+        "floor" => number(float(0)?.floor()),
+        "ceil" => number(float(0)?.ceil()),
+        "abs" => match arg(0)? {
+            Value::Number(n) if n.is_i64() => n.as_i64()?.checked_abs().map(Value::from),
+            _ => number(float(0)?.abs()),
+        },
+        "len" => match arg(0)? {
+            Value::String(s) => Some(Value::from(s.chars().count())),
+            Value::Array(items) => Some(Value::from(items.len())),
+            Value::Object(map) => Some(Value::from(map.len())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Applies an arithmetic operator. Integers stay integers unless they overflow or
+/// are divided; `+` concatenates when either side is a string.
+fn arithmetic(left: Value, op: ArithmeticOp, right: Value) -> Option<Value> {
+    let text = |value: &Value| match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    };
+    match (&left, &right) {
+        (Value::String(_), _) | (_, Value::String(_)) => match op {
+            ArithmeticOp::Add => Some(Value::String(text(&left) + &text(&right))),
+            _ => None,
+        },
+        (Value::Number(a), Value::Number(b)) => {
+            if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+                let exact = match op {
+                    ArithmeticOp::Add => a.checked_add(b),
+                    ArithmeticOp::Sub => a.checked_sub(b),
+                    ArithmeticOp::Mul => a.checked_mul(b),
+                    ArithmeticOp::Rem => a.checked_rem(b),
+                    ArithmeticOp::Div => None,
+                };
+                if let Some(result) = exact {
+                    return Some(Value::from(result));
+                }
+            }
+            let (a, b) = (a.as_f64()?, b.as_f64()?);
+            let result = match op {
+                ArithmeticOp::Add => a + b,
+                ArithmeticOp::Sub => a - b,
+                ArithmeticOp::Mul => a * b,
+                ArithmeticOp::Div => a / b,
+                ArithmeticOp::Rem => a % b,
+            };
+            serde_json::Number::from_f64(result).map(Value::Number)
+        }
+        _ => None,
+    }
+}
+
+/// Equality used by filters: numbers compare by value, missing equals missing.
+fn values_equal(left: &Option<Value>, right: &Option<Value>) -> bool {
+    match (left, right) {
+        (None, None) => true,
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a.as_f64() == b.as_f64(),
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Ordering used by filters: only numbers and strings are comparable.
+fn value_less(left: &Option<Value>, right: &Option<Value>) -> bool {
+    match (left, right) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a.as_f64() < b.as_f64(),
+        (Some(Value::String(a)), Some(Value::String(b))) => a < b,
+        _ => false,
+    }
+}
+
+impl ArithmeticOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            ArithmeticOp::Add => "+",
+            ArithmeticOp::Sub => "-",
+            ArithmeticOp::Mul => "*",
+            ArithmeticOp::Div => "/",
+            ArithmeticOp::Rem => "%",
+        }
+    }
+}
+
+impl CompareOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+}
+
+/// Renders a path back to JSONPath text, starting at `@` or `$`.
+fn render_path(relative: bool, segments: &[Segment]) -> String {
+    let mut text = String::from(if relative { "@" } else { "$" });
+    for segment in segments {
+        text.push_str(&render_segment(segment));
+    }
+    text
+}
+
+/// Renders a path into a variable, starting at `$name`.
+fn render_variable(name: &str, segments: &[Segment]) -> String {
+    format!("${}{}", name, &render_path(true, segments)[1..])
+}
+
+/// Renders a segment in dot notation where possible, otherwise with brackets.
+fn render_segment(segment: &Segment) -> String {
+    let (prefix, selectors) = match segment {
+        Segment::Child(selectors) => (".", selectors),
+        Segment::Descendant(selectors) => ("..", selectors),
+    };
+    match selectors.as_slice() {
+        [Selector::Wildcard] if prefix == ".." => "..*".to_string(),
+        [Selector::Name(name)] if is_plain_name(name) => format!("{}{}", prefix, name),
+        _ => {
+            let rendered: Vec<String> = selectors.iter().map(render_selector).collect();
+            let prefix = if prefix == ".." { ".." } else { "" };
+            format!("{}[{}]", prefix, rendered.join(","))
+        }
+    }
+}
+
+/// Returns true if a member name can be written in dot notation.
+fn is_plain_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn render_selector(selector: &Selector) -> String {
+    let bound = |value: &Option<i64>| value.map(|v| v.to_string()).unwrap_or_default();
+    match selector {
+        Selector::Name(name) => format!("'{}'", name.replace('\\', "\\\\").replace('\'', "\\'")),
+        Selector::Wildcard => "*".to_string(),
+        Selector::Index(index) => index.to_string(),
+        Selector::Slice(start, end, None) => format!("{}:{}", bound(start), bound(end)),
+        Selector::Slice(start, end, step) => {
+            format!("{}:{}:{}", bound(start), bound(end), bound(step))
+        }
+        Selector::Filter(expr) => format!("?({})", render_filter(expr)),
+    }
+}
+
+/// Renders a filter without the group its `?(...)` parentheses produce.
+fn render_filter(expr: &Expr) -> String {
+    match expr {
+        Expr::Test(Operand::Group(inner)) => render_expr(inner),
+        expr => render_expr(expr),
+    }
+}
+
+fn render_expr(expr: &Expr) -> String {
+    let nested = |expr: &Expr| match expr {
+        Expr::Or(_) | Expr::And(_) => format!("({})", render_expr(expr)),
+        expr => render_expr(expr),
+    };
+    match expr {
+        Expr::Or(items) => items.iter().map(nested).collect::<Vec<_>>().join(" || "),
+        Expr::And(items) => items.iter().map(nested).collect::<Vec<_>>().join(" && "),
+        Expr::Not(inner) => match inner.as_ref() {
+            Expr::Compare(..) => format!("!({})", render_expr(inner)),
+            inner => format!("!{}", nested(inner)),
+        },
+        Expr::Test(operand) => render_operand(operand),
+        Expr::Compare(left, op, right) => format!(
+            "{} {} {}",
+            render_operand(left),
+            op.symbol(),
+            render_operand(right)
+        ),
+    }
+}
+
+fn render_operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Literal(value) => value.to_string(),
+        Operand::Path { relative, segments } => render_path(*relative, segments),
+        Operand::Variable { name, segments } => render_variable(name, segments),
+        Operand::Call(name, args) => {
+            let args: Vec<String> = args.iter().map(render_operand).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+        Operand::Apply(function, args) => {
+            let args: Vec<String> = args.iter().map(render_operand).collect();
+            format!("{}({})", function.name, args.join(", "))
+        }
+        Operand::Group(inner) => format!("({})", render_expr(inner)),
+        Operand::Arithmetic(left, op, right) => format!(
+            "{} {} {}",
+            render_operand(left),
+            op.symbol(),
+            render_operand(right)
+        ),
+        Operand::Default(operand, fallback) => format!(
+            "{} // {}",
+            render_operand(operand),
+            render_operand(fallback)
+        ),
+        Operand::Attempt(inner) => format!("{}?", render_operand(inner)),
+    }
+}
+
+/// Describes what a query segment does, for the evaluation plan.
+fn describe_segment(segment: &Segment) -> String {
+    let (scope, selectors) = match segment {
+        Segment::Child(selectors) => ("children", selectors),
+        Segment::Descendant(selectors) => ("all descendants", selectors),
+    };
+    let described: Vec<String> = selectors
+        .iter()
+        .map(|selector| match selector {
+            Selector::Name(name) => format!("member '{}'", name),
+            Selector::Wildcard => format!("every one of the {}", scope),
+            Selector::Index(index) if *index < 0 => {
+                format!("element {} from the end", index.unsigned_abs())
+            }
+            Selector::Index(index) => format!("element {}", index),
+            Selector::Slice(..) => format!("elements {}", render_selector(selector)),
+            Selector::Filter(expr) => format!("{} where {}", scope, render_filter(expr)),
+        })
+        .collect();
+    match segment {
+        Segment::Child(_) => format!("select {}", described.join(", ")),
+        Segment::Descendant(_) => format!("search all descendants for {}", described.join(", ")),
+    }
+}
+
+/// Lists why a segment cannot select the children of its input one at a time.
+fn segment_streaming_reasons(segment: &Segment) -> Vec<&'static str> {
+    let mut reasons = Vec::new();
+    let selectors = match segment {
+        Segment::Child(selectors) => selectors,
+        Segment::Descendant(selectors) => {
+            reasons.push("recursive descent visits every node");
+            selectors
+        }
+    };
+    if selectors.len() > 1 {
+        reasons.push("union selects children out of document order");
+    }
+    for selector in selectors {
+        match selector {
+            Selector::Index(index) if *index < 0 => {
+                reasons.push("negative index needs the array length")
+            }
+            Selector::Slice(start, end, step) => {
+                let negative = [start, end, step]
+                    .iter()
+                    .any(|bound| bound.is_some_and(|b| b < 0));
+                if negative {
+                    reasons.push("slice with negative bounds or step needs the array length");
+                }
+            }
+            _ => {}
+        }
+    }
+    reasons
+}
+
+/// Returns true if an expression contains an absolute (`$`) path.
+fn refers_to_root(expr: &Expr) -> bool {
+    let mut operands = Vec::new();
+    collect_path_operands(expr, &mut operands);
+    operands.iter().any(|(relative, _)| !relative)
+}
+
+/// Collects the paths read by the filters of a segment, resolving relative paths
+/// against the nodes being filtered.
+fn collect_segment_paths(segment: &Segment, base: &str, paths: &mut Vec<String>) {
+    let (candidates, selectors) = match segment {
+        Segment::Child(selectors) => (format!("{}[*]", base), selectors),
+        Segment::Descendant(selectors) => (format!("{}..*", base), selectors),
+    };
+    for selector in selectors {
+        if let Selector::Filter(expr) = selector {
+            collect_expr_paths(expr, &candidates, paths);
+        }
+    }
+}
+
+/// Collects the paths read by an expression, with `@` replaced by `current`.
+fn collect_expr_paths(expr: &Expr, current: &str, paths: &mut Vec<String>) {
+    let mut operands = Vec::new();
+    collect_path_operands(expr, &mut operands);
+    for (relative, segments) in operands {
+        let path = if relative {
+            format!("{}{}", current, &render_path(true, segments)[1..])
+        } else {
+            render_path(false, segments)
+        };
+        push_unique(paths, path);
+        let base = if relative {
+            current.to_string()
+        } else {
+            "$".to_string()
+        };
+        for (i, segment) in segments.iter().enumerate() {
+            let prefix = format!("{}{}", base, &render_path(true, &segments[..i])[1..]);
+            collect_segment_paths(segment, &prefix, paths);
+        }
+    }
+}
+
+/// Collects the names of the variables used by the filters of some segments.
+fn collect_segment_variables<'a>(segments: &'a [Segment], names: &mut Vec<&'a str>) {
+    fn visit_expr<'a>(expr: &'a Expr, names: &mut Vec<&'a str>) {
+        match expr {
+            Expr::Or(items) | Expr::And(items) => {
+                items.iter().for_each(|item| visit_expr(item, names))
+            }
+            Expr::Not(inner) => visit_expr(inner, names),
+            Expr::Test(operand) => visit_operand(operand, names),
+            Expr::Compare(left, _, right) => {
+                visit_operand(left, names);
+                visit_operand(right, names);
+            }
+        }
+    }
+    fn visit_operand<'a>(operand: &'a Operand, names: &mut Vec<&'a str>) {
+        match operand {
+            Operand::Variable { name, segments } => {
+                names.push(name);
+                collect_segment_variables(segments, names);
+            }
+            Operand::Path { segments, .. } => collect_segment_variables(segments, names),
+            Operand::Call(_, args) | Operand::Apply(_, args) => {
+                args.iter().for_each(|arg| visit_operand(arg, names))
+            }
+            Operand::Group(inner) => visit_expr(inner, names),
+            Operand::Arithmetic(left, _, right) | Operand::Default(left, right) => {
+                visit_operand(left, names);
+                visit_operand(right, names);
+            }
+            Operand::Attempt(inner) => visit_operand(inner, names),
+            Operand::Literal(_) => {}
+        }
+    }
+    for segment in segments {
+        let (Segment::Child(selectors) | Segment::Descendant(selectors)) = segment;
+        for selector in selectors {
+            if let Selector::Filter(expr) = selector {
+                visit_expr(expr, names);
+            }
+        }
+    }
+}
+
+/// Collects the path operands of an expression in source order.
+fn collect_path_operands<'a>(expr: &'a Expr, operands: &mut Vec<(bool, &'a [Segment])>) {
+    fn visit<'a>(operand: &'a Operand, operands: &mut Vec<(bool, &'a [Segment])>) {
+        match operand {
+            Operand::Path { relative, segments } => operands.push((*relative, segments)),
+            Operand::Group(inner) => collect_path_operands(inner, operands),
+            Operand::Call(_, args) | Operand::Apply(_, args) => {
+                args.iter().for_each(|arg| visit(arg, operands))
+            }
+            Operand::Variable { .. } => {}
+            Operand::Arithmetic(left, _, right) | Operand::Default(left, right) => {
+                visit(left, operands);
+                visit(right, operands);
+            }
+            Operand::Attempt(inner) => visit(inner, operands),
+            Operand::Literal(_) => {}
+        }
+    }
+    match expr {
+        Expr::Or(items) | Expr::And(items) => {
+            for item in items {
+                collect_path_operands(item, operands);
+            }
+        }
+        Expr::Not(inner) => collect_path_operands(inner, operands),
+        Expr::Test(operand) => visit(operand, operands),
+        Expr::Compare(left, _, right) => {
+            visit(left, operands);
+            visit(right, operands);
+        }
+    }
+}
+
+fn push_unique(paths: &mut Vec<String>, path: String) {
+    if !paths.contains(&path) {
+        paths.push(path);
+    }
+}
+
+/// Converts a segment to its JSON syntax tree.
+fn segment_json(segment: &Segment) -> Value {
+    let (kind, selectors) = match segment {
+        Segment::Child(selectors) => ("child", selectors),
+        Segment::Descendant(selectors) => ("descendant", selectors),
+    };
+    let selectors: Vec<Value> = selectors
+        .iter()
+        .map(|selector| match selector {
+            Selector::Name(name) => json!({ "type": "name", "value": name }),
+            Selector::Wildcard => json!({ "type": "wildcard" }),
+            Selector::Index(index) => json!({ "type": "index", "value": index }),
+            Selector::Slice(start, end, step) => {
+                json!({ "type": "slice", "start": start, "end": end, "step": step })
+            }
+            Selector::Filter(expr) => json!({ "type": "filter", "expr": expr_json(expr) }),
+        })
+        .collect();
+    json!({ "type": kind, "selectors": selectors })
+}
+
+/// Converts an expression to its JSON syntax tree.
+fn expr_json(expr: &Expr) -> Value {
+    match expr {
+        Expr::Or(items) => {
+            json!({ "type": "or", "operands": items.iter().map(expr_json).collect::<Vec<_>>() })
+        }
+        Expr::And(items) => {
+            json!({ "type": "and", "operands": items.iter().map(expr_json).collect::<Vec<_>>() })
+        }
+        Expr::Not(inner) => json!({ "type": "not", "operand": expr_json(inner) }),
+        Expr::Test(operand) => json!({ "type": "test", "operand": operand_json(operand) }),
+        Expr::Compare(left, op, right) => json!({
+            "type": "compare",
+            "op": op.symbol(),
+            "left": operand_json(left),
+            "right": operand_json(right),
+        }),
+    }
+}
+
+fn operand_json(operand: &Operand) -> Value {
+    match operand {
+        Operand::Literal(value) => json!({ "type": "literal", "value": value }),
+        Operand::Path { relative, segments } => json!({
+            "type": "path",
+            "path": render_path(*relative, segments),
+            "segments": segments.iter().map(segment_json).collect::<Vec<_>>(),
+        }),
+        Operand::Variable { name, segments } => json!({
+            "type": "variable",
+            "name": name,
+            "path": render_variable(name, segments),
+            "segments": segments.iter().map(segment_json).collect::<Vec<_>>(),
+        }),
+        Operand::Call(name, args) => json!({
+            "type": "call",
+            "function": name,
+            "args": args.iter().map(operand_json).collect::<Vec<_>>(),
+        }),
+        Operand::Apply(function, args) => json!({
+            "type": "call",
+            "function": function.name,
+            "user_defined": true,
+            "args": args.iter().map(operand_json).collect::<Vec<_>>(),
+        }),
+        Operand::Group(inner) => json!({ "type": "group", "expr": expr_json(inner) }),
+        Operand::Arithmetic(left, op, right) => json!({
+            "type": "arithmetic",
+            "op": op.symbol(),
+            "left": operand_json(left),
+            "right": operand_json(right),
+        }),
+        Operand::Default(operand, fallback) => json!({
+            "type": "default",
+            "operand": operand_json(operand),
+            "fallback": operand_json(fallback),
+        }),
+        Operand::Attempt(inner) => json!({ "type": "attempt", "operand": operand_json(inner) }),
+    }
+}
@@ -0,0 +1,158 @@
+use crate::parser::{parse_steps_with, pointer_index, pointer_tokens, Step};
+use anyhow::{Error, Result};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// How array indexes and awkward keys are written in a `PathSyntax`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BracketStyle {
+    /// Indexes in brackets, awkward keys quoted in brackets: `data.items[0]["a.b"]`.
+    #[default]
+    Indexes,
+    /// Every key quoted in brackets: `["data"]["items"][0]`.
+    All,
+    /// Indexes as plain segments: `data.items.0`. Keys made only of digits are quoted in
+    /// brackets (`data["0"]`), as a plain segment of digits is read as an index.
+    None,
+}
+
+/// The syntax of the paths that `get_by_path_with`, `set_by_path_with`,
+/// `delete_by_path_with`, `search`, `flatten_with` and `diff_json_with` read and write.
+///
+/// The default is the crate's dot/bracket notation, `data.items[0].name`. Keys that are
+/// empty, equal to `*`, or contain the delimiter, brackets, quotes or backslashes are
+/// written quoted in brackets: `data["weird.key"]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathSyntax {
+    /// Text between segments, e.g. `.` or `/`; when empty every key is quoted in brackets.
+    pub delimiter: String,
+    pub brackets: BracketStyle,
+    /// Writes and reads RFC 6901 JSON Pointers (`/data/items/0/name`) instead; the other
+    /// fields are ignored.
+    pub pointer: bool,
+}
+
+impl Default for PathSyntax {
+    fn default() -> Self {
+        PathSyntax::delimited(".")
+    }
+}
+
+impl FromStr for PathSyntax {
+    type Err = Error;
+
+    /// Parses `dot` (`a.b[0]`), `slash` (`a/b[0]`), `brackets` (`["a"]["b"][0]`),
+    /// `segments` (`a.b.0`) or `pointer` (`/a/b/0`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(PathSyntax::default()),
+            "slash" => Ok(PathSyntax::delimited("/")),
+            "brackets" => Ok(PathSyntax {
+                brackets: BracketStyle::All,
+                ..PathSyntax::default()
+            }),
+            "segments" => Ok(PathSyntax {
+                brackets: BracketStyle::None,
+                ..PathSyntax::default()
+            }),
+            "pointer" => Ok(PathSyntax::pointer()),
+            _ => Err(Error::msg(format!("Unsupported path syntax: {}", s))),
+        }
+    }
+}
+
+impl PathSyntax {
+    /// Creates the dot/bracket notation with another delimiter, e.g. `/` for `a/b[0]`.
+    pub fn delimited(delimiter: &str) -> Self {
+        PathSyntax {
+            delimiter: delimiter.to_string(),
+            brackets: BracketStyle::Indexes,
+            pointer: false,
+        }
+    }
+
+    /// Creates the RFC 6901 JSON Pointer syntax, `/a/b/0`.
+    pub fn pointer() -> Self {
+        PathSyntax {
+            pointer: true,
+            ..PathSyntax::default()
+        }
+    }
+
+    /// Rewrites a path of this syntax in another one, e.g. `a.b[0]` as `/a/b/0`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path written in this syntax.
+    /// * `target` - The syntax to write it in.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, Error>` - The path in `target`, or an error if `path` is invalid.
+    pub fn convert(&self, path: &str, target: &PathSyntax) -> Result<String, Error> {
+        Ok(target.format(&self.parse(path)?))
+    }
+
+    /// Splits a path into steps; the empty path is the document itself.
+    pub(crate) fn parse(&self, path: &str) -> Result<Vec<Step>, Error> {
+        if path.is_empty() {
+            Ok(Vec::new())
+        } else if self.pointer {
+            Ok(pointer_tokens(path)?
+                .into_iter()
+                .map(|token| match pointer_index(&token) {
+                    Some(index) => Step::Index(index),
+                    None => Step::Key(token),
+                })
+                .collect())
+        } else {
+            parse_steps_with(path, self)
+        }
+    }
+
+    /// Writes steps as a path.
+    pub(crate) fn format(&self, steps: &[Step]) -> String {
+        steps.iter().fold(String::new(), |path, step| match step {
+            Step::Key(key) => self.join_key(&path, key),
+            Step::Index(index) => self.join_index(&path, *index),
+        })
+    }
+
+    /// Appends an object key to a path, quoting it in brackets when it contains
+    /// characters that would otherwise split it.
+    pub(crate) fn join_key(&self, path: &str, key: &str) -> String {
+        if self.pointer {
+            return format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+        }
+        let plain = !key.is_empty()
+            && key != "*"
+            && !key.contains(['[', ']', '"', '\'', '\\'])
+            && !self.delimiter.is_empty()
+            && !key.contains(self.delimiter.as_str())
+            && match self.brackets {
+                BracketStyle::Indexes => true,
+                BracketStyle::All => false,
+                BracketStyle::None => pointer_index(key).is_none(),
+            };
+        if plain && path.is_empty() {
+            key.to_string()
+        } else if plain {
+            format!("{}{}{}", path, self.delimiter, key)
+        } else {
+            format!("{}[{}]", path, Value::from(key))
+        }
+    }
+
+    /// Appends an array index to a path.
+    pub(crate) fn join_index(&self, path: &str, index: usize) -> String {
+        if self.pointer {
+            format!("{}/{}", path, index)
+        } else if self.brackets != BracketStyle::None || self.delimiter.is_empty() {
+            format!("{}[{}]", path, index)
+        } else if path.is_empty() {
+            index.to_string()
+        } else {
+            format!("{}{}{}", path, self.delimiter, index)
+        }
+    }
+}
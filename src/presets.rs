@@ -0,0 +1,1156 @@
+use crate::stats::StreamSketches;
+use crate::validator::{validate_document, ValidationReport};
+use crate::xml::{json_to_xml, xml_to_json, XmlNodeKind, XmlOptions, TEXT_KEY};
+use anyhow::{Error, Result};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+
+/// Dependency tables of a Cargo manifest and the kind they declare.
+const CARGO_DEPENDENCY_TABLES: [(&str, &str); 3] = [
+    ("dependencies", "normal"),
+    ("dev-dependencies", "dev"),
+    ("build-dependencies", "build"),
+];
+
+/// Summarizes a Rust project from its parsed `Cargo.toml` and, optionally, `Cargo.lock`.
+///
+/// The report has the shape
+/// `{ "package": {...}, "features": {...}, "dependencies": [...], "lock": {...} }`, where
+/// every dependency lists its `name`, `kind` (`normal`, `dev`, or `build`), `requirement`,
+/// `source` (`registry`, `path`, `git`, or `workspace`), `optional`, `default_features`,
+/// `features`, and `target` for platform-specific dependencies. With a lock file,
+/// `lock.packages` maps each locked package to its versions and `lock.tree` is the resolved
+/// dependency tree of the root package, marking repeated subtrees as `deduplicated`.
+///
+/// # Arguments
+///
+/// * `manifest` - The `Cargo.toml` as JSON, e.g. from `parse_toml`.
+/// * `lock` - The `Cargo.lock` as JSON, if available.
+///
+/// # Returns
+///
+/// * `Result<Value>` - The report, or an error if the manifest is not a table.
+pub fn cargo_report(manifest: &Value, lock: Option<&Value>) -> Result<Value> {
+    let table = manifest
+        .as_object()
+        .ok_or_else(|| Error::msg("Cargo manifest must be a table"))?;
+    let package = table.get("package").cloned().unwrap_or(Value::Null);
+
+    let mut dependencies = Vec::new();
+    for (section, kind) in CARGO_DEPENDENCY_TABLES {
+        if let Some(Value::Object(deps)) = table.get(section) {
+            collect_cargo_dependencies(deps, kind, None, &mut dependencies);
+        }
+    }
+    if let Some(Value::Object(targets)) = table.get("target") {
+        for (target, sections) in targets {
+            for (section, kind) in CARGO_DEPENDENCY_TABLES {
+                if let Some(Value::Object(deps)) = sections.get(section) {
+                    collect_cargo_dependencies(deps, kind, Some(target), &mut dependencies);
+                }
+            }
+        }
+    }
+
+    let mut report = json!({
+        "package": {
+            "name": package.get("name").cloned().unwrap_or(Value::Null),
+            "version": package.get("version").cloned().unwrap_or(Value::Null),
+            "edition": package.get("edition").cloned().unwrap_or(Value::Null),
+        },
+        "features": table.get("features").cloned().unwrap_or_else(|| json!({})),
+        "dependencies": dependencies,
+    });
+    if let Some(lock) = lock {
+        let root = package.get("name").and_then(Value::as_str);
+        report["lock"] = cargo_lock_report(lock, root);
+    }
+    Ok(report)
+}
+
+/// Normalizes the entries of one dependency table.
+fn collect_cargo_dependencies(
+    deps: &Map<String, Value>,
+    kind: &str,
+    target: Option<&str>,
+    out: &mut Vec<Value>,
+) {
+    for (name, spec) in deps {
+        let detail = spec.as_object();
+        let field = |key: &str| detail.and_then(|d| d.get(key));
+        let source = if field("path").is_some() {
+            "path"
+        } else if field("git").is_some() {
+            "git"
+        } else if field("workspace") == Some(&Value::Bool(true)) {
+            "workspace"
+        } else {
+            "registry"
+        };
+        let requirement = match spec {
+            Value::String(version) => Value::String(version.clone()),
+            _ => field("version").cloned().unwrap_or(Value::Null),
+        };
+        out.push(json!({
+            "name": name,
+            "package": field("package").cloned().unwrap_or_else(|| Value::String(name.clone())),
+            "kind": kind,
+            "requirement": requirement,
+            "source": source,
+            "optional": field("optional").and_then(Value::as_bool).unwrap_or(false),
+            "default_features": field("default-features")
+                .or_else(|| field("default_features"))
+                .and_then(Value::as_bool)
+                .unwrap_or(true),
+            "features": field("features").cloned().unwrap_or_else(|| json!([])),
+            "target": target,
+        }));
+    }
+}
+
+/// Builds the package index and dependency tree of a `Cargo.lock`.
+fn cargo_lock_report(lock: &Value, root: Option<&str>) -> Value {
+    let packages: Vec<&Value> = lock
+        .get("package")
+        .and_then(Value::as_array)
+        .map(|packages| packages.iter().collect())
+        .unwrap_or_default();
+
+    let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for package in &packages {
+        if let (Some(name), Some(version)) = (
+            package.get("name").and_then(Value::as_str),
+            package.get("version").and_then(Value::as_str),
+        ) {
+            versions
+                .entry(name.to_string())
+                .or_default()
+                .push(version.to_string());
+        }
+    }
+
+    let tree = root
+        .and_then(|root| {
+            packages
+                .iter()
+                .find(|p| p.get("name").and_then(Value::as_str) == Some(root))
+        })
+        .map(|package| lock_tree(package, &packages, &mut HashSet::new()))
+        .unwrap_or(Value::Null);
+
+    json!({
+        "version": lock.get("version").cloned().unwrap_or(Value::Null),
+        "count": packages.len(),
+        "packages": versions,
+        "tree": tree,
+    })
+}
+
+/// Expands a locked package into a tree node. Packages already expanded elsewhere
+/// are emitted once more without children and marked `deduplicated`.
+fn lock_tree(package: &Value, packages: &[&Value], expanded: &mut HashSet<String>) -> Value {
+    let name = package.get("name").and_then(Value::as_str).unwrap_or("");
+    let version = package.get("version").and_then(Value::as_str).unwrap_or("");
+    let mut node = json!({ "name": name, "version": version });
+    if !expanded.insert(format!("{} {}", name, version)) {
+        node["deduplicated"] = Value::Bool(true);
+        return node;
+    }
+    let children: Vec<Value> = package
+        .get("dependencies")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .filter_map(|entry| find_locked(entry, packages))
+        .map(|child| lock_tree(child, packages, expanded))
+        .collect();
+    node["dependencies"] = Value::Array(children);
+    node
+}
+
+/// Finds the package a lock file dependency entry refers to. Entries are `name`,
+/// or `name version` (optionally followed by a source) when several versions are locked.
+fn find_locked<'a>(entry: &str, packages: &[&'a Value]) -> Option<&'a Value> {
+    let mut parts = entry.split_whitespace();
+    let name = parts.next()?;
+    let version = parts.next();
+    packages.iter().copied().find(|p| {
+        p.get("name").and_then(Value::as_str) == Some(name)
+            && version.is_none_or(|v| p.get("version").and_then(Value::as_str) == Some(v))
+    })
+}
+
+/// Dependency sections of an npm `package.json` and the kind they declare.
+const NPM_DEPENDENCY_SECTIONS: [(&str, &str); 4] = [
+    ("dependencies", "prod"),
+    ("devDependencies", "dev"),
+    ("optionalDependencies", "optional"),
+    ("peerDependencies", "peer"),
+];
+
+/// Audits an npm project from its `package.json` and, optionally, its
+/// `package-lock.json` (or `npm-shrinkwrap.json`, lockfile versions 1 to 3).
+///
+/// The report lists every declared dependency and the names declared in more than one
+/// section, groups scripts that run the same command, and for the lock file reports the
+/// installed packages, every package installed in more than one version, and the
+/// most frequently installed names from a `StreamSketches` pass over the installs.
+///
+/// # Arguments
+///
+/// * `package` - The parsed `package.json`.
+/// * `lock` - The parsed lock file, if available.
+/// * `top_k` - How many of the most frequently installed package names to report.
+///
+/// # Returns
+///
+/// * `Result<Value>` - The report, or an error if `package.json` is not an object.
+pub fn npm_audit(package: &Value, lock: Option<&Value>, top_k: usize) -> Result<Value> {
+    let manifest = package
+        .as_object()
+        .ok_or_else(|| Error::msg("package.json must be an object"))?;
+
+    let mut dependencies = Vec::new();
+    let mut sections: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (section, kind) in NPM_DEPENDENCY_SECTIONS {
+        if let Some(Value::Object(deps)) = manifest.get(section) {
+            for (name, requirement) in deps {
+                dependencies
+                    .push(json!({ "name": name, "kind": kind, "requirement": requirement }));
+                sections.entry(name).or_default().push(kind);
+            }
+        }
+    }
+    let declared_twice: Vec<Value> = sections
+        .into_iter()
+        .filter(|(_, kinds)| kinds.len() > 1)
+        .map(|(name, kinds)| json!({ "name": name, "kinds": kinds }))
+        .collect();
+
+    let mut commands: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    let scripts = manifest.get("scripts").and_then(Value::as_object);
+    for (name, command) in scripts.into_iter().flatten() {
+        if let Some(command) = command.as_str() {
+            let normalized = command.split_whitespace().collect::<Vec<_>>().join(" ");
+            commands.entry(normalized).or_default().push(name);
+        }
+    }
+    let duplicate_scripts: Vec<Value> = commands
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(command, names)| json!({ "command": command, "scripts": names }))
+        .collect();
+
+    let mut report = json!({
+        "package": {
+            "name": manifest.get("name").cloned().unwrap_or(Value::Null),
+            "version": manifest.get("version").cloned().unwrap_or(Value::Null),
+        },
+        "dependencies": dependencies,
+        "declared_twice": declared_twice,
+        "scripts": {
+            "count": scripts.map_or(0, Map::len),
+            "duplicates": duplicate_scripts,
+        },
+    });
+    if let Some(lock) = lock {
+        report["lock"] = npm_lock_report(lock, top_k);
+    }
+    Ok(report)
+}
+
+/// Reports installed packages and duplicate versions of an npm lock file.
+fn npm_lock_report(lock: &Value, top_k: usize) -> Value {
+    let mut installs = Vec::new();
+    if let Some(Value::Object(packages)) = lock.get("packages") {
+        for (location, entry) in packages {
+            let Some(index) = location.rfind("node_modules/") else {
+                continue;
+            };
+            let name = entry
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or(&location[index + "node_modules/".len()..]);
+            installs.push(npm_install(name, location, entry));
+        }
+    } else if let Some(Value::Object(dependencies)) = lock.get("dependencies") {
+        collect_npm_v1(dependencies, "", &mut installs);
+    }
+
+    let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut sketches = StreamSketches::new(&["name".to_string()], top_k);
+    for install in &installs {
+        sketches.observe(install);
+        if let (Some(name), Some(version)) = (
+            install.get("name").and_then(Value::as_str),
+            install.get("version").and_then(Value::as_str),
+        ) {
+            let seen = versions.entry(name.to_string()).or_default();
+            if !seen.iter().any(|v| v == version) {
+                seen.push(version.to_string());
+            }
+        }
+    }
+    let duplicates: Vec<Value> = versions
+        .iter_mut()
+        .filter(|(_, versions)| versions.len() > 1)
+        .map(|(name, versions)| {
+            versions.sort();
+            json!({ "name": name, "versions": versions })
+        })
+        .collect();
+    let frequency = sketches.to_json();
+
+    json!({
+        "lockfile_version": lock.get("lockfileVersion").cloned().unwrap_or(Value::Null),
+        "installs": installs.len(),
+        "packages": versions.len(),
+        "duplicates": duplicates,
+        "most_installed": frequency["paths"]["name"]["top"].clone(),
+    })
+}
+
+/// Walks the nested `dependencies` of a version 1 lock file.
+fn collect_npm_v1(dependencies: &Map<String, Value>, parent: &str, out: &mut Vec<Value>) {
+    for (name, entry) in dependencies {
+        let location = format!("{}node_modules/{}", parent, name);
+        out.push(npm_install(name, &location, entry));
+        if let Some(Value::Object(nested)) = entry.get("dependencies") {
+            collect_npm_v1(nested, &format!("{}/", location), out);
+        }
+    }
+}
+
+/// Normalizes one installed package of a lock file.
+fn npm_install(name: &str, location: &str, entry: &Value) -> Value {
+    json!({
+        "name": name,
+        "version": entry.get("version").cloned().unwrap_or(Value::Null),
+        "location": location,
+        "dev": entry.get("dev").and_then(Value::as_bool).unwrap_or(false),
+    })
+}
+
+/// Placeholder written in place of sensitive values.
+pub const REDACTED: &str = "***";
+
+/// Summarizes a Terraform state file (format version 4).
+///
+/// The report lists the `terraform_version`, `serial` and `lineage`, every provider
+/// used with its registry address, alias, resource count and schema versions, every
+/// resource instance with its address and attributes, and the outputs. When `redact`
+/// is set, outputs marked `sensitive` and instance attributes listed in
+/// `sensitive_attributes` are replaced by `"***"`.
+///
+/// # Arguments
+///
+/// * `state` - The parsed `terraform.tfstate`.
+/// * `redact` - Whether to mask sensitive values.
+///
+/// # Returns
+///
+/// * `Result<Value>` - The report, or an error if the state has no `resources` array.
+pub fn tfstate_report(state: &Value, redact: bool) -> Result<Value> {
+    let resources = state
+        .get("resources")
+        .and_then(Value::as_array)
+        .ok_or_else(|| Error::msg("Terraform state must contain a resources array"))?;
+
+    let mut providers: BTreeMap<String, (Value, usize, Vec<u64>)> = BTreeMap::new();
+    let mut instances = Vec::new();
+    for resource in resources {
+        let field = |key: &str| resource.get(key).and_then(Value::as_str).unwrap_or("");
+        let (provider, alias) = parse_provider(field("provider"));
+        let mut address = String::new();
+        if !field("module").is_empty() {
+            address.push_str(field("module"));
+            address.push('.');
+        }
+        if field("mode") == "data" {
+            address.push_str("data.");
+        }
+        address.push_str(&format!("{}.{}", field("type"), field("name")));
+
+        let entry = providers
+            .entry(format!(
+                "{}{}",
+                provider,
+                alias
+                    .as_deref()
+                    .map(|a| format!(".{}", a))
+                    .unwrap_or_default()
+            ))
+            .or_insert_with(|| {
+                (
+                    json!({ "address": provider, "alias": alias }),
+                    0,
+                    Vec::new(),
+                )
+            });
+        for instance in resource
+            .get("instances")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            entry.1 += 1;
+            if let Some(version) = instance.get("schema_version").and_then(Value::as_u64) {
+                if !entry.2.contains(&version) {
+                    entry.2.push(version);
+                }
+            }
+            let instance_address = match instance.get("index_key") {
+                Some(Value::Number(n)) => format!("{}[{}]", address, n),
+                Some(Value::String(key)) => format!("{}[{}]", address, Value::String(key.clone())),
+                _ => address.clone(),
+            };
+            let mut attributes = instance.get("attributes").cloned().unwrap_or(Value::Null);
+            if redact {
+                for path in instance
+                    .get("sensitive_attributes")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    redact_state_path(&mut attributes, path);
+                }
+            }
+            instances.push(json!({
+                "address": instance_address,
+                "mode": field("mode"),
+                "type": field("type"),
+                "name": field("name"),
+                "module": resource.get("module").cloned().unwrap_or(Value::Null),
+                "provider": provider,
+                "attributes": attributes,
+                "dependencies": instance.get("dependencies").cloned().unwrap_or_else(|| json!([])),
+            }));
+        }
+    }
+
+    let mut outputs = Map::new();
+    for (name, output) in state
+        .get("outputs")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+    {
+        let sensitive = output
+            .get("sensitive")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let value = if sensitive && redact {
+            Value::String(REDACTED.to_string())
+        } else {
+            output.get("value").cloned().unwrap_or(Value::Null)
+        };
+        outputs.insert(
+            name.clone(),
+            json!({
+                "type": output.get("type").cloned().unwrap_or(Value::Null),
+                "sensitive": sensitive,
+                "value": value,
+            }),
+        );
+    }
+
+    let providers: Vec<Value> = providers
+        .into_values()
+        .map(|(mut provider, resources, mut schema_versions)| {
+            schema_versions.sort_unstable();
+            provider["resources"] = json!(resources);
+            provider["schema_versions"] = json!(schema_versions);
+            provider
+        })
+        .collect();
+    Ok(json!({
+        "terraform_version": state.get("terraform_version").cloned().unwrap_or(Value::Null),
+        "version": state.get("version").cloned().unwrap_or(Value::Null),
+        "serial": state.get("serial").cloned().unwrap_or(Value::Null),
+        "lineage": state.get("lineage").cloned().unwrap_or(Value::Null),
+        "providers": providers,
+        "resources": instances,
+        "outputs": outputs,
+    }))
+}
+
+/// Splits a provider reference such as `provider["registry.terraform.io/hashicorp/aws"].west`
+/// into its registry address and alias.
+fn parse_provider(reference: &str) -> (String, Option<String>) {
+    let Some(start) = reference.find("[\"") else {
+        return (reference.to_string(), None);
+    };
+    let rest = &reference[start + 2..];
+    let Some(end) = rest.find("\"]") else {
+        return (reference.to_string(), None);
+    };
+    let alias = rest[end + 2..].strip_prefix('.').map(str::to_string);
+    (rest[..end].to_string(), alias)
+}
+
+/// Masks the attribute a Terraform `sensitive_attributes` path points to. Paths are
+/// arrays of `{"type": "get_attr", "value": "name"}` and
+/// `{"type": "index", "value": {"value": 0, "type": "number"}}` steps.
+fn redact_state_path(attributes: &mut Value, path: &Value) {
+    let mut node = attributes;
+    for step in path.as_array().into_iter().flatten() {
+        let next = match (step.get("type").and_then(Value::as_str), step.get("value")) {
+            (Some("get_attr"), Some(Value::String(key))) => node.get_mut(key.as_str()),
+            (Some("index"), Some(index)) => match index.get("value") {
+                Some(Value::Number(n)) => n.as_u64().and_then(|i| node.get_mut(i as usize)),
+                Some(Value::String(key)) => node.get_mut(key.as_str()),
+                _ => None,
+            },
+            _ => None,
+        };
+        match next {
+            Some(next) => node = next,
+            None => return,
+        }
+    }
+    *node = Value::String(REDACTED.to_string());
+}
+
+/// Version URL identifying JSON Feed 1.1 documents.
+pub const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// Namespace of Atom 1.0 documents.
+pub const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+
+/// Month abbreviations used by RFC 822 dates in RSS.
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Weekday abbreviations used by RFC 822 dates, starting on Monday.
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Syndication XML formats a JSON Feed converts to and from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    /// RSS 2.0.
+    Rss,
+    /// Atom 1.0.
+    Atom,
+}
+
+impl FromStr for FeedFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rss" => Ok(FeedFormat::Rss),
+            "atom" => Ok(FeedFormat::Atom),
+            _ => Err(Error::msg(format!("Unsupported feed format: {}", s))),
+        }
+    }
+}
+
+/// Returns the JSON Schema of a JSON Feed 1.1 document.
+fn json_feed_schema() -> Value {
+    let date = json!({
+        "type": "string",
+        "pattern": "^\\d{4}-\\d{2}-\\d{2}[Tt]\\d{2}:\\d{2}:\\d{2}(\\.\\d+)?([Zz]|[+-]\\d{2}:\\d{2})$"
+    });
+    let url = json!({ "type": "string", "pattern": "^https?://" });
+    json!({
+        "type": "object",
+        "required": ["version", "title", "items"],
+        "properties": {
+            "version": { "type": "string", "pattern": "^https://jsonfeed\\.org/version/1(\\.1)?$" },
+            "title": { "type": "string" },
+            "home_page_url": url,
+            "feed_url": url,
+            "description": { "type": "string" },
+            "user_comment": { "type": "string" },
+            "next_url": url,
+            "icon": { "type": "string" },
+            "favicon": { "type": "string" },
+            "language": { "type": "string" },
+            "expired": { "type": "boolean" },
+            "authors": { "type": "array", "items": { "$ref": "#/$defs/author" } },
+            "items": { "type": "array", "items": { "$ref": "#/$defs/item" } }
+        },
+        "$defs": {
+            "author": {
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "url": { "type": "string" },
+                    "avatar": { "type": "string" }
+                },
+                "anyOf": [
+                    { "required": ["name"] },
+                    { "required": ["url"] },
+                    { "required": ["avatar"] }
+                ]
+            },
+            "item": {
+                "type": "object",
+                "required": ["id"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "url": { "type": "string" },
+                    "external_url": { "type": "string" },
+                    "title": { "type": "string" },
+                    "content_html": { "type": "string" },
+                    "content_text": { "type": "string" },
+                    "summary": { "type": "string" },
+                    "image": { "type": "string" },
+                    "banner_image": { "type": "string" },
+                    "date_published": date,
+                    "date_modified": date,
+                    "language": { "type": "string" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "authors": { "type": "array", "items": { "$ref": "#/$defs/author" } }
+                },
+                "anyOf": [
+                    { "required": ["content_html"] },
+                    { "required": ["content_text"] }
+                ]
+            }
+        }
+    })
+}
+
+/// Validates a JSON Feed 1.1 document.
+///
+/// Checks the required `version`, `title` and `items`, the field types, that every
+/// item has an `id` and `content_html` or `content_text`, that authors have at least
+/// one field, and that dates are RFC 3339.
+///
+/// # Arguments
+///
+/// * `feed` - The JSON Feed document.
+///
+/// # Returns
+///
+/// * `ValidationReport` - Every violation found, with its JSON Pointer.
+pub fn validate_json_feed(feed: &Value) -> ValidationReport {
+    validate_document(feed, &json_feed_schema())
+}
+
+/// Converts a JSON Feed to RSS 2.0 or Atom 1.0.
+///
+/// Dates are converted to RFC 822 for RSS, HTML content becomes the RSS `description`
+/// or an Atom `content` of type `html`, and tags become categories.
+///
+/// # Arguments
+///
+/// * `feed` - The JSON Feed document.
+/// * `format` - The XML format to produce.
+///
+/// # Returns
+///
+/// * `Result<String>` - The XML document, or the `ValidationReport` as error if the feed is invalid.
+pub fn json_feed_to_xml(feed: &Value, format: FeedFormat) -> Result<String> {
+    let report = validate_json_feed(feed);
+    if !report.is_valid() {
+        return Err(report.into());
+    }
+    let items = feed["items"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let text =
+        |value: &Value, key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+    let mut document = Map::new();
+    let mut options = XmlOptions::default();
+    match format {
+        FeedFormat::Rss => {
+            let mut entries = Vec::new();
+            for item in items {
+                let mut entry = Map::new();
+                if let Some(title) = text(item, "title") {
+                    entry.insert("title".into(), json!(title));
+                }
+                if let Some(url) = text(item, "url") {
+                    entry.insert("link".into(), json!(url));
+                }
+                let description = text(item, "content_html")
+                    .or_else(|| text(item, "content_text"))
+                    .or_else(|| text(item, "summary"));
+                entry.insert("description".into(), json!(description));
+                entry.insert(
+                    "guid".into(),
+                    json!({ "isPermaLink": "false", TEXT_KEY: text(item, "id") }),
+                );
+                if let Some(date) = text(item, "date_published") {
+                    entry.insert("pubDate".into(), json!(rfc3339_to_rfc822(&date)?));
+                }
+                if let Some(tags) = item.get("tags") {
+                    entry.insert("category".into(), tags.clone());
+                }
+                entries.push(Value::Object(entry));
+            }
+            let mut channel = Map::new();
+            channel.insert("title".into(), feed["title"].clone());
+            if let Some(link) = text(feed, "home_page_url") {
+                channel.insert("link".into(), json!(link));
+            }
+            // RSS requires a description, so the title stands in when there is none.
+            let description = text(feed, "description")
+                .unwrap_or_else(|| feed["title"].as_str().unwrap_or_default().to_string());
+            channel.insert("description".into(), json!(description));
+            if let Some(language) = text(feed, "language") {
+                channel.insert("language".into(), json!(language));
+            }
+            channel.insert("item".into(), Value::Array(entries));
+            document.insert("version".into(), json!("2.0"));
+            document.insert("channel".into(), Value::Object(channel));
+
+            options.root = "rss".to_string();
+            for path in ["version", "channel.item.guid.isPermaLink"] {
+                options
+                    .node_kinds
+                    .insert(path.to_string(), XmlNodeKind::Attribute);
+            }
+            options.element_order.insert(
+                "channel".to_string(),
+                ["title", "link", "description", "language", "item"]
+                    .map(String::from)
+                    .to_vec(),
+            );
+            options.element_order.insert(
+                "channel.item".to_string(),
+                [
+                    "title",
+                    "link",
+                    "description",
+                    "guid",
+                    "pubDate",
+                    "category",
+                ]
+                .map(String::from)
+                .to_vec(),
+            );
+        }
+        FeedFormat::Atom => {
+            let authors = |value: &Value| -> Vec<Value> {
+                value
+                    .get("authors")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .map(|author| {
+                        let mut entry = Map::new();
+                        entry.insert(
+                            "name".into(),
+                            json!(text(author, "name").unwrap_or_default()),
+                        );
+                        if let Some(url) = text(author, "url") {
+                            entry.insert("uri".into(), json!(url));
+                        }
+                        Value::Object(entry)
+                    })
+                    .collect()
+            };
+            let mut latest: Option<String> = None;
+            let mut entries = Vec::new();
+            for item in items {
+                let mut entry = Map::new();
+                entry.insert("id".into(), json!(text(item, "id")));
+                // Atom requires a title; fall back to the summary, then the id.
+                let title = text(item, "title")
+                    .or_else(|| text(item, "summary"))
+                    .or_else(|| text(item, "id"));
+                entry.insert("title".into(), json!(title));
+                let published = text(item, "date_published");
+                if let Some(updated) = text(item, "date_modified").or_else(|| published.clone()) {
+                    if latest.as_ref().is_none_or(|latest| *latest < updated) {
+                        latest = Some(updated.clone());
+                    }
+                    entry.insert("updated".into(), json!(updated));
+                }
+                if let Some(published) = published {
+                    entry.insert("published".into(), json!(published));
+                }
+                if let Some(url) = text(item, "url") {
+                    entry.insert("link".into(), json!({ "href": url }));
+                }
+                entry.insert("author".into(), Value::Array(authors(item)));
+                if let Some(tags) = item.get("tags").and_then(Value::as_array) {
+                    let categories = tags.iter().map(|tag| json!({ "term": tag })).collect();
+                    entry.insert("category".into(), Value::Array(categories));
+                }
+                if let Some(summary) = text(item, "summary") {
+                    entry.insert("summary".into(), json!(summary));
+                }
+                let content = match text(item, "content_html") {
+                    Some(html) => json!({ "type": "html", TEXT_KEY: html }),
+                    None => json!({ "type": "text", TEXT_KEY: text(item, "content_text") }),
+                };
+                entry.insert("content".into(), content);
+                entries.push(Value::Object(entry));
+            }
+            let id = text(feed, "feed_url")
+                .or_else(|| text(feed, "home_page_url"))
+                .unwrap_or_else(|| feed["title"].as_str().unwrap_or_default().to_string());
+            document.insert("id".into(), json!(id));
+            document.insert("title".into(), feed["title"].clone());
+            if let Some(description) = text(feed, "description") {
+                document.insert("subtitle".into(), json!(description));
+            }
+            if let Some(latest) = latest {
+                document.insert("updated".into(), json!(latest));
+            }
+            let mut links = Vec::new();
+            if let Some(home) = text(feed, "home_page_url") {
+                links.push(json!({ "rel": "alternate", "href": home }));
+            }
+            if let Some(url) = text(feed, "feed_url") {
+                links.push(json!({ "rel": "self", "href": url }));
+            }
+            document.insert("link".into(), Value::Array(links));
+            document.insert("author".into(), Value::Array(authors(feed)));
+            document.insert("entry".into(), Value::Array(entries));
+
+            options.root = "feed".to_string();
+            options.default_namespace = Some(ATOM_NAMESPACE.to_string());
+            for path in [
+                "link.rel",
+                "link.href",
+                "entry.link.href",
+                "entry.category.term",
+                "entry.content.type",
+            ] {
+                options
+                    .node_kinds
+                    .insert(path.to_string(), XmlNodeKind::Attribute);
+            }
+            options.element_order.insert(
+                String::new(),
+                [
+                    "id", "title", "subtitle", "updated", "link", "author", "entry",
+                ]
+                .map(String::from)
+                .to_vec(),
+            );
+            options.element_order.insert(
+                "entry".to_string(),
+                [
+                    "id",
+                    "title",
+                    "updated",
+                    "published",
+                    "link",
+                    "author",
+                    "category",
+                    "summary",
+                    "content",
+                ]
+                .map(String::from)
+                .to_vec(),
+            );
+        }
+    }
+    json_to_xml(&Value::Object(document), &options)
+}
+
+/// Converts an RSS 2.0 or Atom 1.0 document to a JSON Feed 1.1.
+///
+/// RSS dates are converted from RFC 822 to RFC 3339. Items without content get an
+/// empty `content_html` (RSS) or their summary as `content_text` (Atom), since JSON
+/// Feed requires one of them.
+///
+/// # Arguments
+///
+/// * `xml` - The RSS or Atom document.
+///
+/// # Returns
+///
+/// * `Result<Value>` - The JSON Feed, or an error for malformed XML, an unknown root
+///   element, or an unreadable date.
+pub fn xml_to_json_feed(xml: &str) -> Result<Value> {
+    let document = xml_to_json(xml)?;
+    let (root, body) = document
+        .as_object()
+        .and_then(|map| map.iter().next())
+        .ok_or_else(|| Error::msg("Empty XML document"))?;
+    let root = root
+        .rsplit_once(':')
+        .map_or(root.as_str(), |(_, local)| local);
+    let mut feed = Map::new();
+    feed.insert("version".into(), json!(JSON_FEED_VERSION));
+    let mut items = Vec::new();
+    match root {
+        "rss" => {
+            let channel = body
+                .get("channel")
+                .ok_or_else(|| Error::msg("RSS document has no channel"))?;
+            feed.insert(
+                "title".into(),
+                json!(xml_text(channel.get("title")).unwrap_or_default()),
+            );
+            if let Some(link) = xml_text(channel.get("link")) {
+                feed.insert("home_page_url".into(), json!(link));
+            }
+            for key in ["description", "language"] {
+                if let Some(value) = xml_text(channel.get(key)) {
+                    feed.insert(key.into(), json!(value));
+                }
+            }
+            for entry in xml_list(channel.get("item")) {
+                let mut item = Map::new();
+                let link = xml_text(entry.get("link"));
+                let id = xml_text(entry.get("guid"))
+                    .or_else(|| link.clone())
+                    .or_else(|| xml_text(entry.get("title")))
+                    .unwrap_or_default();
+                item.insert("id".into(), json!(id));
+                if let Some(link) = link {
+                    item.insert("url".into(), json!(link));
+                }
+                if let Some(title) = xml_text(entry.get("title")) {
+                    item.insert("title".into(), json!(title));
+                }
+                let content = xml_text(entry.get("description")).unwrap_or_default();
+                item.insert("content_html".into(), json!(content));
+                if let Some(date) = xml_text(entry.get("pubDate")) {
+                    item.insert("date_published".into(), json!(rfc822_to_rfc3339(&date)?));
+                }
+                let tags: Vec<String> = xml_list(entry.get("category"))
+                    .into_iter()
+                    .filter_map(|c| xml_text(Some(c)))
+                    .collect();
+                if !tags.is_empty() {
+                    item.insert("tags".into(), json!(tags));
+                }
+                items.push(Value::Object(item));
+            }
+        }
+        "feed" => {
+            feed.insert(
+                "title".into(),
+                json!(xml_text(body.get("title")).unwrap_or_default()),
+            );
+            for link in xml_list(body.get("link")) {
+                let href = xml_text(link.get("@href"));
+                match link
+                    .get("@rel")
+                    .and_then(Value::as_str)
+                    .unwrap_or("alternate")
+                {
+                    "alternate" => feed.insert("home_page_url".into(), json!(href)),
+                    "self" => feed.insert("feed_url".into(), json!(href)),
+                    _ => None,
+                };
+            }
+            if let Some(subtitle) = xml_text(body.get("subtitle")) {
+                feed.insert("description".into(), json!(subtitle));
+            }
+            let authors = atom_authors(body.get("author"));
+            if !authors.is_empty() {
+                feed.insert("authors".into(), json!(authors));
+            }
+            for entry in xml_list(body.get("entry")) {
+                let mut item = Map::new();
+                item.insert(
+                    "id".into(),
+                    json!(xml_text(entry.get("id")).unwrap_or_default()),
+                );
+                let url = xml_list(entry.get("link"))
+                    .into_iter()
+                    .find(|link| {
+                        link.get("@rel")
+                            .and_then(Value::as_str)
+                            .unwrap_or("alternate")
+                            == "alternate"
+                    })
+                    .and_then(|link| xml_text(link.get("@href")));
+                if let Some(url) = url {
+                    item.insert("url".into(), json!(url));
+                }
+                for (from, to) in [
+                    ("title", "title"),
+                    ("summary", "summary"),
+                    ("published", "date_published"),
+                    ("updated", "date_modified"),
+                ] {
+                    if let Some(value) = xml_text(entry.get(from)) {
+                        item.insert(to.into(), json!(value));
+                    }
+                }
+                let content = entry.get("content");
+                let kind = content
+                    .and_then(|c| c.get("@type"))
+                    .and_then(Value::as_str)
+                    .unwrap_or("text");
+                match (xml_text(content), kind) {
+                    (Some(html), "html" | "xhtml") => {
+                        item.insert("content_html".into(), json!(html))
+                    }
+                    (Some(text), _) => item.insert("content_text".into(), json!(text)),
+                    (None, _) => item.insert(
+                        "content_text".into(),
+                        json!(xml_text(entry.get("summary")).unwrap_or_default()),
+                    ),
+                };
+                let tags: Vec<&Value> = xml_list(entry.get("category"))
+                    .into_iter()
+                    .filter_map(|c| c.get("@term"))
+                    .collect();
+                if !tags.is_empty() {
+                    item.insert("tags".into(), json!(tags));
+                }
+                let authors = atom_authors(entry.get("author"));
+                if !authors.is_empty() {
+                    item.insert("authors".into(), json!(authors));
+                }
+                items.push(Value::Object(item));
+            }
+        }
+        other => {
+            return Err(Error::msg(format!(
+                "Expected an rss or feed root element, found {}",
+                other
+            )))
+        }
+    }
+    feed.insert("items".into(), Value::Array(items));
+    Ok(Value::Object(feed))
+}
+
+/// Returns the text of an element converted by `xml_to_json`, whether or not it has attributes.
+fn xml_text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(text) => Some(text.clone()),
+        Value::Object(map) => map
+            .get(TEXT_KEY)
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Returns the elements converted by `xml_to_json` under one name, which are an array
+/// only when the element is repeated.
+fn xml_list(value: Option<&Value>) -> Vec<&Value> {
+    match value {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(items)) => items.iter().collect(),
+        Some(value) => vec![value],
+    }
+}
+
+/// Converts Atom `author` elements to JSON Feed authors.
+fn atom_authors(value: Option<&Value>) -> Vec<Value> {
+    xml_list(value)
+        .into_iter()
+        .map(|author| {
+            let mut entry = Map::new();
+            if let Some(name) = xml_text(author.get("name")) {
+                entry.insert("name".into(), json!(name));
+            }
+            if let Some(uri) = xml_text(author.get("uri")) {
+                entry.insert("url".into(), json!(uri));
+            }
+            Value::Object(entry)
+        })
+        .collect()
+}
+
+/// Converts an RFC 3339 date (`2010-02-07T14:04:00-05:00`) to RFC 822
+/// (`Sun, 07 Feb 2010 14:04:00 -0500`).
+fn rfc3339_to_rfc822(date: &str) -> Result<String> {
+    let invalid = || Error::msg(format!("Invalid RFC 3339 date: {}", date));
+    let field = |start: usize, end: usize| {
+        date.get(start..end)
+            .and_then(|s| s.parse::<i64>().ok())
+            .ok_or_else(invalid)
+    };
+    let (year, month, day) = (field(0, 4)?, field(5, 7)?, field(8, 10)?);
+    if !(1..=12).contains(&month) {
+        return Err(invalid());
+    }
+    let time = date.get(11..19).ok_or_else(invalid)?;
+    let zone = date[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let zone = match zone {
+        "Z" | "z" => "+0000".to_string(),
+        zone if zone.len() == 6 => zone.replace(':', ""),
+        _ => return Err(invalid()),
+    };
+    let weekday = WEEKDAYS[(days_from_civil(year, month, day) + 3).rem_euclid(7) as usize];
+    Ok(format!(
+        "{}, {:02} {} {} {} {}",
+        weekday,
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        time,
+        zone
+    ))
+}
+
+/// Converts an RFC 822 date (`Sun, 07 Feb 2010 14:04:00 EST`) to RFC 3339.
+fn rfc822_to_rfc3339(date: &str) -> Result<String> {
+    let invalid = || Error::msg(format!("Invalid RFC 822 date: {}", date));
+    let rest = date.split_once(',').map_or(date, |(_, rest)| rest);
+    let parts: Vec<&str> = rest.split_whitespace().collect();
+    let [day, month, year, time, zone] = parts[..] else {
+        return Err(invalid());
+    };
+    let day: u32 = day.parse().map_err(|_| invalid())?;
+    let month = MONTHS
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(month))
+        .ok_or_else(invalid)?
+        + 1;
+    let year: u32 = match year.parse().map_err(|_| invalid())? {
+        year @ 0..=49 => year + 2000,
+        year @ 50..=99 => year + 1900,
+        year => year,
+    };
+    let time = match time.len() {
+        5 => format!("{}:00", time),
+        8 => time.to_string(),
+        _ => return Err(invalid()),
+    };
+    let offset = match zone {
+        "GMT" | "UT" | "UTC" | "Z" => "Z".to_string(),
+        "EST" | "CDT" => "-05:00".to_string(),
+        "EDT" => "-04:00".to_string(),
+        "CST" | "MDT" => "-06:00".to_string(),
+        "MST" | "PDT" => "-07:00".to_string(),
+        "PST" => "-08:00".to_string(),
+        zone if zone.len() == 5 && (zone.starts_with('+') || zone.starts_with('-')) => {
+            format!("{}:{}", &zone[..3], &zone[3..])
+        }
+        _ => return Err(invalid()),
+    };
+    Ok(format!(
+        "{:04}-{:02}-{:02}T{}{}",
+        year, month, day, time, offset
+    ))
+}
+
+/// Returns the number of days between 1970-01-01 and a proleptic Gregorian date.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the proleptic Gregorian `(year, month, day)` of a day count since 1970-01-01.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,373 @@
+use crate::flatten::{flatten_with, unflatten_with};
+use crate::path_syntax::{BracketStyle, PathSyntax};
+use crate::querystring::typed_value;
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+
+/// Options for `.env` and Java `.properties` conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertiesOptions {
+    /// Syntax of the keys of nested values: `database.host` and `servers[0]` for
+    /// properties, `DATABASE__HOST` and `SERVERS__0` for `.env` files.
+    pub syntax: PathSyntax,
+    /// Reads values that look like numbers, `true`, `false` or `null` as such instead of
+    /// as strings.
+    pub typed: bool,
+}
+
+impl Default for PropertiesOptions {
+    fn default() -> Self {
+        PropertiesOptions::java()
+    }
+}
+
+impl PropertiesOptions {
+    /// Keys of Java properties and Spring configuration: `database.host`, `servers[0]`.
+    pub fn java() -> Self {
+        PropertiesOptions {
+            syntax: PathSyntax::default(),
+            typed: false,
+        }
+    }
+
+    /// Keys of `.env` files, as ASP.NET Core and pydantic nest them: `DATABASE__HOST`,
+    /// `SERVERS__0`.
+    pub fn dotenv() -> Self {
+        PropertiesOptions {
+            syntax: PathSyntax {
+                brackets: BracketStyle::None,
+                ..PathSyntax::delimited("__")
+            },
+            typed: false,
+        }
+    }
+}
+
+/// Writes an object as a `.env` file, one `KEY=value` line per flattened value, for
+/// Docker Compose and container environment files.
+///
+/// Values other than plain words are double-quoted, with `\"`, `\\`, `\n` and `\$`
+/// escapes, and `null` is written as an empty value.
+///
+/// # Arguments
+///
+/// * `json` - The object, flat or nested.
+/// * `options` - The syntax of the keys of nested values.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The file, or an error if `json` is not an object or a key
+///   cannot be a variable name.
+pub fn json_to_dotenv(json: &Value, options: &PropertiesOptions) -> Result<String, Error> {
+    let mut text = String::new();
+    for (key, value) in flat_entries(json, options, ".env")? {
+        if key.is_empty() || key.contains(['=', '#']) || key.contains(char::is_whitespace) {
+            return Err(Error::msg(format!(
+                "'{}' cannot be an environment variable name",
+                key
+            )));
+        }
+        let plain = value.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ':' | '@' | ',' | '+')
+        });
+        if plain {
+            text.push_str(&format!("{}={}\n", key, value));
+        } else {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('$', "\\$")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            text.push_str(&format!("{}=\"{}\"\n", key, escaped));
+        }
+    }
+    Ok(text)
+}
+
+/// Reads a `.env` file into an object, nesting keys written with `options.syntax`.
+///
+/// Blank lines, `#` comments and `export ` prefixes are skipped. Values may be
+/// unquoted (with trailing ` #` comments removed), single-quoted and taken literally, or
+/// double-quoted with escapes and line breaks. Variables such as `${HOME}` are not
+/// expanded.
+///
+/// # Arguments
+///
+/// * `input` - The file.
+/// * `options` - The syntax of the keys, and whether values are typed.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The object, or an error with the line number of a
+///   malformed line, or an error if a key has an index more than `MAX_ARRAY_PADDING` past
+///   the end of its array.
+pub fn dotenv_to_json(input: &str, options: &PropertiesOptions) -> Result<Value, Error> {
+    let mut entries = Map::new();
+    let mut lines = input.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (key, rest) = line.split_once('=').ok_or_else(|| {
+            Error::msg(format!(
+                "Expected KEY=value on line {}: {}",
+                number + 1,
+                line
+            ))
+        })?;
+        let rest = rest.trim_start();
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            // Double-quoted values may span lines until the closing quote.
+            let mut raw = quoted.to_string();
+            while closing_quote(&raw).is_none() {
+                let (_, next) = lines.next().ok_or_else(|| {
+                    Error::msg(format!("Unterminated quoted value on line {}", number + 1))
+                })?;
+                raw.push('\n');
+                raw.push_str(next);
+            }
+            unescape_dotenv(&raw[..closing_quote(&raw).unwrap()])
+        } else if let Some(quoted) = rest.strip_prefix('\'') {
+            let end = quoted.find('\'').ok_or_else(|| {
+                Error::msg(format!("Unterminated quoted value on line {}", number + 1))
+            })?;
+            quoted[..end].to_string()
+        } else {
+            let end = rest.find(" #").unwrap_or(rest.len());
+            rest[..end].trim_end().to_string()
+        };
+        entries.insert(key.trim_end().to_string(), entry_value(value, options));
+    }
+    unflatten_with(&Value::Object(entries), &options.syntax)
+}
+
+/// Writes an object as a Java `.properties` file, one `key=value` line per flattened
+/// value, escaped so that `java.util.Properties::load` reads it back, with characters
+/// outside ASCII as `\uXXXX`.
+///
+/// # Arguments
+///
+/// * `json` - The object, flat or nested.
+/// * `options` - The syntax of the keys of nested values.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The file, or an error if `json` is not an object.
+pub fn json_to_properties(json: &Value, options: &PropertiesOptions) -> Result<String, Error> {
+    let mut text = String::new();
+    for (key, value) in flat_entries(json, options, "properties")? {
+        text.push_str(&escape_properties(&key, true));
+        text.push('=');
+        text.push_str(&escape_properties(&value, false));
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// Reads a Java `.properties` file into an object, nesting keys written with
+/// `options.syntax`.
+///
+/// Follows `java.util.Properties::load`: `#` and `!` comments, `=`, `:` or whitespace
+/// between key and value, lines continued by a trailing backslash, and `\t`, `\n`,
+/// `\uXXXX` and other backslash escapes.
+///
+/// # Arguments
+///
+/// * `input` - The file.
+/// * `options` - The syntax of the keys, and whether values are typed.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The object, or an error if a `\u` escape is malformed, two
+///   keys conflict, or a key has an index more than `MAX_ARRAY_PADDING` past the end of
+///   its array.
+pub fn properties_to_json(input: &str, options: &PropertiesOptions) -> Result<Value, Error> {
+    let mut entries = Map::new();
+    let mut lines = input.lines().enumerate();
+    while let Some((number, line)) = lines.next() {
+        let mut logical = line.trim_start().to_string();
+        if logical.is_empty() || logical.starts_with(['#', '!']) {
+            continue;
+        }
+        while ends_with_continuation(&logical) {
+            logical.pop();
+            match lines.next() {
+                Some((_, next)) => logical.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+        let (key, value) = split_property(&logical);
+        let key = unescape_properties(key, number + 1)?;
+        let value = unescape_properties(value, number + 1)?;
+        entries.insert(key, entry_value(value, options));
+    }
+    unflatten_with(&Value::Object(entries), &options.syntax)
+}
+
+/// Flattens an object into keys and value texts.
+fn flat_entries(
+    json: &Value,
+    options: &PropertiesOptions,
+    format: &str,
+) -> Result<Vec<(String, String)>, Error> {
+    if !json.is_object() {
+        return Err(Error::msg(format!(
+            "Only an object can be converted to a {} file",
+            format
+        )));
+    }
+    let Value::Object(flat) = flatten_with(json, &options.syntax) else {
+        unreachable!("flatten_with returns an object")
+    };
+    Ok(flat
+        .into_iter()
+        .map(|(key, value)| {
+            let text = match value {
+                Value::String(text) => text,
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            (key, text)
+        })
+        .collect())
+}
+
+/// Converts a value read from a file, typing it if asked to.
+fn entry_value(value: String, options: &PropertiesOptions) -> Value {
+    if options.typed {
+        typed_value(&value)
+    } else {
+        Value::String(value)
+    }
+}
+
+/// Returns the byte offset of the first unescaped `"` in a double-quoted value.
+fn closing_quote(raw: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (offset, c) in raw.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(offset),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Resolves the escapes of a double-quoted `.env` value.
+fn unescape_dotenv(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('t') => value.push('\t'),
+            Some(other @ ('"' | '\\' | '$' | '\'')) => value.push(other),
+            Some(other) => {
+                value.push('\\');
+                value.push(other);
+            }
+            None => value.push('\\'),
+        }
+    }
+    value
+}
+
+/// Returns whether a properties line ends with an odd number of backslashes.
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+/// Splits a logical properties line at the first unescaped `=`, `:` or whitespace.
+fn split_property(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (offset, c) in line.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '=' | ':' | ' ' | '\t' | '\x0C' if !escaped => {
+                let rest = line[offset..].trim_start_matches([' ', '\t', '\x0C']);
+                let rest = rest.strip_prefix(['=', ':']).unwrap_or(rest);
+                return (
+                    &line[..offset],
+                    rest.trim_start_matches([' ', '\t', '\x0C']),
+                );
+            }
+            _ => escaped = false,
+        }
+    }
+    (line, "")
+}
+
+/// Resolves the backslash escapes of a properties key or value.
+fn unescape_properties(text: &str, line: usize) -> Result<String, Error> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    let mut pending_high: Option<u16> = None;
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('f') => result.push('\x0C'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let unit = u16::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 4)
+                    .ok_or_else(|| {
+                        Error::msg(format!("Malformed \\u escape on line {}: \\u{}", line, hex))
+                    })?;
+                // Characters outside the BMP are written as surrogate pairs.
+                match (pending_high.take(), unit) {
+                    (None, 0xD800..=0xDBFF) => pending_high = Some(unit),
+                    (Some(high), 0xDC00..=0xDFFF) => {
+                        result.extend(char::decode_utf16([high, unit]).flatten())
+                    }
+                    (_, unit) => result.push(char::from_u32(unit as u32).unwrap_or('\u{FFFD}')),
+                }
+            }
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    Ok(result)
+}
+
+/// Escapes a properties key or value; keys also escape separators and spaces.
+fn escape_properties(text: &str, key: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (index, c) in text.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\x0C' => escaped.push_str("\\f"),
+            ' ' if key || index == 0 => escaped.push_str("\\ "),
+            '=' | ':' | '#' | '!' if key || index == 0 => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_ascii() => escaped.push(c),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    escaped.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+        }
+    }
+    escaped
+}
@@ -0,0 +1,287 @@
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// How `json_to_querystring` writes arrays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayFormat {
+    /// Empty brackets, as PHP and Rails read them: `tags[]=a&tags[]=b`. Arrays of
+    /// objects or arrays are written with indexes.
+    #[default]
+    Brackets,
+    /// Indexes in brackets: `tags[0]=a&tags[1]=b`.
+    Indices,
+    /// The key repeated, as most Java, Go and Python frameworks read it: `tags=a&tags=b`.
+    /// Arrays of objects or arrays are written with indexes.
+    Repeat,
+}
+
+impl FromStr for ArrayFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "brackets" => Ok(ArrayFormat::Brackets),
+            "indices" => Ok(ArrayFormat::Indices),
+            "repeat" => Ok(ArrayFormat::Repeat),
+            _ => Err(Error::msg(format!("Unsupported array format: {}", s))),
+        }
+    }
+}
+
+/// Options for `querystring_to_json` and `json_to_querystring`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryStringOptions {
+    pub arrays: ArrayFormat,
+    /// Reads values that look like numbers, `true`, `false` or `null` as such instead of
+    /// as strings.
+    pub typed: bool,
+}
+
+/// Reads a query string or `application/x-www-form-urlencoded` body into an object.
+///
+/// Bracketed keys build nested values: `a[b]=1` is `{"a": {"b": "1"}}`, `a[]=1` appends
+/// to an array, and `a[0]=1` sets an array index; sparse indexes are compacted in index
+/// order, so `a[0]=x&a[5]=y&a[1]=z` is `["x", "z", "y"]`. A key repeated without brackets
+/// (`a=1&a=2`) collects its values in an array. `+` and percent escapes are decoded,
+/// and a leading `?` is skipped, so a URL's query can be pasted as is.
+///
+/// # Arguments
+///
+/// * `input` - The query string, e.g. `?page=2&filter[status]=open&tags[]=a`.
+/// * `options` - Whether values are typed; `arrays` is ignored.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The object, or an error if a key's brackets are unbalanced
+///   or a key is used both as a value and as a container.
+pub fn querystring_to_json(input: &str, options: &QueryStringOptions) -> Result<Value, Error> {
+    let input = input.trim();
+    let input = input.strip_prefix('?').unwrap_or(input);
+    let mut root = Node::Object(BTreeMap::new());
+    for pair in input.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = decode(key);
+        let value = decode(value);
+        let value = if options.typed {
+            typed_value(&value)
+        } else {
+            Value::String(value)
+        };
+        let steps = parse_key(&key)?;
+        insert(&mut root, &steps, value, &key)?;
+    }
+    Ok(root.into_value())
+}
+
+/// Writes an object as a query string, the reverse of `querystring_to_json`.
+///
+/// Nested objects are written with bracketed keys (`a[b]=1`) and arrays as set by
+/// `options.arrays`. `null` is written as an empty value, and empty objects and arrays
+/// are left out. Keys and values are percent-encoded as in form bodies, with spaces as
+/// `+`; brackets in keys are kept readable.
+///
+/// # Arguments
+///
+/// * `json` - The object to write.
+/// * `options` - How arrays are written; `typed` is ignored.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The query string without a leading `?`, or an error if
+///   `json` is not an object.
+pub fn json_to_querystring(json: &Value, options: &QueryStringOptions) -> Result<String, Error> {
+    let map = json
+        .as_object()
+        .ok_or_else(|| Error::msg("Only an object can be converted to a query string"))?;
+    let mut pairs = Vec::new();
+    for (key, value) in map {
+        write_pairs(&encode(key, false), value, options, &mut pairs);
+    }
+    Ok(pairs.join("&"))
+}
+
+/// A step of a bracketed key: `a[b][]` is `a`, `b`, then an append.
+#[derive(Debug, PartialEq)]
+enum KeyStep {
+    Key(String),
+    Index(usize),
+    Append,
+}
+
+/// Splits `a[b][0][]` into its steps.
+fn parse_key(key: &str) -> Result<Vec<KeyStep>, Error> {
+    let unbalanced = || Error::msg(format!("Unbalanced brackets in query string key '{}'", key));
+    let (name, mut rest) = match key.find('[') {
+        // A key starting with a bracket is taken literally, as PHP does.
+        Some(0) | None => return Ok(vec![KeyStep::Key(key.to_string())]),
+        Some(start) => (&key[..start], &key[start..]),
+    };
+    let mut steps = vec![KeyStep::Key(name.to_string())];
+    while !rest.is_empty() {
+        let inner = rest.strip_prefix('[').ok_or_else(unbalanced)?;
+        let end = inner.find(']').ok_or_else(unbalanced)?;
+        let segment = &inner[..end];
+        steps.push(if segment.is_empty() {
+            KeyStep::Append
+        } else {
+            match segment.parse::<usize>() {
+                Ok(index) if segment == index.to_string() => KeyStep::Index(index),
+                _ => KeyStep::Key(segment.to_string()),
+            }
+        });
+        rest = &inner[end + 1..];
+    }
+    Ok(steps)
+}
+
+/// A value being read. Array elements keep the index they were given until the whole
+/// query string is read, so that `a[0]=x&a[5]=y&a[1]=z` is `["x", "z", "y"]`.
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Leaf(Value),
+    Object(BTreeMap<String, Node>),
+    Array(BTreeMap<usize, Node>),
+}
+
+impl Node {
+    /// Converts to a value, compacting sparse array indexes as in `qs`.
+    fn into_value(self) -> Value {
+        match self {
+            Node::Empty => Value::Null,
+            Node::Leaf(value) => value,
+            Node::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(name, node)| (name, node.into_value()))
+                    .collect::<Map<String, Value>>(),
+            ),
+            Node::Array(items) => Value::Array(items.into_values().map(Node::into_value).collect()),
+        }
+    }
+}
+
+/// Adds an element after the highest index of an array and returns it.
+fn push_element(items: &mut BTreeMap<usize, Node>, node: Node) -> &mut Node {
+    let next = items.keys().next_back().map_or(0, |index| index + 1);
+    items.entry(next).or_insert(node)
+}
+
+/// Stores a value at the steps of a key, creating objects and arrays on the way.
+fn insert(target: &mut Node, steps: &[KeyStep], value: Value, key: &str) -> Result<(), Error> {
+    let conflict = || {
+        Error::msg(format!(
+            "Query string key '{}' conflicts with an earlier key",
+            key
+        ))
+    };
+    let Some((step, rest)) = steps.split_first() else {
+        // A key repeated without brackets collects its values.
+        match target {
+            Node::Empty => *target = Node::Leaf(value),
+            Node::Array(items) => {
+                push_element(items, Node::Leaf(value));
+            }
+            Node::Object(_) => return Err(conflict()),
+            Node::Leaf(_) => {
+                let first = std::mem::replace(target, Node::Empty);
+                *target = Node::Array(BTreeMap::from([(0, first), (1, Node::Leaf(value))]));
+            }
+        }
+        return Ok(());
+    };
+    if let Node::Empty = target {
+        *target = match step {
+            KeyStep::Key(_) => Node::Object(BTreeMap::new()),
+            KeyStep::Index(_) | KeyStep::Append => Node::Array(BTreeMap::new()),
+        };
+    }
+    let child = match (step, target) {
+        (KeyStep::Key(name), Node::Object(map)) => map.entry(name.clone()).or_insert(Node::Empty),
+        (KeyStep::Index(index), Node::Array(items)) => items.entry(*index).or_insert(Node::Empty),
+        (KeyStep::Append, Node::Array(items)) => push_element(items, Node::Empty),
+        (KeyStep::Index(index), Node::Object(map)) => {
+            map.entry(index.to_string()).or_insert(Node::Empty)
+        }
+        _ => return Err(conflict()),
+    };
+    insert(child, rest, value, key)
+}
+
+/// Writes the pairs of a value under a key.
+fn write_pairs(key: &str, value: &Value, options: &QueryStringOptions, pairs: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (name, item) in map {
+                let key = format!("{}[{}]", key, encode(name, false));
+                write_pairs(&key, item, options, pairs);
+            }
+        }
+        Value::Array(items) => {
+            let nested = items.iter().any(|item| item.is_object() || item.is_array());
+            for (index, item) in items.iter().enumerate() {
+                let key = match options.arrays {
+                    ArrayFormat::Indices => format!("{}[{}]", key, index),
+                    _ if nested => format!("{}[{}]", key, index),
+                    ArrayFormat::Brackets => format!("{}[]", key),
+                    ArrayFormat::Repeat => key.to_string(),
+                };
+                write_pairs(&key, item, options, pairs);
+            }
+        }
+        Value::Null => pairs.push(format!("{}=", key)),
+        Value::String(text) => pairs.push(format!("{}={}", key, encode(text, true))),
+        other => pairs.push(format!("{}={}", key, other)),
+    }
+}
+
+/// Reads a value as JSON if it is a number, `true`, `false` or `null`.
+pub(crate) fn typed_value(text: &str) -> Value {
+    match serde_json::from_str(text) {
+        Ok(value @ (Value::Number(_) | Value::Bool(_) | Value::Null)) => value,
+        _ => Value::String(text.to_string()),
+    }
+}
+
+/// Percent-encodes text for a query string, with spaces as `+`. Brackets are kept in
+/// keys, where they mark nesting.
+fn encode(text: &str, value: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'[' | b']' if !value => encoded.push(byte as char),
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Decodes `+` and percent escapes; malformed escapes are kept as they are.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,58 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A small pseudo-random generator (SplitMix64) with an explicit seed, so that features
+/// drawing random numbers, such as `sample_ndjson`, repeat their result for the same
+/// `--seed`. It is fast and statistically sound but not cryptographic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    /// Creates a generator from a seed; equal seeds give equal sequences on every
+    /// platform and build.
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    /// Returns a seed drawn from the clock and the process's hash randomness, for runs
+    /// without `--seed`. Report it so that the run can be repeated.
+    pub fn entropy_seed() -> u64 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        RandomState::new().hash_one(nanos)
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly distributed number below `bound`, without modulo bias.
+    ///
+    /// # Arguments
+    ///
+    /// * `bound` - The exclusive upper bound; must not be 0.
+    ///
+    /// # Returns
+    ///
+    /// * `u64` - A number in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0, "SeededRng::below needs a positive bound");
+        // Lemire's method: reject the few products that would favour low results.
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let product = u128::from(self.next_u64()) * u128::from(bound);
+            if product as u64 >= threshold {
+                return (product >> 64) as u64;
+            }
+        }
+    }
+}
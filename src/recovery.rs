@@ -0,0 +1,369 @@
+use crate::parser::ParseErrorDetails;
+use serde_json::{Map, Number, Value};
+use std::str::FromStr;
+
+/// A syntax error found by `parse_json_with_recovery`, with its position and snippet.
+pub type ParseDiagnostic = ParseErrorDetails;
+
+/// Parses JSON leniently, reporting every syntax error in one pass instead of
+/// stopping at the first.
+///
+/// Malformed elements are skipped: a bad value is dropped from its object or array,
+/// a missing comma is assumed, and unclosed strings, objects, and arrays are closed
+/// at the end of the line or input. Trailing commas, unquoted keys and values, and
+/// content after the top-level value are reported. This makes it suitable for
+/// linting hand-written config files.
+///
+/// # Arguments
+///
+/// * `input` - The JSON text.
+///
+/// # Returns
+///
+/// * `(Option<Value>, Vec<ParseDiagnostic>)` - The recovered value (`None` if the input
+///   holds no value at all) and the syntax errors in input order; the value is exact
+///   when there are no diagnostics.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = input.len())))]
+pub fn parse_json_with_recovery(input: &str) -> (Option<Value>, Vec<ParseDiagnostic>) {
+    let mut parser = RecoveringParser {
+        input,
+        position: 0,
+        diagnostics: Vec::new(),
+    };
+    parser.skip_whitespace();
+    let value = if parser.at_end() {
+        parser.report(parser.position, "expected a JSON value");
+        None
+    } else {
+        parser.parse_value()
+    };
+    parser.skip_whitespace();
+    if !parser.at_end() {
+        parser.report(
+            parser.position,
+            "unexpected content after the top-level value",
+        );
+    }
+    (value, parser.diagnostics)
+}
+
+/// Recursive-descent JSON parser that records errors and continues.
+struct RecoveringParser<'a> {
+    input: &'a str,
+    position: usize,
+    diagnostics: Vec<ParseDiagnostic>,
+}
+
+impl RecoveringParser<'_> {
+    /// Records a syntax error at a byte offset.
+    fn report(&mut self, offset: usize, message: &str) {
+        if let Some(position) = pest::Position::new(self.input, offset) {
+            self.diagnostics
+                .push(ParseErrorDetails::at(position, message));
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.position).copied()
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.input.len()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+            self.position += 1;
+        }
+    }
+
+    /// Consumes a run of characters that cannot end a token, returning it.
+    fn skip_token(&mut self) -> &str {
+        let start = self.position;
+        while let Some(c) = self.input[self.position..].chars().next() {
+            if c.is_whitespace() || ",:[]{}\"".contains(c) {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+        if self.position == start {
+            // A lone delimiter; consume it so that parsing always advances.
+            self.position += self.input[start..].chars().next().map_or(0, char::len_utf8);
+        }
+        &self.input[start..self.position]
+    }
+
+    /// Parses any value, returning `None` if it is too malformed to keep.
+    fn parse_value(&mut self) -> Option<Value> {
+        let start = self.position;
+        match self.peek()? {
+            b'{' => Some(self.parse_object()),
+            b'[' => Some(self.parse_array()),
+            b'"' => Some(Value::String(self.parse_string())),
+            b'-' | b'0'..=b'9' => self.parse_number(),
+            _ => {
+                let token = self.skip_token().to_string();
+                match token.as_str() {
+                    "true" => Some(Value::Bool(true)),
+                    "false" => Some(Value::Bool(false)),
+                    "null" => Some(Value::Null),
+                    "}" | "]" | "," | ":" => {
+                        self.report(start, &format!("expected a value, found '{}'", token));
+                        None
+                    }
+                    _ => {
+                        self.report(
+                            start,
+                            &format!("unexpected '{}', strings must be quoted", token),
+                        );
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses an object starting at `{`.
+    fn parse_object(&mut self) -> Value {
+        let open = self.position;
+        self.position += 1;
+        let mut map = Map::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => {
+                    self.report(open, "unclosed object");
+                    break;
+                }
+                Some(b'}') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(b'"') => {}
+                Some(b']') => {
+                    self.report(self.position, "expected '}' to close the object, found ']'");
+                    self.position += 1;
+                    break;
+                }
+                Some(_) => {
+                    let start = self.position;
+                    let token = self.skip_token().to_string();
+                    self.report(start, &format!("expected a string key, found '{}'", token));
+                    if token == "," {
+                        continue;
+                    }
+                    self.skip_whitespace();
+                    if self.peek() != Some(b':') {
+                        continue;
+                    }
+                }
+            }
+            let key = match self.peek() {
+                Some(b'"') => Some(self.parse_string()),
+                _ => None,
+            };
+            self.skip_whitespace();
+            if self.peek() == Some(b':') {
+                self.position += 1;
+            } else {
+                self.report(self.position, "expected ':' after the key");
+            }
+            self.skip_whitespace();
+            let value = match self.peek() {
+                None | Some(b'}') => {
+                    self.report(self.position, "expected a value");
+                    None
+                }
+                Some(_) => self.parse_value(),
+            };
+            if let (Some(key), Some(value)) = (key, value) {
+                map.insert(key, value);
+            }
+            if !self.separator(b'}', "object") {
+                break;
+            }
+        }
+        Value::Object(map)
+    }
+
+    /// Parses an array starting at `[`.
+    fn parse_array(&mut self) -> Value {
+        let open = self.position;
+        self.position += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => {
+                    self.report(open, "unclosed array");
+                    break;
+                }
+                Some(b']') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(b'}') => {
+                    self.report(self.position, "expected ']' to close the array, found '}'");
+                    self.position += 1;
+                    break;
+                }
+                Some(_) => {}
+            }
+            if let Some(value) = self.parse_value() {
+                items.push(value);
+            }
+            if !self.separator(b']', "array") {
+                break;
+            }
+        }
+        Value::Array(items)
+    }
+
+    /// Handles what follows a member or element: returns `true` if another one
+    /// follows and `false` once the container is closed.
+    fn separator(&mut self, close: u8, container: &str) -> bool {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b',') => {
+                let comma = self.position;
+                self.position += 1;
+                self.skip_whitespace();
+                if self.peek() == Some(close) {
+                    self.report(comma, &format!("trailing comma in {}", container));
+                    self.position += 1;
+                    return false;
+                }
+                true
+            }
+            Some(c) if c == close => {
+                self.position += 1;
+                false
+            }
+            None => true,
+            Some(_) => {
+                self.report(
+                    self.position,
+                    &format!("expected ',' or '{}' in {}", close as char, container),
+                );
+                true
+            }
+        }
+    }
+
+    /// Parses a string starting at `"`. An unterminated string ends at the line break.
+    fn parse_string(&mut self) -> String {
+        let open = self.position;
+        self.position += 1;
+        let mut result = String::new();
+        loop {
+            let Some(c) = self.input[self.position..].chars().next() else {
+                self.report(open, "unterminated string");
+                return result;
+            };
+            match c {
+                '"' => {
+                    self.position += 1;
+                    return result;
+                }
+                '\n' | '\r' => {
+                    self.report(open, "unterminated string");
+                    return result;
+                }
+                '\\' => {
+                    let escape = self.position;
+                    self.position += 1;
+                    let Some(next) = self.input[self.position..].chars().next() else {
+                        continue;
+                    };
+                    self.position += next.len_utf8();
+                    match next {
+                        '"' => result.push('"'),
+                        '\\' => result.push('\\'),
+                        '/' => result.push('/'),
+                        'b' => result.push('\u{0008}'),
+                        'f' => result.push('\u{000C}'),
+                        'n' => result.push('\n'),
+                        'r' => result.push('\r'),
+                        't' => result.push('\t'),
+                        'u' => match self.unicode_escape() {
+                            Some(c) => result.push(c),
+                            None => self.report(escape, "invalid unicode escape"),
+                        },
+                        other => {
+                            self.report(escape, &format!("invalid escape sequence \\{}", other));
+                            result.push(other);
+                        }
+                    }
+                }
+                c if (c as u32) < 0x20 => {
+                    self.report(
+                        self.position,
+                        "control characters must be escaped in strings",
+                    );
+                    result.push(c);
+                    self.position += 1;
+                }
+                c => {
+                    result.push(c);
+                    self.position += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Reads the hex digits of a `\u` escape, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Option<char> {
+        let hex = |parser: &mut Self| {
+            let digits = parser.input.get(parser.position..parser.position + 4)?;
+            let code = u32::from_str_radix(digits, 16).ok()?;
+            parser.position += 4;
+            Some(code)
+        };
+        let high = hex(self)?;
+        if (0xD800..0xDC00).contains(&high) && self.input[self.position..].starts_with("\\u") {
+            self.position += 2;
+            let low = hex(self)?;
+            return char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low.checked_sub(0xDC00)?));
+        }
+        char::from_u32(high)
+    }
+
+    /// Parses a number; malformed numbers such as `01` or `1.` are reported and dropped.
+    fn parse_number(&mut self) -> Option<Value> {
+        let start = self.position;
+        let token = self.skip_token().to_string();
+        let bytes = token.as_bytes();
+        let digits = |mut i: usize| {
+            let from = i;
+            while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+                i += 1;
+            }
+            (i, i > from)
+        };
+        let mut i = usize::from(bytes.first() == Some(&b'-'));
+        let (end, integer) = digits(i);
+        let mut valid = integer && !(bytes[i] == b'0' && end - i > 1);
+        i = end;
+        if valid && bytes.get(i) == Some(&b'.') {
+            let (end, fraction) = digits(i + 1);
+            valid = fraction;
+            i = end;
+        }
+        if valid && matches!(bytes.get(i), Some(b'e' | b'E')) {
+            i += 1;
+            if matches!(bytes.get(i), Some(b'+' | b'-')) {
+                i += 1;
+            }
+            let (end, exponent) = digits(i);
+            valid = exponent;
+            i = end;
+        }
+        match Number::from_str(&token) {
+            Ok(number) if valid && i == bytes.len() => Some(Value::Number(number)),
+            _ => {
+                self.report(start, &format!("invalid number '{}'", token));
+                None
+            }
+        }
+    }
+}
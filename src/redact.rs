@@ -0,0 +1,246 @@
+use crate::presets::REDACTED;
+use anyhow::{Error, Result};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::OnceLock;
+
+/// Environment variable holding the secret key of `Replacement::Hash` when the rules
+/// do not set one.
+pub const HASH_KEY_VAR: &str = "JSON_PARSER_HASH_KEY";
+
+/// Key fragments that mark a value as sensitive by default.
+const DEFAULT_KEYS: [&str; 9] = [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "apikey",
+    "authorization",
+    "credential",
+    "privatekey",
+    "cookie",
+];
+
+/// Patterns of sensitive text by default: payment card numbers and email addresses.
+const DEFAULT_VALUES: [&str; 2] = [
+    r"\b(?:\d[ -]?){12,18}\d\b",
+    r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+];
+
+/// What a sensitive value is replaced with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Replacement {
+    /// The fixed placeholder `***`.
+    #[default]
+    Mask,
+    /// `hash:` and the first 128 bits of the HMAC-SHA256 of the value under a secret
+    /// key, so equal secrets can still be correlated across a payload. Without the key,
+    /// the hashes cannot be brute-forced back into short secrets such as PINs or card
+    /// numbers, so the key must stay secret.
+    Hash,
+}
+
+/// Rules of `redact`.
+#[derive(Debug, Clone)]
+pub struct RedactionRules {
+    /// Key fragments: a member whose key contains one, ignoring case, `_` and `-`, is
+    /// replaced whole, so `apiKey`, `API_KEY` and `x-api-key` all match `api_key`.
+    pub keys: Vec<String>,
+    /// Patterns searched in every string; only the matching text is replaced.
+    pub values: Vec<Regex>,
+    pub replacement: Replacement,
+    /// The secret HMAC key of `Replacement::Hash`; hashes match only those made with the
+    /// same key. Without one, the key is read from `JSON_PARSER_HASH_KEY`, or else drawn
+    /// at random once per process, so hashes only match within that process.
+    pub hash_key: Option<Vec<u8>>,
+}
+
+/// Redaction config as written in a file, before the patterns are compiled.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    keys: Option<Vec<String>>,
+    values: Option<Vec<String>>,
+    #[serde(default)]
+    replacement: Replacement,
+}
+
+impl Default for RedactionRules {
+    /// Masks passwords, tokens, secrets, API keys, credentials and cookies, and any
+    /// text that looks like a payment card number or an email address.
+    fn default() -> Self {
+        RedactionRules {
+            keys: DEFAULT_KEYS.iter().map(|key| key.to_string()).collect(),
+            values: DEFAULT_VALUES
+                .iter()
+                .map(|pattern| Regex::new(pattern).expect("default redaction pattern"))
+                .collect(),
+            replacement: Replacement::Mask,
+            hash_key: None,
+        }
+    }
+}
+
+impl RedactionRules {
+    /// Parses redaction rules; lists left out keep their defaults.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The rules as JSON, e.g.
+    ///   `{ "keys": ["password", "ssn"], "values": ["\\b\\d{3}-\\d{2}-\\d{4}\\b"], "replacement": "hash" }`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RedactionRules, Error>` - The rules, or an error if they are malformed or
+    ///   a pattern is not a valid regex.
+    pub fn from_json(config: &Value) -> Result<RedactionRules, Error> {
+        let file: RulesFile = serde_json::from_value(config.clone())
+            .map_err(|e| Error::msg(format!("Invalid redaction rules: {}", e)))?;
+        let mut rules = RedactionRules {
+            replacement: file.replacement,
+            ..RedactionRules::default()
+        };
+        if let Some(keys) = file.keys {
+            rules.keys = keys;
+        }
+        if let Some(values) = file.values {
+            rules.values = values
+                .iter()
+                .map(|pattern| {
+                    Regex::new(pattern).map_err(|e| {
+                        Error::msg(format!("Invalid redaction pattern '{}': {}", pattern, e))
+                    })
+                })
+                .collect::<Result<_>>()?;
+        }
+        Ok(rules)
+    }
+
+    /// Returns whether the value of a member with this key is sensitive.
+    fn matches_key(&self, key: &str) -> bool {
+        let key = normalize_key(key);
+        self.keys
+            .iter()
+            .any(|fragment| key.contains(&normalize_key(fragment)))
+    }
+
+    /// Returns the replacement for a sensitive text.
+    fn replace(&self, text: &str) -> String {
+        match self.replacement {
+            Replacement::Mask => REDACTED.to_string(),
+            Replacement::Hash => {
+                let key = self.hash_key.as_deref().unwrap_or_else(|| default_hash_key());
+                let mac = hmac_sha256(key, text.as_bytes());
+                let hex: String = mac[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("hash:{}", hex)
+            }
+        }
+    }
+}
+
+/// Returns the key of `Replacement::Hash` for rules without one: `JSON_PARSER_HASH_KEY`
+/// if set, or else 256 bits drawn once per process from the OS-seeded keys of `RandomState`.
+fn default_hash_key() -> &'static [u8] {
+    static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+    KEY.get_or_init(|| match std::env::var(HASH_KEY_VAR) {
+        Ok(key) if !key.is_empty() => key.into_bytes(),
+        _ => {
+            let state = RandomState::new();
+            let mut hasher = Sha256::new();
+            for part in 0..4u64 {
+                hasher.update(state.hash_one(part).to_le_bytes());
+                hasher.update(RandomState::new().hash_one(part).to_le_bytes());
+            }
+            hasher.finalize().to_vec()
+        }
+    })
+}
+
+/// Computes HMAC-SHA256 (RFC 2104) of a message.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.map(|b| b ^ byte);
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// Masks the sensitive values of a document, e.g. before sharing a production payload
+/// in a bug report.
+///
+/// Members whose keys match `rules.keys` are replaced whole, whatever their type; in
+/// other strings, the text matching `rules.values` is replaced, so
+/// `"contact ann@example.com"` becomes `"contact ***"`. Keys and `null` values are kept.
+///
+/// # Arguments
+///
+/// * `json` - The document to redact in place.
+/// * `rules` - What to redact and how, e.g. `RedactionRules::default()`.
+///
+/// # Returns
+///
+/// * `usize` - The number of values that were changed.
+pub fn redact(json: &mut Value, rules: &RedactionRules) -> usize {
+    match json {
+        Value::Object(map) => map
+            .iter_mut()
+            .map(|(key, value)| {
+                if value.is_null() {
+                    0
+                } else if rules.matches_key(key) {
+                    let text = match &*value {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    *value = Value::String(rules.replace(&text));
+                    1
+                } else {
+                    redact(value, rules)
+                }
+            })
+            .sum(),
+        Value::Array(items) => items.iter_mut().map(|item| redact(item, rules)).sum(),
+        Value::String(text) => {
+            let mut redacted = text.clone();
+            for pattern in &rules.values {
+                redacted = pattern
+                    .replace_all(&redacted, |captures: &regex::Captures| {
+                        rules.replace(&captures[0])
+                    })
+                    .into_owned();
+            }
+            if redacted == *text {
+                0
+            } else {
+                *text = redacted;
+                1
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Lowercases a key and drops `_`, `-` and spaces, so naming conventions compare equal.
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| !matches!(c, '_' | '-' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,261 @@
+use crate::parser::{
+    canonicalize, format_json, parse_json, parse_json5, parse_jsonc, FormatOptions,
+};
+use crate::path::query_jsonpath;
+use crate::recovery::parse_json_with_recovery;
+use anyhow::{Context, Error, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+/// Where `--save-repro` saves cases and `cargo test` replays them from, relative to the
+/// crate root.
+pub const DEFAULT_CORPUS_DIR: &str = "tests/regressions";
+
+/// A check that a regression case fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayFailure {
+    /// The file name of the case.
+    pub case: String,
+    /// The check, e.g. `format` for a pretty-printed value that reads back differently.
+    pub check: &'static str,
+    /// What went wrong, or the panic message.
+    pub message: String,
+}
+
+impl fmt::Display for ReplayFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.case, self.check, self.message)
+    }
+}
+
+/// Replays every `.json` file of a corpus directory with `replay_input`, in file name
+/// order.
+///
+/// # Arguments
+///
+/// * `dir` - The corpus, e.g. `tests/regressions`.
+///
+/// # Returns
+///
+/// * `Result<Vec<ReplayFailure>, Error>` - The failed checks of every case, empty if all
+///   pass, or an error if the directory cannot be read.
+pub fn replay_corpus(dir: &Path) -> Result<Vec<ReplayFailure>, Error> {
+    let mut cases = Vec::new();
+    for entry in fs::read_dir(dir)
+        .with_context(|| format!("Failed to read corpus directory {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            cases.push(path);
+        }
+    }
+    cases.sort();
+    let mut failures = Vec::new();
+    for path in cases {
+        let bytes =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        failures.extend(replay_input(&name, &String::from_utf8_lossy(&bytes)));
+    }
+    Ok(failures)
+}
+
+/// Runs an input through the parsers, serializers and queries, and checks that none
+/// panics and that they agree.
+///
+/// Every parser must return without panicking, whether the input is valid or not, and:
+///
+/// * `parse_json` - `parse_json` rejects the inputs `serde_json` rejects and reads the
+///   same value as `serde_json` from the others.
+///
+/// When the input is valid JSON, as read by the CLI:
+///
+/// * `recovery` - `parse_json_with_recovery` returns the same value without diagnostics.
+/// * `jsonc` - `parse_jsonc` returns the same value.
+/// * `format` - The output of `format_json` reads back as the same value.
+/// * `canonicalize` - Canonicalizing the canonical form again gives the same text.
+/// * `query` - `$..*` selects every value below the root.
+///
+/// # Arguments
+///
+/// * `case` - The name to report failures under.
+/// * `input` - The text of the case.
+///
+/// # Returns
+///
+/// * `Vec<ReplayFailure>` - The failed checks, empty if the case passes.
+pub fn replay_input(case: &str, input: &str) -> Vec<ReplayFailure> {
+    let mut replay = Replay {
+        case,
+        failures: Vec::new(),
+    };
+    let parsed = replay.check("parse_json", || Ok(parse_json(input)));
+    replay.check("json5", || {
+        let _ = parse_json5(input);
+        Ok(())
+    });
+    let recovered = replay.check("recovery", || Ok(parse_json_with_recovery(input)));
+    let expected = serde_json::from_str::<Value>(input);
+    if let Some(parsed) = parsed {
+        replay.check("parse_json", || match (parsed, &expected) {
+            (Ok(parsed), Ok(value)) if parsed != *value => {
+                Err(format!("read {} instead of {}", parsed, value))
+            }
+            (Ok(parsed), Err(e)) => Err(format!("read {} from invalid JSON ({})", parsed, e)),
+            (Err(e), Ok(_)) => Err(format!("rejected valid JSON: {}", e)),
+            _ => Ok(()),
+        });
+    }
+    let Ok(value) = expected else {
+        return replay.failures;
+    };
+    if let Some((recovered, diagnostics)) = recovered {
+        replay.check("recovery", || match diagnostics.first() {
+            Some(diagnostic) => Err(format!("reported valid JSON as invalid{}", diagnostic)),
+            None if recovered.as_ref() != Some(&value) => {
+                Err(format!("read {:?} instead", recovered))
+            }
+            None => Ok(()),
+        });
+    }
+    replay.check("jsonc", || {
+        let parsed = parse_jsonc(input).map_err(|e| e.to_string())?;
+        same_value(&parsed, &value)
+    });
+    replay.check("format", || {
+        let text = format_json(&value, &FormatOptions::default());
+        let parsed = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        same_value(&parsed, &value)
+    });
+    replay.check("canonicalize", || {
+        // Numbers that a double cannot hold are rejected by design.
+        let Ok(first) = canonicalize(&value) else {
+            return Ok(());
+        };
+        let parsed: Value = serde_json::from_str(&first).map_err(|e| e.to_string())?;
+        let second = canonicalize(&parsed).map_err(|e| e.to_string())?;
+        if first == second {
+            Ok(())
+        } else {
+            Err(format!("{} canonicalizes again as {}", first, second))
+        }
+    });
+    replay.check("query", || {
+        let selected = query_jsonpath(&value, "$..*").map_err(|e| e.to_string())?;
+        let expected = descendants(&value);
+        if selected.len() == expected {
+            Ok(())
+        } else {
+            Err(format!(
+                "$..* selected {} values instead of {}",
+                selected.len(),
+                expected
+            ))
+        }
+    });
+    replay.failures
+}
+
+/// Saves an input as a regression case named after its source file and its SHA-256
+/// hash, e.g. `config-3fa9c2d41b07e655.json`, unless the corpus already holds it.
+///
+/// # Arguments
+///
+/// * `dir` - The corpus, created if needed.
+/// * `input` - The input that crashed or was misread.
+/// * `source` - The path or name it came from; only its file stem is kept.
+///
+/// # Returns
+///
+/// * `Result<PathBuf, Error>` - The path of the new or existing case, or an error if it
+///   cannot be written.
+pub fn save_repro(dir: &Path, input: &[u8], source: &str) -> Result<PathBuf, Error> {
+    let digest = Sha256::digest(input);
+    let hash: String = digest[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create corpus directory {}", dir.display()))?;
+    let suffix = format!("-{}.json", hash);
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(&suffix) {
+            return Ok(path);
+        }
+    }
+    let stem: String = Path::new(source)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(40)
+        .collect();
+    let stem = if stem.is_empty() { "case" } else { &stem };
+    let path = dir.join(format!("{}{}", stem, suffix));
+    fs::write(&path, input).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Collects the failed checks of one case.
+struct Replay<'a> {
+    case: &'a str,
+    failures: Vec<ReplayFailure>,
+}
+
+impl Replay<'_> {
+    /// Runs a check, recording its error or panic; returns its result if it passed.
+    fn check<T>(
+        &mut self,
+        check: &'static str,
+        run: impl FnOnce() -> Result<T, String>,
+    ) -> Option<T> {
+        let message = match catch_unwind(AssertUnwindSafe(run)) {
+            Ok(Ok(result)) => return Some(result),
+            Ok(Err(message)) => message,
+            Err(panic) => {
+                let reason = panic
+                    .downcast_ref::<&str>()
+                    .map(|reason| reason.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                format!("panicked: {}", reason)
+            }
+        };
+        self.failures.push(ReplayFailure {
+            case: self.case.to_string(),
+            check,
+            message,
+        });
+        None
+    }
+}
+
+/// Compares a value read back with the original.
+fn same_value(parsed: &Value, value: &Value) -> Result<(), String> {
+    if parsed == value {
+        Ok(())
+    } else {
+        Err(format!("read back as {}", parsed))
+    }
+}
+
+/// Counts the values below the root.
+fn descendants(value: &Value) -> usize {
+    match value {
+        Value::Array(items) => items.iter().map(|item| 1 + descendants(item)).sum(),
+        Value::Object(map) => map.values().map(|item| 1 + descendants(item)).sum(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Error, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// Resource limits for running untrusted expressions and jq programs, such as transform
+/// scripts submitted to shared infrastructure.
+///
+/// Neither language can read files, the environment or the network, so the sandbox
+/// bounds what remains: evaluation steps (CPU), bytes of strings, arrays and objects
+/// built, including the values copied into them (memory), and wall-clock time. Limits
+/// apply to one `run`, typically one record.
+///
+/// In a config file, the `sandbox` section has the same fields, with the time limit as
+/// `timeout_ms`: `{ "max_steps": 100000, "max_memory": 1048576, "timeout_ms": 200 }`.
+/// Fields left out keep their defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sandbox {
+    /// Maximum number of evaluation steps: one per filter or operand evaluated, plus one
+    /// per value produced by iterations such as `range` or `..`.
+    pub max_steps: u64,
+    /// Maximum bytes allocated for strings, arrays and objects built during evaluation,
+    /// counted cumulatively and approximately.
+    pub max_memory: usize,
+    /// Maximum wall-clock time.
+    #[serde(
+        rename = "timeout_ms",
+        serialize_with = "serialize_millis",
+        deserialize_with = "deserialize_millis"
+    )]
+    pub timeout: Duration,
+}
+
+/// Writes a duration as whole milliseconds.
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis().try_into().unwrap_or(u64::MAX))
+}
+
+/// Reads a duration from whole milliseconds.
+fn deserialize_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+impl Default for Sandbox {
+    /// One million steps, 64 MiB and one second per run.
+    fn default() -> Self {
+        Sandbox {
+            max_steps: 1_000_000,
+            max_memory: 64 << 20,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// What is left of the limits of the current run on this thread.
+struct Budget {
+    steps: u64,
+    memory: usize,
+    deadline: Instant,
+    /// The first limit that was exceeded.
+    exceeded: Option<&'static str>,
+}
+
+thread_local! {
+    static BUDGET: RefCell<Option<Budget>> = const { RefCell::new(None) };
+}
+
+/// Restores the enclosing budget when a run ends, even by a panic.
+struct BudgetGuard(Option<Budget>);
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        BUDGET.with(|budget| *budget.borrow_mut() = self.0.take());
+    }
+}
+
+impl Sandbox {
+    /// Runs an evaluation under these limits, e.g.
+    /// `sandbox.run(|| filter.run(&record))`.
+    ///
+    /// Expressions and jq programs run by `evaluate` on this thread stop as soon as a
+    /// limit is exceeded.
+    ///
+    /// # Arguments
+    ///
+    /// * `evaluate` - The evaluation.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<T, Error>` - The result of `evaluate`, or an error naming the exceeded
+    ///   limit, which takes precedence over whatever `evaluate` returned.
+    pub fn run<T>(&self, evaluate: impl FnOnce() -> T) -> Result<T, Error> {
+        let budget = Budget {
+            steps: self.max_steps,
+            memory: self.max_memory,
+            deadline: Instant::now() + self.timeout,
+            exceeded: None,
+        };
+        let guard = BudgetGuard(BUDGET.with(|current| current.borrow_mut().replace(budget)));
+        let result = evaluate();
+        let exceeded = BUDGET.with(|current| current.borrow().as_ref().and_then(|b| b.exceeded));
+        drop(guard);
+        match exceeded {
+            Some(limit) => Err(Error::msg(format!("Sandbox {} limit exceeded", limit))),
+            None => Ok(result),
+        }
+    }
+}
+
+/// Runs an evaluation under a sandbox if one is configured, e.g. the config's `sandbox`
+/// section for route rules, `compute` migrations and saved queries.
+///
+/// # Returns
+///
+/// * `Result<T, Error>` - The result of `evaluate`, or an error naming the exceeded limit.
+pub fn run_in<T>(sandbox: Option<&Sandbox>, evaluate: impl FnOnce() -> T) -> Result<T, Error> {
+    match sandbox {
+        Some(sandbox) => sandbox.run(evaluate),
+        None => Ok(evaluate()),
+    }
+}
+
+/// Counts evaluation steps against the current run's budget, if any.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error once the step or time limit is exceeded.
+pub(crate) fn charge(steps: u64) -> Result<(), Error> {
+    spend(|budget| {
+        // The clock is only read every 1024 steps, as it is slower than a step.
+        let ticks = budget.steps / 1024;
+        budget.steps = budget.steps.checked_sub(steps).ok_or("step")?;
+        if budget.steps / 1024 != ticks && Instant::now() > budget.deadline {
+            return Err("time");
+        }
+        Ok(())
+    })
+}
+
+/// Counts the approximate size of a value built during evaluation, including the values
+/// nested in it, as they are copies: string bytes, plus one `Value` per array element and
+/// two per object member. Also checks the time limit, as copying large values is slow.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error once the memory or time limit is exceeded.
+pub(crate) fn allocate(value: &Value) -> Result<(), Error> {
+    if !matches!(value, Value::String(_) | Value::Array(_) | Value::Object(_)) {
+        return Ok(());
+    }
+    spend(|budget| {
+        if Instant::now() > budget.deadline {
+            return Err("time");
+        }
+        let bytes = deep_size(value, budget.memory).ok_or("memory")?;
+        budget.memory -= bytes;
+        Ok(())
+    })
+}
+
+/// Returns the size `allocate` counts for a value, or `None` as soon as it exceeds
+/// `limit`, so that a huge value is not walked to the end.
+fn deep_size(value: &Value, limit: usize) -> Option<usize> {
+    let mut size = 0;
+    match value {
+        Value::String(text) => size = text.len(),
+        Value::Array(items) => {
+            for item in items {
+                let left = limit.checked_sub(size + std::mem::size_of::<Value>())?;
+                size += std::mem::size_of::<Value>() + deep_size(item, left)?;
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                let member = key.len() + 2 * std::mem::size_of::<Value>();
+                let left = limit.checked_sub(size + member)?;
+                size += member + deep_size(item, left)?;
+            }
+        }
+        _ => {}
+    }
+    (size <= limit).then_some(size)
+}
+
+/// Applies a charge to the current budget, remembering the first exceeded limit.
+fn spend(charge: impl FnOnce(&mut Budget) -> Result<(), &'static str>) -> Result<(), Error> {
+    BUDGET.with(|current| {
+        let mut current = current.borrow_mut();
+        let Some(budget) = current.as_mut() else {
+            return Ok(());
+        };
+        if let Some(limit) = budget.exceeded {
+            return Err(Error::msg(format!("Sandbox {} limit exceeded", limit)));
+        }
+        charge(budget).map_err(|limit| {
+            budget.exceeded = Some(limit);
+            Error::msg(format!("Sandbox {} limit exceeded", limit))
+        })
+    })
+}
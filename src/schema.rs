@@ -0,0 +1,708 @@
+use crate::parser::MAX_ARRAY_PADDING;
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// Policy used when two schemas constrain the same keyword differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintPolicy {
+    /// Keeps the most permissive constraint, accepting data valid for any input schema.
+    Loosest,
+    /// Keeps the most restrictive constraint, accepting only data valid for all input schemas.
+    Strictest,
+}
+
+impl FromStr for ConstraintPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "loosest" => Ok(ConstraintPolicy::Loosest),
+            "strictest" => Ok(ConstraintPolicy::Strictest),
+            _ => Err(Error::msg(format!("Unsupported constraint policy: {}", s))),
+        }
+    }
+}
+
+/// Lower-bound keywords: the loosest value is the smallest one.
+const LOWER_BOUNDS: &[&str] = &[
+    "minimum",
+    "exclusiveMinimum",
+    "minLength",
+    "minItems",
+    "minProperties",
+];
+
+/// Upper-bound keywords: the loosest value is the largest one.
+const UPPER_BOUNDS: &[&str] = &[
+    "maximum",
+    "exclusiveMaximum",
+    "maxLength",
+    "maxItems",
+    "maxProperties",
+];
+
+/// Merges several JSON Schemas into one using the loosest constraint policy.
+///
+/// # Arguments
+///
+/// * `schemas` - The schemas to merge.
+///
+/// # Returns
+///
+/// * `Value` - A schema accepting any document accepted by one of the inputs.
+pub fn merge_schemas(schemas: &[Value]) -> Value {
+    merge_schemas_with(schemas, ConstraintPolicy::Loosest)
+}
+
+/// Merges several JSON Schemas into one.
+///
+/// Properties are united and merged recursively. `required`, `type`, `enum`,
+/// numeric bounds, and `additionalProperties` are combined according to `policy`.
+/// For any other keyword the first schema defining it wins.
+///
+/// # Arguments
+///
+/// * `schemas` - The schemas to merge.
+/// * `policy` - Whether conflicting constraints are loosened or tightened.
+///
+/// # Returns
+///
+/// * `Value` - The merged schema, or an empty schema if `schemas` is empty.
+pub fn merge_schemas_with(schemas: &[Value], policy: ConstraintPolicy) -> Value {
+    schemas
+        .iter()
+        .fold(None, |merged: Option<Value>, schema| match merged {
+            None => Some(schema.clone()),
+            Some(merged) => Some(merge_two(&merged, schema, policy)),
+        })
+        .unwrap_or_else(|| Value::Object(Map::new()))
+}
+
+/// Merges two schemas keyword by keyword.
+fn merge_two(a: &Value, b: &Value, policy: ConstraintPolicy) -> Value {
+    let (a_map, b_map) = match (a, b) {
+        (Value::Object(a_map), Value::Object(b_map)) => (a_map, b_map),
+        // Boolean schemas: `true` accepts everything, `false` nothing.
+        (Value::Bool(x), Value::Bool(y)) => {
+            return Value::Bool(match policy {
+                ConstraintPolicy::Loosest => *x || *y,
+                ConstraintPolicy::Strictest => *x && *y,
+            })
+        }
+        (Value::Bool(flag), other) | (other, Value::Bool(flag)) => {
+            return match (policy, flag) {
+                (ConstraintPolicy::Loosest, true) | (ConstraintPolicy::Strictest, false) => {
+                    Value::Bool(*flag)
+                }
+                _ => other.clone(),
+            }
+        }
+        _ => return a.clone(),
+    };
+
+    let mut merged = a_map.clone();
+    for (key, b_value) in b_map {
+        if key == "properties" {
+            // Merged below, as it depends on `additionalProperties` of both schemas.
+            continue;
+        }
+        let a_value = match a_map.get(key) {
+            Some(a_value) => a_value,
+            None => {
+                // A keyword missing on one side is unconstrained there, so the
+                // loosest merge must drop it unless it only adds structure.
+                if policy == ConstraintPolicy::Strictest || is_structural(key) {
+                    merged.insert(key.clone(), b_value.clone());
+                }
+                continue;
+            }
+        };
+        let value = match key.as_str() {
+            "items" | "additionalProperties" => merge_two(a_value, b_value, policy),
+            "required" => merge_string_sets(a_value, b_value, policy),
+            "enum" => merge_value_sets(a_value, b_value, policy),
+            "type" => merge_types(a_value, b_value, policy),
+            k if LOWER_BOUNDS.contains(&k) => pick_bound(a_value, b_value, policy, true),
+            k if UPPER_BOUNDS.contains(&k) => pick_bound(a_value, b_value, policy, false),
+            _ => a_value.clone(),
+        };
+        merged.insert(key.clone(), value);
+    }
+
+    if a_map.contains_key("properties") || b_map.contains_key("properties") {
+        merged.insert(
+            "properties".to_string(),
+            merge_properties(a_map, b_map, policy),
+        );
+    }
+    if policy == ConstraintPolicy::Loosest {
+        // Constraints present only in the first schema do not apply to the second.
+        merged.retain(|key, _| b_map.contains_key(key) || is_structural(key));
+    }
+    Value::Object(merged)
+}
+
+/// Returns true for keywords that describe structure rather than constrain it.
+fn is_structural(key: &str) -> bool {
+    matches!(
+        key,
+        "properties" | "items" | "$schema" | "$id" | "title" | "description" | "definitions"
+    )
+}
+
+/// Unites the `properties` of two schemas, merging schemas of shared properties.
+///
+/// Under the strictest policy, a property that only one schema lists must also be
+/// allowed by the other's `additionalProperties`: it is merged with that schema, or left
+/// out if it is `false`, so the merged schema accepts no object that either rejects.
+fn merge_properties(
+    a: &Map<String, Value>,
+    b: &Map<String, Value>,
+    policy: ConstraintPolicy,
+) -> Value {
+    let empty = Map::new();
+    let a_properties = a
+        .get("properties")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    let b_properties = b
+        .get("properties")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    let mut merged = Map::new();
+    for (name, a_schema) in a_properties {
+        let schema = match b_properties.get(name) {
+            Some(b_schema) => Some(merge_two(a_schema, b_schema, policy)),
+            None => listed_once(a_schema, b, policy),
+        };
+        if let Some(schema) = schema {
+            merged.insert(name.clone(), schema);
+        }
+    }
+    for (name, b_schema) in b_properties {
+        if !a_properties.contains_key(name) {
+            if let Some(schema) = listed_once(b_schema, a, policy) {
+                merged.insert(name.clone(), schema);
+            }
+        }
+    }
+    Value::Object(merged)
+}
+
+/// Returns the merged schema of a property that only one schema lists, given the other
+/// schema, or `None` if the strictest merge must leave it out.
+fn listed_once(
+    schema: &Value,
+    other: &Map<String, Value>,
+    policy: ConstraintPolicy,
+) -> Option<Value> {
+    match (policy, other.get("additionalProperties")) {
+        (ConstraintPolicy::Strictest, Some(Value::Bool(false))) => None,
+        (ConstraintPolicy::Strictest, Some(extra)) => Some(merge_two(schema, extra, policy)),
+        _ => Some(schema.clone()),
+    }
+}
+
+/// Combines two arrays of strings as an intersection (loosest) or union (strictest).
+fn merge_string_sets(a: &Value, b: &Value, policy: ConstraintPolicy) -> Value {
+    let a_items = a.as_array().cloned().unwrap_or_default();
+    let b_items = b.as_array().cloned().unwrap_or_default();
+    match policy {
+        ConstraintPolicy::Loosest => Value::Array(
+            a_items
+                .into_iter()
+                .filter(|item| b_items.contains(item))
+                .collect(),
+        ),
+        ConstraintPolicy::Strictest => union(a_items, b_items),
+    }
+}
+
+/// Combines two `enum` arrays as a union (loosest) or intersection (strictest).
+fn merge_value_sets(a: &Value, b: &Value, policy: ConstraintPolicy) -> Value {
+    let a_items = a.as_array().cloned().unwrap_or_default();
+    let b_items = b.as_array().cloned().unwrap_or_default();
+    match policy {
+        ConstraintPolicy::Loosest => union(a_items, b_items),
+        ConstraintPolicy::Strictest => Value::Array(
+            a_items
+                .into_iter()
+                .filter(|item| b_items.contains(item))
+                .collect(),
+        ),
+    }
+}
+
+/// Combines two `type` keywords, which may each be a string or an array of strings.
+fn merge_types(a: &Value, b: &Value, policy: ConstraintPolicy) -> Value {
+    let as_list = |value: &Value| match value {
+        Value::Array(items) => items.clone(),
+        other => vec![other.clone()],
+    };
+    let combined = merge_value_sets(&Value::Array(as_list(a)), &Value::Array(as_list(b)), policy);
+    match combined {
+        Value::Array(mut items) if items.len() == 1 => items.remove(0),
+        other => other,
+    }
+}
+
+/// Picks the looser or stricter of two numeric bounds.
+fn pick_bound(a: &Value, b: &Value, policy: ConstraintPolicy, lower: bool) -> Value {
+    let (x, y) = match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => (x, y),
+        _ => return a.clone(),
+    };
+    let take_smaller = lower == (policy == ConstraintPolicy::Loosest);
+    if (x <= y) == take_smaller {
+        a.clone()
+    } else {
+        b.clone()
+    }
+}
+
+/// Unites two arrays, preserving order and dropping duplicates.
+fn union(a: Vec<Value>, b: Vec<Value>) -> Value {
+    let mut items = a;
+    for item in b {
+        if !items.contains(&item) {
+            items.push(item);
+        }
+    }
+    Value::Array(items)
+}
+
+/// Keywords whose values are a single subschema.
+const SUBSCHEMA_KEYWORDS: &[&str] = &["items", "additionalProperties", "not", "contains"];
+
+/// Keywords whose values are maps from names to subschemas.
+const SCHEMA_MAP_KEYWORDS: &[&str] = &["properties", "definitions", "$defs", "patternProperties"];
+
+/// Simplifies a JSON Schema without changing the set of documents it accepts.
+///
+/// The pass is applied recursively and performs the following rewrites:
+/// - identical `anyOf`/`allOf` branches are collapsed, and a single remaining
+///   `anyOf`/`oneOf`/`allOf` branch is inlined; `oneOf` branches are kept, since a
+///   document matching two identical branches matches none of `oneOf`;
+/// - `anyOf` branches consisting only of `type`, `enum`, or `const` are merged
+///   into one `type` list or one `enum`;
+/// - duplicate `enum`, `type`, and `required` entries are removed, and `integer`
+///   is dropped from a `type` list that already contains `number`;
+/// - constraints that never reject anything (`minLength: 0`, `required: []`,
+///   `additionalProperties: true`, ...) are dropped.
+///
+/// # Arguments
+///
+/// * `schema` - The schema to simplify.
+///
+/// # Returns
+///
+/// * `Value` - The simplified schema.
+pub fn simplify_schema(schema: &Value) -> Value {
+    let map = match schema {
+        Value::Object(map) => map,
+        other => return other.clone(),
+    };
+
+    let mut simplified = Map::new();
+    for (key, value) in map {
+        let value = if SUBSCHEMA_KEYWORDS.contains(&key.as_str()) {
+            simplify_schema(value)
+        } else if SCHEMA_MAP_KEYWORDS.contains(&key.as_str()) {
+            match value {
+                Value::Object(entries) => Value::Object(
+                    entries
+                        .iter()
+                        .map(|(name, sub)| (name.clone(), simplify_schema(sub)))
+                        .collect(),
+                ),
+                other => other.clone(),
+            }
+        } else if matches!(key.as_str(), "anyOf" | "allOf") {
+            match value {
+                Value::Array(branches) => {
+                    Value::Array(dedupe(branches.iter().map(simplify_schema).collect()))
+                }
+                other => other.clone(),
+            }
+        } else if key == "oneOf" {
+            match value {
+                Value::Array(branches) => Value::Array(branches.iter().map(simplify_schema).collect()),
+                other => other.clone(),
+            }
+        } else if matches!(key.as_str(), "enum" | "required") {
+            match value {
+                Value::Array(items) => Value::Array(dedupe(items.clone())),
+                other => other.clone(),
+            }
+        } else if key == "type" {
+            simplify_type(value)
+        } else {
+            value.clone()
+        };
+        if !is_redundant(key, &value) {
+            simplified.insert(key.clone(), value);
+        }
+    }
+
+    if let Some(Value::Array(branches)) = simplified.get("anyOf").cloned() {
+        if let Some(merged) = merge_trivial_branches(&branches) {
+            simplified.remove("anyOf");
+            simplified.insert("anyOf".to_string(), Value::Array(merged));
+        }
+    }
+    for keyword in ["anyOf", "oneOf", "allOf"] {
+        inline_single_branch(&mut simplified, keyword);
+    }
+    Value::Object(simplified)
+}
+
+/// Returns true if a keyword/value pair never rejects a document.
+fn is_redundant(key: &str, value: &Value) -> bool {
+    match key {
+        "minLength" | "minItems" | "minProperties" => value.as_u64() == Some(0),
+        "uniqueItems" => value == &Value::Bool(false),
+        "additionalProperties" | "items" => {
+            value == &Value::Bool(true) || value.as_object().is_some_and(|m| m.is_empty())
+        }
+        "required" => value.as_array().is_some_and(|items| items.is_empty()),
+        "properties" => value.as_object().is_some_and(|m| m.is_empty()),
+        _ => false,
+    }
+}
+
+/// Deduplicates a `type` keyword and unwraps single-element lists.
+fn simplify_type(value: &Value) -> Value {
+    let mut types = match value {
+        Value::Array(items) => dedupe(items.clone()),
+        other => return other.clone(),
+    };
+    if types.contains(&Value::String("number".to_string())) {
+        types.retain(|t| t != "integer");
+    }
+    if types.len() == 1 {
+        types.remove(0)
+    } else {
+        Value::Array(types)
+    }
+}
+
+/// Merges `anyOf` branches that consist solely of `type`, or solely of `enum`/`const`.
+/// Returns `None` if no branches could be merged.
+fn merge_trivial_branches(branches: &[Value]) -> Option<Vec<Value>> {
+    let mut types = Vec::new();
+    let mut values = Vec::new();
+    let mut rest = Vec::new();
+    let (mut type_branches, mut enum_branches) = (0, 0);
+
+    for branch in branches {
+        let map = match branch.as_object() {
+            Some(map) if map.len() == 1 => map,
+            _ => {
+                rest.push(branch.clone());
+                continue;
+            }
+        };
+        match map.iter().next() {
+            Some((key, Value::String(t))) if key == "type" => {
+                types.push(Value::String(t.clone()));
+                type_branches += 1;
+            }
+            Some((key, Value::Array(ts))) if key == "type" => {
+                types.extend(ts.iter().cloned());
+                type_branches += 1;
+            }
+            Some((key, Value::Array(items))) if key == "enum" => {
+                values.extend(items.iter().cloned());
+                enum_branches += 1;
+            }
+            Some((key, item)) if key == "const" => {
+                values.push(item.clone());
+                enum_branches += 1;
+            }
+            _ => rest.push(branch.clone()),
+        }
+    }
+    if type_branches < 2 && enum_branches < 2 {
+        return None;
+    }
+
+    let mut merged = Vec::new();
+    if !types.is_empty() {
+        let mut map = Map::new();
+        map.insert("type".to_string(), simplify_type(&Value::Array(types)));
+        merged.push(Value::Object(map));
+    }
+    if !values.is_empty() {
+        let mut map = Map::new();
+        map.insert("enum".to_string(), Value::Array(dedupe(values)));
+        merged.push(Value::Object(map));
+    }
+    merged.extend(rest);
+    Some(merged)
+}
+
+/// Replaces a combinator with its only branch when that branch can be merged
+/// into the parent without overwriting existing keywords.
+fn inline_single_branch(schema: &mut Map<String, Value>, keyword: &str) {
+    let branch = match schema.get(keyword) {
+        Some(Value::Array(branches)) if branches.len() == 1 => branches[0].clone(),
+        _ => return,
+    };
+    if let Value::Object(branch_map) = branch {
+        if branch_map.keys().all(|key| !schema.contains_key(key)) {
+            schema.remove(keyword);
+            schema.extend(branch_map);
+        }
+    }
+}
+
+/// Removes duplicate values while preserving order.
+fn dedupe(items: Vec<Value>) -> Vec<Value> {
+    let mut unique: Vec<Value> = Vec::new();
+    for item in items {
+        if !unique.contains(&item) {
+            unique.push(item);
+        }
+    }
+    unique
+}
+
+/// Deterministic example documents derived from a schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaExamples {
+    /// Contains only required properties and the fewest allowed array items.
+    pub minimal: Value,
+    /// Contains every declared property, including optional ones.
+    pub maximal: Value,
+}
+
+/// Builds a minimal and a maximal example document from a JSON Schema.
+///
+/// Generation is deterministic: `const`, `default`, `examples`, and `enum`
+/// are preferred when present, otherwise a placeholder satisfying the type,
+/// `format`, and bound keywords is produced. `minItems` and `minLength` are only
+/// padded to up to `MAX_ARRAY_PADDING` items or characters, so that a huge bound
+/// gives a short example instead of exhausting memory.
+///
+/// # Arguments
+///
+/// * `schema` - The schema to derive examples from.
+///
+/// # Returns
+///
+/// * `SchemaExamples` - The minimal and maximal examples.
+pub fn example_from_schema(schema: &Value) -> SchemaExamples {
+    SchemaExamples {
+        minimal: build_example(schema, false),
+        maximal: build_example(schema, true),
+    }
+}
+
+/// Recursive helper for `example_from_schema`.
+fn build_example(schema: &Value, maximal: bool) -> Value {
+    let map = match schema {
+        Value::Object(map) => map,
+        _ => return Value::Null,
+    };
+
+    if let Some(value) = map.get("const").or_else(|| map.get("default")) {
+        return value.clone();
+    }
+    for keyword in ["examples", "enum"] {
+        if let Some(first) = map
+            .get(keyword)
+            .and_then(|v| v.as_array())
+            .and_then(|v| v.first())
+        {
+            return first.clone();
+        }
+    }
+    if let Some(Value::Array(branches)) = map.get("allOf") {
+        let mut parts = vec![Value::Object(map.clone())];
+        parts[0].as_object_mut().unwrap().remove("allOf");
+        parts.extend(branches.iter().cloned());
+        return build_example(
+            &merge_schemas_with(&parts, ConstraintPolicy::Strictest),
+            maximal,
+        );
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(first) = map
+            .get(keyword)
+            .and_then(|v| v.as_array())
+            .and_then(|v| v.first())
+        {
+            return build_example(first, maximal);
+        }
+    }
+
+    let schema_type = match map.get("type") {
+        Some(Value::String(t)) => t.as_str(),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(|t| t.as_str())
+            .find(|t| *t != "null")
+            .unwrap_or("null"),
+        _ if map.contains_key("properties") => "object",
+        _ if map.contains_key("items") => "array",
+        _ => "null",
+    };
+
+    match schema_type {
+        "object" => {
+            let required: Vec<&str> = map
+                .get("required")
+                .and_then(|r| r.as_array())
+                .map(|r| r.iter().filter_map(|k| k.as_str()).collect())
+                .unwrap_or_default();
+            let mut object = Map::new();
+            if let Some(Value::Object(properties)) = map.get("properties") {
+                for (name, property) in properties {
+                    if maximal || required.contains(&name.as_str()) {
+                        object.insert(name.clone(), build_example(property, maximal));
+                    }
+                }
+            }
+            Value::Object(object)
+        }
+        "array" => {
+            let min_items = map
+                .get("minItems")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0)
+                .min(MAX_ARRAY_PADDING as u64);
+            let max_items = map
+                .get("maxItems")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(u64::MAX);
+            let count = if maximal {
+                min_items.max(1).min(max_items)
+            } else {
+                min_items
+            };
+            let item = map.get("items").map(|items| build_example(items, maximal));
+            Value::Array(
+                (0..count)
+                    .map(|_| item.clone().unwrap_or(Value::Null))
+                    .collect(),
+            )
+        }
+        "string" => Value::String(example_string(map)),
+        "integer" => {
+            let lower = tighter_bound(map, "minimum", "exclusiveMinimum", true).map(
+                |(bound, exclusive)| {
+                    if exclusive {
+                        bound.floor() + 1.0
+                    } else {
+                        bound.ceil()
+                    }
+                },
+            );
+            let upper = tighter_bound(map, "maximum", "exclusiveMaximum", false).map(
+                |(bound, exclusive)| {
+                    if exclusive {
+                        bound.ceil() - 1.0
+                    } else {
+                        bound.floor()
+                    }
+                },
+            );
+            Value::from(clamp(0.0, lower, upper) as i64)
+        }
+        "number" => {
+            let lower = tighter_bound(map, "minimum", "exclusiveMinimum", true);
+            let upper = tighter_bound(map, "maximum", "exclusiveMaximum", false);
+            let fits = |value: f64| {
+                lower.is_none_or(|(low, exclusive)| value > low || !exclusive && value == low)
+                    && upper
+                        .is_none_or(|(high, exclusive)| value < high || !exclusive && value == high)
+            };
+            let value = match (lower, upper) {
+                _ if fits(0.0) => 0.0,
+                (Some((low, false)), _) if fits(low) => low,
+                (_, Some((high, false))) if fits(high) => high,
+                // Strictly between the bounds, at least one of which is exclusive.
+                (Some((low, _)), Some((high, _))) => low + (high - low) / 2.0,
+                (Some((low, _)), None) => low + low.abs().max(1.0),
+                (None, Some((high, _))) => high - high.abs().max(1.0),
+                (None, None) => 0.0,
+            };
+            Value::from(value)
+        }
+        "boolean" => Value::Bool(false),
+        _ => Value::Null,
+    }
+}
+
+/// Returns the tighter of an inclusive and an exclusive bound, e.g. of `minimum` and
+/// `exclusiveMinimum`, with whether it is exclusive. On a tie the exclusive one wins.
+fn tighter_bound(
+    map: &Map<String, Value>,
+    inclusive: &str,
+    exclusive: &str,
+    lower: bool,
+) -> Option<(f64, bool)> {
+    let inclusive = map.get(inclusive).and_then(Value::as_f64);
+    let exclusive = map.get(exclusive).and_then(Value::as_f64);
+    match (inclusive, exclusive) {
+        (Some(inclusive), Some(exclusive)) => {
+            let tighter = if lower {
+                inclusive > exclusive
+            } else {
+                inclusive < exclusive
+            };
+            Some(if tighter {
+                (inclusive, false)
+            } else {
+                (exclusive, true)
+            })
+        }
+        (Some(inclusive), None) => Some((inclusive, false)),
+        (None, Some(exclusive)) => Some((exclusive, true)),
+        (None, None) => None,
+    }
+}
+
+/// Clamps a preferred value into optional inclusive bounds.
+fn clamp(preferred: f64, lower: Option<f64>, upper: Option<f64>) -> f64 {
+    let mut value = preferred;
+    if let Some(lower) = lower {
+        value = value.max(lower);
+    }
+    if let Some(upper) = upper {
+        value = value.min(upper);
+    }
+    value
+}
+
+/// Produces a placeholder string honoring `format`, `minLength`, and `maxLength`.
+fn example_string(map: &Map<String, Value>) -> String {
+    let base = match map.get("format").and_then(|f| f.as_str()) {
+        Some("date") => "2024-01-01",
+        Some("date-time") => "2024-01-01T00:00:00Z",
+        Some("time") => "00:00:00",
+        Some("email") => "user@example.com",
+        Some("uri") | Some("url") => "https://example.com",
+        Some("uuid") => "00000000-0000-0000-0000-000000000000",
+        Some("ipv4") => "127.0.0.1",
+        Some("ipv6") => "::1",
+        _ => "string",
+    };
+    let min_length = map
+        .get("minLength")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0)
+        .min(MAX_ARRAY_PADDING as u64) as usize;
+    let max_length = map
+        .get("maxLength")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+        .unwrap_or(usize::MAX);
+
+    let mut value = base.to_string();
+    while value.chars().count() < min_length {
+        value.push('x');
+    }
+    value.chars().take(max_length).collect()
+}
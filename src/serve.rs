@@ -0,0 +1,353 @@
+use crate::config::Config;
+use crate::redact::{redact, RedactionRules};
+use anyhow::{Error, Result};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest request line or header line read before a request is rejected.
+const MAX_LINE: usize = 8192;
+
+/// Most header lines of a request; requests with more are rejected with 431.
+const MAX_HEADERS: usize = 100;
+
+/// Time a client has to send its request and read the response, in total.
+const REQUEST_DEADLINE: Duration = Duration::from_secs(10);
+
+/// Most connections answered at once; further ones get 503 until a worker is free.
+const MAX_CONNECTIONS: usize = 64;
+
+/// Pause after a failed `accept`, so that running out of file descriptors does not
+/// turn the accept loop into a busy loop.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A response of `DocumentServer::handle`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// The HTTP status code, e.g. 200 or 401.
+    pub status: u16,
+    /// The JSON body: the document, the list of documents, or `{"error": ...}`.
+    pub body: Value,
+}
+
+impl Response {
+    /// Creates an error response with a message.
+    fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+}
+
+/// Serves the JSON documents stored in a directory over HTTP, masking each response with
+/// the redaction profile bound to the caller's access token, so different consumers of
+/// the same documents see different views.
+///
+/// `GET /documents` lists the documents, and `GET /documents/<name>` returns
+/// `<name>.json` redacted with the profile of the `Authorization: Bearer <token>` header.
+/// Requests without a known token are refused with 401.
+#[derive(Debug, Clone)]
+pub struct DocumentServer {
+    root: PathBuf,
+    /// The rules of each token, by token digest.
+    profiles: BTreeMap<String, RedactionRules>,
+}
+
+impl DocumentServer {
+    /// Creates a server for a directory, with the tokens and profiles of a config.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The directory holding the `.json` documents.
+    /// * `config` - The config, with `tokens` bound to its `redaction` profiles.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<DocumentServer, Error>` - The server, or an error if the config has no
+    ///   tokens or a token is bound to an unknown profile.
+    pub fn new(root: &Path, config: &Config) -> Result<DocumentServer, Error> {
+        if config.tokens.is_empty() {
+            return Err(Error::msg(
+                "The config has no access tokens; add \"tokens\" bound to redaction profiles",
+            ));
+        }
+        let profiles = config
+            .tokens
+            .iter()
+            .map(|(digest, profile)| {
+                Ok((
+                    digest.to_ascii_lowercase(),
+                    config.redaction_profile(profile)?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+        Ok(DocumentServer {
+            root: root.to_path_buf(),
+            profiles,
+        })
+    }
+
+    /// Answers a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The HTTP method; only `GET` is allowed.
+    /// * `target` - The request target, e.g. `/documents/orders`; a query is ignored.
+    /// * `authorization` - The `Authorization` header, if any.
+    ///
+    /// # Returns
+    ///
+    /// * `Response` - The status and JSON body.
+    pub fn handle(&self, method: &str, target: &str, authorization: Option<&str>) -> Response {
+        let token = authorization.and_then(|header| {
+            let (scheme, token) = header.trim().split_once(' ')?;
+            scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+        });
+        let Some(rules) = token.and_then(|token| self.profiles.get(&token_digest(token))) else {
+            return Response::error(401, "A valid bearer token is required");
+        };
+        if method != "GET" {
+            return Response::error(405, "Only GET is supported");
+        }
+        let path = target.split('?').next().unwrap_or_default();
+        match path.strip_prefix("/documents") {
+            Some("" | "/") => match self.document_names() {
+                Ok(names) => Response {
+                    status: 200,
+                    body: json!(names),
+                },
+                Err(_) => Response::error(500, "The documents cannot be listed"),
+            },
+            Some(rest) => match rest.strip_prefix('/').filter(|name| valid_name(name)) {
+                Some(name) => self.document(name, rules),
+                None => Response::error(404, "No such document"),
+            },
+            None => Response::error(404, "No such resource"),
+        }
+    }
+
+    /// Accepts connections forever, answering one request per connection on a thread
+    /// of its own, up to `MAX_CONNECTIONS` at once. Each client has `REQUEST_DEADLINE`
+    /// in total to send its request and read the response, so slow clients cannot hold
+    /// up others. Failed connections and failed `accept` calls are logged as warnings
+    /// and do not stop the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `listener` - The bound listener, e.g. on `127.0.0.1:8080`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - Does not return while the process runs.
+    pub fn serve(&self, listener: TcpListener) -> Result<(), Error> {
+        let active = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("Failed to accept a connection: {}", e);
+                        thread::sleep(ACCEPT_BACKOFF);
+                        continue;
+                    }
+                };
+                if active.fetch_add(1, Ordering::AcqRel) >= MAX_CONNECTIONS {
+                    active.fetch_sub(1, Ordering::AcqRel);
+                    let busy = Response::error(503, "Too many connections, try again later");
+                    if let Err(e) = write_response(stream, &busy, Instant::now() + ACCEPT_BACKOFF) {
+                        log::warn!("Request failed: {}", e);
+                    }
+                    continue;
+                }
+                let active = &active;
+                scope.spawn(move || {
+                    if let Err(e) = self.answer(stream) {
+                        log::warn!("Request failed: {}", e);
+                    }
+                    active.fetch_sub(1, Ordering::AcqRel);
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Reads one request from a connection and writes the response, within
+    /// `REQUEST_DEADLINE` of being called.
+    fn answer(&self, stream: TcpStream) -> io::Result<()> {
+        let deadline = Instant::now() + REQUEST_DEADLINE;
+        let mut reader = BufReader::new(DeadlineStream {
+            stream: stream.try_clone()?,
+            deadline,
+        });
+        let request_line = read_line(&mut reader)?;
+        let mut authorization = None;
+        let mut headers = 0;
+        loop {
+            let line = read_line(&mut reader)?;
+            if line.is_empty() {
+                break;
+            }
+            headers += 1;
+            if headers > MAX_HEADERS {
+                let response = Response::error(431, "Too many header fields");
+                return write_response(stream, &response, deadline);
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("authorization") {
+                    authorization = Some(value.trim().to_string());
+                }
+            }
+        }
+        let mut parts = request_line.split_whitespace();
+        let response = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => self.handle(method, target, authorization.as_deref()),
+            _ => Response::error(400, "Malformed request line"),
+        };
+        write_response(stream, &response, deadline)
+    }
+
+    /// Returns the names of the documents, without `.json`, sorted.
+    fn document_names(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    if valid_name(name) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Reads and redacts a document.
+    fn document(&self, name: &str, rules: &RedactionRules) -> Response {
+        let Ok(text) = fs::read_to_string(self.root.join(format!("{}.json", name))) else {
+            return Response::error(404, "No such document");
+        };
+        match serde_json::from_str::<Value>(&text) {
+            Ok(mut json) => {
+                redact(&mut json, rules);
+                Response {
+                    status: 200,
+                    body: json,
+                }
+            }
+            Err(_) => Response::error(500, "The document is not valid JSON"),
+        }
+    }
+}
+
+/// Returns the SHA-256 hex digest of an access token, as `tokens` in the config lists
+/// them, so the config file does not hold the tokens themselves.
+///
+/// # Arguments
+///
+/// * `token` - The token, as sent after `Bearer`.
+///
+/// # Returns
+///
+/// * `String` - The lowercase hex digest, as printed by `printf %s "$TOKEN" | sha256sum`.
+pub fn token_digest(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Returns whether a document name is safe to join to the root: letters, digits, `-`,
+/// `_` and `.`, not starting with `.`.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// A connection whose reads fail with `TimedOut` once a deadline has passed, however
+/// slowly the client trickles in bytes.
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(remaining(self.deadline)?))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Returns the time left until a deadline, or a `TimedOut` error once it has passed.
+fn remaining(deadline: Instant) -> io::Result<Duration> {
+    let left = deadline.saturating_duration_since(Instant::now());
+    if left.is_zero() {
+        return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Request deadline exceeded",
+        ));
+    }
+    Ok(left)
+}
+
+/// Reads a line without its line ending, refusing lines longer than `MAX_LINE`.
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_LINE as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if line.len() > MAX_LINE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Request line too long",
+        ));
+    }
+    while line
+        .last()
+        .is_some_and(|byte| matches!(byte, b'\n' | b'\r'))
+    {
+        line.pop();
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// Writes a response before a deadline and closes the connection.
+fn write_response(mut stream: TcpStream, response: &Response, deadline: Instant) -> io::Result<()> {
+    stream.set_write_timeout(Some(remaining(deadline)?))?;
+    let body = serde_json::to_string_pretty(&response.body)?;
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason,
+        body.len()
+    );
+    if response.status == 401 {
+        head.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
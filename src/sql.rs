@@ -0,0 +1,213 @@
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+
+/// Options for `json_to_sql`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlOptions {
+    /// The table to insert into, optionally with its schema: `users` or `analytics.users`.
+    pub table: String,
+    /// Rows per `INSERT` statement; more rows per statement load faster.
+    pub rows_per_insert: usize,
+    /// Starts with a `CREATE TABLE IF NOT EXISTS` statement whose column types are
+    /// inferred from the data.
+    pub create_table: bool,
+}
+
+impl Default for SqlOptions {
+    fn default() -> Self {
+        SqlOptions {
+            table: "data".to_string(),
+            rows_per_insert: 1,
+            create_table: false,
+        }
+    }
+}
+
+/// The PostgreSQL type of a column, widened as values of other types are seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnType {
+    /// Only `null` so far.
+    Unknown,
+    Boolean,
+    BigInt,
+    /// Integers beyond the range of `bigint`.
+    Numeric,
+    Double,
+    Text,
+    Jsonb,
+}
+
+impl ColumnType {
+    /// Returns the type of a value.
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => ColumnType::Unknown,
+            Value::Bool(_) => ColumnType::Boolean,
+            Value::Number(number) if number.is_i64() => ColumnType::BigInt,
+            Value::Number(number) if number.is_u64() => ColumnType::Numeric,
+            Value::Number(_) => ColumnType::Double,
+            Value::String(_) => ColumnType::Text,
+            Value::Array(_) | Value::Object(_) => ColumnType::Jsonb,
+        }
+    }
+
+    /// Returns a type holding the values of both; mixed types fall back to `text`.
+    fn widen(self, other: Self) -> Self {
+        use ColumnType::*;
+        match (self, other) {
+            (Unknown, other) | (other, Unknown) => other,
+            (a, b) if a == b => a,
+            (BigInt | Numeric, BigInt | Numeric) => Numeric,
+            (BigInt | Numeric | Double, BigInt | Numeric | Double) => Double,
+            _ => Text,
+        }
+    }
+
+    /// Returns the type as written in `CREATE TABLE`; columns of only `null` are `text`.
+    fn name(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "boolean",
+            ColumnType::BigInt => "bigint",
+            ColumnType::Numeric => "numeric",
+            ColumnType::Double => "double precision",
+            ColumnType::Unknown | ColumnType::Text => "text",
+            ColumnType::Jsonb => "jsonb",
+        }
+    }
+}
+
+/// Writes an array of flat objects as SQL `INSERT` statements with literal values, to
+/// load JSON exports into PostgreSQL with `psql -f`.
+///
+/// Columns are listed in order of first appearance, and fields missing from a row are
+/// `NULL`. Strings are quoted with `'` doubled, numbers and booleans written as they are,
+/// and nested objects and arrays as JSON text for `json` or `jsonb` columns. Table and
+/// column names are always double-quoted, so they keep their case and may be keywords.
+///
+/// # Arguments
+///
+/// * `json` - The array of objects; a single object is one row.
+/// * `options` - The table, rows per statement, and whether to create the table.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The statements, one per line or per group of rows, or an
+///   error if an element is not an object, a string holds a NUL character, or the table
+///   name or `rows_per_insert` is invalid.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(json)))]
+pub fn json_to_sql(json: &Value, options: &SqlOptions) -> Result<String, Error> {
+    if options.rows_per_insert == 0 {
+        return Err(Error::msg("Rows per INSERT must be positive"));
+    }
+    let table = quote_table(&options.table)?;
+    let rows: Vec<&Map<String, Value>> = match json {
+        Value::Object(row) => vec![row],
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                item.as_object().ok_or_else(|| {
+                    Error::msg(format!(
+                        "Element {} is not an object and cannot be a table row",
+                        index
+                    ))
+                })
+            })
+            .collect::<Result<_>>()?,
+        _ => {
+            return Err(Error::msg(
+                "Only an array of objects can be converted to SQL",
+            ))
+        }
+    };
+    let mut columns: Vec<(&str, ColumnType)> = Vec::new();
+    for row in &rows {
+        for (name, value) in row.iter() {
+            match columns.iter_mut().find(|(column, _)| column == name) {
+                Some((_, column_type)) => *column_type = column_type.widen(ColumnType::of(value)),
+                None => columns.push((name, ColumnType::of(value))),
+            }
+        }
+    }
+    if columns.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut sql = String::new();
+    if options.create_table {
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(name, column_type)| {
+                format!("  {} {}", quote_identifier(name), column_type.name())
+            })
+            .collect();
+        sql.push_str(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (\n{}\n);\n",
+            table,
+            definitions.join(",\n")
+        ));
+    }
+    let names: Vec<String> = columns
+        .iter()
+        .map(|(name, _)| quote_identifier(name))
+        .collect();
+    let prefix = format!("INSERT INTO {} ({}) VALUES", table, names.join(", "));
+    for group in rows.chunks(options.rows_per_insert) {
+        let tuples = group
+            .iter()
+            .map(|row| {
+                let values = columns
+                    .iter()
+                    .map(|(name, _)| literal(row.get(*name).unwrap_or(&Value::Null)))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("({})", values.join(", ")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if tuples.len() == 1 {
+            sql.push_str(&format!("{} {};\n", prefix, tuples[0]));
+        } else {
+            sql.push_str(&format!("{}\n  {};\n", prefix, tuples.join(",\n  ")));
+        }
+    }
+    Ok(sql)
+}
+
+/// Writes a value as an SQL literal.
+fn literal(value: &Value) -> Result<String, Error> {
+    Ok(match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(true) => "TRUE".to_string(),
+        Value::Bool(false) => "FALSE".to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => quote_string(text)?,
+        nested => quote_string(&nested.to_string())?,
+    })
+}
+
+/// Quotes a string literal, doubling `'`; backslashes are literal, as with PostgreSQL's
+/// default `standard_conforming_strings`.
+fn quote_string(text: &str) -> Result<String, Error> {
+    if text.contains('\0') {
+        return Err(Error::msg(
+            "Strings with NUL characters cannot be written as SQL",
+        ));
+    }
+    Ok(format!("'{}'", text.replace('\'', "''")))
+}
+
+/// Double-quotes an identifier, doubling `"`.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quotes a table name, with its schema if written as `schema.table`.
+fn quote_table(table: &str) -> Result<String, Error> {
+    if table.is_empty() || table.split('.').any(str::is_empty) || table.contains('\0') {
+        return Err(Error::msg(format!("Invalid table name: '{}'", table)));
+    }
+    Ok(table
+        .split('.')
+        .map(quote_identifier)
+        .collect::<Vec<_>>()
+        .join("."))
+}
@@ -0,0 +1,872 @@
+use crate::cbor::{base64_encode, BASE64};
+use anyhow::{Error, Result};
+use serde_json::{Map, Number, Value};
+
+/// Header string at the start of every SQLite database file.
+const MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// Page size of the databases written by `json_to_sqlite`.
+const PAGE_SIZE: usize = 4096;
+
+/// B-tree page types of rowid tables.
+const PAGE_TABLE_INTERIOR: u8 = 0x05;
+const PAGE_TABLE_LEAF: u8 = 0x0D;
+
+/// `SQLITE_VERSION_NUMBER` recorded in the header of written databases.
+const SQLITE_VERSION: u32 = 3_045_000;
+
+/// Deepest b-tree read before a database is reported as corrupt.
+const MAX_DEPTH: usize = 64;
+
+/// Options for `json_to_sqlite`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqliteOptions {
+    /// The table the rows are written to.
+    pub table: String,
+}
+
+impl Default for SqliteOptions {
+    fn default() -> Self {
+        SqliteOptions {
+            table: "data".to_string(),
+        }
+    }
+}
+
+/// The declared type of a column, inferred from its non-null values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnType {
+    /// Stored as 0 and 1, and read back as `false` and `true`.
+    Boolean,
+    Integer,
+    Real,
+    Text,
+    /// Objects and arrays stored as JSON text, and parsed again when read.
+    Json,
+    /// Mixed types, each value stored with its own type.
+    Any,
+}
+
+impl ColumnType {
+    /// Returns the type of a non-null value.
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Bool(_) => ColumnType::Boolean,
+            Value::Number(number) if number.is_i64() => ColumnType::Integer,
+            Value::Number(_) => ColumnType::Real,
+            Value::String(_) => ColumnType::Text,
+            _ => ColumnType::Json,
+        }
+    }
+
+    /// Returns a type holding the values of both.
+    fn widen(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Integer | ColumnType::Real, ColumnType::Integer | ColumnType::Real) => {
+                ColumnType::Real
+            }
+            _ => ColumnType::Any,
+        }
+    }
+
+    /// Returns the type as declared in `CREATE TABLE`.
+    fn name(self) -> &'static str {
+        match self {
+            ColumnType::Boolean => " BOOLEAN",
+            ColumnType::Integer => " INTEGER",
+            ColumnType::Real => " REAL",
+            ColumnType::Text => " TEXT",
+            ColumnType::Json => " JSON",
+            ColumnType::Any => "",
+        }
+    }
+}
+
+/// A value of a record, as SQLite stores it.
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Field {
+    /// Converts a JSON value; booleans become 0 and 1, and objects and arrays JSON text.
+    fn from_json(value: &Value) -> Self {
+        match value {
+            Value::Null => Field::Null,
+            Value::Bool(flag) => Field::Integer(*flag as i64),
+            Value::Number(number) => match number.as_i64() {
+                Some(integer) => Field::Integer(integer),
+                None => Field::Real(number.as_f64().unwrap_or_default()),
+            },
+            Value::String(text) => Field::Text(text.clone()),
+            nested => Field::Text(nested.to_string()),
+        }
+    }
+}
+
+/// Writes an array of objects as a new SQLite database holding one table, e.g. to query
+/// a JSON export with SQL or hand it to tools that read SQLite.
+///
+/// Columns are listed in order of first appearance and typed from their values:
+/// `BOOLEAN` (stored as 0 and 1), `INTEGER`, `REAL`, `TEXT`, `JSON` for objects and
+/// arrays (stored as JSON text), or no type when the values are mixed, in which case each
+/// value keeps its own type. Fields missing from a row are `NULL`. Rows get rowids 1, 2,
+/// 3 and so on.
+///
+/// # Arguments
+///
+/// * `json` - The array of objects; a single object is one row.
+/// * `options` - The name of the table.
+///
+/// # Returns
+///
+/// * `Result<Vec<u8>, Error>` - The database file, or an error if an element is not an
+///   object or the table has too many columns to describe in one schema page.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(json)))]
+pub fn json_to_sqlite(json: &Value, options: &SqliteOptions) -> Result<Vec<u8>, Error> {
+    let rows: Vec<&Map<String, Value>> = match json {
+        Value::Object(row) => vec![row],
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                item.as_object().ok_or_else(|| {
+                    Error::msg(format!(
+                        "Element {} is not an object and cannot be a table row",
+                        index
+                    ))
+                })
+            })
+            .collect::<Result<_>>()?,
+        _ => {
+            return Err(Error::msg(
+                "Only an array of objects can be converted to SQLite",
+            ))
+        }
+    };
+    let mut columns: Vec<(&str, Option<ColumnType>)> = Vec::new();
+    for row in &rows {
+        for (name, value) in row.iter() {
+            let index = match columns.iter().position(|(column, _)| column == name) {
+                Some(index) => index,
+                None => {
+                    columns.push((name, None));
+                    columns.len() - 1
+                }
+            };
+            if !value.is_null() {
+                let column_type = &mut columns[index].1;
+                let of = ColumnType::of(value);
+                *column_type = Some(column_type.map_or(of, |current| current.widen(of)));
+            }
+        }
+    }
+    let definitions: Vec<String> = columns
+        .iter()
+        .map(|(name, column_type)| {
+            let declared = column_type.unwrap_or(ColumnType::Any).name();
+            format!("{}{}", quote_identifier(name), declared)
+        })
+        .collect();
+    let sql = format!(
+        "CREATE TABLE {} ({})",
+        quote_identifier(&options.table),
+        definitions.join(", ")
+    );
+
+    let mut pages = Pages::default();
+    pages.allocate();
+    let records = rows.iter().map(|row| {
+        let fields: Vec<Field> = columns
+            .iter()
+            .map(|(name, _)| Field::from_json(row.get(*name).unwrap_or(&Value::Null)))
+            .collect();
+        encode_record(&fields)
+    });
+    let root = pages.write_table(records);
+    let schema = encode_record(&[
+        Field::Text("table".to_string()),
+        Field::Text(options.table.clone()),
+        Field::Text(options.table.clone()),
+        Field::Integer(root as i64),
+        Field::Text(sql),
+    ]);
+    let cell = pages.leaf_cell(1, &schema);
+    if 100 + 8 + 2 + cell.len() > PAGE_SIZE {
+        return Err(Error::msg(
+            "Too many columns to describe the table in the schema page",
+        ));
+    }
+    pages.write_leaf(1, &[cell]);
+    Ok(pages.finish())
+}
+
+/// Reads a table of an SQLite database as an array of objects, one per row in rowid
+/// order, without an SQLite library.
+///
+/// Integers, reals and text become numbers and strings, and blobs base64 strings. Values
+/// of `BOOLEAN` columns are read as `true` and `false`, and JSON text in `JSON` columns
+/// is parsed, so databases written by `json_to_sqlite` read back as the original array.
+/// An `INTEGER PRIMARY KEY` column holds the rowid. Changes still in a `-wal` file are
+/// not seen; checkpoint the database first.
+///
+/// # Arguments
+///
+/// * `input` - The database file.
+/// * `table` - The table to read; may be omitted if the database has only one.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The rows, or an error if the file is not an SQLite
+///   database, is corrupt, or has no such table, or the table is a `WITHOUT ROWID`
+///   table.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip(input), fields(bytes = input.len())))]
+pub fn sqlite_to_json(input: &[u8], table: Option<&str>) -> Result<Value, Error> {
+    let database = Database::open(input)?;
+    let mut tables = Vec::new();
+    for (_, payload) in database.table_rows(1)? {
+        let fields = database.decode_record(&payload)?;
+        if let [Field::Text(kind), Field::Text(name), _, Field::Integer(root), Field::Text(sql), ..] =
+            fields.as_slice()
+        {
+            if kind == "table" && !name.starts_with("sqlite_") {
+                tables.push((name.clone(), *root as usize, sql.clone()));
+            }
+        }
+    }
+    let (name, root, sql) = match table {
+        Some(table) => tables
+            .iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(table))
+            .ok_or_else(|| Error::msg(format!("No table named '{}' in the database", table)))?,
+        None if tables.len() == 1 => &tables[0],
+        None => {
+            let names: Vec<&str> = tables.iter().map(|(name, _, _)| name.as_str()).collect();
+            return Err(Error::msg(format!(
+                "The database has {} tables; choose one of: {}",
+                names.len(),
+                names.join(", ")
+            )));
+        }
+    };
+    if sql.to_ascii_uppercase().contains("WITHOUT ROWID") {
+        return Err(Error::msg(format!(
+            "Table '{}' is a WITHOUT ROWID table, which is not supported",
+            name
+        )));
+    }
+    let columns = parse_columns(sql)?;
+    let mut rows = Vec::new();
+    for (rowid, payload) in database.table_rows(*root)? {
+        let mut fields = database.decode_record(&payload)?.into_iter();
+        let mut row = Map::new();
+        for column in &columns {
+            let field = if column.virtual_column {
+                Field::Null
+            } else {
+                fields.next().unwrap_or(Field::Null)
+            };
+            let field = match field {
+                Field::Null if column.rowid => Field::Integer(rowid),
+                field => field,
+            };
+            row.insert(column.name.clone(), field_to_json(field, &column.declared));
+        }
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+/// Pages of a database being written; page `n` is `pages[n - 1]`.
+#[derive(Default)]
+struct Pages {
+    pages: Vec<Vec<u8>>,
+}
+
+impl Pages {
+    /// Adds an empty page and returns its number.
+    fn allocate(&mut self) -> usize {
+        self.pages.push(vec![0; PAGE_SIZE]);
+        self.pages.len()
+    }
+
+    /// Writes the b-tree of a table and returns its root page.
+    fn write_table(&mut self, records: impl Iterator<Item = Vec<u8>>) -> usize {
+        // Leaves, each with the rowid of its last cell.
+        let mut children: Vec<(usize, i64)> = Vec::new();
+        let mut cells: Vec<Vec<u8>> = Vec::new();
+        let mut used = 8;
+        let mut rowid = 0;
+        for record in records {
+            rowid += 1;
+            let cell = self.leaf_cell(rowid, &record);
+            if used + 2 + cell.len() > PAGE_SIZE {
+                let page = self.allocate();
+                self.write_leaf(page, &cells);
+                children.push((page, rowid - 1));
+                cells.clear();
+                used = 8;
+            }
+            used += 2 + cell.len();
+            cells.push(cell);
+        }
+        if !cells.is_empty() || children.is_empty() {
+            let page = self.allocate();
+            self.write_leaf(page, &cells);
+            children.push((page, rowid));
+        }
+        // Interior cells take at most 4 + 9 bytes and a 2-byte pointer; the last child
+        // of each page is its right-most pointer.
+        let fanout = (PAGE_SIZE - 12) / 15 + 1;
+        while children.len() > 1 {
+            children = children
+                .chunks(fanout)
+                .map(|group| {
+                    let page = self.allocate();
+                    self.write_interior(page, group);
+                    (page, group[group.len() - 1].1)
+                })
+                .collect();
+        }
+        children[0].0
+    }
+
+    /// Builds a table leaf cell, moving the end of a large payload to overflow pages.
+    fn leaf_cell(&mut self, rowid: i64, payload: &[u8]) -> Vec<u8> {
+        let mut cell = Vec::new();
+        put_varint(&mut cell, payload.len() as u64);
+        put_varint(&mut cell, rowid as u64);
+        let local = local_payload(payload.len(), PAGE_SIZE);
+        cell.extend_from_slice(&payload[..local]);
+        if local < payload.len() {
+            let chunks: Vec<&[u8]> = payload[local..].chunks(PAGE_SIZE - 4).collect();
+            let first = self.pages.len() + 1;
+            for (index, chunk) in chunks.iter().enumerate() {
+                let page = self.allocate();
+                let next = if index + 1 < chunks.len() {
+                    page + 1
+                } else {
+                    0
+                };
+                let bytes = &mut self.pages[page - 1];
+                bytes[..4].copy_from_slice(&(next as u32).to_be_bytes());
+                bytes[4..4 + chunk.len()].copy_from_slice(chunk);
+            }
+            cell.extend_from_slice(&(first as u32).to_be_bytes());
+        }
+        cell
+    }
+
+    /// Writes a table leaf page holding cells in rowid order.
+    fn write_leaf(&mut self, page: usize, cells: &[Vec<u8>]) {
+        self.write_page(page, PAGE_TABLE_LEAF, cells, None);
+    }
+
+    /// Writes a table interior page over children with their largest rowids.
+    fn write_interior(&mut self, page: usize, children: &[(usize, i64)]) {
+        let (last, rest) = children
+            .split_last()
+            .expect("an interior page has children");
+        let cells: Vec<Vec<u8>> = rest
+            .iter()
+            .map(|(child, rowid)| {
+                let mut cell = (*child as u32).to_be_bytes().to_vec();
+                put_varint(&mut cell, *rowid as u64);
+                cell
+            })
+            .collect();
+        self.write_page(page, PAGE_TABLE_INTERIOR, &cells, Some(last.0));
+    }
+
+    /// Writes a b-tree page: the header, the cell pointers, and the cells packed at the
+    /// end of the page.
+    fn write_page(&mut self, page: usize, kind: u8, cells: &[Vec<u8>], right: Option<usize>) {
+        let start = if page == 1 { 100 } else { 0 };
+        let bytes = &mut self.pages[page - 1];
+        let mut content = PAGE_SIZE;
+        let header_len = if right.is_some() { 12 } else { 8 };
+        for (index, cell) in cells.iter().enumerate() {
+            content -= cell.len();
+            bytes[content..content + cell.len()].copy_from_slice(cell);
+            let pointer = start + header_len + 2 * index;
+            bytes[pointer..pointer + 2].copy_from_slice(&(content as u16).to_be_bytes());
+        }
+        bytes[start] = kind;
+        bytes[start + 3..start + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+        bytes[start + 5..start + 7].copy_from_slice(&(content as u16).to_be_bytes());
+        if let Some(right) = right {
+            bytes[start + 8..start + 12].copy_from_slice(&(right as u32).to_be_bytes());
+        }
+    }
+
+    /// Fills in the database header and returns the file.
+    fn finish(mut self) -> Vec<u8> {
+        let page_count = self.pages.len() as u32;
+        let header = &mut self.pages[0];
+        header[..16].copy_from_slice(MAGIC);
+        header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+        // File format versions 1 (rollback journal), no reserved bytes, and the fixed
+        // payload fractions.
+        header[18..24].copy_from_slice(&[1, 1, 0, 64, 32, 32]);
+        header[24..28].copy_from_slice(&1u32.to_be_bytes());
+        header[28..32].copy_from_slice(&page_count.to_be_bytes());
+        header[40..44].copy_from_slice(&1u32.to_be_bytes());
+        header[44..48].copy_from_slice(&4u32.to_be_bytes());
+        header[56..60].copy_from_slice(&1u32.to_be_bytes());
+        header[92..96].copy_from_slice(&1u32.to_be_bytes());
+        header[96..100].copy_from_slice(&SQLITE_VERSION.to_be_bytes());
+        self.pages.concat()
+    }
+}
+
+/// A database being read.
+struct Database<'a> {
+    input: &'a [u8],
+    page_size: usize,
+    usable: usize,
+    /// 1 for UTF-8, 2 for UTF-16le, 3 for UTF-16be.
+    encoding: u32,
+}
+
+impl<'a> Database<'a> {
+    /// Checks the header of a database file.
+    fn open(input: &'a [u8]) -> Result<Self, Error> {
+        if input.len() < 100 || &input[..16] != MAGIC {
+            return Err(Error::msg("Not an SQLite database"));
+        }
+        let page_size = match u16::from_be_bytes([input[16], input[17]]) {
+            1 => 65536,
+            size => size as usize,
+        };
+        if page_size < 512 || !page_size.is_power_of_two() {
+            return Err(Error::msg(format!(
+                "Invalid SQLite page size {}",
+                page_size
+            )));
+        }
+        let encoding = u32::from_be_bytes(input[56..60].try_into().unwrap());
+        Ok(Database {
+            input,
+            page_size,
+            usable: page_size - input[20] as usize,
+            encoding: encoding.max(1),
+        })
+    }
+
+    /// Returns the bytes of a page.
+    fn page(&self, page: usize) -> Result<&'a [u8], Error> {
+        let start = page.wrapping_sub(1).wrapping_mul(self.page_size);
+        self.input
+            .get(start..start + self.page_size)
+            .filter(|_| page > 0)
+            .ok_or_else(|| Error::msg(format!("SQLite page {} is past the end of the file", page)))
+    }
+
+    /// Returns the rowids and payloads of a table b-tree in rowid order.
+    fn table_rows(&self, root: usize) -> Result<Vec<(i64, Vec<u8>)>, Error> {
+        let mut rows = Vec::new();
+        self.walk(root, 0, &mut rows)?;
+        Ok(rows)
+    }
+
+    /// Collects the rows of a b-tree page and its children.
+    fn walk(&self, page: usize, depth: usize, rows: &mut Vec<(i64, Vec<u8>)>) -> Result<(), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::msg(
+                "SQLite b-tree is too deep; the database is corrupt",
+            ));
+        }
+        let bytes = self.page(page)?;
+        let start = if page == 1 { 100 } else { 0 };
+        let kind = bytes[start];
+        let count = read_u16(bytes, start + 3)? as usize;
+        let header_len = if kind == PAGE_TABLE_INTERIOR { 12 } else { 8 };
+        for index in 0..count {
+            let offset = read_u16(bytes, start + header_len + 2 * index)? as usize;
+            let mut position = offset;
+            match kind {
+                PAGE_TABLE_LEAF => {
+                    let size = read_varint(bytes, &mut position)? as usize;
+                    let rowid = read_varint(bytes, &mut position)? as i64;
+                    rows.push((rowid, self.payload(bytes, position, size)?));
+                }
+                PAGE_TABLE_INTERIOR => {
+                    let child = read_u32(bytes, offset)? as usize;
+                    self.walk(child, depth + 1, rows)?;
+                }
+                _ => {
+                    return Err(Error::msg(format!(
+                        "Unexpected SQLite page type {} in a table",
+                        kind
+                    )))
+                }
+            }
+        }
+        if kind == PAGE_TABLE_INTERIOR {
+            let right = read_u32(bytes, start + 8)? as usize;
+            self.walk(right, depth + 1, rows)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a cell payload, following its overflow pages.
+    fn payload(&self, bytes: &[u8], position: usize, size: usize) -> Result<Vec<u8>, Error> {
+        let local = local_payload(size, self.usable);
+        let corrupt = || Error::msg("SQLite cell extends past its page; the database is corrupt");
+        let mut payload = bytes
+            .get(position..position + local)
+            .ok_or_else(corrupt)?
+            .to_vec();
+        let mut next = if local < size {
+            read_u32(bytes, position + local)? as usize
+        } else {
+            0
+        };
+        while payload.len() < size {
+            if next == 0 || payload.len() > self.input.len() {
+                return Err(corrupt());
+            }
+            let page = self.page(next)?;
+            let take = (size - payload.len()).min(self.usable - 4);
+            payload.extend_from_slice(&page[4..4 + take]);
+            next = read_u32(page, 0)? as usize;
+        }
+        Ok(payload)
+    }
+
+    /// Decodes a record into its fields.
+    fn decode_record(&self, record: &[u8]) -> Result<Vec<Field>, Error> {
+        let corrupt = || Error::msg("Malformed SQLite record");
+        let mut position = 0;
+        let header_len = read_varint(record, &mut position)? as usize;
+        let mut types = Vec::new();
+        while position < header_len {
+            types.push(read_varint(record, &mut position)?);
+        }
+        let mut body = header_len;
+        let mut fields = Vec::with_capacity(types.len());
+        for serial in types {
+            let len = match serial {
+                0 | 8 | 9 | 10 | 11 => 0,
+                1..=4 => serial as usize,
+                5 => 6,
+                6 | 7 => 8,
+                _ => (serial as usize - 12) / 2,
+            };
+            let bytes = record.get(body..body + len).ok_or_else(corrupt)?;
+            body += len;
+            fields.push(match serial {
+                0 | 10 | 11 => Field::Null,
+                8 => Field::Integer(0),
+                9 => Field::Integer(1),
+                1..=6 => {
+                    // Big-endian two's complement, sign-extended from the first byte.
+                    let mut value = if bytes[0] & 0x80 != 0 { -1i64 } else { 0 };
+                    for byte in bytes {
+                        value = (value << 8) | *byte as i64;
+                    }
+                    Field::Integer(value)
+                }
+                7 => Field::Real(f64::from_be_bytes(bytes.try_into().unwrap())),
+                serial if serial % 2 == 0 => Field::Blob(bytes.to_vec()),
+                _ => Field::Text(self.text(bytes)),
+            });
+        }
+        Ok(fields)
+    }
+
+    /// Decodes text in the database encoding.
+    fn text(&self, bytes: &[u8]) -> String {
+        let units = bytes.chunks_exact(2).map(|pair| match self.encoding {
+            2 => u16::from_le_bytes([pair[0], pair[1]]),
+            _ => u16::from_be_bytes([pair[0], pair[1]]),
+        });
+        match self.encoding {
+            2 | 3 => char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+            _ => String::from_utf8_lossy(bytes).into_owned(),
+        }
+    }
+}
+
+/// A column of a table being read.
+struct Column {
+    name: String,
+    /// The declared type in upper case, e.g. `INTEGER` or `VARCHAR(20)`.
+    declared: String,
+    /// An `INTEGER PRIMARY KEY` column, which holds the rowid.
+    rowid: bool,
+    /// A generated `VIRTUAL` column, which is not stored.
+    virtual_column: bool,
+}
+
+/// Reads the columns of a `CREATE TABLE` statement.
+fn parse_columns(sql: &str) -> Result<Vec<Column>, Error> {
+    let invalid = || Error::msg(format!("Cannot read the columns of: {}", sql));
+    let body = &sql[sql.find('(').ok_or_else(invalid)? + 1..sql.rfind(')').ok_or_else(invalid)?];
+    let mut columns: Vec<Column> = Vec::new();
+    let mut primary_key = None;
+    for definition in split_top_level(body) {
+        let definition = definition.trim();
+        let (name, rest) = read_name(definition);
+        let rest = rest.trim_start();
+        let upper = rest.to_ascii_uppercase();
+        match name.to_ascii_uppercase().as_str() {
+            "PRIMARY" if upper.starts_with("KEY") => {
+                let names = &rest[rest.find('(').ok_or_else(invalid)? + 1..];
+                let names = split_top_level(&names[..names.rfind(')').ok_or_else(invalid)?]);
+                if let [only] = names.as_slice() {
+                    primary_key = Some(read_name(only.trim()).0);
+                }
+                continue;
+            }
+            "CONSTRAINT" | "UNIQUE" | "CHECK" | "FOREIGN" => continue,
+            _ => {}
+        }
+        let keywords = [
+            "CONSTRAINT",
+            "PRIMARY",
+            "NOT",
+            "NULL",
+            "UNIQUE",
+            "CHECK",
+            "DEFAULT",
+            "COLLATE",
+            "REFERENCES",
+            "GENERATED",
+            "AS",
+        ];
+        let declared: Vec<&str> = upper
+            .split_whitespace()
+            .take_while(|word| !keywords.contains(word))
+            .collect();
+        let declared = declared.join(" ");
+        let words: Vec<&str> = upper.split_whitespace().collect();
+        let primary = words.windows(2).position(|pair| pair == ["PRIMARY", "KEY"]);
+        columns.push(Column {
+            rowid: declared == "INTEGER"
+                && primary.is_some_and(|index| words.get(index + 2) != Some(&"DESC")),
+            virtual_column: words
+                .iter()
+                .any(|word| *word == "AS" || word.starts_with("AS("))
+                && !words.contains(&"STORED"),
+            declared,
+            name,
+        });
+    }
+    if let Some(key) = primary_key {
+        for column in &mut columns {
+            column.rowid |= column.name.eq_ignore_ascii_case(&key) && column.declared == "INTEGER";
+        }
+    }
+    Ok(columns)
+}
+
+/// Splits a list at commas outside parentheses and quotes.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    for (offset, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(close), c) if c == close => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '[') => quote = Some(']'),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(&text[start..offset]);
+                start = offset + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Reads a possibly quoted name at the start of a definition, returning the rest.
+fn read_name(definition: &str) -> (String, &str) {
+    let close = match definition.chars().next() {
+        Some('"') => '"',
+        Some('`') => '`',
+        Some('\'') => '\'',
+        Some('[') => ']',
+        _ => {
+            let end = definition
+                .find(|c: char| c.is_whitespace() || c == '(')
+                .unwrap_or(definition.len());
+            return (definition[..end].to_string(), &definition[end..]);
+        }
+    };
+    let mut name = String::new();
+    let mut chars = definition.char_indices().skip(1).peekable();
+    while let Some((offset, c)) = chars.next() {
+        if c == close {
+            // Quotes are escaped by doubling them, except in brackets.
+            if close != ']' && chars.peek().map(|(_, next)| *next) == Some(close) {
+                chars.next();
+            } else {
+                return (name, &definition[offset + 1..]);
+            }
+        }
+        name.push(c);
+    }
+    (name, "")
+}
+
+/// Converts a field to JSON, taking the declared type of its column into account.
+fn field_to_json(field: Field, declared: &str) -> Value {
+    match field {
+        Field::Null => Value::Null,
+        Field::Integer(value @ (0 | 1)) if declared.contains("BOOL") => Value::Bool(value == 1),
+        Field::Integer(value) => Value::from(value),
+        Field::Real(value) => Number::from_f64(value).map_or(Value::Null, Value::Number),
+        Field::Text(text) if declared == "JSON" => {
+            serde_json::from_str(&text).unwrap_or(Value::String(text))
+        }
+        Field::Text(text) => Value::String(text),
+        Field::Blob(bytes) => Value::String(base64_encode(&bytes, BASE64, true)),
+    }
+}
+
+/// Encodes fields as a record: a header of serial types, then the values.
+fn encode_record(fields: &[Field]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for field in fields {
+        let serial = match field {
+            Field::Null => 0,
+            Field::Integer(0) => 8,
+            Field::Integer(1) => 9,
+            Field::Integer(value) => {
+                let (serial, len) = match *value {
+                    -0x80..=0x7f => (1, 1),
+                    -0x8000..=0x7fff => (2, 2),
+                    -0x80_0000..=0x7f_ffff => (3, 3),
+                    -0x8000_0000..=0x7fff_ffff => (4, 4),
+                    -0x8000_0000_0000..=0x7fff_ffff_ffff => (5, 6),
+                    _ => (6, 8),
+                };
+                body.extend_from_slice(&value.to_be_bytes()[8 - len..]);
+                serial
+            }
+            Field::Real(value) => {
+                body.extend_from_slice(&value.to_be_bytes());
+                7
+            }
+            Field::Text(text) => {
+                body.extend_from_slice(text.as_bytes());
+                13 + 2 * text.len() as u64
+            }
+            Field::Blob(bytes) => {
+                body.extend_from_slice(bytes);
+                12 + 2 * bytes.len() as u64
+            }
+        };
+        put_varint(&mut types, serial);
+    }
+    // The header length counts its own varint.
+    let mut header_len = types.len() + 1;
+    while types.len() + varint_len(header_len as u64) != header_len {
+        header_len = types.len() + varint_len(header_len as u64);
+    }
+    let mut record = Vec::with_capacity(header_len + body.len());
+    put_varint(&mut record, header_len as u64);
+    record.extend_from_slice(&types);
+    record.extend_from_slice(&body);
+    record
+}
+
+/// Returns how many bytes of a payload are stored in a table leaf cell; the rest goes
+/// to overflow pages.
+fn local_payload(size: usize, usable: usize) -> usize {
+    let max_local = usable - 35;
+    if size <= max_local {
+        return size;
+    }
+    let min_local = (usable - 12) * 32 / 255 - 23;
+    let local = min_local + (size - min_local) % (usable - 4);
+    if local <= max_local {
+        local
+    } else {
+        min_local
+    }
+}
+
+/// Appends an SQLite varint: big-endian groups of 7 bits, with a full ninth byte.
+fn put_varint(out: &mut Vec<u8>, value: u64) {
+    if value >> 56 != 0 {
+        let mut bytes = [0u8; 9];
+        bytes[8] = value as u8;
+        let mut rest = value >> 8;
+        for byte in bytes[..8].iter_mut().rev() {
+            *byte = (rest as u8 & 0x7f) | 0x80;
+            rest >>= 7;
+        }
+        out.extend_from_slice(&bytes);
+        return;
+    }
+    let len = varint_len(value);
+    for index in (0..len).rev() {
+        let group = (value >> (7 * index)) as u8 & 0x7f;
+        out.push(if index > 0 { group | 0x80 } else { group });
+    }
+}
+
+/// Returns the length of a varint.
+fn varint_len(value: u64) -> usize {
+    if value >> 56 != 0 {
+        9
+    } else {
+        (64 - value.leading_zeros() as usize).div_ceil(7).max(1)
+    }
+}
+
+/// Reads an SQLite varint.
+fn read_varint(bytes: &[u8], position: &mut usize) -> Result<u64, Error> {
+    let mut value = 0u64;
+    for index in 0..9 {
+        let byte = *bytes
+            .get(*position)
+            .ok_or_else(|| Error::msg("Truncated SQLite varint"))?;
+        *position += 1;
+        if index == 8 {
+            return Ok((value << 8) | byte as u64);
+        }
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(value)
+}
+
+/// Reads a big-endian `u16`.
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, Error> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .ok_or_else(|| Error::msg("Truncated SQLite page"))
+}
+
+/// Reads a big-endian `u32`.
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|quad| u32::from_be_bytes([quad[0], quad[1], quad[2], quad[3]]))
+        .ok_or_else(|| Error::msg("Truncated SQLite page"))
+}
+
+/// Double-quotes an identifier, doubling `"`.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
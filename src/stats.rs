@@ -0,0 +1,1166 @@
+use crate::cancel;
+use crate::parser::get_by_path;
+use anyhow::{Context, Error, Result};
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+/// Number of distinct values counted exactly before switching to HyperLogLog.
+const EXACT_DISTINCT_LIMIT: usize = 10_000;
+
+/// Number of sample values kept per path.
+const SAMPLE_LIMIT: usize = 5;
+
+/// Hashes a value to 64 bits for the probabilistic sketches.
+fn hash64<T: Hash + ?Sized>(item: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// HyperLogLog sketch estimating the number of distinct items in a stream.
+///
+/// Uses `2^precision` one-byte registers; the standard error is roughly
+/// `1.04 / sqrt(2^precision)`, i.e. about 0.8% for the default precision of 14.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog::new(14)
+    }
+}
+
+impl HyperLogLog {
+    /// Creates an empty sketch. `precision` is clamped to the range 4..=16.
+    pub fn new(precision: u8) -> Self {
+        let precision = precision.clamp(4, 16);
+        HyperLogLog {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    /// Adds an item to the sketch.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        self.insert_hash(hash64(item));
+    }
+
+    /// Adds an already hashed item to the sketch.
+    fn insert_hash(&mut self, hash: u64) {
+        let p = self.precision as u32;
+        let index = (hash >> (64 - p)) as usize;
+        let rest = (hash << p) | (1 << (p - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Merges another sketch of the same precision into this one.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        if other.precision != self.precision {
+            return;
+        }
+        for (mine, theirs) in self.registers.iter_mut().zip(&other.registers) {
+            *mine = (*mine).max(*theirs);
+        }
+    }
+
+    /// Returns the estimated number of distinct items.
+    pub fn count(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// Distinct counter that is exact for small cardinalities and falls back to
+/// HyperLogLog once more than `EXACT_DISTINCT_LIMIT` values have been seen.
+#[derive(Debug, Clone)]
+enum DistinctCounter {
+    Exact(HashSet<u64>),
+    Approximate(HyperLogLog),
+}
+
+impl DistinctCounter {
+    fn insert(&mut self, hash: u64) {
+        match self {
+            DistinctCounter::Exact(set) => {
+                set.insert(hash);
+                if set.len() > EXACT_DISTINCT_LIMIT {
+                    let mut sketch = HyperLogLog::default();
+                    for &h in set.iter() {
+                        sketch.insert_hash(h);
+                    }
+                    *self = DistinctCounter::Approximate(sketch);
+                }
+            }
+            DistinctCounter::Approximate(sketch) => sketch.insert_hash(hash),
+        }
+    }
+
+    fn count(&self) -> (u64, bool) {
+        match self {
+            DistinctCounter::Exact(set) => (set.len() as u64, false),
+            DistinctCounter::Approximate(sketch) => (sketch.count(), true),
+        }
+    }
+}
+
+/// Statistics collected for a single path of a data dictionary.
+#[derive(Debug, Clone)]
+struct PathStats {
+    types: BTreeMap<&'static str, u64>,
+    occurrences: u64,
+    nulls: u64,
+    distinct: DistinctCounter,
+    min: Option<Value>,
+    max: Option<Value>,
+    samples: Vec<Value>,
+}
+
+impl PathStats {
+    fn new() -> Self {
+        PathStats {
+            types: BTreeMap::new(),
+            occurrences: 0,
+            nulls: 0,
+            distinct: DistinctCounter::Exact(HashSet::new()),
+            min: None,
+            max: None,
+            samples: Vec::new(),
+        }
+    }
+
+    fn observe(&mut self, value: &Value) {
+        *self.types.entry(type_name(value)).or_insert(0) += 1;
+        self.occurrences += 1;
+        match value {
+            Value::Null => self.nulls += 1,
+            Value::Object(_) | Value::Array(_) => {}
+            scalar => {
+                self.distinct.insert(hash64(&scalar.to_string()));
+                if self
+                    .min
+                    .as_ref()
+                    .is_none_or(|min| compare_values(scalar, min) == Ordering::Less)
+                {
+                    self.min = Some(scalar.clone());
+                }
+                if self
+                    .max
+                    .as_ref()
+                    .is_none_or(|max| compare_values(scalar, max) == Ordering::Greater)
+                {
+                    self.max = Some(scalar.clone());
+                }
+                if self.samples.len() < SAMPLE_LIMIT && !self.samples.contains(scalar) {
+                    self.samples.push(scalar.clone());
+                }
+            }
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let (distinct, approximate) = self.distinct.count();
+        let null_rate = if self.occurrences == 0 {
+            0.0
+        } else {
+            self.nulls as f64 / self.occurrences as f64
+        };
+        json!({
+            "types": self.types,
+            "occurrences": self.occurrences,
+            "null_rate": null_rate,
+            "distinct": distinct,
+            "distinct_approximate": approximate,
+            "min": self.min,
+            "max": self.max,
+            "samples": self.samples,
+        })
+    }
+}
+
+/// Returns the JSON type name of a value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Total order over JSON values used for min/max tracking and sorting.
+///
+/// Values of different types are ordered null < boolean < number < string <
+/// array < object; numbers compare numerically, strings lexicographically, and
+/// arrays and objects by their serialized form.
+pub fn compare_values(a: &Value, b: &Value) -> Ordering {
+    let rank = |v: &Value| match v {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    };
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x
+            .as_f64()
+            .partial_cmp(&y.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        _ if rank(a) == rank(b) => a.to_string().cmp(&b.to_string()),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Visits every nested value of a record with its path, folding array
+/// elements into a single `[]` segment. The record itself is not visited.
+fn walk_folded_paths(value: &Value, path: &str, visit: &mut dyn FnMut(&str, &Value)) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let child_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                visit(&child_path, child);
+                walk_folded_paths(child, &child_path, visit);
+            }
+        }
+        Value::Array(items) => {
+            let child_path = format!("{}[]", path);
+            for item in items {
+                visit(&child_path, item);
+                walk_folded_paths(item, &child_path, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Accumulates a data dictionary over a stream of records.
+///
+/// Array elements are folded into a single `[]` path segment, so
+/// `orders[].price` describes the price of every order in every record.
+#[derive(Debug, Clone, Default)]
+pub struct DataDictionary {
+    records: u64,
+    paths: BTreeMap<String, PathStats>,
+}
+
+impl DataDictionary {
+    /// Creates an empty dictionary.
+    pub fn new() -> Self {
+        DataDictionary::default()
+    }
+
+    /// Adds a record to the dictionary.
+    pub fn observe(&mut self, record: &Value) {
+        self.records += 1;
+        let paths = &mut self.paths;
+        walk_folded_paths(record, "", &mut |path, value| {
+            paths
+                .entry(path.to_string())
+                .or_insert_with(PathStats::new)
+                .observe(value)
+        });
+    }
+
+    /// Produces the dictionary report as JSON.
+    pub fn to_json(&self) -> Value {
+        let paths: Map<String, Value> = self
+            .paths
+            .iter()
+            .map(|(path, stats)| (path.clone(), stats.to_json()))
+            .collect();
+        json!({ "records": self.records, "paths": paths })
+    }
+}
+
+/// Builds a data dictionary report over a set of records.
+///
+/// For every path the report lists observed types, null rate, distinct count
+/// (exact for small cardinalities, HyperLogLog-approximated above that),
+/// minimum and maximum scalar values, and a few sample values.
+///
+/// # Arguments
+///
+/// * `records` - The records of the dataset.
+///
+/// # Returns
+///
+/// * `Value` - The dictionary report. If a `CancellationToken` stops the call, it covers
+///   the records observed until then and has `"cancelled": true`.
+pub fn data_dictionary<'a, I: IntoIterator<Item = &'a Value>>(records: I) -> Value {
+    let mut dictionary = DataDictionary::new();
+    for record in records {
+        if cancel::is_cancelled() {
+            let mut report = dictionary.to_json();
+            report["cancelled"] = Value::Bool(true);
+            return report;
+        }
+        dictionary.observe(record);
+    }
+    dictionary.to_json()
+}
+
+/// Count-Min sketch estimating item frequencies in a stream.
+///
+/// Estimates never undercount; with `width = ceil(e / epsilon)` and
+/// `depth = ceil(ln(1 / delta))` they overcount by at most `epsilon * N`
+/// with probability `1 - delta`, where `N` is the number of insertions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Vec<u64>,
+}
+
+impl Default for CountMinSketch {
+    fn default() -> Self {
+        CountMinSketch::new(2048, 5)
+    }
+}
+
+impl CountMinSketch {
+    /// Creates an empty sketch with `depth` rows of `width` counters.
+    pub fn new(width: usize, depth: usize) -> Self {
+        let width = width.max(1);
+        let depth = depth.max(1);
+        CountMinSketch {
+            width,
+            depth,
+            counters: vec![0; width * depth],
+        }
+    }
+
+    /// Creates a sketch sized for the given error bound and failure probability.
+    pub fn with_error(epsilon: f64, delta: f64) -> Self {
+        let width = (std::f64::consts::E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil() as usize;
+        CountMinSketch::new(width, depth)
+    }
+
+    /// Adds an item and returns its updated frequency estimate.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) -> u64 {
+        let mut estimate = u64::MAX;
+        for row in 0..self.depth {
+            let index = self.index(row, item);
+            self.counters[index] += 1;
+            estimate = estimate.min(self.counters[index]);
+        }
+        estimate
+    }
+
+    /// Returns the frequency estimate of an item.
+    pub fn estimate<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        (0..self.depth)
+            .map(|row| self.counters[self.index(row, item)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Computes the counter index of an item in the given row.
+    fn index<T: Hash + ?Sized>(&self, row: usize, item: &T) -> usize {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        item.hash(&mut hasher);
+        row * self.width + (hasher.finish() % self.width as u64) as usize
+    }
+}
+
+/// Magic bytes at the start of a saved `BloomFilter`.
+const BLOOM_MAGIC: &[u8; 8] = b"JPBLOOM1";
+
+/// Hashes bytes with 64-bit FNV-1a starting from `seed`, which is stable across builds.
+pub(crate) fn fnv1a(bytes: &[u8], seed: u64) -> u64 {
+    bytes.iter().fold(seed, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+/// Writes a file through a temporary sibling, so an interrupted write leaves the
+/// previous contents in place.
+pub(crate) fn write_replacing(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let partial = path.with_extension("partial");
+    fs::write(&partial, bytes)?;
+    fs::rename(&partial, path)
+}
+
+/// Bloom filter remembering which items of a stream have been seen: an item that was
+/// inserted is always reported as seen, and a new item is wrongly reported as seen
+/// with roughly the false positive rate the filter was sized for.
+///
+/// Items are hashed with FNV-1a rather than `DefaultHasher`, so a filter written with
+/// `save` answers the same when loaded by a later run or another build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    bit_count: u64,
+    hashes: u32,
+    capacity: u64,
+    items: u64,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized for `capacity` distinct items at the given false
+    /// positive rate, e.g. one million items at `0.001` take about 1.8 MB.
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let items = capacity.max(1) as f64;
+        let rate = false_positive_rate.clamp(1e-12, 0.5);
+        let bit_count = (-(items * rate.ln()) / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hashes = (bit_count as f64 / items * ln2).round().clamp(1.0, 32.0) as u32;
+        BloomFilter {
+            bits: vec![0; bit_count.div_ceil(64) as usize],
+            bit_count,
+            hashes,
+            capacity: capacity.max(1) as u64,
+            items: 0,
+        }
+    }
+
+    /// Adds an item and returns `true` if it was (probably) already present.
+    pub fn insert(&mut self, item: &[u8]) -> bool {
+        let mut present = true;
+        let positions: Vec<u64> = self.positions(item).collect();
+        for bit in positions {
+            let (word, mask) = ((bit / 64) as usize, 1u64 << (bit % 64));
+            present &= self.bits[word] & mask != 0;
+            self.bits[word] |= mask;
+        }
+        if !present {
+            self.items += 1;
+        }
+        present
+    }
+
+    /// Returns `true` if the item was (probably) inserted before.
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.positions(item)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1u64 << (bit % 64)) != 0)
+    }
+
+    /// Returns the number of distinct items inserted.
+    pub fn len(&self) -> u64 {
+        self.items
+    }
+
+    /// Returns `true` if nothing has been inserted.
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    /// Returns the number of items the filter was sized for; beyond it the false
+    /// positive rate grows.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Exports the filter as JSON, with the bits as a hex string, so it can be inspected
+    /// or moved to another machine and read back with `from_json`.
+    pub fn to_json(&self) -> Value {
+        let bits: String = self
+            .bits
+            .iter()
+            .map(|word| format!("{:016x}", word))
+            .collect();
+        json!({
+            "hashes": self.hashes,
+            "bit_count": self.bit_count,
+            "capacity": self.capacity,
+            "items": self.items,
+            "bits": bits,
+        })
+    }
+
+    /// Imports a filter exported with `to_json`.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The exported filter.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<BloomFilter, Error>` - The filter, or an error if `json` is not an exported filter.
+    pub fn from_json(json: &Value) -> Result<BloomFilter, Error> {
+        let invalid = || Error::msg("Invalid bloom filter state");
+        let number = |name: &str| json.get(name).and_then(Value::as_u64).ok_or_else(invalid);
+        let (bit_count, capacity, items) =
+            (number("bit_count")?, number("capacity")?, number("items")?);
+        let hashes = u32::try_from(number("hashes")?).map_err(|_| invalid())?;
+        let hex = json
+            .get("bits")
+            .and_then(Value::as_str)
+            .ok_or_else(invalid)?;
+        if hashes == 0 || bit_count == 0 || hex.len() as u64 != bit_count.div_ceil(64) * 16 {
+            return Err(invalid());
+        }
+        let bits = (0..hex.len())
+            .step_by(16)
+            .map(|at| {
+                hex.get(at..at + 16)
+                    .and_then(|word| u64::from_str_radix(word, 16).ok())
+            })
+            .collect::<Option<Vec<u64>>>()
+            .ok_or_else(invalid)?;
+        Ok(BloomFilter {
+            bits,
+            bit_count,
+            hashes,
+            capacity,
+            items,
+        })
+    }
+
+    /// Writes the filter to a file, replacing it only once the new contents are complete.
+    /// Files ending in `.json` get the `to_json` export, others a compact binary form.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The state file.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - An error if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            return write_replacing(path, self.to_json().to_string().as_bytes())
+                .with_context(|| format!("Failed to write bloom filter {}", path.display()));
+        }
+        let mut bytes = Vec::with_capacity(36 + self.bits.len() * 8);
+        bytes.extend_from_slice(BLOOM_MAGIC);
+        bytes.extend_from_slice(&self.hashes.to_le_bytes());
+        for number in [self.bit_count, self.capacity, self.items] {
+            bytes.extend_from_slice(&number.to_le_bytes());
+        }
+        for word in &self.bits {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        write_replacing(path, &bytes)
+            .with_context(|| format!("Failed to write bloom filter {}", path.display()))
+    }
+
+    /// Reads a filter written by `save`, in either form.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The state file.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<BloomFilter, Error>` - The filter, or an error if the file cannot be read
+    ///   or is not a saved bloom filter.
+    pub fn load(path: &Path) -> Result<BloomFilter, Error> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("Failed to read bloom filter {}", path.display()))?;
+        let invalid = || Error::msg(format!("{} is not a saved bloom filter", path.display()));
+        if bytes.first() == Some(&b'{') {
+            let json: Value = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+            return BloomFilter::from_json(&json).map_err(|_| invalid());
+        }
+        let rest = bytes
+            .strip_prefix(BLOOM_MAGIC.as_slice())
+            .ok_or_else(invalid)?;
+        if rest.len() < 28 {
+            return Err(invalid());
+        }
+        let number = |at: usize| u64::from_le_bytes(rest[at..at + 8].try_into().unwrap());
+        let hashes = u32::from_le_bytes(rest[..4].try_into().unwrap());
+        let (bit_count, capacity, items) = (number(4), number(12), number(20));
+        let words = &rest[28..];
+        if hashes == 0 || bit_count == 0 || words.len() as u64 != bit_count.div_ceil(64) * 8 {
+            return Err(invalid());
+        }
+        Ok(BloomFilter {
+            bits: words
+                .chunks_exact(8)
+                .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+                .collect(),
+            bit_count,
+            hashes,
+            capacity,
+            items,
+        })
+    }
+
+    /// Computes the bit positions of an item by double hashing.
+    fn positions(&self, item: &[u8]) -> impl Iterator<Item = u64> + '_ {
+        let first = fnv1a(item, 0xcbf2_9ce4_8422_2325);
+        let second = fnv1a(item, 0x8422_2325_cbf2_9ce4) | 1;
+        (0..u64::from(self.hashes))
+            .map(move |i| first.wrapping_add(i.wrapping_mul(second)) % self.bit_count)
+    }
+}
+
+/// Tracks the `k` most frequent values of a stream using a Count-Min sketch.
+#[derive(Debug, Clone)]
+pub struct HeavyHitters {
+    k: usize,
+    sketch: CountMinSketch,
+    top: HashMap<String, (Value, u64)>,
+}
+
+impl HeavyHitters {
+    /// Creates a tracker for the `k` most frequent values.
+    pub fn new(k: usize) -> Self {
+        HeavyHitters {
+            k,
+            sketch: CountMinSketch::default(),
+            top: HashMap::new(),
+        }
+    }
+
+    /// Adds a value to the stream.
+    pub fn insert(&mut self, value: &Value) {
+        let key = value.to_string();
+        let estimate = self.sketch.insert(&key);
+        if let Some(entry) = self.top.get_mut(&key) {
+            entry.1 = estimate;
+        } else if self.top.len() < self.k {
+            self.top.insert(key, (value.clone(), estimate));
+        } else if let Some((min_key, min_count)) = self
+            .top
+            .iter()
+            .map(|(k, (_, count))| (k.clone(), *count))
+            .min_by_key(|(_, count)| *count)
+        {
+            if estimate > min_count {
+                self.top.remove(&min_key);
+                self.top.insert(key, (value.clone(), estimate));
+            }
+        }
+    }
+
+    /// Returns the tracked values with their estimated counts, most frequent first.
+    pub fn top(&self) -> Vec<(Value, u64)> {
+        let mut top: Vec<(Value, u64)> = self.top.values().cloned().collect();
+        top.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then_with(|| a.0.to_string().cmp(&b.0.to_string()))
+        });
+        top
+    }
+}
+
+/// A cluster of nearby values in a `TDigest`.
+#[derive(Debug, Clone, Copy)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// Streaming quantile estimator (Dunning's merging t-digest).
+///
+/// Values are clustered into centroids that are small near the tails and large
+/// near the median, so extreme percentiles such as p99 stay accurate while memory
+/// is bounded by roughly `compression` centroids.
+#[derive(Debug, Clone)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        TDigest::new(100.0)
+    }
+}
+
+impl TDigest {
+    /// Creates a digest; higher `compression` keeps more centroids and is more accurate.
+    pub fn new(compression: f64) -> Self {
+        TDigest {
+            compression: compression.max(10.0),
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Adds a value; non-finite values are ignored.
+    pub fn insert(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(Centroid {
+            mean: value,
+            weight: 1.0,
+        });
+        if self.buffer.len() >= 5 * self.compression as usize {
+            self.centroids = self.merged();
+            self.buffer.clear();
+        }
+    }
+
+    /// Combines another digest into this one.
+    pub fn merge(&mut self, other: &TDigest) {
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.buffer
+            .extend(other.centroids.iter().chain(&other.buffer));
+        self.centroids = self.merged();
+        self.buffer.clear();
+    }
+
+    /// Returns the number of values added.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Exports the digest as JSON, e.g. to checkpoint a running aggregation.
+    pub fn to_json(&self) -> Value {
+        let centroids: Vec<Value> = self
+            .merged()
+            .iter()
+            .map(|centroid| json!([centroid.mean, centroid.weight]))
+            .collect();
+        json!({
+            "compression": self.compression,
+            "count": self.count,
+            "min": (self.count > 0).then_some(self.min),
+            "max": (self.count > 0).then_some(self.max),
+            "centroids": centroids,
+        })
+    }
+
+    /// Imports a digest exported with `to_json`.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The exported digest.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<TDigest, Error>` - The digest, or an error if `json` is not an exported digest.
+    pub fn from_json(json: &Value) -> Result<TDigest, Error> {
+        let invalid = || Error::msg("Invalid t-digest state");
+        let compression = json["compression"].as_f64().ok_or_else(invalid)?;
+        let mut digest = TDigest::new(compression);
+        digest.count = json["count"].as_u64().ok_or_else(invalid)?;
+        digest.min = json["min"].as_f64().unwrap_or(f64::INFINITY);
+        digest.max = json["max"].as_f64().unwrap_or(f64::NEG_INFINITY);
+        digest.centroids = json["centroids"]
+            .as_array()
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|centroid| {
+                Some(Centroid {
+                    mean: centroid.get(0)?.as_f64()?,
+                    weight: centroid.get(1)?.as_f64()?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        Ok(digest)
+    }
+
+    /// Estimates the value at quantile `q` (between 0 and 1), or `None` if the digest is empty.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let centroids = self.merged();
+        let total: f64 = centroids.iter().map(|c| c.weight).sum();
+        let target = q.clamp(0.0, 1.0) * total;
+        // Each centroid is centered on the middle of the ranks it covers.
+        let mut previous = (0.0, self.min);
+        let mut cumulative = 0.0;
+        for centroid in &centroids {
+            let center = cumulative + centroid.weight / 2.0;
+            if target < center {
+                let (rank, value) = previous;
+                let fraction = (target - rank) / (center - rank);
+                return Some(value + fraction * (centroid.mean - value));
+            }
+            previous = (center, centroid.mean);
+            cumulative += centroid.weight;
+        }
+        let (rank, value) = previous;
+        let fraction = ((target - rank) / (total - rank)).min(1.0);
+        Some(value + fraction * (self.max - value))
+    }
+
+    /// Returns the centroids and buffered values merged under the size limit.
+    fn merged(&self) -> Vec<Centroid> {
+        let mut all: Vec<Centroid> = self.centroids.iter().chain(&self.buffer).copied().collect();
+        all.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+        let total: f64 = all.iter().map(|c| c.weight).sum();
+        let mut merged: Vec<Centroid> = Vec::new();
+        let mut cumulative = 0.0;
+        for centroid in all {
+            if let Some(last) = merged.last_mut() {
+                let proposed = last.weight + centroid.weight;
+                let start = (cumulative - last.weight) / total;
+                let end = (cumulative + centroid.weight) / total;
+                // A centroid may hold at most 4nq(1-q)/compression values.
+                let limit =
+                    4.0 * total * (start * (1.0 - start)).min(end * (1.0 - end)) / self.compression;
+                if proposed <= limit.max(1.0) {
+                    last.mean += (centroid.mean - last.mean) * centroid.weight / proposed;
+                    last.weight = proposed;
+                    cumulative += centroid.weight;
+                    continue;
+                }
+            }
+            cumulative += centroid.weight;
+            merged.push(centroid);
+        }
+        merged
+    }
+}
+
+/// Sketch-based analytics for a set of paths over a stream of records.
+///
+/// Each configured path gets a HyperLogLog distinct counter and a
+/// Count-Min based top-k tracker, so memory stays constant regardless of
+/// the number of records.
+#[derive(Debug, Clone)]
+pub struct StreamSketches {
+    records: u64,
+    paths: Vec<(String, HyperLogLog, HeavyHitters)>,
+}
+
+impl StreamSketches {
+    /// Creates sketches for the given paths (in `get_by_path` syntax), tracking `top_k` heavy hitters each.
+    pub fn new(paths: &[String], top_k: usize) -> Self {
+        StreamSketches {
+            records: 0,
+            paths: paths
+                .iter()
+                .map(|path| {
+                    (
+                        path.clone(),
+                        HyperLogLog::default(),
+                        HeavyHitters::new(top_k),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds a record to every path's sketches. Missing paths are skipped.
+    pub fn observe(&mut self, record: &Value) {
+        self.records += 1;
+        for (path, distinct, heavy_hitters) in &mut self.paths {
+            if let Some(value) = get_by_path(record, path) {
+                distinct.insert(&value.to_string());
+                heavy_hitters.insert(&value);
+            }
+        }
+    }
+
+    /// Produces the sketch report as JSON.
+    pub fn to_json(&self) -> Value {
+        let paths: Map<String, Value> = self
+            .paths
+            .iter()
+            .map(|(path, distinct, heavy_hitters)| {
+                let top: Vec<Value> = heavy_hitters
+                    .top()
+                    .into_iter()
+                    .map(|(value, count)| json!({ "value": value, "count": count }))
+                    .collect();
+                (
+                    path.clone(),
+                    json!({ "distinct": distinct.count(), "top": top }),
+                )
+            })
+            .collect();
+        json!({ "records": self.records, "paths": paths })
+    }
+}
+
+/// Thresholds controlling drift detection in `IncrementalStats`.
+#[derive(Debug, Clone)]
+pub struct DriftOptions {
+    /// Number of values forming the baseline and the sliding recent window.
+    pub window: usize,
+    /// Total variation distance above which a categorical path is flagged.
+    pub categorical_threshold: f64,
+    /// Shift of the recent mean, in baseline standard deviations, above which a numeric path is flagged.
+    pub numeric_threshold: f64,
+}
+
+impl Default for DriftOptions {
+    fn default() -> Self {
+        DriftOptions {
+            window: 1000,
+            categorical_threshold: 0.3,
+            numeric_threshold: 3.0,
+        }
+    }
+}
+
+/// Maximum number of distinct values kept in a path's frequency table.
+const FREQUENCY_LIMIT: usize = 1000;
+
+/// Running mean and variance using Welford's algorithm.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RunningMoments {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningMoments {
+    pub(crate) fn push(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub(crate) fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub(crate) fn stddev(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.count - 1) as f64).sqrt()
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        json!({ "count": self.count, "mean": self.mean, "m2": self.m2 })
+    }
+
+    pub(crate) fn from_json(json: &Value) -> Option<RunningMoments> {
+        Some(RunningMoments {
+            count: json["count"].as_u64()?,
+            mean: json["mean"].as_f64()?,
+            m2: json["m2"].as_f64()?,
+        })
+    }
+}
+
+/// Incrementally maintained metrics for a single path.
+#[derive(Debug, Clone, Default)]
+struct PathMetrics {
+    count: u64,
+    nulls: u64,
+    moments: RunningMoments,
+    min: Option<f64>,
+    max: Option<f64>,
+    frequencies: HashMap<String, u64>,
+    baseline: Vec<String>,
+    baseline_moments: RunningMoments,
+    recent: VecDeque<String>,
+    recent_numbers: VecDeque<f64>,
+}
+
+impl PathMetrics {
+    fn observe(&mut self, value: &Value, window: usize) {
+        self.count += 1;
+        if value.is_null() {
+            self.nulls += 1;
+            return;
+        }
+        let key = value.to_string();
+        if self.frequencies.len() < FREQUENCY_LIMIT || self.frequencies.contains_key(&key) {
+            *self.frequencies.entry(key.clone()).or_insert(0) += 1;
+        }
+        if let Some(x) = value.as_f64() {
+            self.moments.push(x);
+            self.min = Some(self.min.map_or(x, |m| m.min(x)));
+            self.max = Some(self.max.map_or(x, |m| m.max(x)));
+        }
+
+        if self.baseline.len() < window {
+            self.baseline.push(key);
+            if let Some(x) = value.as_f64() {
+                self.baseline_moments.push(x);
+            }
+            return;
+        }
+        self.recent.push_back(key);
+        if self.recent.len() > window {
+            self.recent.pop_front();
+        }
+        if let Some(x) = value.as_f64() {
+            self.recent_numbers.push_back(x);
+            if self.recent_numbers.len() > window {
+                self.recent_numbers.pop_front();
+            }
+        }
+    }
+
+    /// Computes the drift score of the recent window against the frozen baseline.
+    /// Returns `None` until the recent window is full.
+    fn drift(&self, options: &DriftOptions) -> Option<(f64, bool)> {
+        if self.recent.len() < options.window {
+            return None;
+        }
+        if self.baseline_moments.count as usize == self.baseline.len() {
+            let recent_mean =
+                self.recent_numbers.iter().sum::<f64>() / self.recent_numbers.len().max(1) as f64;
+            let stddev = self.baseline_moments.stddev();
+            let shift = (recent_mean - self.baseline_moments.mean).abs();
+            let score = if stddev > 0.0 {
+                shift / stddev
+            } else if shift > 0.0 {
+                f64::INFINITY
+            } else {
+                0.0
+            };
+            return Some((score, score > options.numeric_threshold));
+        }
+
+        let baseline = frequency_distribution(self.baseline.iter());
+        let recent = frequency_distribution(self.recent.iter());
+        let keys: HashSet<&str> = baseline.keys().chain(recent.keys()).copied().collect();
+        let score = keys
+            .iter()
+            .map(|key| (baseline.get(key).unwrap_or(&0.0) - recent.get(key).unwrap_or(&0.0)).abs())
+            .sum::<f64>()
+            / 2.0;
+        Some((score, score > options.categorical_threshold))
+    }
+
+    fn to_json(&self, options: &DriftOptions) -> Value {
+        let mut top: Vec<(&String, &u64)> = self.frequencies.iter().collect();
+        top.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let top: Vec<Value> = top
+            .into_iter()
+            .take(SAMPLE_LIMIT)
+            .map(|(value, count)| {
+                json!({
+                    "value": serde_json::from_str::<Value>(value).unwrap_or(Value::Null),
+                    "count": count,
+                })
+            })
+            .collect();
+        let null_rate = if self.count == 0 {
+            0.0
+        } else {
+            self.nulls as f64 / self.count as f64
+        };
+        let drift = self
+            .drift(options)
+            .map(|(score, drifted)| json!({ "score": score, "drifted": drifted }));
+        let mut report = json!({
+            "count": self.count,
+            "null_rate": null_rate,
+            "top": top,
+            "drift": drift,
+        });
+        if self.moments.count > 0 {
+            report["mean"] = json!(self.moments.mean);
+            report["stddev"] = json!(self.moments.stddev());
+            report["min"] = json!(self.min);
+            report["max"] = json!(self.max);
+        }
+        report
+    }
+}
+
+/// Converts values into relative frequencies.
+fn frequency_distribution<'a, I: Iterator<Item = &'a String>>(values: I) -> HashMap<&'a str, f64> {
+    let mut counts: HashMap<&str, f64> = HashMap::new();
+    let mut total = 0.0;
+    for value in values {
+        *counts.entry(value.as_str()).or_insert(0.0) += 1.0;
+        total += 1.0;
+    }
+    counts.values_mut().for_each(|count| *count /= total);
+    counts
+}
+
+/// Statistics updated record by record, for example while following a
+/// growing NDJSON file.
+///
+/// For every scalar path (arrays folded into `[]`) it keeps counts, null rate,
+/// running mean/stddev/min/max for numbers, a frequency table, and a drift
+/// score comparing a sliding window of recent values with the first `window`
+/// values seen. Each update costs O(depth of the record), independent of how
+/// many records were seen before.
+#[derive(Debug, Clone)]
+pub struct IncrementalStats {
+    records: u64,
+    options: DriftOptions,
+    paths: BTreeMap<String, PathMetrics>,
+}
+
+impl IncrementalStats {
+    /// Creates empty statistics with the given drift options.
+    pub fn new(options: DriftOptions) -> Self {
+        IncrementalStats {
+            records: 0,
+            options,
+            paths: BTreeMap::new(),
+        }
+    }
+
+    /// Updates the statistics with one appended record.
+    pub fn observe(&mut self, record: &Value) {
+        self.records += 1;
+        let window = self.options.window.max(1);
+        let paths = &mut self.paths;
+        walk_folded_paths(record, "", &mut |path, value| {
+            if !value.is_object() && !value.is_array() {
+                paths
+                    .entry(path.to_string())
+                    .or_default()
+                    .observe(value, window);
+            }
+        });
+    }
+
+    /// Returns the number of records observed so far.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Produces a JSON metrics snapshot of the current state.
+    pub fn snapshot(&self) -> Value {
+        let paths: Map<String, Value> = self
+            .paths
+            .iter()
+            .map(|(path, metrics)| (path.clone(), metrics.to_json(&self.options)))
+            .collect();
+        json!({ "records": self.records, "paths": paths })
+    }
+}
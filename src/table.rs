@@ -0,0 +1,181 @@
+use crate::flatten::flatten_with;
+use crate::path_syntax::PathSyntax;
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+
+/// How nested objects and arrays of a row are written by `json_to_markdown` and
+/// `json_to_html`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NestedCells {
+    /// Flattened into columns such as `address.city` and `tags[0]`, as in `json_to_csv`.
+    #[default]
+    Flatten,
+    /// Written as compact JSON in the cell of their top-level key.
+    Inline,
+}
+
+/// Options for `json_to_markdown` and `json_to_html`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableOptions {
+    pub nested: NestedCells,
+    /// Syntax of the names of flattened columns.
+    pub syntax: PathSyntax,
+}
+
+/// The columns and rows of a table, with the columns holding only numbers.
+struct Table {
+    header: Vec<String>,
+    numeric: Vec<bool>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Renders an array of objects as a GitHub-flavored Markdown table, one row per object,
+/// ready to paste into pull requests and wikis.
+///
+/// The header lists every column in order of first appearance; missing fields and
+/// `null` are empty cells. Columns holding only numbers are right-aligned. Pipes are
+/// escaped and line breaks written as `<br>`.
+///
+/// # Arguments
+///
+/// * `json` - The array of objects, e.g. the results of a query.
+/// * `options` - Whether nested values are flattened into columns or inlined.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The table, or an error if `json` is not an array of objects.
+pub fn json_to_markdown(json: &Value, options: &TableOptions) -> Result<String, Error> {
+    let table = build_table(json, options, "Markdown")?;
+    if table.header.is_empty() {
+        return Ok(String::new());
+    }
+    let escape = |text: &str| {
+        text.replace('\\', "\\\\")
+            .replace('|', "\\|")
+            .replace("\r\n", "<br>")
+            .replace('\n', "<br>")
+    };
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+    let mut markdown = line(table.header.iter().map(|name| escape(name)).collect());
+    markdown.push_str(&line(
+        table
+            .numeric
+            .iter()
+            .map(|&numeric| if numeric { "---:" } else { "---" }.to_string())
+            .collect(),
+    ));
+    for row in &table.rows {
+        markdown.push_str(&line(row.iter().map(|cell| escape(cell)).collect()));
+    }
+    Ok(markdown)
+}
+
+/// Renders an array of objects as an HTML `<table>`, one row per object, with the same
+/// columns as `json_to_markdown`. Text is HTML-escaped.
+///
+/// # Arguments
+///
+/// * `json` - The array of objects, e.g. the results of a query.
+/// * `options` - Whether nested values are flattened into columns or inlined.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The table, or an error if `json` is not an array of objects.
+pub fn json_to_html(json: &Value, options: &TableOptions) -> Result<String, Error> {
+    let table = build_table(json, options, "HTML")?;
+    if table.header.is_empty() {
+        return Ok(String::new());
+    }
+    let cell = |tag: &str, text: &str, numeric: bool| {
+        let align = if numeric {
+            " style=\"text-align: right\""
+        } else {
+            ""
+        };
+        format!("      <{tag}{align}>{}</{tag}>\n", escape_html(text))
+    };
+    let mut html = String::from("<table>\n  <thead>\n    <tr>\n");
+    for (name, &numeric) in table.header.iter().zip(&table.numeric) {
+        html.push_str(&cell("th", name, numeric));
+    }
+    html.push_str("    </tr>\n  </thead>\n  <tbody>\n");
+    for row in &table.rows {
+        html.push_str("    <tr>\n");
+        for (text, &numeric) in row.iter().zip(&table.numeric) {
+            html.push_str(&cell("td", text, numeric));
+        }
+        html.push_str("    </tr>\n");
+    }
+    html.push_str("  </tbody>\n</table>\n");
+    Ok(html)
+}
+
+/// Collects the columns and cell texts of an array of objects.
+fn build_table(json: &Value, options: &TableOptions, format: &str) -> Result<Table, Error> {
+    let items = json.as_array().ok_or_else(|| {
+        Error::msg(format!(
+            "Only an array of objects can be converted to a {} table",
+            format
+        ))
+    })?;
+    let mut header: Vec<String> = Vec::new();
+    let mut records: Vec<Map<String, Value>> = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        let record = match (item, options.nested) {
+            (Value::Object(_), NestedCells::Flatten) => match flatten_with(item, &options.syntax) {
+                Value::Object(record) => record,
+                _ => unreachable!("flatten_with returns an object"),
+            },
+            (Value::Object(record), NestedCells::Inline) => record.clone(),
+            _ => {
+                return Err(Error::msg(format!(
+                    "Element {} is not an object and cannot be a table row",
+                    index
+                )))
+            }
+        };
+        for column in record.keys() {
+            if !header.contains(column) {
+                header.push(column.clone());
+            }
+        }
+        records.push(record);
+    }
+    let numeric = header
+        .iter()
+        .map(|column| {
+            let mut values = records
+                .iter()
+                .filter_map(|record| record.get(column))
+                .filter(|value| !value.is_null())
+                .peekable();
+            values.peek().is_some() && values.all(Value::is_number)
+        })
+        .collect();
+    let rows = records
+        .iter()
+        .map(|record| {
+            header
+                .iter()
+                .map(|column| match record.get(column) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(text)) => text.clone(),
+                    Some(other) => other.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+    Ok(Table {
+        header,
+        numeric,
+        rows,
+    })
+}
+
+/// Escapes the characters with a special meaning in HTML text and attributes.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,515 @@
+use crate::warnings;
+use anyhow::{Error, Result};
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::Parser;
+use serde_json::{json, Map, Number, Value};
+use std::collections::HashSet;
+
+/// TomlParser struct, generated from the grammar defined in `toml.pest`.
+///
+/// Covers TOML 1.0: tables, arrays of tables, dotted and quoted keys, basic,
+/// literal and multi-line strings, prefixed and underscored integers, floats,
+/// booleans, arrays, inline tables, and offset/local datetimes.
+#[derive(Parser)]
+#[grammar = "toml.pest"]
+struct TomlParser;
+
+/// Options for `parse_toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TomlOptions {
+    /// Wraps datetimes as `{"type": "datetime", "value": "..."}` so they can be told
+    /// apart from strings. The type is one of `datetime`, `datetime-local`,
+    /// `date-local`, or `time-local`, as in the toml-test JSON encoding.
+    pub annotate_types: bool,
+}
+
+/// Parses a TOML document into JSON.
+///
+/// Datetimes are kept as RFC 3339 strings (`1979-05-27T07:32:00Z`), with the
+/// date/time delimiter and `Z` normalized to upper case.
+///
+/// # Arguments
+///
+/// * `input` - The TOML text, e.g. the contents of a `Cargo.toml`.
+/// * `options` - Datetime annotation.
+///
+/// # Returns
+///
+/// * `Result<Value>` - The document as a JSON object, or an error for invalid TOML,
+///   duplicate keys, redefined tables, or `inf`/`nan` floats that JSON cannot hold.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = input.len())))]
+pub fn parse_toml(input: &str, options: &TomlOptions) -> Result<Value> {
+    let document = TomlParser::parse(Rule::document, input)
+        .map_err(|e| Error::msg(format!("Invalid TOML: {}", e)))?
+        .next()
+        .ok_or_else(|| Error::msg("Empty TOML parse result"))?;
+
+    let mut root = Value::Object(Map::new());
+    let mut current: Vec<String> = Vec::new();
+    let mut defined: HashSet<String> = HashSet::new();
+    for expression in document.into_inner() {
+        match expression.as_rule() {
+            Rule::table => {
+                let path = keys(expression.into_inner().next().unwrap())?;
+                let (_, concrete) = table_at(&mut root, &path)?;
+                if !defined.insert(concrete.clone()) {
+                    return Err(Error::msg(format!("Table [{}] is defined twice", concrete)));
+                }
+                current = path;
+            }
+            Rule::array_table => {
+                let path = keys(expression.into_inner().next().unwrap())?;
+                let (last, parent) = path.split_last().unwrap();
+                let (table, _) = table_at(&mut root, parent)?;
+                match table
+                    .entry(last.clone())
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                {
+                    Value::Array(items) => items.push(Value::Object(Map::new())),
+                    _ => {
+                        return Err(Error::msg(format!(
+                            "Key '{}' is not an array of tables",
+                            path.join(".")
+                        )))
+                    }
+                }
+                current = path;
+            }
+            Rule::keyval => {
+                let (table, _) = table_at(&mut root, &current)?;
+                insert_keyval(table, expression, options)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(root)
+}
+
+/// Navigates to the table at `path`, creating missing tables. Arrays of tables
+/// resolve to their last element. Returns the table and its concrete path, with
+/// array positions, used to detect redefinitions.
+fn table_at<'a>(
+    root: &'a mut Value,
+    path: &[String],
+) -> Result<(&'a mut Map<String, Value>, String)> {
+    let mut concrete = String::new();
+    let mut node = root;
+    for key in path {
+        let Value::Object(map) = node else {
+            return Err(Error::msg(format!("Key '{}' is not a table", concrete)));
+        };
+        if !concrete.is_empty() {
+            concrete.push('.');
+        }
+        concrete.push_str(key);
+        node = map
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Array(items) = node {
+            concrete.push_str(&format!("[{}]", items.len().saturating_sub(1)));
+            node = items
+                .last_mut()
+                .ok_or_else(|| Error::msg(format!("Key '{}' is an empty array", key)))?;
+        }
+    }
+    match node {
+        Value::Object(map) => Ok((map, concrete)),
+        _ => Err(Error::msg(format!("Key '{}' is not a table", concrete))),
+    }
+}
+
+/// Inserts a key-value pair, creating the intermediate tables of a dotted key.
+fn insert_keyval(
+    table: &mut Map<String, Value>,
+    pair: Pair<Rule>,
+    options: &TomlOptions,
+) -> Result<()> {
+    let mut inner = pair.into_inner();
+    let path = keys(inner.next().unwrap())?;
+    let value = convert_value(inner.next().unwrap(), options)?;
+    let (last, parents) = path.split_last().unwrap();
+    let mut table = table;
+    for key in parents {
+        let child = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        table = child
+            .as_object_mut()
+            .ok_or_else(|| Error::msg(format!("Key '{}' is not a table", path.join("."))))?;
+    }
+    if table.contains_key(last) {
+        return Err(Error::msg(format!("Duplicate key '{}'", path.join("."))));
+    }
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+/// Collects the parts of a (possibly dotted) key.
+fn keys(pair: Pair<Rule>) -> Result<Vec<String>> {
+    pair.into_inner()
+        .map(|part| match part.as_rule() {
+            Rule::bare_key => Ok(part.as_str().to_string()),
+            _ => string_value(part),
+        })
+        .collect()
+}
+
+/// Converts a TOML value into JSON.
+fn convert_value(pair: Pair<Rule>, options: &TomlOptions) -> Result<Value> {
+    let text = pair.as_str();
+    Ok(match pair.as_rule() {
+        Rule::basic_string
+        | Rule::ml_basic_string
+        | Rule::literal_string
+        | Rule::ml_literal_string => Value::String(string_value(pair)?),
+        Rule::boolean => Value::Bool(text == "true"),
+        Rule::integer => Value::from(parse_integer(text)?),
+        Rule::float => {
+            let cleaned = text.replace('_', "");
+            cleaned
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| Error::msg(format!("{} has no JSON representation", text)))?
+        }
+        Rule::offset_datetime | Rule::local_datetime | Rule::local_date | Rule::local_time => {
+            let kind = match pair.as_rule() {
+                Rule::offset_datetime => "datetime",
+                Rule::local_datetime => "datetime-local",
+                Rule::local_date => "date-local",
+                _ => "time-local",
+            };
+            let value = normalize_datetime(text)?;
+            if options.annotate_types {
+                json!({ "type": kind, "value": value })
+            } else {
+                Value::String(value)
+            }
+        }
+        Rule::array => Value::Array(
+            pair.into_inner()
+                .map(|item| convert_value(item, options))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        Rule::inline_table => {
+            let mut table = Map::new();
+            for keyval in pair.into_inner() {
+                insert_keyval(&mut table, keyval, options)?;
+            }
+            Value::Object(table)
+        }
+        rule => return Err(Error::msg(format!("Unexpected TOML element: {:?}", rule))),
+    })
+}
+
+/// Parses a decimal, hex, octal, or binary integer with optional underscores.
+fn parse_integer(text: &str) -> Result<i64> {
+    let cleaned = text.replace('_', "");
+    let parsed = match cleaned.get(..2) {
+        Some("0x") => i64::from_str_radix(&cleaned[2..], 16),
+        Some("0o") => i64::from_str_radix(&cleaned[2..], 8),
+        Some("0b") => i64::from_str_radix(&cleaned[2..], 2),
+        _ => cleaned.parse::<i64>(),
+    };
+    parsed.map_err(|e| Error::msg(format!("Invalid integer {}: {}", text, e)))
+}
+
+/// Returns the contents of a string token, resolving escapes in basic strings.
+fn string_value(pair: Pair<Rule>) -> Result<String> {
+    let text = pair.as_str();
+    match pair.as_rule() {
+        Rule::literal_string => Ok(text[1..text.len() - 1].to_string()),
+        Rule::ml_literal_string => Ok(trim_leading_newline(&text[3..text.len() - 3]).to_string()),
+        Rule::basic_string => unescape(&text[1..text.len() - 1], false),
+        Rule::ml_basic_string => unescape(trim_leading_newline(&text[3..text.len() - 3]), true),
+        rule => Err(Error::msg(format!("Expected a string, found {:?}", rule))),
+    }
+}
+
+/// Drops the newline directly after the opening delimiter of a multi-line string.
+fn trim_leading_newline(s: &str) -> &str {
+    s.strip_prefix("\r\n")
+        .or_else(|| s.strip_prefix('\n'))
+        .unwrap_or(s)
+}
+
+/// Resolves the escape sequences of a basic string. In multi-line strings a
+/// backslash at the end of a line also removes the line break and the
+/// whitespace that follows it.
+fn unescape(s: &str, multiline: bool) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => out.push('\u{0008}'),
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('f') => out.push('\u{000C}'),
+            Some('r') => out.push('\r'),
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some(marker @ ('u' | 'U')) => {
+                let length = if marker == 'u' { 4 } else { 8 };
+                let hex: String = chars.by_ref().take(length).collect();
+                let code = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == length)
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        Error::msg(format!("Invalid unicode escape \\{}{}", marker, hex))
+                    })?;
+                out.push(code);
+            }
+            Some(c) if multiline && c.is_whitespace() => {
+                let mut rest = vec![c];
+                while let Some(&next) = chars.peek().filter(|next| next.is_whitespace()) {
+                    rest.push(next);
+                    chars.next();
+                }
+                if !rest.contains(&'\n') {
+                    return Err(Error::msg(
+                        "Line-ending backslash must be followed by a newline",
+                    ));
+                }
+            }
+            Some(other) => return Err(Error::msg(format!("Invalid escape sequence \\{}", other))),
+            None => return Err(Error::msg("Unterminated escape sequence")),
+        }
+    }
+    Ok(out)
+}
+
+/// Normalizes a TOML datetime to RFC 3339 and checks its field ranges.
+fn normalize_datetime(text: &str) -> Result<String> {
+    let mut value = text.to_string();
+    if value.len() > 10 && value.as_bytes()[4] == b'-' {
+        value.replace_range(10..11, "T");
+    }
+    if value.ends_with('z') {
+        value.pop();
+        value.push('Z');
+    }
+    let field = |start: usize| {
+        value
+            .get(start..start + 2)
+            .and_then(|s| s.parse::<u32>().ok())
+    };
+    let (date_ok, time_start) = if value.as_bytes().get(4) == Some(&b'-') {
+        let month = field(5).unwrap_or(0);
+        let day = field(8).unwrap_or(0);
+        ((1..=12).contains(&month) && (1..=31).contains(&day), 11)
+    } else {
+        (true, 0)
+    };
+    let has_time = time_start == 0 || value.len() > 10;
+    let time_ok = !has_time
+        || (field(time_start).is_some_and(|h| h < 24)
+            && field(time_start + 3).is_some_and(|m| m < 60)
+            && field(time_start + 6).is_some_and(|s| s <= 60));
+    if !date_ok || !time_ok {
+        return Err(Error::msg(format!("Invalid datetime {}", text)));
+    }
+    Ok(value)
+}
+
+/// Converts JSON to a TOML document.
+///
+/// Nested objects become `[table]` sections and arrays of objects become
+/// `[[array.of.tables]]`, except inside other arrays, where inline tables are used.
+/// Datetimes annotated as by `parse_toml` with `annotate_types`
+/// (`{"type": "datetime", "value": "1979-05-27T07:32:00Z"}`) are written as TOML
+/// datetimes; plain strings stay strings.
+///
+/// # Arguments
+///
+/// * `json` - The document; it must be an object.
+///
+/// # Returns
+///
+/// * `Result<String>` - The TOML text, or an error for a document that is not an
+///   object or contains `null`, which TOML cannot represent.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn json_to_toml(json: &Value) -> Result<String> {
+    let table = json
+        .as_object()
+        .ok_or_else(|| Error::msg("TOML documents must be objects"))?;
+    let mut out = String::new();
+    write_table(table, &[], &mut out)?;
+    Ok(out)
+}
+
+/// Writes the key/value pairs of a table, then its sub-tables and arrays of tables.
+/// `path` holds the already written header of the table.
+fn write_table(table: &Map<String, Value>, path: &[String], out: &mut String) -> Result<()> {
+    let is_table = |value: &Value| value.is_object() && datetime_literal(value).is_none();
+    let is_table_array = |value: &Value| match value {
+        Value::Array(items) => !items.is_empty() && items.iter().all(is_table),
+        _ => false,
+    };
+    for (key, value) in table {
+        if !is_table(value) && !is_table_array(value) {
+            let path = child_path(path, key);
+            out.push_str(&format!(
+                "{} = {}\n",
+                toml_key(key),
+                inline_value(value, &path)?
+            ));
+        }
+    }
+    for (key, value) in table {
+        let table_path = child_path(path, key);
+        let header = table_path
+            .iter()
+            .map(|key| toml_key(key))
+            .collect::<Vec<_>>()
+            .join(".");
+        match value {
+            Value::Object(child) if is_table(value) => {
+                // Tables holding only sub-tables need no header of their own.
+                let has_values = child.values().any(|v| !is_table(v) && !is_table_array(v));
+                if has_values || child.is_empty() {
+                    push_header(out, &format!("[{}]", header));
+                }
+                write_table(child, &table_path, out)?;
+            }
+            Value::Array(items) if is_table_array(value) => {
+                for item in items {
+                    push_header(out, &format!("[[{}]]", header));
+                    write_table(item.as_object().unwrap(), &table_path, out)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path of a member or element below `path`.
+fn child_path(path: &[String], key: &str) -> Vec<String> {
+    let mut child = path.to_vec();
+    child.push(key.to_string());
+    child
+}
+
+/// Starts a table section, separated from the previous content by a blank line.
+fn push_header(out: &mut String, header: &str) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(header);
+    out.push('\n');
+}
+
+/// Formats a value for the right-hand side of a key/value pair.
+fn inline_value(value: &Value, path: &[String]) -> Result<String> {
+    if let Some(datetime) = datetime_literal(value) {
+        return Ok(datetime.to_string());
+    }
+    Ok(match value {
+        Value::Null => {
+            return Err(Error::msg(format!(
+                "TOML cannot represent null at {}",
+                path.join(".")
+            )))
+        }
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) if n.is_f64() => {
+            let text = n.to_string();
+            // TOML reads a number without a fraction or exponent as an integer.
+            if text.contains(['.', 'e', 'E']) {
+                text
+            } else {
+                format!("{}.0", text)
+            }
+        }
+        Value::Number(n) if n.as_i64().is_none() => {
+            warnings::warn(
+                &path.join("."),
+                format!(
+                    "Integer {} exceeds the TOML range and was written as a float",
+                    n
+                ),
+            );
+            format!("{:?}", n.as_f64().unwrap_or_default())
+        }
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => basic_string(s),
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| inline_value(item, &child_path(path, &i.to_string())))
+                .collect::<Result<Vec<_>>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let entries = map
+                .iter()
+                .map(|(key, item)| {
+                    let path = child_path(path, key);
+                    Ok(format!(
+                        "{} = {}",
+                        toml_key(key),
+                        inline_value(item, &path)?
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if entries.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", entries.join(", "))
+            }
+        }
+    })
+}
+
+/// Returns the datetime of a value annotated like `{"type": "datetime", "value": "..."}`.
+fn datetime_literal(value: &Value) -> Option<&str> {
+    let map = value.as_object()?;
+    let kind = map.get("type")?.as_str()?;
+    let text = map.get("value")?.as_str()?;
+    let is_datetime = matches!(
+        kind,
+        "datetime" | "datetime-local" | "date-local" | "time-local"
+    );
+    (map.len() == 2 && is_datetime && normalize_datetime(text).is_ok()).then_some(text)
+}
+
+/// Writes a key bare when possible, otherwise as a quoted string.
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        basic_string(key)
+    }
+}
+
+/// Writes a TOML basic string, escaping quotes, backslashes and control characters.
+fn basic_string(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
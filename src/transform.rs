@@ -0,0 +1,423 @@
+use crate::array_ops::{array_at_mut, compile_key};
+use crate::parser::{parse_steps, remove_at, select_paths, Step};
+use crate::stats::compare_values;
+use anyhow::{Error, Result};
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// Naming convention for object keys, used by `transform_keys`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCase {
+    /// `userId`
+    Camel,
+    /// `user_id`
+    Snake,
+    /// `user-id`
+    Kebab,
+    /// `UserId`
+    Pascal,
+}
+
+impl FromStr for KeyCase {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "camel" => Ok(KeyCase::Camel),
+            "snake" => Ok(KeyCase::Snake),
+            "kebab" => Ok(KeyCase::Kebab),
+            "pascal" => Ok(KeyCase::Pascal),
+            _ => Err(Error::msg(format!("Unsupported key case: {}", s))),
+        }
+    }
+}
+
+impl KeyCase {
+    /// Converts a key to this case. Words are split at `_`, `-` and spaces and at case
+    /// changes, keeping acronyms together (`HTTPServer` is `http_server` in snake case);
+    /// leading underscores such as in `_id` are kept.
+    pub fn convert(self, key: &str) -> String {
+        let body = key.trim_start_matches('_');
+        let prefix = &key[..key.len() - body.len()];
+        let words = split_words(body);
+        let mut converted = String::from(prefix);
+        for (i, word) in words.iter().enumerate() {
+            let lower = word.to_lowercase();
+            match self {
+                KeyCase::Snake | KeyCase::Kebab => {
+                    if i > 0 {
+                        converted.push(if self == KeyCase::Snake { '_' } else { '-' });
+                    }
+                    converted.push_str(&lower);
+                }
+                KeyCase::Camel if i == 0 => converted.push_str(&lower),
+                KeyCase::Camel | KeyCase::Pascal => {
+                    let mut chars = lower.chars();
+                    if let Some(first) = chars.next() {
+                        converted.extend(first.to_uppercase());
+                        converted.push_str(chars.as_str());
+                    }
+                }
+            }
+        }
+        converted
+    }
+}
+
+/// Renames every object key in a document to a naming convention, recursively,
+/// including objects inside arrays. Values are left unchanged.
+///
+/// # Arguments
+///
+/// * `json` - The document to rewrite in place.
+/// * `case` - The naming convention, e.g. `KeyCase::Snake` turns `userId` into `user_id`.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if two keys of one object convert to the same key,
+///   such as `userId` and `user_id`; the document is then left unchanged.
+pub fn transform_keys(json: &mut Value, case: KeyCase) -> Result<(), Error> {
+    *json = converted_keys(json, case)?;
+    Ok(())
+}
+
+/// Returns a copy of `json` with every object key converted to `case`.
+fn converted_keys(json: &Value, case: KeyCase) -> Result<Value, Error> {
+    match json {
+        Value::Object(map) => {
+            let mut renamed = Map::new();
+            for (key, value) in map {
+                let new_key = case.convert(key);
+                if renamed.contains_key(&new_key) {
+                    return Err(Error::msg(format!(
+                        "Key '{}' conflicts with another key that converts to '{}'",
+                        key, new_key
+                    )));
+                }
+                renamed.insert(new_key, converted_keys(value, case)?);
+            }
+            Ok(Value::Object(renamed))
+        }
+        Value::Array(items) => Ok(Value::Array(
+            items
+                .iter()
+                .map(|item| converted_keys(item, case))
+                .collect::<Result<_, _>>()?,
+        )),
+        other => Ok(other.clone()),
+    }
+}
+
+/// Splits a key into words at separators and case changes.
+fn split_words(key: &str) -> Vec<String> {
+    let chars: Vec<char> = key.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if matches!(c, '_' | '-' | ' ') {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !word.is_empty() {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            // `userId` and `version2Name` split before the capital, `HTTPServer` before `Server`.
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lower)
+            {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Keeps only the values selected by some paths, along with the objects and arrays
+/// leading to them.
+///
+/// Paths use the `select_paths` syntax, so `*` and `[*]` apply a path to every member
+/// or element and `..name` finds a member at any depth: `["id", "items[*].name"]`
+/// keeps the `id` and the `name` of every item. Arrays keep only the elements that
+/// contain a selected value, in their original order.
+///
+/// # Arguments
+///
+/// * `json` - The document to project.
+/// * `keep` - The paths of the values to keep; invalid paths select nothing.
+///
+/// # Returns
+///
+/// * `Value` - The projection; an empty object or array (or `null` for a scalar) if
+///   nothing is selected.
+pub fn project(json: &Value, keep: &[&str]) -> Value {
+    let paths = selected_steps(json, keep);
+    let paths: Vec<&[Step]> = paths.iter().map(Vec::as_slice).collect();
+    project_at(json, &paths).unwrap_or_else(|| match json {
+        Value::Object(_) => Value::Object(Map::new()),
+        Value::Array(_) => Value::Array(Vec::new()),
+        _ => Value::Null,
+    })
+}
+
+/// Removes the values selected by some paths, such as timestamps or internal ids
+/// before a document is committed as a test fixture: `["..updated_at", "items[*].id"]`.
+///
+/// # Arguments
+///
+/// * `json` - The document.
+/// * `drop` - The paths of the values to remove, in the `select_paths` syntax; invalid
+///   paths select nothing.
+///
+/// # Returns
+///
+/// * `Value` - The document without the selected values; removed array elements are
+///   taken out, so later elements shift down.
+pub fn omit(json: &Value, drop: &[&str]) -> Value {
+    let mut result = json.clone();
+    // Later elements go first, so removals do not shift the indexes still to remove.
+    for steps in selected_steps(json, drop).iter().rev() {
+        remove_at(&mut result, steps);
+    }
+    result
+}
+
+/// Returns the distinct locations selected by some paths, sorted.
+fn selected_steps(json: &Value, paths: &[&str]) -> Vec<Vec<Step>> {
+    let mut selected: Vec<Vec<Step>> = paths
+        .iter()
+        .flat_map(|path| select_paths(json, path))
+        .filter_map(|(path, _)| parse_steps(&path).ok())
+        .collect();
+    selected.sort();
+    selected.dedup();
+    selected
+}
+
+/// Recursive helper function for `project`; `paths` are relative to `json`.
+fn project_at(json: &Value, paths: &[&[Step]]) -> Option<Value> {
+    if paths.is_empty() {
+        return None;
+    }
+    if paths.iter().any(|steps| steps.is_empty()) {
+        return Some(json.clone());
+    }
+    let below = |step: &Step| -> Vec<&[Step]> {
+        paths
+            .iter()
+            .filter_map(|steps| steps.split_first())
+            .filter(|(first, _)| *first == step)
+            .map(|(_, rest)| rest)
+            .collect()
+    };
+    match json {
+        Value::Object(map) => Some(Value::Object(
+            map.iter()
+                .filter_map(|(key, value)| {
+                    let kept = project_at(value, &below(&Step::Key(key.clone())))?;
+                    Some((key.clone(), kept))
+                })
+                .collect(),
+        )),
+        Value::Array(items) => Some(Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .filter_map(|(index, item)| project_at(item, &below(&Step::Index(index))))
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// Direction of `sort_array_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl FromStr for SortOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(SortOrder::Ascending),
+            "desc" => Ok(SortOrder::Descending),
+            _ => Err(Error::msg(format!("Unsupported sort order: {}", s))),
+        }
+    }
+}
+
+/// Orders the members of every object by key, recursively, including objects inside
+/// arrays; array elements keep their order.
+///
+/// Objects parsed by this crate already iterate in key order unless serde_json's
+/// `preserve_order` feature is enabled in the build, so this mainly matters for
+/// documents built that way, before they are written as diffable fixtures.
+///
+/// # Arguments
+///
+/// * `json` - The document to reorder in place.
+pub fn sort_keys(json: &mut Value) {
+    match json {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = std::mem::take(map).into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            for (key, mut value) in entries {
+                sort_keys(&mut value);
+                map.insert(key, value);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+/// Sorts the array at a path by a key computed for each element.
+///
+/// The sort is stable, so elements with equal keys keep their order, and keys are
+/// compared with `compare_values`; elements for which the key is undefined sort as
+/// `null`.
+///
+/// # Arguments
+///
+/// * `json` - The document containing the array, sorted in place.
+/// * `path` - The path of the array in `get_by_path` syntax, e.g. `data.users`, or an
+///   empty string for the document itself.
+/// * `key` - An expression evaluated against each element (see `Expression`), e.g.
+///   `age` or `last + first`; a jq-style leading dot as in `.age` is accepted.
+/// * `order` - `SortOrder::Ascending` or `SortOrder::Descending`.
+///
+/// # Returns
+///
+/// * `Result<(), Error>` - An error if the key is not a valid expression or the path
+///   does not lead to an array.
+pub fn sort_array_by(
+    json: &mut Value,
+    path: &str,
+    key: &str,
+    order: SortOrder,
+) -> Result<(), Error> {
+    let key = compile_key(key)?;
+    let items = array_at_mut(json, path)?;
+    let mut keyed: Vec<(Value, Value)> = std::mem::take(items)
+        .into_iter()
+        .map(|item| (key.evaluate(&item).unwrap_or(Value::Null), item))
+        .collect();
+    keyed.sort_by(|a, b| match order {
+        SortOrder::Ascending => compare_values(&a.0, &b.0),
+        SortOrder::Descending => compare_values(&b.0, &a.0),
+    });
+    items.extend(keyed.into_iter().map(|(_, item)| item));
+    Ok(())
+}
+
+/// Which values `prune` removes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PruneOptions {
+    /// Removes `null` values.
+    pub nulls: bool,
+    /// Removes `""`.
+    pub empty_strings: bool,
+    /// Removes `{}`, including objects that are empty once pruned.
+    pub empty_objects: bool,
+    /// Removes `[]`, including arrays that are empty once pruned.
+    pub empty_arrays: bool,
+}
+
+impl Default for PruneOptions {
+    /// Removes nulls, empty strings, and empty objects and arrays.
+    fn default() -> Self {
+        PruneOptions {
+            nulls: true,
+            empty_strings: true,
+            empty_objects: true,
+            empty_arrays: true,
+        }
+    }
+}
+
+impl PruneOptions {
+    /// Returns whether `prune` removes this value, once its own contents are pruned.
+    fn removes(&self, value: &Value) -> bool {
+        match value {
+            Value::Null => self.nulls,
+            Value::String(text) => self.empty_strings && text.is_empty(),
+            Value::Object(map) => self.empty_objects && map.is_empty(),
+            Value::Array(items) => self.empty_arrays && items.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+/// Removes nulls and empty values from a document, recursively, so that
+/// `{"a": {"b": null}, "c": ""}` becomes `{}`.
+///
+/// Members and array elements are both removed, so later elements shift down; the
+/// document itself is kept even if it ends up empty.
+///
+/// # Arguments
+///
+/// * `json` - The document to prune in place.
+/// * `options` - Which values to remove.
+///
+/// # Returns
+///
+/// * `usize` - The number of values removed, not counting the contents of removed
+///   objects and arrays.
+pub fn prune(json: &mut Value, options: PruneOptions) -> usize {
+    let mut removed = 0;
+    match json {
+        Value::Object(map) => map.retain(|_, value| {
+            removed += prune(value, options);
+            let remove = options.removes(value);
+            removed += usize::from(remove);
+            !remove
+        }),
+        Value::Array(items) => items.retain_mut(|item| {
+            removed += prune(item, options);
+            let remove = options.removes(item);
+            removed += usize::from(remove);
+            !remove
+        }),
+        _ => {}
+    }
+    removed
+}
+
+/// Inserts the members of a defaults document that are missing from a document,
+/// recursively through objects present in both. Existing values, including `null`,
+/// are kept, and arrays are not merged.
+///
+/// # Arguments
+///
+/// * `json` - The document to complete in place.
+/// * `defaults` - The defaults, e.g. `{"server": {"port": 8080, "tls": false}}`.
+///
+/// # Returns
+///
+/// * `usize` - The number of members inserted.
+pub fn fill_defaults(json: &mut Value, defaults: &Value) -> usize {
+    let (Value::Object(map), Value::Object(defaults)) = (json, defaults) else {
+        return 0;
+    };
+    let mut inserted = 0;
+    for (key, default) in defaults {
+        match map.get_mut(key) {
+            Some(value) => inserted += fill_defaults(value, default),
+            None => {
+                map.insert(key.clone(), default.clone());
+                inserted += 1;
+            }
+        }
+    }
+    inserted
+}
@@ -0,0 +1,903 @@
+use crate::validator::{validate_document, ValidationReport};
+use anyhow::{Error, Result};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess,
+    SeqAccess, VariantAccess, Visitor,
+};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Most deserialization passes `schema_for` makes before giving up; each pass covers
+/// another enum variant or ends another recursion.
+const MAX_PASSES: usize = 10_000;
+
+/// Derives a JSON Schema (draft 2020-12) from a type implementing `Deserialize`, so
+/// payloads can be checked against the structs a service actually deserializes into,
+/// with errors for every field instead of serde's first one.
+///
+/// The schema is traced by running the type's `Deserialize` implementation against a
+/// recording deserializer, so serde attributes such as `rename`, `rename_all`,
+/// `skip_deserializing` and `alias` are honored. Structs become objects whose fields
+/// are required unless they are `Option`s, `Option`s accept `null`, sequences become
+/// arrays, maps objects, tuples fixed-length arrays, and externally tagged enums a
+/// `oneOf` of their variants. Integer types get their range as `minimum` and `maximum`.
+/// Recursive types refer to themselves through `$defs`.
+///
+/// Fields with `#[serde(default)]` are still listed as required, as the default is not
+/// visible to a deserializer, and types that deserialize with `deserialize_any`, such
+/// as `serde_json::Value`, accept any value. Internally tagged and untagged enums and
+/// `#[serde(flatten)]` cannot be traced.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The schema, or an error if the type cannot be traced.
+pub fn schema_for<T: DeserializeOwned>() -> Result<Value, Error> {
+    let mut trace = Trace::default();
+    for _ in 0..MAX_PASSES {
+        trace.start_pass();
+        let mut slot = Slot::default();
+        let result = T::deserialize(Tracer {
+            trace: &mut trace,
+            path: String::new(),
+            slot: &mut slot,
+        });
+        match result {
+            Err(_) if trace.handled => continue,
+            Err(e) => {
+                return Err(Error::msg(format!(
+                    "Cannot derive a JSON Schema for {}: {}",
+                    std::any::type_name::<T>(),
+                    e
+                )))
+            }
+            Ok(_) if trace.incomplete() => continue,
+            Ok(_) => return Ok(trace.finish(slot.schema)),
+        }
+    }
+    Err(Error::msg(format!(
+        "Cannot derive a JSON Schema for {}: a type contains itself without an Option, a \
+         collection or another enum variant in between",
+        std::any::type_name::<T>()
+    )))
+}
+
+/// Validates a document against the schema of a type, e.g. a request body against
+/// the struct its handler deserializes.
+///
+/// # Arguments
+///
+/// * `json` - The document to validate.
+///
+/// # Returns
+///
+/// * `Result<ValidationReport, Error>` - Every violation, or an error if no schema can
+///   be derived for the type.
+pub fn validate_as<T: DeserializeOwned>(json: &Value) -> Result<ValidationReport, Error> {
+    Ok(validate_document(json, &schema_for::<T>()?))
+}
+
+/// What a traced value wrote: its schema, and whether it is an `Option`, which serde
+/// fills with `None` when the field is missing.
+#[derive(Default)]
+struct Slot {
+    schema: Value,
+    optional: bool,
+}
+
+/// The variants of an enum met at one path, traced one per pass.
+struct EnumTrace {
+    variants: &'static [&'static str],
+    /// The variant the next pass traces.
+    chosen: usize,
+    schemas: Vec<Option<Value>>,
+    /// Variants that contain the enclosing type itself, and so cannot end a pass.
+    recursive: Vec<bool>,
+}
+
+/// State shared by the tracers of all passes. Paths are internal: `.field` for struct
+/// fields, `[]` for sequence elements, `[i]` for tuple elements, `{}` for map values,
+/// `?` for the content of an option and `<Variant>` for enum variants.
+#[derive(Default)]
+struct Trace {
+    /// Structs and enums being traced, with the number of open points when entered.
+    active: Vec<(&'static str, usize)>,
+    /// Paths of the options, sequences, maps and enums that can end a recursion.
+    open: Vec<String>,
+    /// The open point that ends the recursion met in this pass.
+    pending: Option<String>,
+    /// Whether `pending` was acted on, so that the failed pass is retried.
+    handled: bool,
+    /// Options, sequences and maps traced as empty, with the schema of their content.
+    cuts: BTreeMap<String, Value>,
+    /// Variant of each recursive enum occurrence, which only has to end the recursion.
+    fallbacks: BTreeMap<String, usize>,
+    enums: BTreeMap<String, EnumTrace>,
+    /// Schemas of the structs and enums, for `$defs`.
+    defs: BTreeMap<&'static str, Value>,
+    /// Structs and enums that refer to themselves.
+    referenced: BTreeSet<&'static str>,
+    /// The struct or enum traced, if the type is one.
+    root: Option<&'static str>,
+}
+
+impl Trace {
+    /// Resets the per-pass state and picks the enum variants of the next pass: a
+    /// variant not traced yet, else one leading to an enum with such a variant.
+    fn start_pass(&mut self) {
+        self.active.clear();
+        self.open.clear();
+        self.pending = None;
+        self.handled = false;
+        let incomplete: Vec<String> = self
+            .enums
+            .iter()
+            .filter(|(_, trace)| trace.schemas.iter().any(Option::is_none))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for (path, trace) in self.enums.iter_mut() {
+            let usable = |index: usize| !trace.recursive[index];
+            trace.chosen = trace
+                .schemas
+                .iter()
+                .position(Option::is_none)
+                .or_else(|| {
+                    (0..trace.variants.len()).find(|&index| {
+                        let prefix = format!("{}<{}>", path, trace.variants[index]);
+                        usable(index) && incomplete.iter().any(|other| other.starts_with(&prefix))
+                    })
+                })
+                .or_else(|| Some(trace.chosen).filter(|&index| usable(index)))
+                .or_else(|| (0..trace.variants.len()).find(|&index| usable(index)))
+                .unwrap_or(trace.chosen);
+        }
+    }
+
+    /// Returns whether an enum still has variants to trace.
+    fn incomplete(&self) -> bool {
+        self.enums
+            .values()
+            .any(|trace| trace.schemas.iter().any(Option::is_none))
+    }
+
+    /// Returns whether the open point at a path ends the pending recursion, marking it
+    /// as acted on.
+    fn ends_recursion(&mut self, path: &str) -> bool {
+        let ends = !self.handled && self.pending.as_deref() == Some(path);
+        self.handled |= ends;
+        ends
+    }
+
+    /// Resolves the enum placeholders and adds `$schema` and `$defs`.
+    fn finish(&self, root: Value) -> Value {
+        let root = match self.root {
+            Some(name) if self.referenced.contains(name) => {
+                json!({ "$ref": format!("#/$defs/{}", name) })
+            }
+            _ => self.resolve(&root),
+        };
+        let Value::Object(mut schema) = root else {
+            unreachable!("traced schemas are objects")
+        };
+        let mut result = Map::new();
+        result.insert(
+            "$schema".to_string(),
+            json!("https://json-schema.org/draft/2020-12/schema"),
+        );
+        result.append(&mut schema);
+        if !self.referenced.is_empty() {
+            let defs = self
+                .referenced
+                .iter()
+                .map(|name| (name.to_string(), self.resolve(&self.defs[name])))
+                .collect();
+            result.insert("$defs".to_string(), Value::Object(defs));
+        }
+        Value::Object(result)
+    }
+
+    /// Replaces `{"$enum": path}` placeholders with the variants traced at the path:
+    /// unit variants as an `enum` of strings, others as single-key objects.
+    fn resolve(&self, schema: &Value) -> Value {
+        match schema {
+            Value::Object(map) if map.len() == 1 && map.contains_key("$enum") => {
+                let trace = &self.enums[map["$enum"].as_str().unwrap_or_default()];
+                let mut units = Vec::new();
+                let mut branches = Vec::new();
+                for variant in trace.schemas.iter().flatten() {
+                    match variant.get("const") {
+                        Some(name) => units.push(name.clone()),
+                        None => branches.push(self.resolve(variant)),
+                    }
+                }
+                if !units.is_empty() {
+                    branches.insert(0, json!({ "type": "string", "enum": units }));
+                }
+                if branches.len() == 1 {
+                    branches.remove(0)
+                } else {
+                    json!({ "oneOf": branches })
+                }
+            }
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), self.resolve(value)))
+                    .collect(),
+            ),
+            Value::Array(items) => items.iter().map(|item| self.resolve(item)).collect(),
+            other => other.clone(),
+        }
+    }
+}
+
+/// Error of a traced `Deserialize` implementation, or the end of a recursion.
+#[derive(Debug)]
+struct TraceError(String);
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        TraceError(msg.to_string())
+    }
+}
+
+/// A deserializer that records the schema of what it is asked for into a slot, and
+/// hands the visitor a sample value so that deserialization continues.
+struct Tracer<'a> {
+    trace: &'a mut Trace,
+    path: String,
+    slot: &'a mut Slot,
+}
+
+impl Tracer<'_> {
+    /// Creates a tracer for a value nested at a path.
+    fn child<'b>(trace: &'b mut Trace, path: String, slot: &'b mut Slot) -> Tracer<'b> {
+        Tracer { trace, path, slot }
+    }
+
+    /// Enters a struct or enum, or refers to it and ends the pass if it is already
+    /// being traced.
+    fn enter(&mut self, name: &'static str) -> Result<(), TraceError> {
+        if let Some(&(_, depth)) = self.trace.active.iter().rev().find(|(n, _)| *n == name) {
+            return Err(self.recursion(name, depth));
+        }
+        if self.path.is_empty() && self.trace.active.is_empty() {
+            self.trace.root = Some(name);
+        }
+        self.trace.active.push((name, self.trace.open.len()));
+        Ok(())
+    }
+
+    /// Refers to a type being traced and ends the pass at the innermost open point
+    /// entered since, or fails if there is none.
+    fn recursion(&mut self, name: &'static str, depth: usize) -> TraceError {
+        self.slot.schema = json!({ "$ref": format!("#/$defs/{}", name) });
+        self.trace.referenced.insert(name);
+        if self.trace.open.len() > depth {
+            self.trace.pending = self.trace.open.last().cloned();
+            TraceError(format!("{} contains itself", name))
+        } else {
+            TraceError(format!(
+                "{} contains itself without an Option, a collection or another enum variant \
+                 in between",
+                name
+            ))
+        }
+    }
+
+    /// Leaves a struct, recording its schema for `$defs`.
+    fn leave<T>(self, result: Result<T, TraceError>) -> Result<T, TraceError> {
+        let (name, _) = self.trace.active.pop().expect("entered before");
+        if result.is_ok() {
+            self.trace.defs.insert(name, self.slot.schema.clone());
+        }
+        result
+    }
+
+    /// Records the fields of a struct or struct variant.
+    fn fields<'de, V: Visitor<'de>>(
+        &mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        let result = visitor.visit_map(Fields {
+            trace: self.trace,
+            path: &self.path,
+            fields,
+            index: 0,
+            properties: &mut properties,
+            required: &mut required,
+        });
+        let mut schema = json!({ "type": "object", "properties": properties });
+        if !required.is_empty() {
+            schema["required"] = Value::from(required);
+        }
+        self.slot.schema = schema;
+        result
+    }
+
+    /// Records a fixed number of elements, for tuples and tuple structs.
+    fn elements<'de, V: Visitor<'de>>(
+        &mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let paths = (0..len).map(|i| format!("{}[{}]", self.path, i)).collect();
+        let mut schemas = Vec::new();
+        let result = visitor.visit_seq(Elements {
+            trace: self.trace,
+            paths,
+            schemas: &mut schemas,
+        });
+        self.slot.schema = json!({
+            "type": "array",
+            "prefixItems": schemas,
+            "minItems": len,
+            "maxItems": len,
+        });
+        result
+    }
+
+    fn primitive(&mut self, schema: Value) {
+        self.slot.schema = schema;
+    }
+}
+
+macro_rules! trace_integer {
+    ($method:ident, $visit:ident, $ty:ty, $schema:expr) => {
+        fn $method<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+            self.primitive($schema);
+            visitor.$visit(0 as $ty)
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for Tracer<'_> {
+    type Error = TraceError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+        self.primitive(json!({}));
+        visitor.visit_unit().map_err(|e: TraceError| {
+            TraceError(format!(
+                "{} (types read with deserialize_any, such as untagged or internally tagged \
+                 enums and flattened fields, cannot be traced)",
+                e
+            ))
+        })
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+        self.primitive(json!({ "type": "boolean" }));
+        visitor.visit_bool(false)
+    }
+
+    trace_integer!(
+        deserialize_i8,
+        visit_i8,
+        i8,
+        json!({ "type": "integer", "minimum": i8::MIN, "maximum": i8::MAX })
+    );
+    trace_integer!(
+        deserialize_i16,
+        visit_i16,
+        i16,
+        json!({ "type": "integer", "minimum": i16::MIN, "maximum": i16::MAX })
+    );
+    trace_integer!(
+        deserialize_i32,
+        visit_i32,
+        i32,
+        json!({ "type": "integer", "minimum": i32::MIN, "maximum": i32::MAX })
+    );
+    trace_integer!(
+        deserialize_i64,
+        visit_i64,
+        i64,
+        json!({ "type": "integer" })
+    );
+    trace_integer!(
+        deserialize_i128,
+        visit_i128,
+        i128,
+        json!({ "type": "integer" })
+    );
+    trace_integer!(
+        deserialize_u8,
+        visit_u8,
+        u8,
+        json!({ "type": "integer", "minimum": 0, "maximum": u8::MAX })
+    );
+    trace_integer!(
+        deserialize_u16,
+        visit_u16,
+        u16,
+        json!({ "type": "integer", "minimum": 0, "maximum": u16::MAX })
+    );
+    trace_integer!(
+        deserialize_u32,
+        visit_u32,
+        u32,
+        json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX })
+    );
+    trace_integer!(
+        deserialize_u64,
+        visit_u64,
+        u64,
+        json!({ "type": "integer", "minimum": 0 })
+    );
+    trace_integer!(
+        deserialize_u128,
+        visit_u128,
+        u128,
+        json!({ "type": "integer", "minimum": 0 })
+    );
+
+    fn deserialize_f32<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+        self.primitive(json!({ "type": "number" }));
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+        self.primitive(json!({ "type": "number" }));
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+        self.primitive(json!({ "type": "string", "minLength": 1, "maxLength": 1 }));
+        visitor.visit_char('a')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+        self.primitive(json!({ "type": "string" }));
+        visitor.visit_str("")
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+        self.primitive(json!({
+            "type": "array",
+            "items": { "type": "integer", "minimum": 0, "maximum": 255 },
+        }));
+        visitor.visit_bytes(&[])
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.slot.optional = true;
+        if let Some(content) = self.trace.cuts.get(&self.path) {
+            self.slot.schema = nullable(content.clone());
+            return visitor.visit_none();
+        }
+        self.trace.open.push(self.path.clone());
+        let mut slot = Slot::default();
+        let path = format!("{}?", self.path);
+        let result = visitor.visit_some(Tracer::child(self.trace, path, &mut slot));
+        self.trace.open.pop();
+        if result.is_err() && self.trace.ends_recursion(&self.path) {
+            self.trace
+                .cuts
+                .insert(self.path.clone(), slot.schema.clone());
+        }
+        self.slot.schema = nullable(slot.schema);
+        result
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, TraceError> {
+        self.primitive(json!({ "type": "null" }));
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.enter(name)?;
+        let path = self.path.clone();
+        let result = visitor.visit_newtype_struct(Tracer::child(self.trace, path, self.slot));
+        self.slot.optional = false;
+        self.leave(result)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        if let Some(content) = self.trace.cuts.get(&self.path) {
+            self.slot.schema = json!({ "type": "array", "items": content });
+            return visitor.visit_seq(Elements {
+                trace: self.trace,
+                paths: Vec::new(),
+                schemas: &mut Vec::new(),
+            });
+        }
+        self.trace.open.push(self.path.clone());
+        let mut schemas = Vec::new();
+        let result = visitor.visit_seq(Elements {
+            trace: self.trace,
+            paths: vec![format!("{}[]", self.path)],
+            schemas: &mut schemas,
+        });
+        self.trace.open.pop();
+        let items = schemas.pop().unwrap_or_else(|| json!({}));
+        if result.is_err() && self.trace.ends_recursion(&self.path) {
+            self.trace.cuts.insert(self.path.clone(), items.clone());
+        }
+        self.slot.schema = json!({ "type": "array", "items": items });
+        result
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.elements(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.enter(name)?;
+        let result = self.elements(len, visitor);
+        self.leave(result)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        if let Some(content) = self.trace.cuts.get(&self.path) {
+            self.slot.schema = json!({ "type": "object", "additionalProperties": content });
+            return visitor.visit_map(Entries {
+                trace: self.trace,
+                path: None,
+                value: &mut Slot::default(),
+            });
+        }
+        self.trace.open.push(self.path.clone());
+        let mut value = Slot {
+            schema: json!({}),
+            optional: false,
+        };
+        let result = visitor.visit_map(Entries {
+            trace: self.trace,
+            path: Some(self.path.clone()),
+            value: &mut value,
+        });
+        self.trace.open.pop();
+        if result.is_err() && self.trace.ends_recursion(&self.path) {
+            self.trace
+                .cuts
+                .insert(self.path.clone(), value.schema.clone());
+        }
+        self.slot.schema = json!({ "type": "object", "additionalProperties": value.schema });
+        result
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.enter(name)?;
+        let result = self.fields(fields, visitor);
+        self.leave(result)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let occurrences: Vec<usize> = self
+            .trace
+            .active
+            .iter()
+            .filter(|(n, _)| *n == name)
+            .map(|&(_, depth)| depth)
+            .collect();
+        match occurrences.as_slice() {
+            // The enum itself: trace the variant chosen for this pass, and refer to the
+            // variants traced at this path until the schema is assembled.
+            [] => {
+                self.enter(name)?;
+                let trace = self
+                    .trace
+                    .enums
+                    .entry(self.path.clone())
+                    .or_insert_with(|| EnumTrace {
+                        variants,
+                        chosen: 0,
+                        schemas: vec![None; variants.len()],
+                        recursive: vec![false; variants.len()],
+                    });
+                let index = trace.chosen;
+                let mut slot = Slot::default();
+                let result = self.variant(index, variants, visitor, &mut slot);
+                // A variant cut short by a recursion inside it is traced again.
+                let recursive = result.is_err() && self.trace.ends_recursion(&self.path);
+                let trace = self.trace.enums.get_mut(&self.path).unwrap();
+                if result.is_ok() || recursive {
+                    trace.schemas[index] = Some(slot.schema);
+                    trace.recursive[index] = recursive;
+                }
+                let placeholder = json!({ "$enum": self.path });
+                self.trace.defs.entry(name).or_insert(placeholder.clone());
+                self.slot.schema = placeholder;
+                self.trace.active.pop();
+                result
+            }
+            // A recursive occurrence: refer to the enum and trace any variant that does
+            // not recurse again.
+            [_] => {
+                let index = self.trace.fallbacks.get(&self.path).copied().unwrap_or(0);
+                if index >= variants.len() {
+                    return Err(TraceError(format!(
+                        "every variant of {} contains {} itself",
+                        name, name
+                    )));
+                }
+                self.trace.active.push((name, self.trace.open.len()));
+                let result = self.variant(index, variants, visitor, &mut Slot::default());
+                self.trace.active.pop();
+                if result.is_err() && self.trace.ends_recursion(&self.path) {
+                    self.trace.fallbacks.insert(self.path.clone(), index + 1);
+                }
+                self.slot.schema = json!({ "$ref": format!("#/$defs/{}", name) });
+                self.trace.referenced.insert(name);
+                result
+            }
+            [.., depth] => Err(self.recursion(name, *depth)),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        mut self,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.primitive(json!({}));
+        visitor.visit_unit()
+    }
+}
+
+impl Tracer<'_> {
+    /// Traces one variant of an enum as an open point, so that a recursion inside it
+    /// ends there.
+    fn variant<'de, V: Visitor<'de>>(
+        &mut self,
+        index: usize,
+        variants: &'static [&'static str],
+        visitor: V,
+        slot: &mut Slot,
+    ) -> Result<V::Value, TraceError> {
+        self.trace.open.push(self.path.clone());
+        let result = visitor.visit_enum(Variant {
+            trace: self.trace,
+            path: self.path.clone(),
+            name: variants[index],
+            slot,
+        });
+        self.trace.open.pop();
+        result
+    }
+}
+
+/// Allows `null` besides a schema.
+fn nullable(schema: Value) -> Value {
+    match schema {
+        Value::Object(mut map)
+            if map.get("type").is_some_and(Value::is_string)
+                && !map.contains_key("enum")
+                && !map.contains_key("const") =>
+        {
+            let kind = map.remove("type").unwrap();
+            map.insert("type".to_string(), json!([kind, "null"]));
+            Value::Object(map)
+        }
+        schema => json!({ "anyOf": [schema, { "type": "null" }] }),
+    }
+}
+
+/// Hands a struct visitor each field once, recording its schema.
+struct Fields<'a> {
+    trace: &'a mut Trace,
+    path: &'a str,
+    fields: &'static [&'static str],
+    index: usize,
+    properties: &'a mut Map<String, Value>,
+    required: &'a mut Vec<&'static str>,
+}
+
+impl<'de> MapAccess<'de> for Fields<'_> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, TraceError> {
+        match self.fields.get(self.index) {
+            Some(&field) => seed.deserialize(field.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, TraceError> {
+        let field = self.fields[self.index];
+        self.index += 1;
+        let mut slot = Slot::default();
+        let path = format!("{}.{}", self.path, field);
+        let result = seed.deserialize(Tracer::child(self.trace, path, &mut slot));
+        self.properties.insert(field.to_string(), slot.schema);
+        if !slot.optional {
+            self.required.push(field);
+        }
+        result
+    }
+}
+
+/// Hands a sequence visitor one element per path, recording their schemas.
+struct Elements<'a> {
+    trace: &'a mut Trace,
+    paths: Vec<String>,
+    schemas: &'a mut Vec<Value>,
+}
+
+impl<'de> SeqAccess<'de> for Elements<'_> {
+    type Error = TraceError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, TraceError> {
+        let Some(path) = self.paths.get(self.schemas.len()).cloned() else {
+            return Ok(None);
+        };
+        let mut slot = Slot::default();
+        let result = seed.deserialize(Tracer::child(self.trace, path, &mut slot));
+        self.schemas.push(slot.schema);
+        result.map(Some)
+    }
+}
+
+/// Hands a map visitor one entry, recording the schema of its value; no entry for a
+/// map that ends a recursion.
+struct Entries<'a> {
+    trace: &'a mut Trace,
+    path: Option<String>,
+    value: &'a mut Slot,
+}
+
+impl<'de> MapAccess<'de> for Entries<'_> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, TraceError> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        let path = format!("{}{{key}}", path);
+        seed.deserialize(Tracer::child(self.trace, path, &mut Slot::default()))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, TraceError> {
+        let path = format!("{}{{}}", self.path.take().unwrap_or_default());
+        seed.deserialize(Tracer::child(self.trace, path, self.value))
+    }
+}
+
+/// Hands an enum visitor one variant, recording its schema as a single-key object, or
+/// a constant for a unit variant.
+struct Variant<'a> {
+    trace: &'a mut Trace,
+    path: String,
+    name: &'static str,
+    slot: &'a mut Slot,
+}
+
+impl Variant<'_> {
+    /// Creates a tracer for the content of the variant.
+    fn tracer<'b>(&'b mut self, slot: &'b mut Slot) -> Tracer<'b> {
+        let path = format!("{}<{}>", self.path, self.name);
+        Tracer::child(self.trace, path, slot)
+    }
+
+    /// Records the variant as an object with its name as the only key.
+    fn wrap(&mut self, content: Value) {
+        self.slot.schema = json!({
+            "type": "object",
+            "properties": { self.name: content },
+            "required": [self.name],
+            "additionalProperties": false,
+        });
+    }
+}
+
+impl<'de> EnumAccess<'de> for Variant<'_> {
+    type Error = TraceError;
+    type Variant = Self;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Self), TraceError> {
+        let value = seed.deserialize(self.name.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Variant<'_> {
+    type Error = TraceError;
+
+    fn unit_variant(self) -> Result<(), TraceError> {
+        self.slot.schema = json!({ "const": self.name });
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        mut self,
+        seed: T,
+    ) -> Result<T::Value, TraceError> {
+        let mut slot = Slot::default();
+        let result = seed.deserialize(self.tracer(&mut slot));
+        self.wrap(slot.schema);
+        result
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut slot = Slot::default();
+        let result = self.tracer(&mut slot).elements(len, visitor);
+        self.wrap(slot.schema);
+        result
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut slot = Slot::default();
+        let result = self.tracer(&mut slot).fields(fields, visitor);
+        self.wrap(slot.schema);
+        result
+    }
+}
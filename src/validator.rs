@@ -0,0 +1,709 @@
+use crate::cancel;
+use crate::parser::get_by_pointer;
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// A single JSON Schema violation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    /// JSON Pointer (RFC 6901) of the offending value; empty for the document root.
+    pub pointer: String,
+    /// The schema keyword that failed, e.g. `required` or `maximum`.
+    pub keyword: String,
+    /// What the schema requires, e.g. `string` or `at most 3 items`.
+    pub expected: String,
+    /// What the document contains, e.g. `number` or `5 items`.
+    pub actual: String,
+    pub message: String,
+}
+
+/// Every violation found while validating a document.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ValidationReport {
+    pub violations: Vec<SchemaViolation>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no violations were found.
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Converts the report to JSON.
+    pub fn to_json(&self) -> Value {
+        let violations: Vec<Value> = self
+            .violations
+            .iter()
+            .map(|v| {
+                json!({
+                    "path": v.pointer,
+                    "keyword": v.keyword,
+                    "expected": v.expected,
+                    "actual": v.actual,
+                })
+            })
+            .collect();
+        json!({ "valid": self.is_valid(), "violations": violations })
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, v) in self.violations.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            let path = if v.pointer.is_empty() {
+                "/"
+            } else {
+                &v.pointer
+            };
+            write!(
+                f,
+                "{}: expected {}, found {} ({})",
+                path, v.expected, v.actual, v.keyword
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationReport {}
+
+/// Validates a document against either a JSON Schema or a key template.
+///
+/// JSON Schemas (see `is_json_schema`) are checked with `validate_schema`. Any other
+/// schema is treated as the key template used by `validate_json_schema`, and every
+/// document key missing from the template is reported.
+///
+/// # Arguments
+///
+/// * `json` - The JSON document to validate.
+/// * `schema` - The JSON Schema or key template.
+///
+/// # Returns
+///
+/// * `ValidationReport` - Every violation found.
+pub fn validate_document(json: &Value, schema: &Value) -> ValidationReport {
+    validate_document_at(json, schema, Path::new("."))
+}
+
+/// Like `validate_document`, resolving relative `$ref` files against `base_dir`,
+/// usually the directory of the schema file.
+pub fn validate_document_at(json: &Value, schema: &Value, base_dir: &Path) -> ValidationReport {
+    if is_json_schema(schema) {
+        return ValidationReport {
+            violations: validate_schema_at(json, schema, base_dir),
+        };
+    }
+    let mut violations = Vec::new();
+    match (json, schema) {
+        (Value::Object(map), Value::Object(template)) => {
+            for key in map.keys().filter(|key| !template.contains_key(*key)) {
+                push(
+                    &mut violations,
+                    &child_pointer("", key),
+                    "template",
+                    "a key declared in the schema".to_string(),
+                    format!("undeclared key '{}'", key),
+                    format!("Key '{}' is not declared in the schema", key),
+                );
+            }
+        }
+        (Value::Object(_), other) => push(
+            &mut violations,
+            "",
+            "template",
+            "an object schema".to_string(),
+            type_name(other).to_string(),
+            "The schema must be an object".to_string(),
+        ),
+        (other, _) => push(
+            &mut violations,
+            "",
+            "type",
+            "object".to_string(),
+            type_name(other).to_string(),
+            format!("Expected object, found {}", type_name(other)),
+        ),
+    }
+    ValidationReport { violations }
+}
+
+/// Validates a JSON document against a JSON Schema (draft 2020-12).
+///
+/// Supports boolean schemas and the keywords `type`, `enum`, `const`, `properties`,
+/// `required`, `additionalProperties`, `items`, `prefixItems`, `minItems`, `maxItems`,
+/// `uniqueItems`, `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`,
+/// `multipleOf`, `minLength`, `maxLength`, `pattern`, `allOf`, `anyOf`, `oneOf`, `not`,
+/// and `$ref`. Unknown keywords are ignored, as the specification requires.
+///
+/// `$ref` accepts fragments into the current schema document (`#/definitions/address`,
+/// `#/$defs/id`) and relative file references (`common.json`, `common.json#/$defs/id`),
+/// which are resolved against the current directory; see `validate_schema_at`.
+/// References inside a loaded file resolve relative to that file.
+///
+/// # Arguments
+///
+/// * `json` - The JSON document to validate.
+/// * `schema` - The JSON Schema.
+///
+/// # Returns
+///
+/// * `Vec<SchemaViolation>` - Every violation found; empty if the document is valid.
+pub fn validate_schema(json: &Value, schema: &Value) -> Vec<SchemaViolation> {
+    validate_schema_at(json, schema, Path::new("."))
+}
+
+/// Like `validate_schema`, resolving relative `$ref` files against `base_dir`,
+/// usually the directory of the schema file.
+pub fn validate_schema_at(json: &Value, schema: &Value, base_dir: &Path) -> Vec<SchemaViolation> {
+    let files = RefCell::new(HashMap::new());
+    let active = RefCell::new(HashSet::new());
+    let context = Context {
+        document: schema,
+        dir: base_dir,
+        files: &files,
+        active: &active,
+    };
+    let mut violations = Vec::new();
+    validate_node(json, schema, "", &context, &mut violations);
+    violations
+}
+
+/// Where `$ref`s are resolved from while validating.
+struct Context<'a> {
+    /// The schema document `#` fragments point into.
+    document: &'a Value,
+    /// The directory relative file references are resolved against.
+    dir: &'a Path,
+    /// Schema files loaded so far, by path.
+    files: &'a RefCell<HashMap<PathBuf, Rc<Value>>>,
+    /// References being expanded at each instance pointer, to stop reference cycles.
+    active: &'a RefCell<HashSet<String>>,
+}
+
+/// Validates a value against the target of a `$ref`.
+fn validate_ref(
+    value: &Value,
+    reference: &str,
+    pointer: &str,
+    context: &Context,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let (file, fragment) = reference.split_once('#').unwrap_or((reference, ""));
+    let loaded;
+    let dir;
+    let (document, dir, id) = if file.is_empty() {
+        let id = format!("{}#{}", context.dir.display(), fragment);
+        (context.document, context.dir, id)
+    } else {
+        let path = context.dir.join(file);
+        loaded = match load_schema_file(&path, context.files) {
+            Ok(document) => document,
+            Err(message) => {
+                push(
+                    violations,
+                    pointer,
+                    "$ref",
+                    "a resolvable reference".to_string(),
+                    reference.to_string(),
+                    message,
+                );
+                return;
+            }
+        };
+        dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let id = format!("{}#{}", path.display(), fragment);
+        (loaded.as_ref(), dir.as_path(), id)
+    };
+    let Some(target) = get_by_pointer(document, fragment) else {
+        push(
+            violations,
+            pointer,
+            "$ref",
+            "a resolvable reference".to_string(),
+            reference.to_string(),
+            format!("Cannot resolve $ref '{}'", reference),
+        );
+        return;
+    };
+
+    let key = format!("{} @ {}", id, pointer);
+    if !context.active.borrow_mut().insert(key.clone()) {
+        push(
+            violations,
+            pointer,
+            "$ref",
+            "a non-circular reference".to_string(),
+            reference.to_string(),
+            format!("Circular $ref '{}'", reference),
+        );
+        return;
+    }
+    let inner = Context {
+        document,
+        dir,
+        files: context.files,
+        active: context.active,
+    };
+    validate_node(value, &target, pointer, &inner, violations);
+    context.active.borrow_mut().remove(&key);
+}
+
+/// Loads a schema file once per validation run.
+fn load_schema_file(
+    path: &Path,
+    files: &RefCell<HashMap<PathBuf, Rc<Value>>>,
+) -> Result<Rc<Value>, String> {
+    if let Some(document) = files.borrow().get(path) {
+        return Ok(Rc::clone(document));
+    }
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read schema file {}: {}", path.display(), e))?;
+    let document: Value = serde_json::from_str(&text)
+        .map_err(|e| format!("Invalid JSON in schema file {}: {}", path.display(), e))?;
+    let document = Rc::new(document);
+    files
+        .borrow_mut()
+        .insert(path.to_path_buf(), Rc::clone(&document));
+    Ok(document)
+}
+
+/// Returns `true` if a value looks like a JSON Schema rather than a key template.
+///
+/// The crate's original `validate_json_schema` compares documents against a template
+/// object with the same keys, so schemas are recognized by `$schema`, a string or array
+/// `type`, a `properties` object, or a `$ref`.
+pub fn is_json_schema(schema: &Value) -> bool {
+    match schema {
+        Value::Bool(_) => true,
+        Value::Object(map) => {
+            map.contains_key("$schema")
+                || map
+                    .get("type")
+                    .is_some_and(|t| t.is_string() || t.is_array())
+                || map.get("properties").is_some_and(Value::is_object)
+                || map.get("$ref").is_some_and(Value::is_string)
+        }
+        _ => false,
+    }
+}
+
+/// Recursive helper for `validate_schema`.
+fn validate_node(
+    value: &Value,
+    schema: &Value,
+    pointer: &str,
+    context: &Context,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    if cancel::is_abandoned() {
+        return;
+    }
+    let keywords = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            push(
+                violations,
+                pointer,
+                "false",
+                "no value".to_string(),
+                value.to_string(),
+                "No value is allowed here".to_string(),
+            );
+            return;
+        }
+        Value::Object(keywords) => keywords,
+        _ => return,
+    };
+
+    if let Some(reference) = keywords.get("$ref").and_then(Value::as_str) {
+        validate_ref(value, reference, pointer, context, violations);
+    }
+
+    if let Some(expected) = keywords.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| has_type(value, name)) {
+            push(
+                violations,
+                pointer,
+                "type",
+                allowed.join(" or "),
+                type_name(value).to_string(),
+                format!(
+                    "Expected {}, found {}",
+                    allowed.join(" or "),
+                    type_name(value)
+                ),
+            );
+        }
+    }
+    if let Some(Value::Array(options)) = keywords.get("enum") {
+        if !options.contains(value) {
+            push(
+                violations,
+                pointer,
+                "enum",
+                format!("one of {}", Value::Array(options.clone())),
+                value.to_string(),
+                format!("{} is not one of {}", value, Value::Array(options.clone())),
+            );
+        }
+    }
+    if let Some(expected) = keywords.get("const") {
+        if value != expected {
+            push(
+                violations,
+                pointer,
+                "const",
+                expected.to_string(),
+                value.to_string(),
+                format!("Expected {}, found {}", expected, value),
+            );
+        }
+    }
+
+    match value {
+        Value::Object(map) => validate_object(map, keywords, pointer, context, violations),
+        Value::Array(items) => validate_array(items, keywords, pointer, context, violations),
+        Value::String(s) => validate_string(s, keywords, pointer, violations),
+        Value::Number(_) => validate_number(value, keywords, pointer, violations),
+        _ => {}
+    }
+
+    if let Some(Value::Array(schemas)) = keywords.get("allOf") {
+        for subschema in schemas {
+            validate_node(value, subschema, pointer, context, violations);
+        }
+    }
+    if let Some(Value::Array(schemas)) = keywords.get("anyOf") {
+        if !schemas
+            .iter()
+            .any(|subschema| is_valid(value, subschema, pointer, context))
+        {
+            push(
+                violations,
+                pointer,
+                "anyOf",
+                "a match for at least one anyOf schema".to_string(),
+                "no match".to_string(),
+                "Value does not match any of the anyOf schemas".to_string(),
+            );
+        }
+    }
+    if let Some(Value::Array(schemas)) = keywords.get("oneOf") {
+        let matching = schemas
+            .iter()
+            .filter(|subschema| is_valid(value, subschema, pointer, context))
+            .count();
+        if matching != 1 {
+            push(
+                violations,
+                pointer,
+                "oneOf",
+                "a match for exactly 1 oneOf schema".to_string(),
+                format!("{} matches", matching),
+                format!(
+                    "Value matches {} of the oneOf schemas, expected exactly 1",
+                    matching
+                ),
+            );
+        }
+    }
+    if let Some(subschema) = keywords.get("not") {
+        if is_valid(value, subschema, pointer, context) {
+            push(
+                violations,
+                pointer,
+                "not",
+                "a value not matching the 'not' schema".to_string(),
+                value.to_string(),
+                "Value must not match the 'not' schema".to_string(),
+            );
+        }
+    }
+}
+
+/// Validates object keywords.
+fn validate_object(
+    map: &Map<String, Value>,
+    keywords: &Map<String, Value>,
+    pointer: &str,
+    context: &Context,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    if let Some(Value::Array(required)) = keywords.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !map.contains_key(name) {
+                push(
+                    violations,
+                    pointer,
+                    "required",
+                    format!("property '{}'", name),
+                    "missing".to_string(),
+                    format!("Missing required property '{}'", name),
+                );
+            }
+        }
+    }
+    let properties = keywords.get("properties").and_then(Value::as_object);
+    for (key, child) in map {
+        let property_pointer = child_pointer(pointer, key);
+        match properties.and_then(|properties| properties.get(key)) {
+            Some(subschema) => {
+                validate_node(child, subschema, &property_pointer, context, violations)
+            }
+            None => match keywords.get("additionalProperties") {
+                Some(Value::Bool(false)) => push(
+                    violations,
+                    &property_pointer,
+                    "additionalProperties",
+                    "no additional properties".to_string(),
+                    format!("property '{}'", key),
+                    format!("Property '{}' is not allowed", key),
+                ),
+                Some(subschema) => {
+                    validate_node(child, subschema, &property_pointer, context, violations)
+                }
+                None => {}
+            },
+        }
+    }
+}
+
+/// Validates array keywords.
+fn validate_array(
+    items: &[Value],
+    keywords: &Map<String, Value>,
+    pointer: &str,
+    context: &Context,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let prefix = keywords
+        .get("prefixItems")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+    for (index, item) in items.iter().enumerate() {
+        let item_pointer = child_pointer(pointer, &index.to_string());
+        match prefix.get(index).or_else(|| keywords.get("items")) {
+            Some(Value::Bool(false)) if index >= prefix.len() => push(
+                violations,
+                &item_pointer,
+                "items",
+                "no items beyond the prefix".to_string(),
+                item.to_string(),
+                "No items are allowed beyond the prefix".to_string(),
+            ),
+            Some(subschema) => validate_node(item, subschema, &item_pointer, context, violations),
+            None => {}
+        }
+    }
+    if let Some(min) = keywords.get("minItems").and_then(Value::as_u64) {
+        if (items.len() as u64) < min {
+            push(
+                violations,
+                pointer,
+                "minItems",
+                format!("at least {} items", min),
+                format!("{} items", items.len()),
+                format!("Expected at least {} items, found {}", min, items.len()),
+            );
+        }
+    }
+    if let Some(max) = keywords.get("maxItems").and_then(Value::as_u64) {
+        if items.len() as u64 > max {
+            push(
+                violations,
+                pointer,
+                "maxItems",
+                format!("at most {} items", max),
+                format!("{} items", items.len()),
+                format!("Expected at most {} items, found {}", max, items.len()),
+            );
+        }
+    }
+    if keywords.get("uniqueItems") == Some(&Value::Bool(true)) {
+        for (index, item) in items.iter().enumerate() {
+            if items[..index].contains(item) {
+                push(
+                    violations,
+                    &child_pointer(pointer, &index.to_string()),
+                    "uniqueItems",
+                    "unique items".to_string(),
+                    format!("duplicate {}", item),
+                    format!("Duplicate item {}", item),
+                );
+            }
+        }
+    }
+}
+
+/// Validates string keywords.
+fn validate_string(
+    s: &str,
+    keywords: &Map<String, Value>,
+    pointer: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let length = s.chars().count() as u64;
+    if let Some(min) = keywords.get("minLength").and_then(Value::as_u64) {
+        if length < min {
+            push(
+                violations,
+                pointer,
+                "minLength",
+                format!("at least {} characters", min),
+                format!("{} characters", length),
+                format!("Expected at least {} characters, found {}", min, length),
+            );
+        }
+    }
+    if let Some(max) = keywords.get("maxLength").and_then(Value::as_u64) {
+        if length > max {
+            push(
+                violations,
+                pointer,
+                "maxLength",
+                format!("at most {} characters", max),
+                format!("{} characters", length),
+                format!("Expected at most {} characters, found {}", max, length),
+            );
+        }
+    }
+    if let Some(pattern) = keywords.get("pattern").and_then(Value::as_str) {
+        match Regex::new(pattern) {
+            Ok(regex) if regex.is_match(s) => {}
+            Ok(_) => push(
+                violations,
+                pointer,
+                "pattern",
+                format!("a string matching '{}'", pattern),
+                Value::String(s.to_string()).to_string(),
+                format!("'{}' does not match pattern '{}'", s, pattern),
+            ),
+            Err(e) => push(
+                violations,
+                pointer,
+                "pattern",
+                "a valid regular expression".to_string(),
+                pattern.to_string(),
+                format!("Invalid pattern '{}': {}", pattern, e),
+            ),
+        }
+    }
+}
+
+/// Validates numeric keywords.
+fn validate_number(
+    value: &Value,
+    keywords: &Map<String, Value>,
+    pointer: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let Some(n) = value.as_f64() else {
+        return;
+    };
+    let bound = |keyword: &str| keywords.get(keyword).and_then(Value::as_f64);
+    type Check = (&'static str, fn(f64, f64) -> bool, &'static str);
+    let checks: [Check; 4] = [
+        ("minimum", |n, b| n >= b, ">="),
+        ("maximum", |n, b| n <= b, "<="),
+        ("exclusiveMinimum", |n, b| n > b, ">"),
+        ("exclusiveMaximum", |n, b| n < b, "<"),
+    ];
+    for (keyword, check, symbol) in checks {
+        if let Some(b) = bound(keyword) {
+            if !check(n, b) {
+                push(
+                    violations,
+                    pointer,
+                    keyword,
+                    format!("a value {} {}", symbol, b),
+                    value.to_string(),
+                    format!("Expected a value {} {}, found {}", symbol, b, value),
+                );
+            }
+        }
+    }
+    if let Some(divisor) = bound("multipleOf").filter(|d| *d > 0.0) {
+        let quotient = n / divisor;
+        if (quotient - quotient.round()).abs() > 1e-9 {
+            push(
+                violations,
+                pointer,
+                "multipleOf",
+                format!("a multiple of {}", divisor),
+                value.to_string(),
+                format!("{} is not a multiple of {}", value, divisor),
+            );
+        }
+    }
+}
+
+/// Returns `true` if the value has no violations against the schema.
+fn is_valid(value: &Value, schema: &Value, pointer: &str, context: &Context) -> bool {
+    let mut violations = Vec::new();
+    validate_node(value, schema, pointer, context, &mut violations);
+    violations.is_empty()
+}
+
+/// Checks a value against a JSON Schema type name.
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value
+            .as_f64()
+            .is_some_and(|n| value.is_i64() || value.is_u64() || n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+/// Returns the JSON Schema type name of a value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Appends an escaped reference token to a JSON Pointer.
+fn child_pointer(pointer: &str, token: &str) -> String {
+    format!(
+        "{}/{}",
+        pointer,
+        token.replace('~', "~0").replace('/', "~1")
+    )
+}
+
+/// Records a violation.
+fn push(
+    violations: &mut Vec<SchemaViolation>,
+    pointer: &str,
+    keyword: &str,
+    expected: String,
+    actual: String,
+    message: String,
+) {
+    violations.push(SchemaViolation {
+        pointer: pointer.to_string(),
+        keyword: keyword.to_string(),
+        expected,
+        actual,
+        message,
+    });
+}
@@ -0,0 +1,133 @@
+use serde_json::{json, Value};
+use std::cell::RefCell;
+
+/// A non-fatal issue of a call that succeeded, such as a duplicate key of which the
+/// last value was kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Path of the value concerned, e.g. `server.port`, or empty when the message
+    /// gives the position instead, as for parse warnings and skipped NDJSON lines.
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Warnings collected by `Warnings::collect`, so that callers can show non-fatal
+/// issues without parsing log output. They are also logged at warn level.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Warnings {
+    entries: Vec<Warning>,
+}
+
+thread_local! {
+    static COLLECTORS: RefCell<Vec<Vec<Warning>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Removes a collector from this thread when its call ends, even by a panic.
+struct CollectorGuard;
+
+impl Drop for CollectorGuard {
+    fn drop(&mut self) {
+        COLLECTORS.with(|collectors| collectors.borrow_mut().pop());
+    }
+}
+
+impl Warnings {
+    /// Runs library calls and collects their warnings, e.g.
+    /// `let (value, warnings) = Warnings::collect(|| parse_json5(&text));`.
+    ///
+    /// Warnings include duplicate keys (the last value is kept), JSON5 `NaN` and
+    /// `Infinity` replaced by `null`, integers too large for TOML written as floats,
+    /// and skipped lines and records of NDJSON files. Nested calls collect their own
+    /// warnings, which the outer call does not see.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The calls to run.
+    ///
+    /// # Returns
+    ///
+    /// * `(T, Warnings)` - The result of `operation` and the warnings in the order they
+    ///   were found.
+    pub fn collect<T>(operation: impl FnOnce() -> T) -> (T, Warnings) {
+        COLLECTORS.with(|collectors| collectors.borrow_mut().push(Vec::new()));
+        let guard = CollectorGuard;
+        let result = operation();
+        let entries = COLLECTORS.with(|collectors| {
+            collectors
+                .borrow_mut()
+                .last_mut()
+                .map(std::mem::take)
+                .unwrap_or_default()
+        });
+        drop(guard);
+        (result, Warnings { entries })
+    }
+
+    /// Returns the collected warnings in the order they were found.
+    pub fn iter(&self) -> std::slice::Iter<'_, Warning> {
+        self.entries.iter()
+    }
+
+    /// Returns the number of collected warnings.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if nothing was collected.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the warnings as `[{"path": "...", "message": "..."}]`.
+    pub fn to_json(&self) -> Value {
+        self.entries
+            .iter()
+            .map(|warning| json!({ "path": warning.path, "message": warning.message }))
+            .collect()
+    }
+}
+
+impl IntoIterator for Warnings {
+    type Item = Warning;
+    type IntoIter = std::vec::IntoIter<Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Warnings {
+    type Item = &'a Warning;
+    type IntoIter = std::slice::Iter<'a, Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+/// Logs a warning and adds it to the innermost `Warnings::collect` on this thread, if any.
+pub(crate) fn warn(path: &str, message: String) {
+    if path.is_empty() {
+        log::warn!("{}", message);
+    } else {
+        log::warn!("{}: {}", path, message);
+    }
+    COLLECTORS.with(|collectors| {
+        if let Some(entries) = collectors.borrow_mut().last_mut() {
+            entries.push(Warning {
+                path: path.to_string(),
+                message,
+            });
+        }
+    });
+}
@@ -0,0 +1,627 @@
+use anyhow::{Error, Result};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Namespace URI of the XML Schema instance attributes (`xsi:nil`).
+pub const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// Object key holding the text content of an element that also has attributes
+/// (`{"link": {"href": "...", "#text": "..."}}`).
+pub const TEXT_KEY: &str = "#text";
+
+/// Prefix of object keys written as attributes (`{"link": {"@href": "..."}}`), as
+/// produced by `xml_to_json`.
+pub const ATTRIBUTE_PREFIX: char = '@';
+
+/// Element name of the items of a root array when `XmlOptions::item_name` is not set.
+const ROOT_ITEM_NAME: &str = "item";
+
+/// How a JSON value is represented in XML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XmlNodeKind {
+    /// A child element (the default).
+    Element,
+    /// An attribute of the parent element; only valid for scalars.
+    Attribute,
+    /// A child element whose text is wrapped in a CDATA section.
+    Cdata,
+}
+
+impl FromStr for XmlNodeKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "element" => Ok(XmlNodeKind::Element),
+            "attribute" => Ok(XmlNodeKind::Attribute),
+            "cdata" => Ok(XmlNodeKind::Cdata),
+            _ => Err(Error::msg(format!("Unsupported XML node kind: {}", s))),
+        }
+    }
+}
+
+/// Options controlling JSON to XML conversion.
+///
+/// Paths in `node_kinds` and `element_order` are dot-separated object keys below
+/// the root element (`order.customer.id`); array items share the path of their array,
+/// since they become repeated elements with the same name. The root object is `""`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlOptions {
+    /// Name of the root element; may carry a namespace prefix (`soap:Envelope`).
+    pub root: String,
+    /// Default namespace declared on the root element with `xmlns`.
+    pub default_namespace: Option<String>,
+    /// Prefix to namespace URI map, declared on the root element with `xmlns:prefix`.
+    pub namespaces: BTreeMap<String, String>,
+    /// Emits nulls as `xsi:nil="true"` instead of empty elements, declaring `xsi` if needed.
+    pub nil_nulls: bool,
+    /// Representation of the value at each path; unlisted paths become elements.
+    pub node_kinds: BTreeMap<String, XmlNodeKind>,
+    /// Child element order for the object at each path; unlisted children follow in key order.
+    pub element_order: BTreeMap<String, Vec<String>>,
+    /// Starts the document with `<?xml version="1.0" encoding="UTF-8"?>`.
+    pub declaration: bool,
+    /// Wraps each array in one element named after its key, holding an element of this
+    /// name per item (`<tags><item>a</item></tags>`). Otherwise items are repeated
+    /// elements named after the key, and the items of a root array are `<item>`
+    /// elements of the root element.
+    pub item_name: Option<String>,
+    /// Writes strings, numbers and booleans in objects as attributes of the object's
+    /// element, except at paths listed in `node_kinds`.
+    pub scalar_attributes: bool,
+}
+
+impl Default for XmlOptions {
+    fn default() -> Self {
+        XmlOptions {
+            root: "root".to_string(),
+            default_namespace: None,
+            namespaces: BTreeMap::new(),
+            nil_nulls: false,
+            node_kinds: BTreeMap::new(),
+            element_order: BTreeMap::new(),
+            declaration: false,
+            item_name: None,
+            scalar_attributes: false,
+        }
+    }
+}
+
+impl XmlOptions {
+    /// Loads per-path node kinds and element order from a mapping config.
+    ///
+    /// The config has the shape
+    /// `{ "paths": { "order.id": "attribute", "order.note": "cdata" }, "order": { "order": ["id", "items"] } }`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The mapping config as JSON.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Error>` - An error if the config has an unexpected shape.
+    pub fn apply_mapping(&mut self, config: &Value) -> Result<(), Error> {
+        if let Some(paths) = config.get("paths") {
+            let paths = paths
+                .as_object()
+                .ok_or_else(|| Error::msg("'paths' must be an object"))?;
+            for (path, kind) in paths {
+                let kind = kind.as_str().ok_or_else(|| {
+                    Error::msg(format!("Node kind of '{}' must be a string", path))
+                })?;
+                self.node_kinds.insert(path.clone(), kind.parse()?);
+            }
+        }
+        if let Some(order) = config.get("order") {
+            let order = order
+                .as_object()
+                .ok_or_else(|| Error::msg("'order' must be an object"))?;
+            for (path, names) in order {
+                let names = names
+                    .as_array()
+                    .and_then(|names| names.iter().map(|n| n.as_str().map(String::from)).collect())
+                    .ok_or_else(|| {
+                        Error::msg(format!("Order of '{}' must be an array of names", path))
+                    })?;
+                self.element_order.insert(path.clone(), names);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Converts JSON to XML with namespace support.
+///
+/// Object keys become element names and may use any prefix declared in
+/// `options.namespaces`; a key that is not a valid XML name (`a b`, `1a`, `""`) or
+/// uses an undeclared prefix is an error, since the output would not be
+/// namespace-well-formed. Keys starting with `@` become attributes and a `#text` key
+/// the text of its element, so the output of `xml_to_json` converts back. Text and
+/// attribute values are escaped, so `<`, `&` and quotes in values keep the output
+/// well-formed, and characters XML 1.0 does not allow, such as U+0001, are dropped.
+/// The document always has a single root element.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to convert.
+/// * `options` - The root element, namespace declarations, null handling, array item
+///   elements, attributes, and the XML declaration.
+///
+/// # Returns
+///
+/// * `Result<String, Error>` - The XML document, or an error if an element or attribute name
+///   is invalid or uses an undeclared prefix.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn json_to_xml(json: &Value, options: &XmlOptions) -> Result<String, Error> {
+    let mut declarations = String::new();
+    if let Some(uri) = &options.default_namespace {
+        declarations.push_str(&format!(" xmlns=\"{}\"", escape(uri.as_str())));
+    }
+    for (prefix, uri) in &namespaces(options) {
+        declarations.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape(uri.as_str())));
+    }
+
+    let mut output = Vec::new();
+    if options.declaration {
+        writeln!(output, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    }
+    for element in xml_tree(json, options)? {
+        write_element(&element, &mut output, &declarations)?;
+    }
+    String::from_utf8(output).map_err(|e| Error::msg(e.to_string()))
+}
+
+/// An XML element built from a JSON value, before serialization.
+///
+/// `json_path` is the location of the originating value (`order.items[0]`),
+/// so that problems found in the XML can be reported against the JSON input.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct XmlElement {
+    pub(crate) name: String,
+    pub(crate) json_path: String,
+    pub(crate) attributes: Vec<XmlAttribute>,
+    pub(crate) content: XmlContent,
+}
+
+/// An attribute of an `XmlElement`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct XmlAttribute {
+    pub(crate) name: String,
+    pub(crate) value: String,
+    pub(crate) json_path: String,
+}
+
+/// Content of an `XmlElement`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum XmlContent {
+    Children(Vec<XmlElement>),
+    Text(String),
+    Cdata(String),
+    /// A null emitted as `xsi:nil="true"`.
+    Nil,
+    /// A null emitted as an empty element.
+    Empty,
+}
+
+/// Returns the declared namespaces, including `xsi` when nulls are emitted as `xsi:nil`.
+fn namespaces(options: &XmlOptions) -> BTreeMap<String, String> {
+    let mut namespaces = options.namespaces.clone();
+    if options.nil_nulls {
+        namespaces
+            .entry("xsi".to_string())
+            .or_insert_with(|| XSI_NAMESPACE.to_string());
+    }
+    namespaces
+}
+
+/// Maps a JSON value to XML elements without serializing them.
+pub(crate) fn xml_tree(json: &Value, options: &XmlOptions) -> Result<Vec<XmlElement>, Error> {
+    let builder = XmlBuilder {
+        namespaces: namespaces(options),
+        options,
+    };
+    let mut elements = Vec::new();
+    builder.build(json, &options.root, "", "", &mut elements)?;
+    Ok(elements)
+}
+
+/// Recursive builder sharing the namespace declarations and options of a conversion.
+struct XmlBuilder<'a> {
+    namespaces: BTreeMap<String, String>,
+    options: &'a XmlOptions,
+}
+
+impl XmlBuilder<'_> {
+    /// Builds one element for a value (or one element per item for arrays).
+    ///
+    /// `path` is the mapping path used to look up `node_kinds` and `element_order`,
+    /// while `json_path` also records array indices.
+    fn build(
+        &self,
+        json: &Value,
+        tag_name: &str,
+        path: &str,
+        json_path: &str,
+        elements: &mut Vec<XmlElement>,
+    ) -> Result<(), Error> {
+        self.check_name(tag_name)?;
+        let content = match json {
+            Value::Object(map) => {
+                let mut attributes = Vec::new();
+                let mut children = Vec::new();
+                for (key, value) in map {
+                    let child_path = join_path(path, key);
+                    let child_json_path = join_path(json_path, key);
+                    let attribute_name = key.strip_prefix(ATTRIBUTE_PREFIX);
+                    let is_attribute = attribute_name.is_some()
+                        || match self.options.node_kinds.get(&child_path) {
+                            Some(kind) => *kind == XmlNodeKind::Attribute,
+                            None => {
+                                self.options.scalar_attributes
+                                    && key != TEXT_KEY
+                                    && matches!(
+                                        value,
+                                        Value::String(_) | Value::Number(_) | Value::Bool(_)
+                                    )
+                            }
+                        };
+                    if is_attribute {
+                        let name = attribute_name.unwrap_or(key);
+                        self.check_name(name)?;
+                        if let Some(text) = attribute_text(value, &child_path)? {
+                            attributes.push(XmlAttribute {
+                                name: name.to_string(),
+                                value: text,
+                                json_path: child_json_path,
+                            });
+                        }
+                    } else {
+                        children.push((key, value, child_path, child_json_path));
+                    }
+                }
+                if let Some(order) = self.options.element_order.get(path) {
+                    children.sort_by_key(|(key, _, _, _)| {
+                        order
+                            .iter()
+                            .position(|name| name == *key)
+                            .unwrap_or(order.len())
+                    });
+                }
+                if let Some(position) = children.iter().position(|(key, ..)| *key == TEXT_KEY) {
+                    let (_, value, child_path, _) = children.remove(position);
+                    if !children.is_empty() {
+                        return Err(Error::msg(format!(
+                            "Object at '{}' mixes '{}' with child elements",
+                            json_path, TEXT_KEY
+                        )));
+                    }
+                    let text = attribute_text(value, &child_path)?.unwrap_or_default();
+                    elements.push(XmlElement {
+                        name: tag_name.to_string(),
+                        json_path: json_path.to_string(),
+                        attributes,
+                        content: if self.node_kind(&child_path) == XmlNodeKind::Cdata {
+                            XmlContent::Cdata(text)
+                        } else {
+                            XmlContent::Text(text)
+                        },
+                    });
+                    return Ok(());
+                }
+                let mut child_elements = Vec::new();
+                for (key, value, child_path, child_json_path) in children {
+                    self.build(
+                        value,
+                        key,
+                        &child_path,
+                        &child_json_path,
+                        &mut child_elements,
+                    )?;
+                }
+                elements.push(XmlElement {
+                    name: tag_name.to_string(),
+                    json_path: json_path.to_string(),
+                    attributes,
+                    content: XmlContent::Children(child_elements),
+                });
+                return Ok(());
+            }
+            Value::Array(arr) => {
+                let is_root = path.is_empty() && json_path.is_empty();
+                let item_name = match &self.options.item_name {
+                    Some(item_name) => Some(item_name.as_str()),
+                    None if is_root => Some(ROOT_ITEM_NAME),
+                    None => None,
+                };
+                let Some(item_name) = item_name else {
+                    for (index, value) in arr.iter().enumerate() {
+                        let item_path = format!("{}[{}]", json_path, index);
+                        self.build(value, tag_name, path, &item_path, elements)?;
+                    }
+                    return Ok(());
+                };
+                let mut items = Vec::new();
+                for (index, value) in arr.iter().enumerate() {
+                    let item_path = format!("{}[{}]", json_path, index);
+                    self.build(value, item_name, path, &item_path, &mut items)?;
+                }
+                XmlContent::Children(items)
+            }
+            Value::String(s) if self.node_kind(path) == XmlNodeKind::Cdata => {
+                XmlContent::Cdata(s.clone())
+            }
+            Value::String(s) => XmlContent::Text(s.clone()),
+            Value::Number(num) => XmlContent::Text(num.to_string()),
+            Value::Bool(b) => XmlContent::Text(b.to_string()),
+            Value::Null if self.options.nil_nulls => XmlContent::Nil,
+            Value::Null => XmlContent::Empty,
+        };
+        elements.push(XmlElement {
+            name: tag_name.to_string(),
+            json_path: json_path.to_string(),
+            attributes: Vec::new(),
+            content,
+        });
+        Ok(())
+    }
+
+    /// Returns the configured representation of the value at `path`.
+    fn node_kind(&self, path: &str) -> XmlNodeKind {
+        self.options
+            .node_kinds
+            .get(path)
+            .copied()
+            .unwrap_or(XmlNodeKind::Element)
+    }
+
+    /// Ensures an element or attribute name is a valid XML name with at most one
+    /// prefix, and that the prefix is declared.
+    fn check_name(&self, tag_name: &str) -> Result<(), Error> {
+        let valid = match tag_name.split_once(':') {
+            Some((prefix, local)) => is_nc_name(prefix) && is_nc_name(local),
+            None => is_nc_name(tag_name),
+        };
+        if !valid {
+            return Err(Error::msg(format!("'{}' is not a valid XML name", tag_name)));
+        }
+        match tag_name.split_once(':') {
+            Some((prefix, _)) if prefix != "xml" && !self.namespaces.contains_key(prefix) => {
+                Err(Error::msg(format!(
+                    "Undeclared namespace prefix '{}' in element '{}'",
+                    prefix, tag_name
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the text of a value mapped to an attribute, or `None` for nulls (which are omitted).
+fn attribute_text(value: &Value, path: &str) -> Result<Option<String>, Error> {
+    match value {
+        Value::Null => Ok(None),
+        Value::String(s) => Ok(Some(s.clone())),
+        Value::Number(_) | Value::Bool(_) => Ok(Some(value.to_string())),
+        _ => Err(Error::msg(format!(
+            "Value at '{}' is not a scalar and cannot be an attribute",
+            path
+        ))),
+    }
+}
+
+/// Returns whether `name` is an XML name without a colon (an NCName).
+fn is_nc_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_name_start_char) && chars.all(is_name_char)
+}
+
+/// Returns whether `c` may start an XML name, leaving out `:`.
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        'A'..='Z' | '_' | 'a'..='z'
+        | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}')
+}
+
+/// Returns whether `c` may appear in an XML name after its first character, leaving out `:`.
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}')
+}
+
+/// Returns whether XML 1.0 allows `c` in a document.
+fn is_xml_char(c: char) -> bool {
+    matches!(c,
+        '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..)
+}
+
+/// Escapes text or an attribute value, dropping characters XML 1.0 does not allow.
+fn escape_text(text: &str) -> String {
+    if text.chars().all(is_xml_char) {
+        escape(text).into_owned()
+    } else {
+        let allowed: String = text.chars().filter(|&c| is_xml_char(c)).collect();
+        escape(allowed.as_str()).into_owned()
+    }
+}
+
+/// Joins a parent path and an object key with a dot.
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Serializes an element; `declarations` is only non-empty for root elements.
+fn write_element<W: Write>(
+    element: &XmlElement,
+    writer: &mut W,
+    declarations: &str,
+) -> Result<(), Error> {
+    let mut attributes = declarations.to_string();
+    for attribute in &element.attributes {
+        attributes.push_str(&format!(
+            " {}=\"{}\"",
+            attribute.name,
+            escape_text(&attribute.value)
+        ));
+    }
+    let name = &element.name;
+    match &element.content {
+        XmlContent::Children(children) => {
+            writeln!(writer, "<{}{}>", name, attributes)?;
+            for child in children {
+                write_element(child, writer, "")?;
+            }
+            writeln!(writer, "</{}>", name)?;
+        }
+        XmlContent::Text(text) => {
+            writeln!(
+                writer,
+                "<{0}{1}>{2}</{0}>",
+                name,
+                attributes,
+                escape_text(text)
+            )?;
+        }
+        XmlContent::Cdata(text) => {
+            // "]]>" cannot appear inside a CDATA section, so it is split across two sections.
+            let text: String = text.chars().filter(|&c| is_xml_char(c)).collect();
+            let text = text.replace("]]>", "]]]]><![CDATA[>");
+            writeln!(
+                writer,
+                "<{0}{1}><![CDATA[{2}]]></{0}>",
+                name, attributes, text
+            )?;
+        }
+        XmlContent::Nil => {
+            writeln!(writer, "<{}{} xsi:nil=\"true\" />", name, attributes)?;
+        }
+        XmlContent::Empty => {
+            writeln!(writer, "<{}{} />", name, attributes)?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts an XML document to JSON.
+///
+/// The root element becomes a single-key object. An element without attributes or
+/// child elements becomes its text (or `null` when empty); otherwise it becomes an
+/// object whose attributes are stored under `@name` keys, whose text is stored under
+/// `#text`, and whose repeated child elements are collected into arrays. Names keep
+/// their namespace prefix, and `xmlns` declarations are dropped.
+///
+/// # Arguments
+///
+/// * `xml` - The XML document.
+///
+/// # Returns
+///
+/// * `Result<Value, Error>` - The document as JSON, or an error for malformed XML.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = xml.len())))]
+pub fn xml_to_json(xml: &str) -> Result<Value, Error> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut stack: Vec<(String, Map<String, Value>, String)> = Vec::new();
+    let mut root = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => stack.push(open_element(&start)?),
+            Event::Empty(start) => {
+                let element = open_element(&start)?;
+                close_element(element, &mut stack, &mut root)?;
+            }
+            Event::End(_) => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| Error::msg("Unbalanced XML document"))?;
+                close_element(element, &mut stack, &mut root)?;
+            }
+            Event::Text(text) => {
+                if let Some((_, _, content)) = stack.last_mut() {
+                    content.push_str(&text.unescape()?);
+                }
+            }
+            Event::CData(text) => {
+                if let Some((_, _, content)) = stack.last_mut() {
+                    content.push_str(&String::from_utf8_lossy(&text));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !stack.is_empty() {
+        return Err(Error::msg("Unclosed XML element"));
+    }
+    root.ok_or_else(|| Error::msg("XML document has no root element"))
+}
+
+/// Reads the name and attributes of a start tag.
+fn open_element(start: &BytesStart) -> Result<(String, Map<String, Value>, String), Error> {
+    let mut fields = Map::new();
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        if key == "xmlns" || key.starts_with("xmlns:") {
+            continue;
+        }
+        fields.insert(
+            format!("@{}", key),
+            Value::String(attribute.unescape_value()?.into_owned()),
+        );
+    }
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    Ok((name, fields, String::new()))
+}
+
+/// Turns a finished element into JSON and attaches it to its parent (or the root).
+fn close_element(
+    (name, mut fields, text): (String, Map<String, Value>, String),
+    stack: &mut [(String, Map<String, Value>, String)],
+    root: &mut Option<Value>,
+) -> Result<(), Error> {
+    let value = if fields.is_empty() {
+        if text.is_empty() {
+            Value::Null
+        } else {
+            Value::String(text)
+        }
+    } else {
+        if !text.is_empty() {
+            fields.insert(TEXT_KEY.to_string(), Value::String(text));
+        }
+        Value::Object(fields)
+    };
+    let Some((_, parent, _)) = stack.last_mut() else {
+        if root.is_some() {
+            return Err(Error::msg("XML document has more than one root element"));
+        }
+        let mut document = Map::new();
+        document.insert(name, value);
+        *root = Some(Value::Object(document));
+        return Ok(());
+    };
+    match parent.get_mut(&name) {
+        Some(Value::Array(items)) => items.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+        None => {
+            parent.insert(name, value);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,546 @@
+use crate::xml::{xml_tree, XmlContent, XmlElement, XmlOptions};
+use anyhow::{Error, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A violation of an XSD found in the XML generated from a JSON document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XsdViolation {
+    /// Path of the originating JSON value (`order.items[1].qty`); empty for the root.
+    pub path: String,
+    pub message: String,
+}
+
+/// Converts JSON to XML with the given options and validates the result against an XSD.
+///
+/// Supports the commonly used subset of XML Schema: global and local element
+/// declarations (`type`, `ref`, `minOccurs`, `maxOccurs`, `nillable`), named and
+/// inline complex types with `sequence`, `all`, or `choice` content and attributes
+/// (`use="required"`), and simple types restricted by `enumeration`, length, and
+/// inclusive/exclusive bounds over the built-in string, numeric, boolean, and date types.
+/// `pattern` facets and imported schemas are not checked.
+///
+/// # Arguments
+///
+/// * `json` - The JSON document to convert.
+/// * `options` - The XML conversion options.
+/// * `xsd` - The XML Schema document.
+///
+/// # Returns
+///
+/// * `Result<Vec<XsdViolation>, Error>` - Every violation with its JSON path, or an error if
+///   the XSD cannot be parsed or the conversion fails.
+pub fn validate_against_xsd(
+    json: &Value,
+    options: &XmlOptions,
+    xsd: &str,
+) -> Result<Vec<XsdViolation>, Error> {
+    let root = parse_schema(xsd)?;
+    let schema = Schema::new(&root);
+    let mut violations = Vec::new();
+    for element in xml_tree(json, options)? {
+        match schema.elements.get(local_name(&element.name)) {
+            Some(declaration) => schema.validate_element(&element, declaration, &mut violations),
+            None => violations.push(violation(
+                &element.json_path,
+                format!("Element '{}' is not declared in the schema", element.name),
+            )),
+        }
+    }
+    Ok(violations)
+}
+
+/// A node of the parsed XSD document.
+#[derive(Debug, Default)]
+struct SchemaNode {
+    /// Local name, e.g. `element` for `xs:element`.
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<SchemaNode>,
+}
+
+impl SchemaNode {
+    /// Returns the value of an attribute.
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    /// Returns the first child with the given local name.
+    fn child(&self, name: &str) -> Option<&SchemaNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Returns every child with the given local name.
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SchemaNode> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+/// Parses an XSD document into a tree of `SchemaNode`s.
+fn parse_schema(xsd: &str) -> Result<SchemaNode, Error> {
+    let mut reader = Reader::from_str(xsd);
+    reader.config_mut().trim_text(true);
+    let mut stack = vec![SchemaNode::default()];
+    loop {
+        match reader.read_event()? {
+            Event::Start(start) => stack.push(schema_node(&start)?),
+            Event::Empty(start) => {
+                let node = schema_node(&start)?;
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(node);
+                }
+            }
+            Event::End(_) => {
+                let node = stack
+                    .pop()
+                    .filter(|_| !stack.is_empty())
+                    .ok_or_else(|| Error::msg("Unbalanced XSD document"))?;
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(node);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    let document = stack.pop().filter(|_| stack.is_empty());
+    document
+        .and_then(|mut document| document.children.pop())
+        .filter(|root| root.name == "schema")
+        .ok_or_else(|| Error::msg("XSD document must have an xs:schema root element"))
+}
+
+/// Builds a `SchemaNode` (without children) from a start tag.
+fn schema_node(start: &BytesStart) -> Result<SchemaNode, Error> {
+    let mut attributes = HashMap::new();
+    for attribute in start.attributes() {
+        let attribute = attribute?;
+        let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+        attributes.insert(key, attribute.unescape_value()?.into_owned());
+    }
+    Ok(SchemaNode {
+        name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+        attributes,
+        children: Vec::new(),
+    })
+}
+
+/// Strips a namespace prefix from a qualified name.
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, local)| local)
+}
+
+/// The type of an element or attribute.
+enum TypeRef<'a> {
+    Complex(&'a SchemaNode),
+    Simple(&'a SchemaNode),
+    Builtin(&'a str),
+    Any,
+}
+
+/// Global declarations of a parsed schema, indexed by local name.
+struct Schema<'a> {
+    elements: HashMap<&'a str, &'a SchemaNode>,
+    complex_types: HashMap<&'a str, &'a SchemaNode>,
+    simple_types: HashMap<&'a str, &'a SchemaNode>,
+}
+
+impl<'a> Schema<'a> {
+    /// Indexes the top-level declarations of an `xs:schema` node.
+    fn new(root: &'a SchemaNode) -> Self {
+        let index = |kind: &str| -> HashMap<&'a str, &'a SchemaNode> {
+            root.children
+                .iter()
+                .filter(|node| node.name == kind)
+                .filter_map(|node| Some((node.attr("name")?, node)))
+                .collect()
+        };
+        Schema {
+            elements: index("element"),
+            complex_types: index("complexType"),
+            simple_types: index("simpleType"),
+        }
+    }
+
+    /// Resolves the type of a declaration from its `type` attribute or inline definition.
+    fn type_of(&self, declaration: &'a SchemaNode) -> TypeRef<'a> {
+        if let Some(name) = declaration.attr("type") {
+            return self.named_type(name);
+        }
+        if let Some(complex) = declaration.child("complexType") {
+            return TypeRef::Complex(complex);
+        }
+        if let Some(simple) = declaration.child("simpleType") {
+            return TypeRef::Simple(simple);
+        }
+        TypeRef::Any
+    }
+
+    /// Resolves a type name to a user-defined or built-in type.
+    fn named_type(&self, name: &'a str) -> TypeRef<'a> {
+        let local = local_name(name);
+        if let Some(complex) = self.complex_types.get(local) {
+            TypeRef::Complex(complex)
+        } else if let Some(simple) = self.simple_types.get(local) {
+            TypeRef::Simple(simple)
+        } else if local == "anyType" {
+            TypeRef::Any
+        } else {
+            TypeRef::Builtin(local)
+        }
+    }
+
+    /// Resolves `ref` on a local element declaration to the global declaration.
+    fn resolve(&self, declaration: &'a SchemaNode) -> &'a SchemaNode {
+        declaration
+            .attr("ref")
+            .and_then(|name| self.elements.get(local_name(name)).copied())
+            .unwrap_or(declaration)
+    }
+
+    /// Validates an element against its declaration.
+    fn validate_element(
+        &self,
+        element: &XmlElement,
+        declaration: &'a SchemaNode,
+        violations: &mut Vec<XsdViolation>,
+    ) {
+        if element.content == XmlContent::Nil {
+            if declaration.attr("nillable") != Some("true") {
+                violations.push(violation(
+                    &element.json_path,
+                    format!("Element '{}' is null but not nillable", element.name),
+                ));
+            }
+            return;
+        }
+        match self.type_of(declaration) {
+            TypeRef::Any => {}
+            TypeRef::Complex(complex) => self.validate_complex(element, complex, violations),
+            simple => {
+                for attribute in &element.attributes {
+                    violations.push(violation(
+                        &attribute.json_path,
+                        format!("Unexpected attribute '{}'", attribute.name),
+                    ));
+                }
+                match &element.content {
+                    XmlContent::Text(text) | XmlContent::Cdata(text) => {
+                        if let Some(error) = self.check_simple(text, &simple) {
+                            violations.push(violation(&element.json_path, error));
+                        }
+                    }
+                    XmlContent::Empty => {
+                        if let Some(error) = self.check_simple("", &simple) {
+                            violations.push(violation(&element.json_path, error));
+                        }
+                    }
+                    XmlContent::Children(_) => violations.push(violation(
+                        &element.json_path,
+                        format!(
+                            "Element '{}' must contain text, not child elements",
+                            element.name
+                        ),
+                    )),
+                    XmlContent::Nil => {}
+                }
+            }
+        }
+    }
+
+    /// Validates the attributes and child elements of an element with a complex type.
+    fn validate_complex(
+        &self,
+        element: &XmlElement,
+        complex: &'a SchemaNode,
+        violations: &mut Vec<XsdViolation>,
+    ) {
+        let declared: Vec<&SchemaNode> = complex.children_named("attribute").collect();
+        for declaration in &declared {
+            let name = declaration.attr("name").unwrap_or_default();
+            let present = element
+                .attributes
+                .iter()
+                .any(|a| local_name(&a.name) == name);
+            if declaration.attr("use") == Some("required") && !present {
+                violations.push(violation(
+                    &element.json_path,
+                    format!("Missing required attribute '{}'", name),
+                ));
+            }
+        }
+        for attribute in &element.attributes {
+            let declaration = declared
+                .iter()
+                .find(|d| d.attr("name") == Some(local_name(&attribute.name)));
+            let error = match declaration {
+                Some(declaration) => self
+                    .check_simple(&attribute.value, &self.type_of(declaration))
+                    .map(|error| format!("Attribute '{}': {}", attribute.name, error)),
+                None => Some(format!("Unexpected attribute '{}'", attribute.name)),
+            };
+            if let Some(message) = error {
+                violations.push(violation(&attribute.json_path, message));
+            }
+        }
+
+        let children: &[XmlElement] = match &element.content {
+            XmlContent::Children(children) => children,
+            XmlContent::Text(_) | XmlContent::Cdata(_) => {
+                violations.push(violation(
+                    &element.json_path,
+                    format!(
+                        "Element '{}' must contain child elements, not text",
+                        element.name
+                    ),
+                ));
+                return;
+            }
+            XmlContent::Nil | XmlContent::Empty => &[],
+        };
+        let model = complex
+            .children
+            .iter()
+            .find(|node| matches!(node.name.as_str(), "sequence" | "all" | "choice"));
+        let Some(model) = model else {
+            for child in children {
+                violations.push(unexpected(child));
+            }
+            return;
+        };
+        let particles: Vec<&SchemaNode> = model
+            .children_named("element")
+            .map(|particle| self.resolve(particle))
+            .collect();
+
+        match model.name.as_str() {
+            "sequence" => {
+                let mut position = 0;
+                for (particle, source) in particles.iter().zip(model.children_named("element")) {
+                    let (min, max) = occurs(source);
+                    let count = children[position..]
+                        .iter()
+                        .take_while(|child| matches_particle(child, particle))
+                        .count();
+                    for child in &children[position..position + count] {
+                        self.validate_element(child, particle, violations);
+                    }
+                    if count < min {
+                        violations.push(violation(
+                            &element.json_path,
+                            format!(
+                                "Expected at least {} '{}' element(s) in '{}', found {}",
+                                min,
+                                particle_name(particle),
+                                element.name,
+                                count
+                            ),
+                        ));
+                    } else if count > max {
+                        violations.push(violation(
+                            &children[position + max].json_path,
+                            format!(
+                                "At most {} '{}' element(s) allowed",
+                                max,
+                                particle_name(particle)
+                            ),
+                        ));
+                    }
+                    position += count;
+                }
+                for child in &children[position..] {
+                    if particles
+                        .iter()
+                        .any(|particle| matches_particle(child, particle))
+                    {
+                        violations.push(violation(
+                            &child.json_path,
+                            format!(
+                                "Element '{}' is out of order for the sequence in '{}'",
+                                child.name, element.name
+                            ),
+                        ));
+                    } else {
+                        violations.push(unexpected(child));
+                    }
+                }
+            }
+            kind => {
+                let mut counts = vec![0; particles.len()];
+                for child in children {
+                    match particles
+                        .iter()
+                        .position(|particle| matches_particle(child, particle))
+                    {
+                        Some(index) => {
+                            counts[index] += 1;
+                            self.validate_element(child, particles[index], violations);
+                        }
+                        None => violations.push(unexpected(child)),
+                    }
+                }
+                if kind == "all" {
+                    for ((particle, source), count) in particles
+                        .iter()
+                        .zip(model.children_named("element"))
+                        .zip(&counts)
+                    {
+                        let (min, max) = occurs(source);
+                        if *count < min || *count > max {
+                            violations.push(violation(
+                                &element.json_path,
+                                format!(
+                                    "Expected between {} and {} '{}' element(s) in '{}', found {}",
+                                    min,
+                                    max,
+                                    particle_name(particle),
+                                    element.name,
+                                    count
+                                ),
+                            ));
+                        }
+                    }
+                } else {
+                    let used = counts.iter().filter(|count| **count > 0).count();
+                    let (min, _) = occurs(model);
+                    if used > 1 || (used == 0 && min > 0) {
+                        violations.push(violation(
+                            &element.json_path,
+                            format!(
+                                "Expected exactly one of {:?} in '{}'",
+                                particles
+                                    .iter()
+                                    .map(|p| particle_name(p))
+                                    .collect::<Vec<_>>(),
+                                element.name
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks a lexical value against a simple or built-in type, returning an error message.
+    fn check_simple(&self, text: &str, type_ref: &TypeRef<'a>) -> Option<String> {
+        match type_ref {
+            TypeRef::Any | TypeRef::Complex(_) => None,
+            TypeRef::Builtin(name) => check_builtin(text, name),
+            TypeRef::Simple(simple) => {
+                let restriction = simple.child("restriction")?;
+                if let Some(base) = restriction.attr("base") {
+                    if let Some(error) = self.check_simple(text, &self.named_type(base)) {
+                        return Some(error);
+                    }
+                }
+                let enumeration: Vec<&str> = restriction
+                    .children_named("enumeration")
+                    .filter_map(|facet| facet.attr("value"))
+                    .collect();
+                if !enumeration.is_empty() && !enumeration.contains(&text) {
+                    return Some(format!("'{}' is not one of {:?}", text, enumeration));
+                }
+                restriction
+                    .children
+                    .iter()
+                    .find_map(|facet| check_facet(text, facet))
+            }
+        }
+    }
+}
+
+/// Returns `(minOccurs, maxOccurs)` of a particle, both defaulting to 1.
+fn occurs(node: &SchemaNode) -> (usize, usize) {
+    let min = node
+        .attr("minOccurs")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(1);
+    let max = match node.attr("maxOccurs") {
+        Some("unbounded") => usize::MAX,
+        Some(value) => value.parse().unwrap_or(1),
+        None => 1,
+    };
+    (min, max)
+}
+
+/// Builds the violation for an element the content model does not allow.
+fn unexpected(child: &XmlElement) -> XsdViolation {
+    violation(
+        &child.json_path,
+        format!("Unexpected element '{}'", child.name),
+    )
+}
+
+/// Checks a lexical value against a built-in XML Schema type.
+fn check_builtin(text: &str, name: &str) -> Option<String> {
+    let valid = match name {
+        "int" | "integer" | "long" | "short" | "byte" => text.parse::<i64>().is_ok(),
+        "nonNegativeInteger" | "unsignedInt" | "unsignedLong" | "unsignedShort" => {
+            text.parse::<u64>().is_ok()
+        }
+        "positiveInteger" => text.parse::<u64>().is_ok_and(|n| n > 0),
+        "decimal" | "double" | "float" => text.parse::<f64>().is_ok(),
+        "boolean" => matches!(text, "true" | "false" | "1" | "0"),
+        "date" => is_date(text),
+        "dateTime" => text.split_once('T').is_some_and(|(date, _)| is_date(date)),
+        _ => true,
+    };
+    if valid {
+        None
+    } else {
+        Some(format!("'{}' is not a valid xs:{}", text, name))
+    }
+}
+
+/// Checks the `YYYY-MM-DD` shape of an `xs:date`.
+fn is_date(text: &str) -> bool {
+    let parts: Vec<&str> = text.splitn(3, '-').collect();
+    parts.len() == 3
+        && [4, 2, 2]
+            .iter()
+            .zip(&parts)
+            .all(|(len, part)| part.len() == *len && part.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Checks a length or bound facet of a restriction, returning an error message.
+fn check_facet(text: &str, facet: &SchemaNode) -> Option<String> {
+    let limit = facet.attr("value")?;
+    let length = text.chars().count();
+    let number = || text.parse::<f64>().ok();
+    let bound = limit.parse::<f64>().ok();
+    let valid = match facet.name.as_str() {
+        "length" => limit.parse() == Ok(length),
+        "minLength" => limit.parse().is_ok_and(|min: usize| length >= min),
+        "maxLength" => limit.parse().is_ok_and(|max: usize| length <= max),
+        "minInclusive" => number().zip(bound).is_some_and(|(n, b)| n >= b),
+        "maxInclusive" => number().zip(bound).is_some_and(|(n, b)| n <= b),
+        "minExclusive" => number().zip(bound).is_some_and(|(n, b)| n > b),
+        "maxExclusive" => number().zip(bound).is_some_and(|(n, b)| n < b),
+        _ => true,
+    };
+    if valid {
+        None
+    } else {
+        Some(format!("'{}' violates {} {}", text, facet.name, limit))
+    }
+}
+
+/// Builds a violation at a JSON path.
+fn violation(path: &str, message: String) -> XsdViolation {
+    XsdViolation {
+        path: path.to_string(),
+        message,
+    }
+}
+
+/// Returns the declared name of an element particle.
+fn particle_name(particle: &SchemaNode) -> &str {
+    particle.attr("name").unwrap_or_default()
+}
+
+/// Checks whether an element matches an element particle by local name.
+fn matches_particle(child: &XmlElement, particle: &SchemaNode) -> bool {
+    local_name(&child.name) == particle_name(particle)
+}
@@ -0,0 +1,497 @@
+use anyhow::{Error, Result};
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// How string scalars are quoted by `json_to_yaml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YamlQuoting {
+    /// Quotes only where YAML requires it (serde_yaml's rules).
+    Minimal,
+    /// Single-quotes every string value; multi-line strings fall back to double quotes.
+    Single,
+    /// Double-quotes every string value.
+    Double,
+}
+
+impl FromStr for YamlQuoting {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(YamlQuoting::Minimal),
+            "single" => Ok(YamlQuoting::Single),
+            "double" => Ok(YamlQuoting::Double),
+            _ => Err(Error::msg(format!(
+                "Unsupported YAML quoting policy: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Options for `json_to_yaml`.
+///
+/// The defaults reproduce serde_yaml's output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YamlOptions {
+    /// Emits repeated objects and arrays once with an anchor (`&a1`) and refers
+    /// back to them with aliases (`*a1`).
+    pub anchors: bool,
+    /// Minimum number of nodes (the collection itself plus all descendants) a
+    /// repeated subtree needs before it is factored into an anchor.
+    pub anchor_min_size: usize,
+    /// Spaces per nesting level.
+    pub indent: usize,
+    /// Indents sequences nested under a mapping key instead of aligning the
+    /// dashes with the key.
+    pub indent_sequences: bool,
+    /// Collections of at most this many scalar items are written in flow style
+    /// (`[1, 2]`, `{a: 1}`); `0` always uses block style.
+    pub flow_max_items: usize,
+    /// Quoting policy for string values. Mapping keys are always quoted minimally.
+    pub quoting: YamlQuoting,
+    /// Preferred maximum line width; long plain strings are folded and flow
+    /// collections that would not fit are written in block style. `0` disables wrapping.
+    pub width: usize,
+    /// Starts the document with `---` and ends it with `...`.
+    pub document_markers: bool,
+}
+
+impl Default for YamlOptions {
+    fn default() -> Self {
+        YamlOptions {
+            anchors: false,
+            anchor_min_size: 2,
+            indent: 2,
+            indent_sequences: false,
+            flow_max_items: 0,
+            quoting: YamlQuoting::Minimal,
+            width: 0,
+            document_markers: false,
+        }
+    }
+}
+
+/// Parses a YAML stream into one JSON value per document.
+///
+/// Anchors and aliases are resolved and `<<` merge keys are applied. Alias
+/// expansion is bounded, so self-referencing aliases and alias bombs are reported
+/// as errors instead of looping. Non-string mapping keys are converted to strings.
+///
+/// # Arguments
+///
+/// * `input` - The YAML text, possibly containing several `---` separated documents.
+///
+/// # Returns
+///
+/// * `Result<Vec<Value>>` - The documents in stream order, or an error naming the failing document.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(bytes = input.len())))]
+pub fn parse_yaml_documents(input: &str) -> Result<Vec<Value>> {
+    let mut documents = Vec::new();
+    for (index, document) in serde_yaml::Deserializer::from_str(input).enumerate() {
+        let mut value =
+            serde_yaml::Value::deserialize(document).map_err(|e| yaml_error(index, e))?;
+        value.apply_merge().map_err(|e| yaml_error(index, e))?;
+        documents.push(yaml_to_json(value)?);
+    }
+    Ok(documents)
+}
+
+/// Converts JSON to YAML.
+///
+/// # Arguments
+///
+/// * `json` - The JSON value to convert.
+/// * `options` - Anchor handling and output style.
+///
+/// # Returns
+///
+/// * `Result<String>` - The YAML document.
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+pub fn json_to_yaml(json: &Value, options: &YamlOptions) -> Result<String> {
+    if options.indent == 0 {
+        return Err(Error::msg("YAML indent must be at least 1"));
+    }
+    let mut writer = YamlWriter {
+        options,
+        repeated: HashSet::new(),
+        anchors: HashMap::new(),
+        out: String::new(),
+    };
+    if options.anchors {
+        let mut counts = HashMap::new();
+        count_subtrees(json, &mut counts);
+        writer.repeated = counts
+            .into_iter()
+            .filter(|(_, (count, size))| *count > 1 && *size >= options.anchor_min_size)
+            .map(|(key, _)| key)
+            .collect();
+    }
+    if options.document_markers {
+        writer.out.push_str("---\n");
+    }
+    match json {
+        Value::Object(map) if !map.is_empty() && !writer.fits_flow(json) => {
+            writer.write_mapping(map, 0, false)?
+        }
+        Value::Array(items) if !items.is_empty() && !writer.fits_flow(json) => {
+            writer.write_sequence(items, 0, false)?
+        }
+        Value::Object(_) | Value::Array(_) => {
+            let flow = writer.flow(json)?;
+            writer.out.push_str(&flow);
+            writer.out.push('\n');
+        }
+        _ => {
+            let text = writer.scalar(json, 0)?;
+            writer.out.push_str(&text);
+            writer.out.push('\n');
+        }
+    }
+    if options.document_markers {
+        writer.out.push_str("...\n");
+    }
+    Ok(writer.out)
+}
+
+/// Wraps a serde_yaml error with the index of the failing document.
+fn yaml_error(index: usize, error: serde_yaml::Error) -> Error {
+    let hint = if error.to_string().contains("recursion limit") {
+        " (cyclic or too deeply nested aliases)"
+    } else {
+        ""
+    };
+    Error::msg(format!(
+        "Invalid YAML in document {}{}: {}",
+        index + 1,
+        hint,
+        error
+    ))
+}
+
+/// Converts a resolved YAML value into JSON.
+fn yaml_to_json(value: serde_yaml::Value) -> Result<Value> {
+    Ok(match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else {
+                n.as_f64()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+                    .ok_or_else(|| Error::msg(format!("{} has no JSON representation", n)))?
+            }
+        }
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(items) => Value::Array(
+            items
+                .into_iter()
+                .map(yaml_to_json)
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        serde_yaml::Value::Mapping(mapping) => {
+            let mut map = Map::new();
+            for (key, value) in mapping {
+                let key = match key {
+                    serde_yaml::Value::String(s) => s,
+                    serde_yaml::Value::Number(n) => n.to_string(),
+                    serde_yaml::Value::Bool(b) => b.to_string(),
+                    serde_yaml::Value::Null => "null".to_string(),
+                    other => {
+                        return Err(Error::msg(format!(
+                            "Unsupported non-scalar mapping key: {:?}",
+                            other
+                        )))
+                    }
+                };
+                map.insert(key, yaml_to_json(value)?);
+            }
+            Value::Object(map)
+        }
+        serde_yaml::Value::Tagged(tagged) => yaml_to_json(tagged.value)?,
+    })
+}
+
+/// Counts occurrences of every non-empty collection, keyed by its compact JSON.
+///
+/// Repeated subtrees are not descended into again, so collections nested inside a
+/// repeated subtree are only counted once per distinct parent. Returns the node count.
+fn count_subtrees(value: &Value, counts: &mut HashMap<String, (usize, usize)>) -> usize {
+    let children: Vec<&Value> = match value {
+        Value::Object(map) => map.values().collect(),
+        Value::Array(items) => items.iter().collect(),
+        _ => return 1,
+    };
+    if children.is_empty() {
+        return 1;
+    }
+    let key = value.to_string();
+    if let Some((count, size)) = counts.get_mut(&key) {
+        *count += 1;
+        return *size;
+    }
+    let size = 1 + children
+        .into_iter()
+        .map(|child| count_subtrees(child, counts))
+        .sum::<usize>();
+    counts.insert(key, (1, size));
+    size
+}
+
+/// Block-style YAML writer.
+struct YamlWriter<'a> {
+    options: &'a YamlOptions,
+    repeated: HashSet<String>,
+    anchors: HashMap<String, String>,
+    out: String,
+}
+
+impl YamlWriter<'_> {
+    /// Writes mapping entries at `indent`; with `inline_first` the first key
+    /// continues the current line (after `- `).
+    fn write_mapping(
+        &mut self,
+        map: &Map<String, Value>,
+        indent: usize,
+        inline_first: bool,
+    ) -> Result<()> {
+        for (index, (key, value)) in map.iter().enumerate() {
+            if index > 0 || !inline_first {
+                self.out.push_str(&" ".repeat(indent));
+            }
+            self.out.push_str(&key_scalar(key)?);
+            self.out.push(':');
+            self.write_child(value, indent, true)?;
+        }
+        Ok(())
+    }
+
+    /// Writes sequence items at `indent`.
+    fn write_sequence(&mut self, items: &[Value], indent: usize, inline_first: bool) -> Result<()> {
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 || !inline_first {
+                self.out.push_str(&" ".repeat(indent));
+            }
+            self.out.push('-');
+            self.write_child(item, indent, false)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a value following `key:` or `-`, including any anchor or alias.
+    fn write_child(&mut self, value: &Value, indent: usize, in_mapping: bool) -> Result<()> {
+        let is_collection = match value {
+            Value::Object(map) => !map.is_empty(),
+            Value::Array(items) => !items.is_empty(),
+            _ => false,
+        };
+        let step = self.options.indent;
+        if !is_collection {
+            let text = self.scalar(value, indent)?;
+            self.out.push(' ');
+            self.out.push_str(&text);
+            self.out.push('\n');
+            return Ok(());
+        }
+
+        let mut anchored = false;
+        if !self.repeated.is_empty() {
+            let key = value.to_string();
+            if self.repeated.contains(&key) {
+                if let Some(name) = self.anchors.get(&key) {
+                    self.out.push_str(&format!(" *{}\n", name));
+                    return Ok(());
+                }
+                let name = format!("a{}", self.anchors.len() + 1);
+                self.out.push_str(&format!(" &{}", name));
+                self.anchors.insert(key, name);
+                anchored = true;
+            }
+        }
+
+        if self.fits_flow(value) {
+            let flow = self.flow(value)?;
+            self.out.push(' ');
+            self.out.push_str(&flow);
+            self.out.push('\n');
+            return Ok(());
+        }
+
+        let inline = !in_mapping && !anchored;
+        if inline {
+            // Pad `-` to a full indentation step so following lines align with the first.
+            self.out.push_str(&" ".repeat(step.max(2) - 1));
+        } else {
+            self.out.push('\n');
+        }
+        let child_indent = if in_mapping {
+            indent + step
+        } else {
+            indent + step.max(2)
+        };
+        match value {
+            Value::Object(map) => self.write_mapping(map, child_indent, inline),
+            Value::Array(items) => {
+                // Like serde_yaml, sequences under a mapping key are not indented by default.
+                let child_indent = if in_mapping && !self.options.indent_sequences {
+                    indent
+                } else {
+                    child_indent
+                };
+                self.write_sequence(items, child_indent, inline)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns `true` if a collection should be written in flow style on the current line.
+    fn fits_flow(&self, value: &Value) -> bool {
+        let items: Vec<&Value> = match value {
+            Value::Object(map) => map.values().collect(),
+            Value::Array(items) => items.iter().collect(),
+            _ => return false,
+        };
+        if items.len() > self.options.flow_max_items
+            || items.iter().any(|item| match item {
+                Value::Object(map) => !map.is_empty(),
+                Value::Array(items) => !items.is_empty(),
+                _ => false,
+            })
+        {
+            return false;
+        }
+        match self.flow(value) {
+            Ok(flow) => {
+                let column = self.out.len() - self.out.rfind('\n').map_or(0, |i| i + 1);
+                self.options.width == 0 || column + 1 + flow.len() <= self.options.width
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Formats a collection of scalars in flow style.
+    fn flow(&self, value: &Value) -> Result<String> {
+        match value {
+            Value::Object(map) => {
+                let entries = map
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok(format!(
+                            "{}: {}",
+                            flow_scalar(&Value::String(key.clone()), YamlQuoting::Minimal)?,
+                            flow_scalar(value, self.options.quoting)?
+                        ))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("{{{}}}", entries.join(", ")))
+            }
+            Value::Array(items) => {
+                let entries = items
+                    .iter()
+                    .map(|item| flow_scalar(item, self.options.quoting))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("[{}]", entries.join(", ")))
+            }
+            _ => flow_scalar(value, self.options.quoting),
+        }
+    }
+
+    /// Formats a scalar (or empty collection) in block context below a node at `indent`.
+    fn scalar(&self, value: &Value, indent: usize) -> Result<String> {
+        let Value::String(s) = value else {
+            return minimal_scalar(value, indent);
+        };
+        match self.options.quoting {
+            YamlQuoting::Single if !s.contains('\n') => Ok(single_quoted(s)),
+            YamlQuoting::Single | YamlQuoting::Double => Ok(serde_json::to_string(s)?),
+            YamlQuoting::Minimal => {
+                let text = minimal_scalar(value, indent)?;
+                let content_indent = indent + self.options.indent;
+                let column = self.out.len() - self.out.rfind('\n').map_or(0, |i| i + 1);
+                let available = self.options.width.saturating_sub(content_indent);
+                if self.options.width > 0
+                    && column + 1 + text.len() > self.options.width
+                    && available > 0
+                    && is_foldable(s)
+                {
+                    return Ok(folded(s, content_indent, available));
+                }
+                Ok(text)
+            }
+        }
+    }
+}
+
+/// Formats a value with serde_yaml's quoting rules, indenting continuation
+/// lines of block literals relative to `indent`.
+fn minimal_scalar(value: &Value, indent: usize) -> Result<String> {
+    let text = serde_yaml::to_string(value).map_err(|e| Error::msg(e.to_string()))?;
+    Ok(text
+        .trim_end_matches('\n')
+        .replace('\n', &format!("\n{}", " ".repeat(indent))))
+}
+
+/// Formats a scalar for flow context, where flow indicators force quoting.
+fn flow_scalar(value: &Value, quoting: YamlQuoting) -> Result<String> {
+    match value {
+        Value::String(s) if quoting == YamlQuoting::Single && !s.contains('\n') => {
+            Ok(single_quoted(s))
+        }
+        Value::String(s)
+            if quoting != YamlQuoting::Minimal || s.contains(['\n', ',', '[', ']', '{', '}']) =>
+        {
+            Ok(serde_json::to_string(s)?)
+        }
+        _ => minimal_scalar(value, 0),
+    }
+}
+
+/// Single-quotes a string, doubling embedded quotes.
+fn single_quoted(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Returns `true` if a string survives YAML line folding unchanged.
+fn is_foldable(s: &str) -> bool {
+    s.contains(' ')
+        && !s.contains(['\n', '\t', '\r'])
+        && !s.contains("  ")
+        && !s.starts_with(' ')
+        && !s.ends_with(' ')
+}
+
+/// Writes a string as a folded block scalar wrapped to `width` columns.
+fn folded(s: &str, indent: usize, width: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for word in s.split(' ') {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    let padding = " ".repeat(indent);
+    let mut out = ">-".to_string();
+    for line in lines {
+        out.push('\n');
+        out.push_str(&padding);
+        out.push_str(&line);
+    }
+    out
+}
+
+/// Formats a mapping key; multi-line keys are double-quoted.
+fn key_scalar(key: &str) -> Result<String> {
+    if key.contains('\n') {
+        return Ok(serde_json::to_string(key)?);
+    }
+    minimal_scalar(&Value::String(key.to_string()), 0)
+}
@@ -379,7 +379,7 @@ fn test_handle_large_json() {
         // Перевіряємо, чи повернена помилка є саме ParsingError
         if let Err(err) = result {
             assert!(
-                matches!(err, json_parser_with_pest::ParserError::JsonParseError),
+                matches!(err, json_parser_with_pest::ParserError::JsonParseError(_)),
                 "Expected JsonParseError, but got: {:?}",
                 err
             );
@@ -614,4 +614,4570 @@ fn test_empty_structures() {
             result.err()
         );
     }
-}
\ No newline at end of file
+}
+/// Test pretty diff rendering with context lines and collapsed unchanged subtrees.
+#[test]
+fn test_render_diff_pretty() {
+    use json_parser_with_pest::{render_diff, DiffFormat, RenderOptions};
+
+    let old = json!({
+        "name": "service",
+        "replicas": 2,
+        "labels": { "a": 1, "b": 2, "c": 3, "d": 4, "e": 5, "f": 6, "g": 7, "h": 8 }
+    });
+    let new = json!({
+        "name": "service",
+        "replicas": 3,
+        "labels": { "a": 1, "b": 2, "c": 3, "d": 4, "e": 5, "f": 6, "g": 7, "h": 8 }
+    });
+    let options = RenderOptions {
+        context: 2,
+        color: false,
+        ..RenderOptions::default()
+    };
+    let rendered = render_diff(&old, &new, DiffFormat::Pretty, &options)
+        .expect("Failed to render pretty diff");
+
+    assert!(rendered.contains("-   \"replicas\": 2"), "Missing removed line: {}", rendered);
+    assert!(rendered.contains("+   \"replicas\": 3"), "Missing added line: {}", rendered);
+    assert!(
+        rendered.contains("\"labels\": { … 10 unchanged lines … },"),
+        "Large subtree was not collapsed: {}",
+        rendered
+    );
+    assert!(!rendered.contains("\x1b["), "Colors emitted despite being disabled.");
+}
+
+/// Test the patch and JSON diff output formats.
+#[test]
+fn test_render_diff_patch_and_json() {
+    use json_parser_with_pest::{render_diff, DiffFormat, RenderOptions};
+
+    let old = json!({ "a": 1, "b": [1, 2] });
+    let new = json!({ "a": 2, "b": [1], "c": true });
+    let options = RenderOptions::default();
+
+    let patch: Value = serde_json::from_str(
+        &render_diff(&old, &new, DiffFormat::Patch, &options).expect("Failed to render patch"),
+    )
+    .expect("Patch output is not valid JSON");
+    assert_eq!(
+        patch,
+        json!([
+            { "op": "replace", "path": "/a", "value": 2 },
+            { "op": "add", "path": "/c", "value": true },
+            { "op": "remove", "path": "/b/1" }
+        ]),
+        "Unexpected patch output."
+    );
+
+    let report: Value = serde_json::from_str(
+        &render_diff(&old, &new, DiffFormat::Json, &options).expect("Failed to render JSON diff"),
+    )
+    .expect("JSON diff output is not valid JSON");
+    assert_eq!(report[0]["path"], "a", "Unexpected path in JSON diff report.");
+    assert_eq!(report[0]["kind"], "changed", "Unexpected kind in JSON diff report.");
+}
+
+/// Test the side-by-side diff view keeps changed paths on the same row.
+#[test]
+fn test_render_side_by_side() {
+    use json_parser_with_pest::{render_side_by_side, RenderOptions};
+
+    let old = json!({ "image": "app:1.0", "port": 80 });
+    let new = json!({ "image": "app:1.1", "port": 80 });
+    let options = RenderOptions {
+        color: false,
+        width: 60,
+        ..RenderOptions::default()
+    };
+    let rendered = render_side_by_side(&old, &new, &options);
+    let changed_row = rendered
+        .lines()
+        .find(|line| line.contains(" | "))
+        .expect("No changed row in side-by-side output");
+
+    assert!(changed_row.contains("\"app:1.0\""), "Old value missing: {}", changed_row);
+    assert!(changed_row.contains("\"app:1.1\""), "New value missing: {}", changed_row);
+    assert!(
+        rendered.lines().all(|line| line.chars().count() <= 60),
+        "Side-by-side output exceeds the configured width:\n{}",
+        rendered
+    );
+}
+
+/// Test merging schemas with the loosest and strictest constraint policies.
+#[test]
+fn test_merge_schemas() {
+    use json_parser_with_pest::{merge_schemas, merge_schemas_with, ConstraintPolicy};
+
+    let billing = json!({
+        "type": "object",
+        "properties": {
+            "id": { "type": "integer", "minimum": 1 },
+            "amount": { "type": "number", "maximum": 1000 }
+        },
+        "required": ["id", "amount"]
+    });
+    let shipping = json!({
+        "type": "object",
+        "properties": {
+            "id": { "type": "string", "minimum": 10 },
+            "address": { "type": "string" }
+        },
+        "required": ["id", "address"]
+    });
+
+    let loose = merge_schemas(&[billing.clone(), shipping.clone()]);
+    assert_eq!(loose["required"], json!(["id"]), "Loosest merge should intersect required.");
+    assert_eq!(
+        loose["properties"]["id"],
+        json!({ "type": ["integer", "string"], "minimum": 1 }),
+        "Loosest merge should unite types and keep the smaller minimum."
+    );
+    assert!(
+        loose["properties"]["address"].is_object() && loose["properties"]["amount"].is_object(),
+        "Merged schema should contain the union of properties."
+    );
+
+    let strict = merge_schemas_with(&[billing, shipping], ConstraintPolicy::Strictest);
+    assert_eq!(
+        strict["required"],
+        json!(["id", "amount", "address"]),
+        "Strictest merge should unite required."
+    );
+    assert_eq!(strict["properties"]["id"]["minimum"], 10, "Strictest merge should keep the larger minimum.");
+}
+
+/// Test schema simplification of verbose combinators and redundant constraints.
+#[test]
+fn test_simplify_schema() {
+    use json_parser_with_pest::simplify_schema;
+
+    let verbose = json!({
+        "type": "object",
+        "properties": {
+            "id": {
+                "anyOf": [
+                    { "type": "integer" },
+                    { "type": "number" },
+                    { "type": "integer" }
+                ]
+            },
+            "status": {
+                "anyOf": [
+                    { "enum": ["active", "inactive"] },
+                    { "const": "pending" },
+                    { "enum": ["active"] }
+                ]
+            },
+            "name": { "type": ["string", "string"], "minLength": 0 }
+        },
+        "required": ["id", "id"],
+        "additionalProperties": true
+    });
+    let simplified = simplify_schema(&verbose);
+
+    assert_eq!(
+        simplified,
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "number" },
+                "status": { "enum": ["active", "inactive", "pending"] },
+                "name": { "type": "string" }
+            },
+            "required": ["id"]
+        }),
+        "Schema was not simplified as expected."
+    );
+}
+
+/// Test deterministic minimal and maximal example generation from a schema.
+#[test]
+fn test_example_from_schema() {
+    use json_parser_with_pest::example_from_schema;
+
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "id": { "type": "integer", "minimum": 1 },
+            "email": { "type": "string", "format": "email" },
+            "role": { "enum": ["admin", "user"] },
+            "tags": { "type": "array", "items": { "type": "string", "minLength": 8 } }
+        },
+        "required": ["id", "role"]
+    });
+    let examples = example_from_schema(&schema);
+
+    assert_eq!(
+        examples.minimal,
+        json!({ "id": 1, "role": "admin" }),
+        "Minimal example should only contain required properties."
+    );
+    assert_eq!(
+        examples.maximal,
+        json!({ "id": 1, "email": "user@example.com", "role": "admin", "tags": ["stringxx"] }),
+        "Maximal example should contain every property."
+    );
+    assert_eq!(
+        example_from_schema(&schema),
+        examples,
+        "Example generation should be deterministic."
+    );
+}
+
+/// Test the data dictionary report over a small dataset.
+#[test]
+fn test_data_dictionary() {
+    use json_parser_with_pest::data_dictionary;
+
+    let records = vec![
+        json!({ "id": 1, "email": "a@example.com", "tags": ["x", "y"] }),
+        json!({ "id": 2, "email": null, "tags": ["x"] }),
+        json!({ "id": 3, "email": "c@example.com", "tags": [] }),
+    ];
+    let report = data_dictionary(&records);
+
+    assert_eq!(report["records"], 3, "Unexpected record count.");
+    let id = &report["paths"]["id"];
+    assert_eq!(id["types"], json!({ "integer": 3 }), "Unexpected types for 'id'.");
+    assert_eq!(id["min"], 1, "Unexpected minimum for 'id'.");
+    assert_eq!(id["max"], 3, "Unexpected maximum for 'id'.");
+    assert_eq!(id["distinct"], 3, "Unexpected distinct count for 'id'.");
+
+    let email = &report["paths"]["email"];
+    let null_rate = email["null_rate"].as_f64().unwrap();
+    assert!((null_rate - 1.0 / 3.0).abs() < 1e-9, "Unexpected null rate: {}", null_rate);
+
+    let tags = &report["paths"]["tags[]"];
+    assert_eq!(tags["occurrences"], 3, "Array elements should share one path.");
+    assert_eq!(tags["distinct"], 2, "Unexpected distinct count for 'tags[]'.");
+    assert_eq!(tags["samples"], json!(["x", "y"]), "Unexpected samples for 'tags[]'.");
+}
+
+/// Test the HyperLogLog estimate stays within a few percent of the true cardinality.
+#[test]
+fn test_hyperloglog_estimate() {
+    use json_parser_with_pest::HyperLogLog;
+
+    let mut sketch = HyperLogLog::default();
+    for i in 0..50_000 {
+        sketch.insert(&i);
+        sketch.insert(&i);
+    }
+    let estimate = sketch.count() as f64;
+    assert!(
+        (estimate - 50_000.0).abs() / 50_000.0 < 0.05,
+        "HyperLogLog estimate too far off: {}",
+        estimate
+    );
+}
+
+/// Test streaming sketches report distinct counts and heavy hitters per path.
+#[test]
+fn test_stream_sketches() {
+    use json_parser_with_pest::StreamSketches;
+
+    let mut sketches = StreamSketches::new(&["country".to_string()], 2);
+    for (index, country) in ["UA", "PL", "UA", "DE", "UA", "PL"].iter().enumerate() {
+        sketches.observe(&json!({ "id": index, "country": country }));
+    }
+    sketches.observe(&json!({ "id": 99 }));
+    let report = sketches.to_json();
+
+    assert_eq!(report["records"], 7, "Unexpected record count.");
+    assert_eq!(report["paths"]["country"]["distinct"], 3, "Unexpected distinct estimate.");
+    assert_eq!(
+        report["paths"]["country"]["top"],
+        json!([{ "value": "UA", "count": 3 }, { "value": "PL", "count": 2 }]),
+        "Unexpected heavy hitters."
+    );
+}
+
+/// Test external merge sort of NDJSON across multiple spilled runs.
+#[test]
+fn test_sort_ndjson() {
+    use json_parser_with_pest::{sort_ndjson, SortOptions};
+    use std::io::Write;
+
+    let dir = tempdir().expect("Failed to create temp directory");
+    let input = dir.path().join("input.ndjson");
+    let output = dir.path().join("sorted.ndjson");
+    let mut file = File::create(&input).expect("Failed to create input file");
+    for (id, age) in [(1, 40), (2, 25), (3, 31), (4, 25), (5, 19), (6, 52), (7, 31)] {
+        writeln!(file, "{{\"id\": {}, \"user\": {{\"age\": {}}}}}", id, age)
+            .expect("Failed to write record");
+    }
+
+    let mut options = SortOptions::new("user.age");
+    options.chunk_records = 3;
+    options.temp_dir = Some(dir.path().to_path_buf());
+    let count = sort_ndjson(&input, &output, &options).expect("Failed to sort NDJSON");
+    assert_eq!(count, 7, "Unexpected number of sorted records.");
+
+    let ids: Vec<u64> = std::fs::read_to_string(&output)
+        .expect("Failed to read sorted output")
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["id"].as_u64().unwrap())
+        .collect();
+    assert_eq!(ids, vec![5, 2, 4, 3, 7, 1, 6], "Records are not sorted stably by age.");
+
+    options.descending = true;
+    sort_ndjson(&input, &output, &options).expect("Failed to sort NDJSON descending");
+    let first: Value = serde_json::from_str(
+        std::fs::read_to_string(&output).unwrap().lines().next().unwrap(),
+    )
+    .unwrap();
+    assert_eq!(first["id"], 6, "Descending sort should start with the oldest user.");
+}
+
+/// Test binary-search lookups in sorted NDJSON with and without a sidecar index.
+#[test]
+fn test_lookup_sorted_ndjson() {
+    use json_parser_with_pest::{build_index, lookup_sorted};
+    use std::io::Write;
+
+    let dir = tempdir().expect("Failed to create temp directory");
+    let input = dir.path().join("sorted.ndjson");
+    let mut file = File::create(&input).expect("Failed to create input file");
+    for id in 0..500 {
+        writeln!(file, "{{\"id\": {}, \"copy\": 0}}", id * 2).expect("Failed to write record");
+        if id == 100 {
+            writeln!(file, "{{\"id\": {}, \"copy\": 1}}", id * 2).expect("Failed to write record");
+        }
+    }
+    drop(file);
+
+    let found = lookup_sorted(&input, "id", &json!(200)).expect("Bisection lookup failed");
+    assert_eq!(found.len(), 2, "Expected both records with id 200: {:?}", found);
+    assert!(
+        lookup_sorted(&input, "id", &json!(201)).unwrap().is_empty(),
+        "Lookup of a missing key should return nothing."
+    );
+
+    let entries = build_index(&input, "id", 64).expect("Failed to build index");
+    assert_eq!(entries, 8, "Unexpected number of index entries.");
+    let found = lookup_sorted(&input, "id", &json!(998)).expect("Indexed lookup failed");
+    assert_eq!(found, vec![json!({ "id": 998, "copy": 0 })], "Indexed lookup returned wrong records.");
+    let found = lookup_sorted(&input, "id", &json!(0)).expect("Indexed lookup failed");
+    assert_eq!(found.len(), 1, "Indexed lookup of the first key failed.");
+}
+
+/// Test JSONPath wildcards, recursive descent, filters, slices, and unions.
+#[test]
+fn test_query_jsonpath() {
+    use json_parser_with_pest::query_jsonpath;
+
+    let store = json!({
+        "store": {
+            "book": [
+                { "title": "Sayings", "price": 8.95, "category": "reference" },
+                { "title": "Sword", "price": 12.99, "category": "fiction" },
+                { "title": "Moby Dick", "price": 8.99, "category": "fiction", "isbn": "0-553" },
+                { "title": "Rings", "price": 22.99, "category": "fiction", "isbn": "0-395" }
+            ],
+            "bicycle": { "color": "red", "price": 19.95 }
+        }
+    });
+    let query = |path: &str| query_jsonpath(&store, path).expect("Query failed");
+
+    assert_eq!(query("$.store.book[*].title").len(), 4, "Wildcard should select every title.");
+    assert_eq!(query("$..price").len(), 5, "Recursive descent should find every price.");
+    assert_eq!(
+        query("$.store.book[?(@.price > 10 && @.category == 'fiction')].title"),
+        vec![json!("Sword"), json!("Rings")],
+        "Filter selected the wrong books."
+    );
+    assert_eq!(
+        query("$..book[?(@.isbn)].title"),
+        vec![json!("Moby Dick"), json!("Rings")],
+        "Existence filter selected the wrong books."
+    );
+    assert_eq!(
+        query("$.store.book[0:2].title"),
+        vec![json!("Sayings"), json!("Sword")],
+        "Slice selected the wrong books."
+    );
+    assert_eq!(query("$.store.book[-1].title"), vec![json!("Rings")], "Negative index failed.");
+    assert_eq!(
+        query("$.store.book[0,2]['title']"),
+        vec![json!("Sayings"), json!("Moby Dick")],
+        "Union selected the wrong books."
+    );
+    assert_eq!(query("$.store.book[::-2].title"), vec![json!("Rings"), json!("Sword")], "Negative step failed.");
+    assert!(query_jsonpath(&store, "$.store[?(@.price >").is_err(), "Invalid expression should fail.");
+}
+
+/// Test incremental statistics with drift detection over appended records.
+#[test]
+fn test_incremental_stats_drift() {
+    use json_parser_with_pest::{DriftOptions, IncrementalStats};
+
+    let mut stats = IncrementalStats::new(DriftOptions {
+        window: 50,
+        ..DriftOptions::default()
+    });
+    for i in 0..100 {
+        stats.observe(&json!({ "status": "ok", "latency": 100 + i % 10 }));
+    }
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot["records"], 100, "Unexpected record count.");
+    assert_eq!(snapshot["paths"]["status"]["drift"]["drifted"], false, "No drift expected yet.");
+    assert_eq!(snapshot["paths"]["latency"]["min"], 100.0, "Unexpected running minimum.");
+
+    for _ in 0..50 {
+        stats.observe(&json!({ "status": "error", "latency": 900 }));
+    }
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot["paths"]["status"]["drift"]["drifted"], true, "Categorical drift not detected.");
+    assert_eq!(snapshot["paths"]["latency"]["drift"]["drifted"], true, "Numeric drift not detected.");
+    assert_eq!(snapshot["paths"]["status"]["count"], 150, "Counts should accumulate incrementally.");
+}
+
+/// Test following an NDJSON file picks up appended and partially written records.
+#[test]
+fn test_ndjson_tail() {
+    use json_parser_with_pest::NdjsonTail;
+    use std::io::Write;
+
+    let dir = tempdir().expect("Failed to create temp directory");
+    let path = dir.path().join("events.ndjson");
+    let mut file = File::create(&path).expect("Failed to create file");
+    writeln!(file, "{{\"n\": 1}}").unwrap();
+
+    let mut tail = NdjsonTail::new(&path, true).expect("Failed to follow file");
+    assert_eq!(tail.poll().unwrap(), vec![json!({ "n": 1 })], "Existing record not read.");
+
+    write!(file, "{{\"n\": 2}}\n{{\"n\":").unwrap();
+    file.flush().unwrap();
+    assert_eq!(tail.poll().unwrap(), vec![json!({ "n": 2 })], "Partial line should be buffered.");
+
+    writeln!(file, " 3}}").unwrap();
+    file.flush().unwrap();
+    assert_eq!(tail.poll().unwrap(), vec![json!({ "n": 3 })], "Completed line not read.");
+    assert!(tail.poll().unwrap().is_empty(), "No new records expected.");
+}
+
+/// Tests applying a declarative migration spec with per-record failure reporting.
+#[test]
+fn test_migrate_records() {
+    use json_parser_with_pest::{migrate_records, Migration};
+
+    let spec = json!([
+        { "op": "rename", "path": "user.name", "to": "fullName" },
+        { "op": "cast", "path": "age", "type": "integer" },
+        { "op": "move", "from": "city", "to": "address.city" },
+        { "op": "default", "path": "status", "value": "active" }
+    ]);
+    let migration = Migration::from_json(&spec).expect("Spec should parse");
+    let mut records = vec![
+        json!({ "user": { "name": "Ann" }, "age": "42", "city": "Oslo" }),
+        json!({ "user": { "name": "Bob" }, "age": "unknown", "status": "banned" }),
+    ];
+    let report = migrate_records(&mut records, &migration);
+
+    assert_eq!(
+        records[0],
+        json!({ "user": { "fullName": "Ann" }, "age": 42, "address": { "city": "Oslo" }, "status": "active" }),
+        "First record should be fully migrated"
+    );
+    assert_eq!(report.migrated, 1, "Only one record should migrate");
+    assert_eq!(report.failures.len(), 1, "The uncastable age should be reported");
+    assert_eq!(report.failures[0].record, 1, "Failure should point at the second record");
+    assert_eq!(report.failures[0].operation, 1, "Failure should point at the cast operation");
+    assert_eq!(records[1]["user"]["name"], "Bob", "Failed records should be left unchanged");
+}
+
+/// Tests RFC 6901 JSON Pointer access, including `~0`/`~1` escapes and array appends.
+#[test]
+fn test_json_pointer_functions() {
+    use json_parser_with_pest::parser::{get_by_pointer, remove_by_pointer, set_by_pointer};
+
+    let mut doc = json!({ "a.b": { "c/d": [1, 2] }, "m~n": true });
+    assert_eq!(get_by_pointer(&doc, "/a.b/c~1d/1"), Some(json!(2)), "Escaped slash should resolve");
+    assert_eq!(get_by_pointer(&doc, "/m~0n"), Some(json!(true)), "Escaped tilde should resolve");
+    assert_eq!(get_by_pointer(&doc, ""), Some(doc.clone()), "Empty pointer should return the document");
+    assert_eq!(get_by_pointer(&doc, "/a.b/c~1d/01"), None, "Leading zeros are not valid indices");
+
+    set_by_pointer(&mut doc, "/a.b/c~1d/-", json!(3)).expect("Append should succeed");
+    set_by_pointer(&mut doc, "/new", json!("x")).expect("Insert should succeed");
+    assert!(set_by_pointer(&mut doc, "/missing/child", json!(1)).is_err(), "Missing parent should fail");
+    assert_eq!(doc["a.b"]["c/d"], json!([1, 2, 3]), "Value should be appended");
+
+    let removed = remove_by_pointer(&mut doc, "/a.b/c~1d/0").expect("Remove should succeed");
+    assert_eq!(removed, json!(1), "Removed value should be returned");
+    assert_eq!(doc["a.b"]["c/d"], json!([2, 3]), "Array should shrink");
+    assert!(remove_by_pointer(&mut doc, "/nope").is_err(), "Removing a missing path should fail");
+}
+
+/// Tests that a generated JSON Patch round-trips and that every RFC 6902 operation applies.
+#[test]
+fn test_json_patch_generate_and_apply() {
+    use json_parser_with_pest::{apply_patch, generate_patch};
+
+    let old = json!({ "name": "a", "tags": ["x", "y", "z", "w"], "meta": { "v": 1 } });
+    let new = json!({ "name": "b", "tags": ["x"], "extra": true });
+    let patch = generate_patch(&old, &new);
+    let mut doc = old.clone();
+    apply_patch(&mut doc, &patch).expect("Generated patch should apply");
+    assert_eq!(doc, new, "Generated patch should transform old into new");
+
+    let mut doc = json!({ "a": [1, 2], "b": { "c": 3 } });
+    let patch = json!([
+        { "op": "add", "path": "/a/1", "value": 9 },
+        { "op": "copy", "from": "/b/c", "path": "/d" },
+        { "op": "move", "from": "/b", "path": "/e" },
+        { "op": "test", "path": "/d", "value": 3 },
+        { "op": "remove", "path": "/a/0" }
+    ]);
+    apply_patch(&mut doc, &patch).expect("Patch should apply");
+    assert_eq!(doc, json!({ "a": [9, 2], "d": 3, "e": { "c": 3 } }), "Operations should apply in order");
+
+    let failing = json!([
+        { "op": "replace", "path": "/d", "value": 4 },
+        { "op": "test", "path": "/d", "value": 3 }
+    ]);
+    assert!(apply_patch(&mut doc, &failing).is_err(), "Failed test operation should error");
+    assert_eq!(doc["d"], json!(3), "Failed patch should leave the document unchanged");
+}
+
+/// Tests generating an inverse migration and verifying the round trip on sample records.
+#[test]
+fn test_migration_inverse_round_trip() {
+    use json_parser_with_pest::Migration;
+
+    let migration = Migration::from_json(&json!([
+        { "op": "rename", "path": "user.name", "to": "fullName" },
+        { "op": "cast", "path": "age", "type": "integer", "from": "string" },
+        { "op": "move", "from": "zip", "to": "postalCode" }
+    ]))
+    .expect("Spec should parse");
+    let inverse = migration.inverse().expect("Migration should be reversible");
+    assert_eq!(
+        serde_json::to_value(&inverse).unwrap(),
+        json!({ "operations": [
+            { "op": "move", "from": "postalCode", "to": "zip" },
+            { "op": "cast", "path": "age", "type": "string", "from": "integer" },
+            { "op": "rename", "path": "user.fullName", "to": "name" }
+        ]}),
+        "Inverse should undo the operations in reverse order"
+    );
+
+    let sample = vec![
+        json!({ "user": { "name": "Ann" }, "age": "42", "zip": "0150" }),
+        json!({ "user": { "name": "Bob" }, "age": "042" }),
+    ];
+    assert_eq!(
+        migration.verify_round_trip(&inverse, &sample),
+        vec![1],
+        "A lossy cast should be reported as not round-tripping"
+    );
+
+    let with_default = Migration::from_json(&json!([{ "op": "default", "path": "x", "value": 1 }])).unwrap();
+    assert!(with_default.inverse().is_err(), "Defaults should not be reversible");
+}
+
+/// Tests namespace declarations, prefixed elements, and `xsi:nil` in XML conversion.
+#[test]
+fn test_json_to_xml_namespaces() {
+    use json_parser_with_pest::{json_to_xml, XmlOptions};
+
+    let mut options = XmlOptions {
+        root: "soap:Envelope".to_string(),
+        nil_nulls: true,
+        ..XmlOptions::default()
+    };
+    options
+        .namespaces
+        .insert("soap".to_string(), "http://schemas.xmlsoap.org/soap/envelope/".to_string());
+    let json = json!({ "soap:Body": { "name": "A & B", "middle": null } });
+    let xml = json_to_xml(&json, &options).expect("Conversion should succeed");
+
+    assert!(
+        xml.starts_with("<soap:Envelope xmlns:soap=\"http://schemas.xmlsoap.org/soap/envelope/\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">"),
+        "Root should declare namespaces: {}",
+        xml
+    );
+    assert!(xml.contains("<middle xsi:nil=\"true\" />"), "Null should use xsi:nil");
+    assert!(xml.contains("<name>A &amp; B</name>"), "Text should be escaped");
+    assert!(
+        json_to_xml(&json!({ "ns:x": 1 }), &XmlOptions::default()).is_err(),
+        "Undeclared prefixes should be rejected"
+    );
+}
+
+/// Tests the path-based diff report with one line per added, removed, or changed path.
+#[test]
+fn test_render_diff_paths() {
+    use json_parser_with_pest::{render_diff, DiffFormat, RenderOptions};
+
+    let old = json!({ "user": { "age": 30 }, "tags": ["a"], "legacy": true });
+    let new = json!({ "user": { "age": 31 }, "tags": ["a", "b"] });
+    let options = RenderOptions {
+        color: false,
+        ..RenderOptions::default()
+    };
+    let report = render_diff(&old, &new, DiffFormat::Paths, &options).expect("Failed to render paths");
+    assert_eq!(
+        report,
+        "- legacy: true\n+ tags[1]: \"b\"\n~ user.age: 30 -> 31\n",
+        "Unexpected path-based report"
+    );
+}
+
+/// Tests per-path attribute, CDATA, and element order mapping in XML conversion.
+#[test]
+fn test_json_to_xml_mapping() {
+    use json_parser_with_pest::{json_to_xml, XmlOptions};
+
+    let mut options = XmlOptions::default();
+    options
+        .apply_mapping(&json!({
+            "paths": { "order.id": "attribute", "order.note": "cdata", "order.items.sku": "attribute" },
+            "order": { "order": ["total", "items"] }
+        }))
+        .expect("Mapping should load");
+    let json = json!({
+        "order": {
+            "id": 7,
+            "items": [{ "sku": "A1", "qty": 2 }],
+            "note": "<fragile>",
+            "total": 9.5
+        }
+    });
+    let xml = json_to_xml(&json, &options).expect("Conversion should succeed");
+    assert_eq!(
+        xml,
+        "<root>\n<order id=\"7\">\n<total>9.5</total>\n<items sku=\"A1\">\n<qty>2</qty>\n</items>\n<note><![CDATA[<fragile>]]></note>\n</order>\n</root>\n",
+        "Unexpected mapped XML"
+    );
+
+    let mut invalid = XmlOptions::default();
+    invalid.apply_mapping(&json!({ "paths": { "order": "attribute" } })).unwrap();
+    assert!(json_to_xml(&json, &invalid).is_err(), "Objects cannot become attributes");
+}
+
+/// Tests deep merging with union-by-key arrays and null deletion.
+#[test]
+fn test_merge_json_strategies() {
+    use json_parser_with_pest::{merge_json, ArrayMerge, MergeStrategy, NullMerge};
+
+    let base = json!({
+        "db": { "host": "localhost", "port": 5432, "debug": true },
+        "services": [{ "name": "api", "replicas": 1 }, { "name": "worker", "replicas": 1 }],
+        "tags": ["a"]
+    });
+    let overlay = json!({
+        "db": { "host": "prod-db", "debug": null },
+        "services": [{ "name": "api", "replicas": 3 }, { "name": "cron" }],
+        "tags": ["b"]
+    });
+
+    let mut merged = base.clone();
+    merge_json(
+        &mut merged,
+        &overlay,
+        MergeStrategy {
+            arrays: ArrayMerge::UnionByKey("name".to_string()),
+            nulls: NullMerge::Delete,
+        },
+    );
+    assert_eq!(
+        merged,
+        json!({
+            "db": { "host": "prod-db", "port": 5432 },
+            "services": [
+                { "name": "api", "replicas": 3 },
+                { "name": "worker", "replicas": 1 },
+                { "name": "cron" }
+            ],
+            "tags": ["a", "b"]
+        }),
+        "Unexpected union-by-key merge"
+    );
+
+    let mut merged = base.clone();
+    merge_json(&mut merged, &overlay, MergeStrategy::default());
+    assert_eq!(merged["tags"], json!(["b"]), "Arrays should be replaced by default");
+    assert_eq!(merged["db"]["debug"], Value::Null, "Nulls should overwrite by default");
+}
+
+/// Tests XSD validation of generated XML with violations mapped back to JSON paths.
+#[test]
+fn test_validate_against_xsd() {
+    use json_parser_with_pest::{validate_against_xsd, XmlOptions};
+
+    let xsd = r#"<?xml version="1.0"?>
+<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:simpleType name="Status">
+    <xs:restriction base="xs:string">
+      <xs:enumeration value="open"/>
+      <xs:enumeration value="closed"/>
+    </xs:restriction>
+  </xs:simpleType>
+  <xs:element name="order">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="status" type="Status"/>
+        <xs:element name="item" maxOccurs="unbounded">
+          <xs:complexType>
+            <xs:sequence>
+              <xs:element name="qty" type="xs:positiveInteger"/>
+            </xs:sequence>
+            <xs:attribute name="sku" type="xs:string" use="required"/>
+          </xs:complexType>
+        </xs:element>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#;
+    let mut options = XmlOptions {
+        root: "order".to_string(),
+        ..XmlOptions::default()
+    };
+    options
+        .apply_mapping(&json!({ "paths": { "item.sku": "attribute" }, "order": { "": ["status", "item"] } }))
+        .unwrap();
+
+    let valid = json!({ "status": "open", "item": [{ "sku": "A", "qty": 1 }, { "sku": "B", "qty": 2 }] });
+    let violations = validate_against_xsd(&valid, &options, xsd).expect("XSD should parse");
+    assert!(violations.is_empty(), "Unexpected violations: {:?}", violations);
+
+    let invalid = json!({ "status": "pending", "item": [{ "sku": "A", "qty": 1 }, { "qty": 0 }] });
+    let paths: Vec<String> = validate_against_xsd(&invalid, &options, xsd)
+        .expect("XSD should parse")
+        .into_iter()
+        .map(|violation| violation.path)
+        .collect();
+    assert_eq!(paths, vec!["status", "item[1]", "item[1].qty"], "Violations should map to JSON paths");
+}
+
+/// Tests JSON Schema validation reporting every violation with its JSON Pointer.
+#[test]
+fn test_validate_schema_keywords() {
+    use json_parser_with_pest::validate_schema;
+
+    let schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "object",
+        "required": ["name", "age"],
+        "properties": {
+            "name": { "type": "string", "pattern": "^[A-Z]" },
+            "age": { "type": "integer", "minimum": 0, "maximum": 150 },
+            "role": { "enum": ["admin", "user"] },
+            "tags": { "type": "array", "items": { "type": "string" } },
+            "address": {
+                "type": "object",
+                "properties": { "zip": { "type": "string", "maxLength": 5 } }
+            }
+        }
+    });
+
+    let valid = json!({ "name": "Ann", "age": 30, "role": "admin", "tags": ["a"] });
+    assert!(validate_schema(&valid, &schema).is_empty(), "Valid document should pass");
+
+    let invalid = json!({
+        "name": "ann",
+        "role": "root",
+        "tags": ["a", 1],
+        "address": { "zip": "123456" }
+    });
+    let mut found: Vec<(String, String)> = validate_schema(&invalid, &schema)
+        .into_iter()
+        .map(|v| (v.pointer, v.keyword))
+        .collect();
+    found.sort();
+    let expected: Vec<(String, String)> = [
+        ("", "required"),
+        ("/address/zip", "maxLength"),
+        ("/name", "pattern"),
+        ("/role", "enum"),
+        ("/tags/1", "type"),
+    ]
+    .iter()
+    .map(|(p, k)| (p.to_string(), k.to_string()))
+    .collect();
+    assert_eq!(found, expected, "Every violation should be reported with its pointer");
+}
+
+/// Tests multi-document YAML parsing with anchors, merge keys, and JSON→YAML anchor factoring.
+#[test]
+fn test_yaml_documents_and_anchors() {
+    use json_parser_with_pest::{json_to_yaml, parse_yaml_documents, YamlOptions};
+    use serde_json::json;
+
+    let stream = "base: &base\n  host: localhost\n  port: 80\nprod:\n  <<: *base\n  port: 443\n---\n[1, 2]\n";
+    let documents = parse_yaml_documents(stream).expect("Valid YAML stream should parse");
+    assert_eq!(
+        documents,
+        vec![
+            json!({
+                "base": { "host": "localhost", "port": 80 },
+                "prod": { "host": "localhost", "port": 443 }
+            }),
+            json!([1, 2]),
+        ],
+        "Aliases and merge keys should be resolved per document"
+    );
+    assert!(
+        parse_yaml_documents("a: &x [1, *x]\n").is_err(),
+        "A self-referencing alias should be reported instead of looping"
+    );
+
+    let data = json!({ "a": { "k": [1, 2] }, "b": { "k": [1, 2] } });
+    let options = YamlOptions {
+        anchors: true,
+        ..YamlOptions::default()
+    };
+    let yaml = json_to_yaml(&data, &options).expect("Conversion should succeed");
+    assert!(yaml.contains("a: &a1"), "First occurrence should be anchored: {}", yaml);
+    assert!(yaml.contains("b: *a1"), "Repeat should be an alias: {}", yaml);
+    assert_eq!(
+        parse_yaml_documents(&yaml).expect("Anchored YAML should parse"),
+        vec![data],
+        "Anchored YAML should round-trip"
+    );
+}
+
+/// Tests YAML output style options: indent, flow collections, quoting, width, and markers.
+#[test]
+fn test_json_to_yaml_style_options() {
+    use json_parser_with_pest::{json_to_yaml, parse_yaml_documents, YamlOptions, YamlQuoting};
+    use serde_json::json;
+
+    let data = json!({
+        "name": "svc",
+        "ports": [80, 443],
+        "env": { "mode": "prod" },
+        "description": "a fairly long sentence that should be folded"
+    });
+    assert_eq!(
+        json_to_yaml(&data, &YamlOptions::default()).unwrap(),
+        serde_yaml::to_string(&data).unwrap(),
+        "Default options should match serde_yaml"
+    );
+
+    let options = YamlOptions {
+        indent: 4,
+        flow_max_items: 2,
+        quoting: YamlQuoting::Single,
+        document_markers: true,
+        ..YamlOptions::default()
+    };
+    let yaml = json_to_yaml(&data, &options).unwrap();
+    assert_eq!(
+        yaml,
+        "---\ndescription: 'a fairly long sentence that should be folded'\nenv: {mode: 'prod'}\nname: 'svc'\nports: [80, 443]\n...\n",
+        "Style options should be applied"
+    );
+
+    let options = YamlOptions {
+        width: 30,
+        ..YamlOptions::default()
+    };
+    let yaml = json_to_yaml(&data, &options).unwrap();
+    assert!(yaml.contains("description: >-\n"), "Long strings should be folded: {}", yaml);
+    assert!(
+        yaml.lines().all(|line| line.len() <= 30),
+        "Lines should respect the width: {}",
+        yaml
+    );
+    assert_eq!(
+        parse_yaml_documents(&yaml).unwrap(),
+        vec![data],
+        "Styled YAML should round-trip"
+    );
+}
+
+/// Tests that validation reports list every violation with path, expected, and actual values.
+#[test]
+fn test_validation_report() {
+    use json_parser_with_pest::validate_document;
+    use serde_json::json;
+
+    let schema = json!({
+        "type": "object",
+        "properties": { "age": { "type": "integer" }, "tags": { "maxItems": 1 } }
+    });
+    let report = validate_document(&json!({ "age": "old", "tags": [1, 2] }), &schema);
+    assert!(!report.is_valid(), "Report should contain violations");
+    let found: Vec<(&str, &str, &str)> = report
+        .violations
+        .iter()
+        .map(|v| (v.pointer.as_str(), v.expected.as_str(), v.actual.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("/age", "integer", "string"),
+            ("/tags", "at most 1 items", "2 items")
+        ],
+        "Every violation should carry its path, expected, and actual values"
+    );
+    assert_eq!(
+        report.to_string(),
+        "/age: expected integer, found string (type)\n/tags: expected at most 1 items, found 2 items (maxItems)",
+        "Display should print one violation per line"
+    );
+
+    let template = json!({ "name": "", "age": 0 });
+    let report = validate_document(&json!({ "name": "A", "extra": 1, "other": 2 }), &template);
+    assert_eq!(
+        report.to_json()["violations"]
+            .as_array()
+            .map(|v| v.iter().map(|v| v["path"].clone()).collect::<Vec<_>>()),
+        Some(vec![json!("/extra"), json!("/other")]),
+        "Key templates should report every undeclared key"
+    );
+}
+
+/// Tests TOML to JSON conversion, including datetimes, tables, and arrays of tables.
+#[test]
+fn test_parse_toml() {
+    use json_parser_with_pest::{parse_toml, TomlOptions};
+    use serde_json::json;
+
+    let toml = r#"
+title = "Example" # trailing comment
+[owner]
+dob = 1979-05-27 07:32:00z
+limits = { cpu = 0x10, memory.max = 1_024 }
+
+[[bin]]
+name = "a"
+[[bin]]
+name = 'b'
+tags = [
+  "x", # first
+  "y",
+]
+"#;
+    let json = parse_toml(toml, &TomlOptions::default()).expect("Valid TOML should parse");
+    assert_eq!(
+        json,
+        json!({
+            "title": "Example",
+            "owner": {
+                "dob": "1979-05-27T07:32:00Z",
+                "limits": { "cpu": 16, "memory": { "max": 1024 } }
+            },
+            "bin": [{ "name": "a" }, { "name": "b", "tags": ["x", "y"] }]
+        }),
+        "TOML should map onto JSON with RFC 3339 datetimes"
+    );
+
+    let annotated = parse_toml("day = 2024-02-29\n", &TomlOptions { annotate_types: true })
+        .expect("Valid TOML should parse");
+    assert_eq!(
+        annotated,
+        json!({ "day": { "type": "date-local", "value": "2024-02-29" } }),
+        "Datetimes should carry their TOML type when annotated"
+    );
+    assert!(
+        parse_toml("a = 1\na = 2\n", &TomlOptions::default()).is_err(),
+        "Duplicate keys should be rejected"
+    );
+}
+
+/// Tests `$ref` resolution to internal definitions and relative schema files.
+#[test]
+fn test_validate_schema_refs() {
+    use json_parser_with_pest::validate_schema_at;
+    use serde_json::json;
+
+    let dir = tempdir().expect("Failed to create temp dir");
+    std::fs::write(
+        dir.path().join("common.json"),
+        r##"{ "$defs": { "id": { "type": "integer", "minimum": 1 }, "ids": { "items": { "$ref": "#/$defs/id" } } } }"##,
+    )
+    .expect("Failed to write schema file");
+
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "owner": { "$ref": "#/definitions/person" },
+            "ids": { "$ref": "common.json#/$defs/ids" },
+            "loop": { "$ref": "#/definitions/loop" },
+            "missing": { "$ref": "#/definitions/nope" }
+        },
+        "definitions": {
+            "person": { "required": ["name"], "properties": { "id": { "$ref": "common.json#/$defs/id" } } },
+            "loop": { "$ref": "#/definitions/loop" }
+        }
+    });
+    let document = json!({ "owner": { "id": 0 }, "ids": [1, "x"], "loop": 1, "missing": 1 });
+    let mut found: Vec<(String, String)> = validate_schema_at(&document, &schema, dir.path())
+        .into_iter()
+        .map(|v| (v.pointer, v.keyword))
+        .collect();
+    found.sort();
+    let expected: Vec<(String, String)> = [
+        ("/ids/1", "type"),
+        ("/loop", "$ref"),
+        ("/missing", "$ref"),
+        ("/owner", "required"),
+        ("/owner/id", "minimum"),
+    ]
+    .iter()
+    .map(|(p, k)| (p.to_string(), k.to_string()))
+    .collect();
+    assert_eq!(found, expected, "References should resolve across documents");
+}
+
+/// Tests the Cargo preset on a parsed manifest and lock file.
+#[test]
+fn test_cargo_report() {
+    use json_parser_with_pest::{cargo_report, parse_toml, query_jsonpath, TomlOptions};
+    use serde_json::json;
+
+    let manifest = parse_toml(
+        r#"
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[features]
+default = ["fast"]
+fast = ["dep:rayon"]
+
+[dependencies]
+serde = { version = "1", features = ["derive"], default-features = false }
+rayon = { version = "1.8", optional = true }
+
+[dev-dependencies]
+tempfile = "3"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+"#,
+        &TomlOptions::default(),
+    )
+    .expect("Manifest should parse");
+    let lock = parse_toml(
+        r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["serde", "libc"]
+
+[[package]]
+name = "libc"
+version = "0.2.150"
+
+[[package]]
+name = "serde"
+version = "1.0.190"
+dependencies = ["libc"]
+"#,
+        &TomlOptions::default(),
+    )
+    .expect("Lock file should parse");
+
+    let report = cargo_report(&manifest, Some(&lock)).expect("Report should build");
+    assert_eq!(report["package"]["name"], json!("app"));
+    assert_eq!(report["features"]["fast"], json!(["dep:rayon"]));
+    assert_eq!(
+        query_jsonpath(&report, "$.dependencies[?(@.optional == true)].name").unwrap(),
+        vec![json!("rayon")],
+        "Optional dependencies should be queryable"
+    );
+    assert_eq!(
+        query_jsonpath(&report, "$.dependencies[?(@.target == 'cfg(unix)')].name").unwrap(),
+        vec![json!("libc")],
+        "Target-specific dependencies should carry their target"
+    );
+    let serde = &query_jsonpath(&report, "$.dependencies[?(@.name == 'serde')]").unwrap()[0];
+    assert_eq!(serde["default_features"], json!(false));
+    assert_eq!(serde["features"], json!(["derive"]));
+    assert_eq!(
+        report["lock"]["tree"],
+        json!({
+            "name": "app",
+            "version": "0.1.0",
+            "dependencies": [
+                {
+                    "name": "serde",
+                    "version": "1.0.190",
+                    "dependencies": [{ "name": "libc", "version": "0.2.150", "dependencies": [] }]
+                },
+                { "name": "libc", "version": "0.2.150", "deduplicated": true }
+            ]
+        }),
+        "The lock tree should expand each package once"
+    );
+}
+
+/// Tests the npm audit preset on package.json and a version 3 lockfile.
+#[test]
+fn test_npm_audit() {
+    use json_parser_with_pest::npm_audit;
+    use serde_json::json;
+
+    let package = json!({
+        "name": "web",
+        "version": "1.0.0",
+        "scripts": { "build": "tsc  -p .", "compile": "tsc -p .", "test": "jest" },
+        "dependencies": { "lodash": "^4.17.0", "react": "^18.0.0" },
+        "devDependencies": { "lodash": "^4.17.0", "jest": "^29.0.0" }
+    });
+    let lock = json!({
+        "lockfileVersion": 3,
+        "packages": {
+            "": { "name": "web", "version": "1.0.0" },
+            "node_modules/lodash": { "version": "4.17.21" },
+            "node_modules/react": { "version": "18.2.0" },
+            "node_modules/jest": { "version": "29.7.0", "dev": true },
+            "node_modules/jest/node_modules/lodash": { "version": "4.17.15", "dev": true },
+            "node_modules/@types/node": { "version": "20.1.0", "dev": true }
+        }
+    });
+
+    let report = npm_audit(&package, Some(&lock), 1).expect("Audit should succeed");
+    assert_eq!(
+        report["declared_twice"],
+        json!([{ "name": "lodash", "kinds": ["prod", "dev"] }]),
+        "Dependencies declared in several sections should be reported"
+    );
+    assert_eq!(
+        report["scripts"]["duplicates"],
+        json!([{ "command": "tsc -p .", "scripts": ["build", "compile"] }]),
+        "Scripts running the same command should be grouped"
+    );
+    assert_eq!(report["lock"]["installs"], json!(5));
+    assert_eq!(
+        report["lock"]["duplicates"],
+        json!([{ "name": "lodash", "versions": ["4.17.15", "4.17.21"] }]),
+        "Packages installed in several versions should be reported"
+    );
+    assert_eq!(
+        report["lock"]["most_installed"],
+        json!([{ "value": "lodash", "count": 2 }]),
+        "The most installed package should come from the frequency sketch"
+    );
+}
+
+/// Tests that `handle_large_json` yields every top-level value and supports early exit.
+#[test]
+fn test_handle_large_json_iterator() {
+    use std::ops::ControlFlow;
+
+    let dir = tempdir().expect("Failed to create temp dir");
+    let file_path = dir.path().join("stream.json");
+    std::fs::write(&file_path, "{\"id\": 1}\n{\"id\": 2}\n[3]\n{\"id\": ").expect("Failed to write file");
+
+    let values: Vec<_> = json_parser_with_pest::handle_large_json(&file_path)
+        .expect("File should open")
+        .collect();
+    assert_eq!(values.len(), 4, "Three values and one error should be yielded");
+    assert_eq!(values[1].as_ref().ok(), Some(&json!({ "id": 2 })));
+    assert!(values[3].is_err(), "The truncated value should be reported as an error");
+
+    let mut seen = Vec::new();
+    let flow = json_parser_with_pest::handle_large_json(&file_path)
+        .expect("File should open")
+        .try_for_each(|value| match value {
+            Ok(value) if value.get("id") == Some(&json!(2)) => ControlFlow::Break(value),
+            Ok(value) => {
+                seen.push(value);
+                ControlFlow::Continue(())
+            }
+            Err(_) => ControlFlow::Continue(()),
+        });
+    assert_eq!(flow, ControlFlow::Break(json!({ "id": 2 })), "Iteration should stop early");
+    assert_eq!(seen, vec![json!({ "id": 1 })]);
+}
+
+/// Test the tfstate preset: instance addresses, provider aliases, and masking of
+/// sensitive outputs and attributes.
+#[test]
+fn test_tfstate_report() {
+    use json_parser_with_pest::tfstate_report;
+
+    let state = json!({
+        "version": 4,
+        "terraform_version": "1.5.7",
+        "serial": 12,
+        "lineage": "abc",
+        "outputs": {
+            "db_password": { "value": "hunter2", "type": "string", "sensitive": true },
+            "url": { "value": "https://example.com", "type": "string" }
+        },
+        "resources": [
+            {
+                "mode": "managed",
+                "type": "aws_db_instance",
+                "name": "main",
+                "module": "module.db",
+                "provider": "provider[\"registry.terraform.io/hashicorp/aws\"].west",
+                "instances": [{
+                    "schema_version": 2,
+                    "attributes": { "id": "db-1", "password": "hunter2", "tags": ["a", "b"] },
+                    "sensitive_attributes": [
+                        [{ "type": "get_attr", "value": "password" }],
+                        [{ "type": "get_attr", "value": "tags" }, { "type": "index", "value": { "value": 1, "type": "number" } }]
+                    ]
+                }]
+            },
+            {
+                "mode": "data",
+                "type": "aws_ami",
+                "name": "ubuntu",
+                "provider": "provider[\"registry.terraform.io/hashicorp/aws\"]",
+                "instances": [
+                    { "index_key": 0, "schema_version": 0, "attributes": { "id": "ami-0" } },
+                    { "index_key": "eu", "schema_version": 0, "attributes": { "id": "ami-1" } }
+                ]
+            }
+        ]
+    });
+
+    let report = tfstate_report(&state, true).unwrap();
+    assert_eq!(report["terraform_version"], json!("1.5.7"));
+    let addresses: Vec<&str> = report["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["address"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        addresses,
+        vec![
+            "module.db.aws_db_instance.main",
+            "data.aws_ami.ubuntu[0]",
+            "data.aws_ami.ubuntu[\"eu\"]"
+        ],
+        "Addresses should include module, mode and index key"
+    );
+    assert_eq!(report["resources"][0]["attributes"]["password"], json!("***"));
+    assert_eq!(report["resources"][0]["attributes"]["tags"], json!(["a", "***"]));
+    assert_eq!(report["outputs"]["db_password"]["value"], json!("***"));
+    assert_eq!(report["outputs"]["url"]["value"], json!("https://example.com"));
+    assert_eq!(
+        report["providers"],
+        json!([
+            { "address": "registry.terraform.io/hashicorp/aws", "alias": null, "resources": 2, "schema_versions": [0] },
+            { "address": "registry.terraform.io/hashicorp/aws", "alias": "west", "resources": 1, "schema_versions": [2] }
+        ]),
+        "Providers should be grouped by address and alias"
+    );
+
+    let shown = tfstate_report(&state, false).unwrap();
+    assert_eq!(shown["outputs"]["db_password"]["value"], json!("hunter2"));
+    assert_eq!(shown["resources"][0]["attributes"]["password"], json!("hunter2"));
+
+    assert!(tfstate_report(&json!({ "version": 4 }), true).is_err());
+}
+
+/// Test JSON Feed validation and the round trip through RSS and Atom.
+#[test]
+fn test_json_feed_conversion() {
+    use json_parser_with_pest::{json_feed_to_xml, validate_json_feed, xml_to_json_feed, FeedFormat};
+
+    let feed = json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": "My Blog",
+        "home_page_url": "https://example.org/",
+        "feed_url": "https://example.org/feed.json",
+        "items": [{
+            "id": "2",
+            "url": "https://example.org/second",
+            "title": "Second & last",
+            "content_html": "<p>Hello</p>",
+            "date_published": "2010-02-07T14:04:00-05:00",
+            "tags": ["rust", "json"]
+        }]
+    });
+    assert!(validate_json_feed(&feed).is_valid());
+
+    let invalid = json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": "My Blog",
+        "items": [{ "id": "1", "date_published": "yesterday" }]
+    });
+    let report = validate_json_feed(&invalid);
+    let paths: Vec<&str> = report.violations.iter().map(|v| v.pointer.as_str()).collect();
+    assert!(paths.contains(&"/items/0"), "Missing content should be reported: {}", report);
+    assert!(paths.contains(&"/items/0/date_published"), "Bad date should be reported: {}", report);
+    assert!(json_feed_to_xml(&invalid, FeedFormat::Rss).is_err());
+
+    let rss = json_feed_to_xml(&feed, FeedFormat::Rss).unwrap();
+    assert!(rss.starts_with("<rss version=\"2.0\">"), "Unexpected RSS: {}", rss);
+    assert!(rss.contains("<pubDate>Sun, 07 Feb 2010 14:04:00 -0500</pubDate>"));
+    assert!(rss.contains("<guid isPermaLink=\"false\">2</guid>"));
+    let from_rss = xml_to_json_feed(&rss).unwrap();
+    assert_eq!(from_rss["items"][0]["title"], json!("Second & last"));
+    assert_eq!(from_rss["items"][0]["content_html"], json!("<p>Hello</p>"));
+    assert_eq!(from_rss["items"][0]["date_published"], json!("2010-02-07T14:04:00-05:00"));
+    assert_eq!(from_rss["items"][0]["tags"], json!(["rust", "json"]));
+    assert!(validate_json_feed(&from_rss).is_valid());
+
+    let atom = json_feed_to_xml(&feed, FeedFormat::Atom).unwrap();
+    assert!(atom.starts_with("<feed xmlns=\"http://www.w3.org/2005/Atom\">"), "Unexpected Atom: {}", atom);
+    let from_atom = xml_to_json_feed(&atom).unwrap();
+    assert_eq!(from_atom["feed_url"], json!("https://example.org/feed.json"));
+    assert_eq!(from_atom["items"][0]["url"], json!("https://example.org/second"));
+    assert_eq!(from_atom["items"][0]["content_html"], json!("<p>Hello</p>"));
+    assert_eq!(from_atom["items"][0]["tags"], json!(["rust", "json"]));
+    assert!(validate_json_feed(&from_atom).is_valid());
+}
+
+/// Test pretty-printing with spaces, tabs, compact arrays and the trailing newline.
+#[test]
+fn test_format_json() {
+    use json_parser_with_pest::{format_json, FormatOptions};
+
+    let value = json!({ "b": [1, 2], "a": { "c": [], "d": [{ "e": null }] }, "f": {} });
+    let default = format_json(&value, &FormatOptions::default());
+    assert_eq!(
+        default,
+        "{\n  \"a\": {\n    \"c\": [],\n    \"d\": [\n      {\n        \"e\": null\n      }\n    ]\n  },\n  \"b\": [\n    1,\n    2\n  ],\n  \"f\": {}\n}\n"
+    );
+    assert_eq!(
+        serde_json::from_str::<Value>(&default).unwrap(),
+        value,
+        "Formatted output should parse back to the same value"
+    );
+
+    let options = FormatOptions {
+        use_tabs: true,
+        sort_keys: true,
+        trailing_newline: false,
+        compact_arrays: true,
+        ..FormatOptions::default()
+    };
+    assert_eq!(
+        format_json(&value, &options),
+        "{\n\t\"a\": {\n\t\t\"c\": [],\n\t\t\"d\": [\n\t\t\t{\n\t\t\t\t\"e\": null\n\t\t\t}\n\t\t]\n\t},\n\t\"b\": [1, 2],\n\t\"f\": {}\n}"
+    );
+
+    let four = FormatOptions { indent: 4, ..FormatOptions::default() };
+    assert_eq!(format_json(&json!([true]), &four), "[\n    true\n]\n");
+}
+
+/// Test RFC 8785 canonicalization: key order, number formatting, and string escapes.
+#[test]
+fn test_canonicalize() {
+    use json_parser_with_pest::canonicalize;
+
+    // Example from RFC 8785, section 3.2.2.
+    let mut input: Value = serde_json::from_str(
+        r#"{
+            "string": "€$\u000F\u000aA'B\"\\\\\"\/",
+            "literals": [null, true, false]
+        }"#,
+    )
+    .unwrap();
+    // Built from Rust literals, since serde_json's default float parsing is not exact.
+    input["numbers"] = json!([333333333.3333333, 1E30, 4.50, 2e-3, 0.000000000000000000000000001]);
+    assert_eq!(
+        canonicalize(&input).unwrap(),
+        r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+    );
+
+    // Keys are ordered by UTF-16 code units, which puts U+1F600 before U+FB33.
+    let keys = json!({ "\u{fb33}": 1, "\u{1f600}": 2, "a": 3 });
+    assert_eq!(canonicalize(&keys).unwrap(), "{\"a\":3,\"\u{1f600}\":2,\"\u{fb33}\":1}");
+
+    assert_eq!(canonicalize(&json!([-0.0, 1e21, 1e20, -1.5e-7, 100])).unwrap(), "[0,1e+21,100000000000000000000,-1.5e-7,100]");
+    assert!(canonicalize(&json!(9007199254740993u64)).is_err(), "Integers beyond 2^53 lose precision");
+}
+
+/// Test enrichment from exact, prefix and CIDR lookup tables, including a CSV file.
+#[test]
+fn test_migration_enrich() {
+    use json_parser_with_pest::migrate::{migrate_records, Migration};
+
+    let dir = tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("networks.csv"),
+        "network,country,\"name\"\n10.0.0.0/8,XX,\"Private, class A\"\n10.1.0.0/16,YY,Office\n2001:db8::/32,ZZ,Docs\n",
+    )
+    .unwrap();
+    let mut migration = Migration::from_json(&json!([
+        { "op": "enrich", "path": "ip", "to": "geo.country", "table": "networks.csv",
+          "key": "network", "field": "country", "match": "cidr" },
+        { "op": "enrich", "path": "ip", "to": "network", "table": "networks.csv",
+          "key": "network", "match": "cidr" },
+        { "op": "enrich", "path": "status", "to": "status_text", "table": { "200": "OK", "404": "Not Found" } },
+        { "op": "enrich", "path": "phone", "to": "region", "table": { "+1": "NANP", "+44": "UK" }, "match": "prefix" }
+    ]))
+    .unwrap();
+    let unloaded = migrate_records(&mut [json!({ "ip": "10.0.0.1" })], &migration);
+    assert_eq!(unloaded.failures.len(), 1, "File tables must be loaded first");
+
+    migration.load_tables(dir.path()).unwrap();
+    let mut records = vec![
+        json!({ "ip": "10.1.2.3", "status": 404, "phone": "+44 20 7946 0000" }),
+        json!({ "ip": "10.200.0.1" }),
+        json!({ "ip": "2001:db8::1" }),
+        json!({ "ip": "192.168.0.1", "status": 500 }),
+    ];
+    let report = migrate_records(&mut records, &migration);
+    assert_eq!(report.migrated, 4, "{:?}", report.failures);
+    assert_eq!(records[0]["geo"]["country"], json!("YY"), "The longest network should win");
+    assert_eq!(records[0]["network"], json!({ "country": "YY", "name": "Office" }));
+    assert_eq!(records[0]["status_text"], json!("Not Found"));
+    assert_eq!(records[0]["region"], json!("UK"));
+    assert_eq!(records[1]["network"]["name"], json!("Private, class A"));
+    assert_eq!(records[2]["geo"]["country"], json!("ZZ"));
+    assert_eq!(records[3], json!({ "ip": "192.168.0.1", "status": 500 }), "Unmatched records stay unchanged");
+    assert!(migration.inverse().is_err());
+}
+
+/// Test expressions and computed fields in migrations.
+#[test]
+fn test_computed_fields() {
+    use json_parser_with_pest::migrate::{migrate_records, Migration};
+    use json_parser_with_pest::{query_jsonpath, Expression};
+
+    let record = json!({ "first": "Ada", "last": "Lovelace", "start": 100, "end": 142.5, "n": 7, "user": { "age": 36 } });
+    let eval = |text: &str| Expression::compile(text).unwrap().evaluate(&record);
+    assert_eq!(eval("first + \" \" + last"), Some(json!("Ada Lovelace")));
+    assert_eq!(eval("end - start"), Some(json!(42.5)));
+    assert_eq!(eval("n % 4 + n * 2"), Some(json!(17)), "Integers should stay integers");
+    assert_eq!(eval("n / 2"), Some(json!(3.5)));
+    assert_eq!(eval("(n - 1) * 2"), Some(json!(12)));
+    assert_eq!(eval("user.age >= 18 && @.first == 'Ada'"), Some(json!(true)));
+    assert_eq!(eval("$.user.age + 1"), Some(json!(37)));
+    assert_eq!(eval("missing + 1"), None);
+    assert_eq!(eval("n / 0"), None);
+    assert!(Expression::compile("first +").is_err());
+
+    let books = json!([{ "price": 8, "qty": 20 }, { "price": 30, "qty": 2 }]);
+    assert_eq!(query_jsonpath(&books, "$[?(@.price * @.qty > 100)].qty").unwrap(), vec![json!(20)]);
+
+    let migration = Migration::from_json(&json!([
+        { "op": "compute", "path": "full_name", "expr": "first + ' ' + last" },
+        { "op": "compute", "path": "timing.latency_ms", "expr": "end - start" }
+    ]))
+    .unwrap();
+    let mut records = vec![record.clone(), json!({ "first": "Alan", "last": "Turing" })];
+    let report = migrate_records(&mut records, &migration);
+    assert_eq!(report.migrated, 2);
+    assert_eq!(records[0]["full_name"], json!("Ada Lovelace"));
+    assert_eq!(records[0]["timing"]["latency_ms"], json!(42.5));
+    assert_eq!(records[1]["full_name"], json!("Alan Turing"));
+    assert!(records[1].get("timing").is_none(), "Missing operands should leave the record unchanged");
+}
+
+/// Test that parse errors carry their position, snippet and expected tokens.
+#[test]
+fn test_parse_error_position() {
+    use json_parser_with_pest::{parse_json, ParserError};
+
+    let input = "{\n  \"a\": 1,\n  \"b\": [1, 2,]\n}";
+    let Err(ParserError::JsonParseError(details)) = parse_json(input) else {
+        panic!("Expected a JsonParseError");
+    };
+    assert_eq!((details.line, details.column), (3, 14));
+    assert_eq!(details.offset, Some(input.find(",]").unwrap() + 1));
+    assert_eq!(details.snippet, "  \"b\": [1, 2,]");
+    assert!(details.expected.contains(&"number".to_string()), "{:?}", details.expected);
+    assert_eq!(
+        details.excerpt().lines().collect::<Vec<_>>()[..2],
+        ["3 |   \"b\": [1, 2,]", "  |              ^ expected object, array, string, number, boolean, null, date, identifier, version, key value array"][..]
+    );
+    let message = ParserError::JsonParseError(details).to_string();
+    assert!(message.starts_with("JSON parse error at line 3, column 14: expected"), "{}", message);
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("broken.ndjson");
+    std::fs::write(&path, "{\"a\": 1}\n{\"a\": }\n").unwrap();
+    let errors: Vec<_> = json_parser_with_pest::handle_large_json(&path)
+        .unwrap()
+        .filter_map(Result::err)
+        .collect();
+    match errors.as_slice() {
+        [ParserError::JsonParseError(details)] => assert_eq!(details.line, 2),
+        other => panic!("Expected one parse error, got {:?}", other),
+    }
+}
+
+/// Test routing NDJSON records to several outputs, the default output, and quarantine.
+#[test]
+fn test_route_ndjson() {
+    use json_parser_with_pest::{route_ndjson, RouteConfig};
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("events.ndjson");
+    std::fs::write(
+        &input,
+        "{\"level\": \"error\", \"ms\": 900}\n{\"level\": \"info\", \"ms\": 1500}\nnot json\n{\"level\": \"info\", \"ms\": 5}\n",
+    )
+    .unwrap();
+    let output = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+    let config = RouteConfig::from_json(&json!({
+        "outputs": {
+            "errors": output("errors.ndjson"),
+            "slow": output("slow.ndjson"),
+            "rest": output("rest.ndjson"),
+            "bad": output("bad.ndjson")
+        },
+        "rules": [
+            { "when": "level == 'error'", "output": "errors" },
+            { "when": "ms > 500", "output": "slow" }
+        ],
+        "default": "rest",
+        "quarantine": "bad"
+    }))
+    .unwrap();
+    let report = route_ndjson(&input, &config).unwrap();
+    assert_eq!(report.total, 4);
+    assert_eq!(report.invalid, 1);
+    assert_eq!(
+        report.to_json()["routed"],
+        json!({ "errors": 1, "slow": 2, "rest": 1, "bad": 1 }),
+        "The first record should fan out to both errors and slow"
+    );
+    let read = |name: &str| std::fs::read_to_string(output(name)).unwrap();
+    assert_eq!(read("errors.ndjson"), "{\"level\": \"error\", \"ms\": 900}\n");
+    assert_eq!(read("rest.ndjson"), "{\"level\": \"info\", \"ms\": 5}\n");
+    let quarantined: Value = serde_json::from_str(read("bad.ndjson").trim()).unwrap();
+    assert_eq!(quarantined["line"], json!(3));
+    assert_eq!(quarantined["text"], json!("not json"));
+
+    let unknown = RouteConfig::from_json(&json!({ "outputs": {}, "rules": [{ "when": "true", "output": "x" }] }));
+    assert!(unknown.is_err(), "Rules must name declared outputs");
+}
+
+/// Test that parse failures collect their context as diagnostics instead of printing.
+#[test]
+fn test_parse_error_diagnostics() {
+    use json_parser_with_pest::{parse_json, ParserError};
+
+    let input = "{\n  \"name\": \"x\",\n  \"tags\": [1, 2,]\n}";
+    let Err(ParserError::JsonParseError(details)) = parse_json(input) else {
+        panic!("Expected a JsonParseError");
+    };
+    let notes: Vec<String> = details.diagnostics.iter().map(|d| d.to_string()).collect();
+    assert_eq!(
+        notes,
+        vec!["in array (line 3, column 11)", "in object (line 1, column 1)"],
+        "Diagnostics should list the enclosing containers, innermost first"
+    );
+    assert!(details.excerpt().ends_with("note: in array (line 3, column 11)\nnote: in object (line 1, column 1)"));
+}
+
+/// Tests that lenient parsing reports every syntax error and keeps the valid parts.
+#[test]
+fn test_parse_json_with_recovery() {
+    use json_parser_with_pest::parse_json_with_recovery;
+
+    let input = "{\n  \"name\": \"John\",\n  \"age\": 30 \"city\": Paris,\n  \"tags\": [1, 2,],\n}";
+    let (value, diagnostics) = parse_json_with_recovery(input);
+    assert_eq!(value, Some(json!({ "name": "John", "age": 30, "tags": [1, 2] })));
+    let found: Vec<(usize, usize)> = diagnostics.iter().map(|d| (d.line, d.column)).collect();
+    assert_eq!(found, vec![(3, 13), (3, 21), (4, 16), (4, 18)]);
+    assert!(diagnostics[1].to_string().contains("strings must be quoted"));
+
+    let (value, diagnostics) = parse_json_with_recovery("[1, {\"a\": true}");
+    assert_eq!(value, Some(json!([1, { "a": true }])));
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].to_string().contains("unclosed array"));
+
+    let (value, diagnostics) = parse_json_with_recovery(r#"{"a": [1, 2.5e3, "x\n"]}"#);
+    assert_eq!(value, Some(json!({ "a": [1, 2500.0, "x\n"] })));
+    assert!(diagnostics.is_empty());
+}
+
+/// Tests tumbling and sliding window aggregation over an NDJSON event stream.
+#[test]
+fn test_aggregate_windows() {
+    use json_parser_with_pest::{aggregate_windows, WindowOptions};
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("events.ndjson");
+    let output = dir.path().join("windows.ndjson");
+    std::fs::write(
+        &input,
+        [
+            r#"{"ts": "2024-01-01T00:00:05Z", "route": "/a", "ms": 10}"#,
+            r#"{"ts": "2024-01-01T00:00:30Z", "route": "/b", "ms": 30}"#,
+            r#"{"ts": "2024-01-01T01:00:45+01:00", "route": "/a", "ms": 20}"#,
+            r#"{"ts": "2024-01-01T00:01:10Z", "route": "/a", "ms": 40}"#,
+            r#"{"ts": "2024-01-01T00:00:50Z", "route": "/a", "ms": 99}"#,
+            r#"{"route": "/a"}"#,
+        ]
+        .join("\n"),
+    )
+    .unwrap();
+    let read = |path: &std::path::Path| -> Vec<Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    let mut options = WindowOptions::new("ts", 60);
+    options.group_by = Some("route".to_string());
+    options.value = Some("ms".to_string());
+    options.percentiles = vec![50.0, 100.0];
+    options.precision = Some(2);
+    assert_eq!(aggregate_windows(&input, &output, &options).unwrap(), 2);
+    let windows = read(&output);
+    assert_eq!(
+        windows[0],
+        json!({
+            "start": "2024-01-01T00:00:00Z", "end": "2024-01-01T00:01:00Z",
+            "count": 3, "sum": 60.0, "min": 10.0, "max": 30.0, "mean": 20.0, "stddev": 10.0,
+            "p50": 20.0, "p100": 30.0,
+            "groups": {
+                "/a": {
+                    "count": 2, "sum": 30.0, "min": 10.0, "max": 20.0, "mean": 15.0, "stddev": 7.07,
+                    "p50": 10.0, "p100": 20.0
+                },
+                "/b": {
+                    "count": 1, "sum": 30.0, "min": 30.0, "max": 30.0, "mean": 30.0, "stddev": 0.0,
+                    "p50": 30.0, "p100": 30.0
+                }
+            }
+        })
+    );
+    assert_eq!(windows[1]["count"], json!(1));
+
+    let mut options = WindowOptions::new("t", 20);
+    options.slide = Some(10);
+    std::fs::write(&input, "{\"t\": 5}\n{\"t\": 12}\n{\"t\": 25}\n").unwrap();
+    assert_eq!(aggregate_windows(&input, &output, &options).unwrap(), 4);
+    let counts: Vec<(Value, Value)> = read(&output)
+        .iter()
+        .map(|w| (w["start"].clone(), w["count"].clone()))
+        .collect();
+    assert_eq!(
+        counts,
+        vec![
+            (json!(-10), json!(1)),
+            (json!(0), json!(2)),
+            (json!(10), json!(2)),
+            (json!(20), json!(1))
+        ]
+    );
+}
+
+/// Tests JSON5 parsing: comments, identifier keys, single quotes, trailing commas and extended numbers.
+#[test]
+fn test_parse_json5() {
+    use json_parser_with_pest::{parse_json5, ParserError};
+
+    let input = r#"
+        // Frontend config
+        {
+            name: 'app',
+            $schema: "v1",
+            port: 0x1F90, /* dev server */
+            ratio: .5,
+            scale: +2.,
+            limits: [-0xff, Infinity, NaN,],
+            quote: 'It\'s "fine"',
+            multi: 'a\
+b',
+            nullable: null,
+        }
+    "#;
+    assert_eq!(
+        parse_json5(input).unwrap(),
+        json!({
+            "name": "app",
+            "$schema": "v1",
+            "port": 8080,
+            "ratio": 0.5,
+            "scale": 2.0,
+            "limits": [-255, null, null],
+            "quote": "It's \"fine\"",
+            "multi": "ab",
+            "nullable": null
+        })
+    );
+    assert_eq!(
+        parse_json5(r#"{"a": [1, 2.5e3, "x\ny"]}"#).unwrap(),
+        json!({ "a": [1, 2500.0, "x\ny"] })
+    );
+
+    let error = parse_json5("{ a: 1,, }").unwrap_err();
+    match error {
+        ParserError::JsonParseError(details) => assert_eq!((details.line, details.column), (1, 8)),
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert!(parse_json5("{ a: 01 }").is_err());
+    assert!(parse_json5("{ 'a': 1 } // done").is_ok());
+}
+
+/// Tests that the t-digest estimates tail percentiles of a large stream within tolerance.
+#[test]
+fn test_tdigest_percentiles() {
+    use json_parser_with_pest::TDigest;
+
+    let mut digest = TDigest::new(100.0);
+    let mut other = TDigest::new(100.0);
+    // A fixed permutation of 1..=100_000 so values do not arrive in order.
+    for i in 0..100_000u64 {
+        let value = (i * 7919 % 100_000 + 1) as f64;
+        if i % 2 == 0 {
+            digest.insert(value);
+        } else {
+            other.insert(value);
+        }
+    }
+    digest.merge(&other);
+    assert_eq!(digest.count(), 100_000);
+    for (q, expected) in [(0.5, 50_000.0), (0.9, 90_000.0), (0.99, 99_000.0), (0.999, 99_900.0)] {
+        let estimate = digest.quantile(q).unwrap();
+        assert!(
+            (estimate - expected).abs() <= 100_000.0 * 0.002,
+            "q{} = {} (expected {})",
+            q,
+            estimate,
+            expected
+        );
+    }
+    assert_eq!(digest.quantile(0.0), Some(1.0));
+    assert_eq!(digest.quantile(1.0), Some(100_000.0));
+    assert_eq!(TDigest::default().quantile(0.5), None);
+}
+
+/// Tests that explain reports the paths, plan and the step where a query stops matching.
+#[test]
+fn test_explain_query() {
+    use json_parser_with_pest::explain;
+
+    let document = json!({
+        "store": { "book": [{ "title": "A", "price": 5 }, { "title": "B", "price": 20 }] },
+        "limit": 10
+    });
+    let report = explain("$.store.books[?(@.price > 10)].title", Some(&document)).unwrap();
+    assert_eq!(report["kind"], json!("query"));
+    assert_eq!(
+        report["paths"],
+        json!(["$.store.books[*].price", "$.store.books[?(@.price > 10)].title"])
+    );
+    assert_eq!(report["plan"]["mode"], json!("streaming"));
+    assert_eq!(report["plan"]["empty_at"], json!(2));
+    let matched: Vec<Value> = report["plan"]["steps"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|step| step["matched"].clone())
+        .collect();
+    assert_eq!(matched, vec![json!(1), json!(0), json!(0), json!(0)]);
+    assert_eq!(report["ast"][2]["selectors"][0]["expr"]["operand"]["type"], json!("group"));
+
+    let report = explain("$..book[?(@.price > $.limit)]", Some(&document)).unwrap();
+    assert_eq!(report["plan"]["mode"], json!("full-document"));
+    assert_eq!(report["plan"]["reasons"].as_array().unwrap().len(), 2);
+    assert_eq!(report["plan"]["matched"], json!(1));
+
+    let report = explain("price * qty > 100", Some(&json!({ "price": 50 }))).unwrap();
+    assert_eq!(report["kind"], json!("expression"));
+    assert_eq!(report["paths"], json!(["$.price", "$.qty"]));
+    assert_eq!(report["plan"]["operands"][1], json!({ "path": "@.qty", "found": false, "value": null }));
+    assert_eq!(report["ast"]["left"]["op"], json!("*"));
+
+    assert!(explain("$.store[", None).is_err());
+}
+
+/// Tests JSONC parsing and comment stripping, keeping error positions in the original text.
+#[test]
+fn test_parse_jsonc() {
+    use json_parser_with_pest::{parse_jsonc, strip_json_comments, ParserError};
+
+    let input = "{\n  // Editor settings\n  \"editor.tabSize\": 2, /* spaces */\n  \"files.exclude\": {\n    \"**/*.tmp\": true, // \"quoted\" in a comment\n  },\n  \"url\": \"http://example.com/*x*/\",\n  \"list\": [1, 2, /* last */],\n}\n";
+    let expected = json!({
+        "editor.tabSize": 2,
+        "files.exclude": { "**/*.tmp": true },
+        "url": "http://example.com/*x*/",
+        "list": [1, 2]
+    });
+    assert_eq!(parse_jsonc(input).unwrap(), expected);
+
+    let stripped = strip_json_comments(input);
+    assert_eq!(
+        stripped,
+        "{\n  \"editor.tabSize\": 2,\n  \"files.exclude\": {\n    \"**/*.tmp\": true\n  },\n  \"url\": \"http://example.com/*x*/\",\n  \"list\": [1, 2]\n}\n"
+    );
+    assert_eq!(serde_json::from_str::<Value>(&stripped).unwrap(), expected);
+
+    match parse_jsonc("{\n  /* ok */ \"a\": 1,\n  \"b\" 2\n}").unwrap_err() {
+        ParserError::JsonParseError(details) => assert_eq!((details.line, details.column), (3, 7)),
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert!(parse_jsonc("{ 'a': 1 }").is_err());
+}
+
+/// Tests that `JsonCst` edits keep comments and formatting outside the edited spans.
+#[test]
+fn test_json_cst_edits() {
+    use json_parser_with_pest::JsonCst;
+
+    let source = "{\n  // Editor settings\n  \"editor.tabSize\": 2, // spaces\n  /* deprecated */\n  \"old\": true,\n  \"files.exclude\": {\n    \"**/*.tmp\": true\n  },\n  \"list\": [1, 2]\n}\n";
+    let mut cst = JsonCst::parse(source).expect("JSONC should parse");
+    assert_eq!(cst.to_string(), source);
+    assert_eq!(cst.get("/files.exclude/**~1*.tmp"), Some(json!(true)));
+
+    cst.set("/editor.tabSize", &json!(4)).unwrap();
+    cst.set("/files.exclude/dist", &json!(false)).unwrap();
+    cst.insert("/list/-", &json!(3)).unwrap();
+    cst.insert("/list/0", &json!(0)).unwrap();
+    assert_eq!(cst.remove("/old").unwrap(), json!(true));
+    assert!(cst.insert("/list/9", &json!(1)).is_err());
+    assert!(cst.insert("/editor.tabSize", &json!(1)).is_err());
+
+    let expected = "{\n  // Editor settings\n  \"editor.tabSize\": 4, // spaces\n  /* deprecated */\n  \"files.exclude\": {\n    \"**/*.tmp\": true,\n    \"dist\": false\n  },\n  \"list\": [0, 1, 2, 3]\n}\n";
+    assert_eq!(cst.to_string(), expected);
+    assert_eq!(cst.value()["files.exclude"], json!({"**/*.tmp": true, "dist": false}));
+
+    cst.set("/added", &json!({"a": [1]})).unwrap();
+    assert!(cst
+        .to_string()
+        .ends_with("  \"list\": [0, 1, 2, 3],\n  \"added\": {\n    \"a\": [\n      1\n    ]\n  }\n}\n"));
+}
+
+/// Tests query result paths and the raw, NDJSON, TSV and path output formats.
+#[test]
+fn test_query_output_formats() {
+    use json_parser_with_pest::{format_query_results, JsonPath, QueryFormat};
+
+    let json = json!({
+        "users": [
+            {"name": "Ann", "role": "admin", "tags": ["a", "b"]},
+            {"name": "Bob\tB", "active": false}
+        ]
+    });
+    let users = JsonPath::compile("$.users[*]").unwrap();
+    let results = users.select_with_paths(&json);
+    assert_eq!(results[1].0, "$['users'][1]");
+
+    let names = JsonPath::compile("$..name").unwrap().select_with_paths(&json);
+    assert_eq!(format_query_results(&names, QueryFormat::Raw), "Ann\nBob\tB\n");
+    assert_eq!(
+        format_query_results(&names, QueryFormat::Paths),
+        "$['users'][0]['name']\t\"Ann\"\n$['users'][1]['name']\t\"Bob\\tB\"\n"
+    );
+    assert_eq!(
+        format_query_results(&results, QueryFormat::Tsv),
+        "name\trole\ttags\tactive\nAnn\tadmin\t[\"a\",\"b\"]\t\nBob\\tB\t\t\tfalse\n"
+    );
+    let tags = JsonPath::compile("$.users[0].tags").unwrap().select_with_paths(&json);
+    assert_eq!(format_query_results(&tags, QueryFormat::Tsv), "a\tb\n");
+    assert_eq!(format_query_results(&tags, QueryFormat::Ndjson), "[\"a\",\"b\"]\n");
+    assert!("yaml".parse::<QueryFormat>().is_err());
+}
+
+/// Tests saved queries from a config file with parameter defaults, overrides and transforms.
+#[test]
+fn test_saved_queries() {
+    use json_parser_with_pest::Config;
+    use std::collections::BTreeMap;
+
+    let config = Config::from_json(&json!({
+        "queries": {
+            "active_users": {
+                "description": "Names of active users at least min_age old",
+                "query": "$.users[?(@.active == true && @.age >= ${min_age})]",
+                "transform": "name + ${suffix}",
+                "params": {"min_age": 18, "suffix": ""}
+            },
+            "by_role": {"query": "$.users[?(@.role == ${role})].name"}
+        }
+    }))
+    .unwrap();
+    let json = json!({"users": [
+        {"name": "Ann", "age": 30, "active": true, "role": "admin"},
+        {"name": "Bob", "age": 16, "active": true, "role": "user"},
+        {"name": "Cid", "age": 40, "active": false, "role": "user"}
+    ]});
+
+    let active = config.saved_query("active_users").unwrap();
+    assert_eq!(
+        active.run(&json, &BTreeMap::new()).unwrap(),
+        vec![("$['users'][0]".to_string(), json!("Ann"))]
+    );
+    let params = BTreeMap::from([("min_age".to_string(), json!(10)), ("suffix".to_string(), json!("!"))]);
+    let names: Vec<Value> = active.run(&json, &params).unwrap().into_iter().map(|(_, v)| v).collect();
+    assert_eq!(names, vec![json!("Ann!"), json!("Bob!")]);
+
+    // Parameters are substituted as literals, so quotes cannot escape the filter.
+    let by_role = config.saved_query("by_role").unwrap();
+    let injected = BTreeMap::from([("role".to_string(), json!("user' || true || '"))]);
+    assert!(by_role.run(&json, &injected).unwrap().is_empty());
+    assert!(by_role.run(&json, &BTreeMap::new()).is_err());
+    assert!(active.run(&json, &BTreeMap::from([("other".to_string(), json!(1))])).is_err());
+    assert!(config.saved_query("missing").is_err());
+    assert!(Config::from_json(&json!({"queries": {"bad": {"query": "$.["}}})).is_err());
+}
+
+/// Tests setting and deleting nested values by path, creating intermediate containers.
+#[test]
+fn test_set_and_delete_by_path() {
+    use json_parser_with_pest::parser::get_by_path;
+    use json_parser_with_pest::{delete_by_path, set_by_path};
+
+    let mut json = json!({"config": {"name": "app"}});
+    set_by_path(&mut json, "config.servers[1].port", json!(8080)).unwrap();
+    set_by_path(&mut json, "config.name", json!("api")).unwrap();
+    set_by_path(&mut json, "matrix[0][1]", json!(true)).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "config": {"name": "api", "servers": [null, {"port": 8080}]},
+            "matrix": [[null, true]]
+        })
+    );
+    assert_eq!(get_by_path(&json, "matrix[0][1]"), Some(json!(true)));
+    assert!(set_by_path(&mut json, "config.name.first", json!("x")).is_err());
+    assert!(set_by_path(&mut json, "config[0]", json!("x")).is_err());
+
+    assert_eq!(delete_by_path(&mut json, "config.servers[0]").unwrap(), Value::Null);
+    assert_eq!(delete_by_path(&mut json, "matrix").unwrap(), json!([[null, true]]));
+    assert_eq!(json, json!({"config": {"name": "api", "servers": [{"port": 8080}]}}));
+    assert!(delete_by_path(&mut json, "config.missing").is_err());
+}
+
+/// Tests `$name` variables bound to secondary documents in queries and expressions.
+#[test]
+fn test_query_variables() {
+    use json_parser_with_pest::{Expression, JsonPath, Variables};
+
+    let orders = json!({"orders": [
+        {"id": 1, "user": 7, "total": 30},
+        {"id": 2, "user": 8, "total": 50},
+        {"id": 3, "user": 7, "total": 5}
+    ]});
+    let mut variables = Variables::new();
+    variables.insert("users".to_string(), json!([{"id": 7, "name": "Ann", "vip": true}, {"id": 8, "name": "Bob"}]));
+    variables.insert("limits".to_string(), json!({"min": 10}));
+
+    let vip_orders = JsonPath::compile("$.orders[?(@.user == $users[?(@.vip)].id && @.total >= $limits.min)].id").unwrap();
+    let results = vip_orders.select_with_variables(&orders, &variables).unwrap();
+    assert_eq!(results, vec![("$['orders'][0]['id']".to_string(), &json!(1))]);
+    assert!(vip_orders.select(&orders).is_empty());
+    assert!(vip_orders.select_with_variables(&orders, &Variables::new()).is_err());
+
+    let names = JsonPath::compile("$users[?(@.id == 8)].name").unwrap();
+    let results = names.select_with_variables(&orders, &variables).unwrap();
+    assert_eq!(results, vec![("$users[1]['name']".to_string(), &json!("Bob"))]);
+
+    let over_limit = Expression::compile("total - $limits.min").unwrap();
+    assert_eq!(over_limit.evaluate_with_variables(&json!({"total": 30}), &variables), Some(json!(20)));
+    assert_eq!(over_limit.evaluate(&json!({"total": 30})), None);
+}
+
+/// Tests quoted keys with dots, brackets and escapes in `get_by_path`-style paths.
+#[test]
+fn test_quoted_path_keys() {
+    use json_parser_with_pest::parser::{get_by_path, search_by_value};
+    use json_parser_with_pest::{delete_by_path, diff_json, set_by_path};
+
+    let mut json = json!({
+        "data": {"weird.key": {"value": 1}, "a[0]": "x", "it's \"q\"": [true]},
+        "plain": {"items": [{"name": "n"}]}
+    });
+    assert_eq!(get_by_path(&json, "data[\"weird.key\"].value"), Some(json!(1)));
+    assert_eq!(get_by_path(&json, "data['a[0]']"), Some(json!("x")));
+    assert_eq!(get_by_path(&json, r#"data["it's \"q\""][0]"#), Some(json!(true)));
+    assert_eq!(get_by_path(&json, "plain.items[0].name"), Some(json!("n")));
+    assert_eq!(get_by_path(&json, "data.weird.key"), None);
+    assert_eq!(get_by_path(&json, "plain.items[0"), None);
+    assert_eq!(get_by_path(&json, "plain..items"), None);
+
+    set_by_path(&mut json, "data['new.key'][1]", json!(2)).unwrap();
+    assert_eq!(json["data"]["new.key"], json!([null, 2]));
+    assert_eq!(delete_by_path(&mut json, "data['new.key']").unwrap(), json!([null, 2]));
+
+    // Paths produced by the library can be fed back to get_by_path.
+    let found = search_by_value(&json, "x");
+    assert_eq!(found, vec!["data[\"a[0]\"]".to_string()]);
+    let changes = diff_json(&json, &json!({"data": {"weird.key": {"value": 2}}}));
+    let path = &changes.iter().find(|c| c.new_value == Some(json!(2))).unwrap().path;
+    assert_eq!(path, "data[\"weird.key\"].value");
+    assert_eq!(get_by_path(&json, path), Some(json!(1)));
+}
+
+/// Tests the date and time functions of the expression language.
+#[test]
+fn test_expression_date_functions() {
+    use json_parser_with_pest::{Expression, JsonPath};
+
+    let eval = |text: &str, record: &Value| Expression::compile(text).unwrap().evaluate(record);
+    let record = json!({"ts": "2024-03-01T23:30:00+02:00", "end": "2024-03-02", "log": "01/Mar/2024:10:00:00 +0000"});
+
+    assert_eq!(eval("parse_date(ts)", &record), Some(json!(1709328600)));
+    assert_eq!(eval("parse_date(log, '%d/%b/%Y:%H:%M:%S %z')", &record), Some(json!(1709287200)));
+    assert_eq!(eval("date_diff(end, ts, 'hours')", &record), Some(json!(2.5)));
+    assert_eq!(eval("format_date(ts)", &record), Some(json!("2024-03-01T21:30:00Z")));
+    assert_eq!(eval("format_date(ts, '%Y/%m/%d %H:%M %z', '-05:00')", &record), Some(json!("2024/03/01 16:30 -0500")));
+    assert_eq!(eval("to_timezone(1709328600, '+0530')", &record), Some(json!("2024-03-02T03:00:00+05:30")));
+    assert_eq!(eval("date_diff(now(), ts, 'days') > 100", &record), Some(json!(true)));
+    assert_eq!(eval("parse_date(end, 'bogus')", &record), None);
+    assert!(Expression::compile("unknown(ts)").is_err());
+    assert!(Expression::compile("parse_date()").is_err());
+
+    let logs = json!([{"ts": "2024-03-01T10:00:00Z"}, {"ts": "2024-03-05T10:00:00Z"}]);
+    let recent = JsonPath::compile("$[?(parse_date(@.ts) >= parse_date('2024-03-02'))].ts").unwrap();
+    assert_eq!(recent.select(&logs), vec![&json!("2024-03-05T10:00:00Z")]);
+}
+
+/// Tests wildcard and recursive selection with concrete result paths.
+#[test]
+fn test_select_paths() {
+    use json_parser_with_pest::parser::get_by_path;
+    use json_parser_with_pest::select_paths;
+
+    let json = json!({
+        "data": {"items": [
+            {"name": "a", "tags": [{"name": "t1"}]},
+            {"name": "b"},
+            {"id": 3}
+        ]},
+        "name": "root",
+        "odd.key": {"name": "c"}
+    });
+    assert_eq!(
+        select_paths(&json, "data.items[*].name"),
+        vec![
+            ("data.items[0].name".to_string(), json!("a")),
+            ("data.items[1].name".to_string(), json!("b")),
+        ]
+    );
+    let names: Vec<String> = select_paths(&json, "..name").into_iter().map(|(path, _)| path).collect();
+    assert_eq!(
+        names,
+        vec!["name", "data.items[0].name", "data.items[0].tags[0].name", "data.items[1].name", "[\"odd.key\"].name"]
+    );
+    for path in &names {
+        assert!(get_by_path(&json, path).is_some());
+    }
+    assert_eq!(select_paths(&json, "data..tags.*.name"), vec![("data.items[0].tags[0].name".to_string(), json!("t1"))]);
+    assert_eq!(select_paths(&json, "data.items[2]").len(), 1);
+    assert!(select_paths(&json, "data.items[").is_empty());
+    assert_eq!(get_by_path(&json, "data.items[*].name"), None);
+}
+
+/// Tests the string functions available to expressions and filters.
+#[test]
+fn test_expression_string_functions() {
+    use json_parser_with_pest::{Expression, JsonPath};
+
+    let record = json!({ "name": "  Ada Lovelace ", "tags": "a,b,c", "email": "ADA@example.com", "items": [1, 2, 3] });
+    let eval = |text: &str| Expression::compile(text).unwrap().evaluate(&record);
+    assert_eq!(eval("split(tags, ',')"), Some(json!(["a", "b", "c"])));
+    assert_eq!(eval("join(split(tags, ','), '-')"), Some(json!("a-b-c")));
+    assert_eq!(eval("lower(email)"), Some(json!("ada@example.com")));
+    assert_eq!(eval("upper(trim(name))"), Some(json!("ADA LOVELACE")));
+    assert_eq!(eval("replace(tags, ',', ';')"), Some(json!("a;b;c")));
+    assert_eq!(eval("matches(email, '@example[.]com$')"), Some(json!(true)));
+    assert_eq!(eval("substring(trim(name), 0, 3)"), Some(json!("Ada")));
+    assert_eq!(eval("substring(trim(name), -8)"), Some(json!("Lovelace")));
+    assert_eq!(eval("len(items) + len(tags)"), Some(json!(8)));
+    assert_eq!(eval("len(missing)"), None);
+    assert!(Expression::compile("matches(email, '[')").is_err());
+    assert!(Expression::compile("lower(email, name)").is_err());
+
+    let users = json!([{ "name": "ann" }, { "name": "Bob" }]);
+    let path = JsonPath::compile("$[?(matches(@.name, '^[A-Z]'))].name").unwrap();
+    assert_eq!(path.select(&users), vec![&json!("Bob")]);
+}
+
+/// Tests jq-style pipelines evaluated with `eval_expr`.
+#[test]
+fn test_eval_expr_pipelines() {
+    use json_parser_with_pest::{eval_expr, JqFilter};
+
+    let json = json!({
+        "items": [
+            { "name": "pen", "price": 5, "tags": ["office"] },
+            { "name": "lamp", "price": 20, "tags": [] },
+            { "name": "desk", "price": 150 }
+        ]
+    });
+    assert_eq!(
+        eval_expr(&json, ".items[] | select(.price > 10) | {name, price}").unwrap(),
+        vec![json!({ "name": "lamp", "price": 20 }), json!({ "name": "desk", "price": 150 })]
+    );
+    assert_eq!(eval_expr(&json, ".items | map(.price) | add").unwrap(), vec![json!(175)]);
+    assert_eq!(eval_expr(&json, ".items | sort_by(-.price) | map(.name) | join(\",\")").unwrap(), vec![json!("desk,lamp,pen")]);
+    assert_eq!(eval_expr(&json, ".items[0].name, .items[-1].price").unwrap(), vec![json!("pen"), json!(150)]);
+    assert_eq!(eval_expr(&json, ".items[1:] | length").unwrap(), vec![json!(2)]);
+    assert_eq!(eval_expr(&json, "[.items[].tags[]?]").unwrap(), vec![json!(["office"])]);
+    assert_eq!(eval_expr(&json, ".missing // \"none\"").unwrap(), vec![json!("none")]);
+    assert_eq!(
+        eval_expr(&json, ".items[] | if .price < 10 then \"cheap\" elif .price < 100 then \"fair\" else \"dear\" end").unwrap(),
+        vec![json!("cheap"), json!("fair"), json!("dear")]
+    );
+    assert_eq!(eval_expr(&json, ".items | group_by(.price > 10) | map(length)").unwrap(), vec![json!([1, 2])]);
+
+    // Compiled programs are reusable; errors are reported, not swallowed.
+    let filter = JqFilter::compile(".name | ascii_upcase").unwrap();
+    assert_eq!(filter.run(&json!({ "name": "ada" })).unwrap(), vec![json!("ADA")]);
+    assert_eq!(eval_expr(&json, ".items[]").unwrap().len(), 3);
+    assert!(eval_expr(&json, ".items[0].price[]").is_err());
+    assert!(JqFilter::compile(".items | frobnicate").is_err());
+    assert!(JqFilter::compile(".items[").is_err());
+}
+
+/// Tests math functions and aggregates over path results in expressions.
+#[test]
+fn test_expression_math_functions() {
+    use json_parser_with_pest::{Expression, JsonPath};
+
+    let order = json!({
+        "id": 7,
+        "delta": -3,
+        "scores": [4, 8, null, 6],
+        "items": [{ "price": 2.5, "qty": 2 }, { "price": 10, "qty": 1 }, { "price": 1.25, "qty": 4 }]
+    });
+    let eval = |text: &str| Expression::compile(text).unwrap().evaluate(&order);
+    assert_eq!(eval("round(10 / 3, 2)"), Some(json!(3.33)));
+    assert_eq!(eval("round(2.5)"), Some(json!(3)));
+    assert_eq!(eval("floor(2.7) + ceil(2.2)"), Some(json!(5)));
+    assert_eq!(eval("abs(delta)"), Some(json!(3)));
+    assert_eq!(eval("sum(@.items[*].price)"), Some(json!(13.75)));
+    assert_eq!(eval("avg(@.scores)"), Some(json!(6)));
+    assert_eq!(eval("count(@.scores)"), Some(json!(3)));
+    assert_eq!(eval("max(@.scores, 10)"), Some(json!(10)));
+    assert_eq!(eval("min(@.items[*].qty)"), Some(json!(1)));
+    assert_eq!(eval("sum(@.missing[*])"), Some(json!(0)));
+    assert_eq!(eval("avg(@.missing[*])"), None);
+    assert!(Expression::compile("max()").is_err());
+
+    let orders = json!([order, { "id": 8, "items": [{ "price": 1 }] }]);
+    let path = JsonPath::compile("$[?(sum(@.items[*].price) > 5)].id").unwrap();
+    assert_eq!(path.select(&orders), vec![&json!(7)]);
+}
+
+/// Tests searching with regex, substring, typed and key matching.
+#[test]
+fn test_search_with_options() {
+    use json_parser_with_pest::{search, SearchOptions};
+
+    let json = json!({
+        "users": [
+            { "name": "John Smith", "email": "JOHN@example.com", "age": 30, "admin": true },
+            { "name": "Ann", "email": "ann@test.org", "age": 30.0, "manager": null }
+        ],
+        "owner": "John"
+    });
+    let paths = |pattern: &str, options: SearchOptions| -> Vec<String> {
+        search(&json, pattern, &options).unwrap().into_iter().map(|(path, _)| path).collect()
+    };
+    assert_eq!(paths("John", SearchOptions::default()), vec!["owner"]);
+    assert_eq!(
+        paths("john", SearchOptions { substring: true, ignore_case: true, ..Default::default() }),
+        vec!["owner", "users[0].email", "users[0].name"]
+    );
+    assert_eq!(
+        paths(r"@example\.com$", SearchOptions { regex: true, ignore_case: true, ..Default::default() }),
+        vec!["users[0].email"]
+    );
+    assert_eq!(paths("30", SearchOptions { typed: true, ..Default::default() }), vec!["users[0].age", "users[1].age"]);
+    assert_eq!(paths("null", SearchOptions { typed: true, ..Default::default() }), vec!["users[1].manager"]);
+    assert_eq!(paths("30", SearchOptions::default()), Vec::<String>::new());
+    assert_eq!(
+        paths("^(admin|manager)$", SearchOptions { keys: true, regex: true, ..Default::default() }),
+        vec!["users[0].admin", "users[1].manager"]
+    );
+    assert_eq!(paths("a", SearchOptions { substring: true, limit: Some(2), ..Default::default() }).len(), 2);
+
+    let found = search(&json, "true", &SearchOptions { typed: true, ..Default::default() }).unwrap();
+    assert_eq!(found, vec![("users[0].admin".to_string(), json!(true))]);
+    assert!(search(&json, "(", &SearchOptions { regex: true, ..Default::default() }).is_err());
+}
+
+/// Tests finding keys at any depth by name, glob and regular expression.
+#[test]
+fn test_search_by_key() {
+    use json_parser_with_pest::search_by_key;
+
+    let config = json!({
+        "timeout": 30,
+        "http": { "client": { "timeout": 5, "read_timeout": 10 }, "retries": 3 },
+        "services": [{ "name": "db", "connect_timeout": 2 }, { "name": "cache", "timeout": null }],
+        "weird.keys": { "timeout": 1 }
+    });
+    assert_eq!(
+        search_by_key(&config, "timeout"),
+        vec!["http.client.timeout", "services[1].timeout", "timeout", "[\"weird.keys\"].timeout"]
+    );
+    assert_eq!(
+        search_by_key(&config, "*_timeout"),
+        vec!["http.client.read_timeout", "services[0].connect_timeout"]
+    );
+    assert_eq!(search_by_key(&config, "retr???"), vec!["http.retries"]);
+    assert_eq!(search_by_key(&config, "/^(name|retries)$/"), vec!["http.retries", "services[0].name", "services[1].name"]);
+    assert_eq!(search_by_key(&config, "weird.keys"), vec!["[\"weird.keys\"]"]);
+    assert!(search_by_key(&config, "/(/").is_empty());
+}
+
+/// Tests the `//` default and `?` operators in expressions and `try`/`catch` in pipelines.
+#[test]
+fn test_expression_default_operators() {
+    use json_parser_with_pest::{eval_expr, Expression, JsonPath};
+
+    let records = json!([
+        { "name": "Ann", "nickname": "annie", "address": { "city": "Kyiv" } },
+        { "name": "Bob", "nickname": null },
+        { "nickname": false }
+    ]);
+    let eval = |text: &str, record: &Value| Expression::compile(text).unwrap().evaluate(record);
+    let label = "@.nickname // @.name // 'anonymous'";
+    assert_eq!(eval(label, &records[0]), Some(json!("annie")));
+    assert_eq!(eval(label, &records[1]), Some(json!("Bob")));
+    assert_eq!(eval(label, &records[2]), Some(json!("anonymous")));
+    assert_eq!(eval("address.city", &records[1]), None);
+    assert_eq!(eval("address.city?", &records[1]), Some(Value::Null));
+    assert_eq!(eval("@.age // 0 + 1", &records[0]), Some(json!(1)));
+    assert_eq!(eval("10 / 2", &records[0]), Some(json!(5.0)));
+
+    let path = JsonPath::compile("$[?((@.address.city // 'none') == 'none')].name").unwrap();
+    assert_eq!(path.select(&records), vec![&json!("Bob")]);
+
+    assert_eq!(eval_expr(&json!(5), "try .a catch \"bad\"").unwrap(), vec![json!("bad")]);
+    assert_eq!(eval_expr(&json!(5), "[try .a]").unwrap(), vec![json!([])]);
+    assert_eq!(eval_expr(&json!({ "a": 1 }), "try .a catch 0").unwrap(), vec![json!(1)]);
+}
+
+/// Tests flattening documents to compound keys and restoring them.
+#[test]
+fn test_flatten_and_unflatten() {
+    use json_parser_with_pest::{flatten, unflatten};
+
+    let json = json!({
+        "a": { "b": [{ "c": 1 }, { "d": [true, null] }] },
+        "weird.key": { "x[0]": "y" },
+        "empty": { "list": [], "map": {} },
+        "": 0
+    });
+    let flat = flatten(&json, ".");
+    assert_eq!(
+        flat,
+        json!({
+            "[\"\"]": 0,
+            "a.b[0].c": 1,
+            "a.b[1].d[0]": true,
+            "a.b[1].d[1]": null,
+            "empty.list": [],
+            "empty.map": {},
+            "[\"weird.key\"][\"x[0]\"]": "y"
+        })
+    );
+    assert_eq!(unflatten(&flat, ".").unwrap(), json);
+
+    let slashed = flatten(&json, "/");
+    assert_eq!(slashed["a/b[0]/c"], json!(1));
+    assert_eq!(slashed["weird.key[\"x[0]\"]"], json!("y"));
+    assert_eq!(unflatten(&slashed, "/").unwrap(), json);
+
+    assert_eq!(unflatten(&flatten(&json!([1, [2]]), "."), ".").unwrap(), json!([1, [2]]));
+    assert_eq!(unflatten(&flatten(&json!("text"), "."), ".").unwrap(), json!("text"));
+    assert_eq!(flatten(&json!({}), "."), json!({}));
+    assert_eq!(unflatten(&json!({}), ".").unwrap(), json!({}));
+    assert!(unflatten(&json!({ "a": 1, "a.b": 2 }), ".").is_err());
+    assert!(unflatten(&json!({ "a[x]": 1 }), ".").is_err());
+    assert!(unflatten(&json!([1]), ".").is_err());
+}
+
+/// Tests user-defined functions from expression files in queries, expressions and saved queries.
+#[test]
+fn test_user_defined_functions() {
+    use json_parser_with_pest::{Config, Expression, FunctionLibrary, JsonPath};
+
+    let library = FunctionLibrary::parse(
+        "# Shared helpers\n\
+         def normalize(text): lower(trim($text));\n\
+         def full_name(user): normalize($user.first + ' ' + $user.last);\n\
+         def is_admin(): normalize(role) == 'admin';",
+    )
+    .unwrap();
+    assert_eq!(library.names(), vec!["full_name", "is_admin", "normalize"]);
+
+    let json = json!({ "users": [
+        { "first": " Ada", "last": "LOVELACE", "role": " Admin" },
+        { "first": "Alan", "last": "Turing", "role": "user" }
+    ] });
+    let query = JsonPath::compile_with_functions("$.users[?(is_admin())]", &library).unwrap();
+    assert_eq!(query.select(&json), vec![&json["users"][0]]);
+    let name = Expression::compile_with_functions("full_name(@)", &library).unwrap();
+    assert_eq!(name.evaluate(&json["users"][1]), Some(json!("alan turing")));
+
+    assert!(JsonPath::compile("$.users[?(is_admin())]").is_err());
+    assert!(Expression::compile_with_functions("normalize(a, b)", &library).is_err());
+    assert!(FunctionLibrary::parse("def len(x): 1;").is_err());
+    assert!(FunctionLibrary::parse("def f(x): g($x); def g(x): $x;").is_err());
+    assert!(FunctionLibrary::parse("def f(x): $y;").is_err());
+    assert!(FunctionLibrary::parse("def f(x): $x; def f(y): $y;").is_err());
+
+    let dir = tempdir().unwrap();
+    std::fs::write(dir.path().join("functions.expr"), "def normalize(text): lower(trim($text));").unwrap();
+    std::fs::write(
+        dir.path().join("config.json"),
+        r#"{ "functions": ["functions.expr"], "queries": { "admins": { "query": "$.users[?(normalize(@.role) == 'admin')]", "transform": "normalize(last)" } } }"#,
+    )
+    .unwrap();
+    let config = Config::load(&dir.path().join("config.json")).unwrap();
+    let results = config.saved_query("admins").unwrap().run(&json, &Default::default()).unwrap();
+    assert_eq!(results, vec![("$['users'][0]".to_string(), json!("lovelace"))]);
+}
+
+/// Tests converting object keys between naming conventions.
+#[test]
+fn test_transform_keys() {
+    use json_parser_with_pest::{transform_keys, KeyCase};
+
+    let original = json!({
+        "userId": 1,
+        "HTTPStatus": 200,
+        "_links": { "self-href": "/u/1" },
+        "addressLines": [{ "line_one": "Main St", "version2Name": "x" }]
+    });
+    let mut json = original.clone();
+    transform_keys(&mut json, KeyCase::Snake).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "user_id": 1,
+            "http_status": 200,
+            "_links": { "self_href": "/u/1" },
+            "address_lines": [{ "line_one": "Main St", "version2_name": "x" }]
+        })
+    );
+    transform_keys(&mut json, KeyCase::Camel).unwrap();
+    assert_eq!(json["addressLines"][0]["lineOne"], json!("Main St"));
+    assert_eq!(json["_links"]["selfHref"], json!("/u/1"));
+    assert_eq!(json["httpStatus"], json!(200));
+
+    assert_eq!(KeyCase::Kebab.convert("userId"), "user-id");
+    assert_eq!(KeyCase::Pascal.convert("user_id"), "UserId");
+    assert_eq!("snake".parse::<KeyCase>().unwrap(), KeyCase::Snake);
+    assert!("upper".parse::<KeyCase>().is_err());
+
+    let mut conflicting = json!({ "userId": 1, "user_id": 2 });
+    assert!(transform_keys(&mut conflicting, KeyCase::Snake).is_err());
+}
+
+/// Tests that streamable queries are detected and give the same results while streaming.
+#[test]
+fn test_select_streaming() {
+    use json_parser_with_pest::{JsonPath, Variables};
+
+    let json = json!({
+        "meta": { "count": 3 },
+        "orders": [
+            { "id": 1, "total": 50, "lines": [{ "sku": "a" }] },
+            { "id": 2, "total": 150, "lines": [{ "sku": "b" }, { "sku": "c" }] },
+            { "id": 3, "total": 300, "lines": [] }
+        ],
+        "limit": 100
+    });
+    let text = serde_json::to_string(&json).unwrap();
+    let mut variables = Variables::new();
+    variables.insert("vip".to_string(), json!({ "id": 2 }));
+    for (path, streamable) in [
+        ("$.orders[?(@.total > 100)].id", true),
+        ("$.orders[*]..sku", true),
+        ("$.orders[1:3].lines[-1]", true),
+        ("$.orders[0]", true),
+        ("$.meta.*", true),
+        ("$.orders[?(@.id == $vip.id)].total", true),
+        ("$", true),
+        ("$.missing[*]", true),
+        ("$..sku", false),
+        ("$.orders[-1].id", false),
+        ("$.orders[0,2].id", false),
+        ("$.orders[?(@.total > $.limit)].id", false),
+    ] {
+        let query = JsonPath::compile(path).unwrap();
+        assert_eq!(query.streaming_blockers().is_empty(), streamable, "{}", path);
+        let mut streamed = Vec::new();
+        query
+            .select_streaming(text.as_bytes(), &variables, |path, value| streamed.push((path, value)))
+            .unwrap();
+        let expected: Vec<(String, Value)> = query
+            .select_with_variables(&json, &variables)
+            .unwrap()
+            .into_iter()
+            .map(|(path, value)| (path, value.clone()))
+            .collect();
+        assert_eq!(streamed, expected, "{}", path);
+    }
+
+    let reasons = JsonPath::compile("$..orders[?(@.total > $.limit)]").unwrap().streaming_blockers();
+    assert_eq!(reasons.len(), 2);
+    let query = JsonPath::compile("$.orders[*].id").unwrap();
+    assert!(query.select_streaming("[1, 2".as_bytes(), &variables, |_, _| {}).is_err());
+    assert!(query.select_streaming("{} {}".as_bytes(), &variables, |_, _| {}).is_err());
+}
+
+/// Tests keeping and removing values by path.
+#[test]
+fn test_project_and_omit() {
+    use json_parser_with_pest::{omit, project};
+
+    let json = json!({
+        "id": 7,
+        "updated_at": "2024-01-01",
+        "items": [
+            { "name": "a", "internal_id": 1, "meta": { "updated_at": "x" } },
+            { "internal_id": 2 },
+            { "name": "c", "internal_id": 3 }
+        ]
+    });
+    assert_eq!(
+        project(&json, &["id", "items[*].name"]),
+        json!({ "id": 7, "items": [{ "name": "a" }, { "name": "c" }] })
+    );
+    assert_eq!(project(&json, &["items[1]"]), json!({ "items": [{ "internal_id": 2 }] }));
+    assert_eq!(project(&json, &["missing"]), json!({}));
+    assert_eq!(
+        omit(&json, &["..updated_at", "items[*].internal_id"]),
+        json!({
+            "id": 7,
+            "items": [{ "name": "a", "meta": {} }, {}, { "name": "c" }]
+        })
+    );
+    assert_eq!(omit(&json!([1, 2, 3, 4]), &["[1]", "[3]"]), json!([1, 3]));
+    assert_eq!(omit(&json, &["no[such"]), json);
+}
+
+/// Tests skipping NDJSON records seen in the same or an earlier run.
+#[test]
+fn test_dedup_ndjson_with_persistent_state() {
+    use json_parser_with_pest::{dedup_ndjson, BloomFilter, DedupOptions};
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("batch.ndjson");
+    let output = dir.path().join("new.ndjson");
+    let state = dir.path().join("seen.bloom");
+    std::fs::write(
+        &input,
+        "{\"id\": 1, \"v\": \"a\"}\n{\"v\": \"b\", \"id\": 2}\n{\"id\": 1, \"v\": \"c\"}\nnot json\n{\"v\": \"d\"}\n",
+    )
+    .unwrap();
+    let options = DedupOptions {
+        key: Some("id".to_string()),
+        state: Some(state.clone()),
+        ..DedupOptions::default()
+    };
+    let report = dedup_ndjson(&input, &output, &options).unwrap();
+    assert_eq!((report.total, report.written, report.duplicates), (5, 3, 1));
+    assert_eq!((report.missing_key, report.invalid), (1, 1));
+    let written = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(written.lines().count(), 3);
+    assert!(written.contains("\"b\"") && !written.contains("\"c\""));
+
+    // A second run over the same batch only passes the record without a key.
+    let report = dedup_ndjson(&input, &output, &options).unwrap();
+    assert_eq!((report.written, report.duplicates), (1, 3));
+
+    // Without a key, records are compared by content regardless of key order.
+    std::fs::write(&input, "{\"a\": 1, \"b\": 2}\n{\"b\": 2, \"a\": 1}\n").unwrap();
+    let report = dedup_ndjson(&input, &output, &DedupOptions::default()).unwrap();
+    assert_eq!((report.written, report.duplicates), (1, 1));
+
+    let mut filter = BloomFilter::new(100, 0.01);
+    assert!(!filter.insert(b"x"));
+    assert!(filter.insert(b"x"));
+    filter.save(&state).unwrap();
+    let loaded = BloomFilter::load(&state).unwrap();
+    assert_eq!(loaded, filter);
+    assert!(loaded.contains(b"x") && !loaded.contains(b"y"));
+    std::fs::write(&state, "garbage").unwrap();
+    assert!(BloomFilter::load(&state).is_err());
+}
+
+/// Tests continuing window aggregation and deduplication from JSON checkpoints.
+#[test]
+fn test_streaming_state_checkpoints() {
+    use json_parser_with_pest::{aggregate_windows, dedup_ndjson, BloomFilter, DedupOptions, WindowOptions};
+
+    let dir = tempdir().unwrap();
+    let events: Vec<String> = (0..40)
+        .map(|i| format!("{{\"t\": {}, \"g\": \"{}\", \"v\": {}}}", i * 7, i % 3, i))
+        .collect();
+    let all = dir.path().join("all.ndjson");
+    std::fs::write(&all, events.join("\n")).unwrap();
+    let mut options = WindowOptions::new("t", 60);
+    options.group_by = Some("g".to_string());
+    options.value = Some("v".to_string());
+    options.percentiles = vec![50.0, 90.0];
+    options.compression = Some(50.0);
+    let expected_path = dir.path().join("expected.ndjson");
+    let windows = aggregate_windows(&all, &expected_path, &options).unwrap();
+    let expected = std::fs::read_to_string(&expected_path).unwrap();
+
+    // The same stream in two batches, checkpointing the open windows in between.
+    let state = dir.path().join("windows.json");
+    options.state = Some(state.clone());
+    let mut written = String::new();
+    for (batch, flush) in [(&events[..17], false), (&events[17..], true)] {
+        let input = dir.path().join("batch.ndjson");
+        let output = dir.path().join("out.ndjson");
+        std::fs::write(&input, batch.join("\n")).unwrap();
+        options.flush = flush;
+        aggregate_windows(&input, &output, &options).unwrap();
+        written.push_str(&std::fs::read_to_string(&output).unwrap());
+        let checkpoint: Value = serde_json::from_str(&std::fs::read_to_string(&state).unwrap()).unwrap();
+        assert_eq!(checkpoint["windows"].as_array().unwrap().is_empty(), flush);
+    }
+    assert_eq!(written.lines().count(), windows);
+    assert_eq!(written, expected);
+
+    options.size = 30;
+    assert!(aggregate_windows(&all, &expected_path, &options).is_err());
+
+    // A dedup state saved as JSON is read back by the next run.
+    let input = dir.path().join("ids.ndjson");
+    let output = dir.path().join("new.ndjson");
+    std::fs::write(&input, "{\"id\": 1}\n{\"id\": 2}\n").unwrap();
+    let options = DedupOptions {
+        key: Some("id".to_string()),
+        state: Some(dir.path().join("seen.json")),
+        ..DedupOptions::default()
+    };
+    assert_eq!(dedup_ndjson(&input, &output, &options).unwrap().written, 2);
+    assert_eq!(dedup_ndjson(&input, &output, &options).unwrap().duplicates, 2);
+    let exported = BloomFilter::load(&dir.path().join("seen.json")).unwrap();
+    assert_eq!(exported.len(), 2);
+    assert_eq!(BloomFilter::from_json(&exported.to_json()).unwrap(), exported);
+    assert!(BloomFilter::from_json(&json!({ "bits": "zz" })).is_err());
+}
+
+/// Tests masking secrets by key and card numbers and emails by pattern.
+#[test]
+fn test_redact() {
+    use json_parser_with_pest::{redact, RedactionRules, Replacement};
+
+    let payload = json!({
+        "user": { "email": "ann@example.com", "apiKey": "k-123", "note": "card 4111 1111 1111 1111, call me" },
+        "auth": [{ "ACCESS_TOKEN": "abc" }, { "access_token": "abc" }],
+        "password": { "nested": true },
+        "refresh_token": null,
+        "id": 42
+    });
+
+    let mut masked = payload.clone();
+    assert_eq!(redact(&mut masked, &RedactionRules::default()), 6);
+    assert_eq!(masked["user"]["email"], json!("***"));
+    assert_eq!(masked["user"]["apiKey"], json!("***"));
+    assert_eq!(masked["user"]["note"], json!("card ***, call me"));
+    assert_eq!(masked["password"], json!("***"));
+    assert_eq!(masked["refresh_token"], Value::Null);
+    assert_eq!(masked["id"], json!(42));
+
+    // Hashes are stable, so equal secrets can still be matched up.
+    let mut hashed = payload.clone();
+    let rules = RedactionRules { replacement: Replacement::Hash, ..RedactionRules::default() };
+    redact(&mut hashed, &rules);
+    let token = hashed["auth"][0]["ACCESS_TOKEN"].as_str().unwrap();
+    assert!(token.starts_with("hash:"));
+    assert_eq!(hashed["auth"][1]["access_token"], json!(token));
+
+    let rules = RedactionRules::from_json(&json!({ "keys": ["note"], "values": [] })).unwrap();
+    let mut custom = payload.clone();
+    assert_eq!(redact(&mut custom, &rules), 1);
+    assert_eq!(custom["user"]["email"], payload["user"]["email"]);
+    assert!(RedactionRules::from_json(&json!({ "values": ["("] })).is_err());
+    assert!(RedactionRules::from_json(&json!({ "replacement": "blur" })).is_err());
+}
+
+/// Tests sorting object keys and sorting arrays by an expression.
+#[test]
+fn test_sort_keys_and_sort_array_by() {
+    use json_parser_with_pest::{sort_array_by, sort_keys, SortOrder};
+
+    let mut json = json!({ "b": 1, "a": [{ "z": 1, "y": 2 }] });
+    sort_keys(&mut json);
+    assert_eq!(serde_json::to_string(&json).unwrap(), r#"{"a":[{"y":2,"z":1}],"b":1}"#);
+
+    let mut users = json!({ "data": { "users": [
+        { "name": "ann", "age": 31 },
+        { "name": "bob" },
+        { "name": "cid", "age": 25 },
+        { "name": "dan", "age": 31 }
+    ] } });
+    sort_array_by(&mut users, "data.users", ".age", SortOrder::Descending).unwrap();
+    let names: Vec<&str> = users["data"]["users"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|user| user["name"].as_str().unwrap())
+        .collect();
+    // Ties keep their order; a missing key sorts as null.
+    assert_eq!(names, ["ann", "dan", "cid", "bob"]);
+
+    let mut root = json!([3, 1, 2]);
+    sort_array_by(&mut root, "", "@", "asc".parse().unwrap()).unwrap();
+    assert_eq!(root, json!([1, 2, 3]));
+    assert!(sort_array_by(&mut users, "data", "age", SortOrder::Ascending).is_err());
+    assert!(sort_array_by(&mut root, "", "age +", SortOrder::Ascending).is_err());
+}
+
+/// Tests recording the changes of a mutation into audit sinks.
+#[test]
+fn test_audit_log() {
+    use json_parser_with_pest::{apply_patch, audited, hash_value, merge_json, AuditEntry, AuditLog, AuditSink, MergeStrategy};
+
+    let mut config = json!({ "db": { "password": "old", "port": 5432 }, "debug": true });
+    let patch = json!([
+        { "op": "replace", "path": "/db/password", "value": "new" },
+        { "op": "remove", "path": "/debug" },
+        { "op": "add", "path": "/db/host", "value": "localhost" }
+    ]);
+    let mut entries: Vec<AuditEntry> = Vec::new();
+    audited(&mut config, "patch", Some(&mut entries), |json| apply_patch(json, &patch)).unwrap();
+    assert_eq!(entries.len(), 3);
+    let password = entries.iter().find(|entry| entry.path == "db.password").unwrap();
+    assert_eq!(password.operation, "patch");
+    assert_eq!(password.old_hash.as_deref(), Some(hash_value(&json!("old")).as_str()));
+    assert_eq!(password.new_hash.as_deref(), Some(hash_value(&json!("new")).as_str()));
+    assert!(password.timestamp.ends_with('Z'));
+    let host = entries.iter().find(|entry| entry.path == "db.host").unwrap();
+    assert!(host.old_hash.is_none() && host.new_hash.is_some());
+    let debug = entries.iter().find(|entry| entry.path == "debug").unwrap();
+    assert!(debug.old_hash.is_some() && debug.new_hash.is_none());
+
+    // Hashes use the canonical form, and secrets never reach the log.
+    assert_eq!(hash_value(&json!({ "a": 1.0, "b": 2 })), hash_value(&json!({ "b": 2, "a": 1 })));
+    assert!(hash_value(&json!("new")).starts_with("sha256:"));
+
+    // Failed mutations record nothing, and without a sink the call just runs.
+    let before = config.clone();
+    let bad = json!([{ "op": "test", "path": "/db/port", "value": 1 }]);
+    assert!(audited(&mut config, "patch", Some(&mut entries), |json| apply_patch(json, &bad)).is_err());
+    assert_eq!(entries.len(), 3);
+    audited(&mut config, "merge", None, |json| {
+        merge_json(json, &json!({ "db": { "port": 6543 } }), MergeStrategy::default());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(config["db"]["port"], json!(6543));
+    assert_ne!(config, before);
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("audit.ndjson");
+    for _ in 0..2 {
+        let mut log = AuditLog::open(&path, Some("ann")).unwrap();
+        log.record(entries[0].clone()).unwrap();
+    }
+    let lines: Vec<AuditEntry> = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1].actor.as_deref(), Some("ann"));
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("\"new\""));
+}
+
+/// Tests removing null and empty values and inserting missing defaults.
+#[test]
+fn test_prune_and_fill_defaults() {
+    use json_parser_with_pest::{fill_defaults, prune, PruneOptions};
+
+    let document = json!({
+        "a": { "b": null, "c": [null, "", {}] },
+        "name": "",
+        "tags": [],
+        "count": 0,
+        "flag": false,
+        "items": [1, null, 2]
+    });
+    let mut pruned = document.clone();
+    assert_eq!(prune(&mut pruned, PruneOptions::default()), 9);
+    assert_eq!(pruned, json!({ "count": 0, "flag": false, "items": [1, 2] }));
+
+    let mut nulls_only = document.clone();
+    let options = PruneOptions { nulls: true, empty_strings: false, empty_objects: false, empty_arrays: false };
+    assert_eq!(prune(&mut nulls_only, options), 3);
+    assert_eq!(nulls_only["a"], json!({ "c": ["", {}] }));
+    assert_eq!(nulls_only["tags"], json!([]));
+
+    let mut root = json!({ "x": null });
+    prune(&mut root, PruneOptions::default());
+    assert_eq!(root, json!({}));
+
+    let mut config = json!({ "server": { "port": 9000, "tls": null }, "hosts": ["a"] });
+    let defaults = json!({
+        "server": { "port": 8080, "tls": false, "timeout": 30 },
+        "hosts": ["localhost"],
+        "debug": false
+    });
+    assert_eq!(fill_defaults(&mut config, &defaults), 2);
+    assert_eq!(
+        config,
+        json!({ "server": { "port": 9000, "tls": null, "timeout": 30 }, "hosts": ["a"], "debug": false })
+    );
+    assert_eq!(fill_defaults(&mut config, &defaults), 0);
+}
+
+/// Tests named redaction profiles in the config file.
+#[test]
+fn test_redaction_profiles() {
+    use json_parser_with_pest::{redact, Config};
+
+    let config = Config::from_json(&json!({
+        "redaction": {
+            "support": { "keys": ["password"], "values": [] },
+            "partner": { "keys": ["password", "email"], "values": [], "replacement": "hash" }
+        }
+    }))
+    .unwrap();
+    let document = json!({ "email": "ann@example.com", "password": "hunter2" });
+
+    let mut support = document.clone();
+    redact(&mut support, &config.redaction_profile("support").unwrap());
+    assert_eq!(support, json!({ "email": "ann@example.com", "password": "***" }));
+
+    let mut partner = document.clone();
+    assert_eq!(redact(&mut partner, &config.redaction_profile("partner").unwrap()), 2);
+    assert!(partner["email"].as_str().unwrap().starts_with("hash:"));
+
+    let error = config.redaction_profile("public").unwrap_err().to_string();
+    assert!(error.contains("available: partner, support"));
+    assert!(Config::from_json(&json!({ "redaction": { "bad": { "values": ["("] } } })).is_err());
+}
+
+/// Tests that the document server masks each response with the redaction profile of the
+/// caller's token.
+#[test]
+fn test_serve_redaction_profiles() {
+    use json_parser_with_pest::{token_digest, Config, DocumentServer};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    let dir = tempdir().unwrap();
+    let order = json!({ "id": 7, "email": "ann@example.com", "card": { "token": "tok_123" } });
+    std::fs::write(dir.path().join("order.json"), order.to_string()).unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "not served").unwrap();
+    let config = Config::from_json(&json!({
+        "redaction": {
+            "support": { "keys": ["token"], "values": [] },
+            "partner": { "keys": ["token", "email"], "replacement": "hash" },
+            "admin": { "keys": [], "values": [] }
+        },
+        "tokens": {
+            (token_digest("s3cret-support")): "support",
+            (token_digest("s3cret-partner")): "partner",
+            (token_digest("s3cret-admin")): "admin"
+        }
+    })).unwrap();
+    let server = DocumentServer::new(dir.path(), &config).unwrap();
+
+    let support = server.handle("GET", "/documents/order", Some("Bearer s3cret-support"));
+    assert_eq!(support.status, 200);
+    assert_eq!(support.body, json!({ "id": 7, "email": "ann@example.com", "card": { "token": "***" } }));
+    let partner = server.handle("GET", "/documents/order?x=1", Some("bearer s3cret-partner"));
+    assert!(partner.body["email"].as_str().unwrap().starts_with("hash:"));
+    assert_eq!(server.handle("GET", "/documents/order", Some("Bearer s3cret-admin")).body, order);
+    assert_eq!(server.handle("GET", "/documents", Some("Bearer s3cret-admin")).body, json!(["order"]));
+
+    assert_eq!(server.handle("GET", "/documents/order", None).status, 401);
+    assert_eq!(server.handle("GET", "/documents/order", Some("Bearer wrong")).status, 401);
+    assert_eq!(server.handle("POST", "/documents/order", Some("Bearer s3cret-admin")).status, 405);
+    assert_eq!(server.handle("GET", "/documents/../order", Some("Bearer s3cret-admin")).status, 404);
+    assert_eq!(server.handle("GET", "/documents/missing", Some("Bearer s3cret-admin")).status, 404);
+
+    assert!(DocumentServer::new(dir.path(), &Config::default()).is_err());
+    assert!(Config::from_json(&json!({ "tokens": { "abc": "nobody" } })).is_err());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || server.serve(listener));
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET /documents/order HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer s3cret-support\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    let body: Value = serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+    assert_eq!(body["card"]["token"], json!("***"));
+}
+
+/// Tests that a seed makes sampling reproducible.
+#[test]
+fn test_seeded_sample() {
+    use json_parser_with_pest::{sample_ndjson, SampleOptions, SeededRng};
+    use std::fs;
+
+    assert_eq!(SeededRng::new(0).next_u64(), 0xe220_a839_7b1d_cdaf);
+    let mut rng = SeededRng::new(7);
+    assert!((0..1000).all(|_| rng.below(10) < 10));
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("events.ndjson");
+    let lines: Vec<String> = (0..100).map(|i| format!("{{\"id\":{}}}", i)).collect();
+    fs::write(&input, lines.join("\n") + "\n\n").unwrap();
+    let sample = |seed: u64, size: usize, name: &str| {
+        let output = dir.path().join(name);
+        let total = sample_ndjson(&input, &output, &SampleOptions { size, seed }).unwrap();
+        assert_eq!(total, 100);
+        fs::read_to_string(output).unwrap()
+    };
+    let first = sample(42, 10, "a.ndjson");
+    assert_eq!(first.lines().count(), 10);
+    assert_eq!(first, sample(42, 10, "b.ndjson"));
+    assert_ne!(first, sample(43, 10, "c.ndjson"));
+    let ids: Vec<u64> = first
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap()["id"].as_u64().unwrap())
+        .collect();
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(sample(42, 1000, "d.ndjson"), lines.join("\n") + "\n");
+
+}
+
+/// Tests that a client that stalls mid-request does not hold up other clients of serve
+#[test]
+fn test_serve_concurrent_connections() {
+    use json_parser_with_pest::{token_digest, Config, DocumentServer};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::time::{Duration, Instant};
+
+    let dir = tempdir().unwrap();
+    std::fs::write(dir.path().join("order.json"), r#"{"id": 7}"#).unwrap();
+    let config = Config::from_json(&json!({
+        "redaction": { "admin": { "keys": [], "values": [] } },
+        "tokens": { (token_digest("s3cret")): "admin" }
+    })).unwrap();
+    let server = DocumentServer::new(dir.path(), &config).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || server.serve(listener));
+
+    let mut stalled = TcpStream::connect(address).unwrap();
+    stalled.write_all(b"GET /documents/order HTTP/1.1\r\nHost: x\r\n").unwrap();
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(b"GET /documents/order HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "Unexpected response: {}", response);
+    assert!(started.elapsed() < Duration::from_secs(5), "A stalled client blocked the server.");
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut request = b"GET /documents/order HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n".to_vec();
+    request.extend(b"X-Filler: 1\r\n".repeat(100));
+    request.extend(b"\r\n");
+    stream.write_all(&request).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"),
+        "Unexpected response: {}",
+        response
+    );
+}
+
+/// Tests deduping, slicing, chunking, concatenating and zipping arrays by path.
+#[test]
+fn test_array_ops() {
+    use json_parser_with_pest::array_ops::{chunk, concat, dedupe, slice, zip};
+
+    let mut json = json!({ "data": { "items": [
+        { "id": 1, "v": "a" }, { "id": 2 }, { "id": 1.0, "v": "b" }, { "v": "no id" }, { "v": "no id" }
+    ] } });
+    assert_eq!(dedupe(&mut json, "data.items", Some(".id")).unwrap(), 1);
+    assert_eq!(json["data"]["items"].as_array().unwrap().len(), 4);
+    assert_eq!(dedupe(&mut json, "data.items", None).unwrap(), 1);
+    assert_eq!(json["data"]["items"][0], json!({ "id": 1, "v": "a" }));
+
+    let mut numbers = json!([0, 1, 2, 3, 4, 5]);
+    slice(&mut numbers, "", -4, Some(-1)).unwrap();
+    assert_eq!(numbers, json!([2, 3, 4]));
+    slice(&mut numbers, "", 5, None).unwrap();
+    assert_eq!(numbers, json!([]));
+
+    let mut batches = json!({ "ids": [1, 2, 3, 4, 5] });
+    chunk(&mut batches, "ids", 2).unwrap();
+    assert_eq!(batches["ids"], json!([[1, 2], [3, 4], [5]]));
+    assert!(chunk(&mut batches, "ids", 0).is_err());
+
+    let mut first = json!({ "items": [1] });
+    concat(&mut first, "items", &[json!([2, 3]), json!([])]).unwrap();
+    assert_eq!(first["items"], json!([1, 2, 3]));
+    assert!(concat(&mut first, "items", &[json!({ "a": 1 })]).is_err());
+
+    let people = json!({ "names": ["ann", "bob", "cid"], "ages": [31, 25] });
+    assert_eq!(zip(&people, &["names", "ages"]).unwrap(), json!([["ann", 31], ["bob", 25]]));
+    assert!(zip(&people, &["names", "missing"]).is_err());
+    assert!(dedupe(&mut json, "data", None).is_err());
+}
+
+/// Tests stopping jq programs and expressions that exceed the sandbox limits.
+#[test]
+fn test_sandboxed_evaluation() {
+    use json_parser_with_pest::{Expression, FunctionLibrary, JqFilter, Sandbox};
+    use std::time::Duration;
+
+    let sandbox = Sandbox::default();
+    let record = json!({ "items": [1, 2, 3], "name": "ann" });
+
+    // Ordinary programs run unchanged.
+    let filter = JqFilter::compile("[.items[] * 2]").unwrap();
+    assert_eq!(sandbox.run(|| filter.run(&record)).unwrap().unwrap(), vec![json!([2, 4, 6])]);
+
+    let error = sandbox.run(|| JqFilter::compile("[range(1e12)]").unwrap().run(&record)).unwrap_err();
+    assert!(error.to_string().contains("step"));
+    // Errors swallowed by `?` or `try` still stop the run.
+    let swallowed = JqFilter::compile("try ([range(1e12)] | length) catch 0").unwrap();
+    assert!(sandbox.run(|| swallowed.run(&record)).is_err());
+
+    let small = Sandbox { max_memory: 1 << 16, ..Sandbox::default() };
+    let doubling = JqFilter::compile(&format!(".name{}", " | . + .".repeat(30))).unwrap();
+    assert!(small.run(|| doubling.run(&record)).unwrap_err().to_string().contains("memory"));
+
+    // User-defined functions can double a string exponentially.
+    let mut library = FunctionLibrary::default();
+    let mut definitions = String::from("def f0($x): $x + $x;\n");
+    for level in 1..40 {
+        definitions.push_str(&format!("def f{}($x): f{}(f{}($x));\n", level, level - 1, level - 1));
+    }
+    library.define(&definitions).unwrap();
+    let bomb = Expression::compile_with_functions("f39(name)", &library).unwrap();
+    assert!(sandbox.run(|| bomb.evaluate(&record)).is_err());
+
+    let slow = Sandbox { timeout: Duration::ZERO, max_steps: u64::MAX, ..Sandbox::default() };
+    let busy = JqFilter::compile("[range(100000)] | length").unwrap();
+    assert!(slow.run(|| busy.run(&record)).unwrap_err().to_string().contains("time"));
+
+    // Outside a sandbox, evaluation is unlimited.
+    assert_eq!(busy.run(&record).unwrap(), vec![json!(100000)]);
+}
+
+/// Tests converting JSON to TOML and back.
+#[test]
+fn test_toml_conversion() {
+    use json_parser_with_pest::{convert_from_format, convert_to_format, parse_toml, TomlOptions};
+
+    let json = json!({
+        "package": { "name": "demo", "version": "0.1.0", "ratio": 1.0 },
+        "dependencies": { "serde": { "version": "1.0", "features": ["derive"] } },
+        "bin": [{ "name": "a", "path": "src/a.rs" }, { "name": "b" }],
+        "matrix": [[1, 2], [{ "x": 1 }]],
+        "title": "quote \" and\nnewline",
+        "weird key": true,
+        "released": { "type": "datetime", "value": "1979-05-27T07:32:00Z" }
+    });
+    let toml = convert_to_format(&json, "toml").unwrap();
+    assert!(toml.contains("[package]\nname = \"demo\"\nratio = 1.0\n"));
+    assert!(toml.contains("[dependencies.serde]\n"));
+    assert!(!toml.contains("[dependencies]\n"));
+    assert!(toml.contains("[[bin]]\nname = \"a\""));
+    assert!(toml.contains("matrix = [[1, 2], [{ x = 1 }]]"));
+    assert!(toml.contains("\"weird key\" = true"));
+    assert!(toml.contains("released = 1979-05-27T07:32:00Z"));
+
+    let annotated = parse_toml(&toml, &TomlOptions { annotate_types: true }).unwrap();
+    assert_eq!(annotated, json);
+    let mut plain = json.clone();
+    plain["released"] = json!("1979-05-27T07:32:00Z");
+    assert_eq!(convert_from_format(&toml, "toml").unwrap(), plain);
+
+    assert!(convert_to_format(&json!({ "a": null }), "toml").unwrap_err().to_string().contains("null at a"));
+    assert!(convert_to_format(&json!([1]), "toml").is_err());
+    assert_eq!(convert_from_format("a: 1\n", "yaml").unwrap(), json!({ "a": 1 }));
+    assert!(convert_from_format("a = 1", "ini").is_err());
+}
+
+/// Tests benchmark reports and their comparison with a baseline
+#[test]
+fn test_bench_compare() {
+    use json_parser_with_pest::{bench_report, compare_bench, run_bench};
+
+    let dir = tempdir().unwrap();
+    let corpus = dir.path().join("small.json");
+    std::fs::write(&corpus, r#"{"items": [1, 2, 3], "name": "small"}"#).unwrap();
+    let results = run_bench(&[("small".to_string(), corpus)], 3).unwrap();
+    assert_eq!(results[0].iterations, 3);
+    let report = bench_report(&results);
+    assert_eq!(results.len(), 3);
+    assert!(report["benchmarks"]["small/serde_json"]["mb_per_s"].as_f64().unwrap() > 0.0);
+    assert!(report["benchmarks"]["small/parse_json"]["mb_per_s"].as_f64().unwrap() > 0.0);
+    assert!(report["benchmarks"]["small/json5"]["mb_per_s"].as_f64().unwrap() > 0.0);
+
+    let current = json!({"benchmarks": {"canada/json5": {"mb_per_s": 80.0}, "twitter/json5": {"mb_per_s": 102.0}, "extra/json5": {"mb_per_s": 5.0}}});
+    let baseline = json!({"benchmarks": {"canada/json5": {"mb_per_s": 100.0}, "twitter/json5": {"mb_per_s": 100.0}, "citm_catalog/json5": {"mb_per_s": 50.0}}});
+    let comparison = compare_bench(&current, &baseline, 5.0).unwrap();
+    assert_eq!(comparison["regressions"], json!(1));
+    assert_eq!(comparison["benchmarks"]["canada/json5"]["status"], json!("regression"));
+    assert_eq!(comparison["benchmarks"]["canada/json5"]["change_percent"], json!(-20.0));
+    assert_eq!(comparison["benchmarks"]["twitter/json5"]["status"], json!("ok"));
+    assert_eq!(comparison["benchmarks"]["extra/json5"]["status"], json!("new"));
+    assert_eq!(comparison["benchmarks"]["citm_catalog/json5"]["status"], json!("missing"));
+    assert!(compare_bench(&current, &json!({}), 5.0).is_err());
+}
+
+/// Tests CSV export with nested columns and import back to JSON
+#[test]
+fn test_csv_conversion() {
+    use json_parser_with_pest::{convert_from_format, convert_to_format, csv_to_json, json_to_csv, CsvOptions};
+
+    let records = json!([
+        {"id": 1, "name": "Ann, Jr.", "address": {"city": "Oslo", "zip": "0150"}, "tags": ["a", "b"]},
+        {"id": 2, "name": "Bob \"B\"", "active": true, "address": {"city": null}}
+    ]);
+    let csv = convert_to_format(&records, "csv").unwrap();
+    assert_eq!(
+        csv,
+        "address.city,address.zip,id,name,tags[0],tags[1],active\n\
+         Oslo,0150,1,\"Ann, Jr.\",a,b,\n\
+         ,,2,\"Bob \"\"B\"\"\",,,true\n"
+    );
+    let back = convert_from_format(&csv, "csv").unwrap();
+    assert_eq!(back[0]["address"], json!({"city": "Oslo", "zip": "0150"}));
+    assert_eq!(back[0]["tags"], json!(["a", "b"]));
+    assert_eq!(back[1]["name"], json!("Bob \"B\""));
+    assert_eq!(back[1]["active"], json!(true));
+    assert_eq!(back[1]["id"], json!(2));
+
+    let options = CsvOptions { delimiter: ';', infer_types: false, ..CsvOptions::default() };
+    let rows = csv_to_json("id;note\n7;\"two\nlines\"\n", &options).unwrap();
+    assert_eq!(rows, json!([{"id": "7", "note": "two\nlines"}]));
+    assert_eq!(json_to_csv(&rows, &options).unwrap(), "id;note\n7;\"two\nlines\"\n");
+
+    assert!(convert_to_format(&json!([1, 2]), "csv").is_err());
+    assert!(csv_to_json("a,b\n1\n", &CsvOptions::default()).is_err());
+}
+
+/// Tests CBOR encoding and decoding, including datetime and bignum tags
+#[test]
+fn test_cbor_conversion() {
+    use json_parser_with_pest::{cbor_to_json, convert_from_bytes, convert_to_bytes, json_to_cbor, CborOptions};
+
+    let reading = json!({"id": 500, "temp": -12, "ratio": 0.1, "ok": true, "tags": ["a"], "note": null});
+    let bytes = convert_to_bytes(&reading, "cbor").unwrap();
+    assert_eq!(convert_from_bytes(&bytes, "cbor").unwrap(), reading);
+    assert_eq!(json_to_cbor(&json!([1, -1, 1.5])).unwrap(), vec![0x83, 0x01, 0x20, 0xfa, 0x3f, 0xc0, 0x00, 0x00]);
+
+    // RFC 8949 appendix A: tag 0 and tag 1 datetimes, bignums, a half float and byte strings.
+    let plain = CborOptions::default();
+    let date = b"\xc0\x74\x32\x30\x31\x33\x2d\x30\x33\x2d\x32\x31\x54\x32\x30\x3a\x30\x34\x3a\x30\x30\x5a";
+    assert_eq!(cbor_to_json(date, &plain).unwrap(), json!("2013-03-21T20:04:00Z"));
+    assert_eq!(cbor_to_json(&[0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0], &plain).unwrap(), json!("2013-03-21T20:04:00Z"));
+    let big = [0xc2, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+    assert_eq!(cbor_to_json(&big, &plain).unwrap(), json!("18446744073709551616"));
+    let negative = [0xc3, 0x49, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+    assert_eq!(cbor_to_json(&negative, &plain).unwrap(), json!("-18446744073709551617"));
+    assert_eq!(cbor_to_json(&[0xf9, 0x3e, 0x00], &plain).unwrap(), json!(1.5));
+    assert_eq!(cbor_to_json(&[0x44, 0x01, 0x02, 0x03, 0x04], &plain).unwrap(), json!("AQIDBA"));
+    assert_eq!(cbor_to_json(&[0x9f, 0x01, 0x02, 0xff], &plain).unwrap(), json!([1, 2]));
+
+    let annotated = CborOptions { annotate_types: true };
+    let value = cbor_to_json(&negative, &annotated).unwrap();
+    assert_eq!(value, json!({"type": "bignum", "value": "-18446744073709551617"}));
+    assert_eq!(json_to_cbor(&value).unwrap(), negative);
+    assert_eq!(json_to_cbor(&cbor_to_json(date, &annotated).unwrap()).unwrap(), date.to_vec());
+
+    assert!(cbor_to_json(&[0x82, 0x01], &plain).is_err());
+    assert!(cbor_to_json(&[0x01, 0x02], &plain).is_err());
+}
+
+/// Tests that cancelled calls stop with an error or a partial report
+#[test]
+fn test_cancellation_token() {
+    use json_parser_with_pest::{
+        data_dictionary, dedup_ndjson, handle_large_json, sort_ndjson, CancellationToken, Cancelled, DedupOptions,
+        ParserError, SortOptions,
+    };
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("input.ndjson");
+    std::fs::write(&input, "{\"id\": 2}\n{\"id\": 1}\n{\"id\": 2}\n").unwrap();
+    let output = dir.path().join("output.ndjson");
+
+    let token = CancellationToken::new();
+    let sorted = token.run(|| sort_ndjson(&input, &output, &SortOptions::new("id")));
+    assert_eq!(sorted.unwrap(), 3);
+
+    let canceller = token.clone();
+    canceller.cancel();
+    assert!(token.is_cancelled());
+    let error = token.run(|| sort_ndjson(&input, &output, &SortOptions::new("id"))).unwrap_err();
+    assert!(error.is::<Cancelled>());
+
+    let report = token.run(|| dedup_ndjson(&input, &output, &DedupOptions::default())).unwrap();
+    assert!(report.cancelled);
+    assert_eq!(report.total, 0);
+    assert_eq!(report.to_json()["cancelled"], json!(true));
+
+    let mut values = token.run(|| handle_large_json(&input)).unwrap();
+    assert!(token.run(|| matches!(values.next(), Some(Err(ParserError::Cancelled)))));
+    assert!(values.next().is_none());
+
+    let records = vec![json!({"a": 1})];
+    assert_eq!(token.run(|| data_dictionary(&records))["cancelled"], json!(true));
+    // Outside of `run`, the token has no effect.
+    assert!(data_dictionary(&records).get("cancelled").is_none());
+    assert!(sort_ndjson(&input, &output, &SortOptions::new("id")).is_ok());
+}
+
+/// Tests BSON conversion with Extended JSON for ObjectId, dates and binary data
+#[test]
+fn test_bson_conversion() {
+    use json_parser_with_pest::{bson_to_json, convert_from_bytes, convert_to_bytes, json_to_bson, BsonOptions};
+
+    // {"hello": "world"} from the BSON specification.
+    let hello = b"\x16\x00\x00\x00\x02hello\x00\x06\x00\x00\x00world\x00\x00";
+    assert_eq!(json_to_bson(&json!({"hello": "world"})).unwrap(), hello.to_vec());
+    assert_eq!(convert_from_bytes(hello, "bson").unwrap(), json!({"hello": "world"}));
+
+    let order = json!({
+        "_id": {"$oid": "507f1f77bcf86cd799439011"},
+        "created": {"$date": "2024-03-01T12:00:00.250Z"},
+        "thumbnail": {"$binary": {"base64": "AQID", "subType": "00"}},
+        "total": {"$numberDecimal": "19.99"},
+        "qty": 3,
+        "views": 5000000000i64,
+        "ratio": 0.5,
+        "tags": ["a", null],
+    });
+    let bytes = convert_to_bytes(&order, "bson").unwrap();
+    assert_eq!(convert_from_bytes(&bytes, "bson").unwrap(), order);
+
+    let canonical = bson_to_json(&bytes, &BsonOptions { canonical: true }).unwrap();
+    assert_eq!(canonical["qty"], json!({"$numberInt": "3"}));
+    assert_eq!(canonical["views"], json!({"$numberLong": "5000000000"}));
+    assert_eq!(canonical["created"], json!({"$date": {"$numberLong": "1709294400250"}}));
+    assert_eq!(json_to_bson(&canonical).unwrap(), bytes);
+
+    // A dump holds documents one after another.
+    let mut dump = bytes.clone();
+    dump.extend_from_slice(hello);
+    let documents = bson_to_json(&dump, &BsonOptions::default()).unwrap();
+    assert_eq!(documents, json!([order, {"hello": "world"}]));
+    assert_eq!(json_to_bson(&documents).unwrap(), dump);
+
+    assert!(json_to_bson(&json!([1])).is_err());
+    assert!(json_to_bson(&json!({"_id": {"$oid": "xyz"}})).is_err());
+    assert!(bson_to_json(&hello[..10], &BsonOptions::default()).is_err());
+}
+
+/// Tests that calls exceeding their timeout fail with ParserError::Timeout
+#[test]
+fn test_with_timeout() {
+    use json_parser_with_pest::{parse_json5, with_timeout, ParserError};
+    use std::time::Duration;
+
+    let text = "{id: 1, tags: ['a', 'b',]}".to_string();
+    let parsed = with_timeout(Duration::from_secs(5), move || parse_json5(&text)).unwrap();
+    assert_eq!(parsed.unwrap(), json!({"id": 1, "tags": ["a", "b"]}));
+
+    let slow = with_timeout(Duration::from_millis(20), || {
+        std::thread::sleep(Duration::from_millis(500));
+        1
+    });
+    match slow {
+        Err(ParserError::Timeout(timeout)) => assert_eq!(timeout, Duration::from_millis(20)),
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+    assert_eq!(ParserError::Timeout(Duration::from_millis(20)).to_string(), "Operation timed out after 20ms");
+}
+
+/// Tests that with_timeout stops parsing that timed out and resumes worker panics
+#[test]
+fn test_with_timeout_stops_work() {
+    use json_parser_with_pest::{parse_json, with_timeout, ParserError};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    let text = format!("[{}]", vec!["{\"a\": [1, 2, 3]}"; 1000].join(","));
+    let stopped = Arc::new(AtomicBool::new(false));
+    let flag = stopped.clone();
+    let result = with_timeout(Duration::from_millis(20), move || loop {
+        if let Err(ParserError::Cancelled) = parse_json(&text) {
+            flag.store(true, Ordering::SeqCst);
+            return;
+        }
+    });
+    assert!(matches!(result, Err(ParserError::Timeout(_))));
+    let started = Instant::now();
+    while !stopped.load(Ordering::SeqCst) {
+        assert!(started.elapsed() < Duration::from_secs(5), "Parsing kept running after the timeout.");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    let panicked = std::panic::catch_unwind(|| {
+        with_timeout(Duration::from_secs(5), || -> () { panic!("worker failed") })
+    });
+    let payload = panicked.expect_err("the worker panic should be resumed");
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"worker failed"));
+}
+
+/// Tests that non-fatal issues are collected as warnings next to the result
+#[test]
+fn test_warnings_collection() {
+    use json_parser_with_pest::{json_to_toml, parse_json5, Warning, Warnings};
+
+    let (value, warnings) = Warnings::collect(|| parse_json5("{a: 1, b: NaN, a: 2}"));
+    assert_eq!(value.unwrap(), json!({"a": 2, "b": null}));
+    let messages: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        [
+            "NaN at line 1, column 11 has no JSON representation and became null",
+            "Duplicate key 'a' at line 1, column 16; the last value is kept",
+        ]
+    );
+
+    let (toml, warnings) = Warnings::collect(|| json_to_toml(&json!({"server": {"id": u64::MAX}})));
+    assert_eq!(toml.unwrap(), "[server]\nid = 1.8446744073709552e19\n");
+    assert_eq!(
+        warnings.into_iter().collect::<Vec<_>>(),
+        [Warning {
+            path: "server.id".to_string(),
+            message: "Integer 18446744073709551615 exceeds the TOML range and was written as a float".to_string(),
+        }]
+    );
+
+    // Nested calls keep their warnings to themselves.
+    let ((_, inner), outer) = Warnings::collect(|| Warnings::collect(|| parse_json5("{a: 1, a: 2}")));
+    assert_eq!((inner.len(), outer.len()), (1, 0));
+    assert!(Warnings::collect(|| parse_json5("{a: 1}")).1.is_empty());
+    assert_eq!(inner.to_json()[0]["path"], json!(""));
+}
+
+/// Tests reading YAML and the other text formats into JSON with parse_format
+#[test]
+fn test_parse_format() {
+    use json_parser_with_pest::{convert_to_format, parse_format, ParserError};
+
+    let config = json!({"name": "api", "ports": [80, 443], "tls": {"enabled": true}});
+    let yaml = convert_to_format(&config, "yaml").unwrap();
+    assert_eq!(parse_format(&yaml, "yaml").unwrap(), config);
+    let toml = convert_to_format(&config, "toml").unwrap();
+    assert_eq!(parse_format(&toml, "toml").unwrap(), config);
+
+    let json = r#"{"name": "api", "ports": [80, 443], "tls": {"enabled": true}}"#;
+    assert_eq!(parse_format(json, "json").unwrap(), config);
+    let jsonc = "{\n  // service\n  \"name\": \"api\", \"ports\": [80, 443,], \"tls\": {\"enabled\": true},\n}";
+    assert_eq!(parse_format(jsonc, "jsonc").unwrap(), config);
+    assert_eq!(parse_format("{name: 'api', ports: [80, 443], tls: {enabled: true}}", "json5").unwrap(), config);
+
+    let error = parse_format("{\"name\": }", "json").unwrap_err();
+    assert!(matches!(error.downcast_ref::<ParserError>(), Some(ParserError::JsonParseError(_))));
+    assert!(parse_format("a: 1", "ini").is_err());
+}
+
+/// Tests reading CSV with European number and date formats
+#[test]
+fn test_csv_locale_import() {
+    use json_parser_with_pest::{csv_to_json, CsvOptions, Warnings};
+
+    let csv = "name;born;salary;zip;code\nAnna;01.03.1990;1.234,50;01234;1.5\nBen;31.12.1985 23:59;-12,5;10115;7\nCara;unknown;1.000.000;;1.2345\n";
+    let mut options = CsvOptions {
+        delimiter: ';',
+        decimal_separator: ',',
+        thousands_separator: Some('.'),
+        ..CsvOptions::default()
+    };
+    options.date_formats.insert("born".to_string(), "%d.%m.%Y".to_string());
+    let (rows, warnings) = Warnings::collect(|| csv_to_json(csv, &options));
+    assert_eq!(
+        rows.unwrap(),
+        json!([
+            {"name": "Anna", "born": "1990-03-01", "salary": 1234.5, "zip": "01234", "code": "1.5"},
+            {"name": "Ben", "born": "31.12.1985 23:59", "salary": -12.5, "zip": 10115, "code": 7},
+            {"name": "Cara", "born": "unknown", "salary": 1000000, "zip": null, "code": "1.2345"}
+        ])
+    );
+    let paths: Vec<&str> = warnings.iter().map(|warning| warning.path.as_str()).collect();
+    assert_eq!(paths, ["[1].born", "[2].born"]);
+
+    options.date_formats.insert("born".to_string(), "%d.%m.%Y %H:%M".to_string());
+    let rows = csv_to_json("born\n31.12.1985 23:59\n", &options).unwrap();
+    assert_eq!(rows, json!([{"born": "1985-12-31T23:59:00Z"}]));
+
+    // The default options keep reading numbers with a decimal point only.
+    let rows = csv_to_json("a,b\n\"1,5\",2.5\n", &CsvOptions::default()).unwrap();
+    assert_eq!(rows, json!([{"a": "1,5", "b": 2.5}]));
+}
+
+/// Tests the XML declaration, array item wrappers and scalar attributes
+#[test]
+fn test_xml_items_and_attributes() {
+    use json_parser_with_pest::{json_to_xml, xml_to_json, XmlOptions};
+
+    let json = json!([
+        {"id": 1, "name": "Tom & \"Jerry\"", "tags": ["<a>", "b"], "note": null, "address": {"city": "Kyiv"}},
+    ]);
+    let options = XmlOptions {
+        root: "users".to_string(),
+        declaration: true,
+        item_name: Some("user".to_string()),
+        scalar_attributes: true,
+        ..XmlOptions::default()
+    };
+    let xml = json_to_xml(&json, &options).unwrap();
+    assert_eq!(
+        xml,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <users>\n\
+         <user id=\"1\" name=\"Tom &amp; &quot;Jerry&quot;\">\n\
+         <address city=\"Kyiv\">\n</address>\n\
+         <note />\n\
+         <tags>\n<user>&lt;a&gt;</user>\n<user>b</user>\n</tags>\n\
+         </user>\n\
+         </users>\n"
+    );
+    let parsed = xml_to_json(&xml).unwrap();
+    assert_eq!(parsed["users"]["user"]["@name"], json!("Tom & \"Jerry\""));
+    assert_eq!(parsed["users"]["user"]["tags"]["user"], json!(["<a>", "b"]));
+
+    let xml = json_to_xml(&json!({"tags": ["a"]}), &XmlOptions { item_name: Some("item".to_string()), ..XmlOptions::default() }).unwrap();
+    assert_eq!(xml, "<root>\n<tags>\n<item>a</item>\n</tags>\n</root>\n");
+}
+
+/// Tests validating CSV rows against a schema during import, quarantining failures
+#[test]
+fn test_csv_schema_import() {
+    use json_parser_with_pest::{csv_to_json_validated, CsvOptions};
+
+    let schema = json!({
+        "type": "object",
+        "required": ["id", "zip"],
+        "properties": {
+            "id": {"type": "integer"},
+            "zip": {"type": "string"},
+            "score": {"type": ["number", "null"]},
+            "address": {"type": "object", "properties": {"city": {"type": "string"}}}
+        }
+    });
+    let csv = "id,zip,score,address.city\n1,01234,9.5,Kyiv\nx,10115,,Berlin\n3,2000\n4,42,,1984\n";
+    let import = csv_to_json_validated(csv, &schema, &CsvOptions::default()).unwrap();
+    assert_eq!(
+        import.rows,
+        [
+            json!({"id": 1, "zip": "01234", "score": 9.5, "address": {"city": "Kyiv"}}),
+            json!({"id": 4, "zip": "42", "score": null, "address": {"city": "1984"}}),
+        ]
+    );
+    let rows: Vec<&Value> = import.quarantined.iter().map(|row| &row["row"]).collect();
+    assert_eq!(rows, [&json!(3), &json!(4)]);
+    assert_eq!(import.quarantined[0]["text"], json!("x,10115,,Berlin"));
+    assert!(import.quarantined[0]["error"].as_str().unwrap().starts_with("/id: expected integer"));
+    assert_eq!(import.quarantined[1]["error"], json!("CSV row 4 has 2 fields, expected 4"));
+}
+
+/// Tests that one `PathSyntax` is written and read consistently by get, set, search, flatten and diff.
+#[test]
+fn test_path_syntax() {
+    use json_parser_with_pest::{
+        delete_by_path_with, diff_json_with, flatten_with, get_by_path_with, search, set_by_path_with, unflatten_with,
+        BracketStyle, PathSyntax, SearchOptions,
+    };
+
+    let json = json!({"data": {"items": [{"name": "a/b", "0": "zero"}], "x.y": 1}});
+    let slash = PathSyntax::delimited("/");
+    let segments: PathSyntax = "segments".parse().unwrap();
+    assert_eq!(segments.brackets, BracketStyle::None);
+
+    let flat = flatten_with(&json, &slash);
+    assert_eq!(flat, json!({"data/items[0]/0": "zero", "data/items[0]/name": "a/b", "data/x.y": 1}));
+    assert_eq!(unflatten_with(&flat, &slash).unwrap(), json);
+    let flat = flatten_with(&json, &segments);
+    assert_eq!(flat, json!({"data.items.0[\"0\"]": "zero", "data.items.0.name": "a/b", "data[\"x.y\"]": 1}));
+    assert_eq!(unflatten_with(&flat, &segments).unwrap(), json);
+    let pointers = flatten_with(&json, &PathSyntax::pointer());
+    assert_eq!(pointers["/data/items/0/name"], json!("a/b"));
+    let brackets: PathSyntax = "brackets".parse().unwrap();
+    assert!(flatten_with(&json, &brackets).get("[\"data\"][\"x.y\"]").is_some());
+
+    let options = SearchOptions { syntax: slash.clone(), ..Default::default() };
+    let found = search(&json, "a/b", &options).unwrap();
+    assert_eq!(found[0].0, "data/items[0]/name");
+    assert_eq!(get_by_path_with(&json, &found[0].0, &slash), Some(json!("a/b")));
+    assert_eq!(get_by_path_with(&json, "data.items.0.name", &segments), Some(json!("a/b")));
+    assert_eq!(get_by_path_with(&json, "/data/x.y", &PathSyntax::pointer()), Some(json!(1)));
+    assert_eq!(get_by_path_with(&json, "", &slash), Some(json.clone()));
+
+    let mut edited = json.clone();
+    set_by_path_with(&mut edited, "data/tags[1]", json!("b"), &slash).unwrap();
+    assert_eq!(edited["data"]["tags"], json!([null, "b"]));
+    assert_eq!(delete_by_path_with(&mut edited, "data/x.y", &slash).unwrap(), json!(1));
+
+    let changes = diff_json_with(&json, &edited, &slash);
+    let paths: Vec<&str> = changes.iter().map(|entry| entry.path.as_str()).collect();
+    assert_eq!(paths, vec!["data/x.y", "data/tags"]);
+    assert_eq!(changes[1].pointer, "/data/tags");
+
+    assert_eq!(PathSyntax::default().convert("data[\"x.y\"]", &PathSyntax::pointer()).unwrap(), "/data/x.y");
+    assert_eq!(PathSyntax::pointer().convert("/a~1b/2", &slash).unwrap(), "[\"a/b\"][2]");
+}
+
+/// Tests rendering arrays of objects as Markdown and HTML tables.
+#[test]
+fn test_table_output() {
+    use json_parser_with_pest::{convert_to_format, json_to_html, json_to_markdown, NestedCells, TableOptions};
+
+    let rows = json!([
+        {"name": "a|b", "size": 10, "meta": {"owner": "ann"}},
+        {"name": "<c>", "size": null, "note": "two\nlines"}
+    ]);
+    assert_eq!(
+        convert_to_format(&rows, "markdown").unwrap(),
+        "| meta.owner | name | size | note |\n| --- | --- | ---: | --- |\n\
+         | ann | a\\|b | 10 |  |\n|  | <c> |  | two<br>lines |\n"
+    );
+    let inline = TableOptions { nested: NestedCells::Inline, ..Default::default() };
+    assert!(json_to_markdown(&rows, &inline).unwrap().starts_with("| meta | name | size | note |\n| --- | --- | ---: | --- |\n| {\"owner\":\"ann\"} |"));
+
+    let html = json_to_html(&rows, &TableOptions::default()).unwrap();
+    assert!(html.starts_with("<table>\n  <thead>\n    <tr>\n      <th>meta.owner</th>\n"));
+    assert!(html.contains("      <th style=\"text-align: right\">size</th>\n"));
+    assert!(html.contains("      <td>&lt;c&gt;</td>\n"));
+    assert!(html.ends_with("  </tbody>\n</table>\n"));
+    assert!(convert_to_format(&json!([1, 2]), "html").is_err());
+}
+
+/// Tests querying, editing, validating and serializing through `JsonDocument`.
+#[test]
+fn test_json_document() {
+    use json_parser_with_pest::{DocumentOptions, JsonDocument, PathSyntax, SearchOptions};
+
+    let text = "{\n  // build settings\n  \"compiler\": {\"strict\": false, \"targets\": [\"es5\"]},\n  \"name\": \"app\"\n}\n";
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("settings.jsonc");
+    std::fs::write(&path, text).unwrap();
+
+    let mut doc = JsonDocument::open(&path).unwrap();
+    assert_eq!(doc.source(), Some(path.display().to_string().as_str()));
+    assert_eq!(doc.get("compiler.targets[0]"), Some(json!("es5")));
+    assert_eq!(doc.query("$..strict").unwrap(), vec![json!(false)]);
+    let span = doc.span("name").unwrap();
+    assert_eq!(&text[span], "\"app\"");
+
+    doc.set("compiler.strict", json!(true)).unwrap();
+    assert!(doc.to_json().contains("// build settings"));
+    assert_eq!(doc.value()["compiler"]["strict"], json!(true));
+    assert_eq!(doc.delete("name").unwrap(), json!("app"));
+    assert_eq!(doc.validate(&json!({"type": "object", "required": ["name"]})).violations.len(), 1);
+
+    doc.set("output.dir", json!("dist")).unwrap();
+    assert_eq!(doc.span("compiler"), None);
+    assert!(doc.to_json().starts_with("{\n  \"compiler\""));
+    assert!(doc.serialize("yaml").unwrap().contains("dir: dist"));
+
+    let options = DocumentOptions { syntax: PathSyntax::pointer(), ..Default::default() };
+    let doc = JsonDocument::from_value(json!({"a": [{"b": "x"}]})).with_options(options).with_source("https://example.com/a.json");
+    let found = doc.search("x", &SearchOptions::default()).unwrap();
+    assert_eq!(found[0].0, "/a/0/b");
+    assert_eq!(doc.get(&found[0].0), Some(json!("x")));
+    assert!(JsonDocument::parse("{\"a\": }").is_err());
+}
+
+/// Tests the layout and inferred schema of Parquet files written by `json_to_parquet`.
+#[cfg(feature = "parquet")]
+#[test]
+fn test_parquet_export() {
+    use json_parser_with_pest::{convert_to_bytes, json_to_parquet, NestedCells, ParquetOptions};
+
+    let records = json!([
+        {"id": 1, "name": "ann", "active": true, "score": 1.5, "address": {"city": "Kyiv"}},
+        {"id": 2, "name": null, "active": false, "score": 2}
+    ]);
+    /// Reads an unsigned LEB128 varint.
+    fn varint(bytes: &[u8], at: &mut usize) -> u64 {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = bytes[*at];
+            *at += 1;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte < 0x80 {
+                break;
+            }
+        }
+        value
+    }
+    /// Reads a zigzag varint.
+    fn signed(bytes: &[u8], at: &mut usize) -> i64 {
+        let value = varint(bytes, at);
+        (value >> 1) as i64 ^ -((value & 1) as i64)
+    }
+    /// Reads a Thrift compact value of a type; structs become objects keyed by field id.
+    fn thrift(bytes: &[u8], at: &mut usize, kind: u8) -> Value {
+        match kind {
+            1 | 2 => json!(kind == 1),
+            4..=6 => json!(signed(bytes, at)),
+            8 => {
+                let len = varint(bytes, at) as usize;
+                *at += len;
+                json!(String::from_utf8_lossy(&bytes[*at - len..*at]))
+            }
+            9 => {
+                let header = bytes[*at];
+                *at += 1;
+                let len = match header >> 4 {
+                    15 => varint(bytes, at) as usize,
+                    len => len as usize,
+                };
+                (0..len).map(|_| thrift(bytes, at, header & 0x0F)).collect()
+            }
+            12 => {
+                let (mut fields, mut id) = (serde_json::Map::new(), 0);
+                loop {
+                    let header = bytes[*at];
+                    *at += 1;
+                    if header == 0 {
+                        return Value::Object(fields);
+                    }
+                    id = match header >> 4 {
+                        0 => signed(bytes, at),
+                        delta => id + i64::from(delta),
+                    };
+                    fields.insert(id.to_string(), thrift(bytes, at, header & 0x0F));
+                }
+            }
+            other => panic!("unexpected Thrift type {}", other),
+        }
+    }
+
+    let file = convert_to_bytes(&records, "parquet").unwrap();
+    assert_eq!(&file[..4], b"PAR1");
+    assert_eq!(&file[file.len() - 4..], b"PAR1");
+    let footer_length = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap()) as usize;
+    let footer_start = file.len() - 8 - footer_length;
+    let mut at = footer_start;
+    let meta = thrift(&file, &mut at, 12);
+    assert_eq!(at, file.len() - 8, "The footer should end before its length");
+    assert_eq!(meta["3"], json!(2), "num_rows");
+    assert!(meta["6"].as_str().unwrap().starts_with("json_parser_with_pest version"));
+    let schema = meta["2"].as_array().unwrap();
+    assert_eq!(schema[0]["4"], "schema");
+    assert_eq!(schema[0]["5"], json!(schema.len() - 1));
+    let row_groups = meta["4"].as_array().unwrap();
+    assert_eq!(row_groups.len(), 1);
+    assert_eq!(row_groups[0]["3"], json!(2));
+
+    // Decode every column chunk: its page header, the definition levels and the
+    // PLAIN values, and rebuild the (flattened) rows from them.
+    let mut rows = vec![serde_json::Map::new(), serde_json::Map::new()];
+    for (column, chunk) in schema[1..].iter().zip(row_groups[0]["1"].as_array().unwrap()) {
+        let name = column["4"].as_str().unwrap();
+        let chunk_meta = &chunk["3"];
+        assert_eq!(chunk_meta["1"], column["1"], "physical type of {}", name);
+        assert_eq!(chunk_meta["3"], json!([name]));
+        assert_eq!(chunk_meta["4"], json!(0), "codec of {}", name);
+        assert_eq!(chunk_meta["5"], json!(2), "num_values of {}", name);
+        let offset = chunk_meta["9"].as_u64().unwrap() as usize;
+        assert!(offset >= 4 && offset < footer_start);
+        let mut at = offset;
+        let header = thrift(&file, &mut at, 12);
+        assert_eq!(header["1"], json!(0), "page type of {}", name);
+        assert_eq!(header["2"], header["3"]);
+        assert_eq!(header["5"]["1"], json!(2));
+        assert_eq!(header["5"]["2"], json!(0), "PLAIN encoding of {}", name);
+        let page_end = at + header["3"].as_u64().unwrap() as usize;
+        assert_eq!(page_end - offset, chunk_meta["6"].as_u64().unwrap() as usize);
+
+        let levels_length = u32::from_le_bytes(file[at..at + 4].try_into().unwrap()) as usize;
+        at += 4;
+        let levels_end = at + levels_length;
+        let mut defined = Vec::new();
+        while at < levels_end {
+            let run = varint(&file, &mut at);
+            assert_eq!(run & 1, 0, "only RLE runs are written");
+            defined.extend(std::iter::repeat_n(file[at] == 1, (run >> 1) as usize));
+            at += 1;
+        }
+        assert_eq!(defined.len(), 2);
+        let present = defined.iter().filter(|&&defined| defined).count();
+        let mut values = (0..present).map(|index| match column["1"].as_i64().unwrap() {
+            0 => json!(file[at] >> index & 1 == 1),
+            2 => {
+                at += 8;
+                json!(i64::from_le_bytes(file[at - 8..at].try_into().unwrap()))
+            }
+            5 => {
+                at += 8;
+                json!(f64::from_le_bytes(file[at - 8..at].try_into().unwrap()))
+            }
+            6 => {
+                let len = u32::from_le_bytes(file[at..at + 4].try_into().unwrap()) as usize;
+                at += 4 + len;
+                json!(std::str::from_utf8(&file[at - len..at]).unwrap())
+            }
+            other => panic!("unexpected physical type {}", other),
+        }).collect::<Vec<_>>().into_iter();
+        if column["1"] == json!(0) {
+            at += present.div_ceil(8);
+        }
+        assert_eq!(at, page_end, "page of {} should hold exactly its values", name);
+        for (row, defined) in rows.iter_mut().zip(defined) {
+            let value = if defined { values.next().unwrap() } else { Value::Null };
+            row.insert(name.to_string(), value);
+        }
+    }
+    assert_eq!(
+        Value::Array(rows.into_iter().map(Value::Object).collect()),
+        json!([
+            {"id": 1, "name": "ann", "active": true, "score": 1.5, "address.city": "Kyiv"},
+            {"id": 2, "name": null, "active": false, "score": 2.0, "address.city": null}
+        ])
+    );
+    let contains = |haystack: &[u8], needle: &[u8]| haystack.windows(needle.len()).any(|window| window == needle);
+
+    let inline = ParquetOptions { nested: NestedCells::Inline, row_group_size: 1 };
+    let file = json_to_parquet(&records, &inline).unwrap();
+    assert!(contains(&file, br#"{"city":"Kyiv"}"#));
+    assert!(json_to_parquet(&json!([1]), &inline).is_err());
+}
+
+/// Tests Avro files and the Avro JSON encoding, with inferred and given schemas.
+#[test]
+fn test_avro_conversion() {
+    use json_parser_with_pest::{avro_json_to_json, avro_to_json, convert_from_bytes, convert_to_bytes, infer_avro_schema, json_to_avro, json_to_avro_json, AvroOptions};
+
+    let records = json!([
+        {"id": 1, "name": "ann", "score": 1.5, "address": {"city": "Kyiv"}, "tags": ["a"]},
+        {"id": -2, "score": 2, "address": {"city": "Lviv"}, "tags": []}
+    ]);
+    let schema = infer_avro_schema(&records);
+    assert_eq!(schema["name"], "Record");
+    let fields = schema["fields"].as_array().unwrap();
+    assert_eq!(fields[0], json!({"name": "address", "type": {"type": "record", "name": "Record_address", "fields": [{"name": "city", "type": "string"}]}}));
+    assert_eq!(fields[1], json!({"name": "id", "type": "long"}));
+    assert_eq!(fields[2], json!({"name": "name", "type": ["null", "string"], "default": null}));
+    assert_eq!(fields[3], json!({"name": "score", "type": "double"}));
+
+    let file = convert_to_bytes(&records, "avro").unwrap();
+    assert_eq!(&file[..4], b"Obj\x01");
+    let mut expected = records.clone();
+    expected[0]["id"] = json!(1);
+    expected[1]["name"] = json!(null);
+    expected[1]["score"] = json!(2.0);
+    assert_eq!(convert_from_bytes(&file, "avro").unwrap(), expected);
+    let encoded = json_to_avro_json(&records, &infer_avro_schema(&records)).unwrap();
+    assert_eq!(encoded[0]["name"], json!({"string": "ann"}));
+    assert_eq!(encoded[1]["name"], json!(null));
+
+    let schema = json!({"type": "record", "name": "Event", "namespace": "com.example", "fields": [
+        {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["click", "view"]}},
+        {"name": "count", "type": "int"},
+        {"name": "next", "type": ["null", "Event"], "default": null}
+    ]});
+    let event = json!({"kind": "view", "count": 3, "next": {"kind": "click", "count": 1}});
+    let options = AvroOptions { schema: Some(schema.clone()) };
+    let file = json_to_avro(&event, &options).unwrap();
+    // One block of one record: kind 1, count 3, the Event branch, kind 0, count 1, null.
+    assert!(file.windows(8).any(|window| window == [2, 12, 2, 6, 2, 0, 2, 0]));
+    let decoded = avro_to_json(&file).unwrap();
+    assert_eq!(decoded["next"], json!({"kind": "click", "count": 1, "next": null}));
+
+    let encoded = json_to_avro_json(&event, &schema).unwrap();
+    assert_eq!(encoded["next"]["com.example.Event"]["next"], json!(null));
+    assert_eq!(avro_json_to_json(&encoded, &schema).unwrap(), decoded);
+
+    let error = json_to_avro(&json!({"kind": "scroll", "count": 1}), &options).unwrap_err();
+    assert!(error.to_string().contains("at kind"), "{}", error);
+    assert!(json_to_avro(&json!({"kind": "view", "count": 1u64 << 40}), &options).is_err());
+    assert!(avro_to_json(&file[..file.len() - 3]).is_err());
+}
+
+/// Tests building objects and arrays with `JsonBuilder`.
+#[test]
+fn test_json_builder() {
+    use json_parser_with_pest::{JsonBuilder, ObjectBuilder};
+
+    let verbose = false;
+    let nickname: Option<&str> = None;
+    let value = JsonBuilder::object()
+        .key("name").value("ann")
+        .key("age").value(30)
+        .array("tags", |tags| tags.value("a").extend(["b", "c"]))
+        .object("address", |address| address.field("city", "Kyiv").field_opt("zip", nickname))
+        .key("scores").array(|scores| scores.value(1.5).array(|inner| inner.value(true)).object(|o| o.field("n", json!(null))))
+        .field_opt("nickname", nickname)
+        .when(verbose, |object| object.field("debug", true))
+        .extend((0..2).map(|i| (format!("k{}", i), i)))
+        .build();
+    assert_eq!(value, json!({
+        "name": "ann", "age": 30, "tags": ["a", "b", "c"], "address": {"city": "Kyiv"},
+        "scores": [1.5, [true], {"n": null}], "k0": 0, "k1": 1
+    }));
+
+    let rows: Value = JsonBuilder::array()
+        .extend((1..=2).map(|id| ObjectBuilder::default().field("id", id)))
+        .when(true, |rows| rows.value(json!("end")))
+        .into();
+    assert_eq!(rows, json!([{"id": 1}, {"id": 2}, "end"]));
+    assert_eq!(JsonBuilder::object().field("a", 1).field("a", 2).build(), json!({"a": 2}));
+}
+
+/// Tests JSON Schemas derived from `Deserialize` types, including enums and recursion.
+#[test]
+fn test_schema_for_types() {
+    use json_parser_with_pest::{schema_for, validate_as};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct User {
+        user_name: String,
+        age: u8,
+        nickname: Option<String>,
+        tags: Vec<String>,
+        scores: HashMap<String, f64>,
+        role: Role,
+    }
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    enum Role {
+        Admin,
+        Guest,
+        Custom(String),
+    }
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Node {
+        value: i64,
+        children: Vec<Node>,
+    }
+
+    let schema = schema_for::<User>().unwrap();
+    assert_eq!(schema["$schema"], "https://json-schema.org/draft/2020-12/schema");
+    assert_eq!(schema["properties"]["userName"], json!({"type": "string"}));
+    assert_eq!(schema["properties"]["age"], json!({"type": "integer", "minimum": 0, "maximum": 255}));
+    assert_eq!(schema["properties"]["nickname"], json!({"type": ["string", "null"]}));
+    assert_eq!(schema["properties"]["scores"], json!({"type": "object", "additionalProperties": {"type": "number"}}));
+    assert_eq!(schema["required"], json!(["userName", "age", "tags", "scores", "role"]));
+    assert_eq!(schema["properties"]["role"]["oneOf"][0], json!({"type": "string", "enum": ["Admin", "Guest"]}));
+    assert_eq!(schema["properties"]["role"]["oneOf"][1]["properties"]["Custom"], json!({"type": "string"}));
+
+    let report = validate_as::<User>(&json!({
+        "userName": "ann", "age": 300, "tags": ["a"], "scores": {"math": "A"}, "role": "Owner"
+    })).unwrap();
+    let pointers: Vec<&str> = report.violations.iter().map(|v| v.pointer.as_str()).collect();
+    assert_eq!(pointers, ["/age", "/role", "/scores/math"]);
+
+    let schema = schema_for::<Node>().unwrap();
+    assert_eq!(schema["$ref"], "#/$defs/Node");
+    assert_eq!(schema["$defs"]["Node"]["properties"]["children"]["items"], json!({"$ref": "#/$defs/Node"}));
+    let report = validate_as::<Node>(&json!({"value": 1, "children": [{"value": "x", "children": []}]})).unwrap();
+    assert_eq!(report.violations[0].pointer, "/children/0/value");
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct Endless {
+        next: Box<Endless>,
+    }
+    assert!(schema_for::<Endless>().is_err());
+}
+
+/// Tests reading and writing query strings with nested keys and arrays.
+#[test]
+fn test_querystring_conversion() {
+    use json_parser_with_pest::{convert_from_format, convert_to_format, json_to_querystring, querystring_to_json, ArrayFormat, QueryStringOptions};
+
+    let parsed = convert_from_format("?page=2&filter[status]=open&filter[q]=a+b%26c&tags[]=x&tags[]=y&id=1&id=2&flag", "querystring").unwrap();
+    assert_eq!(parsed, json!({
+        "page": "2", "filter": {"status": "open", "q": "a b&c"}, "tags": ["x", "y"], "id": ["1", "2"], "flag": ""
+    }));
+    let typed = QueryStringOptions { typed: true, ..Default::default() };
+    assert_eq!(querystring_to_json("n=1.5&ok=true&none=null&zip=007&items[0][id]=3&items[1][id]=4", &typed).unwrap(), json!({
+        "n": 1.5, "ok": true, "none": null, "zip": "007", "items": [{"id": 3}, {"id": 4}]
+    }));
+    assert!(querystring_to_json("a[b=1", &typed).is_err());
+    assert!(querystring_to_json("a=1&a[b]=2", &typed).is_err());
+
+    let json = json!({"q": "a b&c", "filter": {"status": "open"}, "tags": ["x", "y"], "rows": [{"id": 1}], "empty": null});
+    assert_eq!(convert_to_format(&json, "querystring").unwrap(), "empty=&filter[status]=open&q=a+b%26c&rows[0][id]=1&tags[]=x&tags[]=y");
+    let repeat = QueryStringOptions { arrays: ArrayFormat::Repeat, ..Default::default() };
+    assert_eq!(json_to_querystring(&json!({"tags": ["x", "y"]}), &repeat).unwrap(), "tags=x&tags=y");
+    let indices = QueryStringOptions { arrays: ArrayFormat::Indices, ..Default::default() };
+    assert_eq!(json_to_querystring(&json!({"tags": ["x"]}), &indices).unwrap(), "tags[0]=x");
+    assert_eq!(querystring_to_json(&convert_to_format(&json, "querystring").unwrap(), &typed).unwrap()["rows"], json!([{"id": 1}]));
+    assert!(json_to_querystring(&json!([1]), &repeat).is_err());
+}
+
+/// Tests round-tripping configuration through `.env` and Java properties files.
+#[test]
+fn test_dotenv_and_properties_conversion() {
+    use json_parser_with_pest::{convert_from_format, convert_to_format, dotenv_to_json, json_to_dotenv, properties_to_json, PropertiesOptions};
+
+    let config = json!({"APP_NAME": "shop", "DATABASE": {"HOST": "db", "PORT": 5432}, "SERVERS": ["a", "b"], "GREETING": "Hello \"you\"\n$HOME", "EMPTY": null});
+    let env = convert_to_format(&config, "dotenv").unwrap();
+    assert_eq!(env, "APP_NAME=shop\nDATABASE__HOST=db\nDATABASE__PORT=5432\nEMPTY=\nGREETING=\"Hello \\\"you\\\"\\n\\$HOME\"\nSERVERS__0=a\nSERVERS__1=b\n");
+    let typed = PropertiesOptions { typed: true, ..PropertiesOptions::dotenv() };
+    let mut expected = config.clone();
+    expected["EMPTY"] = json!("");
+    assert_eq!(dotenv_to_json(&env, &typed).unwrap(), expected);
+
+    let file = "# comment\nexport TOKEN='a#b $X'\nURL=http://x # trailing\nMULTI=\"one\ntwo\"\n\nDEBUG=true\n";
+    assert_eq!(convert_from_format(file, "dotenv").unwrap(), json!({"TOKEN": "a#b $X", "URL": "http://x", "MULTI": "one\ntwo", "DEBUG": "true"}));
+    assert!(convert_from_format("NO_EQUALS\n", "dotenv").is_err());
+    assert!(convert_from_format("A=\"open\n", "dotenv").is_err());
+    assert!(json_to_dotenv(&json!(["a"]), &PropertiesOptions::dotenv()).is_err());
+
+    let properties = "! comment\nserver.port : 8080\nserver.name=caf\\u00E9\nlist[0] first\nlong = one \\\n    two\nkey\\ with\\ space=x\n";
+    let parsed = properties_to_json(properties, &PropertiesOptions { typed: true, ..PropertiesOptions::java() }).unwrap();
+    assert_eq!(parsed, json!({"server": {"port": 8080, "name": "café"}, "list": ["first"], "long": "one two", "key with space": "x"}));
+    let written = convert_to_format(&parsed, "properties").unwrap();
+    assert_eq!(written, "key\\ with\\ space=x\nlist[0]=first\nlong=one two\nserver.name=caf\\u00E9\nserver.port=8080\n");
+    assert_eq!(convert_from_format(&written, "properties").unwrap()["server"]["name"], json!("café"));
+}
+
+/// Tests the serde_json-compatible functions of the compat module.
+#[test]
+fn test_compat_api() {
+    use json_parser_with_pest::compat::{self, Category};
+    use std::collections::BTreeMap;
+
+    let parsed: BTreeMap<String, Vec<u32>> = compat::from_str(r#"{"b": [1, 2], "a": [3]}"#).unwrap();
+    assert_eq!(parsed["b"], vec![1, 2]);
+    assert_eq!(compat::to_string(&parsed).unwrap(), r#"{"a":[3],"b":[1,2]}"#);
+    assert_eq!(compat::to_string_pretty(&parsed).unwrap(), "{\n  \"a\": [\n    3\n  ],\n  \"b\": [\n    1,\n    2\n  ]\n}");
+    assert_eq!(compat::to_string_pretty(&parsed).unwrap(), serde_json::to_string_pretty(&parsed).unwrap());
+    let mut buffer = Vec::new();
+    compat::to_writer(&mut buffer, &compat::json!({"x": null})).unwrap();
+    assert_eq!(buffer, br#"{"x":null}"#);
+
+    let error = compat::from_str::<compat::Value>("{\n  \"a\": x\n}").unwrap_err();
+    assert_eq!((error.classify(), error.line(), error.column()), (Category::Syntax, 2, 8));
+    assert_eq!(error.details().unwrap().snippet, "  \"a\": x");
+    assert!(error.to_string().starts_with("JSON parse error at line 2, column 8: expected value"));
+    assert!(compat::from_str::<compat::Value>("[1, 2").unwrap_err().is_eof());
+    let error = compat::from_str::<Vec<u32>>("[-1]").unwrap_err();
+    assert!(error.is_data() && error.line() == 0);
+    let mut bad_keys = std::collections::HashMap::new();
+    bad_keys.insert(vec![1], 2);
+    assert!(compat::to_string(&bad_keys).unwrap_err().is_data());
+}
+
+/// Replays the regression corpus in tests/regressions and tests saving new cases.
+#[test]
+fn test_regression_corpus() {
+    use json_parser_with_pest::{replay_corpus, replay_input, save_repro};
+
+    let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/regressions");
+    let failures = replay_corpus(&corpus).unwrap();
+    assert!(failures.is_empty(), "{}", failures.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"));
+
+    let dir = tempdir().unwrap();
+    let case = save_repro(dir.path(), b"{\"a\": [1,", "crashes/input 1.json").unwrap();
+    assert!(case.file_name().unwrap().to_str().unwrap().starts_with("input_1-"));
+    assert_eq!(save_repro(dir.path(), b"{\"a\": [1,", "other.json").unwrap(), case);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    assert!(replay_corpus(dir.path()).unwrap().is_empty());
+    assert!(replay_input("valid", "[1, {\"a\": null, \"b\": [\"\\u00e9\"]}]").is_empty());
+}
+
+/// Tests the union, intersection and difference of arrays by element or by key.
+#[test]
+fn test_array_set_operations() {
+    use json_parser_with_pest::array_ops::{difference, intersection, union};
+
+    let mut ids = json!({ "ids": [3, 1, 2, 1] });
+    union(&mut ids, "ids", &[json!([2, 4.0, 5]), json!([5, 6])], None).unwrap();
+    assert_eq!(ids["ids"], json!([3, 1, 2, 4.0, 5, 6]));
+    let mut ids = json!([3, 1, 2, 1, 7]);
+    intersection(&mut ids, "", &[json!([1, 2, 3]), json!([1.0, 3, 9])], None).unwrap();
+    assert_eq!(ids, json!([3, 1]));
+    let mut ids = json!([3, 1, 2, 1, 7]);
+    difference(&mut ids, "", &[json!([2]), json!([7])], None).unwrap();
+    assert_eq!(ids, json!([3, 1]));
+
+    let crm = json!([{ "id": 1, "name": "ann" }, { "id": 2, "name": "bob" }, { "name": "no id" }, { "id": 1, "name": "dup" }]);
+    let billing = [json!([{ "id": 2, "plan": "pro" }, { "id": 3, "plan": "free" }, { "name": "no id" }])];
+    let mut both = crm.clone();
+    intersection(&mut both, "", &billing, Some(".id")).unwrap();
+    assert_eq!(both, json!([{ "id": 2, "name": "bob" }]));
+    let mut missing = crm.clone();
+    difference(&mut missing, "", &billing, Some(".id")).unwrap();
+    assert_eq!(missing, json!([{ "id": 1, "name": "ann" }, { "name": "no id" }]));
+    let mut all = crm.clone();
+    union(&mut all, "", &billing, Some(".id")).unwrap();
+    assert_eq!(all, json!([{ "id": 1, "name": "ann" }, { "id": 2, "name": "bob" }, { "name": "no id" }, { "id": 3, "plan": "free" }, { "name": "no id" }]));
+
+    let mut objects = json!([{ "a": 1, "b": [1, 2] }, { "c": 3 }]);
+    difference(&mut objects, "", &[json!([{ "b": [1, 2], "a": 1.0 }])], None).unwrap();
+    assert_eq!(objects, json!([{ "c": 3 }]));
+    assert!(union(&mut objects, "", &[json!({ "a": 1 })], None).is_err());
+    assert!(intersection(&mut objects, "missing", &[json!([])], None).is_err());
+    assert!(difference(&mut objects, "", &[json!([])], Some("(")).is_err());
+}
+
+/// Tests generating SQL INSERT statements from an array of objects.
+#[test]
+fn test_json_to_sql() {
+    use json_parser_with_pest::{convert_to_format, json_to_sql, SqlOptions};
+
+    let rows = json!([
+        { "id": 1, "name": "O'Brien", "active": true, "score": 9.5, "tags": ["a"] },
+        { "id": 2, "name": "C:\\path", "user": "x\"y", "score": 7 },
+        { "id": 9223372036854775808u64, "name": null }
+    ]);
+    assert_eq!(convert_to_format(&rows, "sql").unwrap(), concat!(
+        "INSERT INTO \"data\" (\"active\", \"id\", \"name\", \"score\", \"tags\", \"user\") VALUES (TRUE, 1, 'O''Brien', 9.5, '[\"a\"]', NULL);\n",
+        "INSERT INTO \"data\" (\"active\", \"id\", \"name\", \"score\", \"tags\", \"user\") VALUES (NULL, 2, 'C:\\path', 7, NULL, 'x\"y');\n",
+        "INSERT INTO \"data\" (\"active\", \"id\", \"name\", \"score\", \"tags\", \"user\") VALUES (NULL, 9223372036854775808, NULL, NULL, NULL, NULL);\n",
+    ));
+    let options = SqlOptions { table: "analytics.Users".to_string(), rows_per_insert: 2, create_table: true };
+    assert_eq!(json_to_sql(&rows, &options).unwrap(), concat!(
+        "CREATE TABLE IF NOT EXISTS \"analytics\".\"Users\" (\n",
+        "  \"active\" boolean,\n  \"id\" numeric,\n  \"name\" text,\n  \"score\" double precision,\n  \"tags\" jsonb,\n  \"user\" text\n);\n",
+        "INSERT INTO \"analytics\".\"Users\" (\"active\", \"id\", \"name\", \"score\", \"tags\", \"user\") VALUES\n",
+        "  (TRUE, 1, 'O''Brien', 9.5, '[\"a\"]', NULL),\n  (NULL, 2, 'C:\\path', 7, NULL, 'x\"y');\n",
+        "INSERT INTO \"analytics\".\"Users\" (\"active\", \"id\", \"name\", \"score\", \"tags\", \"user\") VALUES (NULL, 9223372036854775808, NULL, NULL, NULL, NULL);\n",
+    ));
+    assert_eq!(json_to_sql(&json!({ "a": "b" }), &SqlOptions::default()).unwrap(), "INSERT INTO \"data\" (\"a\") VALUES ('b');\n");
+    assert_eq!(json_to_sql(&json!([]), &SqlOptions::default()).unwrap(), "");
+    assert!(json_to_sql(&json!([1]), &SqlOptions::default()).is_err());
+    assert!(json_to_sql(&json!([{ "a": "nul\u{0}" }]), &SqlOptions::default()).is_err());
+    assert!(json_to_sql(&rows, &SqlOptions { table: "a..b".to_string(), ..SqlOptions::default() }).is_err());
+}
+
+/// Tests writing an array to an SQLite database and reading the table back.
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_sqlite_export_and_import() {
+    use json_parser_with_pest::{json_to_sqlite, sqlite_to_json, SqliteOptions};
+
+    let rows: Vec<Value> = (0..2000)
+        .map(|i| json!({
+            "id": i,
+            "name": format!("user {}", i),
+            "score": if i % 2 == 0 { json!(i) } else { json!(0.5) },
+            "active": i % 3 == 0,
+            "tags": ["a", i],
+            "mixed": if i % 5 == 0 { json!("x") } else { json!(i) },
+            "note": if i == 7 { json!("long ".repeat(3000)) } else { json!(null) },
+        }))
+        .collect();
+    let rows = Value::Array(rows);
+    let options = SqliteOptions { table: "Users \"x\"".to_string() };
+    let database = json_to_sqlite(&rows, &options).unwrap();
+    assert!(database.starts_with(b"SQLite format 3\0"));
+    assert_eq!(database.len() % 4096, 0);
+    let schema = "CREATE TABLE \"Users \"\"x\"\"\" (\"active\" BOOLEAN, \"id\" INTEGER, \"mixed\", \"name\" TEXT, \"note\" TEXT, \"score\" REAL, \"tags\" JSON)";
+    assert!(database.windows(schema.len()).any(|window| window == schema.as_bytes()));
+    assert_eq!(sqlite_to_json(&database, None).unwrap(), rows);
+    assert_eq!(sqlite_to_json(&database, Some("users \"X\"")).unwrap(), rows);
+
+    let empty = json_to_sqlite(&json!([]), &SqliteOptions::default()).unwrap();
+    assert_eq!(sqlite_to_json(&empty, Some("data")).unwrap(), json!([]));
+    let single = json_to_sqlite(&json!({ "a": null }), &SqliteOptions::default()).unwrap();
+    assert_eq!(sqlite_to_json(&single, None).unwrap(), json!([{ "a": null }]));
+    assert!(sqlite_to_json(&database, Some("missing")).is_err());
+    assert!(sqlite_to_json(b"not a database", None).is_err());
+    assert!(json_to_sqlite(&json!([1]), &SqliteOptions::default()).is_err());
+    assert!(json_to_sqlite(&json!("text"), &SqliteOptions::default()).is_err());
+}
+
+/// Tests that setting an index far past the end of an array fails instead of padding it.
+#[test]
+fn test_set_by_path_index_limit() {
+    use json_parser_with_pest::{set_by_path, ParserError, MAX_ARRAY_PADDING};
+
+    let mut json = json!({ "a": [1, 2] });
+    let error = set_by_path(&mut json, "a[4000000000]", json!(1)).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ParserError>(),
+        Some(ParserError::IndexOutOfRange { index: 4000000000, len: 2 })
+    ));
+    assert!(set_by_path(&mut json, "a[18446744073709551615]", json!(1)).is_err());
+    assert_eq!(json, json!({ "a": [1, 2] }));
+
+    set_by_path(&mut json, &format!("a[{}]", 2 + MAX_ARRAY_PADDING), json!(3)).unwrap();
+    let items = json["a"].as_array().unwrap();
+    assert_eq!(items.len(), 3 + MAX_ARRAY_PADDING);
+    assert_eq!(items[2], json!(null));
+    assert_eq!(items[2 + MAX_ARRAY_PADDING], json!(3));
+}
+
+/// Tests that unflattening rejects indexes far past the end of an array but rebuilds
+/// large arrays whose keys sort out of numeric order.
+#[test]
+fn test_unflatten_index_limit() {
+    use json_parser_with_pest::{flatten, unflatten, ParserError};
+
+    let error = unflatten(&json!({ "a[100000000000]": 1 }), ".").unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ParserError>(),
+        Some(ParserError::IndexOutOfRange { index: 100000000000, len: 0 })
+    ));
+    assert!(unflatten(&json!({ "a[0]": 1, "a[5000]": 2 }), ".").is_err());
+    assert_eq!(unflatten(&json!({ "a[3]": 1 }), ".").unwrap(), json!({ "a": [null, null, null, 1] }));
+
+    let large = json!({ "items": (0..25000).map(|i| json!({ "id": i })).collect::<Vec<_>>() });
+    assert_eq!(unflatten(&flatten(&large, "."), ".").unwrap(), large);
+}
+
+/// Tests that a CSV header with an index far past the end of its array is an error.
+#[test]
+fn test_csv_header_index_limit() {
+    use json_parser_with_pest::{csv_to_json, CsvOptions};
+
+    let error = csv_to_json("id,a[4000000000]\n1,x\n", &CsvOptions::default()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "CSV row 2: Invalid key 'a[4000000000]': Index 4000000000 is more than 1000 past the end of an array of 0 elements"
+    );
+    assert_eq!(
+        csv_to_json("a[1],a[0]\nx,y\n", &CsvOptions::default()).unwrap(),
+        json!([{ "a": ["y", "x"] }])
+    );
+}
+
+/// Tests that `.env` and properties keys with an index far past the end of an array are
+/// errors.
+#[test]
+fn test_dotenv_and_properties_index_limit() {
+    use json_parser_with_pest::{
+        dotenv_to_json, properties_to_json, BracketStyle, ParserError, PathSyntax, PropertiesOptions,
+    };
+
+    let error = dotenv_to_json("X__4000000000=1\n", &PropertiesOptions::dotenv()).unwrap_err();
+    assert!(matches!(error.downcast_ref::<ParserError>(), Some(ParserError::IndexOutOfRange { .. })));
+    let error = properties_to_json("x[4000000000]=1\n", &PropertiesOptions::java()).unwrap_err();
+    assert!(matches!(error.downcast_ref::<ParserError>(), Some(ParserError::IndexOutOfRange { .. })));
+    let dotted = PropertiesOptions {
+        syntax: PathSyntax { brackets: BracketStyle::None, ..PathSyntax::delimited(".") },
+        typed: false,
+    };
+    let error = properties_to_json("x.4000000000=1\n", &dotted).unwrap_err();
+    assert!(matches!(error.downcast_ref::<ParserError>(), Some(ParserError::IndexOutOfRange { .. })));
+    assert_eq!(
+        dotenv_to_json("X__1=b\nX__0=a\n", &PropertiesOptions::dotenv()).unwrap(),
+        json!({ "X": ["a", "b"] })
+    );
+}
+
+/// Tests that sparse query string indexes are sorted and compacted once the whole query
+/// string is read, as `qs` does.
+#[test]
+fn test_querystring_sparse_indexes() {
+    use json_parser_with_pest::{querystring_to_json, QueryStringOptions};
+
+    let options = QueryStringOptions::default();
+    assert_eq!(querystring_to_json("a[0]=x&a[5]=y&a[1]=z", &options).unwrap(), json!({ "a": ["x", "z", "y"] }));
+    assert_eq!(
+        querystring_to_json("items[3][id]=b&items[0][id]=a&items[3][n]=2", &options).unwrap(),
+        json!({ "items": [{ "id": "a" }, { "id": "b", "n": "2" }] })
+    );
+    assert_eq!(querystring_to_json("a[]=x&a[7]=y&a[]=z", &options).unwrap(), json!({ "a": ["x", "y", "z"] }));
+    assert_eq!(querystring_to_json("a=1&a=2&a[9]=3", &options).unwrap(), json!({ "a": ["1", "2", "3"] }));
+}
+
+/// Test that JSONPath slices with extreme steps stop instead of overflowing.
+#[test]
+fn test_jsonpath_slice_extreme_step() {
+    use json_parser_with_pest::query_jsonpath;
+
+    let numbers = json!([1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(
+        query_jsonpath(&numbers, "$[5:10:9223372036854775807]").expect("Query failed"),
+        vec![json!(6)],
+        "Slice with the largest step selected the wrong items."
+    );
+    assert_eq!(
+        query_jsonpath(&numbers, "$[1::-9223372036854775808]").expect("Query failed"),
+        vec![json!(2)],
+        "Slice with the smallest step selected the wrong items."
+    );
+}
+
+/// Test that a key conversion conflict leaves the document unchanged.
+#[test]
+fn test_transform_keys_conflict_keeps_document() {
+    use json_parser_with_pest::{transform_keys, KeyCase};
+
+    let original = json!({ "fooBar": 1, "foo_bar": 2, "HTTPServer": { "maxConn": 3 } });
+    let mut json = original.clone();
+    assert!(transform_keys(&mut json, KeyCase::Snake).is_err(), "Conflicting keys should fail.");
+    assert_eq!(json, original, "Failed conversion should leave the document unchanged.");
+}
+
+/// Tests that json_to_xml only writes well-formed XML names, text and a single root
+#[test]
+fn test_xml_names_and_root() {
+    use json_parser_with_pest::{json_to_xml, xml_to_json, XmlOptions};
+
+    let options = XmlOptions::default();
+    for key in ["a b", "1a", "", "a:b:c", "x:y"] {
+        assert!(json_to_xml(&json!({ key: 1 }), &options).is_err(), "Key {:?} should be rejected.", key);
+    }
+    assert!(json_to_xml(&json!({"r": {"@a b": "1"}}), &options).is_err());
+    let bad_root = XmlOptions { root: "my root".to_string(), ..XmlOptions::default() };
+    assert!(json_to_xml(&json!(1), &bad_root).is_err());
+
+    let xml = json_to_xml(&json!({"r": {"@a": "1", "b": "2"}}), &options).unwrap();
+    assert_eq!(xml, "<root>\n<r a=\"1\">\n<b>2</b>\n</r>\n</root>\n");
+    let xml = json_to_xml(&json!({"r": {"#text": "hi", "@a": "1"}}), &options).unwrap();
+    assert_eq!(xml, "<root>\n<r a=\"1\">hi</r>\n</root>\n");
+    let source = "<r a=\"1\"><b>2</b><c d=\"3\">x</c></r>";
+    let parsed = xml_to_json(source).unwrap();
+    let root = XmlOptions { root: "r".to_string(), ..XmlOptions::default() };
+    let xml = json_to_xml(&parsed["r"], &root).unwrap();
+    assert_eq!(xml_to_json(&xml).unwrap(), parsed, "xml_to_json output should convert back.");
+
+    let xml = json_to_xml(&json!({"a": "x\u{1}y", "@b": "\u{fffe}z"}), &options).unwrap();
+    assert_eq!(xml, "<root b=\"z\">\n<a>xy</a>\n</root>\n");
+
+    let xml = json_to_xml(&json!([1, {"a": 2}]), &options).unwrap();
+    assert_eq!(xml, "<root>\n<item>1</item>\n<item>\n<a>2</a>\n</item>\n</root>\n");
+    assert!(xml_to_json(&xml).is_ok(), "Root array should give a single root element.");
+}
+
+/// Test that simplification keeps duplicate oneOf branches, which reject every document.
+#[test]
+fn test_simplify_schema_keeps_one_of_duplicates() {
+    use json_parser_with_pest::simplify_schema;
+
+    let schema = json!({ "oneOf": [{ "type": "string" }, { "type": "string" }] });
+    assert_eq!(simplify_schema(&schema), schema, "Duplicate oneOf branches must be kept.");
+    let schema = json!({ "oneOf": [{ "type": ["string", "string"] }] });
+    assert_eq!(simplify_schema(&schema), json!({ "type": "string" }), "Single oneOf branch should be inlined.");
+}
+
+/// Tests that dates with out-of-range fields or wrong separators are rejected.
+#[test]
+fn test_parse_date_rejects_invalid_dates() {
+    use json_parser_with_pest::datetime::parse_date;
+    use json_parser_with_pest::{csv_to_json, CsvOptions};
+
+    for text in [
+        "2024-02-30T99:99:99Z",
+        "2024x03y01T12a00b00Z",
+        "2024-02-30T12:00:00Z",
+        "2024-03-01T24:00:00Z",
+        "2024-03-01T12:60:00Z",
+        "2024-03-01T12:00:61Z",
+        "2024-+3-01T12:00:00Z",
+        "2024-02-31",
+        "2023-02-29",
+        "2024-04-31",
+    ] {
+        assert_eq!(parse_date(text, None), None, "{} should be rejected.", text);
+    }
+    assert_eq!(parse_date("2024-02-29", None), Some(1709164800.0));
+    assert_eq!(parse_date("2000-02-29T00:00:00Z", None), Some(951782400.0));
+    assert_eq!(parse_date("1900-02-29", None), None);
+    assert_eq!(parse_date("29.02.2001", Some("%d.%m.%Y")), None);
+    assert_eq!(parse_date("29.02.2004", Some("%d.%m.%Y")), Some(1078012800.0));
+
+    let mut options = CsvOptions::default();
+    options.date_formats.insert("born".to_string(), "%d.%m.%Y".to_string());
+    let rows = csv_to_json("born\n29.02.2001\n", &options).unwrap();
+    assert_eq!(rows, json!([{"born": "29.02.2001"}]), "Invalid dates should stay text.");
+}
+
+/// Tests that redact --hash uses HMAC-SHA256 under a secret key
+#[test]
+fn test_redact_keyed_hash() {
+    use json_parser_with_pest::{redact, RedactionRules, Replacement};
+
+    let hashed = |secret: &str, hash_key: Option<&[u8]>| {
+        let mut json = json!({ "password": secret });
+        let rules = RedactionRules {
+            replacement: Replacement::Hash,
+            hash_key: hash_key.map(<[u8]>::to_vec),
+            ..RedactionRules::default()
+        };
+        redact(&mut json, &rules);
+        json["password"].clone()
+    };
+    // RFC 4231 test case 2, truncated to 128 bits.
+    assert_eq!(
+        hashed("what do ya want for nothing?", Some(b"Jefe")),
+        json!("hash:5bdcc146bf60754e6a042426089575c7")
+    );
+    let long_key = [0xaa; 131];
+    assert_eq!(
+        hashed("Test Using Larger Than Block-Size Key - Hash Key First", Some(&long_key)),
+        json!("hash:60e431591ee0b67f0d8a26aacbf5b77f")
+    );
+    assert_eq!(hashed("hunter2", None), hashed("hunter2", None));
+    assert_ne!(hashed("hunter2", None), hashed("hunter2", Some(b"key")));
+    assert_ne!(hashed("hunter2", Some(b"key")), hashed("hunter2", Some(b"other key")));
+}
+
+/// Tests that the config's sandbox section applies to saved queries, route rules and compute migrations
+#[test]
+fn test_config_sandbox() {
+    use json_parser_with_pest::migrate::{migrate_records, Migration};
+    use json_parser_with_pest::ndjson::{route_ndjson, RouteConfig};
+    use json_parser_with_pest::{Config, Sandbox};
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    let heavy = "a + a + a + a + a + a + a + a + a + a";
+    let config = Config::from_json(&json!({
+        "queries": {
+            "light": { "query": "$.items[*]", "transform": "a" },
+            "heavy": { "query": "$.items[*]", "transform": heavy }
+        },
+        "sandbox": { "max_steps": 8, "timeout_ms": 500 }
+    }))
+    .unwrap();
+    assert_eq!(
+        config.sandbox,
+        Some(Sandbox { max_steps: 8, timeout: Duration::from_millis(500), ..Sandbox::default() })
+    );
+    assert!(Config::from_json(&json!({ "sandbox": { "max_step": 8 } })).is_err());
+    let document = json!({ "items": [{ "a": 1 }] });
+    let light = config.saved_query("light").unwrap().run(&document, &BTreeMap::new()).unwrap();
+    assert_eq!(light, vec![("$['items'][0]".to_string(), json!(1))]);
+    let error = config.saved_query("heavy").unwrap().run(&document, &BTreeMap::new()).unwrap_err();
+    assert!(error.to_string().contains("step"), "Unexpected error: {}", error);
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("input.ndjson");
+    std::fs::write(&input, "{\"a\": 1}\n").unwrap();
+    let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+    let mut routes = RouteConfig::from_json(&json!({
+        "outputs": { "big": path("big.ndjson"), "bad": path("bad.ndjson") },
+        "rules": [{ "when": format!("{} > 5", heavy), "output": "big" }],
+        "quarantine": "bad"
+    }))
+    .unwrap();
+    assert_eq!(route_ndjson(&input, &routes).unwrap().invalid, 0);
+    routes.sandbox = config.sandbox;
+    let report = route_ndjson(&input, &routes).unwrap();
+    assert_eq!(report.invalid, 1);
+    let quarantined = std::fs::read_to_string(dir.path().join("bad.ndjson")).unwrap();
+    assert!(quarantined.contains("step"), "Unexpected quarantine: {}", quarantined);
+
+    let mut migration = Migration::from_json(&json!([{ "op": "compute", "path": "sum", "expr": heavy }])).unwrap();
+    let mut records = vec![json!({ "a": 1 })];
+    assert_eq!(migrate_records(&mut records, &migration).migrated, 1);
+    migration.sandbox = config.sandbox;
+    let mut records = vec![json!({ "a": 1 })];
+    let report = migrate_records(&mut records, &migration);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(records, vec![json!({ "a": 1 })]);
+}
+
+/// Tests that window aggregation skips timestamps outside the years 0000 to 9999 and
+/// rejects window sizes that would overflow, instead of wrapping around.
+#[test]
+fn test_aggregate_windows_extreme_timestamps() {
+    use json_parser_with_pest::{aggregate_windows, WindowOptions};
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("events.ndjson");
+    let output = dir.path().join("windows.ndjson");
+    std::fs::write(&input, "{\"t\": 5}\n{\"t\": 1e19}\n{\"t\": -1e19}\n{\"t\": 12}\n").unwrap();
+    let options = WindowOptions::new("t", 60);
+    assert_eq!(aggregate_windows(&input, &output, &options).unwrap(), 1);
+    let window: Value = serde_json::from_str(std::fs::read_to_string(&output).unwrap().trim()).unwrap();
+    assert_eq!(window["start"], json!(0));
+    assert_eq!(window["count"], json!(2));
+
+    assert!(aggregate_windows(&input, &output, &WindowOptions::new("t", u64::MAX)).is_err());
+    assert!(aggregate_windows(&input, &output, &WindowOptions::new("t", 1 << 62)).is_err());
+}
+
+/// Tests that examples of bounded numbers satisfy exclusive and combined bounds.
+#[test]
+fn test_example_from_schema_number_bounds() {
+    use json_parser_with_pest::example_from_schema;
+
+    let example = |schema: Value| example_from_schema(&schema).minimal;
+    assert_eq!(
+        example(json!({ "type": "number", "exclusiveMinimum": 0, "exclusiveMaximum": 1 })),
+        json!(0.5)
+    );
+    assert_eq!(example(json!({ "type": "number", "exclusiveMinimum": 0 })), json!(1.0));
+    assert_eq!(example(json!({ "type": "number", "exclusiveMaximum": -4 })), json!(-8.0));
+    assert_eq!(
+        example(json!({ "type": "number", "minimum": 2, "exclusiveMinimum": 5, "maximum": 6 })),
+        json!(6.0)
+    );
+    assert_eq!(
+        example(json!({ "type": "number", "minimum": 5, "exclusiveMinimum": 2 })),
+        json!(5.0)
+    );
+    assert_eq!(
+        example(json!({ "type": "integer", "minimum": 2, "exclusiveMinimum": 5 })),
+        json!(6)
+    );
+    assert_eq!(
+        example(json!({ "type": "number", "minimum": -3, "maximum": 3 })),
+        json!(0.0)
+    );
+}
+
+/// Tests that a lookup ignores an index built before the file was rewritten.
+#[test]
+fn test_lookup_sorted_stale_index() {
+    use json_parser_with_pest::{build_index, lookup_sorted};
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("sorted.ndjson");
+    let records = |prefix: &str| -> String {
+        (0..500)
+            .map(|id| format!("{{{}\"id\": {}}}\n", prefix, id * 2))
+            .collect()
+    };
+    std::fs::write(&input, records("")).unwrap();
+    assert_eq!(build_index(&input, "id", 64).unwrap(), 8);
+
+    std::fs::write(&input, records("\"name\": \"rewritten\", ")).unwrap();
+    let found = lookup_sorted(&input, "id", &json!(900)).unwrap();
+    assert_eq!(found, vec![json!({ "name": "rewritten", "id": 900 })]);
+
+    assert_eq!(build_index(&input, "id", 64).unwrap(), 8);
+    let found = lookup_sorted(&input, "id", &json!(900)).unwrap();
+    assert_eq!(found, vec![json!({ "name": "rewritten", "id": 900 })]);
+}
+
+/// Tests that null deletion also drops nulls inside objects the overlay adds, as in
+/// RFC 7396, while arrays are inserted as they are.
+#[test]
+fn test_merge_json_delete_nested_nulls() {
+    use json_parser_with_pest::{merge_json, ArrayMerge, MergeStrategy, NullMerge};
+
+    let strategy = MergeStrategy {
+        arrays: ArrayMerge::Replace,
+        nulls: NullMerge::Delete,
+    };
+    let mut merged = json!({});
+    merge_json(&mut merged, &json!({ "n": { "x": null } }), strategy.clone());
+    assert_eq!(merged, json!({ "n": {} }));
+
+    let mut merged = json!({ "a": "b", "c": [1] });
+    merge_json(
+        &mut merged,
+        &json!({ "a": { "b": null, "d": { "e": null, "f": 1 } }, "c": [null, { "g": null }] }),
+        strategy,
+    );
+    assert_eq!(
+        merged,
+        json!({ "a": { "d": { "f": 1 } }, "c": [null, { "g": null }] })
+    );
+}
+
+/// Tests that CSV import strips a byte order mark and rejects repeated columns and a
+/// decimal separator equal to the delimiter.
+#[test]
+fn test_csv_header_checks() {
+    use json_parser_with_pest::{csv_to_json, csv_to_json_validated, CsvOptions};
+
+    let options = CsvOptions::default();
+    let rows = csv_to_json("\u{feff}id,name\n1,Anna\n", &options).unwrap();
+    assert_eq!(rows, json!([{ "id": 1, "name": "Anna" }]));
+
+    let error = csv_to_json("id,name,id\n1,Anna,2\n", &options).unwrap_err();
+    assert!(error.to_string().contains("'id'"), "Unexpected error: {}", error);
+    assert!(csv_to_json_validated("id,id\n1,2\n", &json!({}), &options).is_err());
+
+    let european = CsvOptions {
+        decimal_separator: ',',
+        ..CsvOptions::default()
+    };
+    let error = csv_to_json("price\n\"2,5\"\n", &european).unwrap_err();
+    assert!(error.to_string().contains("delimiter"), "Unexpected error: {}", error);
+    let european = CsvOptions {
+        delimiter: ';',
+        ..european
+    };
+    assert_eq!(
+        csv_to_json("price\n2,5\n", &european).unwrap(),
+        json!([{ "price": 2.5 }])
+    );
+}
+
+/// Tests that parsing, conversion and streaming open `tracing` spans with their input
+/// sizes, by collecting them with a minimal subscriber.
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_spans() {
+    use json_parser_with_pest::{convert_to_format, for_each_record, handle_large_json, parse_json};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// The name and `bytes` field of every span opened.
+    type Opened = Arc<Mutex<Vec<(String, Option<u64>)>>>;
+    #[derive(Default)]
+    struct Spans {
+        opened: Opened,
+        next_id: AtomicU64,
+    }
+    struct Bytes(Option<u64>);
+    impl Visit for Bytes {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "bytes" {
+                self.0 = Some(value);
+            }
+        }
+        fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+    }
+    impl Subscriber for Spans {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut bytes = Bytes(None);
+            span.record(&mut bytes);
+            self.opened.lock().unwrap().push((span.metadata().name().to_string(), bytes.0));
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let dir = tempdir().unwrap();
+    let input = dir.path().join("records.json");
+    std::fs::write(&input, "{\"id\": 1}\n{\"id\": 2}\n").unwrap();
+    let subscriber = Spans::default();
+    let opened = subscriber.opened.clone();
+    tracing::subscriber::with_default(subscriber, || {
+        let json = parse_json(r#"{"id": 1}"#).unwrap();
+        convert_to_format(&json, "yaml").unwrap();
+        let mut records = 0;
+        for_each_record(&input, |_| records += 1).unwrap();
+        assert_eq!(records, 2);
+        assert_eq!(handle_large_json(&input).unwrap().count(), 2);
+    });
+
+    let opened = opened.lock().unwrap();
+    let names: Vec<&str> = opened.iter().map(|(name, _)| name.as_str()).collect();
+    for name in ["parse_json", "convert_to_format", "json_to_yaml", "for_each_record", "handle_large_json", "json_value"] {
+        assert!(names.contains(&name), "No {} span in {:?}", name, names);
+    }
+    assert!(opened.contains(&("parse_json".to_string(), Some(9))), "Unexpected spans: {:?}", opened);
+    // One span per call of `next`, including the last one that finds no more values.
+    assert_eq!(names.iter().filter(|&&name| name == "json_value").count(), 3);
+}
+
+/// Tests that the sandbox counts the values copied into arrays and objects.
+#[test]
+fn test_sandbox_counts_copies() {
+    use json_parser_with_pest::{JqFilter, Sandbox};
+    use std::time::Duration;
+
+    let sandbox = Sandbox {
+        max_steps: 1000,
+        max_memory: 64 << 10,
+        timeout: Duration::from_millis(100),
+    };
+    let record = json!({ "name": "ann" });
+    let arrays = ["[., ., ., ., ., ., ., ., ., .]"; 7].join(" | ");
+    let filter = JqFilter::compile(&arrays).unwrap();
+    assert!(sandbox
+        .run(|| filter.run(&record))
+        .unwrap_err()
+        .to_string()
+        .contains("memory"));
+
+    let objects = ["{a: ., b: ., c: ., d: ., e: ., f: ., g: ., h: ., i: ., j: .}"; 7].join(" | ");
+    let filter = JqFilter::compile(&objects).unwrap();
+    assert!(sandbox
+        .run(|| filter.run(&record))
+        .unwrap_err()
+        .to_string()
+        .contains("memory"));
+
+    // Small copies stay within the limits.
+    let filter = JqFilter::compile("[., .] | {a: ., b: .}").unwrap();
+    assert_eq!(
+        sandbox.run(|| filter.run(&record)).unwrap().unwrap(),
+        vec![json!({ "a": [record.clone(), record.clone()], "b": [record.clone(), record] })]
+    );
+}
+
+/// Tests that parse_json reads strings and array elements as serde_json does.
+#[test]
+fn test_parse_json_strings_and_arrays() {
+    use json_parser_with_pest::parse_json;
+
+    assert_eq!(
+        parse_json(r#"{"a": 1, "a": 2}"#).unwrap(),
+        json!({ "a": 2 })
+    );
+    assert_eq!(
+        parse_json(r#"[1.0, -0, 12345678901234567890, [[]], {"b": null}]"#).unwrap(),
+        serde_json::from_str::<Value>(r#"[1.0, -0, 12345678901234567890, [[]], {"b": null}]"#)
+            .unwrap()
+    );
+    assert_eq!(
+        parse_json(r#""tab\t\u00e9\ud83d\ude00""#).unwrap(),
+        json!("tab\t\u{e9}\u{1f600}")
+    );
+    assert!(parse_json(r#""\ud800""#).is_err());
+}
+
+/// Tests that the strictest schema merge accepts no document that an input rejects.
+#[test]
+fn test_merge_schemas_closed_properties() {
+    use json_parser_with_pest::validator::validate_document;
+    use json_parser_with_pest::{merge_schemas_with, ConstraintPolicy};
+
+    let closed = json!({
+        "type": "object",
+        "properties": { "id": { "type": "integer" }, "name": { "type": "string" } },
+        "additionalProperties": false
+    });
+    let open = json!({
+        "type": "object",
+        "properties": { "id": { "type": "integer" }, "email": { "type": "string" } }
+    });
+    let typed = json!({
+        "type": "object",
+        "properties": { "id": { "type": "integer" } },
+        "additionalProperties": { "type": "string", "maxLength": 3 }
+    });
+    let documents = [
+        json!({ "id": 1 }),
+        json!({ "id": 1, "name": "ann" }),
+        json!({ "id": 1, "name": "annabel" }),
+        json!({ "id": 1, "email": "a@b.c" }),
+        json!({ "id": 1, "name": 5 }),
+    ];
+    for (a, b) in [
+        (&closed, &open),
+        (&open, &closed),
+        (&closed, &typed),
+        (&open, &typed),
+    ] {
+        let merged = merge_schemas_with(&[a.clone(), b.clone()], ConstraintPolicy::Strictest);
+        for document in &documents {
+            let both = validate_document(document, a).is_valid()
+                && validate_document(document, b).is_valid();
+            assert_eq!(
+                validate_document(document, &merged).is_valid(),
+                both,
+                "Merged schema {} disagrees on {}",
+                merged,
+                document
+            );
+        }
+    }
+    let merged = merge_schemas_with(&[closed, open], ConstraintPolicy::Strictest);
+    assert_eq!(
+        merged["properties"],
+        json!({ "id": { "type": "integer" }, "name": { "type": "string" } })
+    );
+}
+
+/// Tests that examples of schemas with huge minimum sizes stay small.
+#[test]
+fn test_example_from_schema_huge_bounds() {
+    use json_parser_with_pest::example_from_schema;
+
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "items": { "type": "array", "minItems": 1_000_000_000_000u64, "items": { "type": "integer" } },
+            "text": { "type": "string", "minLength": 1_000_000_000_000u64 }
+        },
+        "required": ["items", "text"]
+    });
+    let examples = example_from_schema(&schema);
+    assert_eq!(examples.minimal["items"].as_array().unwrap().len(), 1000);
+    assert_eq!(examples.minimal["text"].as_str().unwrap().len(), 1000);
+}